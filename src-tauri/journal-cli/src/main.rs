@@ -0,0 +1,226 @@
+// Companion command line for capturing thoughts without opening the app. It
+// opens the same encrypted database with the same keychain key, so entries
+// added here show up in the app (and its syncs) like any other.
+
+use chrono::{DateTime, Local, Utc};
+use std::io::{IsTerminal, Read};
+use std::process::ExitCode;
+
+use journal_core::text::text_to_html;
+use journal_core::validation::{clean_entry, DEFAULT_MAX_BODY_MB};
+use journal_core::{insert_entry, parse_entry_date, DatabaseManager, JournalEntry};
+
+const USAGE: &str = "Usage:
+  journal-cli add [--title TITLE] [--date RFC3339] [TEXT...]
+      Adds an entry. Reads the text from stdin when TEXT is omitted or '-'.
+  journal-cli list [--today] [--limit N] [--archived]
+      Lists entries, newest first. Archived entries are left out unless
+      --archived is given.";
+
+#[derive(Debug, PartialEq)]
+enum Command {
+    Add {
+        title: Option<String>,
+        date: Option<String>,
+        /// `None` means read from stdin.
+        text: Option<String>,
+    },
+    List {
+        today: bool,
+        limit: Option<usize>,
+        archived: bool,
+    },
+}
+
+fn option_value(args: &mut impl Iterator<Item = String>, flag: &str) -> Result<String, String> {
+    args.next().ok_or_else(|| format!("{} needs a value", flag))
+}
+
+fn parse_args(args: Vec<String>) -> Result<Command, String> {
+    let mut args = args.into_iter();
+    match args.next().as_deref() {
+        Some("add") => {
+            let (mut title, mut date, mut words) = (None, None, Vec::new());
+            while let Some(arg) = args.next() {
+                match arg.as_str() {
+                    "--title" => title = Some(option_value(&mut args, "--title")?),
+                    "--date" => date = Some(option_value(&mut args, "--date")?),
+                    _ => words.push(arg),
+                }
+            }
+            let text = match words.as_slice() {
+                [] => None,
+                [dash] if dash == "-" => None,
+                _ => Some(words.join(" ")),
+            };
+            Ok(Command::Add { title, date, text })
+        }
+        Some("list") => {
+            let (mut today, mut limit, mut archived) = (false, None, false);
+            while let Some(arg) = args.next() {
+                match arg.as_str() {
+                    "--today" => today = true,
+                    "--archived" => archived = true,
+                    "--limit" => {
+                        let value = option_value(&mut args, "--limit")?;
+                        limit = Some(value.parse().map_err(|_| format!("Invalid limit '{}'", value))?);
+                    }
+                    _ => return Err(format!("Unknown option '{}'", arg)),
+                }
+            }
+            Ok(Command::List { today, limit, archived })
+        }
+        Some(other) => Err(format!("Unknown command '{}'", other)),
+        None => Err("No command given".to_string()),
+    }
+}
+
+fn read_stdin() -> Result<String, String> {
+    let mut stdin = std::io::stdin();
+    // Waiting on a terminal would look like a hang; text has to be piped in
+    if stdin.is_terminal() {
+        return Err("No text given; pass it as an argument or pipe it in".to_string());
+    }
+    let mut text = String::new();
+    stdin
+        .read_to_string(&mut text)
+        .map_err(|e| format!("Failed to read stdin: {}", e))?;
+    Ok(text)
+}
+
+/// The title and body saved for `text`, checked as the app checks what it
+/// saves. The app's own limit is a setting this doesn't read, so the default
+/// applies.
+fn entry_for(title: &str, text: &str) -> Result<(String, String), String> {
+    clean_entry(title, &text_to_html(text.trim_end()), DEFAULT_MAX_BODY_MB)
+}
+
+fn add(title: Option<String>, date: Option<String>, text: Option<String>) -> Result<i32, String> {
+    let text = match text {
+        Some(text) => text,
+        None => read_stdin()?,
+    };
+    if text.trim().is_empty() {
+        return Err("Nothing to add".to_string());
+    }
+    let created_at = match date.as_deref() {
+        Some(date) => parse_entry_date(date)?,
+        None => Utc::now().to_rfc3339(),
+    };
+    let title = title.unwrap_or_else(|| Local::now().format("%B %-d, %Y").to_string());
+    let (title, body) = entry_for(&title, &text)?;
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    insert_entry(&db.conn, &title, &body, &created_at).map_err(|e| e.to_string())
+}
+
+fn list(today: bool, limit: Option<usize>, archived: bool) -> Result<Vec<JournalEntry>, String> {
+    let day = if today { Local::now().date_naive().to_string() } else { String::new() };
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    let mut stmt = db
+        .conn
+        .prepare(&format!(
+            "SELECT {} FROM journal_entries
+             WHERE (?1 = '' OR local_date = ?1) AND (archived = 0 OR ?3)
+             ORDER BY created_ms DESC
+             LIMIT ?2",
+            JournalEntry::COLUMNS
+        ))
+        .map_err(|e| e.to_string())?;
+    let limit = limit.map_or(-1, |limit| limit as i64);
+    let entries = stmt
+        .query_map(rusqlite::params![day, limit, archived], JournalEntry::from_row)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(entries)
+}
+
+fn local_time(created_at: &str) -> String {
+    DateTime::parse_from_rfc3339(created_at)
+        .map(|date| date.with_timezone(&Local).format("%Y-%m-%d %H:%M").to_string())
+        .unwrap_or_else(|_| created_at.to_string())
+}
+
+fn run(command: Command) -> Result<(), String> {
+    match command {
+        Command::Add { title, date, text } => {
+            let id = add(title, date, text)?;
+            println!("Added entry {}", id);
+        }
+        Command::List { today, limit, archived } => {
+            for entry in list(today, limit, archived)? {
+                println!("{:>6}  {}  {}", entry.id, local_time(&entry.created_at), entry.title);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    env_logger::init();
+    let command = match parse_args(std::env::args().skip(1).collect()) {
+        Ok(command) => command,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+    match run(command) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("journal-cli: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_add() {
+        assert_eq!(
+            parse_args(args(&["add", "--title", "Morning", "slept", "well"])),
+            Ok(Command::Add {
+                title: Some("Morning".to_string()),
+                date: None,
+                text: Some("slept well".to_string()),
+            })
+        );
+        assert_eq!(
+            parse_args(args(&["add", "-"])),
+            Ok(Command::Add { title: None, date: None, text: None })
+        );
+        assert!(parse_args(args(&["add", "--date"])).is_err());
+    }
+
+    #[test]
+    fn test_entry_for() {
+        assert_eq!(
+            entry_for("Mor\u{1b}ning", "slept\u{0} well\n").unwrap(),
+            ("Morning".to_string(), "<p>slept well</p>".to_string())
+        );
+        let huge = "a".repeat(DEFAULT_MAX_BODY_MB as usize * 1024 * 1024);
+        assert!(entry_for("Morning", &huge).unwrap_err().starts_with(journal_core::validation::TOO_LARGE));
+    }
+
+    #[test]
+    fn test_parse_list() {
+        assert_eq!(
+            parse_args(args(&["list", "--today", "--limit", "5"])),
+            Ok(Command::List { today: true, limit: Some(5), archived: false })
+        );
+        assert_eq!(
+            parse_args(args(&["list", "--archived"])),
+            Ok(Command::List { today: false, limit: None, archived: true })
+        );
+        assert!(parse_args(args(&["list", "--limit", "many"])).is_err());
+        assert!(parse_args(args(&["list", "--yesterday"])).is_err());
+        assert!(parse_args(args(&[])).is_err());
+    }
+}
@@ -0,0 +1,20 @@
+use sha2::{Digest, Sha256};
+
+/// Hex SHA-256 of an attachment's bytes. Blobs are stored under it, so an
+/// identical file attached twice is only stored once.
+pub fn content_hash(data: &[u8]) -> String {
+    hex::encode(Sha256::digest(data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_content_hash() {
+        assert_eq!(
+            content_hash(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+}
@@ -0,0 +1,124 @@
+use log::{debug, warn};
+use rusqlite::Connection;
+use serde::Serialize;
+use std::sync::Mutex;
+
+use crate::timestamp_now;
+
+// A record of the things that happen to the journal as a whole (its key
+// moving or changing, the database being replaced or copied out, every entry
+// being deleted) so its owner can check nothing happened they didn't do. The
+// table refuses updates and deletes. Some events, like moving the key out of
+// a legacy key file, happen before the database can be opened; those are held
+// here until it is.
+
+static PENDING: Mutex<Vec<(String, AuditAction, String)>> = Mutex::new(Vec::new());
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    /// The key moved to a different store, e.g. from a key file into the keychain.
+    KeyMigration,
+    /// The database was re-encrypted under a new key.
+    KeyRotation,
+    /// The database was replaced by another, e.g. an import or a restored backup.
+    Import,
+    /// Entries or the whole database were copied out of the app.
+    Export,
+    /// Every entry was deleted.
+    DeleteAll,
+    /// A sealed entry was opened up for editing.
+    Unseal,
+}
+
+impl AuditAction {
+    fn as_str(self) -> &'static str {
+        match self {
+            AuditAction::KeyMigration => "key_migration",
+            AuditAction::KeyRotation => "key_rotation",
+            AuditAction::Import => "import",
+            AuditAction::Export => "export",
+            AuditAction::DeleteAll => "delete_all",
+            AuditAction::Unseal => "unseal",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditEvent {
+    pub id: i64,
+    pub occurred_at: String,
+    pub action: String,
+    pub detail: String,
+}
+
+/// Adds an event to the log in `conn`.
+pub fn record(conn: &Connection, action: AuditAction, detail: &str) -> rusqlite::Result<()> {
+    insert(conn, &timestamp_now(), action, detail)
+}
+
+fn insert(conn: &Connection, occurred_at: &str, action: AuditAction, detail: &str) -> rusqlite::Result<()> {
+    debug!("Audit: {} {}", action.as_str(), detail);
+    conn.execute(
+        "INSERT INTO audit_log (occurred_at, action, detail) VALUES (?1, ?2, ?3)",
+        rusqlite::params![occurred_at, action.as_str(), detail],
+    )?;
+    Ok(())
+}
+
+/// Holds an event that happened while the database couldn't be opened, until
+/// `flush` writes it.
+pub(crate) fn record_later(action: AuditAction, detail: &str) {
+    if let Ok(mut pending) = PENDING.lock() {
+        pending.push((timestamp_now(), action, detail.to_string()));
+    }
+}
+
+/// Writes the events held by `record_later` to the log in `conn`.
+pub(crate) fn flush(conn: &Connection) {
+    let Ok(mut pending) = PENDING.lock() else {
+        return;
+    };
+    while let Some((occurred_at, action, detail)) = pending.first() {
+        if let Err(e) = insert(conn, occurred_at, *action, detail) {
+            warn!("Could not write to the audit log: {}", e);
+            return;
+        }
+        pending.remove(0);
+    }
+}
+
+/// The log, newest first.
+pub fn audit_log(conn: &Connection) -> rusqlite::Result<Vec<AuditEvent>> {
+    conn.prepare("SELECT id, occurred_at, action, detail FROM audit_log ORDER BY id DESC")?
+        .query_map([], |row| {
+            Ok(AuditEvent {
+                id: row.get(0)?,
+                occurred_at: row.get(1)?,
+                action: row.get(2)?,
+                detail: row.get(3)?,
+            })
+        })?
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DatabaseManager;
+
+    #[test]
+    fn test_log_is_append_only() {
+        let db = DatabaseManager::open_in_memory().unwrap();
+        record(&db.conn, AuditAction::Export, "journal.md").unwrap();
+        record_later(AuditAction::KeyMigration, "journal.key");
+        flush(&db.conn);
+
+        let log = audit_log(&db.conn).unwrap();
+        let actions: Vec<&str> = log.iter().map(|event| event.action.as_str()).collect();
+        assert_eq!(actions, ["key_migration", "export"]);
+        assert!(db.conn.execute("UPDATE audit_log SET detail = ''", []).is_err());
+        assert!(db.conn.execute("DELETE FROM audit_log", []).is_err());
+        assert_eq!(audit_log(&db.conn).unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,119 @@
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+
+// SQLCipher's tunable parameters. The database file doesn't record them, so
+// every connection has to be given the same ones right after its key, and
+// changing them means rewriting the database. A profile that never set them
+// uses SQLCipher 4's defaults.
+
+const MIN_KDF_ITER: u32 = 256_000;
+const MIN_PAGE_SIZE: u32 = 512;
+const MAX_PAGE_SIZE: u32 = 65_536;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HmacAlgorithm {
+    #[serde(rename = "HMAC_SHA1")]
+    Sha1,
+    #[serde(rename = "HMAC_SHA256")]
+    Sha256,
+    #[serde(rename = "HMAC_SHA512")]
+    Sha512,
+}
+
+impl HmacAlgorithm {
+    fn pragma_value(self) -> &'static str {
+        match self {
+            HmacAlgorithm::Sha1 => "HMAC_SHA1",
+            HmacAlgorithm::Sha256 => "HMAC_SHA256",
+            HmacAlgorithm::Sha512 => "HMAC_SHA512",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CipherParams {
+    pub page_size: u32,
+    /// PBKDF2 iterations the key is stretched with; more makes guessing it
+    /// slower, and so opening the database.
+    pub kdf_iter: u32,
+    pub hmac_algorithm: HmacAlgorithm,
+}
+
+impl Default for CipherParams {
+    fn default() -> Self {
+        CipherParams {
+            page_size: 4096,
+            kdf_iter: MIN_KDF_ITER,
+            hmac_algorithm: HmacAlgorithm::Sha512,
+        }
+    }
+}
+
+impl CipherParams {
+    /// Refuses parameters SQLCipher can't use, and a work factor below its default.
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.page_size.is_power_of_two() || !(MIN_PAGE_SIZE..=MAX_PAGE_SIZE).contains(&self.page_size) {
+            return Err(format!(
+                "The page size must be a power of two from {} to {}",
+                MIN_PAGE_SIZE, MAX_PAGE_SIZE
+            ));
+        }
+        if self.kdf_iter < MIN_KDF_ITER {
+            return Err(format!("The key needs at least {} KDF iterations", MIN_KDF_ITER));
+        }
+        Ok(())
+    }
+
+    /// Gives database `schema` of `conn` these parameters. Must come right
+    /// after its key, before anything is read.
+    pub fn apply(&self, conn: &Connection, schema: &str) -> rusqlite::Result<()> {
+        conn.execute_batch(&format!(
+            "PRAGMA {schema}.cipher_page_size = {};
+             PRAGMA {schema}.kdf_iter = {};
+             PRAGMA {schema}.cipher_hmac_algorithm = {};",
+            self.page_size,
+            self.kdf_iter,
+            self.hmac_algorithm.pragma_value(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        assert!(CipherParams::default().validate().is_ok());
+        let raised = CipherParams {
+            kdf_iter: 1_000_000,
+            page_size: 8192,
+            ..CipherParams::default()
+        };
+        assert!(raised.validate().is_ok());
+        assert!(CipherParams { kdf_iter: 64_000, ..raised }.validate().is_err());
+        assert!(CipherParams { page_size: 5000, ..raised }.validate().is_err());
+        assert!(CipherParams { page_size: 256, ..raised }.validate().is_err());
+    }
+
+    #[test]
+    fn test_parameters_have_to_match() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("journal.db");
+        let params = CipherParams {
+            kdf_iter: 300_000,
+            hmac_algorithm: HmacAlgorithm::Sha256,
+            ..CipherParams::default()
+        };
+        let open = |params: CipherParams| {
+            let conn = Connection::open(&path).unwrap();
+            conn.pragma_update(None, "key", "secret").unwrap();
+            params.apply(&conn, "main").unwrap();
+            conn
+        };
+        open(params).execute_batch("CREATE TABLE t (x)").unwrap();
+        assert!(open(params).execute_batch("SELECT * FROM t").is_ok());
+        assert!(open(CipherParams::default()).execute_batch("SELECT * FROM t").is_err());
+    }
+}
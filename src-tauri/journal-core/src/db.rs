@@ -0,0 +1,883 @@
+use log::{debug, warn};
+use rusqlite::backup::StepResult;
+use serde::Serialize;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+use crate::audit::{self, AuditAction};
+use crate::cipher::CipherParams;
+use crate::keychain::{KeyProvider, KeychainManager};
+use crate::migration_history::{self, MigrationStep};
+use crate::migrations;
+use crate::profiles::{self, active_profile};
+use crate::timestamp_now;
+
+pub const DATABASE_FILE_NAME: &str = "journal.db";
+/// `JRNL`, stored in the database header by a migration. Databases that
+/// haven't been opened since then have 0.
+pub const APPLICATION_ID: i32 = 0x4A524E4C;
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+/// Pages a backup copies at a time, pausing in between so writers aren't held off.
+const BACKUP_PAGES_PER_STEP: i32 = 256;
+const BACKUP_PAUSE: Duration = Duration::from_millis(10);
+
+pub struct DatabaseManager {
+    pub conn: rusqlite::Connection,
+    /// The SQLCipher key `conn` was opened with; `None` for an unencrypted database.
+    key: Option<String>,
+    /// The SQLCipher parameters `conn` was opened with.
+    cipher: CipherParams,
+}
+
+/// Keys `conn` for SQLCipher. Must come before anything is read.
+fn unlock(conn: &rusqlite::Connection, key: &str, cipher: &CipherParams) -> rusqlite::Result<()> {
+    conn.pragma_update(None, "key", key)?;
+    cipher.apply(conn, "main")
+}
+
+/// Runs `apply` in a transaction on `conn`, committing if it succeeds and
+/// rolling back if it returns an error, so a failure part-way through never
+/// leaves the database half-modified.
+pub fn in_transaction<T, E: From<rusqlite::Error>>(
+    conn: &rusqlite::Connection,
+    apply: impl FnOnce(&rusqlite::Transaction) -> Result<T, E>,
+) -> Result<T, E> {
+    let tx = conn.unchecked_transaction()?;
+    // Dropping `tx` on an error rolls it back
+    let value = apply(&tx)?;
+    tx.commit()?;
+    Ok(value)
+}
+
+impl DatabaseManager {
+    /// Opens the open profile's journal with the key from the system keychain,
+    /// recovering from a legacy key file or data folder if needed.
+    pub fn new() -> Result<Self, ErrorResponse> {
+        debug!("Initializing database manager");
+        let profile = active_profile();
+        let db_dir = profiles::database_dir()?;
+        if profile.database_dir.is_some() {
+            // A database that was moved elsewhere is never recreated in its
+            // place, e.g. while the volume it's on isn't mounted
+            if !db_dir.is_dir() {
+                return Err(ErrorResponse {
+                    message: format!("The journal's folder {:?} isn't available", db_dir),
+                    error_type: "file_error".to_string(),
+                });
+            }
+        } else {
+            fs::create_dir_all(&db_dir).map_err(|e| ErrorResponse {
+                message: format!("Failed to create database directory: {}", e),
+                error_type: "file_error".to_string(),
+            })?;
+        }
+        let db_path = db_dir.join(DATABASE_FILE_NAME);
+        // Track whether a database already exists before we open it or copy one in
+        let mut db_exists = db_path.exists();
+        // ------------------------------------------------------------------
+        // Legacy migration: copy an existing database from the *alternate*
+        // application‑support folder (e.g. "Journal" ↔ "Journal‑dev") if the
+        // current location is empty. This prevents data loss when users move
+        // between release and dev builds.
+        // ------------------------------------------------------------------
+        if !db_path.exists() && profile.is_default() && profile.database_dir.is_none() {
+            let base = dirs::data_local_dir().ok_or_else(|| ErrorResponse {
+                message: "Could not determine application support directory".to_string(),
+                error_type: "app_support_error".to_string(),
+            })?;
+            // The folder we're NOT currently using
+            let alt_folder = if cfg!(debug_assertions) { "Journal" } else { "Journal-dev" };
+            let alt_db_path = base.join(alt_folder).join(DATABASE_FILE_NAME);
+            if alt_db_path.exists() {
+                debug!("Found legacy database at {:?}, migrating…", alt_db_path);
+                // Ensure destination directory exists (already created above, but be safe)
+                fs::create_dir_all(&db_dir).map_err(|e| ErrorResponse {
+                    message: format!("Failed to create database directory: {}", e),
+                    error_type: "file_error".to_string(),
+                })?;
+                fs::copy(&alt_db_path, &db_path).map_err(|e| ErrorResponse {
+                    message: format!("Failed to migrate legacy database: {}", e),
+                    error_type: "file_error".to_string(),
+                })?;
+                // Mark that a database now exists in the current location
+                db_exists = true;
+                migration_history::record_later(
+                    MigrationStep::LegacyDatabaseCopied,
+                    &format!("from {:?}", alt_db_path),
+                );
+            }
+        }
+        debug!("Database path: {:?}", db_path);
+        let keychain = KeychainManager::new()
+            .map_err(|e| ErrorResponse {
+                message: e.to_string(),
+                error_type: "keychain_error".to_string(),
+            })?;
+        // Ensure we have a key in the Keychain (handles legacy file migration too)
+        keychain.authorize_keychain().map_err(|e| ErrorResponse {
+            message: e.to_string(),
+            error_type: "keychain_error".to_string(),
+        })?;
+
+        // After authorization, migrate any legacy key-file and get the correct key
+        let mut encryption_key = keychain
+            .initialize_key()
+            .map_err(|e| ErrorResponse {
+                message: e.to_string(),
+                error_type: "keychain_error".to_string(),
+            })?;
+        if profile.unencrypted {
+            return Self::open_unencrypted(&db_path);
+        }
+        let cipher = profile.cipher.unwrap_or_default();
+        let conn_result = rusqlite::Connection::open(&db_path);
+        let mut conn = match conn_result {
+            Ok(c) => c,
+            Err(e) => {
+                #[cfg(debug_assertions)]
+                {
+                    warn!("Failed to open DB in dev mode: {}. Attempting to reset DB.", e);
+                    let _ = fs::remove_file(&db_path);
+                    rusqlite::Connection::open(&db_path).map_err(|e| ErrorResponse {
+                        message: format!("Failed to create new database after reset: {}", e),
+                        error_type: "database_error".to_string(),
+                    })?
+                }
+                #[cfg(not(debug_assertions))]
+                {
+                    return Err(ErrorResponse {
+                        message: format!("Failed to open database: {}", e),
+                        error_type: "database_error".to_string(),
+                    });
+                }
+            }
+        };
+        debug!("Setting database encryption key");
+        let set_key_result = unlock(&conn, &encryption_key, &cipher);
+        if let Err(e) = set_key_result {
+            #[cfg(debug_assertions)]
+            {
+                warn!(
+                    "Failed to set key in dev mode: {}. Trying to migrate legacy key before resetting DB.",
+                    e
+                );
+
+                // Assume a full reset is needed unless migration succeeds
+                let mut must_reset = true;
+
+                // 1️⃣ Try migrating any legacy on‑disk key first
+                if let Ok(Some(key_path)) = KeychainManager::detect_existing_key_file() {
+                    warn!("Attempting key migration from {:?}", key_path);
+                    if keychain.migrate_existing_key(&key_path).is_ok() {
+                        if let Ok(new_key) = keychain.get_key() {
+                            // Use the migrated key going forward
+                            encryption_key = new_key;
+
+                            // Re‑open the connection and retry with the migrated key
+                            conn = rusqlite::Connection::open(&db_path).map_err(|e| ErrorResponse {
+                                message: format!("Failed to reopen database after key migration: {}", e),
+                                error_type: "database_error".to_string(),
+                            })?;
+
+                            if unlock(&conn, &encryption_key, &cipher).is_ok() {
+                                debug!("Key migration succeeded – no data loss 🎉");
+                                must_reset = false;
+                            }
+                        }
+                    }
+                }
+
+                // 2️⃣ Last resort: wipe and recreate the DB (old behaviour)
+                if must_reset {
+                    warn!("Resetting database because it could not be opened with any key.");
+                    migration_history::record_later(MigrationStep::DatabaseReset, "it couldn't be opened with any key");
+                    let _ = fs::remove_file(&db_path);
+                    conn = rusqlite::Connection::open(&db_path).map_err(|e| ErrorResponse {
+                        message: format!("Failed to create new database after reset: {}", e),
+                        error_type: "database_error".to_string(),
+                    })?;
+                    unlock(&conn, &encryption_key, &cipher).map_err(|e| ErrorResponse {
+                        message: format!("Failed to set key after reset: {}", e),
+                        error_type: "database_error".to_string(),
+                    })?;
+                }
+            }
+            #[cfg(not(debug_assertions))]
+            {
+                return Err(ErrorResponse {
+                    message: format!("Failed to set database encryption key: {}", e),
+                    error_type: "database_error".to_string(),
+                });
+            }
+        }
+        if db_exists {
+            debug!("Checking for journal_entries table in existing database");
+            let schema_missing_or_error = {
+                let check = conn.prepare("SELECT name FROM sqlite_master WHERE type='table' AND name='journal_entries'");
+                match check {
+                    Ok(mut stmt) => {
+                        let mut rows = stmt.query([]).map_err(|e| ErrorResponse {
+                            message: format!("Failed to query schema: {}", e),
+                            error_type: "database_error".to_string(),
+                        })?;
+                        rows.next()?.is_none()
+                    }
+                    Err(_) => true
+                }
+            };
+            if schema_missing_or_error {
+                #[cfg(debug_assertions)]
+                {
+                    warn!(
+                        "Schema not found or unreadable – possible key mismatch. \
+                         Attempting last‑chance key migration before wiping."
+                    );
+
+                    // Flag to decide whether we really need to reset the DB
+                    let mut recovered = false;
+
+                    // 👉 Try migrating any stray on‑disk key (if one still exists)
+                    if let Ok(Some(key_path)) = KeychainManager::detect_existing_key_file() {
+                        warn!("Attempting key migration from {:?}", key_path);
+                        if keychain.migrate_existing_key(&key_path).is_ok() {
+                            if let Ok(new_key) = keychain.get_key() {
+                                // Use the migrated key from now on
+                                encryption_key = new_key;
+
+                                // Re‑open the connection with the migrated key
+                                if let Ok(c) = rusqlite::Connection::open(&db_path) {
+                                    if unlock(&c, &encryption_key, &cipher).is_ok() {
+                                        // Quick sanity‑check: does the expected table exist now?
+                                        let table_ok = c
+                                            .query_row(
+                                                "SELECT 1 FROM sqlite_master \
+                                                 WHERE type='table' AND name='journal_entries' \
+                                                 LIMIT 1",
+                                                [],
+                                                |_| Ok::<_, rusqlite::Error>(()),
+                                            )
+                                            .is_ok();
+
+                                        if table_ok {
+                                            debug!(
+                                                "Key migration succeeded – keeping existing \
+                                                 database intact 🎉"
+                                            );
+                                            conn = c;
+                                            recovered = true;
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+
+                    // ❌ Migration failed – fall back to the original dev‑mode reset
+                    if !recovered {
+                        warn!("Resetting database because it could not be opened with any key.");
+                        migration_history::record_later(MigrationStep::DatabaseReset, "its schema couldn't be read with any key");
+                        let _ = fs::remove_file(&db_path);
+                        conn = rusqlite::Connection::open(&db_path).map_err(|e| ErrorResponse {
+                            message: format!("Failed to create new database after reset: {}", e),
+                            error_type: "database_error".to_string(),
+                        })?;
+                        unlock(&conn, &encryption_key, &cipher).map_err(|e| ErrorResponse {
+                            message: format!("Failed to set key after reset: {}", e),
+                            error_type: "database_error".to_string(),
+                        })?;
+                        debug!("Creating database schema after reset");
+                        create_base_schema(&conn).map_err(|e| ErrorResponse {
+                            message: format!("Failed to create database schema after reset: {}", e),
+                            error_type: "database_error".to_string(),
+                        })?;
+                    }
+                }
+                #[cfg(not(debug_assertions))]
+                {
+                    return Err(ErrorResponse {
+                        message: "Database exists but schema is missing or corrupt. Please reset or migrate your database.".to_string(),
+                        error_type: "database_error".to_string(),
+                    });
+                }
+            }
+        } else {
+            debug!("Creating database schema");
+            create_base_schema(&conn).map_err(|e| ErrorResponse {
+                message: format!("Failed to create database schema: {}", e),
+                error_type: "database_error".to_string(),
+            })?;
+        }
+        let db = Self::prepare(conn, Some(encryption_key), cipher)?;
+        audit::flush(&db.conn);
+        migration_history::flush(&db.conn);
+        Ok(db)
+    }
+
+    /// Opens the database at `path`, creating it if needed, encrypted with the
+    /// key from `keys`. Unlike `new` it never resets or migrates anything on a
+    /// key mismatch; it just fails.
+    pub fn open(path: &Path, keys: &dyn KeyProvider) -> Result<Self, ErrorResponse> {
+        Self::open_with_cipher(path, keys, CipherParams::default())
+    }
+
+    /// Like `open`, for a database written with other SQLCipher parameters.
+    pub fn open_with_cipher(path: &Path, keys: &dyn KeyProvider, cipher: CipherParams) -> Result<Self, ErrorResponse> {
+        debug!("Opening database at {:?}", path);
+        let key = keys.database_key().map_err(|e| ErrorResponse {
+            message: e.to_string(),
+            error_type: "keychain_error".to_string(),
+        })?;
+        let conn = rusqlite::Connection::open(path)?;
+        unlock(&conn, &key, &cipher)?;
+        create_base_schema(&conn)?;
+        Self::prepare(conn, Some(key), cipher)
+    }
+
+    /// Opens the plain SQLite database at `path`, creating it if needed.
+    fn open_unencrypted(path: &Path) -> Result<Self, ErrorResponse> {
+        debug!("Opening unencrypted database at {:?}", path);
+        let conn = rusqlite::Connection::open(path)?;
+        create_base_schema(&conn)?;
+        let db = Self::prepare(conn, None, CipherParams::default())?;
+        audit::flush(&db.conn);
+        migration_history::flush(&db.conn);
+        Ok(db)
+    }
+
+    /// The schema version of the database at `path`, read without migrating
+    /// or otherwise writing to it, so a journal that fails to open can still
+    /// be looked at. `key` is `None` for an unencrypted database.
+    pub fn stored_schema_version(path: &Path, key: Option<&str>, cipher: &CipherParams) -> Result<i64, ErrorResponse> {
+        let conn = rusqlite::Connection::open_with_flags(path, rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+        if let Some(key) = key {
+            unlock(&conn, key, cipher)?;
+        }
+        // Fails with "file is not a database" when the key is wrong
+        conn.query_row("SELECT COUNT(*) FROM sqlite_master", [], |row| row.get::<_, i64>(0))?;
+        Ok(conn.query_row("PRAGMA user_version", [], |row| row.get(0))?)
+    }
+
+    /// A fresh, unencrypted database that lives only as long as the manager.
+    pub fn open_in_memory() -> Result<Self, ErrorResponse> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+        create_base_schema(&conn)?;
+        Self::prepare(conn, None, CipherParams::default())
+    }
+
+    fn prepare(conn: rusqlite::Connection, key: Option<String>, cipher: CipherParams) -> Result<Self, ErrorResponse> {
+        // Enforce ON DELETE CASCADE for the tables that hang off journal_entries
+        conn.pragma_update(None, "foreign_keys", true)?;
+        // Several windows save through their own connections, so wait out a
+        // concurrent writer instead of failing with SQLITE_BUSY
+        conn.busy_timeout(BUSY_TIMEOUT)?;
+        migrations::run(&conn)?;
+        Ok(Self { conn, key, cipher })
+    }
+
+    /// Runs `apply` in a transaction, as `in_transaction` does.
+    pub fn in_transaction<T, E: From<rusqlite::Error>>(
+        &self,
+        apply: impl FnOnce(&rusqlite::Transaction) -> Result<T, E>,
+    ) -> Result<T, E> {
+        in_transaction(&self.conn, apply)
+    }
+
+    pub fn is_encrypted(&self) -> bool {
+        self.key.is_some()
+    }
+
+    /// The SQLCipher build the database is encrypted with: its crypto
+    /// provider (e.g. `openssl`) and version. `None` for plain SQLite.
+    pub fn cipher(&self) -> Result<Option<(String, String)>, ErrorResponse> {
+        if !self.is_encrypted() {
+            return Ok(None);
+        }
+        let pragma = |name: &str| {
+            self.conn
+                .query_row(&format!("PRAGMA {}", name), [], |row| row.get::<_, String>(0))
+        };
+        Ok(Some((pragma("cipher_provider")?, pragma("cipher_version")?)))
+    }
+
+    /// When the database was last re-encrypted under a new key; `None` if it
+    /// still has the key it was created with.
+    pub fn last_key_rotation(&self) -> Result<Option<String>, ErrorResponse> {
+        Ok(self
+            .conn
+            .query_row("SELECT MAX(rotated_at) FROM key_rotations", [], |row| row.get(0))?)
+    }
+
+    pub fn export_database(&self, export_path: &PathBuf) -> Result<(), ErrorResponse> {
+        debug!("Exporting database to {:?}", export_path);
+        fs::copy(self.conn.path().unwrap(), export_path)
+            .map_err(|e| ErrorResponse {
+                message: format!("Failed to export database: {}", e),
+                error_type: "file_error".to_string(),
+            })?;
+        Ok(())
+    }
+
+    /// Writes a consistent copy of the live database to `path` with SQLite's
+    /// online backup API. SQLCipher requires both sides to share a key, so the
+    /// copy is encrypted exactly like the original.
+    pub fn backup_to(&self, path: &Path) -> Result<(), ErrorResponse> {
+        self.backup_with_progress(path, &mut |_, _| true)
+    }
+
+    /// `backup_to`, calling `progress` with the pages copied so far and the
+    /// total after each step. Returning false stops the backup, and the
+    /// partial copy is removed.
+    pub fn backup_with_progress(
+        &self,
+        path: &Path,
+        progress: &mut dyn FnMut(u64, u64) -> bool,
+    ) -> Result<(), ErrorResponse> {
+        debug!("Backing up database to {:?}", path);
+        if path.exists() {
+            fs::remove_file(path).map_err(|e| ErrorResponse {
+                message: format!("Failed to replace existing backup: {}", e),
+                error_type: "file_error".to_string(),
+            })?;
+        }
+        let mut dest = rusqlite::Connection::open(path)?;
+        if let Some(key) = &self.key {
+            unlock(&dest, key, &self.cipher)?;
+        }
+        let finished = {
+            let backup = rusqlite::backup::Backup::new(&self.conn, &mut dest)?;
+            loop {
+                if backup.step(BACKUP_PAGES_PER_STEP)? == StepResult::Done {
+                    break true;
+                }
+                let copied = backup.progress();
+                if !progress((copied.pagecount - copied.remaining) as u64, copied.pagecount as u64) {
+                    break false;
+                }
+                thread::sleep(BACKUP_PAUSE);
+            }
+        };
+        if !finished {
+            drop(dest);
+            let _ = fs::remove_file(path);
+            return Err(ErrorResponse {
+                message: "Backup cancelled".to_string(),
+                error_type: "cancelled".to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Backs the database up to `path` and checks that the copy opens with the
+    /// same key, passes an integrity check and holds the same entries.
+    fn copy_verified(&self, path: &Path) -> Result<(), ErrorResponse> {
+        self.backup_to(path)?;
+        self.verify_copy(path, self.key.as_deref(), &self.cipher)
+    }
+
+    /// Checks that the copy at `path` opens with `key` and `cipher`, passes an
+    /// integrity check and holds the same entries as this database.
+    fn verify_copy(&self, path: &Path, key: Option<&str>, cipher: &CipherParams) -> Result<(), ErrorResponse> {
+        let copy = rusqlite::Connection::open(path)?;
+        if let Some(key) = key {
+            unlock(&copy, key, cipher)?;
+        }
+        let integrity: String = copy.query_row("PRAGMA integrity_check", [], |row| row.get(0))?;
+        let count_entries = |conn: &rusqlite::Connection| {
+            conn.query_row("SELECT COUNT(*) FROM journal_entries", [], |row| row.get::<_, i64>(0))
+        };
+        if integrity != "ok" || count_entries(&copy)? != count_entries(&self.conn)? {
+            return Err(ErrorResponse {
+                message: format!("The copy of the journal at {:?} didn't match the original", path),
+                error_type: "file_error".to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Moves the open profile's database into `dir`, e.g. onto an external
+    /// encrypted volume. Other connections are kept from writing while it's
+    /// copied, and the profile only switches to the copy once it has been
+    /// verified; the original is removed after that. Returns the new path.
+    pub fn relocate(dir: &Path) -> Result<PathBuf, ErrorResponse> {
+        if !dir.is_dir() {
+            return Err(ErrorResponse {
+                message: format!("{:?} isn't a folder", dir),
+                error_type: "file_error".to_string(),
+            });
+        }
+        let source = profiles::database_path()?;
+        let target = dir.join(DATABASE_FILE_NAME);
+        if target.canonicalize().ok() == source.canonicalize().ok() {
+            return Ok(source);
+        }
+        if target.exists() {
+            return Err(ErrorResponse {
+                message: format!("{:?} already holds a journal", dir),
+                error_type: "file_error".to_string(),
+            });
+        }
+        let db = Self::new()?;
+        // Holding a write transaction on a second connection keeps writers out
+        // while still letting the backup read; a connection can't back up
+        // while it's writing itself
+        let writer_lock = Self::new()?;
+        writer_lock.conn.execute_batch("BEGIN IMMEDIATE")?;
+        let result = db.copy_verified(&target).and_then(|()| profiles::set_database_dir(dir));
+        let _ = writer_lock.conn.execute_batch("ROLLBACK");
+        drop((db, writer_lock));
+        if let Err(e) = result {
+            let _ = fs::remove_file(&target);
+            return Err(e);
+        }
+        debug!("Moved database from {:?} to {:?}", source, target);
+        if let Err(e) = fs::remove_file(&source) {
+            warn!("Failed to remove the database left at {:?}: {}", source, e);
+        }
+        Ok(target)
+    }
+
+    /// Writes a verified copy of the database to `path`, encrypted with `key`
+    /// and `cipher`, or as plain SQLite when there's no key.
+    fn convert_to(&self, path: &Path, key: Option<&str>, cipher: &CipherParams) -> Result<(), ErrorResponse> {
+        self.conn.execute(
+            "ATTACH DATABASE ?1 AS converted KEY ?2",
+            rusqlite::params![path.to_string_lossy(), key.unwrap_or("")],
+        )?;
+        let result = match key {
+            Some(_) => cipher.apply(&self.conn, "converted"),
+            None => Ok(()),
+        }
+        .and_then(|()| self.conn.query_row("SELECT sqlcipher_export('converted')", [], |_| Ok(())))
+        .and_then(|()| {
+            // The export copies the schema but not how many migrations it has had
+            let version: i64 = self.conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+            self.conn.execute_batch(&format!("PRAGMA converted.user_version = {}", version))
+        });
+        self.conn.execute_batch("DETACH DATABASE converted")?;
+        result?;
+        self.verify_copy(path, key, cipher)
+    }
+
+    /// Converts the open profile's database to plain SQLite or back, for a
+    /// journal kept on a volume that's encrypted already. The key stays in the
+    /// keychain either way, so the journal still locks, and is the one
+    /// encrypting again uses, along with the SQLCipher parameters it had.
+    pub fn set_encrypted(encrypted: bool) -> Result<(), ErrorResponse> {
+        let cipher = active_profile().cipher.unwrap_or_default();
+        let detail = if encrypted { "encrypted the database" } else { "stopped encrypting the database" };
+        Self::rewrite(!encrypted, cipher, detail)
+    }
+
+    /// Re-encrypts the open profile's database with new SQLCipher parameters,
+    /// e.g. to raise its KDF work factor.
+    pub fn set_cipher_params(cipher: CipherParams) -> Result<(), ErrorResponse> {
+        cipher.validate()?;
+        if active_profile().unencrypted {
+            return Err("The journal isn't encrypted".to_string().into());
+        }
+        let detail = format!(
+            "changed the cipher to {} byte pages, {} KDF iterations",
+            cipher.page_size, cipher.kdf_iter
+        );
+        Self::rewrite(false, cipher, &detail)
+    }
+
+    /// Rewrites the open profile's database as plain SQLite when `unencrypted`,
+    /// or encrypted with `cipher`, unless it's that already. Other connections
+    /// are kept from writing while the new copy is written next to the
+    /// database; it replaces the database once it has been verified. The
+    /// change is recorded as a key rotation.
+    fn rewrite(unencrypted: bool, cipher: CipherParams, detail: &str) -> Result<(), ErrorResponse> {
+        let before = active_profile();
+        let db = Self::new()?;
+        let current = Some(db.cipher).filter(|_| db.is_encrypted());
+        if current == Some(cipher).filter(|_| !unencrypted) {
+            return Ok(());
+        }
+        let key = match (&db.key, unencrypted) {
+            (_, true) => None,
+            (Some(key), false) => Some(key.clone()),
+            (None, false) => Some(KeychainManager::new().and_then(|keychain| keychain.get_key()).map_err(
+                |e| ErrorResponse {
+                    message: e.to_string(),
+                    error_type: "keychain_error".to_string(),
+                },
+            )?),
+        };
+        let path = profiles::database_path()?;
+        let converted = path.with_extension("db.converting");
+        let _ = fs::remove_file(&converted);
+        let writer_lock = Self::new()?;
+        writer_lock.conn.execute_batch("BEGIN IMMEDIATE")?;
+        let result = db.convert_to(&converted, key.as_deref(), &cipher);
+        let _ = writer_lock.conn.execute_batch("ROLLBACK");
+        drop((db, writer_lock));
+        let result = result.and_then(|()| profiles::set_encryption(unencrypted, cipher)).and_then(|_| {
+            fs::rename(&converted, &path).map_err(|e| {
+                let _ = profiles::set_encryption(before.unencrypted, before.cipher.unwrap_or_default());
+                ErrorResponse {
+                    message: format!("Failed to replace the database: {}", e),
+                    error_type: "file_error".to_string(),
+                }
+            })
+        });
+        if let Err(e) = result {
+            let _ = fs::remove_file(&converted);
+            return Err(e);
+        }
+        let db = Self::new()?;
+        db.conn.execute("INSERT INTO key_rotations (rotated_at) VALUES (?1)", [timestamp_now()])?;
+        audit::record(&db.conn, AuditAction::KeyRotation, detail)?;
+        Ok(())
+    }
+
+    pub fn import_database(&self, import_path: &PathBuf) -> Result<(), ErrorResponse> {
+        debug!("Importing database from {:?}", import_path);
+        fs::copy(import_path, self.conn.path().unwrap())
+            .map_err(|e| ErrorResponse {
+                message: format!("Failed to import database: {}", e),
+                error_type: "file_error".to_string(),
+            })?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ErrorResponse {
+    pub message: String,
+    pub error_type: String,
+}
+
+impl From<String> for ErrorResponse {
+    fn from(error: String) -> Self {
+        ErrorResponse { 
+            message: error,
+            error_type: "unknown_error".to_string() 
+        }
+    }
+}
+
+impl From<rusqlite::Error> for ErrorResponse {
+    fn from(error: rusqlite::Error) -> Self {
+        ErrorResponse { 
+            message: error.to_string(),
+            error_type: "database_error".to_string() 
+        }
+    }
+}
+
+impl fmt::Display for ErrorResponse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.error_type, self.message)
+    }
+}
+
+/// The original `journal_entries` table that every migration builds on.
+pub(crate) fn create_base_schema(conn: &rusqlite::Connection) -> rusqlite::Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS journal_entries (
+            id INTEGER PRIMARY KEY,
+            title TEXT NOT NULL,
+            body TEXT NOT NULL,
+            created_at TEXT NOT NULL
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+pub fn app_support_dir() -> Result<PathBuf, ErrorResponse> {
+    let base = dirs::data_local_dir().ok_or_else(|| ErrorResponse {
+        message: "Could not determine application support directory".to_string(),
+        error_type: "app_support_error".to_string(),
+    })?;
+    
+    let folder_name = if cfg!(debug_assertions) {
+        "Journal-dev"
+    } else {
+        "Journal"
+    };
+    
+    Ok(base.join(folder_name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keychain::StaticKey;
+    use crate::{insert_entry, timestamp_now};
+
+    fn key(key: &str) -> StaticKey {
+        StaticKey(key.to_string())
+    }
+
+    fn count_entries(db: &DatabaseManager) -> i64 {
+        db.conn
+            .query_row("SELECT COUNT(*) FROM journal_entries", [], |row| row.get(0))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_open_reopens_with_the_same_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("journal.db");
+        let db = DatabaseManager::open(&path, &key("secret")).unwrap();
+        insert_entry(&db.conn, "First", "<p>Hello</p>", &timestamp_now()).unwrap();
+        drop(db);
+
+        let db = DatabaseManager::open(&path, &key("secret")).unwrap();
+        assert_eq!(count_entries(&db), 1);
+        drop(db);
+        assert!(DatabaseManager::open(&path, &key("wrong")).is_err());
+    }
+
+    #[test]
+    fn test_stored_schema_version() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("journal.db");
+        drop(DatabaseManager::open(&path, &key("secret")).unwrap());
+        let cipher = CipherParams::default();
+        assert_eq!(
+            DatabaseManager::stored_schema_version(&path, Some("secret"), &cipher).unwrap(),
+            crate::SCHEMA_VERSION
+        );
+        assert!(DatabaseManager::stored_schema_version(&path, Some("wrong"), &cipher).is_err());
+        assert!(DatabaseManager::stored_schema_version(&dir.path().join("missing.db"), None, &cipher).is_err());
+    }
+
+    #[test]
+    fn test_in_transaction_rolls_back_on_error() {
+        let db = DatabaseManager::open_in_memory().unwrap();
+        let failed: Result<(), ErrorResponse> = db.in_transaction(|tx| {
+            insert_entry(tx, "First", "", &timestamp_now())?;
+            Err("Stopped part-way".to_string().into())
+        });
+        assert_eq!(failed.unwrap_err().message, "Stopped part-way");
+        assert_eq!(count_entries(&db), 0);
+
+        let id = db.in_transaction(|tx| insert_entry(tx, "Second", "", &timestamp_now())).unwrap();
+        assert!(id > 0);
+        assert_eq!(count_entries(&db), 1);
+    }
+
+    #[test]
+    fn test_cipher_and_key_rotation() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = DatabaseManager::open(&dir.path().join("journal.db"), &key("secret")).unwrap();
+        let (provider, version) = db.cipher().unwrap().unwrap();
+        assert!(!provider.is_empty());
+        assert!(!version.is_empty());
+        assert_eq!(db.last_key_rotation().unwrap(), None);
+    }
+
+    #[test]
+    fn test_convert_to_plain_sqlite_and_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = DatabaseManager::open(&dir.path().join("journal.db"), &key("secret")).unwrap();
+        insert_entry(&db.conn, "First", "<p>Hello</p>", &timestamp_now()).unwrap();
+        audit::record(&db.conn, AuditAction::Export, "journal.md").unwrap();
+
+        let plain_path = dir.path().join("plain.db");
+        db.convert_to(&plain_path, None, &CipherParams::default()).unwrap();
+        let plain = DatabaseManager::open_unencrypted(&plain_path).unwrap();
+        assert!(!plain.is_encrypted());
+        assert_eq!(plain.cipher().unwrap(), None);
+        assert_eq!(count_entries(&plain), 1);
+        assert_eq!(audit::audit_log(&plain.conn).unwrap().len(), 1);
+        assert!(plain.conn.execute("DELETE FROM audit_log", []).is_err());
+
+        let encrypted_path = dir.path().join("encrypted.db");
+        let raised = CipherParams {
+            kdf_iter: 300_000,
+            ..CipherParams::default()
+        };
+        plain.convert_to(&encrypted_path, Some("other"), &raised).unwrap();
+        let encrypted = DatabaseManager::open_with_cipher(&encrypted_path, &key("other"), raised).unwrap();
+        assert_eq!(count_entries(&encrypted), 1);
+        assert!(DatabaseManager::open(&encrypted_path, &key("other")).is_err());
+        assert!(DatabaseManager::open_with_cipher(&encrypted_path, &key("secret"), raised).is_err());
+
+        // A copy keeps the parameters it was made with
+        let backup = dir.path().join("backup.db");
+        encrypted.backup_to(&backup).unwrap();
+        assert_eq!(count_entries(&DatabaseManager::open_with_cipher(&backup, &key("other"), raised).unwrap()), 1);
+    }
+
+    #[test]
+    fn test_backup_is_encrypted_with_the_same_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = DatabaseManager::open(&dir.path().join("journal.db"), &key("secret")).unwrap();
+        insert_entry(&db.conn, "First", "<p>Hello</p>", &timestamp_now()).unwrap();
+        let backup = dir.path().join("backup.db");
+        db.backup_to(&backup).unwrap();
+
+        assert_eq!(count_entries(&DatabaseManager::open(&backup, &key("secret")).unwrap()), 1);
+        assert!(DatabaseManager::open(&backup, &key("other")).is_err());
+    }
+
+    #[test]
+    fn test_backup_reports_progress_and_can_be_stopped() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = DatabaseManager::open(&dir.path().join("journal.db"), &key("secret")).unwrap();
+        let body = format!("<p>{}</p>", "words ".repeat(2000));
+        for _ in 0..100 {
+            insert_entry(&db.conn, "Entry", &body, &timestamp_now()).unwrap();
+        }
+        let backup = dir.path().join("backup.db");
+        let mut steps = Vec::new();
+        db.backup_with_progress(&backup, &mut |copied, total| {
+            steps.push((copied, total));
+            true
+        })
+        .unwrap();
+        assert!(!steps.is_empty());
+        assert!(steps.windows(2).all(|pair| pair[0].0 < pair[1].0));
+        assert_eq!(count_entries(&DatabaseManager::open(&backup, &key("secret")).unwrap()), 100);
+
+        let error = db.backup_with_progress(&backup, &mut |_, _| false).err().unwrap();
+        assert_eq!(error.error_type, "cancelled");
+        assert!(!backup.exists());
+    }
+
+    #[test]
+    fn test_copy_verified_while_writers_are_held_off() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("journal.db");
+        let db = DatabaseManager::open(&path, &key("secret")).unwrap();
+        insert_entry(&db.conn, "First", "<p>Hello</p>", &timestamp_now()).unwrap();
+        let writer_lock = DatabaseManager::open(&path, &key("secret")).unwrap();
+        writer_lock.conn.execute_batch("BEGIN IMMEDIATE").unwrap();
+        let volume = tempfile::tempdir().unwrap();
+        let copy = volume.path().join("journal.db");
+        db.copy_verified(&copy).unwrap();
+        writer_lock.conn.execute_batch("ROLLBACK").unwrap();
+
+        assert_eq!(count_entries(&DatabaseManager::open(&copy, &key("secret")).unwrap()), 1);
+    }
+
+    #[test]
+    fn test_second_connection_waits_for_writer() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("journal.db");
+        let writer = DatabaseManager::open(&path, &key("secret")).unwrap();
+        let other = DatabaseManager::open(&path, &key("secret")).unwrap();
+        writer.conn.execute_batch("BEGIN IMMEDIATE").unwrap();
+        insert_entry(&writer.conn, "First", "", &timestamp_now()).unwrap();
+        let handle = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(100));
+            writer.conn.execute_batch("COMMIT").unwrap();
+        });
+        insert_entry(&other.conn, "Second", "", &timestamp_now()).unwrap();
+        handle.join().unwrap();
+        assert_eq!(count_entries(&other), 2);
+    }
+
+    #[test]
+    fn test_open_in_memory_starts_empty() {
+        let db = DatabaseManager::open_in_memory().unwrap();
+        assert_eq!(count_entries(&db), 0);
+    }
+}
@@ -0,0 +1,178 @@
+use chrono::{SecondsFormat, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::links::update_links;
+use crate::people::update_people;
+use crate::sentiment::body_sentiment;
+use crate::text::body_word_count;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub id: i32,
+    pub title: String,
+    pub created_at: String,
+    pub word_count: i64,
+    pub notebook_id: Option<i32>,
+    pub archived: bool,
+    /// The day the entry was written on where it was written, as `YYYY-MM-DD`.
+    /// Lists, streaks and "on this day" go by it rather than the UTC date.
+    pub local_date: Option<String>,
+}
+
+impl JournalEntry {
+    /// Column list matching `from_row`, for any query that returns entry summaries.
+    pub const COLUMNS: &'static str = "id, title, created_at, word_count, notebook_id, archived, local_date";
+
+    pub fn from_row(row: &rusqlite::Row) -> rusqlite::Result<Self> {
+        Ok(JournalEntry {
+            id: row.get(0)?,
+            title: row.get(1)?,
+            created_at: row.get(2)?,
+            word_count: row.get(3)?,
+            notebook_id: row.get(4)?,
+            archived: row.get(5)?,
+            local_date: row.get(6)?,
+        })
+    }
+}
+
+/// The current time in the same format the schema triggers write.
+pub fn timestamp_now() -> String {
+    Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+/// Validates a user-supplied RFC3339 timestamp, whatever offset it was given in.
+pub fn parse_entry_datetime(date: &str) -> Result<chrono::DateTime<Utc>, String> {
+    chrono::DateTime::parse_from_rfc3339(date.trim())
+        .map(|d| d.with_timezone(&Utc))
+        .map_err(|e| format!("Invalid entry date '{}': {}", date, e))
+}
+
+/// Like `parse_entry_datetime`, normalized to the UTC string form entries are stored in
+/// so they stay consistently ordered.
+pub fn parse_entry_date(date: &str) -> Result<String, String> {
+    parse_entry_datetime(date).map(|d| d.to_rfc3339())
+}
+
+/// The day `created_at` falls on in this machine's time zone, as stored in
+/// `local_date` when an entry is written or moved to another date. Entries
+/// keep it when read elsewhere, so one written in the evening in California
+/// stays on that day in a list shown in Lisbon.
+pub fn local_date_for(created_at: &str) -> Option<String> {
+    let created_at = parse_entry_datetime(created_at).ok()?;
+    Some(created_at.with_timezone(&chrono::Local).date_naive().to_string())
+}
+
+/// `created_at` as Unix epoch milliseconds, stored in `created_ms` so entries
+/// sort and filter by instant whatever offset their RFC3339 string was written
+/// with. `created_at` stays the form the API hands out.
+pub fn created_ms_for(created_at: &str) -> Option<i64> {
+    parse_entry_datetime(created_at).ok().map(|d| d.timestamp_millis())
+}
+
+/// UTC bounds of a local calendar day, as RFC3339 strings that compare with
+/// stored `created_at` values through `julianday`. The end is exclusive.
+pub fn local_day_bounds(day: chrono::NaiveDate) -> (String, String) {
+    let midnight = day.and_hms_opt(0, 0, 0).unwrap_or_default();
+    let start = chrono::Local
+        .from_local_datetime(&midnight)
+        .earliest()
+        .map(|start| start.with_timezone(&Utc))
+        .unwrap_or_else(Utc::now);
+    (start.to_rfc3339(), (start + chrono::Duration::days(1)).to_rfc3339())
+}
+
+/// Inserts an entry and returns its id. Every path that creates entries goes
+/// through here so derived columns like `word_count` and `sentiment` stay in sync.
+pub fn insert_entry(
+    conn: &rusqlite::Connection,
+    title: &str,
+    body: &str,
+    created_at: &str,
+) -> rusqlite::Result<i32> {
+    conn.execute(
+        "INSERT INTO journal_entries (title, body, created_at, word_count, sentiment, uuid, updated_at, local_date,
+                                      created_ms)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+        rusqlite::params![
+            title,
+            body,
+            created_at,
+            body_word_count(body),
+            body_sentiment(body),
+            uuid::Uuid::new_v4().to_string(),
+            timestamp_now(),
+            local_date_for(created_at),
+            created_ms_for(created_at),
+        ],
+    )?;
+    let id = conn.last_insert_rowid() as i32;
+    update_links(conn, id, body)?;
+    update_people(conn, id, body)?;
+    Ok(id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DatabaseManager;
+
+    #[test]
+    fn test_parse_entry_date_normalizes_to_utc() {
+        assert_eq!(
+            parse_entry_date(" 2024-03-01T09:30:00+02:00 ").unwrap(),
+            "2024-03-01T07:30:00+00:00"
+        );
+        assert!(parse_entry_date("March 1st").is_err());
+    }
+
+    #[test]
+    fn test_local_day_bounds_span_one_day() {
+        let (start, end) = local_day_bounds(chrono::NaiveDate::from_ymd_opt(2024, 6, 15).unwrap());
+        let start = parse_entry_datetime(&start).unwrap();
+        let end = parse_entry_datetime(&end).unwrap();
+        assert_eq!(end - start, chrono::Duration::days(1));
+    }
+
+    #[test]
+    fn test_insert_entry_fills_derived_columns() {
+        let db = DatabaseManager::open_in_memory().unwrap();
+        let id = insert_entry(&db.conn, "Monday", "<p>Lunch with [[Sam]] today</p>", "2024-03-01T12:00:00+00:00")
+            .unwrap();
+        let (word_count, uuid, sentiment, created_ms): (i64, String, Option<f64>, i64) = db
+            .conn
+            .query_row(
+                "SELECT word_count, uuid, sentiment, created_ms FROM journal_entries WHERE id = ?1",
+                rusqlite::params![id],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+            )
+            .unwrap();
+        assert_eq!(word_count, 4);
+        assert_eq!(created_ms, 1_709_294_400_000);
+        // Nothing in the body reads as positive or negative
+        assert_eq!(sentiment, None);
+        assert!(!uuid.is_empty());
+        let link: String = db
+            .conn
+            .query_row(
+                "SELECT target_title FROM entry_links WHERE source_id = ?1",
+                rusqlite::params![id],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(link, "Sam");
+
+        let entry = db
+            .conn
+            .query_row(
+                &format!("SELECT {} FROM journal_entries WHERE id = ?1", JournalEntry::COLUMNS),
+                rusqlite::params![id],
+                JournalEntry::from_row,
+            )
+            .unwrap();
+        assert_eq!(entry.title, "Monday");
+        assert_eq!(entry.local_date, local_date_for("2024-03-01T12:00:00+00:00"));
+        assert_eq!(entry.notebook_id, None);
+        assert!(!entry.archived);
+    }
+}
@@ -0,0 +1,995 @@
+use keyring::Entry;
+use log::{debug, error, info, warn};
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+use dirs::data_local_dir;
+use uuid::Uuid;
+use std::sync::RwLock;
+use std::thread;
+use std::time::Duration;
+
+use crate::audit::{self, AuditAction};
+use crate::migration_history::{self, MigrationStep};
+use crate::profiles::{self, active_profile};
+use crate::{passphrase_key, recovery_key, DatabaseManager};
+
+const SERVICE_NAME: &str = "com.journal.app";
+const ACCOUNT_NAME: &str = "journal_encryption_key";
+const KEY_FILE_NAME: &str = "journal.key";
+/// How many more times a read that failed for a passing reason is tried.
+const TRANSIENT_RETRIES: u32 = 4;
+/// The wait before the first retry, doubled before each one after it.
+const FIRST_RETRY_DELAY: Duration = Duration::from_millis(200);
+
+/// Runs `read` until it succeeds, fails for good, or has failed
+/// `TRANSIENT_RETRIES` more times with errors `is_transient` says may pass,
+/// calling `wait` with a doubling delay before each retry.
+fn with_backoff<T>(
+    mut read: impl FnMut() -> keyring::Result<T>,
+    is_transient: impl Fn(&keyring::Error) -> bool,
+    mut wait: impl FnMut(Duration),
+) -> keyring::Result<T> {
+    let mut delay = FIRST_RETRY_DELAY;
+    for retry in 1..=TRANSIENT_RETRIES {
+        match read() {
+            Err(e) if is_transient(&e) => {
+                warn!("Keychain read failed ({}), retry {} of {} in {:?}", e, retry, TRANSIENT_RETRIES, delay);
+                wait(delay);
+                delay *= 2;
+            }
+            result => return result,
+        }
+    }
+    read()
+}
+
+// Static in-memory cache for the encryption key
+static IN_MEMORY_KEY: RwLock<Option<String>> = RwLock::new(None);
+
+fn cached_key() -> Option<String> {
+    IN_MEMORY_KEY.read().ok().and_then(|key| key.clone())
+}
+
+fn cache_key(key: &str) {
+    if let Ok(mut cached) = IN_MEMORY_KEY.write() {
+        *cached = Some(key.to_string());
+    }
+}
+
+#[derive(Debug)]
+pub enum KeychainError {
+    KeychainAccess(String),
+    KeyNotFound,
+    KeyStorage(String),
+    KeyRetrieval(String),
+    KeyDeletion(String),
+    AuthenticationRequired,
+    FileIO(String),
+    MigrationError(String),
+    AppSupportDirNotFound,
+    KeyGeneration(String),
+    AuthenticationFailed,
+    KeychainAccessDenied,
+    KeychainError(String),
+    FileError(String),
+    /// There's no keychain to use at all, e.g. no Secret Service on Linux.
+    KeystoreUnavailable(String),
+    /// The profile is keyed by a passphrase that hasn't been entered yet.
+    PassphraseRequired,
+    WrongPassphrase,
+    PassphraseTooShort(usize),
+    /// The database was keyed from a keychain that isn't available here.
+    KeyUnreachable,
+    /// There's a database but no key for it, e.g. after moving to a new machine.
+    RecoveryKeyRequired,
+    InvalidRecoveryKey,
+    WrongRecoveryKey,
+}
+
+impl fmt::Display for KeychainError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeychainError::KeychainAccess(msg) => write!(f, "Keychain access error: {}", msg),
+            KeychainError::KeyNotFound => write!(f, "Key not found in keychain"),
+            KeychainError::KeyStorage(msg) => write!(f, "Failed to store key: {}", msg),
+            KeychainError::KeyRetrieval(msg) => write!(f, "Failed to retrieve key: {}", msg),
+            KeychainError::KeyDeletion(msg) => write!(f, "Failed to delete key: {}", msg),
+            KeychainError::AuthenticationRequired => write!(f, "Authentication required to access keychain"),
+            KeychainError::FileIO(msg) => write!(f, "File I/O error: {}", msg),
+            KeychainError::MigrationError(msg) => write!(f, "Migration error: {}", msg),
+            KeychainError::AppSupportDirNotFound => write!(f, "Application Support directory not found"),
+            KeychainError::KeyGeneration(msg) => write!(f, "Failed to generate key: {}", msg),
+            KeychainError::AuthenticationFailed => write!(f, "Authentication failed to access the system keychain"),
+            KeychainError::KeychainAccessDenied => write!(f, "Access denied to the system keychain"),
+            KeychainError::KeychainError(msg) => write!(f, "Keychain error: {}", msg),
+            KeychainError::FileError(msg) => write!(f, "File error: {}", msg),
+            KeychainError::KeystoreUnavailable(msg) => write!(f, "No system keychain is available: {}", msg),
+            KeychainError::PassphraseRequired => write!(f, "The journal's passphrase is required"),
+            KeychainError::WrongPassphrase => write!(f, "Incorrect passphrase"),
+            KeychainError::PassphraseTooShort(min) => write!(f, "Passphrase is shorter than {} characters", min),
+            KeychainError::KeyUnreachable => write!(f, "The database key is in a keychain that isn't available"),
+            KeychainError::RecoveryKeyRequired => write!(f, "The database exists but its key isn't in the keychain"),
+            KeychainError::InvalidRecoveryKey => write!(f, "Invalid recovery key"),
+            KeychainError::WrongRecoveryKey => write!(f, "The recovery key doesn't open the database"),
+        }
+    }
+}
+
+impl Error for KeychainError {}
+
+impl KeychainError {
+    /// Sorts a `keyring` failure by what can be done about it, since each
+    /// platform's store reports the same problems differently.
+    fn from_keyring(error: keyring::Error) -> Self {
+        if platform::is_unavailable(&error) {
+            return KeychainError::KeystoreUnavailable(error.to_string());
+        }
+        match error {
+            keyring::Error::NoEntry => KeychainError::KeyNotFound,
+            keyring::Error::NoStorageAccess(_) => KeychainError::KeychainAccessDenied,
+            error if platform::is_denied(&error) => KeychainError::KeychainAccessDenied,
+            error => KeychainError::KeychainError(error.to_string()),
+        }
+    }
+
+    /// Whether unlocking should go on with the journal's recovery key.
+    pub fn needs_recovery_key(&self) -> bool {
+        matches!(
+            self,
+            KeychainError::KeyUnreachable
+                | KeychainError::RecoveryKeyRequired
+                | KeychainError::InvalidRecoveryKey
+                | KeychainError::WrongRecoveryKey
+        )
+    }
+
+    /// Whether unlocking should go on with a passphrase instead of the keychain.
+    pub fn needs_passphrase(&self) -> bool {
+        matches!(
+            self,
+            KeychainError::KeystoreUnavailable(_) | KeychainError::PassphraseRequired | KeychainError::WrongPassphrase
+        )
+    }
+
+    /// Converts the error into a user-friendly message
+    pub fn to_user_message(&self) -> String {
+        match self {
+            // Authentication errors
+            KeychainError::AuthenticationFailed => 
+                "Unable to access the system keychain. Please check your system permissions.".to_string(),
+            
+            // Keychain access errors
+            KeychainError::KeychainAccessDenied |
+            KeychainError::KeychainError(_) |
+            KeychainError::KeyNotFound => 
+                "There was a problem accessing the system keychain. Please ensure you have granted the necessary permissions.".to_string(),
+            
+            // File system errors
+            KeychainError::FileError(_) |
+            KeychainError::AppSupportDirNotFound => 
+                "There was a problem accessing the application data. Please ensure you have the necessary permissions.".to_string(),
+            
+            // Migration errors
+            KeychainError::MigrationError(_) => 
+                "There was a problem migrating your encryption key. Please try restarting the application.".to_string(),
+            
+            // Key generation errors
+            KeychainError::KeyGeneration(_) => 
+                "There was a problem generating a new encryption key. Please try restarting the application.".to_string(),
+            
+            // No keychain on this desktop, so the key comes from a passphrase
+            KeychainError::KeystoreUnavailable(_) =>
+                "No system keychain is available on this desktop. Choose a passphrase to encrypt your journal with instead.".to_string(),
+            KeychainError::PassphraseRequired =>
+                "Enter your journal's passphrase to unlock it.".to_string(),
+            KeychainError::WrongPassphrase =>
+                "That passphrase is incorrect. Please try again.".to_string(),
+            KeychainError::PassphraseTooShort(min) =>
+                format!("Please choose a passphrase of at least {} characters.", min),
+            KeychainError::KeyUnreachable =>
+                "Your journal's key is stored in a system keychain that isn't available on this desktop. Enter its recovery key to open it here.".to_string(),
+            KeychainError::RecoveryKeyRequired =>
+                "This journal's key isn't in this computer's keychain. Enter its recovery key to open it here.".to_string(),
+            KeychainError::InvalidRecoveryKey =>
+                "That isn't a valid recovery key. Please check it for typos.".to_string(),
+            KeychainError::WrongRecoveryKey =>
+                "That recovery key belongs to a different journal.".to_string(),
+
+            // Generic error fallback
+            _ => 
+                "An unexpected error occurred. Please try restarting the application.".to_string(),
+        }
+    }
+}
+
+pub struct KeychainManager {
+    keyring: Entry,
+}
+
+impl KeychainManager {
+    pub fn new() -> Result<Self, KeychainError> {
+        debug!("Initializing KeychainManager");
+        Ok(Self {
+            keyring: Entry::new(SERVICE_NAME, &active_profile().keychain_account(ACCOUNT_NAME))
+                .map_err(|e| KeychainError::KeychainError(e.to_string()))?,
+        })
+    }
+
+    fn get_app_support_dir() -> Result<PathBuf, KeychainError> {
+        let base = data_local_dir().ok_or(KeychainError::AppSupportDirNotFound)?;
+        let folder_name = if cfg!(debug_assertions) {
+            "Journal-dev"
+        } else {
+            "Journal"
+        };
+        Ok(base.join(folder_name))
+    }
+
+    fn get_key_file_path() -> Result<PathBuf, KeychainError> {
+        let app_dir = Self::get_app_support_dir()?;
+        Ok(app_dir.join(KEY_FILE_NAME))
+    }
+
+    pub fn detect_existing_key_file() -> Result<Option<PathBuf>, KeychainError> {
+        debug!("Checking for existing key file");
+        // Key files predate profiles, so only the default one can have been keyed by one
+        if !active_profile().is_default() {
+            return Ok(None);
+        }
+
+        // Current (expected) location
+        let current_path = Self::get_key_file_path()?;
+        if current_path.exists() {
+            debug!("Found key file at expected location: {:?}", current_path);
+            return Ok(Some(current_path));
+        }
+
+        // Look for a legacy key file in the *other* support directory
+        let base = data_local_dir().ok_or(KeychainError::AppSupportDirNotFound)?;
+        let legacy_folder = if cfg!(debug_assertions) { "Journal" } else { "Journal-dev" };
+        let legacy_path = base.join(legacy_folder).join(KEY_FILE_NAME);
+
+        if legacy_path.exists() {
+            debug!(
+                "Found legacy key file in alternate support directory: {:?}",
+                legacy_path
+            );
+            Ok(Some(legacy_path))
+        } else {
+            debug!("No key file found in any known location");
+            Ok(None)
+        }
+    }
+
+    pub fn generate_and_store_new_key(&self) -> Result<String, KeychainError> {
+        debug!("Generating new encryption key");
+        
+        // Generate a new UUID v4 as the encryption key
+        let new_key = Uuid::new_v4().to_string();
+        debug!("Generated new key");
+        
+        // Store the key in the keychain
+        self.store_key(&new_key)?;
+        info!("Successfully stored new key in keychain");
+        migration_history::record_later(MigrationStep::KeyGenerated, "stored in the keychain");
+        
+        Ok(new_key)
+    }
+
+    pub fn initialize_key(&self) -> Result<String, KeychainError> {
+        debug!("Initializing encryption key");
+
+        // 1️⃣ Try retrieving a key directly from the keychain
+        match self.get_key() {
+            Ok(key) => {
+                debug!("Found existing key in keychain");
+                // Clean up any stale key file once keychain is confirmed
+                let _ = self.cleanup_stale_key_file();
+                Ok(key)
+            }
+            Err(KeychainError::KeyNotFound) => {
+                debug!("Key not found in keychain, will check for and migrate any existing key file");
+                // If a legacy key file exists, migrate it
+                if let Some(key_file_path) = Self::detect_existing_key_file()? {
+                    debug!("Migrating existing key file: {:?}", key_file_path);
+                    self.migrate_existing_key(&key_file_path)?;
+                    // After migration, cleanup any remaining key file
+                    let _ = self.cleanup_stale_key_file();
+                    // Retrieve the migrated key from keychain
+                    let key = self.get_key()?;
+                    Ok(key)
+                } else {
+                    // No key file: generate a new key and store in keychain
+                    debug!("No existing key file, generating new key");
+                    let new_key = self.generate_and_store_new_key()?;
+                    // Clean up if a key file somehow exists
+                    let _ = self.cleanup_stale_key_file();
+                    Ok(new_key)
+                }
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Creates a backup of the key file before migration
+    fn backup_key_file(&self, key_file_path: &PathBuf) -> Result<PathBuf, KeychainError> {
+        let backup_path = key_file_path.with_extension("key.backup");
+        debug!("Creating backup of key file at {:?}", backup_path);
+        
+        fs::copy(key_file_path, &backup_path)
+            .map_err(|e| KeychainError::FileError(format!("Failed to create backup: {}", e)))?;
+        
+        debug!("Successfully created backup at {:?}", backup_path);
+        Ok(backup_path)
+    }
+
+    /// Restores the key file from backup
+    fn restore_from_backup(&self, backup_path: &PathBuf, original_path: &PathBuf) -> Result<(), KeychainError> {
+        debug!("Restoring key file from backup at {:?}", backup_path);
+        
+        fs::copy(backup_path, original_path)
+            .map_err(|e| KeychainError::FileError(format!("Failed to restore from backup: {}", e)))?;
+        
+        debug!("Successfully restored key file from backup");
+        Ok(())
+    }
+
+    /// Attempts to recover from a failed migration
+    fn recover_from_failed_migration(&self, key_file_path: &PathBuf) -> Result<(), KeychainError> {
+        debug!("Attempting to recover from failed migration");
+        
+        let backup_path = key_file_path.with_extension("key.backup");
+        if backup_path.exists() {
+            debug!("Found backup file, attempting restoration");
+            self.restore_from_backup(&backup_path, key_file_path)?;
+            
+            // Clean up the backup file after successful restoration
+            fs::remove_file(&backup_path)
+                .map_err(|e| KeychainError::FileError(format!("Failed to clean up backup: {}", e)))?;
+            
+            debug!("Successfully recovered from failed migration");
+            Ok(())
+        } else {
+            debug!("No backup file found for recovery");
+            Err(KeychainError::MigrationError("No backup available for recovery".to_string()))
+        }
+    }
+
+    pub fn migrate_existing_key(&self, key_file_path: &PathBuf) -> Result<(), KeychainError> {
+        debug!("Starting migration of existing key file: {:?}", key_file_path);
+        
+        // Check if key file exists
+        if !key_file_path.exists() {
+            debug!("No existing key file found at {:?}", key_file_path);
+            return Ok(());
+        }
+
+        // Create a backup before proceeding
+        let backup_path = self.backup_key_file(key_file_path)?;
+
+        // Read the key from the file
+        let key = match fs::read_to_string(key_file_path) {
+            Ok(key) => {
+                debug!("Successfully read key from file");
+                key
+            }
+            Err(e) => {
+                error!("Failed to read key file: {}", e);
+                // Attempt to recover from backup
+                self.recover_from_failed_migration(key_file_path)?;
+                return Err(KeychainError::FileError(format!("Failed to read key file: {}", e)));
+            }
+        };
+
+        // Store the key in the keychain
+        if let Err(e) = self.store_key(&key) {
+            error!("Failed to store key in keychain: {}", e);
+            // Attempt to recover from backup
+            self.recover_from_failed_migration(key_file_path)?;
+            return Err(e);
+        }
+        
+        debug!("Successfully stored key in keychain");
+
+        // Delete the local key file
+        if let Err(e) = fs::remove_file(key_file_path) {
+            error!("Failed to delete key file: {}", e);
+            // Attempt to recover from backup
+            self.recover_from_failed_migration(key_file_path)?;
+            return Err(KeychainError::FileError(format!("Failed to delete key file: {}", e)));
+        }
+        
+        // Clean up the backup file after successful migration
+        if let Err(e) = fs::remove_file(&backup_path) {
+            warn!("Failed to clean up backup file: {}", e);
+            // This is not critical, so we don't return an error
+        }
+        
+        info!("Successfully migrated key to keychain and removed local file");
+        audit::record_later(AuditAction::KeyMigration, &format!("moved into the keychain from {:?}", key_file_path));
+        migration_history::record_later(MigrationStep::KeyFileMigrated, &format!("from {:?}", key_file_path));
+        Ok(())
+    }
+
+    /// Whether the key has already been unlocked for this process, i.e. whether
+    /// opening the database now would happen without a keychain prompt.
+    pub fn has_cached_key() -> bool {
+        cached_key().is_some()
+    }
+
+    /// Drops the key cached for this process, so the journal can't be opened
+    /// again until it's read from the keychain anew.
+    pub fn forget_cached_key() {
+        if let Ok(mut cached) = IN_MEMORY_KEY.write() {
+            *cached = None;
+        }
+    }
+
+    /// Stores an auxiliary secret (e.g. the sync passphrase) under its own account
+    /// name, next to the database key and, like it, kept per profile.
+    pub fn store_secret(account: &str, secret: &str) -> Result<(), KeychainError> {
+        Entry::new(SERVICE_NAME, &active_profile().keychain_account(account))
+            .and_then(|entry| entry.set_password(secret))
+            .map_err(|e| KeychainError::KeyStorage(e.to_string()))
+    }
+
+    /// Reads a secret written by `store_secret`; `None` if it was never stored.
+    pub fn get_secret(account: &str) -> Result<Option<String>, KeychainError> {
+        let entry = Entry::new(SERVICE_NAME, &active_profile().keychain_account(account))
+            .map_err(|e| KeychainError::KeychainAccess(e.to_string()))?;
+        match entry.get_password() {
+            Ok(secret) => Ok(Some(secret)),
+            Err(keyring::Error::NoEntry) => Ok(None),
+            Err(e) => Err(KeychainError::KeyRetrieval(e.to_string())),
+        }
+    }
+
+    pub fn delete_secret(account: &str) -> Result<(), KeychainError> {
+        let entry = Entry::new(SERVICE_NAME, &active_profile().keychain_account(account))
+            .map_err(|e| KeychainError::KeychainAccess(e.to_string()))?;
+        match entry.delete_password() {
+            Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(e) => Err(KeychainError::KeyDeletion(e.to_string())),
+        }
+    }
+
+    /// Removes the database key from the keychain. The copy cached for this
+    /// process stays until it exits.
+    pub fn delete_key(&self) -> Result<(), KeychainError> {
+        match self.keyring.delete_password() {
+            Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(e) => Err(KeychainError::KeyDeletion(e.to_string())),
+        }
+    }
+
+    /// Attempts to retrieve a key from the keychain, with specific handling for access denied scenarios
+    pub fn get_key(&self) -> Result<String, KeychainError> {
+        // First check the in-memory cache
+        if let Some(key) = cached_key() {
+            debug!("Retrieved key from in-memory cache");
+            return Ok(key);
+        }
+
+        // If not in cache, try to get from keychain. Right after login the
+        // keychain can refuse for a moment before it's unlocked
+        match with_backoff(|| self.keyring.get_password(), platform::is_transient, thread::sleep) {
+            Ok(key) => {
+                debug!("Successfully retrieved key from keychain");
+                // Store in cache for future use
+                cache_key(&key);
+                Ok(key)
+            }
+            Err(e) => {
+                let error = KeychainError::from_keyring(e);
+                match &error {
+                    KeychainError::KeyNotFound => log::error!("Key not found in keychain"),
+                    error => log::error!("Failed to retrieve key from keychain: {}", error),
+                }
+                Err(error)
+            }
+        }
+    }
+
+    /// Attempts to store a key in the keychain, with specific handling for access denied scenarios
+    fn store_key(&self, key: &str) -> Result<(), KeychainError> {
+        match self.keyring.set_password(key) {
+            Ok(_) => {
+                log::info!("Successfully stored key in keychain");
+                // Update the in-memory cache
+                cache_key(key);
+                Ok(())
+            }
+            Err(e) => {
+                let error = KeychainError::from_keyring(e);
+                log::error!("Failed to store key in keychain: {}", error);
+                Err(error)
+            }
+        }
+    }
+
+
+    /// Deletes any leftover on‑disk `journal.key` once the key is safely stored in
+    /// the macOS Keychain.  It is a no‑op if no file is found.
+    fn cleanup_stale_key_file(&self) -> Result<(), KeychainError> {
+        if let Some(path) = Self::detect_existing_key_file()? {
+            if path.exists() {
+                debug!("Deleting stale key file at {:?}", path);
+                fs::remove_file(&path).map_err(|e| {
+                    KeychainError::FileError(format!("Failed to delete key file: {}", e))
+                })?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Ensures we have a usable encryption key, prompting the user only once.
+    ///
+    /// Strategy:
+    /// 1. If we have already cached a key for this process (`IN_MEMORY_KEY`),
+    ///    return immediately – no keychain I/O and therefore no prompt.
+    /// 2. Try to *read* the existing key from the Keychain (`get_key()`).
+    ///    • Success ⇒ key is now cached, we're done (one “use item” prompt
+    ///    unless the user chose “Always Allow” previously).
+    ///    • `KeyNotFound` ⇒ first launch. Generate & store a brand‑new key,
+    ///    which triggers exactly one “add item” prompt.
+    ///    • Any other error (access‑denied, etc.) bubbles up.
+    pub fn authorize_keychain(&self) -> Result<(), KeychainError> {
+        // ──────────────────────────────────────────────────────────────
+        // 1️⃣ Fast path: key is already cached for this process.
+        // We **do not** delete any on‑disk key file yet; the database may
+        // still depend on it. Cleanup happens after the DB opens.
+        // ──────────────────────────────────────────────────────────────
+        if Self::has_cached_key() {
+            return Ok(());
+        }
+        // A profile keyed by a passphrase never touches the keychain
+        if Self::uses_passphrase() {
+            return Err(KeychainError::PassphraseRequired);
+        }
+
+        // ──────────────────────────────────────────────────────────────
+        // 2️⃣ Try the key already stored in the macOS Keychain.  
+        // If that works, again leave any legacy key file alone for now.
+        // ──────────────────────────────────────────────────────────────
+        match self.get_key() {
+            Ok(_) => {
+                // Key read & cached – we'll delete any legacy key file later,
+                // once the database has opened successfully.
+                Ok(())
+            }, // key read & cached
+            Err(KeychainError::KeyNotFound) => {
+                // First look for a legacy on‑disk `journal.key` and migrate it.
+                if let Some(path) = Self::detect_existing_key_file()? {
+                    // One‑time migration (single “add item” prompt).
+                    self.migrate_existing_key(&path)?;
+                    self.cleanup_stale_key_file()?;
+                    // Re‑read so the key is cached for this process.
+                    self.get_key().map(|_| ())
+                } else if profiles::database_path().is_ok_and(|path| path.exists()) {
+                    // A journal copied from another machine; a new key would
+                    // only fail to open it
+                    Err(KeychainError::RecoveryKeyRequired)
+                } else {
+                    // Brand‑new install: generate a fresh key (single prompt).
+                    self.generate_and_store_new_key().and_then(|_| {
+                        self.cleanup_stale_key_file()?;
+                        Ok(())
+                    })
+                }
+            }
+            Err(e) => Err(e), // propagate access‑denied or other errors
+        }
+    }
+}
+
+impl KeychainManager {
+    /// Whether the open profile's key comes from a passphrase rather than the
+    /// system keychain.
+    pub fn uses_passphrase() -> bool {
+        profiles::profile_dir().is_ok_and(|dir| passphrase_key::is_set_up_in(&dir))
+    }
+
+    /// Unlocks a profile keyed by a passphrase, or, when its database doesn't
+    /// exist yet, starts keying it by `passphrase`. Meant for desktops with no
+    /// system keychain, where `authorize_keychain` asks for this.
+    pub fn unlock_with_passphrase(passphrase: &str) -> Result<(), KeychainError> {
+        let file_error = |e: crate::ErrorResponse| KeychainError::FileError(e.message);
+        let dir = profiles::profile_dir().map_err(file_error)?;
+        let key = if passphrase_key::is_set_up_in(&dir) {
+            passphrase_key::unlock_in(&dir, passphrase)?
+        } else if profiles::database_path().map_err(file_error)?.exists() {
+            return Err(KeychainError::KeyUnreachable);
+        } else {
+            passphrase_key::set_up_in(&dir, passphrase)?
+        };
+        cache_key(&key);
+        Ok(())
+    }
+
+    /// Moves the open profile's key from the keychain to `passphrase`, for
+    /// when the keychain won't hand it over. An existing database is
+    /// re-encrypted under the passphrase's key, which takes its current key:
+    /// the one unlocked this session, or else its recovery key.
+    pub fn switch_to_passphrase(passphrase: &str, recovery_key: Option<&str>) -> Result<(), KeychainError> {
+        let file_error = |e: crate::ErrorResponse| KeychainError::FileError(e.message);
+        let dir = profiles::profile_dir().map_err(file_error)?;
+        if passphrase_key::is_set_up_in(&dir) {
+            return Self::unlock_with_passphrase(passphrase);
+        }
+        let path = profiles::database_path().map_err(file_error)?;
+        let profile = active_profile();
+        if !path.exists() || profile.unencrypted {
+            cache_key(&passphrase_key::set_up_in(&dir, passphrase)?);
+            return Ok(());
+        }
+        let old_key = match (cached_key(), recovery_key) {
+            (Some(key), _) => key,
+            (None, Some(recovery_key)) => recovery_key::decode(recovery_key)?,
+            (None, None) => return Err(KeychainError::RecoveryKeyRequired),
+        };
+        let db = DatabaseManager::open_with_cipher(&path, &StaticKey(old_key), profile.cipher.unwrap_or_default())
+            .map_err(|_| KeychainError::WrongRecoveryKey)?;
+        let key = passphrase_key::set_up_in(&dir, passphrase)?;
+        let rekeyed = db
+            .conn
+            .pragma_update(None, "rekey", &key)
+            .and_then(|()| db.conn.execute("INSERT INTO key_rotations (rotated_at) VALUES (?1)", [crate::timestamp_now()]))
+            .and_then(|_| audit::record(&db.conn, AuditAction::KeyRotation, "moved from the keychain to a passphrase"));
+        if let Err(e) = rekeyed {
+            let _ = passphrase_key::remove_in(&dir);
+            return Err(KeychainError::KeyStorage(e.to_string()));
+        }
+        info!("Moved the journal's key from the keychain to a passphrase");
+        // The old key opens nothing now; a keychain that refused to read it
+        // may well refuse to delete it too
+        if let Err(e) = Self::new().and_then(|keychain| keychain.delete_key()) {
+            warn!("Could not remove the old key from the keychain: {}", e);
+        }
+        cache_key(&key);
+        Ok(())
+    }
+
+    /// Forgets the open profile's database key everywhere it's kept: in this
+    /// process, in the keychain, and, for a profile keyed by a passphrase, the
+    /// salt it's derived with. The database can't be opened again afterwards.
+    pub fn erase_key() -> Result<(), KeychainError> {
+        Self::forget_cached_key();
+        let dir = profiles::profile_dir().map_err(|e| KeychainError::FileError(e.message))?;
+        if passphrase_key::is_set_up_in(&dir) {
+            return passphrase_key::remove_in(&dir);
+        }
+        Self::new()?.delete_key()
+    }
+
+    /// Key files left on disk in plain text, by builds that predate the
+    /// keychain or by a migration that was interrupted, in either the release
+    /// or the dev support folder. Only the default profile can have any.
+    pub fn plaintext_key_files() -> Result<Vec<PathBuf>, KeychainError> {
+        if !active_profile().is_default() {
+            return Ok(Vec::new());
+        }
+        let base = data_local_dir().ok_or(KeychainError::AppSupportDirNotFound)?;
+        Ok(["Journal", "Journal-dev"]
+            .iter()
+            .flat_map(|folder| {
+                let path = base.join(folder).join(KEY_FILE_NAME);
+                [path.with_extension("key.backup"), path]
+            })
+            .filter(|path| path.exists())
+            .collect())
+    }
+
+    /// The recovery key for the open profile's database, which must already
+    /// be unlocked. It's the database key itself, so it should only be shown
+    /// on request.
+    pub fn recovery_key() -> Result<String, KeychainError> {
+        let key = cached_key().ok_or(KeychainError::AuthenticationRequired)?;
+        Ok(recovery_key::encode(&key))
+    }
+
+    /// Unlocks the open profile's database with its recovery key, then stores
+    /// the key in the keychain so later launches don't need it. Where the
+    /// keychain can't hold it, the journal is unlocked for this session only.
+    pub fn restore_with_recovery_key(recovery_key: &str) -> Result<(), KeychainError> {
+        let key = recovery_key::decode(recovery_key)?;
+        let path = profiles::database_path().map_err(|e| KeychainError::FileError(e.message))?;
+        if !path.exists() {
+            return Err(KeychainError::FileError(format!("There's no journal at {:?}", path)));
+        }
+        let cipher = active_profile().cipher.unwrap_or_default();
+        DatabaseManager::open_with_cipher(&path, &StaticKey(key.clone()), cipher)
+            .map_err(|_| KeychainError::WrongRecoveryKey)?;
+        if Self::uses_passphrase() {
+            cache_key(&key);
+            return Ok(());
+        }
+        match Self::new()?.store_key(&key) {
+            Ok(()) => {
+                audit::record_later(AuditAction::KeyMigration, "restored into the keychain from a recovery key");
+                Ok(())
+            }
+            Err(KeychainError::KeystoreUnavailable(_)) => {
+                warn!("No keychain to store the recovered key in; it's kept for this session");
+                cache_key(&key);
+                Ok(())
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    /// The Secret Service is reached over D-Bus, so a desktop without one
+    /// shows up as a missing bus or a name nothing answers to.
+    pub fn is_unavailable(error: &keyring::Error) -> bool {
+        let message = error.to_string().to_lowercase();
+        [
+            "org.freedesktop.secrets",
+            "serviceunknown",
+            "dbus_session_bus_address",
+            "failed to connect to socket",
+            "connection refused",
+            "no such file or directory",
+        ]
+        .iter()
+        .any(|sign| message.contains(sign))
+    }
+
+    /// A collection the user declined to unlock.
+    pub fn is_denied(error: &keyring::Error) -> bool {
+        let message = error.to_string().to_lowercase();
+        message.contains("locked") || message.contains("dismissed")
+    }
+
+    pub fn is_transient(_error: &keyring::Error) -> bool {
+        false
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod platform {
+    /// Credential Manager needs a logon session, which services and some
+    /// remote sessions don't have (ERROR_NO_SUCH_LOGON_SESSION).
+    pub fn is_unavailable(error: &keyring::Error) -> bool {
+        let message = error.to_string().to_lowercase();
+        message.contains("logon session") || message.contains("1312")
+    }
+
+    pub fn is_denied(error: &keyring::Error) -> bool {
+        error.to_string().to_lowercase().contains("access is denied")
+    }
+
+    pub fn is_transient(_error: &keyring::Error) -> bool {
+        false
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "windows")))]
+mod platform {
+    /// The macOS Keychain is always there.
+    pub fn is_unavailable(_error: &keyring::Error) -> bool {
+        false
+    }
+
+    /// errSecAuthFailed (-25293), when the user gave the wrong password for
+    /// the keychain, and errSecUserCanceled (-128), when they cancelled the
+    /// prompt. Anything else, such as a keychain still locked at login, isn't
+    /// the user saying no.
+    pub fn is_denied(error: &keyring::Error) -> bool {
+        let message = error.to_string().to_lowercase();
+        [
+            "-25293",
+            "user name or passphrase you entered is not correct",
+            "-128",
+            "user canceled the operation",
+        ]
+        .iter()
+        .any(|sign| message.contains(sign))
+    }
+
+    /// errSecInteractionNotAllowed (-25308), which the keychain returns while
+    /// it's still locked during login, or briefly after waking.
+    pub fn is_transient(error: &keyring::Error) -> bool {
+        let message = error.to_string().to_lowercase();
+        message.contains("interaction is not allowed") || message.contains("-25308")
+    }
+}
+
+/// Supplies the database encryption key. `DatabaseManager::open` takes one so
+/// tests and tools can open a database without going through the keychain.
+pub trait KeyProvider {
+    fn database_key(&self) -> Result<String, KeychainError>;
+}
+
+impl KeyProvider for KeychainManager {
+    fn database_key(&self) -> Result<String, KeychainError> {
+        self.authorize_keychain()?;
+        self.initialize_key()
+    }
+}
+
+/// A key known up front, such as a test fixture's.
+pub struct StaticKey(pub String);
+
+impl KeyProvider for StaticKey {
+    fn database_key(&self) -> Result<String, KeychainError> {
+        Ok(self.0.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    #[ignore = "needs the OS keychain"]
+    fn test_keychain_operations() {
+        let manager = KeychainManager::new().unwrap();
+        let test_key = "test_key_123";
+
+        // Test storing key
+        assert!(manager.store_key(test_key).is_ok());
+
+        // Test retrieving key
+        let retrieved_key = manager.get_key().unwrap();
+        assert_eq!(retrieved_key, test_key);
+
+        // Test deleting key
+        assert!(manager.delete_key().is_ok());
+
+        // Verify key is deleted
+        assert!(matches!(manager.get_key(), Err(KeychainError::KeyNotFound)));
+    }
+
+    #[test]
+    #[ignore = "needs the OS keychain"]
+    fn test_migration() {
+        let manager = KeychainManager::new().unwrap();
+        let test_key = "test_key_456";
+
+        // Create a temporary key file
+        let temp_file = NamedTempFile::new().unwrap();
+        fs::write(&temp_file, test_key).unwrap();
+
+        // Test migration
+        assert!(manager.migrate_existing_key(&temp_file.path().to_path_buf()).is_ok());
+
+        // Verify key was stored in keychain
+        let retrieved_key = manager.get_key().unwrap();
+        assert_eq!(retrieved_key, test_key);
+
+        // Verify file was deleted
+        assert!(!temp_file.path().exists());
+
+        // Cleanup
+        manager.delete_key().unwrap();
+    }
+
+    #[test]
+    fn test_key_file_detection() {
+        // Test with non-existent file
+        let result = KeychainManager::detect_existing_key_file().unwrap();
+        assert!(result.is_none());
+
+        // Create a temporary key file in the app support directory
+        let app_dir = KeychainManager::get_app_support_dir().unwrap();
+        fs::create_dir_all(&app_dir).unwrap();
+        let key_file_path = app_dir.join(KEY_FILE_NAME);
+        fs::write(&key_file_path, "test_key").unwrap();
+
+        // Test detection
+        let result = KeychainManager::detect_existing_key_file().unwrap();
+        assert!(result.is_some());
+        assert_eq!(result.unwrap(), key_file_path);
+
+        // Cleanup
+        fs::remove_file(key_file_path).unwrap();
+    }
+
+    #[test]
+    #[ignore = "needs the OS keychain"]
+    fn test_new_key_generation() {
+        let manager = KeychainManager::new().unwrap();
+        
+        // Generate and store a new key
+        let new_key = manager.generate_and_store_new_key().unwrap();
+        
+        // Verify the key was stored
+        let retrieved_key = manager.get_key().unwrap();
+        assert_eq!(retrieved_key, new_key);
+        
+        // Cleanup
+        manager.delete_key().unwrap();
+    }
+
+    #[test]
+    #[ignore = "needs the OS keychain"]
+    fn test_key_initialization() {
+        let manager = KeychainManager::new().unwrap();
+        
+        // Test initialization with no existing key
+        let key = manager.initialize_key().unwrap();
+        assert!(!key.is_empty());
+        
+        // Cleanup
+        manager.delete_key().unwrap();
+        
+        // Test initialization with existing key file
+        let app_dir = KeychainManager::get_app_support_dir().unwrap();
+        fs::create_dir_all(&app_dir).unwrap();
+        let key_file_path = app_dir.join(KEY_FILE_NAME);
+        let test_key = "test_key_789";
+        fs::write(&key_file_path, test_key).unwrap();
+        
+        let key = manager.initialize_key().unwrap();
+        assert_eq!(key, test_key);
+        assert!(!key_file_path.exists());
+        
+        // Cleanup
+        manager.delete_key().unwrap();
+    }
+
+    #[test]
+    fn test_with_backoff() {
+        let transient = |e: &keyring::Error| matches!(e, keyring::Error::PlatformFailure(_));
+        let mut waits = Vec::new();
+        let mut failures = 2;
+        let read = || {
+            if failures > 0 {
+                failures -= 1;
+                return Err(keyring::Error::PlatformFailure("User interaction is not allowed.".into()));
+            }
+            Ok("key")
+        };
+        assert_eq!(with_backoff(read, transient, |delay| waits.push(delay)).unwrap(), "key");
+        assert_eq!(waits, [FIRST_RETRY_DELAY, FIRST_RETRY_DELAY * 2]);
+
+        // Other errors aren't retried, and transient ones only so often
+        let mut reads = 0;
+        let missing = || -> keyring::Result<()> {
+            reads += 1;
+            Err(keyring::Error::NoEntry)
+        };
+        assert!(matches!(with_backoff(missing, transient, |_| {}), Err(keyring::Error::NoEntry)));
+        assert_eq!(reads, 1);
+        let mut reads = 0;
+        let always = || -> keyring::Result<()> {
+            reads += 1;
+            Err(keyring::Error::PlatformFailure("User interaction is not allowed.".into()))
+        };
+        assert!(with_backoff(always, transient, |_| {}).is_err());
+        assert_eq!(reads, TRANSIENT_RETRIES + 1);
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_missing_secret_service_is_unavailable() {
+        let error = keyring::Error::PlatformFailure(
+            "org.freedesktop.DBus.Error.ServiceUnknown: The name org.freedesktop.secrets was not provided by any .service files"
+                .into(),
+        );
+        assert!(KeychainError::from_keyring(error).needs_passphrase());
+        assert!(matches!(KeychainError::from_keyring(keyring::Error::NoEntry), KeychainError::KeyNotFound));
+        let locked = keyring::Error::NoStorageAccess("Prompt dismissed".into());
+        assert!(matches!(KeychainError::from_keyring(locked), KeychainError::KeychainAccessDenied));
+    }
+
+    #[test]
+    #[cfg(target_os = "macos")]
+    fn test_only_refusals_are_denied() {
+        let cancelled = keyring::Error::PlatformFailure("User canceled the operation. (-128)".into());
+        assert!(matches!(KeychainError::from_keyring(cancelled), KeychainError::KeychainAccessDenied));
+        let wrong_password =
+            keyring::Error::PlatformFailure("The user name or passphrase you entered is not correct.".into());
+        assert!(matches!(KeychainError::from_keyring(wrong_password), KeychainError::KeychainAccessDenied));
+        // Still locked at login: retried rather than treated as a refusal
+        let locked = keyring::Error::PlatformFailure("User interaction is not allowed. (-25308)".into());
+        assert!(platform::is_transient(&locked));
+        assert!(matches!(KeychainError::from_keyring(locked), KeychainError::KeychainError(_)));
+    }
+}
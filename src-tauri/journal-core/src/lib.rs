@@ -0,0 +1,26 @@
+// Storage shared by the Journal app, its command line, and its sync: the
+// encrypted database, the keychain that holds its key, and the entry model.
+
+pub mod attachments;
+pub mod audit;
+pub mod cipher;
+mod db;
+mod entries;
+pub mod keychain;
+pub mod links;
+pub mod migration_history;
+mod migrations;
+mod passphrase_key;
+mod recovery_key;
+pub mod people;
+pub mod profiles;
+pub mod sentiment;
+pub mod text;
+pub mod validation;
+
+pub use db::{app_support_dir, in_transaction, DatabaseManager, ErrorResponse, APPLICATION_ID};
+pub use migrations::SCHEMA_VERSION;
+pub use entries::{
+    created_ms_for, insert_entry, local_date_for, local_day_bounds, parse_entry_date, parse_entry_datetime,
+    timestamp_now, JournalEntry,
+};
@@ -0,0 +1,52 @@
+use crate::text::strip_html;
+
+const MAX_LINK_TITLE_CHARS: usize = 200;
+
+/// Extracts the distinct `[[entry title]]` targets from an HTML body, in order
+/// of first appearance. Titles are compared ASCII case-insensitively, which
+/// matches how SQLite's `NOCASE` resolves them later.
+pub fn parse_links(body: &str) -> Vec<String> {
+    let text = strip_html(body);
+    let mut links: Vec<String> = Vec::new();
+    let mut rest = text.as_str();
+    while let Some(start) = rest.find("[[") {
+        let after = &rest[start + 2..];
+        let Some(end) = after.find("]]") else { break };
+        let title = after[..end].trim();
+        if !title.is_empty()
+            && !title.contains('\n')
+            && title.chars().count() <= MAX_LINK_TITLE_CHARS
+            && !links.iter().any(|l| l.eq_ignore_ascii_case(title))
+        {
+            links.push(title.to_string());
+        }
+        rest = &after[end + 2..];
+    }
+    links
+}
+
+/// Replaces the stored outgoing links of an entry with those found in `body`.
+pub fn update_links(conn: &rusqlite::Connection, entry_id: i32, body: &str) -> rusqlite::Result<()> {
+    conn.execute("DELETE FROM entry_links WHERE source_id = ?1", rusqlite::params![entry_id])?;
+    let mut insert = conn.prepare("INSERT INTO entry_links (source_id, target_title) VALUES (?1, ?2)")?;
+    for title in parse_links(body) {
+        insert.execute(rusqlite::params![entry_id, title])?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_links() {
+        let body = "<p>Saw [[Trip to Lisbon]] again, and [[ trip to lisbon ]] plus [[Mum]].</p><p>[[]] [[unclosed</p>";
+        assert_eq!(parse_links(body), vec!["Trip to Lisbon", "Mum"]);
+    }
+
+    #[test]
+    fn test_parse_links_without_links() {
+        assert!(parse_links("<p>No links here [single] ]]</p>").is_empty());
+    }
+}
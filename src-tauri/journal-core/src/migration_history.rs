@@ -0,0 +1,120 @@
+use log::{debug, warn};
+use rusqlite::Connection;
+use serde::Serialize;
+use std::sync::Mutex;
+
+use crate::timestamp_now;
+
+// What the app did on its own to get the journal open: copying a database
+// over from the other build's folder, moving the key out of a legacy key
+// file, making a new key, upgrading the schema. These paths run once and
+// silently, so when a journal comes up empty or won't open, this is what
+// shows which of them ran and when. It stays on this machine. Most of these
+// happen before the database can be opened, so they're held here until it is,
+// like the audit log's.
+
+static PENDING: Mutex<Vec<(String, MigrationStep, String)>> = Mutex::new(Vec::new());
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MigrationStep {
+    /// The database was copied in from the release or dev build's folder.
+    LegacyDatabaseCopied,
+    /// The key was moved from a `journal.key` file into the keychain.
+    KeyFileMigrated,
+    /// No key was found, so a new one was made.
+    KeyGenerated,
+    /// A development build couldn't open the database and started a new one.
+    DatabaseReset,
+    /// Schema migrations ran.
+    SchemaUpgraded,
+}
+
+impl MigrationStep {
+    fn as_str(self) -> &'static str {
+        match self {
+            MigrationStep::LegacyDatabaseCopied => "legacy_database_copied",
+            MigrationStep::KeyFileMigrated => "key_file_migrated",
+            MigrationStep::KeyGenerated => "key_generated",
+            MigrationStep::DatabaseReset => "database_reset",
+            MigrationStep::SchemaUpgraded => "schema_upgraded",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MigrationRecord {
+    pub id: i64,
+    pub occurred_at: String,
+    pub step: String,
+    pub detail: String,
+}
+
+/// Adds a step to the history in `conn`.
+pub(crate) fn record(conn: &Connection, step: MigrationStep, detail: &str) -> rusqlite::Result<()> {
+    insert(conn, &timestamp_now(), step, detail)
+}
+
+fn insert(conn: &Connection, occurred_at: &str, step: MigrationStep, detail: &str) -> rusqlite::Result<()> {
+    debug!("Migration history: {} {}", step.as_str(), detail);
+    conn.execute(
+        "INSERT INTO migration_history (occurred_at, step, detail) VALUES (?1, ?2, ?3)",
+        rusqlite::params![occurred_at, step.as_str(), detail],
+    )?;
+    Ok(())
+}
+
+/// Holds a step taken before the database could be opened, until `flush`
+/// writes it.
+pub(crate) fn record_later(step: MigrationStep, detail: &str) {
+    if let Ok(mut pending) = PENDING.lock() {
+        pending.push((timestamp_now(), step, detail.to_string()));
+    }
+}
+
+/// Writes the steps held by `record_later` to the history in `conn`.
+pub(crate) fn flush(conn: &Connection) {
+    let Ok(mut pending) = PENDING.lock() else {
+        return;
+    };
+    while let Some((occurred_at, step, detail)) = pending.first() {
+        if let Err(e) = insert(conn, occurred_at, *step, detail) {
+            warn!("Could not write to the migration history: {}", e);
+            return;
+        }
+        pending.remove(0);
+    }
+}
+
+/// The history, newest first.
+pub fn migration_history(conn: &Connection) -> rusqlite::Result<Vec<MigrationRecord>> {
+    conn.prepare("SELECT id, occurred_at, step, detail FROM migration_history ORDER BY id DESC")?
+        .query_map([], |row| {
+            Ok(MigrationRecord {
+                id: row.get(0)?,
+                occurred_at: row.get(1)?,
+                step: row.get(2)?,
+                detail: row.get(3)?,
+            })
+        })?
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DatabaseManager;
+
+    #[test]
+    fn test_history() {
+        let db = DatabaseManager::open_in_memory().unwrap();
+        record_later(MigrationStep::KeyFileMigrated, "journal.key");
+        flush(&db.conn);
+
+        let history = migration_history(&db.conn).unwrap();
+        let steps: Vec<&str> = history.iter().map(|record| record.step.as_str()).collect();
+        // Creating the database ran every schema migration first
+        assert_eq!(steps, ["key_file_migrated", "schema_upgraded"]);
+        assert_eq!(history[1].detail, format!("from version 0 to {}", crate::SCHEMA_VERSION));
+    }
+}
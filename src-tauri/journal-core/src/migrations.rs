@@ -0,0 +1,606 @@
+use log::debug;
+use rusqlite::Connection;
+use uuid::Uuid;
+
+use crate::attachments::content_hash;
+use crate::db::in_transaction;
+use crate::entries::{created_ms_for, local_date_for};
+use crate::ErrorResponse;
+use crate::links::update_links;
+use crate::migration_history::{self, MigrationStep};
+use crate::people::update_people;
+use crate::sentiment::body_sentiment;
+use crate::text::body_word_count;
+
+/// A single schema change: plain SQL, or Rust code for data that SQL alone
+/// can't compute (e.g. backfilling values derived from entry bodies).
+enum Migration {
+    Sql(&'static str),
+    Code(fn(&Connection) -> rusqlite::Result<()>),
+}
+
+/// Schema changes applied on top of the base `journal_entries` table.
+///
+/// `PRAGMA user_version` records how many of these have already run, so each
+/// step executes exactly once per database. Only ever append to this list –
+/// never edit or reorder a step that has shipped.
+const MIGRATIONS: &[Migration] = &[
+    // 1: mood tracking
+    Migration::Sql(
+        "ALTER TABLE journal_entries ADD COLUMN mood_score INTEGER;
+         ALTER TABLE journal_entries ADD COLUMN mood_emoji TEXT;",
+    ),
+    // 2: stored word counts
+    Migration::Sql(
+        "ALTER TABLE journal_entries ADD COLUMN word_count INTEGER NOT NULL DEFAULT 0;
+         CREATE INDEX IF NOT EXISTS idx_journal_entries_word_count ON journal_entries(word_count);",
+    ),
+    // 3: word counts for entries written before 2
+    Migration::Code(backfill_word_counts),
+    // 4: entry templates
+    Migration::Sql(
+        "CREATE TABLE IF NOT EXISTS templates (
+            id INTEGER PRIMARY KEY,
+            name TEXT NOT NULL UNIQUE,
+            title TEXT NOT NULL,
+            body TEXT NOT NULL,
+            created_at TEXT NOT NULL
+        );",
+    ),
+    // 5: user-added writing prompts
+    Migration::Sql(
+        "CREATE TABLE IF NOT EXISTS user_prompts (
+            id INTEGER PRIMARY KEY,
+            text TEXT NOT NULL,
+            created_at TEXT NOT NULL
+        );",
+    ),
+    // 6: wiki-style links between entries
+    Migration::Sql(
+        "CREATE TABLE IF NOT EXISTS entry_links (
+            source_id INTEGER NOT NULL REFERENCES journal_entries(id) ON DELETE CASCADE,
+            target_title TEXT NOT NULL,
+            PRIMARY KEY (source_id, target_title)
+        );
+        CREATE INDEX IF NOT EXISTS idx_entry_links_target ON entry_links(target_title COLLATE NOCASE);",
+    ),
+    // 7: links in entries written before 6
+    Migration::Code(backfill_links),
+    // 8: entry tags
+    Migration::Sql(
+        "CREATE TABLE IF NOT EXISTS entry_tags (
+            entry_id INTEGER NOT NULL REFERENCES journal_entries(id) ON DELETE CASCADE,
+            tag TEXT NOT NULL COLLATE NOCASE,
+            PRIMARY KEY (entry_id, tag)
+        );
+        CREATE INDEX IF NOT EXISTS idx_entry_tags_tag ON entry_tags(tag);",
+    ),
+    // 9: notebooks
+    Migration::Sql(
+        "CREATE TABLE IF NOT EXISTS notebooks (
+            id INTEGER PRIMARY KEY,
+            name TEXT NOT NULL UNIQUE,
+            created_at TEXT NOT NULL
+        );
+        ALTER TABLE journal_entries ADD COLUMN notebook_id INTEGER REFERENCES notebooks(id) ON DELETE SET NULL;
+        CREATE INDEX IF NOT EXISTS idx_journal_entries_notebook ON journal_entries(notebook_id);",
+    ),
+    // 10: passphrase-locked entries
+    Migration::Sql(
+        "ALTER TABLE journal_entries ADD COLUMN locked INTEGER NOT NULL DEFAULT 0;
+         ALTER TABLE journal_entries ADD COLUMN lock_salt BLOB;
+         ALTER TABLE journal_entries ADD COLUMN lock_nonce BLOB;
+         ALTER TABLE journal_entries ADD COLUMN locked_body BLOB;",
+    ),
+    // 11: stable identity and change time for sync
+    Migration::Sql(
+        "ALTER TABLE journal_entries ADD COLUMN uuid TEXT;
+         ALTER TABLE journal_entries ADD COLUMN updated_at TEXT;",
+    ),
+    // 12: uuids for entries written before 11
+    Migration::Code(backfill_uuids),
+    // 13: tombstones, plus triggers that keep updated_at current so every
+    // write path is covered without each command having to remember it
+    Migration::Sql(
+        "CREATE UNIQUE INDEX IF NOT EXISTS idx_journal_entries_uuid ON journal_entries(uuid);
+        CREATE TABLE IF NOT EXISTS deleted_entries (
+            uuid TEXT PRIMARY KEY,
+            deleted_at TEXT NOT NULL
+        );
+        CREATE TRIGGER IF NOT EXISTS journal_entries_tombstone AFTER DELETE ON journal_entries
+        WHEN OLD.uuid IS NOT NULL
+        BEGIN
+            INSERT OR REPLACE INTO deleted_entries (uuid, deleted_at)
+            VALUES (OLD.uuid, strftime('%Y-%m-%dT%H:%M:%fZ', 'now'));
+        END;
+        CREATE TRIGGER IF NOT EXISTS journal_entries_touch
+        AFTER UPDATE OF title, body, created_at, mood_score, mood_emoji ON journal_entries
+        WHEN NEW.updated_at IS OLD.updated_at
+        BEGIN
+            UPDATE journal_entries SET updated_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now') WHERE id = NEW.id;
+        END;
+        CREATE TRIGGER IF NOT EXISTS entry_tags_touch_insert AFTER INSERT ON entry_tags
+        BEGIN
+            UPDATE journal_entries SET updated_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now') WHERE id = NEW.entry_id;
+        END;
+        CREATE TRIGGER IF NOT EXISTS entry_tags_touch_delete AFTER DELETE ON entry_tags
+        BEGIN
+            UPDATE journal_entries SET updated_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now') WHERE id = OLD.entry_id;
+        END;",
+    ),
+    // 14: Lamport clocks for sync. Every local change takes the next clock
+    // value and clears `origin`; changes merged from another device keep the
+    // clock value and device id they arrived with.
+    Migration::Sql(
+        "CREATE TABLE IF NOT EXISTS sync_clock (
+            id INTEGER PRIMARY KEY CHECK (id = 1),
+            value INTEGER NOT NULL
+        );
+        INSERT OR IGNORE INTO sync_clock (id, value) VALUES (1, 1);
+        CREATE TABLE IF NOT EXISTS sync_cursors (
+            remote TEXT NOT NULL,
+            device_id TEXT NOT NULL,
+            last_seq INTEGER NOT NULL,
+            PRIMARY KEY (remote, device_id)
+        );
+        CREATE TABLE IF NOT EXISTS sync_pushes (
+            remote TEXT PRIMARY KEY,
+            lamport INTEGER NOT NULL
+        );
+        ALTER TABLE journal_entries ADD COLUMN lamport INTEGER NOT NULL DEFAULT 1;
+        ALTER TABLE journal_entries ADD COLUMN origin TEXT;
+        ALTER TABLE deleted_entries ADD COLUMN lamport INTEGER NOT NULL DEFAULT 1;
+        ALTER TABLE deleted_entries ADD COLUMN origin TEXT;
+        DROP TRIGGER IF EXISTS journal_entries_tombstone;
+        DROP TRIGGER IF EXISTS journal_entries_touch;
+        DROP TRIGGER IF EXISTS entry_tags_touch_insert;
+        DROP TRIGGER IF EXISTS entry_tags_touch_delete;
+        CREATE TRIGGER journal_entries_stamp_insert AFTER INSERT ON journal_entries
+        WHEN NEW.origin IS NULL
+        BEGIN
+            UPDATE sync_clock SET value = value + 1;
+            UPDATE journal_entries SET lamport = (SELECT value FROM sync_clock) WHERE id = NEW.id;
+        END;
+        CREATE TRIGGER journal_entries_tombstone AFTER DELETE ON journal_entries
+        WHEN OLD.uuid IS NOT NULL
+        BEGIN
+            UPDATE sync_clock SET value = value + 1;
+            INSERT OR REPLACE INTO deleted_entries (uuid, deleted_at, lamport, origin)
+            VALUES (OLD.uuid, strftime('%Y-%m-%dT%H:%M:%fZ', 'now'), (SELECT value FROM sync_clock), NULL);
+        END;
+        CREATE TRIGGER journal_entries_touch
+        AFTER UPDATE OF title, body, created_at, mood_score, mood_emoji ON journal_entries
+        WHEN NEW.lamport IS OLD.lamport AND NEW.origin IS OLD.origin
+        BEGIN
+            UPDATE sync_clock SET value = value + 1;
+            UPDATE journal_entries
+            SET updated_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now'), lamport = (SELECT value FROM sync_clock), origin = NULL
+            WHERE id = NEW.id;
+        END;
+        CREATE TRIGGER entry_tags_touch_insert AFTER INSERT ON entry_tags
+        BEGIN
+            UPDATE sync_clock SET value = value + 1;
+            UPDATE journal_entries
+            SET updated_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now'), lamport = (SELECT value FROM sync_clock), origin = NULL
+            WHERE id = NEW.entry_id;
+        END;
+        CREATE TRIGGER entry_tags_touch_delete AFTER DELETE ON entry_tags
+        BEGIN
+            UPDATE sync_clock SET value = value + 1;
+            UPDATE journal_entries
+            SET updated_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now'), lamport = (SELECT value FROM sync_clock), origin = NULL
+            WHERE id = OLD.entry_id;
+        END;",
+    ),
+    // 15: conflict review. Bases start at the current versions, which is
+    // what other devices were last sent before bases were tracked.
+    Migration::Sql(
+        "CREATE TABLE IF NOT EXISTS sync_bases (
+            uuid TEXT PRIMARY KEY,
+            lamport INTEGER NOT NULL,
+            origin TEXT,
+            title TEXT NOT NULL,
+            body TEXT NOT NULL
+        );
+        INSERT OR IGNORE INTO sync_bases (uuid, lamport, origin, title, body)
+        SELECT uuid, lamport, origin, title, body FROM journal_entries WHERE uuid IS NOT NULL AND locked = 0;
+        CREATE TABLE IF NOT EXISTS conflicts (
+            id INTEGER PRIMARY KEY,
+            entry_id INTEGER NOT NULL UNIQUE REFERENCES journal_entries(id) ON DELETE CASCADE,
+            base_title TEXT NOT NULL,
+            base_body TEXT NOT NULL,
+            local_title TEXT NOT NULL,
+            local_body TEXT NOT NULL,
+            remote_title TEXT NOT NULL,
+            remote_body TEXT NOT NULL,
+            remote_origin TEXT NOT NULL,
+            kept TEXT NOT NULL,
+            detected_at TEXT NOT NULL
+        );",
+    ),
+    // 16: entry attachments (voice memos), stored inside the encrypted database
+    Migration::Sql(
+        "CREATE TABLE IF NOT EXISTS attachments (
+            id INTEGER PRIMARY KEY,
+            entry_id INTEGER NOT NULL REFERENCES journal_entries(id) ON DELETE CASCADE,
+            kind TEXT NOT NULL,
+            mime TEXT NOT NULL,
+            size INTEGER NOT NULL,
+            data BLOB NOT NULL,
+            created_at TEXT NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_attachments_entry ON attachments(entry_id);",
+    ),
+    // 17: attachment bytes move to blobs keyed by content hash, shared by
+    // every attachment with the same bytes
+    Migration::Code(split_attachment_blobs),
+    // 18: weather when the entry was written, as JSON
+    Migration::Sql("ALTER TABLE journal_entries ADD COLUMN weather TEXT;"),
+    // 19: where the entry was written
+    Migration::Sql(
+        "ALTER TABLE journal_entries ADD COLUMN latitude REAL;
+        ALTER TABLE journal_entries ADD COLUMN longitude REAL;
+        ALTER TABLE journal_entries ADD COLUMN place TEXT;
+        CREATE INDEX IF NOT EXISTS idx_journal_entries_latitude ON journal_entries(latitude);",
+    ),
+    // 20: free-form per-entry fields as a JSON object
+    Migration::Sql("ALTER TABLE journal_entries ADD COLUMN metadata TEXT NOT NULL DEFAULT '{}';"),
+    // 21: user-defined typed fields, with defaults and values stored as JSON
+    Migration::Sql(
+        "CREATE TABLE IF NOT EXISTS custom_fields (
+            id INTEGER PRIMARY KEY,
+            name TEXT NOT NULL UNIQUE COLLATE NOCASE,
+            field_type TEXT NOT NULL,
+            default_value TEXT,
+            created_at TEXT NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS custom_field_values (
+            entry_id INTEGER NOT NULL REFERENCES journal_entries(id) ON DELETE CASCADE,
+            field_id INTEGER NOT NULL REFERENCES custom_fields(id) ON DELETE CASCADE,
+            value TEXT NOT NULL,
+            PRIMARY KEY (entry_id, field_id)
+        );
+        CREATE INDEX IF NOT EXISTS idx_custom_field_values_field ON custom_field_values(field_id);",
+    ),
+    // 22: the template today's entry is created from, at most one
+    Migration::Sql(
+        "ALTER TABLE templates ADD COLUMN is_default INTEGER NOT NULL DEFAULT 0;
+        CREATE UNIQUE INDEX IF NOT EXISTS idx_templates_default ON templates(is_default) WHERE is_default = 1;",
+    ),
+    // 23: archived entries, hidden from the timeline and search by default
+    Migration::Sql(
+        "ALTER TABLE journal_entries ADD COLUMN archived INTEGER NOT NULL DEFAULT 0;
+        CREATE INDEX IF NOT EXISTS idx_journal_entries_archived ON journal_entries(archived);",
+    ),
+    // 24: per-entry term counts for related-entry suggestions, and the
+    // version of each entry they were counted from
+    Migration::Sql(
+        "CREATE TABLE IF NOT EXISTS entry_terms (
+            entry_id INTEGER NOT NULL REFERENCES journal_entries(id) ON DELETE CASCADE,
+            term TEXT NOT NULL,
+            count INTEGER NOT NULL,
+            PRIMARY KEY (entry_id, term)
+        );
+        CREATE INDEX IF NOT EXISTS idx_entry_terms_term ON entry_terms(term);
+        CREATE TABLE IF NOT EXISTS entry_terms_indexed (
+            entry_id INTEGER PRIMARY KEY REFERENCES journal_entries(id) ON DELETE CASCADE,
+            updated_at TEXT
+        );",
+    ),
+    // 25: summaries from a local language model, of one entry or a date range
+    Migration::Sql(
+        "CREATE TABLE IF NOT EXISTS summaries (
+            id INTEGER PRIMARY KEY,
+            entry_id INTEGER REFERENCES journal_entries(id) ON DELETE CASCADE,
+            range_start TEXT,
+            range_end TEXT,
+            model TEXT NOT NULL,
+            summary TEXT NOT NULL,
+            created_at TEXT NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_summaries_entry ON summaries(entry_id);",
+    ),
+    // 26: stored sentiment scores
+    Migration::Sql("ALTER TABLE journal_entries ADD COLUMN sentiment REAL;"),
+    // 27: sentiment for entries written before 26
+    Migration::Code(backfill_sentiment),
+    // 28: people mentioned in entries
+    Migration::Sql(
+        "CREATE TABLE IF NOT EXISTS people (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL UNIQUE COLLATE NOCASE
+        );
+        CREATE TABLE IF NOT EXISTS entry_people (
+            entry_id INTEGER NOT NULL REFERENCES journal_entries(id) ON DELETE CASCADE,
+            person_id INTEGER NOT NULL REFERENCES people(id) ON DELETE CASCADE,
+            PRIMARY KEY (entry_id, person_id)
+        );
+        CREATE INDEX IF NOT EXISTS idx_entry_people_person ON entry_people(person_id);",
+    ),
+    // 29: people in entries written before 28
+    Migration::Code(backfill_people),
+    // 30: text found in attachments, such as words recognized in photos.
+    // Keyed by blob, so identical files are only read once.
+    Migration::Sql(
+        "CREATE TABLE IF NOT EXISTS attachment_text (
+            hash TEXT PRIMARY KEY REFERENCES attachment_blobs(hash) ON DELETE CASCADE,
+            source TEXT NOT NULL,
+            text TEXT NOT NULL,
+            extracted_at TEXT NOT NULL
+        );",
+    ),
+    // 31: when the database was re-encrypted under a new key
+    Migration::Sql(
+        "CREATE TABLE IF NOT EXISTS key_rotations (
+            id INTEGER PRIMARY KEY,
+            rotated_at TEXT NOT NULL
+        );",
+    ),
+    // 32: append-only log of what happened to the journal as a whole
+    Migration::Sql(
+        "CREATE TABLE IF NOT EXISTS audit_log (
+            id INTEGER PRIMARY KEY,
+            occurred_at TEXT NOT NULL,
+            action TEXT NOT NULL,
+            detail TEXT NOT NULL DEFAULT ''
+        );
+        CREATE TRIGGER IF NOT EXISTS audit_log_no_update BEFORE UPDATE ON audit_log
+        BEGIN SELECT RAISE(ABORT, 'the audit log is append-only'); END;
+        CREATE TRIGGER IF NOT EXISTS audit_log_no_delete BEFORE DELETE ON audit_log
+        BEGIN SELECT RAISE(ABORT, 'the audit log is append-only'); END;",
+    ),
+    // 33: daily health figures imported from other apps, such as steps and sleep
+    Migration::Sql(
+        "CREATE TABLE IF NOT EXISTS daily_metrics (
+            day TEXT NOT NULL,
+            kind TEXT NOT NULL,
+            value REAL NOT NULL,
+            imported_at TEXT NOT NULL,
+            PRIMARY KEY (day, kind)
+        );",
+    ),
+    // 34: the day an entry was written on where it was written, so it isn't
+    // grouped under the next UTC day
+    Migration::Sql(
+        "ALTER TABLE journal_entries ADD COLUMN local_date TEXT;
+        CREATE INDEX IF NOT EXISTS idx_journal_entries_local_date ON journal_entries(local_date);",
+    ),
+    // 35: local dates for entries written before 34, in this machine's time
+    // zone, which is the best guess there is
+    Migration::Code(backfill_local_dates),
+    // 36: when each entry was written as epoch milliseconds, which orders
+    // correctly where RFC3339 strings with different offsets don't
+    Migration::Sql(
+        "ALTER TABLE journal_entries ADD COLUMN created_ms INTEGER;
+        CREATE INDEX IF NOT EXISTS idx_journal_entries_created_ms ON journal_entries(created_ms);",
+    ),
+    // 37: fills in 36. `created_at` is left as it was, since changing it
+    // would count as an edit and send every entry out on the next sync.
+    Migration::Code(backfill_created_ms),
+    // 38: a count of edits to each entry's text, so a save from an editor
+    // opened on an older version can be refused instead of overwriting
+    Migration::Sql(
+        "ALTER TABLE journal_entries ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
+        CREATE TRIGGER journal_entries_version AFTER UPDATE OF title, body ON journal_entries
+        WHEN NEW.version IS OLD.version
+        BEGIN
+            UPDATE journal_entries SET version = OLD.version + 1 WHERE id = NEW.id;
+        END;",
+    ),
+    // 39: what the app did by itself to get the journal open, for support
+    Migration::Sql(
+        "CREATE TABLE IF NOT EXISTS migration_history (
+            id INTEGER PRIMARY KEY,
+            occurred_at TEXT NOT NULL,
+            step TEXT NOT NULL,
+            detail TEXT NOT NULL DEFAULT ''
+        );",
+    ),
+    // 40: marks the file as a journal (`APPLICATION_ID`), so an archive
+    // can be told from any other SQLite database
+    Migration::Sql("PRAGMA application_id = 1246907980;"),
+];
+
+/// The schema version a database has once every migration has run.
+pub const SCHEMA_VERSION: i64 = MIGRATIONS.len() as i64;
+
+/// Brings the schema up to date, running each pending step in its own transaction.
+pub fn run(conn: &Connection) -> Result<(), ErrorResponse> {
+    let version: i64 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(version.max(0) as usize) {
+        let target = index as i64 + 1;
+        debug!("Applying schema migration {}", target);
+        in_transaction(conn, |tx| -> Result<(), ErrorResponse> {
+            match migration {
+                Migration::Sql(sql) => tx.execute_batch(sql),
+                Migration::Code(apply) => apply(tx),
+            }
+            .map_err(|e| ErrorResponse {
+                message: format!("Failed to apply schema migration {}: {}", target, e),
+                error_type: "database_error".to_string(),
+            })?;
+            tx.pragma_update(None, "user_version", target)?;
+            Ok(())
+        })?;
+    }
+    if version < SCHEMA_VERSION {
+        let detail = format!("from version {} to {}", version, SCHEMA_VERSION);
+        migration_history::record(conn, MigrationStep::SchemaUpgraded, &detail)?;
+    }
+    Ok(())
+}
+
+fn backfill_word_counts(conn: &Connection) -> rusqlite::Result<()> {
+    let bodies = conn
+        .prepare("SELECT id, body FROM journal_entries")?
+        .query_map([], |row| Ok((row.get::<_, i32>(0)?, row.get::<_, String>(1)?)))?
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut update = conn.prepare("UPDATE journal_entries SET word_count = ?1 WHERE id = ?2")?;
+    for (id, body) in bodies {
+        update.execute(rusqlite::params![body_word_count(&body), id])?;
+    }
+    Ok(())
+}
+
+fn backfill_sentiment(conn: &Connection) -> rusqlite::Result<()> {
+    let bodies = conn
+        .prepare("SELECT id, body FROM journal_entries")?
+        .query_map([], |row| Ok((row.get::<_, i32>(0)?, row.get::<_, String>(1)?)))?
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut update = conn.prepare("UPDATE journal_entries SET sentiment = ?1 WHERE id = ?2")?;
+    for (id, body) in bodies {
+        update.execute(rusqlite::params![body_sentiment(&body), id])?;
+    }
+    Ok(())
+}
+
+fn backfill_links(conn: &Connection) -> rusqlite::Result<()> {
+    let bodies = conn
+        .prepare("SELECT id, body FROM journal_entries")?
+        .query_map([], |row| Ok((row.get::<_, i32>(0)?, row.get::<_, String>(1)?)))?
+        .collect::<Result<Vec<_>, _>>()?;
+    for (id, body) in bodies {
+        update_links(conn, id, &body)?;
+    }
+    Ok(())
+}
+
+fn backfill_people(conn: &Connection) -> rusqlite::Result<()> {
+    let bodies = conn
+        .prepare("SELECT id, body FROM journal_entries")?
+        .query_map([], |row| Ok((row.get::<_, i32>(0)?, row.get::<_, String>(1)?)))?
+        .collect::<Result<Vec<_>, _>>()?;
+    for (id, body) in bodies {
+        update_people(conn, id, &body)?;
+    }
+    Ok(())
+}
+
+fn backfill_local_dates(conn: &Connection) -> rusqlite::Result<()> {
+    let dates = conn
+        .prepare("SELECT id, created_at FROM journal_entries WHERE local_date IS NULL")?
+        .query_map([], |row| Ok((row.get::<_, i32>(0)?, row.get::<_, String>(1)?)))?
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut update = conn.prepare("UPDATE journal_entries SET local_date = ?1 WHERE id = ?2")?;
+    for (id, created_at) in dates {
+        update.execute(rusqlite::params![local_date_for(&created_at), id])?;
+    }
+    Ok(())
+}
+
+fn backfill_created_ms(conn: &Connection) -> rusqlite::Result<()> {
+    let dates = conn
+        .prepare("SELECT id, created_at FROM journal_entries")?
+        .query_map([], |row| Ok((row.get::<_, i32>(0)?, row.get::<_, String>(1)?)))?
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut update = conn.prepare("UPDATE journal_entries SET created_ms = ?1 WHERE id = ?2")?;
+    for (id, created_at) in dates {
+        update.execute(rusqlite::params![created_ms_for(&created_at), id])?;
+    }
+    Ok(())
+}
+
+fn backfill_uuids(conn: &Connection) -> rusqlite::Result<()> {
+    let ids = conn
+        .prepare("SELECT id FROM journal_entries WHERE uuid IS NULL")?
+        .query_map([], |row| row.get::<_, i32>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut update = conn.prepare(
+        "UPDATE journal_entries SET uuid = ?1, updated_at = COALESCE(updated_at, created_at) WHERE id = ?2",
+    )?;
+    for id in ids {
+        update.execute(rusqlite::params![Uuid::new_v4().to_string(), id])?;
+    }
+    Ok(())
+}
+
+fn split_attachment_blobs(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS attachment_blobs (
+            hash TEXT PRIMARY KEY,
+            size INTEGER NOT NULL,
+            data BLOB NOT NULL
+        );
+        ALTER TABLE attachments ADD COLUMN hash TEXT;",
+    )?;
+    let attachments = conn
+        .prepare("SELECT id, data FROM attachments")?
+        .query_map([], |row| Ok((row.get::<_, i32>(0)?, row.get::<_, Vec<u8>>(1)?)))?
+        .collect::<Result<Vec<_>, _>>()?;
+    for (id, data) in attachments {
+        let hash = content_hash(&data);
+        conn.execute(
+            "INSERT OR IGNORE INTO attachment_blobs (hash, size, data) VALUES (?1, ?2, ?3)",
+            rusqlite::params![hash, data.len() as i64, data],
+        )?;
+        conn.execute("UPDATE attachments SET hash = ?1 WHERE id = ?2", rusqlite::params![hash, id])?;
+    }
+    conn.execute_batch(
+        "ALTER TABLE attachments DROP COLUMN data;
+        CREATE INDEX IF NOT EXISTS idx_attachments_hash ON attachments(hash);",
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::create_base_schema;
+    use crate::DatabaseManager;
+
+    #[test]
+    fn test_run_brings_schema_up_to_date_once() {
+        let db = DatabaseManager::open_in_memory().unwrap();
+        let version: i64 = db.conn.query_row("PRAGMA user_version", [], |row| row.get(0)).unwrap();
+        assert_eq!(version, SCHEMA_VERSION);
+        // A second run finds nothing pending
+        run(&db.conn).unwrap();
+        let again: i64 = db.conn.query_row("PRAGMA user_version", [], |row| row.get(0)).unwrap();
+        assert_eq!(again, version);
+        let application_id: i32 = db.conn.query_row("PRAGMA application_id", [], |row| row.get(0)).unwrap();
+        assert_eq!(application_id, crate::APPLICATION_ID);
+    }
+
+    #[test]
+    fn test_version_counts_text_edits() {
+        let db = DatabaseManager::open_in_memory().unwrap();
+        let id = crate::insert_entry(&db.conn, "Monday", "", "2024-03-01T12:00:00+00:00").unwrap();
+        let version = || -> i64 {
+            db.conn.query_row("SELECT version FROM journal_entries WHERE id = ?1", [id], |row| row.get(0)).unwrap()
+        };
+        assert_eq!(version(), 1);
+        db.conn.execute("UPDATE journal_entries SET body = '<p>Hi</p>' WHERE id = ?1", [id]).unwrap();
+        db.conn.execute("UPDATE journal_entries SET title = 'Tuesday' WHERE id = ?1", [id]).unwrap();
+        assert_eq!(version(), 3);
+        // Other columns aren't the text anyone is editing
+        db.conn.execute("UPDATE journal_entries SET mood_score = 4 WHERE id = ?1", [id]).unwrap();
+        assert_eq!(version(), 3);
+    }
+
+    #[test]
+    fn test_split_attachment_blobs_shares_identical_bytes() {
+        let conn = Connection::open_in_memory().unwrap();
+        create_base_schema(&conn).unwrap();
+        for migration in &MIGRATIONS[..16] {
+            match migration {
+                Migration::Sql(sql) => conn.execute_batch(sql).unwrap(),
+                Migration::Code(apply) => apply(&conn).unwrap(),
+            }
+        }
+        conn.execute_batch(
+            "INSERT INTO journal_entries (id, title, body, created_at) VALUES (1, 'a', '', ''), (2, 'b', '', '');
+             INSERT INTO attachments (entry_id, kind, mime, size, data, created_at)
+             VALUES (1, 'audio', 'audio/webm', 3, x'010203', ''), (2, 'audio', 'audio/webm', 3, x'010203', '');",
+        )
+        .unwrap();
+
+        split_attachment_blobs(&conn).unwrap();
+        let blobs: i64 = conn.query_row("SELECT COUNT(*) FROM attachment_blobs", [], |row| row.get(0)).unwrap();
+        assert_eq!(blobs, 1);
+        let hashes: i64 = conn
+            .query_row("SELECT COUNT(*) FROM attachments WHERE hash = ?1", [content_hash(&[1, 2, 3])], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(hashes, 2);
+    }
+}
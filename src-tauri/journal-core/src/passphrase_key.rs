@@ -0,0 +1,112 @@
+use argon2::Argon2;
+use log::{debug, info};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::Path;
+use uuid::Uuid;
+
+use crate::keychain::KeychainError;
+
+// The database key for desktops with no system keychain, such as a headless
+// Linux box without a Secret Service. The key is stretched from a passphrase
+// the user enters each launch, so nothing secret is stored; the profile folder
+// only keeps the salt and a hash of the key, to tell a mistyped passphrase
+// from a wrong one before the database is touched.
+
+const FILE_NAME: &str = "journal.passphrase.json";
+const MIN_PASSPHRASE_CHARS: usize = 8;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct PassphraseKeyFile {
+    /// Hex-encoded Argon2 salt.
+    salt: String,
+    /// Hex SHA-256 of the derived key.
+    check: String,
+}
+
+fn check(key: &str) -> String {
+    hex::encode(Sha256::digest(key.as_bytes()))
+}
+
+fn derive(passphrase: &str, salt: &[u8]) -> Result<String, KeychainError> {
+    let mut key = [0u8; 32];
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| KeychainError::KeyGeneration(e.to_string()))?;
+    Ok(hex::encode(key))
+}
+
+/// Whether the profile in `dir` is keyed by a passphrase.
+pub(crate) fn is_set_up_in(dir: &Path) -> bool {
+    dir.join(FILE_NAME).exists()
+}
+
+/// Starts keying the profile in `dir` by `passphrase`, returning the key.
+pub(crate) fn set_up_in(dir: &Path, passphrase: &str) -> Result<String, KeychainError> {
+    if passphrase.chars().count() < MIN_PASSPHRASE_CHARS {
+        return Err(KeychainError::PassphraseTooShort(MIN_PASSPHRASE_CHARS));
+    }
+    let salt = Uuid::new_v4();
+    let key = derive(passphrase, salt.as_bytes())?;
+    let file = PassphraseKeyFile {
+        salt: hex::encode(salt.as_bytes()),
+        check: check(&key),
+    };
+    let contents = serde_json::to_string_pretty(&file).map_err(|e| KeychainError::FileError(e.to_string()))?;
+    fs::create_dir_all(dir).map_err(|e| KeychainError::FileError(e.to_string()))?;
+    fs::write(dir.join(FILE_NAME), contents).map_err(|e| KeychainError::FileError(e.to_string()))?;
+    info!("Keyed the journal by a passphrase");
+    Ok(key)
+}
+
+/// The key `passphrase` gives the profile in `dir`, if it's the right one.
+pub(crate) fn unlock_in(dir: &Path, passphrase: &str) -> Result<String, KeychainError> {
+    let contents = fs::read_to_string(dir.join(FILE_NAME)).map_err(|e| KeychainError::FileError(e.to_string()))?;
+    let file: PassphraseKeyFile =
+        serde_json::from_str(&contents).map_err(|e| KeychainError::FileError(e.to_string()))?;
+    let salt = hex::decode(&file.salt).map_err(|e| KeychainError::FileError(e.to_string()))?;
+    let key = derive(passphrase, &salt)?;
+    if check(&key) != file.check {
+        debug!("Passphrase did not match");
+        return Err(KeychainError::WrongPassphrase);
+    }
+    Ok(key)
+}
+
+/// Stops keying the profile in `dir` by a passphrase.
+pub(crate) fn remove_in(dir: &Path) -> Result<(), KeychainError> {
+    match fs::remove_file(dir.join(FILE_NAME)) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(KeychainError::FileError(e.to_string())),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_up_and_unlock() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(!is_set_up_in(dir.path()));
+        assert!(matches!(set_up_in(dir.path(), "short"), Err(KeychainError::PassphraseTooShort(8))));
+
+        let key = set_up_in(dir.path(), "correct horse").unwrap();
+        assert!(is_set_up_in(dir.path()));
+        assert_eq!(key.len(), 64);
+        assert_eq!(unlock_in(dir.path(), "correct horse").unwrap(), key);
+        assert!(matches!(unlock_in(dir.path(), "battery staple"), Err(KeychainError::WrongPassphrase)));
+
+        remove_in(dir.path()).unwrap();
+        assert!(!is_set_up_in(dir.path()));
+        remove_in(dir.path()).unwrap();
+    }
+
+    #[test]
+    fn test_salt_differs_per_set_up() {
+        let a = tempfile::tempdir().unwrap();
+        let b = tempfile::tempdir().unwrap();
+        assert_ne!(set_up_in(a.path(), "correct horse").unwrap(), set_up_in(b.path(), "correct horse").unwrap());
+    }
+}
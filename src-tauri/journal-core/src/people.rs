@@ -0,0 +1,130 @@
+use crate::text::{is_stop_word, strip_html};
+
+const MAX_NAME_WORDS: usize = 3;
+const MAX_NAME_CHARS: usize = 100;
+
+/// Capitalized words that are rarely people.
+const NOT_NAMES: &[&str] = &[
+    "i", "im", "ive", "id", "ill", "mr", "mrs", "ms", "dr", "monday", "tuesday", "wednesday", "thursday",
+    "friday", "saturday", "sunday", "january", "february", "march", "april", "may", "june", "july", "august",
+    "september", "october", "november", "december", "christmas", "easter", "ok", "okay", "yes", "no", "oh",
+    "god", "tv", "today", "tomorrow", "yesterday", "tonight",
+];
+
+/// A word as written, without surrounding punctuation or a possessive `'s`.
+fn clean(word: &str) -> &str {
+    let word = word.trim_matches(|c: char| !c.is_alphanumeric());
+    word.strip_suffix("'s").or_else(|| word.strip_suffix("’s")).unwrap_or(word)
+}
+
+fn is_name_word(word: &str) -> bool {
+    let starts_upper = word.chars().next().is_some_and(char::is_uppercase);
+    let lower = word.to_lowercase().replace(['\'', '’'], "");
+    starts_upper
+        && word.chars().count() > 1
+        && word.chars().all(|c| c.is_alphabetic() || c == '-' || c == '\'' || c == '’')
+        && !is_stop_word(&lower)
+        && !NOT_NAMES.contains(&lower.as_str())
+}
+
+fn push_unique(people: &mut Vec<String>, name: String) {
+    if !name.is_empty()
+        && name.chars().count() <= MAX_NAME_CHARS
+        && !people.iter().any(|p| p.eq_ignore_ascii_case(&name))
+    {
+        people.push(name);
+    }
+}
+
+/// Finds the people an HTML body mentions, in order of first appearance:
+/// `@name` mentions, and runs of up to three capitalized words ("Sam",
+/// "Aunt Jo"). A single capitalized word that starts a sentence is skipped,
+/// since that's usually just how the sentence begins.
+pub fn parse_people(body: &str) -> Vec<String> {
+    let text = strip_html(body);
+    let mut people = Vec::new();
+    for sentence in text.split(['.', '!', '?', '\n', ':', ';', '"', '“', '”']) {
+        let mut run: Vec<&str> = Vec::new();
+        let mut run_starts_sentence = false;
+        let finish = |run: &mut Vec<&str>, starts_sentence: bool, people: &mut Vec<String>| {
+            if run.len() > 1 || (run.len() == 1 && !starts_sentence) {
+                push_unique(people, run.join(" "));
+            }
+            run.clear();
+        };
+        for (i, raw) in sentence.split_whitespace().enumerate() {
+            if let Some(mention) = raw.strip_prefix('@') {
+                finish(&mut run, run_starts_sentence, &mut people);
+                let mention = mention.trim_end_matches(|c: char| !c.is_alphanumeric());
+                if mention.chars().all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.')) {
+                    push_unique(&mut people, mention.to_string());
+                }
+                continue;
+            }
+            let word = clean(raw);
+            if is_name_word(word) && run.len() < MAX_NAME_WORDS {
+                if run.is_empty() {
+                    run_starts_sentence = i == 0;
+                }
+                run.push(word);
+            } else {
+                finish(&mut run, run_starts_sentence, &mut people);
+            }
+            // Punctuation around a word (or a possessive) ends the name
+            if word.len() < raw.len() {
+                finish(&mut run, run_starts_sentence, &mut people);
+            }
+        }
+        finish(&mut run, run_starts_sentence, &mut people);
+    }
+    people
+}
+
+/// Replaces the people an entry mentions with those found in `body`, and
+/// forgets anyone no entry mentions any more.
+pub fn update_people(conn: &rusqlite::Connection, entry_id: i32, body: &str) -> rusqlite::Result<()> {
+    conn.execute("DELETE FROM entry_people WHERE entry_id = ?1", rusqlite::params![entry_id])?;
+    let mut insert_person = conn.prepare("INSERT OR IGNORE INTO people (name) VALUES (?1)")?;
+    let mut insert_mention = conn.prepare(
+        "INSERT OR IGNORE INTO entry_people (entry_id, person_id) SELECT ?1, id FROM people WHERE name = ?2",
+    )?;
+    for name in parse_people(body) {
+        insert_person.execute(rusqlite::params![name])?;
+        insert_mention.execute(rusqlite::params![entry_id, name])?;
+    }
+    conn.execute("DELETE FROM people WHERE id NOT IN (SELECT person_id FROM entry_people)", [])?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{insert_entry, timestamp_now, DatabaseManager};
+
+    #[test]
+    fn test_parse_people() {
+        let body = "<p>Lunch with Aunt Jo and Sam's kids on Friday, then @maria_k called.</p>\
+                    <p>Sam was late. Weather was grim! I met Ana Lima; ana lima was great.</p>";
+        assert_eq!(parse_people(body), vec!["Aunt Jo", "Sam", "maria_k", "Ana Lima"]);
+    }
+
+    #[test]
+    fn test_sentence_starts_are_not_names() {
+        assert!(parse_people("<p>Coffee first. Then work.</p>").is_empty());
+        assert_eq!(parse_people("<p>Ben came over</p>"), Vec::<String>::new());
+        assert_eq!(parse_people("<p>Ben Ortiz came over</p>"), vec!["Ben Ortiz"]);
+    }
+
+    #[test]
+    fn test_update_people() {
+        let db = DatabaseManager::open_in_memory().unwrap();
+        let id = insert_entry(&db.conn, "Monday", "<p>Walked with Priya</p>", &timestamp_now()).unwrap();
+        let names = |conn: &rusqlite::Connection| -> Vec<String> {
+            let mut stmt = conn.prepare("SELECT name FROM people ORDER BY name").unwrap();
+            stmt.query_map([], |row| row.get(0)).unwrap().collect::<Result<_, _>>().unwrap()
+        };
+        assert_eq!(names(&db.conn), vec!["Priya"]);
+        update_people(&db.conn, id, "<p>Called @dad and priya</p>").unwrap();
+        assert_eq!(names(&db.conn), vec!["dad"]);
+    }
+}
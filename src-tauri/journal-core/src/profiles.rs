@@ -0,0 +1,441 @@
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+use crate::cipher::CipherParams;
+use crate::db::{app_support_dir, ErrorResponse, DATABASE_FILE_NAME};
+
+// Separate journals in one install, e.g. a private one kept apart from the
+// everyday one. Each profile has its own folder, holding its database and
+// settings, and its own keychain accounts, so it's unlocked on its own. The
+// default profile is the original journal at the top of the app support
+// folder, where it has always been. Which profile is open is remembered in
+// `profiles.json` next to it, along with any profile whose database has been
+// moved somewhere else, such as an external encrypted volume.
+
+pub const DEFAULT_PROFILE: &str = "Default";
+const PROFILES_DIR_NAME: &str = "Profiles";
+const PROFILES_FILE_NAME: &str = "profiles.json";
+const MAX_NAME_LENGTH: usize = 64;
+
+/// The open profile, once read from `profiles.json`.
+static ACTIVE: RwLock<Option<Profile>> = RwLock::new(None);
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Profile {
+    pub name: String,
+    /// Names the profile's folder and keychain accounts; empty for the default profile.
+    pub slug: String,
+    /// Where the profile's database lives, when it has been moved out of the
+    /// profile's folder.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub database_dir: Option<PathBuf>,
+    /// Whether the database is plain SQLite rather than encrypted, for a
+    /// journal kept on a volume that's encrypted already.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub unencrypted: bool,
+    /// SQLCipher parameters the database was written with, when they aren't
+    /// the defaults.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cipher: Option<CipherParams>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProfileSummary {
+    pub name: String,
+    pub active: bool,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct ProfileList {
+    /// Every profile but the default one.
+    profiles: Vec<Profile>,
+    /// Slug of the open profile; the default one when unset.
+    active: Option<String>,
+    /// Where the default profile's database lives, when it has been moved.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    default_database_dir: Option<PathBuf>,
+    /// Whether the default profile's database is unencrypted.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    default_unencrypted: bool,
+    /// The default profile's SQLCipher parameters, when they aren't the defaults.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    default_cipher: Option<CipherParams>,
+}
+
+impl Profile {
+    fn default_profile() -> Profile {
+        Profile {
+            name: DEFAULT_PROFILE.to_string(),
+            slug: String::new(),
+            database_dir: None,
+            unencrypted: false,
+            cipher: None,
+        }
+    }
+
+    pub fn is_default(&self) -> bool {
+        self.slug.is_empty()
+    }
+
+    /// The folder holding this profile's database and settings, under `root`.
+    fn dir_in(&self, root: &Path) -> PathBuf {
+        if self.is_default() {
+            root.to_path_buf()
+        } else {
+            root.join(PROFILES_DIR_NAME).join(&self.slug)
+        }
+    }
+
+    /// The folder holding this profile's database, under `root` unless it has
+    /// been moved.
+    fn database_dir_in(&self, root: &Path) -> PathBuf {
+        self.database_dir.clone().unwrap_or_else(|| self.dir_in(root))
+    }
+
+    /// The keychain account this profile stores `account` under.
+    pub fn keychain_account(&self, account: &str) -> String {
+        if self.is_default() {
+            account.to_string()
+        } else {
+            format!("{}.{}", account, self.slug)
+        }
+    }
+}
+
+impl ProfileList {
+    fn default_profile(&self) -> Profile {
+        Profile {
+            database_dir: self.default_database_dir.clone(),
+            unencrypted: self.default_unencrypted,
+            cipher: self.default_cipher,
+            ..Profile::default_profile()
+        }
+    }
+}
+
+fn file_error(message: String) -> ErrorResponse {
+    ErrorResponse {
+        message,
+        error_type: "file_error".to_string(),
+    }
+}
+
+/// Lowercase letters, digits and dashes from `name`, for folder and account names.
+fn slug(name: &str) -> String {
+    let mut slug = String::new();
+    for c in name.chars().flat_map(char::to_lowercase) {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    slug.trim_end_matches('-').to_string()
+}
+
+fn load(root: &Path) -> ProfileList {
+    let path = root.join(PROFILES_FILE_NAME);
+    match fs::read_to_string(&path) {
+        Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|e| {
+            warn!("Ignoring invalid profiles file {:?}: {}", path, e);
+            ProfileList::default()
+        }),
+        Err(_) => ProfileList::default(),
+    }
+}
+
+fn save(root: &Path, list: &ProfileList) -> Result<(), ErrorResponse> {
+    fs::create_dir_all(root).map_err(|e| file_error(format!("Failed to create profiles folder: {}", e)))?;
+    let contents = serde_json::to_string_pretty(list).map_err(|e| e.to_string())?;
+    fs::write(root.join(PROFILES_FILE_NAME), contents)
+        .map_err(|e| file_error(format!("Failed to save profiles: {}", e)))
+}
+
+fn find(list: &ProfileList, name: &str) -> Option<Profile> {
+    if name.trim().eq_ignore_ascii_case(DEFAULT_PROFILE) {
+        return Some(list.default_profile());
+    }
+    list.profiles.iter().find(|profile| profile.name.eq_ignore_ascii_case(name.trim())).cloned()
+}
+
+fn active_in(root: &Path) -> Profile {
+    let list = load(root);
+    list.active
+        .as_deref()
+        .and_then(|slug| list.profiles.iter().find(|profile| profile.slug == slug))
+        .cloned()
+        .unwrap_or_else(|| list.default_profile())
+}
+
+fn list_in(root: &Path) -> Vec<ProfileSummary> {
+    let active = active_in(root);
+    let list = load(root);
+    std::iter::once(list.default_profile())
+        .chain(list.profiles)
+        .map(|profile| ProfileSummary {
+            active: profile == active,
+            name: profile.name,
+        })
+        .collect()
+}
+
+fn create_in(root: &Path, name: &str) -> Result<Profile, ErrorResponse> {
+    let name = name.trim();
+    let slug = slug(name);
+    if slug.is_empty() || name.chars().count() > MAX_NAME_LENGTH {
+        return Err(format!("\"{}\" can't be used as a profile name", name).into());
+    }
+    let mut list = load(root);
+    if find(&list, name).is_some() || list.profiles.iter().any(|profile| profile.slug == slug) {
+        return Err(format!("There's already a profile called {}", name).into());
+    }
+    let profile = Profile {
+        name: name.to_string(),
+        slug,
+        database_dir: None,
+        unencrypted: false,
+        cipher: None,
+    };
+    fs::create_dir_all(profile.dir_in(root))
+        .map_err(|e| file_error(format!("Failed to create profile folder: {}", e)))?;
+    list.profiles.push(profile.clone());
+    save(root, &list)?;
+    debug!("Created profile {}", profile.name);
+    Ok(profile)
+}
+
+fn switch_in(root: &Path, name: &str) -> Result<Profile, ErrorResponse> {
+    let mut list = load(root);
+    let profile = find(&list, name).ok_or_else(|| format!("There's no profile called {}", name.trim()))?;
+    list.active = Some(profile.slug.clone()).filter(|slug| !slug.is_empty());
+    save(root, &list)?;
+    Ok(profile)
+}
+
+/// Moves where `profile` keeps its database to `dir`, or back to its own
+/// folder when `dir` is that folder.
+fn set_database_dir_in(root: &Path, profile: &Profile, dir: &Path) -> Result<Profile, ErrorResponse> {
+    let dir = dir
+        .canonicalize()
+        .map_err(|e| file_error(format!("Can't use {:?} for the journal: {}", dir, e)))?;
+    let own_dir = profile.dir_in(root);
+    let database_dir = Some(dir).filter(|dir| own_dir.canonicalize().map_or(true, |own_dir| *dir != own_dir));
+    replace_in(
+        root,
+        Profile {
+            database_dir,
+            ..profile.clone()
+        },
+    )
+}
+
+/// Records whether `profile`'s database is encrypted, and with which SQLCipher parameters.
+fn set_encryption_in(
+    root: &Path,
+    profile: &Profile,
+    unencrypted: bool,
+    cipher: CipherParams,
+) -> Result<Profile, ErrorResponse> {
+    replace_in(
+        root,
+        Profile {
+            unencrypted,
+            cipher: Some(cipher).filter(|cipher| *cipher != CipherParams::default()),
+            ..profile.clone()
+        },
+    )
+}
+
+/// Stores `updated` in place of the profile with its slug.
+fn replace_in(root: &Path, updated: Profile) -> Result<Profile, ErrorResponse> {
+    let mut list = load(root);
+    if updated.is_default() {
+        list.default_database_dir = updated.database_dir.clone();
+        list.default_unencrypted = updated.unencrypted;
+        list.default_cipher = updated.cipher;
+    } else {
+        let stored = list
+            .profiles
+            .iter_mut()
+            .find(|stored| stored.slug == updated.slug)
+            .ok_or_else(|| format!("There's no profile called {}", updated.name))?;
+        *stored = updated.clone();
+    }
+    save(root, &list)?;
+    Ok(updated)
+}
+
+/// The profile the app has open.
+pub fn active_profile() -> Profile {
+    if let Some(profile) = ACTIVE.read().ok().and_then(|active| active.clone()) {
+        return profile;
+    }
+    let profile = app_support_dir().map(|root| active_in(&root)).unwrap_or_else(|e| {
+        warn!("Could not read profiles, using the default one: {}", e);
+        Profile::default_profile()
+    });
+    if let Ok(mut active) = ACTIVE.write() {
+        *active = Some(profile.clone());
+    }
+    profile
+}
+
+/// The open profile's folder, which holds its database and settings.
+pub fn profile_dir() -> Result<PathBuf, ErrorResponse> {
+    Ok(active_profile().dir_in(&app_support_dir()?))
+}
+
+/// The folder holding the open profile's database.
+pub fn database_dir() -> Result<PathBuf, ErrorResponse> {
+    Ok(active_profile().database_dir_in(&app_support_dir()?))
+}
+
+/// The open profile's database file.
+pub fn database_path() -> Result<PathBuf, ErrorResponse> {
+    Ok(database_dir()?.join(DATABASE_FILE_NAME))
+}
+
+/// Records that the open profile's database now lives in `dir`. This only
+/// changes where it's looked for; `DatabaseManager::relocate` moves it.
+pub fn set_database_dir(dir: &Path) -> Result<Profile, ErrorResponse> {
+    let profile = set_database_dir_in(&app_support_dir()?, &active_profile(), dir)?;
+    if let Ok(mut active) = ACTIVE.write() {
+        *active = Some(profile.clone());
+    }
+    Ok(profile)
+}
+
+/// Records whether the open profile's database is encrypted, and how. Only
+/// `DatabaseManager` should call this, once the database itself has been
+/// rewritten to match.
+pub(crate) fn set_encryption(unencrypted: bool, cipher: CipherParams) -> Result<Profile, ErrorResponse> {
+    let profile = set_encryption_in(&app_support_dir()?, &active_profile(), unencrypted, cipher)?;
+    if let Ok(mut active) = ACTIVE.write() {
+        *active = Some(profile.clone());
+    }
+    Ok(profile)
+}
+
+pub fn list_profiles() -> Result<Vec<ProfileSummary>, ErrorResponse> {
+    Ok(list_in(&app_support_dir()?))
+}
+
+/// Adds an empty profile. Its database and key are created the first time
+/// it's opened.
+pub fn create_profile(name: &str) -> Result<Profile, ErrorResponse> {
+    create_in(&app_support_dir()?, name)
+}
+
+/// Makes `name` the open profile, now and on later launches. Connections and
+/// keys already in use belong to the previous profile, so callers should drop
+/// them; the key cached for this process is forgotten here.
+pub fn switch_profile(name: &str) -> Result<Profile, ErrorResponse> {
+    let profile = switch_in(&app_support_dir()?, name)?;
+    crate::keychain::KeychainManager::forget_cached_key();
+    if let Ok(mut active) = ACTIVE.write() {
+        *active = Some(profile.clone());
+    }
+    Ok(profile)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slug() {
+        assert_eq!(slug("Therapy Notes!"), "therapy-notes");
+        assert_eq!(slug("  Été 2024 "), "t-2024");
+        assert_eq!(slug("***"), "");
+    }
+
+    #[test]
+    fn test_create_and_switch() {
+        let root = tempfile::tempdir().unwrap();
+        let root = root.path();
+        assert_eq!(active_in(root), Profile::default_profile());
+
+        let therapy = create_in(root, " Therapy ").unwrap();
+        assert_eq!(therapy.slug, "therapy");
+        assert!(root.join("Profiles/therapy").is_dir());
+        assert!(create_in(root, "therapy").is_err());
+        assert!(create_in(root, "default").is_err());
+        assert!(create_in(root, "!!").is_err());
+
+        switch_in(root, "THERAPY").unwrap();
+        assert_eq!(active_in(root), therapy);
+        let names: Vec<(String, bool)> = list_in(root).into_iter().map(|p| (p.name, p.active)).collect();
+        assert_eq!(names, vec![("Default".to_string(), false), ("Therapy".to_string(), true)]);
+
+        switch_in(root, "Default").unwrap();
+        assert!(active_in(root).is_default());
+        assert!(switch_in(root, "Work").is_err());
+    }
+
+    #[test]
+    fn test_profile_locations() {
+        let root = Path::new("/support");
+        let therapy = Profile {
+            name: "Therapy".to_string(),
+            slug: "therapy".to_string(),
+            database_dir: None,
+            unencrypted: false,
+            cipher: None,
+        };
+        assert_eq!(Profile::default_profile().dir_in(root), root);
+        assert_eq!(therapy.dir_in(root), root.join("Profiles").join("therapy"));
+        assert_eq!(Profile::default_profile().keychain_account("sync"), "sync");
+        assert_eq!(therapy.keychain_account("sync"), "sync.therapy");
+    }
+
+    #[test]
+    fn test_moved_database_dir() {
+        let root = tempfile::tempdir().unwrap();
+        let root = root.path();
+        let volume = tempfile::tempdir().unwrap();
+        let therapy = create_in(root, "Therapy").unwrap();
+
+        let moved = set_database_dir_in(root, &therapy, volume.path()).unwrap();
+        let volume_dir = volume.path().canonicalize().unwrap();
+        assert_eq!(moved.database_dir_in(root), volume_dir);
+        switch_in(root, "Therapy").unwrap();
+        assert_eq!(active_in(root), moved);
+        // The default profile's database stays where it was
+        assert_eq!(find(&load(root), DEFAULT_PROFILE).unwrap().database_dir_in(root), root);
+
+        let default = set_database_dir_in(root, &Profile::default_profile(), volume.path()).unwrap();
+        assert_eq!(find(&load(root), DEFAULT_PROFILE), Some(default));
+
+        let back = set_database_dir_in(root, &moved, &root.join("Profiles/therapy")).unwrap();
+        assert_eq!(back.database_dir, None);
+        assert_eq!(active_in(root).database_dir_in(root), root.join("Profiles/therapy"));
+        assert!(set_database_dir_in(root, &moved, &volume.path().join("missing")).is_err());
+    }
+
+    #[test]
+    fn test_encryption_is_kept_per_profile() {
+        let root = tempfile::tempdir().unwrap();
+        let root = root.path();
+        let therapy = create_in(root, "Therapy").unwrap();
+        let defaults = CipherParams::default();
+
+        let plain = set_encryption_in(root, &Profile::default_profile(), true, defaults).unwrap();
+        assert!(plain.unencrypted);
+        assert_eq!(plain.cipher, None);
+        assert_eq!(find(&load(root), DEFAULT_PROFILE), Some(plain));
+        assert!(!find(&load(root), "Therapy").unwrap().unencrypted);
+
+        let raised = CipherParams {
+            kdf_iter: 1_000_000,
+            ..defaults
+        };
+        set_encryption_in(root, &therapy, false, raised).unwrap();
+        set_encryption_in(root, &Profile::default_profile(), false, defaults).unwrap();
+        assert_eq!(find(&load(root), "Therapy").unwrap().cipher, Some(raised));
+        assert!(!find(&load(root), DEFAULT_PROFILE).unwrap().unencrypted);
+    }
+}
@@ -0,0 +1,145 @@
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::keychain::KeychainError;
+
+// Recovery keys: the database key written out so it can be typed back in on
+// a machine whose keychain never held it. The key is packed as tightly as its
+// kind allows (a keychain UUID, a passphrase-derived hex key, or any other
+// string from an old key file), followed by a short checksum so typos are
+// caught, then spelled in Crockford base32 in dash-separated groups of four.
+
+const ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+const GROUP_LEN: usize = 4;
+const CHECKSUM_LEN: usize = 2;
+
+const KIND_TEXT: u8 = 0;
+const KIND_UUID: u8 = 1;
+const KIND_HEX: u8 = 2;
+
+fn checksum(payload: &[u8]) -> [u8; CHECKSUM_LEN] {
+    let digest = Sha256::digest(payload);
+    [digest[0], digest[1]]
+}
+
+fn to_base32(bytes: &[u8]) -> String {
+    let mut out = String::new();
+    let (mut buffer, mut bits) = (0u32, 0);
+    for &byte in bytes {
+        buffer = (buffer << 8) | byte as u32;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            out.push(ALPHABET[((buffer >> bits) & 31) as usize] as char);
+        }
+    }
+    if bits > 0 {
+        out.push(ALPHABET[((buffer << (5 - bits)) & 31) as usize] as char);
+    }
+    out
+}
+
+fn from_base32(text: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::new();
+    let (mut buffer, mut bits) = (0u32, 0);
+    for c in text.chars() {
+        // Crockford base32 reads the letters people confuse with digits as those digits
+        let c = match c.to_ascii_uppercase() {
+            'O' => '0',
+            'I' | 'L' => '1',
+            c => c,
+        };
+        let value = ALPHABET.iter().position(|&a| a as char == c)? as u32;
+        buffer = (buffer << 5) | value;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((buffer >> bits) as u8);
+        }
+    }
+    Some(out)
+}
+
+fn pack(key: &str) -> Vec<u8> {
+    if let Ok(uuid) = Uuid::parse_str(key) {
+        if uuid.hyphenated().to_string() == key {
+            return [&[KIND_UUID][..], uuid.as_bytes()].concat();
+        }
+    }
+    if key.len() == 64 && key.bytes().all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b)) {
+        if let Ok(bytes) = hex::decode(key) {
+            return [&[KIND_HEX][..], &bytes[..]].concat();
+        }
+    }
+    [&[KIND_TEXT][..], key.as_bytes()].concat()
+}
+
+fn unpack(payload: &[u8]) -> Option<String> {
+    let (&kind, bytes) = payload.split_first()?;
+    match kind {
+        KIND_UUID => Some(Uuid::from_slice(bytes).ok()?.hyphenated().to_string()),
+        KIND_HEX if bytes.len() == 32 => Some(hex::encode(bytes)),
+        KIND_TEXT => String::from_utf8(bytes.to_vec()).ok(),
+        _ => None,
+    }
+}
+
+/// The recovery key for database key `key`.
+pub(crate) fn encode(key: &str) -> String {
+    let payload = pack(key);
+    let encoded = to_base32(&[&payload[..], &checksum(&payload)].concat());
+    let groups: Vec<String> = encoded
+        .as_bytes()
+        .chunks(GROUP_LEN)
+        .map(|group| String::from_utf8_lossy(group).into_owned())
+        .collect();
+    groups.join("-")
+}
+
+/// The database key a recovery key was made from. Spaces, dashes and case
+/// don't matter.
+pub(crate) fn decode(recovery_key: &str) -> Result<String, KeychainError> {
+    let invalid = || KeychainError::InvalidRecoveryKey;
+    let compact: String = recovery_key.chars().filter(|c| !c.is_whitespace() && *c != '-').collect();
+    let bytes = from_base32(&compact).ok_or_else(invalid)?;
+    if bytes.len() <= CHECKSUM_LEN {
+        return Err(invalid());
+    }
+    let (payload, sum) = bytes.split_at(bytes.len() - CHECKSUM_LEN);
+    if sum != checksum(payload) {
+        return Err(invalid());
+    }
+    unpack(payload).ok_or_else(invalid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trips_every_kind_of_key() {
+        let uuid = "0f8e2c1a-3b4d-4e5f-8a9b-0c1d2e3f4a5b";
+        let hex_key = "ab".repeat(32);
+        for key in [uuid, hex_key.as_str(), "an old key file\n"] {
+            assert_eq!(decode(&encode(key)).unwrap(), key);
+        }
+        assert_eq!(encode(uuid).len(), 38);
+    }
+
+    #[test]
+    fn test_decode_forgives_formatting() {
+        let key = "0f8e2c1a-3b4d-4e5f-8a9b-0c1d2e3f4a5b";
+        let recovery_key = encode(key).to_lowercase().replace('-', " ").replace('0', "o");
+        assert_eq!(decode(&recovery_key).unwrap(), key);
+    }
+
+    #[test]
+    fn test_decode_catches_typos() {
+        let recovery_key = encode("0f8e2c1a-3b4d-4e5f-8a9b-0c1d2e3f4a5b");
+        let mut typo: Vec<char> = recovery_key.chars().collect();
+        typo[2] = if typo[2] == 'X' { 'Y' } else { 'X' };
+        assert!(decode(&typo.into_iter().collect::<String>()).is_err());
+        assert!(decode("").is_err());
+        assert!(decode("not a key!").is_err());
+    }
+}
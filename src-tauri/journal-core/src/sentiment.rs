@@ -0,0 +1,116 @@
+// A small lexicon-based sentiment scorer, in the spirit of AFINN and VADER.
+// It runs on every save, so it has to be fast and entirely on-device; it
+// knows a couple of hundred common words, flips words that follow a negation and
+// strengthens words after an intensifier. Good enough to chart how entries
+// feel over weeks, not to judge any single sentence.
+
+use crate::text::strip_html;
+
+/// Keeps a handful of strong words from pinning the score at the extremes.
+const NORMALIZATION: f64 = 15.0;
+/// How many words back a negation still applies.
+const NEGATION_WINDOW: usize = 3;
+const NEGATION_FACTOR: f64 = -0.75;
+const INTENSIFIER_FACTOR: f64 = 1.5;
+
+const NEGATIONS: &[&str] = &[
+    "not", "no", "never", "none", "nothing", "nobody", "neither", "nor", "cannot", "dont", "didnt", "doesnt",
+    "isnt", "wasnt", "werent", "arent", "wont", "cant", "couldnt", "shouldnt", "wouldnt", "hardly", "without",
+];
+
+const INTENSIFIERS: &[&str] = &[
+    "very", "really", "so", "extremely", "incredibly", "totally", "completely", "deeply", "truly", "super",
+];
+
+/// Word valences from -3 (very negative) to 3 (very positive).
+const LEXICON: &[(&str, i8)] = &[
+    ("amazing", 3), ("awesome", 3), ("beautiful", 3), ("blessed", 3), ("brilliant", 3), ("ecstatic", 3),
+    ("excellent", 3), ("fantastic", 3), ("incredible", 3), ("joy", 3), ("joyful", 3), ("love", 3),
+    ("loved", 3), ("loving", 3), ("perfect", 3), ("thrilled", 3), ("wonderful", 3), ("delighted", 3),
+    ("happy", 2), ("happiness", 2), ("glad", 2), ("grateful", 2), ("thankful", 2), ("excited", 2),
+    ("exciting", 2), ("fun", 2), ("great", 2), ("good", 2), ("proud", 2), ("relaxed", 2), ("calm", 2),
+    ("peaceful", 2), ("hopeful", 2), ("hope", 2), ("enjoyed", 2), ("enjoy", 2), ("laughed", 2),
+    ("laugh", 2), ("smile", 2), ("smiled", 2), ("success", 2), ("successful", 2), ("win", 2), ("won", 2),
+    ("confident", 2), ("inspired", 2), ("content", 2), ("relieved", 2), ("kind", 2), ("friendly", 2),
+    ("nice", 2), ("lovely", 2), ("cozy", 2), ("energized", 2), ("accomplished", 2), ("celebrate", 2),
+    ("celebrated", 2), ("fine", 1), ("okay", 1), ("ok", 1), ("better", 1), ("interesting", 1), ("liked", 1),
+    ("productive", 1), ("rested", 1), ("safe", 1), ("clear", 1), ("easy", 1),
+    ("tired", -1), ("bored", -1), ("boring", -1), ("meh", -1), ("busy", -1), ("confused", -1),
+    ("distracted", -1), ("late", -1), ("hard", -1), ("difficult", -1), ("problem", -1), ("problems", -1),
+    ("worried", -2), ("worry", -2), ("anxious", -2), ("anxiety", -2), ("stress", -2), ("stressed", -2),
+    ("stressful", -2), ("sad", -2), ("upset", -2), ("angry", -2), ("annoyed", -2), ("frustrated", -2),
+    ("frustrating", -2), ("lonely", -2), ("hurt", -2), ("sick", -2), ("ill", -2), ("pain", -2),
+    ("afraid", -2), ("scared", -2), ("nervous", -2), ("disappointed", -2), ("disappointing", -2),
+    ("exhausted", -2), ("overwhelmed", -2), ("bad", -2), ("cried", -2), ("cry", -2), ("crying", -2),
+    ("fail", -2), ("failed", -2), ("failure", -2), ("guilty", -2), ("ashamed", -2), ("regret", -2),
+    ("lost", -2), ("argument", -2), ("fight", -2), ("sorry", -1), ("miss", -1), ("missed", -1),
+    ("awful", -3), ("terrible", -3), ("horrible", -3), ("hate", -3), ("hated", -3), ("miserable", -3),
+    ("depressed", -3), ("devastated", -3), ("heartbroken", -3), ("furious", -3), ("panic", -3),
+    ("worst", -3), ("hopeless", -3), ("grief", -3),
+];
+
+fn valence(word: &str) -> Option<f64> {
+    LEXICON.iter().find(|(w, _)| *w == word).map(|(_, v)| f64::from(*v))
+}
+
+/// Sentiment of plain text from -1 (negative) to 1 (positive), or `None` if
+/// none of its words carry any.
+pub fn text_sentiment(text: &str) -> Option<f64> {
+    let words: Vec<String> = text
+        .to_lowercase()
+        .split(|c: char| !(c.is_alphanumeric() || c == '\'' || c == '’'))
+        .map(|word| word.replace(['\'', '’'], ""))
+        .filter(|word| !word.is_empty())
+        .collect();
+    let mut total = 0.0;
+    let mut scored = false;
+    for (i, word) in words.iter().enumerate() {
+        let Some(mut value) = valence(word) else {
+            continue;
+        };
+        scored = true;
+        if i > 0 && INTENSIFIERS.contains(&words[i - 1].as_str()) {
+            value *= INTENSIFIER_FACTOR;
+        }
+        let window = &words[i.saturating_sub(NEGATION_WINDOW)..i];
+        if window.iter().any(|w| NEGATIONS.contains(&w.as_str())) {
+            value *= NEGATION_FACTOR;
+        }
+        total += value;
+    }
+    scored.then(|| (total / (total * total + NORMALIZATION).sqrt()).clamp(-1.0, 1.0))
+}
+
+/// Sentiment of an HTML entry body.
+pub fn body_sentiment(body: &str) -> Option<f64> {
+    text_sentiment(&strip_html(body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_polarity() {
+        assert!(text_sentiment("What a wonderful, happy day").unwrap() > 0.5);
+        assert!(text_sentiment("Awful. I felt lonely and exhausted").unwrap() < -0.5);
+        assert_eq!(text_sentiment("Went to the shop for milk"), None);
+        assert_eq!(body_sentiment(""), None);
+    }
+
+    #[test]
+    fn test_negation_and_intensifiers() {
+        let happy = text_sentiment("I was happy").unwrap();
+        assert!(text_sentiment("I wasn't happy").unwrap() < 0.0);
+        assert!(text_sentiment("I wasn’t happy").unwrap() < 0.0);
+        assert!(text_sentiment("I was not at all happy").unwrap() < 0.0);
+        assert!(text_sentiment("I was very happy").unwrap() > happy);
+    }
+
+    #[test]
+    fn test_score_is_bounded() {
+        let gushing = "amazing ".repeat(100);
+        let score = text_sentiment(&gushing).unwrap();
+        assert!(score > 0.99 && score <= 1.0);
+    }
+}
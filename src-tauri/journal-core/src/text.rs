@@ -0,0 +1,119 @@
+// Entry bodies are stored as the editor's HTML; these helpers work on their text.
+
+/// Removes tags and decodes the handful of entities the editor emits,
+/// leaving a space where block-level markup used to separate words.
+pub fn strip_html(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => {
+                in_tag = false;
+                text.push(' ');
+            }
+            _ if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+    text.replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Counts whitespace-separated tokens that contain at least one letter or digit.
+pub fn count_words(text: &str) -> usize {
+    text.split_whitespace()
+        .filter(|token| token.chars().any(char::is_alphanumeric))
+        .count()
+}
+
+/// Word count of an HTML entry body.
+pub fn body_word_count(body: &str) -> i64 {
+    count_words(&strip_html(body)) as i64
+}
+
+/// Common English words that say little about what an entry is about, plus
+/// a few that journals are full of ("today", "really").
+const STOP_WORDS: &[&str] = &[
+    "a", "about", "above", "after", "again", "against", "all", "also", "am", "an", "and", "any", "are", "as",
+    "at", "be", "because", "been", "before", "being", "below", "between", "both", "but", "by", "can", "could",
+    "did", "do", "does", "doing", "don", "down", "during", "each", "even", "few", "for", "from", "further",
+    "get", "got", "had", "has", "have", "having", "he", "her", "here", "hers", "herself", "him", "himself",
+    "his", "how", "i", "if", "in", "into", "is", "it", "its", "itself", "just", "like", "me", "more", "most",
+    "much", "my", "myself", "no", "nor", "not", "now", "of", "off", "on", "once", "one", "only", "or",
+    "other", "our", "ours", "ourselves", "out", "over", "own", "really", "same", "she", "should", "so",
+    "some", "still", "such", "than", "that", "the", "their", "theirs", "them", "themselves", "then", "there",
+    "these", "they", "thing", "things", "this", "those", "through", "to", "today", "too", "under", "until",
+    "up", "us", "very", "was", "way", "we", "were", "what", "when", "where", "which", "while", "who", "whom",
+    "why", "will", "with", "would", "you", "your", "yours", "yourself", "yourselves",
+];
+
+/// Whether `word` (lowercase) is too common to say what a text is about.
+pub fn is_stop_word(word: &str) -> bool {
+    STOP_WORDS.contains(&word)
+}
+
+/// The start of an HTML body as one line of plain text, cut at a word
+/// boundary with an ellipsis if it runs past `max_chars`.
+pub fn snippet(html: &str, max_chars: usize) -> String {
+    let text = strip_html(html).split_whitespace().collect::<Vec<_>>().join(" ");
+    if text.chars().count() <= max_chars {
+        return text;
+    }
+    // One more than fits, to see whether the last word ends right at the cut.
+    // A word that doesn't fit is left out rather than split.
+    let cut: String = text.chars().take(max_chars + 1).collect();
+    let cut = match cut.rfind(' ') {
+        Some(space) if space > 0 => cut[..space].to_string(),
+        _ => text.chars().take(max_chars).collect(),
+    };
+    format!("{}…", cut.trim_end_matches(|c: char| c.is_ascii_punctuation()))
+}
+
+/// Plain text as editor paragraphs, one per line, for text that arrives from
+/// outside the editor.
+pub fn text_to_html(text: &str) -> String {
+    text.lines()
+        .map(|line| {
+            let escaped = line.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;");
+            format!("<p>{}</p>", escaped)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_html() {
+        assert_eq!(
+            strip_html("<p>Fish &amp; chips</p><p>tonight</p>").split_whitespace().collect::<Vec<_>>(),
+            vec!["Fish", "&", "chips", "tonight"]
+        );
+    }
+
+    #[test]
+    fn test_word_count_ignores_markup_and_punctuation() {
+        assert_eq!(body_word_count("<p>Hello,   world</p><ul><li>one - two</li></ul>"), 4);
+        assert_eq!(body_word_count(""), 0);
+    }
+
+    #[test]
+    fn test_snippet() {
+        assert_eq!(snippet("<p>Fish &amp; chips</p>\n<p>tonight</p>", 40), "Fish & chips tonight");
+        assert_eq!(snippet("<p>Trams, tiles and custard tarts</p>", 16), "Trams, tiles and…");
+        assert_eq!(snippet("<p>Trams, tiles and custard tarts</p>", 14), "Trams, tiles…");
+        assert_eq!(snippet("<p>Supercalifragilistic</p>", 5), "Super…");
+        assert_eq!(snippet("", 10), "");
+    }
+
+    #[test]
+    fn test_text_to_html_escapes_markup() {
+        assert_eq!(text_to_html("a < b\nfish & chips"), "<p>a &lt; b</p><p>fish &amp; chips</p>");
+    }
+}
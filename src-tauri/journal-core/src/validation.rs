@@ -0,0 +1,74 @@
+// Checks on text on its way into the journal. The editor can't produce most
+// of what's refused here, but the local API, imports, emailed notes, the
+// command line and other apps can, and a giant or garbled row is hard to get
+// rid of once it's in the database. Errors start with a code callers can tell
+// apart.
+
+/// How the error starts when a title is required and none was given.
+pub const EMPTY_TITLE: &str = "empty_title";
+/// How the error starts when a body or attachment is over its limit.
+pub const TOO_LARGE: &str = "too_large";
+/// Largest entry body saved, in MB, where no other limit is set.
+pub const DEFAULT_MAX_BODY_MB: u32 = 10;
+
+/// `text` without control characters, other than the tabs and line breaks
+/// that text legitimately holds.
+pub fn strip_control_chars(text: &str) -> String {
+    text.chars().filter(|c| !c.is_control() || matches!(c, '\n' | '\r' | '\t')).collect()
+}
+
+/// Refuses a title with nothing in it, for callers that need one.
+pub fn require_title(title: &str) -> Result<(), String> {
+    if strip_control_chars(title).trim().is_empty() {
+        return Err(format!("{}: an entry needs a title", EMPTY_TITLE));
+    }
+    Ok(())
+}
+
+/// Refuses `bytes` of `what` over `max_mb`; 0 turns the limit off.
+pub fn check_size(what: &str, bytes: usize, max_mb: u32) -> Result<(), String> {
+    if max_mb > 0 && bytes > max_mb as usize * 1024 * 1024 {
+        return Err(format!("{}: {} are limited to {} MB", TOO_LARGE, what, max_mb));
+    }
+    Ok(())
+}
+
+/// An entry body as it's saved: control characters stripped, and refused if
+/// it's over `max_mb`.
+pub fn clean_body(body: &str, max_mb: u32) -> Result<String, String> {
+    let body = strip_control_chars(body);
+    check_size("Entries", body.len(), max_mb)?;
+    Ok(body)
+}
+
+/// An entry's title and body as they're saved. An empty title is allowed;
+/// callers that need one check with `require_title`.
+pub fn clean_entry(title: &str, body: &str, max_body_mb: u32) -> Result<(String, String), String> {
+    Ok((strip_control_chars(title), clean_body(body, max_body_mb)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_control_chars() {
+        assert_eq!(strip_control_chars("Mon\u{0}day\u{7}\n\tnotes\r\n"), "Monday\n\tnotes\r\n");
+    }
+
+    #[test]
+    fn test_require_title() {
+        assert!(require_title("Monday").is_ok());
+        assert!(require_title(" \u{1b} ").unwrap_err().starts_with(EMPTY_TITLE));
+    }
+
+    #[test]
+    fn test_clean_entry() {
+        let (title, body) = clean_entry("Mon\u{0}day", "<p>Hi\u{8}</p>", 1).unwrap();
+        assert_eq!((title.as_str(), body.as_str()), ("Monday", "<p>Hi</p>"));
+        let huge = "a".repeat(1024 * 1024 + 1);
+        assert!(clean_entry("", &huge, 1).unwrap_err().starts_with(TOO_LARGE));
+        // 0 turns the limit off
+        assert!(clean_entry("", &huge, 0).is_ok());
+    }
+}
@@ -0,0 +1,507 @@
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::OsRng;
+use chrono::{Local, Utc};
+use log::{debug, error, info, warn};
+use rusqlite::OptionalExtension;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use tauri::{command, AppHandle, Emitter};
+
+use journal_core::keychain::KeychainManager;
+use journal_core::links::update_links;
+use journal_core::people::update_people;
+use journal_core::sentiment::body_sentiment;
+use journal_core::text::{body_word_count, text_to_html};
+use journal_core::insert_entry;
+
+use crate::search::search_entries;
+use crate::settings::Settings;
+use crate::validation::{clean_body, require_title, strip_control_chars};
+use crate::{
+    create_entry, get_entries, git_sync, load_entry, markdown_mirror, CreateEntryRequest, DatabaseManager, JournalEntry,
+};
+
+// An opt-in HTTP API on localhost for launchers and scripts (Raycast, Alfred,
+// shell one-liners). It only listens on the loopback interface and every
+// request must carry the bearer token from Settings, which lives in the
+// keychain. Requests are refused while the journal is locked rather than
+// triggering a keychain prompt on the user's behalf.
+//
+//   GET  /entries             entry summaries, newest first
+//   GET  /entries/today       summaries of entries written today
+//   GET  /entries/{id}        one full entry
+//   GET  /search?q=...        the search box query language
+//   POST /entries             {"title", "body", "created_at"?} -> {"id"}
+//   POST /entries/today       {"text"} appended to today's entry -> {"id"}
+//
+// Unlike the app, which starts entries untitled, the API refuses an entry
+// without a title, with an `empty_title` error.
+
+pub(crate) const TOKEN_ACCOUNT: &str = "journal_api_token";
+const TOKEN_BYTES: usize = 32;
+const MAX_HEADER_BYTES: u64 = 16 * 1024;
+const MAX_BODY_BYTES: usize = 1024 * 1024;
+const IO_TIMEOUT: Duration = Duration::from_secs(10);
+const ACCEPT_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+static SERVER: Mutex<Option<ApiServer>> = Mutex::new(None);
+
+struct ApiServer {
+    stop: Arc<AtomicBool>,
+}
+
+struct Request {
+    method: String,
+    path: String,
+    query: Vec<(String, String)>,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Request {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn query_param(&self, name: &str) -> Option<&str> {
+        self.query.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str())
+    }
+
+    fn json<T: for<'de> Deserialize<'de>>(&self) -> Result<T, ApiError> {
+        serde_json::from_slice(&self.body).map_err(|e| ApiError::bad_request(format!("Invalid JSON body: {}", e)))
+    }
+}
+
+struct ApiError {
+    status: u16,
+    message: String,
+}
+
+impl ApiError {
+    fn new(status: u16, message: impl Into<String>) -> Self {
+        ApiError { status, message: message.into() }
+    }
+
+    fn bad_request(message: impl Into<String>) -> Self {
+        ApiError::new(400, message)
+    }
+}
+
+impl From<String> for ApiError {
+    fn from(message: String) -> Self {
+        ApiError::bad_request(message)
+    }
+}
+
+#[derive(Deserialize)]
+struct AppendRequest {
+    text: String,
+}
+
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let hex = |i: usize| bytes.get(i).and_then(|&byte| (byte as char).to_digit(16));
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match (bytes[i], hex(i + 1), hex(i + 2)) {
+            (b'%', Some(high), Some(low)) => {
+                decoded.push((high * 16 + low) as u8);
+                i += 2;
+            }
+            (b'+', _, _) => decoded.push(b' '),
+            (byte, _, _) => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn parse_target(target: &str) -> (String, Vec<(String, String)>) {
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let query = query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (percent_decode(key), percent_decode(value))
+        })
+        .collect();
+    (path.trim_end_matches('/').to_string(), query)
+}
+
+fn read_request(stream: &TcpStream) -> Result<Request, ApiError> {
+    let mut reader = BufReader::new(stream.take(MAX_HEADER_BYTES));
+    let mut line = String::new();
+    reader
+        .read_line(&mut line)
+        .map_err(|_| ApiError::bad_request("Malformed request"))?;
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return Err(ApiError::bad_request("Malformed request"));
+    };
+    let (path, query) = parse_target(target);
+    let method = method.to_string();
+    let mut headers = Vec::new();
+    loop {
+        line.clear();
+        reader
+            .read_line(&mut line)
+            .map_err(|_| ApiError::bad_request("Malformed request"))?;
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        let (name, value) = header
+            .split_once(':')
+            .ok_or_else(|| ApiError::bad_request("Malformed header"))?;
+        headers.push((name.trim().to_string(), value.trim().to_string()));
+    }
+    let mut request = Request { method, path, query, headers, body: Vec::new() };
+    let length: usize = request
+        .header("Content-Length")
+        .map(|length| length.parse().map_err(|_| ApiError::bad_request("Invalid Content-Length")))
+        .transpose()?
+        .unwrap_or(0);
+    if length > MAX_BODY_BYTES {
+        return Err(ApiError::new(413, "Request body is too large"));
+    }
+    // Whatever the header reader buffered already belongs to the body
+    let mut body = reader.buffer().to_vec();
+    if body.len() < length {
+        let mut rest = vec![0u8; length - body.len()];
+        let mut stream = reader.into_inner().into_inner();
+        stream
+            .read_exact(&mut rest)
+            .map_err(|_| ApiError::bad_request("Truncated request body"))?;
+        body.extend(rest);
+    }
+    body.truncate(length);
+    request.body = body;
+    Ok(request)
+}
+
+fn write_response(mut stream: &TcpStream, status: u16, body: &Value) {
+    let reason = match status {
+        200 => "OK",
+        201 => "Created",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        413 => "Payload Too Large",
+        503 => "Service Unavailable",
+        _ => "Internal Server Error",
+    };
+    let body = body.to_string();
+    let response = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        reason,
+        body.len(),
+        body
+    );
+    if let Err(e) = stream.write_all(response.as_bytes()) {
+        debug!("Failed to write API response: {}", e);
+    }
+}
+
+/// Compares in constant time so response timing doesn't leak the token.
+fn token_matches(given: &str, expected: &str) -> bool {
+    given.len() == expected.len()
+        && given
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+fn authorize(request: &Request, token: &str, port: u16) -> Result<(), ApiError> {
+    // A page in the browser can reach localhost too; requiring our own host
+    // name defeats DNS rebinding, and the token covers everything else
+    let host = request.header("Host").unwrap_or_default();
+    if host != format!("127.0.0.1:{}", port) && host != format!("localhost:{}", port) {
+        return Err(ApiError::new(403, "Unexpected Host header"));
+    }
+    let given = request
+        .header("Authorization")
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .unwrap_or_default();
+    if !token_matches(given.trim(), token) {
+        return Err(ApiError::new(401, "Missing or invalid API token"));
+    }
+    Ok(())
+}
+
+fn to_json<T: serde::Serialize>(value: T) -> Result<Value, ApiError> {
+    serde_json::to_value(value).map_err(|e| ApiError::new(500, e.to_string()))
+}
+
+fn entries_today() -> Result<Vec<JournalEntry>, String> {
+    let today = Local::now().date_naive().to_string();
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    let mut stmt = db
+        .conn
+        .prepare(&format!(
+            "SELECT {} FROM journal_entries WHERE local_date = ?1 ORDER BY created_ms ASC, id ASC",
+            JournalEntry::COLUMNS
+        ))
+        .map_err(|e| e.to_string())?;
+    let entries = stmt
+        .query_map(rusqlite::params![today], JournalEntry::from_row)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(entries)
+}
+
+/// Appends to the first unlocked entry written today, or starts one titled
+/// with today's date.
+pub(crate) fn append_to_today(text: &str) -> Result<i32, String> {
+    if text.trim().is_empty() {
+        return Err("Nothing to append".to_string());
+    }
+    let today = Local::now().date_naive().to_string();
+    let html = text_to_html(&strip_control_chars(text.trim_end()));
+    let limits = Settings::load().limits;
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    let tx = db.conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    let today: Option<(i32, String)> = tx
+        .query_row(
+            "SELECT id, body FROM journal_entries
+             WHERE locked = 0 AND local_date = ?1
+             ORDER BY created_ms ASC, id ASC LIMIT 1",
+            rusqlite::params![today],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+    let id = match today {
+        Some((id, body)) => {
+            let body = clean_body(&format!("{}{}", body, html), &limits)?;
+            tx.execute(
+                "UPDATE journal_entries SET body = ?1, word_count = ?2, sentiment = ?3 WHERE id = ?4",
+                rusqlite::params![body, body_word_count(&body), body_sentiment(&body), id],
+            )
+            .map_err(|e| e.to_string())?;
+            update_links(&tx, id, &body).map_err(|e| e.to_string())?;
+            update_people(&tx, id, &body).map_err(|e| e.to_string())?;
+            id
+        }
+        None => {
+            let title = Local::now().format("%B %-d, %Y").to_string();
+            let html = clean_body(&html, &limits)?;
+            insert_entry(&tx, &title, &html, &Utc::now().to_rfc3339()).map_err(|e| e.to_string())?
+        }
+    };
+    tx.commit().map_err(|e| e.to_string())?;
+    git_sync::commit_on_save();
+    markdown_mirror::refresh();
+    Ok(id)
+}
+
+/// `?include_archived=true` on listing endpoints.
+fn include_archived(request: &Request) -> Option<bool> {
+    request.query_param("include_archived").map(|value| value == "true")
+}
+
+fn route(request: &Request) -> Result<(u16, Value), ApiError> {
+    let segments: Vec<&str> = request.path.split('/').filter(|segment| !segment.is_empty()).collect();
+    match (request.method.as_str(), segments.as_slice()) {
+        ("GET", ["entries"]) => Ok((200, to_json(get_entries(include_archived(request))?)?)),
+        ("GET", ["entries", "today"]) => Ok((200, to_json(entries_today()?)?)),
+        ("GET", ["entries", id]) => {
+            let id: i32 = id.parse().map_err(|_| ApiError::new(404, "No such entry"))?;
+            let db = DatabaseManager::new().map_err(|e| ApiError::new(500, e.to_string()))?;
+            let entry = load_entry(&db.conn, id).map_err(|_| ApiError::new(404, "No such entry"))?;
+            Ok((200, to_json(entry)?))
+        }
+        ("GET", ["search"]) => {
+            let query = request.query_param("q").unwrap_or_default().to_string();
+            Ok((200, to_json(search_entries(query, include_archived(request))?)?))
+        }
+        ("POST", ["entries"]) => {
+            let entry = request.json::<CreateEntryRequest>()?;
+            require_title(&entry.title)?;
+            let id = create_entry(entry)?;
+            Ok((201, json!({ "id": id })))
+        }
+        ("POST", ["entries", "today"]) => {
+            let id = append_to_today(&request.json::<AppendRequest>()?.text)?;
+            Ok((200, json!({ "id": id })))
+        }
+        (_, ["entries"]) | (_, ["entries", _]) | (_, ["search"]) => Err(ApiError::new(405, "Method not allowed")),
+        _ => Err(ApiError::new(404, "Not found")),
+    }
+}
+
+fn handle(stream: TcpStream, token: &str, port: u16, app: &AppHandle) {
+    let _ = stream.set_read_timeout(Some(IO_TIMEOUT));
+    let result = read_request(&stream).and_then(|request| {
+        authorize(&request, token, port)?;
+        if !KeychainManager::has_cached_key() {
+            return Err(ApiError::new(503, "Journal is locked"));
+        }
+        debug!("API {} {}", request.method, request.path);
+        let response = route(&request)?;
+        if request.method == "POST" {
+            let _ = app.emit("entries-changed", ());
+        }
+        Ok(response)
+    });
+    match result {
+        Ok((status, body)) => write_response(&stream, status, &body),
+        Err(e) => write_response(&stream, e.status, &json!({ "error": e.message })),
+    }
+}
+
+fn run_listener(listener: TcpListener, token: String, port: u16, stop: Arc<AtomicBool>, app: AppHandle) {
+    while !stop.load(Ordering::Relaxed) {
+        match listener.accept() {
+            Ok((stream, _)) => {
+                let token = token.clone();
+                let app = app.clone();
+                thread::spawn(move || {
+                    let _ = stream.set_nonblocking(false);
+                    handle(stream, &token, port, &app);
+                });
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => thread::sleep(ACCEPT_POLL_INTERVAL),
+            Err(e) => {
+                error!("API server stopped: {}", e);
+                return;
+            }
+        }
+    }
+}
+
+fn generate_token() -> Result<String, String> {
+    let mut token = [0u8; TOKEN_BYTES];
+    OsRng.fill_bytes(&mut token);
+    let token = hex::encode(token);
+    KeychainManager::store_secret(TOKEN_ACCOUNT, &token).map_err(|e| e.to_string())?;
+    Ok(token)
+}
+
+/// Starts the API server, if it's enabled.
+pub fn start_server(app: AppHandle) -> Result<(), String> {
+    let settings = Settings::load().api;
+    let mut server = SERVER.lock().map_err(|e| e.to_string())?;
+    if !settings.enabled || server.is_some() {
+        return Ok(());
+    }
+    let token = KeychainManager::get_secret(TOKEN_ACCOUNT)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "The API has no token; turn it on again".to_string())?;
+    let listener = TcpListener::bind(("127.0.0.1", settings.port))
+        .map_err(|e| format!("Failed to open API port {}: {}", settings.port, e))?;
+    listener.set_nonblocking(true).map_err(|e| e.to_string())?;
+    let stop = Arc::new(AtomicBool::new(false));
+    let listener_stop = stop.clone();
+    let port = settings.port;
+    thread::spawn(move || run_listener(listener, token, port, listener_stop, app));
+    info!("API server listening on 127.0.0.1:{}", port);
+    *server = Some(ApiServer { stop });
+    Ok(())
+}
+
+fn stop_server() {
+    if let Some(server) = SERVER.lock().ok().and_then(|mut server| server.take()) {
+        server.stop.store(true, Ordering::Relaxed);
+        info!("API server stopped");
+    }
+}
+
+/// Turns the local API on or off, optionally on a different port. Returns
+/// the token clients must send, creating one the first time.
+#[command]
+pub fn set_api_server(app: AppHandle, enabled: bool, port: Option<u16>) -> Result<Option<String>, String> {
+    let mut settings = Settings::load();
+    let token = if enabled {
+        match KeychainManager::get_secret(TOKEN_ACCOUNT).map_err(|e| e.to_string())? {
+            Some(token) => Some(token),
+            None => Some(generate_token()?),
+        }
+    } else {
+        None
+    };
+    settings.api.enabled = enabled;
+    if let Some(port) = port {
+        settings.api.port = port;
+    }
+    settings.save().map_err(|e| e.to_string())?;
+    stop_server();
+    if enabled {
+        // The old listener lets go of the port on its next poll
+        thread::sleep(ACCEPT_POLL_INTERVAL * 2);
+    }
+    start_server(app)?;
+    Ok(token)
+}
+
+/// Replaces the API token, cutting off every client that used the old one.
+#[command]
+pub fn reset_api_token(app: AppHandle) -> Result<String, String> {
+    let token = generate_token()?;
+    warn!("API token was reset");
+    stop_server();
+    thread::sleep(ACCEPT_POLL_INTERVAL * 2);
+    start_server(app)?;
+    Ok(token)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_target_decodes_query() {
+        let (path, query) = parse_target("/search/?q=tag%3Awork+coffee&empty");
+        assert_eq!(path, "/search");
+        assert_eq!(
+            query,
+            vec![
+                ("q".to_string(), "tag:work coffee".to_string()),
+                ("empty".to_string(), String::new()),
+            ]
+        );
+        assert_eq!(percent_decode("100%"), "100%");
+        assert_eq!(percent_decode("caf%C3%A9"), "café");
+    }
+
+    #[test]
+    fn test_token_matches() {
+        assert!(token_matches("abc123", "abc123"));
+        assert!(!token_matches("abc124", "abc123"));
+        assert!(!token_matches("abc", "abc123"));
+    }
+
+    #[test]
+    fn test_read_request_with_body() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let client = thread::spawn(move || {
+            let mut stream = TcpStream::connect(address).unwrap();
+            stream
+                .write_all(b"POST /entries/today HTTP/1.1\r\nHost: localhost\r\nContent-Length: 15\r\n\r\n{\"text\": \"hi\"}\n")
+                .unwrap();
+        });
+        let (stream, _) = listener.accept().unwrap();
+        let request = read_request(&stream).ok().unwrap();
+        client.join().unwrap();
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.path, "/entries/today");
+        assert_eq!(request.header("host"), Some("localhost"));
+        assert_eq!(request.json::<AppendRequest>().ok().unwrap().text, "hi");
+    }
+}
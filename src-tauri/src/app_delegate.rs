@@ -0,0 +1,22 @@
+use objc2::runtime::{AnyClass, AnyObject, Imp, Sel};
+use objc2::{ffi, MainThreadMarker};
+use objc2_app_kit::NSApplication;
+
+// Tauri owns the macOS application delegate, and some AppKit hooks (the Dock
+// menu, continuing a Spotlight search) are only ever asked of the delegate.
+// They're added to its class at startup instead.
+
+/// Adds method `sel`, with Objective-C type encoding `types`, to the app
+/// delegate's class. False if there's no delegate or it already has one.
+///
+/// # Safety
+/// `imp` must be an `extern "C-unwind"` function taking the receiver and
+/// `sel` followed by arguments matching `types`.
+pub unsafe fn add_method(mtm: MainThreadMarker, sel: Sel, imp: Imp, types: &std::ffi::CStr) -> bool {
+    let Some(delegate) = NSApplication::sharedApplication(mtm).delegate() else {
+        return false;
+    };
+    let delegate: &AnyObject = delegate.as_ref();
+    let class: *const AnyClass = delegate.class();
+    ffi::class_addMethod(class.cast_mut(), sel, imp, types.as_ptr()).as_bool()
+}
@@ -0,0 +1,264 @@
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use journal_core::text::{strip_html, text_to_html};
+
+use crate::export::html_to_markdown;
+use crate::file_drop::markdown_to_html;
+use crate::importers::{read_file, ImportInput, ImportedEntry, Importer};
+
+// Apple Notes, which has no export of its own. Notes saved as a folder of
+// `.html` or `.txt` files, one subfolder per Notes folder as export tools lay
+// them out, are read from disk and dated by each file's creation time, which
+// those tools keep. On macOS the notes can instead be read from Notes itself
+// through AppleScript; macOS asks the user to allow that the first time, so
+// nothing is read until they choose to. Either way each Notes folder becomes a
+// notebook, and locked notes are passed over.
+
+/// Lists every note but those in Recently Deleted or locked, as JSON. Reading
+/// a property of `folder.notes` fetches it for all the folder's notes at once,
+/// which is much faster than asking note by note.
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+const NOTES_SCRIPT: &str = r#"
+const Notes = Application("Notes");
+const notes = [];
+for (const account of Notes.accounts()) {
+    for (const folder of account.folders()) {
+        const name = folder.name();
+        if (name === "Recently Deleted") continue;
+        const titles = folder.notes.name();
+        const bodies = folder.notes.body();
+        const created = folder.notes.creationDate();
+        const locked = folder.notes.passwordProtected();
+        titles.forEach((title, i) => {
+            if (!locked[i]) {
+                notes.push({ folder: name, title, body: bodies[i], created: created[i].toISOString() });
+            }
+        });
+    }
+}
+JSON.stringify(notes);
+"#;
+
+/// A folder of notes exported from Apple Notes.
+pub struct AppleNotes;
+
+/// Apple Notes itself, read through AppleScript.
+pub struct NotesApp;
+
+#[derive(Deserialize)]
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+struct ScriptedNote {
+    folder: String,
+    title: String,
+    /// The note as HTML, its title the first line.
+    body: String,
+    created: String,
+}
+
+/// `html` as the editor's markup. Notes puts each line in a `<div>`, and the
+/// title again as the first one, which is dropped since it's the entry's title.
+fn notes_html(html: &str, title: &str) -> String {
+    let lower = html.to_ascii_lowercase();
+    // An exported file is a whole page; only its body is the note
+    let mut body = match lower.find("<body").and_then(|start| Some(start + lower[start..].find('>')? + 1)) {
+        Some(start) => &html[start..lower.rfind("</body>").filter(|&end| end >= start).unwrap_or(html.len())],
+        None => html,
+    };
+    body = body.trim_start();
+    if let Some(end) = ["</div>", "</h1>"].iter().filter_map(|tag| Some(body.find(tag)? + tag.len())).min() {
+        if strip_html(&body[..end]).trim() == title.trim() {
+            body = &body[end..];
+        }
+    }
+    let body = body.replace("<div><br></div>", "").replace("<div><br/></div>", "");
+    markdown_to_html(&html_to_markdown(&body))
+}
+
+/// `text` without its first line if that's the title.
+fn notes_text<'a>(text: &'a str, title: &str) -> &'a str {
+    let text = text.trim_start_matches('\u{feff}').trim_start();
+    match text.split_once('\n') {
+        Some((first, rest)) if first.trim() == title.trim() => rest,
+        None if text.trim() == title.trim() => "",
+        _ => text,
+    }
+}
+
+/// `.html`, `.htm` and `.txt` files under `dir`, skipping hidden ones.
+fn note_files(dir: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        let path = entry.path();
+        let file_type = entry.file_type()?;
+        let extension = path.extension().and_then(|e| e.to_str()).unwrap_or_default().to_ascii_lowercase();
+        if file_type.is_dir() {
+            note_files(&path, files)?;
+        } else if file_type.is_file() && matches!(extension.as_str(), "html" | "htm" | "txt") {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// When the file was created, or else last modified.
+fn created_at(path: &Path) -> Result<DateTime<Utc>, String> {
+    let metadata = fs::metadata(path).map_err(|e| e.to_string())?;
+    let time = metadata.created().or_else(|_| metadata.modified()).map_err(|e| e.to_string())?;
+    Ok(DateTime::from(time))
+}
+
+/// The note in the file at `path`, in the notebook named for the folders
+/// between `dir` and the file.
+fn read_note(dir: &Path, path: &Path) -> Result<ImportedEntry, String> {
+    let title = path.file_stem().and_then(|stem| stem.to_str()).unwrap_or_default().trim().to_string();
+    let contents = read_file(path)?;
+    let is_text = path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("txt"));
+    let body = if is_text { text_to_html(notes_text(&contents, &title).trim()) } else { notes_html(&contents, &title) };
+    let folders: Vec<String> = path
+        .parent()
+        .and_then(|parent| parent.strip_prefix(dir).ok())
+        .map(|folder| folder.iter().map(|part| part.to_string_lossy().into_owned()).collect())
+        .unwrap_or_default();
+    Ok(ImportedEntry {
+        title,
+        body,
+        created_at: created_at(path)?,
+        tags: Vec::new(),
+        notebook: (!folders.is_empty()).then(|| folders.join(" / ")),
+    })
+}
+
+impl Importer for AppleNotes {
+    fn name(&self) -> &'static str {
+        "Apple Notes"
+    }
+
+    fn input(&self) -> ImportInput {
+        ImportInput::Folder
+    }
+
+    fn read(&self, path: &Path) -> Result<Vec<ImportedEntry>, String> {
+        let mut files = Vec::new();
+        note_files(path, &mut files).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        files.sort();
+        let mut entries = files.iter().map(|file| read_note(path, file)).collect::<Result<Vec<_>, _>>()?;
+        entries.sort_by_key(|entry| entry.created_at);
+        Ok(entries)
+    }
+}
+
+/// The notes the script printed.
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+fn parse_scripted_notes(json: &str) -> Result<Vec<ImportedEntry>, String> {
+    let notes: Vec<ScriptedNote> = serde_json::from_str(json).map_err(|e| e.to_string())?;
+    let mut entries = notes
+        .into_iter()
+        .map(|note| {
+            let created_at = DateTime::parse_from_rfc3339(&note.created)
+                .map_err(|e| format!("Invalid date {}: {}", note.created, e))?;
+            Ok(ImportedEntry {
+                body: notes_html(&note.body, &note.title),
+                title: note.title,
+                created_at: created_at.with_timezone(&Utc),
+                tags: Vec::new(),
+                notebook: Some(note.folder),
+            })
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    entries.sort_by_key(|entry| entry.created_at);
+    Ok(entries)
+}
+
+impl Importer for NotesApp {
+    fn name(&self) -> &'static str {
+        "Apple Notes"
+    }
+
+    fn input(&self) -> ImportInput {
+        ImportInput::App
+    }
+
+    #[cfg(target_os = "macos")]
+    fn read(&self, _path: &Path) -> Result<Vec<ImportedEntry>, String> {
+        let output = std::process::Command::new("osascript")
+            .args(["-l", "JavaScript", "-e", NOTES_SCRIPT])
+            .output()
+            .map_err(|e| format!("Failed to run osascript: {}", e))?;
+        if !output.status.success() {
+            // Most often the user didn't allow the journal to control Notes
+            return Err(format!("Couldn't read Notes: {}", String::from_utf8_lossy(&output.stderr).trim()));
+        }
+        parse_scripted_notes(&String::from_utf8_lossy(&output.stdout))
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    fn read(&self, _path: &Path) -> Result<Vec<ImportedEntry>, String> {
+        Err("Reading Apple Notes directly is only available on macOS".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_notes_html() {
+        let page = "<html><head><title>Lisbon</title><style>body { margin: 0 }</style></head>\
+                    <body><div><h1>Lisbon</h1></div><div>Trams and <b>tiles</b></div><div><br></div>\
+                    <div>Rain</div></body></html>";
+        assert_eq!(notes_html(page, "Lisbon"), "<p>Trams and <strong>tiles</strong></p>\n<p>Rain</p>\n");
+        assert_eq!(notes_html("<div>Groceries</div><div>Milk</div>", "Groceries"), "<p>Milk</p>\n");
+        assert_eq!(notes_html("<div>Milk</div>", "Groceries"), "<p>Milk</p>\n");
+    }
+
+    #[test]
+    fn test_notes_text() {
+        assert_eq!(notes_text("Groceries\nMilk\nEggs", "Groceries"), "Milk\nEggs");
+        assert_eq!(notes_text("Milk\nEggs", "Groceries"), "Milk\nEggs");
+        assert_eq!(notes_text("Groceries", "Groceries"), "");
+    }
+
+    #[test]
+    fn test_read_export_folder() {
+        let dir = std::env::temp_dir().join(format!("journal-apple-notes-{}", std::process::id()));
+        fs::create_dir_all(dir.join("Travel/Portugal")).unwrap();
+        fs::write(dir.join("Travel/Portugal/Lisbon.html"), "<div>Lisbon</div><div>Trams</div>").unwrap();
+        fs::write(dir.join("Groceries.txt"), "Groceries\nMilk").unwrap();
+        fs::write(dir.join("Travel/.DS_Store"), "").unwrap();
+        fs::write(dir.join("Travel/photo.png"), [0u8; 4]).unwrap();
+
+        let mut entries = AppleNotes.read(&dir).unwrap();
+        entries.sort_by(|a, b| a.title.cmp(&b.title));
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].title, "Groceries");
+        assert_eq!(entries[0].body, text_to_html("Milk"));
+        assert_eq!(entries[0].notebook, None);
+        assert_eq!(entries[1].title, "Lisbon");
+        assert_eq!(entries[1].body, "<p>Trams</p>\n");
+        assert_eq!(entries[1].notebook.as_deref(), Some("Travel / Portugal"));
+        assert_eq!(entries[1].created_at, created_at(&dir.join("Travel/Portugal/Lisbon.html")).unwrap());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_parse_scripted_notes() {
+        let json = r#"[{"folder": "Notes", "title": "Later", "body": "<div>Later</div><div>Two</div>",
+                        "created": "2024-04-02T12:00:00.000Z"},
+                       {"folder": "Travel", "title": "Lisbon", "body": "<div>Lisbon</div><div>Trams</div>",
+                        "created": "2024-03-05T12:00:00.000Z"}]"#;
+        let entries = parse_scripted_notes(json).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].title, "Lisbon");
+        assert_eq!(entries[0].body, "<p>Trams</p>\n");
+        assert_eq!(entries[0].notebook.as_deref(), Some("Travel"));
+        assert_eq!(entries[0].created_at.to_rfc3339(), "2024-03-05T12:00:00+00:00");
+        assert!(parse_scripted_notes("[{\"folder\": \"Notes\"}]").is_err());
+    }
+}
@@ -0,0 +1,75 @@
+use log::debug;
+use tauri::command;
+
+use crate::{recent_menu, DatabaseManager};
+
+// Archived entries stay in the journal, and in backups and sync, but drop out
+// of the timeline and search so years of imported history don't crowd out
+// what's being written now. Both can still be asked to include them.
+
+fn set_archived(conn: &rusqlite::Connection, id: i32, archived: bool) -> Result<(), String> {
+    let updated = conn
+        .execute(
+            "UPDATE journal_entries SET archived = ?1 WHERE id = ?2",
+            rusqlite::params![archived, id],
+        )
+        .map_err(|e| e.to_string())?;
+    if updated == 0 {
+        return Err(format!("Entry {} not found", id));
+    }
+    Ok(())
+}
+
+#[command]
+pub fn archive_entry(id: i32) -> Result<(), String> {
+    debug!("Archiving entry {}", id);
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    set_archived(&db.conn, id, true)?;
+    recent_menu::refresh();
+    Ok(())
+}
+
+#[command]
+pub fn unarchive_entry(id: i32) -> Result<(), String> {
+    debug!("Unarchiving entry {}", id);
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    set_archived(&db.conn, id, false)?;
+    recent_menu::refresh();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::SearchQuery;
+    use journal_core::{insert_entry, timestamp_now};
+
+    fn matching_titles(conn: &rusqlite::Connection, query: &SearchQuery) -> Vec<String> {
+        let (clause, params) = query.to_sql();
+        let mut stmt = conn
+            .prepare(&format!("SELECT title FROM journal_entries WHERE {} ORDER BY id", clause))
+            .unwrap();
+        stmt.query_map(rusqlite::params_from_iter(params.iter()), |row| row.get(0))
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_archived_entries_are_hidden_from_search() {
+        let db = DatabaseManager::open_in_memory().unwrap();
+        let old = insert_entry(&db.conn, "Coffee in 2009", "", &timestamp_now()).unwrap();
+        insert_entry(&db.conn, "Coffee today", "", &timestamp_now()).unwrap();
+        set_archived(&db.conn, old, true).unwrap();
+
+        let mut query = SearchQuery::parse("coffee").unwrap();
+        assert_eq!(matching_titles(&db.conn, &query), vec!["Coffee today"]);
+        query.include_archived = true;
+        assert_eq!(matching_titles(&db.conn, &query), vec!["Coffee in 2009", "Coffee today"]);
+
+        set_archived(&db.conn, old, false).unwrap();
+        query.include_archived = false;
+        assert_eq!(matching_titles(&db.conn, &query).len(), 2);
+        assert!(set_archived(&db.conn, old + 10, true).is_err());
+    }
+}
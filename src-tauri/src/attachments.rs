@@ -0,0 +1,473 @@
+use log::{debug, error, info};
+use rusqlite::{DatabaseName, OptionalExtension};
+use serde::Serialize;
+use std::io::Write;
+use tauri::http::{header, Request, Response, StatusCode};
+use tauri::{command, AppHandle, Runtime, UriSchemeContext};
+use tauri_plugin_clipboard_manager::ClipboardExt;
+
+use journal_core::attachments::content_hash;
+use journal_core::timestamp_now;
+
+use crate::locks::is_locked;
+use crate::settings::Settings;
+use crate::validation::check_attachment;
+use crate::DatabaseManager;
+
+// Files attached to entries: voice memos and pasted or dropped images. The bytes live in the
+// encrypted database next to the entry, so they're covered by the same key
+// and backups. They're written and read in chunks through SQLite's
+// incremental blob I/O, and the webview plays them through the `attachment:`
+// protocol, which serves byte ranges so a long memo can be seeked without
+// loading all of it.
+//
+// Bytes are stored once per content hash in `attachment_blobs`; attachments
+// only refer to them. Deleting an entry drops its attachments but leaves the
+// blobs, which `gc_attachments` clears out once nothing refers to them.
+
+pub const SCHEME: &str = "attachment";
+
+const MAX_AUDIO_BYTES: usize = 100 * 1024 * 1024;
+pub(crate) const MAX_IMAGE_BYTES: usize = 25 * 1024 * 1024;
+/// Size of each blob write, and the most an open-ended range request gets.
+const STREAM_CHUNK: usize = 1024 * 1024;
+
+#[derive(Debug, Serialize)]
+pub struct Attachment {
+    id: i32,
+    entry_id: i32,
+    kind: String,
+    mime: String,
+    size: i64,
+    created_at: String,
+    #[serde(skip)]
+    hash: String,
+}
+
+impl Attachment {
+    const COLUMNS: &'static str = "id, entry_id, kind, mime, size, created_at, hash";
+
+    fn from_row(row: &rusqlite::Row) -> rusqlite::Result<Self> {
+        Ok(Attachment {
+            id: row.get(0)?,
+            entry_id: row.get(1)?,
+            kind: row.get(2)?,
+            mime: row.get(3)?,
+            size: row.get(4)?,
+            created_at: row.get(5)?,
+            hash: row.get(6)?,
+        })
+    }
+}
+
+/// What the editor embeds to show an attachment inline.
+#[derive(Debug, Serialize)]
+pub struct AttachmentRef {
+    /// `attachment:<id>`, the same on every platform, so it's what entry
+    /// bodies should store.
+    pub(crate) token: String,
+    /// Where this webview loads it from right now.
+    url: String,
+    attachment: Attachment,
+}
+
+#[derive(Debug, Serialize)]
+pub struct GcReport {
+    removed: usize,
+    freed_bytes: i64,
+}
+
+/// Stores `data` as a new attachment of `entry_id`. The bytes are only
+/// written if no blob with the same hash exists yet, and then streamed into a
+/// zero-filled blob rather than bound as one large parameter.
+fn insert_attachment(
+    conn: &rusqlite::Connection,
+    entry_id: i32,
+    kind: &str,
+    mime: &str,
+    data: &[u8],
+) -> Result<i32, String> {
+    let hash = content_hash(data);
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    let stored: bool = tx
+        .query_row(
+            "SELECT EXISTS(SELECT 1 FROM attachment_blobs WHERE hash = ?1)",
+            rusqlite::params![hash],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    if !stored {
+        tx.execute(
+            "INSERT INTO attachment_blobs (hash, size, data) VALUES (?1, ?2, zeroblob(?2))",
+            rusqlite::params![hash, data.len() as i64],
+        )
+        .map_err(|e| e.to_string())?;
+        let mut blob = tx
+            .blob_open(DatabaseName::Main, "attachment_blobs", "data", tx.last_insert_rowid(), false)
+            .map_err(|e| e.to_string())?;
+        for chunk in data.chunks(STREAM_CHUNK) {
+            blob.write_all(chunk).map_err(|e| e.to_string())?;
+        }
+    } else {
+        debug!("Reusing stored blob {}", hash);
+    }
+    tx.execute(
+        "INSERT INTO attachments (entry_id, kind, mime, size, hash, created_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        rusqlite::params![entry_id, kind, mime, data.len() as i64, hash, timestamp_now()],
+    )
+    .map_err(|e| e.to_string())?;
+    let id = tx.last_insert_rowid() as i32;
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(id)
+}
+
+/// Reads `len` bytes of the blob stored under `hash`, starting at `start`.
+pub(crate) fn read_blob(conn: &rusqlite::Connection, hash: &str, start: usize, len: usize) -> rusqlite::Result<Vec<u8>> {
+    let row_id: i64 = conn.query_row(
+        "SELECT rowid FROM attachment_blobs WHERE hash = ?1",
+        rusqlite::params![hash],
+        |row| row.get(0),
+    )?;
+    let blob = conn.blob_open(DatabaseName::Main, "attachment_blobs", "data", row_id, true)?;
+    let mut buf = vec![0; len];
+    blob.read_at_exact(&mut buf, start)?;
+    Ok(buf)
+}
+
+fn find_attachment(conn: &rusqlite::Connection, id: i32) -> rusqlite::Result<Option<Attachment>> {
+    conn.query_row(
+        &format!("SELECT {} FROM attachments WHERE id = ?1", Attachment::COLUMNS),
+        rusqlite::params![id],
+        Attachment::from_row,
+    )
+    .optional()
+}
+
+fn attachment_token(id: i32) -> String {
+    format!("{}:{}", SCHEME, id)
+}
+
+/// The URL the webview loads attachment `id` from. Windows and Android
+/// webviews only accept custom protocols in the `http://<scheme>.localhost` form.
+fn attachment_url(id: i32) -> String {
+    if cfg!(any(windows, target_os = "android")) {
+        format!("http://{}.localhost/{}", SCHEME, id)
+    } else {
+        format!("{}://localhost/{}", SCHEME, id)
+    }
+}
+
+/// Parses a single `bytes=` range against a body of `size` bytes into an
+/// inclusive `(start, end)`, or `None` if it can't be satisfied.
+fn parse_range(header: &str, size: usize) -> Option<(usize, usize)> {
+    let spec = header.trim().strip_prefix("bytes=")?;
+    if size == 0 || spec.contains(',') {
+        return None;
+    }
+    let (start, end) = spec.split_once('-')?;
+    let (start, end) = match (start.trim(), end.trim()) {
+        ("", suffix) => {
+            let len: usize = suffix.parse().ok()?;
+            (size.saturating_sub(len), size - 1)
+        }
+        (start, "") => {
+            let start: usize = start.parse().ok()?;
+            (start, start.saturating_add(STREAM_CHUNK - 1).min(size - 1))
+        }
+        (start, end) => (start.parse().ok()?, end.parse::<usize>().ok()?.min(size - 1)),
+    };
+    (start <= end && start < size).then_some((start, end))
+}
+
+fn plain_response(status: StatusCode, message: &str) -> Response<Vec<u8>> {
+    Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, "text/plain")
+        .body(message.as_bytes().to_vec())
+        .unwrap_or_default()
+}
+
+fn serve(request: &Request<Vec<u8>>) -> Result<Response<Vec<u8>>, String> {
+    let Ok(id) = request.uri().path().trim_start_matches('/').parse::<i32>() else {
+        return Ok(plain_response(StatusCode::NOT_FOUND, "Not found"));
+    };
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    let Some(attachment) = find_attachment(&db.conn, id).map_err(|e| e.to_string())? else {
+        return Ok(plain_response(StatusCode::NOT_FOUND, "Not found"));
+    };
+    let size = attachment.size as usize;
+    let response = Response::builder()
+        .header(header::CONTENT_TYPE, &attachment.mime)
+        .header(header::ACCEPT_RANGES, "bytes");
+    let Some(range) = request.headers().get(header::RANGE) else {
+        let body = read_blob(&db.conn, &attachment.hash, 0, size).map_err(|e| e.to_string())?;
+        return response.body(body).map_err(|e| e.to_string());
+    };
+    match range.to_str().ok().and_then(|range| parse_range(range, size)) {
+        Some((start, end)) => {
+            let body = read_blob(&db.conn, &attachment.hash, start, end - start + 1).map_err(|e| e.to_string())?;
+            response
+                .status(StatusCode::PARTIAL_CONTENT)
+                .header(header::CONTENT_RANGE, format!("bytes {}-{}/{}", start, end, size))
+                .body(body)
+                .map_err(|e| e.to_string())
+        }
+        None => response
+            .status(StatusCode::RANGE_NOT_SATISFIABLE)
+            .header(header::CONTENT_RANGE, format!("bytes */{}", size))
+            .body(Vec::new())
+            .map_err(|e| e.to_string()),
+    }
+}
+
+/// Handler for the `attachment:` protocol registered on the webview.
+pub fn handle_protocol<R: Runtime>(_ctx: UriSchemeContext<'_, R>, request: Request<Vec<u8>>) -> Response<Vec<u8>> {
+    serve(&request).unwrap_or_else(|e| {
+        error!("Failed to serve attachment {}: {}", request.uri(), e);
+        plain_response(StatusCode::INTERNAL_SERVER_ERROR, "Failed to load attachment")
+    })
+}
+
+/// Attaches a recorded voice memo to an entry.
+#[command]
+pub fn add_audio_attachment(entry_id: i32, data: Vec<u8>, mime: String) -> Result<Attachment, String> {
+    let mime = mime.trim().to_ascii_lowercase();
+    if !mime.starts_with("audio/") {
+        return Err(format!("'{}' is not an audio type", mime));
+    }
+    if data.is_empty() {
+        return Err("The recording is empty".to_string());
+    }
+    if data.len() > MAX_AUDIO_BYTES {
+        return Err(format!("Recordings are limited to {} MB", MAX_AUDIO_BYTES / (1024 * 1024)));
+    }
+    attach(entry_id, "audio", &mime, &data)
+}
+
+fn attach(entry_id: i32, kind: &str, mime: &str, data: &[u8]) -> Result<Attachment, String> {
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    if is_locked(&db.conn, entry_id).map_err(|e| e.to_string())? {
+        return Err(format!("Entry {} is locked; unlock it before attaching", entry_id));
+    }
+    check_attachment(data.len(), &Settings::load().limits)?;
+    debug!("Attaching {} bytes of {} to entry {}", data.len(), mime, entry_id);
+    let id = insert_attachment(&db.conn, entry_id, kind, mime, data)?;
+    find_attachment(&db.conn, id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Attachment {} not found", id))
+}
+
+/// Clipboard images arrive as raw RGBA pixels; webviews need an image file.
+fn encode_png(rgba: &[u8], width: u32, height: u32) -> Result<Vec<u8>, String> {
+    let mut png = Vec::new();
+    let mut encoder = png::Encoder::new(&mut png, width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(|e| e.to_string())?;
+    writer.write_image_data(rgba).map_err(|e| e.to_string())?;
+    writer.finish().map_err(|e| e.to_string())?;
+    Ok(png)
+}
+
+/// Saves the image on the clipboard as a PNG attachment of the entry. Reading
+/// it here rather than in the webview avoids a clipboard permission prompt
+/// and the round trip of the pixels through JavaScript.
+#[command]
+pub fn paste_image_from_clipboard(app: AppHandle, entry_id: i32) -> Result<AttachmentRef, String> {
+    let image = app
+        .clipboard()
+        .read_image()
+        .map_err(|_| "There's no image on the clipboard".to_string())?;
+    let data = encode_png(image.rgba(), image.width(), image.height())?;
+    add_image(entry_id, "image/png", &data)
+}
+
+/// Stores an image file as an attachment of the entry, ready to embed.
+pub(crate) fn add_image(entry_id: i32, mime: &str, data: &[u8]) -> Result<AttachmentRef, String> {
+    if data.len() > MAX_IMAGE_BYTES {
+        return Err(format!("Images are limited to {} MB", MAX_IMAGE_BYTES / (1024 * 1024)));
+    }
+    let attachment = attach(entry_id, "image", mime, data)?;
+    Ok(AttachmentRef {
+        token: attachment_token(attachment.id),
+        url: attachment_url(attachment.id),
+        attachment,
+    })
+}
+
+/// A URL the webview can play the voice memo from, e.g. in an `<audio>` tag.
+#[command]
+pub fn get_audio_attachment(id: i32) -> Result<String, String> {
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    match find_attachment(&db.conn, id).map_err(|e| e.to_string())? {
+        Some(attachment) if attachment.kind == "audio" => Ok(attachment_url(id)),
+        _ => Err(format!("Audio attachment {} not found", id)),
+    }
+}
+
+#[command]
+pub fn get_entry_attachments(entry_id: i32) -> Result<Vec<Attachment>, String> {
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    let mut stmt = db
+        .conn
+        .prepare(&format!(
+            "SELECT {} FROM attachments WHERE entry_id = ?1 ORDER BY id",
+            Attachment::COLUMNS
+        ))
+        .map_err(|e| e.to_string())?;
+    let attachments = stmt
+        .query_map(rusqlite::params![entry_id], Attachment::from_row)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(attachments)
+}
+
+/// Records text found in the blob stored under `hash`, replacing any from before.
+pub(crate) fn store_attachment_text(
+    conn: &rusqlite::Connection,
+    hash: &str,
+    source: &str,
+    text: &str,
+) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT OR REPLACE INTO attachment_text (hash, source, text, extracted_at) VALUES (?1, ?2, ?3, ?4)",
+        rusqlite::params![hash, source, text, timestamp_now()],
+    )?;
+    Ok(())
+}
+
+/// Hashes and sizes of up to `limit` blobs of `kind` attachments that no
+/// text has been stored for yet.
+pub(crate) fn unread_blobs(
+    conn: &rusqlite::Connection,
+    kind: &str,
+    limit: usize,
+) -> rusqlite::Result<Vec<(String, i64)>> {
+    let mut stmt = conn.prepare(
+        "SELECT DISTINCT a.hash, a.size FROM attachments a
+         LEFT JOIN attachment_text t ON t.hash = a.hash
+         WHERE a.kind = ?1 AND a.hash IS NOT NULL AND t.hash IS NULL
+         LIMIT ?2",
+    )?;
+    let unread = stmt
+        .query_map(rusqlite::params![kind, limit], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(unread)
+}
+
+/// Text found in an attachment, or `None` if it hasn't been read yet.
+#[command]
+pub fn get_attachment_text(id: i32) -> Result<Option<String>, String> {
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    db.conn
+        .query_row(
+            "SELECT t.text FROM attachments a JOIN attachment_text t ON t.hash = a.hash WHERE a.id = ?1",
+            rusqlite::params![id],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())
+}
+
+/// Deletes blobs no attachment refers to any more.
+fn remove_orphaned_blobs(conn: &rusqlite::Connection) -> rusqlite::Result<GcReport> {
+    const ORPHANED: &str = "hash NOT IN (SELECT hash FROM attachments WHERE hash IS NOT NULL)";
+    let tx = conn.unchecked_transaction()?;
+    let freed_bytes = tx.query_row(
+        &format!("SELECT COALESCE(SUM(size), 0) FROM attachment_blobs WHERE {}", ORPHANED),
+        [],
+        |row| row.get(0),
+    )?;
+    let removed = tx.execute(&format!("DELETE FROM attachment_blobs WHERE {}", ORPHANED), [])?;
+    tx.commit()?;
+    Ok(GcReport { removed, freed_bytes })
+}
+
+/// Maintenance: removes blobs left behind by deleted entries and compacts the
+/// database so the space is actually returned to the disk.
+#[command]
+pub fn gc_attachments() -> Result<GcReport, String> {
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    let report = remove_orphaned_blobs(&db.conn).map_err(|e| e.to_string())?;
+    if report.removed > 0 {
+        info!("Removed {} orphaned attachment blobs ({} bytes)", report.removed, report.freed_bytes);
+        db.conn.execute_batch("VACUUM").map_err(|e| e.to_string())?;
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use journal_core::insert_entry;
+
+    #[test]
+    fn test_parse_range() {
+        assert_eq!(parse_range("bytes=0-99", 1000), Some((0, 99)));
+        assert_eq!(parse_range("bytes=900-", 1000), Some((900, 999)));
+        assert_eq!(parse_range("bytes=-100", 1000), Some((900, 999)));
+        assert_eq!(parse_range("bytes=990-2000", 1000), Some((990, 999)));
+        assert_eq!(parse_range("bytes=0-", 10 * STREAM_CHUNK), Some((0, STREAM_CHUNK - 1)));
+        assert_eq!(parse_range("bytes=1000-", 1000), None);
+        assert_eq!(parse_range("bytes=5-2", 1000), None);
+        assert_eq!(parse_range("bytes=0-1,5-6", 1000), None);
+        assert_eq!(parse_range("items=0-1", 1000), None);
+        assert_eq!(parse_range("bytes=0-0", 0), None);
+    }
+
+    #[test]
+    fn test_encode_png() {
+        let png = encode_png(&[255, 0, 0, 255, 0, 0, 255, 128], 2, 1).unwrap();
+        assert!(png.starts_with(b"\x89PNG\r\n\x1a\n"));
+        // Pixel data that doesn't match the dimensions is refused
+        assert!(encode_png(&[0; 4], 2, 2).is_err());
+    }
+
+    #[test]
+    fn test_attachment_round_trip() {
+        let db = DatabaseManager::open_in_memory().unwrap();
+        let entry_id = insert_entry(&db.conn, "Memo", "", &timestamp_now()).unwrap();
+        let data: Vec<u8> = (0..STREAM_CHUNK + 10).map(|i| i as u8).collect();
+        let id = insert_attachment(&db.conn, entry_id, "audio", "audio/webm", &data).unwrap();
+
+        let attachment = find_attachment(&db.conn, id).unwrap().unwrap();
+        assert_eq!(attachment.size, data.len() as i64);
+        assert_eq!(read_blob(&db.conn, &attachment.hash, 0, data.len()).unwrap(), data);
+        assert_eq!(read_blob(&db.conn, &attachment.hash, STREAM_CHUNK, 10).unwrap(), &data[STREAM_CHUNK..]);
+
+        db.conn
+            .execute("DELETE FROM journal_entries WHERE id = ?1", rusqlite::params![entry_id])
+            .unwrap();
+        assert!(find_attachment(&db.conn, id).unwrap().is_none());
+    }
+
+    fn count_blobs(conn: &rusqlite::Connection) -> i64 {
+        conn.query_row("SELECT COUNT(*) FROM attachment_blobs", [], |row| row.get(0))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_identical_attachments_share_a_blob_until_collected() {
+        let db = DatabaseManager::open_in_memory().unwrap();
+        let first = insert_entry(&db.conn, "One", "", &timestamp_now()).unwrap();
+        let second = insert_entry(&db.conn, "Two", "", &timestamp_now()).unwrap();
+        insert_attachment(&db.conn, first, "image", "image/png", b"same bytes").unwrap();
+        insert_attachment(&db.conn, second, "image", "image/png", b"same bytes").unwrap();
+        insert_attachment(&db.conn, second, "audio", "audio/webm", b"other").unwrap();
+        assert_eq!(count_blobs(&db.conn), 2);
+
+        // Still referenced by the second entry
+        db.conn
+            .execute("DELETE FROM journal_entries WHERE id = ?1", rusqlite::params![first])
+            .unwrap();
+        assert_eq!(remove_orphaned_blobs(&db.conn).unwrap().removed, 0);
+
+        db.conn
+            .execute("DELETE FROM journal_entries WHERE id = ?1", rusqlite::params![second])
+            .unwrap();
+        let report = remove_orphaned_blobs(&db.conn).unwrap();
+        assert_eq!((report.removed, report.freed_bytes), (2, 15));
+        assert_eq!(count_blobs(&db.conn), 0);
+    }
+}
@@ -0,0 +1,393 @@
+use chrono::{DateTime, Datelike, Local, NaiveDateTime, TimeZone, Utc};
+use log::{debug, error, info, warn};
+use serde::Serialize;
+use std::cmp::Reverse;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+use tauri::command;
+
+use journal_core::audit::AuditAction;
+use journal_core::keychain::KeychainManager;
+use journal_core::profiles::{active_profile, profile_dir};
+use journal_core::{ErrorResponse, APPLICATION_ID, SCHEMA_VERSION};
+
+use crate::export::newer_version_error;
+use crate::security;
+use crate::settings::{BackupFrequency, BackupSettings, Settings};
+use crate::DatabaseManager;
+
+const BACKUP_DIR_NAME: &str = "Backups";
+const BACKUP_PREFIX: &str = "journal-";
+const SCHEDULED_REASON: &str = "scheduled";
+/// Give the user time to unlock the keychain before the first scheduled check.
+const SCHEDULER_START_DELAY: Duration = Duration::from_secs(5 * 60);
+const SCHEDULER_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Debug, Serialize)]
+pub struct BackupInfo {
+    /// File name inside its backups folder; stable identifier for the backup.
+    id: String,
+    path: String,
+    created_at: String,
+    size_bytes: u64,
+    /// Why the snapshot was taken, e.g. `pre-import` or `scheduled`.
+    reason: String,
+}
+
+pub fn backups_dir() -> Result<PathBuf, ErrorResponse> {
+    Ok(profile_dir()?.join(BACKUP_DIR_NAME))
+}
+
+/// Where scheduled backups go: the user's chosen folder, or `Backups/`.
+pub(crate) fn scheduled_dir(settings: &BackupSettings) -> Result<PathBuf, ErrorResponse> {
+    match &settings.directory {
+        Some(dir) => Ok(PathBuf::from(dir)),
+        None => backups_dir(),
+    }
+}
+
+/// `journal-20240105-093000-pre-import.db` for a backup taken now for `reason`.
+pub(crate) fn backup_file_name(reason: &str) -> String {
+    format!("{}{}-{}.db", BACKUP_PREFIX, Local::now().format("%Y%m%d-%H%M%S"), reason)
+}
+
+fn write_snapshot(db: &DatabaseManager, dir: &Path, reason: &str) -> Result<PathBuf, ErrorResponse> {
+    fs::create_dir_all(dir).map_err(|e| ErrorResponse {
+        message: format!("Failed to create backups directory: {}", e),
+        error_type: "file_error".to_string(),
+    })?;
+    let path = dir.join(backup_file_name(reason));
+    db.backup_to(&path)?;
+    info!("Created {} backup at {:?}", reason, path);
+    Ok(path)
+}
+
+/// Copies the current database into `Backups/` before an operation that could
+/// lose data. The copy stays encrypted with the same key as the live database.
+pub fn snapshot(db: &DatabaseManager, reason: &str) -> Result<PathBuf, ErrorResponse> {
+    write_snapshot(db, &backups_dir()?, reason)
+}
+
+/// Splits `journal-20240105-093000-pre-import.db` into its local timestamp and reason.
+pub(crate) fn parse_backup_name(file_name: &str) -> Option<(NaiveDateTime, &str)> {
+    let stem = file_name.strip_prefix(BACKUP_PREFIX)?.strip_suffix(".db")?;
+    // date (8) + '-' + time (6) + '-'
+    let taken_at = NaiveDateTime::parse_from_str(stem.get(..15)?, "%Y%m%d-%H%M%S").ok()?;
+    let reason = stem.get(16..).filter(|reason| !reason.is_empty())?;
+    Some((taken_at, reason))
+}
+
+fn backups_in(dir: &Path) -> Result<Vec<(PathBuf, NaiveDateTime, String)>, String> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut backups = Vec::new();
+    for dir_entry in fs::read_dir(dir).map_err(|e| e.to_string())? {
+        let path = dir_entry.map_err(|e| e.to_string())?.path();
+        let file_name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+        if let Some((taken_at, reason)) = parse_backup_name(&file_name) {
+            let reason = reason.to_string();
+            backups.push((path, taken_at, reason));
+        }
+    }
+    Ok(backups)
+}
+
+/// Grandfather-style retention: keep the newest backup of each of the last
+/// `keep_daily` days and of each of the last `keep_weekly` ISO weeks; return
+/// everything else. The newest backup is always kept, even with both at 0.
+pub(crate) fn backups_to_prune<T>(
+    mut backups: Vec<(T, NaiveDateTime)>,
+    keep_daily: u32,
+    keep_weekly: u32,
+) -> Vec<T> {
+    let keep_daily = keep_daily.max(1);
+    backups.sort_by_key(|backup| Reverse(backup.1));
+    let mut days = HashSet::new();
+    let mut weeks = HashSet::new();
+    let mut prune = Vec::new();
+    for (backup, taken_at) in backups {
+        let day = taken_at.date();
+        let week = (day.iso_week().year(), day.iso_week().week());
+        let mut keep = false;
+        if !days.contains(&day) && days.len() < keep_daily as usize {
+            days.insert(day);
+            keep = true;
+        }
+        if !weeks.contains(&week) && weeks.len() < keep_weekly as usize {
+            weeks.insert(week);
+            keep = true;
+        }
+        if !keep {
+            prune.push(backup);
+        }
+    }
+    prune
+}
+
+fn prune_scheduled(dir: &Path, settings: &BackupSettings) -> Result<(), String> {
+    let scheduled = backups_in(dir)?
+        .into_iter()
+        .filter(|(_, _, reason)| reason == SCHEDULED_REASON)
+        .map(|(path, taken_at, _)| (path, taken_at))
+        .collect();
+    for path in backups_to_prune(scheduled, settings.keep_daily, settings.keep_weekly) {
+        debug!("Pruning old backup {:?}", path);
+        if let Err(e) = fs::remove_file(&path) {
+            warn!("Failed to prune backup {:?}: {}", path, e);
+        }
+    }
+    Ok(())
+}
+
+/// Whether a backup taken at `last` is old enough at `now` for the next one.
+pub(crate) fn is_overdue(frequency: BackupFrequency, last: Option<NaiveDateTime>, now: NaiveDateTime) -> bool {
+    let interval = match frequency {
+        BackupFrequency::Daily => chrono::Duration::days(1),
+        BackupFrequency::Weekly => chrono::Duration::weeks(1),
+    };
+    last.is_none_or(|last| now - last >= interval)
+}
+
+fn is_due(settings: &BackupSettings, last: Option<NaiveDateTime>, now: NaiveDateTime) -> bool {
+    settings.enabled && is_overdue(settings.frequency, last, now)
+}
+
+/// Takes a scheduled backup if one is due, then applies the retention policy.
+fn run_scheduled_backup() -> Result<(), String> {
+    let settings = Settings::load().backup;
+    let dir = scheduled_dir(&settings).map_err(|e| e.to_string())?;
+    let last = backups_in(&dir)?
+        .into_iter()
+        .filter(|(_, _, reason)| reason == SCHEDULED_REASON)
+        .map(|(_, taken_at, _)| taken_at)
+        .max();
+    if !is_due(&settings, last, Local::now().naive_local()) {
+        return Ok(());
+    }
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    write_snapshot(&db, &dir, SCHEDULED_REASON).map_err(|e| e.to_string())?;
+    prune_scheduled(&dir, &settings)
+}
+
+/// Starts the background thread that takes scheduled backups. It never opens
+/// the database before the key has been unlocked for this session, so it can't
+/// trigger a keychain prompt on its own.
+pub fn start_scheduler() {
+    thread::spawn(|| {
+        thread::sleep(SCHEDULER_START_DELAY);
+        loop {
+            if KeychainManager::has_cached_key() {
+                if let Err(e) = run_scheduled_backup() {
+                    error!("Scheduled backup failed: {}", e);
+                }
+            }
+            thread::sleep(SCHEDULER_INTERVAL);
+        }
+    });
+}
+
+/// Refuses a database that says it's something other than a journal, or one
+/// with a schema from a newer version of the app, which would be migrated
+/// wrongly or not at all.
+fn check_archive(conn: &rusqlite::Connection) -> Result<(), String> {
+    let (application_id, version): (i32, i64) = conn
+        .query_row("SELECT * FROM pragma_application_id(), pragma_user_version()", [], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })
+        .map_err(|e| e.to_string())?;
+    // Databases last opened before the id was added have none
+    if application_id != 0 && application_id != APPLICATION_ID {
+        return Err("This file is a database, but not one from Journal".to_string());
+    }
+    if version > SCHEMA_VERSION {
+        return Err(newer_version_error("database", None, "open"));
+    }
+    Ok(())
+}
+
+/// Opens a backup or archive with the current key and checks it holds a
+/// journal this version can read, so a backup from another machine, key or
+/// version is rejected before anything is replaced.
+pub(crate) fn validate_backup(path: &Path) -> Result<(), String> {
+    let conn = rusqlite::Connection::open_with_flags(path, rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .map_err(|e| format!("Failed to open backup: {}", e))?;
+    // An unencrypted journal can only take unencrypted backups, and the other way round
+    let profile = active_profile();
+    if !profile.unencrypted {
+        let key = KeychainManager::new()
+            .and_then(|keychain| keychain.get_key())
+            .map_err(|e| e.to_user_message())?;
+        conn.pragma_update(None, "key", &key).map_err(|e| e.to_string())?;
+        profile.cipher.unwrap_or_default().apply(&conn, "main").map_err(|e| e.to_string())?;
+    }
+    conn.query_row(
+        "SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'journal_entries'",
+        [],
+        |_| Ok(()),
+    )
+    .map_err(|_| "Backup can't be opened with this journal's key or is not a journal database".to_string())?;
+    check_archive(&conn)
+}
+
+/// The app's `Backups/` folder plus the scheduled-backup folder, if different.
+fn known_backup_dirs() -> Result<Vec<PathBuf>, String> {
+    let mut dirs = vec![backups_dir().map_err(|e| e.to_string())?];
+    let scheduled = scheduled_dir(&Settings::load().backup).map_err(|e| e.to_string())?;
+    if !dirs.contains(&scheduled) {
+        dirs.push(scheduled);
+    }
+    Ok(dirs)
+}
+
+/// Every backup file in the known backup folders.
+pub(crate) fn all_backups() -> Result<Vec<PathBuf>, String> {
+    let mut paths = Vec::new();
+    for dir in known_backup_dirs()? {
+        paths.extend(backups_in(&dir)?.into_iter().map(|(path, _, _)| path));
+    }
+    Ok(paths)
+}
+
+/// Resolves a backup id from `list_backups` to its file. Only names inside the
+/// known backup folders are accepted, never arbitrary paths.
+fn find_backup(id: &str) -> Result<PathBuf, String> {
+    if parse_backup_name(id).is_none() || id.contains(['/', '\\']) {
+        return Err(format!("Invalid backup id '{}'", id));
+    }
+    known_backup_dirs()?
+        .into_iter()
+        .map(|dir| dir.join(id))
+        .find(|path| path.is_file())
+        .ok_or_else(|| format!("Backup '{}' not found", id))
+}
+
+/// Replaces the live database with the backup at `path`, snapshotting the
+/// current one first. The backup must open with the current key before
+/// anything is touched.
+pub(crate) fn restore_from(path: &Path) -> Result<(), String> {
+    validate_backup(path)?;
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    snapshot(&db, "pre-restore").map_err(|e| e.to_string())?;
+    db.import_database(&path.to_path_buf()).map_err(|e| e.to_string())?;
+    drop(db);
+    info!("Restored database from {:?}", path);
+    Ok(())
+}
+
+/// Replaces the live database with a backup, snapshotting the current one first.
+#[command]
+pub fn restore_backup(id: String) -> Result<(), String> {
+    restore_from(&find_backup(&id)?)?;
+    security::audit(AuditAction::Import, &format!("restored backup {}", id));
+    Ok(())
+}
+
+/// When the newest backup in any known backup folder was taken, whatever
+/// it was taken for; `None` if there are none.
+pub(crate) fn last_backup_at() -> Result<Option<String>, String> {
+    Ok(list_backups()?.into_iter().next().map(|backup| backup.created_at))
+}
+
+/// Backups from every known backup folder, newest first.
+#[command]
+pub fn list_backups() -> Result<Vec<BackupInfo>, String> {
+    let mut backups = Vec::new();
+    for dir in known_backup_dirs()? {
+        for (path, taken_at, reason) in backups_in(&dir)? {
+            let metadata = fs::metadata(&path).map_err(|e| e.to_string())?;
+            // The name records when the snapshot was taken; the file's mtime
+            // changes if the backup is ever copied around.
+            let created_at = match Local.from_local_datetime(&taken_at).earliest() {
+                Some(local) => local.to_rfc3339(),
+                None => DateTime::<Utc>::from(metadata.modified().map_err(|e| e.to_string())?).to_rfc3339(),
+            };
+            backups.push((
+                taken_at,
+                BackupInfo {
+                    id: path.file_name().unwrap_or_default().to_string_lossy().to_string(),
+                    path: path.to_string_lossy().to_string(),
+                    created_at,
+                    size_bytes: metadata.len(),
+                    reason,
+                },
+            ));
+        }
+    }
+    backups.sort_by_key(|backup| Reverse(backup.0));
+    Ok(backups.into_iter().map(|(_, info)| info).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn at(y: i32, m: u32, d: u32, h: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap().and_hms_opt(h, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn test_check_archive() {
+        let db = DatabaseManager::open_in_memory().unwrap();
+        assert!(check_archive(&db.conn).is_ok());
+        db.conn.pragma_update(None, "user_version", SCHEMA_VERSION + 1).unwrap();
+        assert!(check_archive(&db.conn).unwrap_err().starts_with(crate::export::NEWER_VERSION));
+        db.conn.pragma_update(None, "user_version", 1).unwrap();
+        assert!(check_archive(&db.conn).is_ok());
+        db.conn.pragma_update(None, "application_id", 42).unwrap();
+        assert!(check_archive(&db.conn).is_err());
+    }
+
+    #[test]
+    fn test_parse_backup_name() {
+        assert_eq!(
+            parse_backup_name("journal-20240105-093000-pre-import.db"),
+            Some((at(2024, 1, 5, 9) + chrono::Duration::minutes(30), "pre-import"))
+        );
+        assert_eq!(parse_backup_name("journal-20240105-093000-.db"), None);
+        assert_eq!(parse_backup_name("journal-2024-pre-import.db"), None);
+        assert_eq!(parse_backup_name("notes.db"), None);
+    }
+
+    #[test]
+    fn test_retention_keeps_newest_per_day_and_week() {
+        let backups: Vec<(PathBuf, NaiveDateTime)> = (1..=20)
+            .flat_map(|day| {
+                vec![
+                    (PathBuf::from(format!("{}-am", day)), at(2024, 1, day, 8)),
+                    (PathBuf::from(format!("{}-pm", day)), at(2024, 1, day, 20)),
+                ]
+            })
+            .collect();
+        let pruned = backups_to_prune(backups, 3, 2);
+        let kept: Vec<String> = (1..=20)
+            .flat_map(|day| vec![format!("{}-am", day), format!("{}-pm", day)])
+            .filter(|name| !pruned.contains(&PathBuf::from(name)))
+            .collect();
+        // Last three days, plus the newest of the previous ISO week (Sunday the 14th)
+        assert_eq!(kept, vec!["14-pm", "18-pm", "19-pm", "20-pm"]);
+    }
+
+    #[test]
+    fn test_retention_keeps_newest_backup() {
+        let backups = vec![("old", at(2024, 1, 9, 8)), ("new", at(2024, 1, 10, 8))];
+        assert_eq!(backups_to_prune(backups, 0, 0), vec!["old"]);
+    }
+
+    #[test]
+    fn test_is_due() {
+        let mut settings = BackupSettings { enabled: true, ..BackupSettings::default() };
+        let now = at(2024, 1, 10, 12);
+        assert!(is_due(&settings, None, now));
+        assert!(!is_due(&settings, Some(at(2024, 1, 10, 1)), now));
+        assert!(is_due(&settings, Some(at(2024, 1, 9, 11)), now));
+        settings.frequency = BackupFrequency::Weekly;
+        assert!(!is_due(&settings, Some(at(2024, 1, 9, 11)), now));
+        settings.enabled = false;
+        assert!(!is_due(&settings, None, now));
+    }
+}
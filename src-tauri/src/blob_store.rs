@@ -0,0 +1,615 @@
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use log::debug;
+use reqwest::blocking::{Client, RequestBuilder, Response};
+use reqwest::{Method, StatusCode};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+// Where sync changesets and cloud backups are kept. Both only need a flat
+// namespace of immutable files, so any backend that can list, read, write and
+// delete by name will do: a synced folder (iCloud Drive, Dropbox, ...),
+// WebDAV, or S3.
+//
+// WebDAV uploads are checked once they're in: the server must report the
+// size that was sent, and the SHA-256 too if it keeps checksums, as Nextcloud
+// does. On Nextcloud, large blobs go up in pieces, and an upload that was cut
+// off picks up from the pieces already there when the same blob is sent again.
+
+/// Blobs bigger than this go up in pieces where the server supports it.
+const WEBDAV_CHUNK_SIZE: usize = 10 * 1024 * 1024;
+const PROPFIND_NAMES: &str =
+    r#"<?xml version="1.0"?><d:propfind xmlns:d="DAV:"><d:prop><d:resourcetype/></d:prop></d:propfind>"#;
+const PROPFIND_SIZES: &str = concat!(
+    r#"<?xml version="1.0"?><d:propfind xmlns:d="DAV:" xmlns:oc="http://owncloud.org/ns">"#,
+    r#"<d:prop><d:getcontentlength/><oc:checksums/></d:prop></d:propfind>"#
+);
+
+/// A configured sync location. Passwords and secret keys are not part of it;
+/// they live in the keychain next to the sync passphrase.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Remote {
+    Folder {
+        path: String,
+    },
+    WebDav {
+        url: String,
+        username: String,
+    },
+    S3 {
+        endpoint: String,
+        region: String,
+        bucket: String,
+        #[serde(default)]
+        prefix: String,
+        access_key_id: String,
+    },
+}
+
+impl Remote {
+    /// Identifies the location in the local sync cursors, so switching to a
+    /// different remote starts from scratch instead of skipping its history.
+    pub fn id(&self) -> String {
+        match self {
+            Remote::Folder { path } => format!("folder:{}", path),
+            Remote::WebDav { url, .. } => format!("webdav:{}", url),
+            Remote::S3 { endpoint, bucket, prefix, .. } => format!("s3:{}/{}/{}", endpoint, bucket, prefix),
+        }
+    }
+
+    pub fn needs_credential(&self) -> bool {
+        !matches!(self, Remote::Folder { .. })
+    }
+
+    pub fn open(&self, credential: Option<String>) -> Result<Box<dyn BlobStore>, String> {
+        let credential = || credential.clone().ok_or_else(|| "This sync location needs a password".to_string());
+        Ok(match self {
+            Remote::Folder { path } => Box::new(FolderStore::new(PathBuf::from(path))?),
+            Remote::WebDav { url, username } => Box::new(WebDavStore {
+                base_url: with_trailing_slash(url),
+                username: username.clone(),
+                password: credential()?,
+                client: Client::new(),
+            }),
+            Remote::S3 { endpoint, region, bucket, prefix, access_key_id } => Box::new(S3Store {
+                endpoint: endpoint.trim_end_matches('/').to_string(),
+                region: region.clone(),
+                bucket: bucket.clone(),
+                prefix: prefix.clone(),
+                access_key_id: access_key_id.clone(),
+                secret_access_key: credential()?,
+                client: Client::new(),
+            }),
+        })
+    }
+}
+
+pub trait BlobStore {
+    /// Names of every blob in the store.
+    fn list(&self) -> Result<Vec<String>, String>;
+    fn get(&self, name: &str) -> Result<Vec<u8>, String>;
+    /// Writes a blob in one piece; readers never see a partial one.
+    fn put(&self, name: &str, data: &[u8]) -> Result<(), String>;
+    fn delete(&self, name: &str) -> Result<(), String>;
+}
+
+fn with_trailing_slash(url: &str) -> String {
+    format!("{}/", url.trim_end_matches('/'))
+}
+
+/// Text of every element called `local_name`, ignoring namespace prefixes.
+/// WebDAV and S3 listings only need this much XML.
+fn element_texts(xml: &str, local_name: &str) -> Vec<String> {
+    let mut texts = Vec::new();
+    let mut rest = xml;
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start + 1..];
+        let Some(end) = rest.find('>') else { break };
+        let tag = &rest[..end];
+        rest = &rest[end + 1..];
+        let name = tag.split_whitespace().next().unwrap_or("");
+        let name = name.rsplit(':').next().unwrap_or(name);
+        if name == local_name && !tag.starts_with('/') && !tag.ends_with('/') {
+            let text = &rest[..rest.find('<').unwrap_or(rest.len())];
+            texts.push(text.replace("&amp;", "&").replace("&lt;", "<").replace("&gt;", ">"));
+        }
+    }
+    texts
+}
+
+/// Everything inside each element called `local_name`, for elements that
+/// don't nest, such as the `response`s of a WebDAV listing.
+fn element_blocks<'a>(xml: &'a str, local_name: &str) -> Vec<&'a str> {
+    let mut blocks = Vec::new();
+    let mut open = None;
+    let mut pos = 0;
+    while let Some(start) = xml[pos..].find('<') {
+        let tag_start = pos + start;
+        let Some(end) = xml[tag_start..].find('>') else { break };
+        let tag = &xml[tag_start + 1..tag_start + end];
+        pos = tag_start + end + 1;
+        let name = tag.trim_start_matches('/').split_whitespace().next().unwrap_or("");
+        let name = name.rsplit(':').next().unwrap_or(name);
+        if name != local_name || tag.ends_with('/') {
+            continue;
+        }
+        match open {
+            None if !tag.starts_with('/') => open = Some(pos),
+            Some(from) if tag.starts_with('/') => {
+                blocks.push(&xml[from..tag_start]);
+                open = None;
+            }
+            _ => {}
+        }
+    }
+    blocks
+}
+
+pub struct FolderStore {
+    root: PathBuf,
+}
+
+impl FolderStore {
+    fn new(root: PathBuf) -> Result<Self, String> {
+        if !root.is_dir() {
+            return Err(format!("Sync folder {:?} does not exist", root));
+        }
+        Ok(FolderStore { root })
+    }
+}
+
+impl BlobStore for FolderStore {
+    fn list(&self) -> Result<Vec<String>, String> {
+        let mut names = Vec::new();
+        for entry in fs::read_dir(&self.root).map_err(|e| format!("Failed to read sync folder: {}", e))? {
+            let entry = entry.map_err(|e| e.to_string())?;
+            if entry.path().is_file() {
+                names.push(entry.file_name().to_string_lossy().into_owned());
+            }
+        }
+        Ok(names)
+    }
+
+    fn get(&self, name: &str) -> Result<Vec<u8>, String> {
+        fs::read(self.root.join(name)).map_err(|e| format!("Failed to read {}: {}", name, e))
+    }
+
+    /// Writes through a temporary file so the file provider never uploads half a blob.
+    fn put(&self, name: &str, data: &[u8]) -> Result<(), String> {
+        let path = self.root.join(name);
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, data).map_err(|e| format!("Failed to write {}: {}", name, e))?;
+        fs::rename(&tmp, &path).map_err(|e| format!("Failed to write {}: {}", name, e))
+    }
+
+    fn delete(&self, name: &str) -> Result<(), String> {
+        fs::remove_file(self.root.join(name)).map_err(|e| format!("Failed to delete {}: {}", name, e))
+    }
+}
+
+pub struct WebDavStore {
+    base_url: String,
+    username: String,
+    password: String,
+    client: Client,
+}
+
+impl WebDavStore {
+    fn request(&self, method: Method, name: &str) -> RequestBuilder {
+        self.request_url(method, &format!("{}{}", self.base_url, name))
+    }
+
+    fn request_url(&self, method: Method, url: &str) -> RequestBuilder {
+        self.client.request(method, url).basic_auth(&self.username, Some(&self.password))
+    }
+
+    fn propfind(&self, url: &str, depth: &str, body: &'static str) -> Result<Response, String> {
+        let propfind = Method::from_bytes(b"PROPFIND").map_err(|e| e.to_string())?;
+        self.request_url(propfind, url)
+            .header("Depth", depth)
+            .header("Content-Type", "application/xml")
+            .body(body)
+            .send()
+            .map_err(|e| format!("Failed to reach the WebDAV server: {}", e))
+    }
+
+    /// Where Nextcloud takes uploads in pieces, if this is a Nextcloud folder:
+    /// `.../remote.php/dav/files/<user>/...` has them at `.../remote.php/dav/uploads/<user>/`.
+    fn uploads_url(&self) -> Option<String> {
+        let (server, path) = self.base_url.split_once("/remote.php/dav/files/")?;
+        let user = path.split('/').next().filter(|user| !user.is_empty())?;
+        Some(format!("{}/remote.php/dav/uploads/{}/", server, user))
+    }
+
+    /// Sizes of the pieces already in upload folder `transfer`, by name, or
+    /// `None` if the folder doesn't exist yet.
+    fn uploaded_chunks(&self, transfer: &str) -> Result<Option<BTreeMap<String, u64>>, String> {
+        let response = self.propfind(transfer, "1", PROPFIND_SIZES)?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let body = check_status(response, "resume upload")?.text().map_err(|e| e.to_string())?;
+        Ok(Some(
+            element_blocks(&body, "response")
+                .into_iter()
+                .filter_map(|response| {
+                    let href = element_texts(response, "href").pop()?;
+                    let size = element_texts(response, "getcontentlength").pop()?.parse().ok()?;
+                    Some((href.trim_end_matches('/').rsplit('/').next()?.to_string(), size))
+                })
+                .collect(),
+        ))
+    }
+
+    /// Uploads `data` as `name` in pieces, the way Nextcloud's chunked upload
+    /// works. The upload folder is named after the blob and its contents, so
+    /// sending the same blob again skips the pieces that already arrived.
+    fn put_chunked(&self, uploads: &str, name: &str, data: &[u8], checksum: &str) -> Result<(), String> {
+        let destination = format!("{}{}", self.base_url, name);
+        let transfer_id = hex::encode(Sha256::digest(format!("{}\n{}", name, checksum)));
+        let transfer = format!("{}journal-{}/", uploads, &transfer_id[..32]);
+        let uploaded = match self.uploaded_chunks(&transfer)? {
+            Some(uploaded) => uploaded,
+            None => {
+                let mkcol = Method::from_bytes(b"MKCOL").map_err(|e| e.to_string())?;
+                let response = self
+                    .request_url(mkcol, &transfer)
+                    .header("Destination", &destination)
+                    .send()
+                    .map_err(|e| format!("Failed to upload {}: {}", name, e))?;
+                check_status(response, "start upload")?;
+                BTreeMap::new()
+            }
+        };
+        for (i, chunk) in data.chunks(WEBDAV_CHUNK_SIZE).enumerate() {
+            // Nextcloud assembles the pieces in order of name
+            let chunk_name = format!("{:05}", i + 1);
+            if uploaded.get(&chunk_name) == Some(&(chunk.len() as u64)) {
+                debug!("Piece {} of {} already uploaded", chunk_name, name);
+                continue;
+            }
+            let response = self
+                .request_url(Method::PUT, &format!("{}{}", transfer, chunk_name))
+                .header("Destination", &destination)
+                .body(chunk.to_vec())
+                .send()
+                .map_err(|e| format!("Failed to upload {}: {}", name, e))?;
+            check_status(response, "upload sync data")?;
+        }
+        let mv = Method::from_bytes(b"MOVE").map_err(|e| e.to_string())?;
+        let response = self
+            .request_url(mv, &format!("{}.file", transfer))
+            .header("Destination", &destination)
+            .header("Overwrite", "T")
+            .header("OC-Total-Length", data.len())
+            .header("OC-Checksum", checksum)
+            .send()
+            .map_err(|e| format!("Failed to upload {}: {}", name, e))?;
+        check_status(response, "finish upload").map(|_| ())
+    }
+
+    /// Checks the server holds `name` as it was sent: `len` bytes, and the
+    /// SHA-256 in `checksum` if the server reports one.
+    fn verify(&self, name: &str, len: usize, checksum: &str) -> Result<(), String> {
+        let response = self.propfind(&format!("{}{}", self.base_url, name), "0", PROPFIND_SIZES)?;
+        let body = check_status(response, "check upload")?.text().map_err(|e| e.to_string())?;
+        let size = element_texts(&body, "getcontentlength").pop().and_then(|size| size.parse().ok());
+        if size != Some(len) {
+            return Err(format!("{} didn't arrive intact: the server has a different size", name));
+        }
+        // Nextcloud lists the checksums it knows, e.g. `SHA1:... SHA256:...`
+        let stored = element_texts(&body, "checksum");
+        let expected = checksum.split_once(':').map_or(checksum, |(_, sum)| sum);
+        let sha256 = stored.iter().flat_map(|sums| sums.split_whitespace()).find_map(|sum| {
+            sum.split_once(':').filter(|(kind, _)| kind.eq_ignore_ascii_case("SHA256")).map(|(_, sum)| sum)
+        });
+        match sha256 {
+            Some(sum) if !sum.eq_ignore_ascii_case(expected) => {
+                Err(format!("{} didn't arrive intact: the server has different contents", name))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+fn check_status(response: Response, action: &str) -> Result<Response, String> {
+    if response.status().is_success() {
+        Ok(response)
+    } else {
+        Err(format!("Failed to {}: server returned {}", action, response.status()))
+    }
+}
+
+impl BlobStore for WebDavStore {
+    fn list(&self) -> Result<Vec<String>, String> {
+        let response = self.propfind(&self.base_url, "1", PROPFIND_NAMES)?;
+        let body = check_status(response, "list sync folder")?
+            .text()
+            .map_err(|e| e.to_string())?;
+        // The collection itself is listed too, with a trailing slash
+        Ok(element_texts(&body, "href")
+            .iter()
+            .filter(|href| !href.ends_with('/'))
+            .filter_map(|href| href.rsplit('/').next().map(str::to_string))
+            .collect())
+    }
+
+    fn get(&self, name: &str) -> Result<Vec<u8>, String> {
+        let response = self
+            .request(Method::GET, name)
+            .send()
+            .map_err(|e| format!("Failed to download {}: {}", name, e))?;
+        let bytes = check_status(response, "download sync data")?
+            .bytes()
+            .map_err(|e| e.to_string())?;
+        Ok(bytes.to_vec())
+    }
+
+    fn put(&self, name: &str, data: &[u8]) -> Result<(), String> {
+        let checksum = format!("SHA256:{}", hex::encode(Sha256::digest(data)));
+        match self.uploads_url() {
+            Some(uploads) if data.len() > WEBDAV_CHUNK_SIZE => self.put_chunked(&uploads, name, data, &checksum)?,
+            _ => {
+                let response = self
+                    .request(Method::PUT, name)
+                    .header("OC-Checksum", &checksum)
+                    .body(data.to_vec())
+                    .send()
+                    .map_err(|e| format!("Failed to upload {}: {}", name, e))?;
+                check_status(response, "upload sync data")?;
+            }
+        }
+        self.verify(name, data.len(), &checksum)
+    }
+
+    fn delete(&self, name: &str) -> Result<(), String> {
+        let response = self
+            .request(Method::DELETE, name)
+            .send()
+            .map_err(|e| format!("Failed to delete {}: {}", name, e))?;
+        check_status(response, "delete sync data").map(|_| ())
+    }
+}
+
+pub struct S3Store {
+    endpoint: String,
+    region: String,
+    bucket: String,
+    prefix: String,
+    access_key_id: String,
+    secret_access_key: String,
+    client: Client,
+}
+
+const EMPTY_PAYLOAD_SHA256: &str = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+
+/// RFC 3986 encoding as SigV4 expects it; `/` is kept in paths only.
+fn uri_encode(value: &str, keep_slash: bool) -> String {
+    let mut encoded = String::new();
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => encoded.push(byte as char),
+            b'/' if keep_slash => encoded.push('/'),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+fn hmac_sha256(key: &[u8], data: &str) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(data.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// Sorted, encoded query string; used both for signing and for the request URL
+/// so the two can't disagree.
+fn canonical_query(query: &[(&str, &str)]) -> String {
+    let mut pairs: Vec<String> = query
+        .iter()
+        .map(|(key, value)| format!("{}={}", uri_encode(key, false), uri_encode(value, false)))
+        .collect();
+    pairs.sort();
+    pairs.join("&")
+}
+
+/// AWS Signature Version 4 over the `host`, `x-amz-content-sha256` and
+/// `x-amz-date` headers, which is all these requests send.
+#[allow(clippy::too_many_arguments)]
+fn signature_v4(
+    secret_access_key: &str,
+    region: &str,
+    method: &str,
+    host: &str,
+    path: &str,
+    query: &[(&str, &str)],
+    payload_sha256: &str,
+    amz_date: &str,
+) -> String {
+    let canonical_request = format!(
+        "{}\n{}\n{}\nhost:{}\nx-amz-content-sha256:{}\nx-amz-date:{}\n\nhost;x-amz-content-sha256;x-amz-date\n{}",
+        method,
+        uri_encode(path, true),
+        canonical_query(query),
+        host,
+        payload_sha256,
+        amz_date,
+        payload_sha256
+    );
+    let date = &amz_date[..8];
+    let scope = format!("{}/{}/s3/aws4_request", date, region);
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+        amz_date,
+        scope,
+        hex::encode(Sha256::digest(canonical_request.as_bytes()))
+    );
+    let mut key = hmac_sha256(format!("AWS4{}", secret_access_key).as_bytes(), date);
+    for part in [region, "s3", "aws4_request"] {
+        key = hmac_sha256(&key, part);
+    }
+    hex::encode(hmac_sha256(&key, &string_to_sign))
+}
+
+impl S3Store {
+    /// Sends a path-style request (`endpoint/bucket/key`), which every
+    /// S3-compatible service accepts.
+    fn send(
+        &self,
+        method: Method,
+        key: &str,
+        query: &[(&str, &str)],
+        body: Option<&[u8]>,
+    ) -> Result<reqwest::blocking::Response, String> {
+        let url = reqwest::Url::parse(&self.endpoint).map_err(|e| format!("Invalid S3 endpoint: {}", e))?;
+        let host = match (url.host_str(), url.port()) {
+            (Some(host), Some(port)) => format!("{}:{}", host, port),
+            (Some(host), None) => host.to_string(),
+            (None, _) => return Err("Invalid S3 endpoint: missing host".to_string()),
+        };
+        let path = format!("/{}/{}", self.bucket, key);
+        let payload_sha256 = match body {
+            Some(body) => hex::encode(Sha256::digest(body)),
+            None => EMPTY_PAYLOAD_SHA256.to_string(),
+        };
+        let amz_date = Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+        let signature = signature_v4(
+            &self.secret_access_key,
+            &self.region,
+            method.as_str(),
+            &host,
+            &path,
+            query,
+            &payload_sha256,
+            &amz_date,
+        );
+        let authorization = format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}/{}/s3/aws4_request, SignedHeaders=host;x-amz-content-sha256;x-amz-date, Signature={}",
+            self.access_key_id,
+            &amz_date[..8],
+            self.region,
+            signature
+        );
+        let mut url = format!("{}{}", self.endpoint, uri_encode(&path, true));
+        if !query.is_empty() {
+            url = format!("{}?{}", url, canonical_query(query));
+        }
+        let mut request = self
+            .client
+            .request(method, url)
+            .header("x-amz-content-sha256", payload_sha256)
+            .header("x-amz-date", amz_date)
+            .header("Authorization", authorization);
+        if let Some(body) = body {
+            request = request.body(body.to_vec());
+        }
+        request.send().map_err(|e| format!("S3 request failed: {}", e))
+    }
+}
+
+impl BlobStore for S3Store {
+    fn list(&self) -> Result<Vec<String>, String> {
+        let mut names = Vec::new();
+        let mut token: Option<String> = None;
+        loop {
+            let mut query = vec![("list-type", "2"), ("prefix", self.prefix.as_str())];
+            if let Some(token) = &token {
+                query.push(("continuation-token", token.as_str()));
+            }
+            let body = check_status(self.send(Method::GET, "", &query, None)?, "list sync bucket")?
+                .text()
+                .map_err(|e| e.to_string())?;
+            names.extend(
+                element_texts(&body, "Key")
+                    .iter()
+                    .filter_map(|key| key.strip_prefix(&self.prefix).map(str::to_string)),
+            );
+            let truncated = element_texts(&body, "IsTruncated").first().map(String::as_str) == Some("true");
+            token = element_texts(&body, "NextContinuationToken").into_iter().next();
+            if !truncated || token.is_none() {
+                return Ok(names);
+            }
+        }
+    }
+
+    fn get(&self, name: &str) -> Result<Vec<u8>, String> {
+        let key = format!("{}{}", self.prefix, name);
+        let bytes = check_status(self.send(Method::GET, &key, &[], None)?, "download sync data")?
+            .bytes()
+            .map_err(|e| e.to_string())?;
+        Ok(bytes.to_vec())
+    }
+
+    fn put(&self, name: &str, data: &[u8]) -> Result<(), String> {
+        let key = format!("{}{}", self.prefix, name);
+        check_status(self.send(Method::PUT, &key, &[], Some(data))?, "upload sync data").map(|_| ())
+    }
+
+    fn delete(&self, name: &str) -> Result<(), String> {
+        let key = format!("{}{}", self.prefix, name);
+        check_status(self.send(Method::DELETE, &key, &[], None)?, "delete sync data").map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_element_texts_ignores_namespace_prefix() {
+        let xml = r#"<d:multistatus xmlns:d="DAV:"><d:response><d:href>/dav/journal/</d:href></d:response>
+            <d:response><d:href>/dav/journal/a.000000000001.changeset</d:href></d:response></d:multistatus>"#;
+        assert_eq!(
+            element_texts(xml, "href"),
+            vec!["/dav/journal/", "/dav/journal/a.000000000001.changeset"]
+        );
+        assert_eq!(element_texts("<Key>x&amp;y</Key><Keys/>", "Key"), vec!["x&y"]);
+    }
+
+    #[test]
+    fn test_element_blocks_splits_responses() {
+        let xml = r#"<d:multistatus xmlns:d="DAV:"><d:response><d:href>/up/</d:href><d:getcontentlength/></d:response>
+            <d:response><d:href>/up/00001</d:href><d:getcontentlength>10</d:getcontentlength></d:response>
+            </d:multistatus>"#;
+        let blocks = element_blocks(xml, "response");
+        assert_eq!(blocks.len(), 2);
+        assert!(element_texts(blocks[0], "getcontentlength").is_empty());
+        assert_eq!(element_texts(blocks[1], "href"), vec!["/up/00001"]);
+        assert_eq!(element_texts(blocks[1], "getcontentlength"), vec!["10"]);
+    }
+
+    #[test]
+    fn test_uploads_url_only_for_nextcloud() {
+        let store = |url: &str| WebDavStore {
+            base_url: with_trailing_slash(url),
+            username: "ana".to_string(),
+            password: "secret".to_string(),
+            client: Client::new(),
+        };
+        assert_eq!(
+            store("https://cloud.example.com/remote.php/dav/files/ana/Journal").uploads_url().as_deref(),
+            Some("https://cloud.example.com/remote.php/dav/uploads/ana/")
+        );
+        assert_eq!(store("https://dav.example.com/journal").uploads_url(), None);
+    }
+
+    #[test]
+    fn test_signature_v4_matches_aws_example() {
+        // "GET Bucket (List Objects)" example from the AWS SigV4 documentation
+        let signature = signature_v4(
+            "wJalrXUtnFEMI/K7MDENG/bPxRfiCYEXAMPLEKEY",
+            "us-east-1",
+            "GET",
+            "examplebucket.s3.amazonaws.com",
+            "/",
+            &[("max-keys", "2"), ("prefix", "J")],
+            EMPTY_PAYLOAD_SHA256,
+            "20130524T000000Z",
+        );
+        assert_eq!(signature, "34b48302e7b5fa45bde8084f4b7868a86f0a534bc59db6670ed5711ef69dc6f7");
+    }
+}
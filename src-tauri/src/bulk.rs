@@ -0,0 +1,139 @@
+use log::debug;
+use tauri::command;
+
+use journal_core::in_transaction;
+
+use crate::sealing;
+use crate::tags::{normalize_tags, replace_tags};
+use crate::DatabaseManager;
+
+// Multi-select actions from the entry list. Each runs in a single transaction
+// so the selection is either changed as a whole or not at all.
+
+fn delete_entries(conn: &rusqlite::Connection, ids: &[i32]) -> Result<usize, String> {
+    for id in ids {
+        sealing::ensure_unsealed(conn, *id)?;
+    }
+    in_transaction(conn, |tx| {
+        let mut delete = tx.prepare("DELETE FROM journal_entries WHERE id = ?1")?;
+        let mut deleted = 0;
+        for id in ids {
+            deleted += delete.execute(rusqlite::params![id])?;
+        }
+        Ok::<_, rusqlite::Error>(deleted)
+    })
+    .map_err(|e| e.to_string())
+}
+
+fn set_tags(conn: &rusqlite::Connection, ids: &[i32], tags: Vec<String>) -> Result<(), String> {
+    let tags = normalize_tags(tags)?;
+    in_transaction(conn, |tx| ids.iter().try_for_each(|id| replace_tags(tx, *id, &tags)))
+        .map_err(|e| e.to_string())
+}
+
+fn move_to_notebook(conn: &rusqlite::Connection, ids: &[i32], notebook_id: Option<i32>) -> Result<(), String> {
+    in_transaction(conn, |tx| {
+        let mut update = tx.prepare("UPDATE journal_entries SET notebook_id = ?1 WHERE id = ?2")?;
+        for id in ids {
+            update.execute(rusqlite::params![notebook_id, id])?;
+        }
+        Ok::<_, rusqlite::Error>(())
+    })
+    .map_err(|e| e.to_string())
+}
+
+/// Deletes every listed entry and returns how many were removed.
+#[command]
+pub fn bulk_delete(ids: Vec<i32>) -> Result<usize, String> {
+    debug!("Bulk deleting {} entries", ids.len());
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    delete_entries(&db.conn, &ids)
+}
+
+/// Replaces the tags on every listed entry with `tags`.
+#[command]
+pub fn bulk_set_tags(ids: Vec<i32>, tags: Vec<String>) -> Result<(), String> {
+    debug!("Setting tags on {} entries", ids.len());
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    set_tags(&db.conn, &ids, tags)
+}
+
+/// Moves every listed entry into a notebook, or out of any notebook when
+/// `notebook_id` is `null`.
+#[command]
+pub fn bulk_move_to_notebook(ids: Vec<i32>, notebook_id: Option<i32>) -> Result<(), String> {
+    debug!("Moving {} entries to notebook {:?}", ids.len(), notebook_id);
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    move_to_notebook(&db.conn, &ids, notebook_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tags::tags_for_entry;
+    use journal_core::insert_entry;
+
+    fn entries(conn: &rusqlite::Connection, count: usize) -> Vec<i32> {
+        (0..count)
+            .map(|_| insert_entry(conn, "Monday", "<p>Notes</p>", "2024-03-05T12:00:00+00:00").unwrap())
+            .collect()
+    }
+
+    fn entry_count(conn: &rusqlite::Connection) -> i64 {
+        conn.query_row("SELECT COUNT(*) FROM journal_entries", [], |row| row.get(0)).unwrap()
+    }
+
+    fn notebook_of(conn: &rusqlite::Connection, id: i32) -> Option<i32> {
+        conn.query_row("SELECT notebook_id FROM journal_entries WHERE id = ?1", [id], |row| row.get(0))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_delete_counts_removed_entries() {
+        let db = DatabaseManager::open_in_memory().unwrap();
+        let ids = entries(&db.conn, 3);
+        // Ids that are already gone aren't counted
+        assert_eq!(delete_entries(&db.conn, &[ids[0], ids[1], 999]).unwrap(), 2);
+        assert_eq!(entry_count(&db.conn), 1);
+    }
+
+    #[test]
+    fn test_set_tags() {
+        let db = DatabaseManager::open_in_memory().unwrap();
+        let ids = entries(&db.conn, 2);
+        set_tags(&db.conn, &ids, vec!["#work".to_string(), "travel".to_string()]).unwrap();
+        for id in &ids {
+            assert_eq!(tags_for_entry(&db.conn, *id).unwrap(), ["travel", "work"]);
+        }
+    }
+
+    #[test]
+    fn test_set_tags_is_all_or_nothing() {
+        let db = DatabaseManager::open_in_memory().unwrap();
+        let ids = entries(&db.conn, 2);
+        set_tags(&db.conn, &ids, vec!["work".to_string()]).unwrap();
+        assert!(set_tags(&db.conn, &ids, vec!["two words".to_string()]).is_err());
+        // The missing entry fails after the first has been retagged
+        assert!(set_tags(&db.conn, &[ids[0], 999], vec!["travel".to_string()]).is_err());
+        for id in &ids {
+            assert_eq!(tags_for_entry(&db.conn, *id).unwrap(), ["work"]);
+        }
+    }
+
+    #[test]
+    fn test_move_to_notebook_is_all_or_nothing() {
+        let db = DatabaseManager::open_in_memory().unwrap();
+        let ids = entries(&db.conn, 2);
+        db.conn
+            .execute("INSERT INTO notebooks (name, created_at) VALUES ('Travel', '2024-03-05T12:00:00+00:00')", [])
+            .unwrap();
+        let notebook = db.conn.last_insert_rowid() as i32;
+        move_to_notebook(&db.conn, &ids, Some(notebook)).unwrap();
+        assert!(move_to_notebook(&db.conn, &ids, Some(notebook + 1)).is_err());
+        for id in &ids {
+            assert_eq!(notebook_of(&db.conn, *id), Some(notebook));
+        }
+        move_to_notebook(&db.conn, &ids[..1], None).unwrap();
+        assert_eq!((notebook_of(&db.conn, ids[0]), notebook_of(&db.conn, ids[1])), (None, Some(notebook)));
+    }
+}
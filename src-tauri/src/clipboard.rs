@@ -0,0 +1,240 @@
+use log::{debug, warn};
+use serde::Deserialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+use std::time::Duration;
+use tauri::{command, AppHandle};
+use tauri_plugin_clipboard_manager::ClipboardExt;
+
+use crate::export::{html_to_markdown, paragraphs, ExportedEntry};
+use crate::locks::is_locked;
+use crate::settings::Settings;
+use crate::tags::tags_for_entry;
+use crate::DatabaseManager;
+
+// Journal text copied out of the app doesn't linger on the clipboard, where
+// every other app and clipboard manager can read it. When the setting is on,
+// the clipboard is emptied a while after each copy, unless something else was
+// copied in the meantime. Copies made in the editor (Edit ▸ Copy or the
+// shortcut) are reported by the window, since the native menu item copies on
+// its own. `copy_entry_to_clipboard` copies a whole entry, with its date and
+// tags, as Markdown, plain text or HTML for pasting elsewhere.
+
+/// Bumped by every copy, so only the latest one's timer clears.
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// The text `copy_entry` puts on the clipboard: the title, then the body as Markdown.
+fn entry_text(title: &str, body: &str) -> String {
+    let body = html_to_markdown(body);
+    match (title.trim(), body.is_empty()) {
+        ("", _) => body,
+        (title, true) => title.to_string(),
+        (title, false) => format!("{}\n\n{}", title, body),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CopyFormat {
+    Markdown,
+    Plaintext,
+    /// HTML for apps that paste formatted text, with plain text for the rest.
+    Html,
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// The line under the heading: the day, unless the heading already is the
+/// day, then the tags.
+fn byline(entry: &ExportedEntry) -> String {
+    let mut parts = Vec::new();
+    if !entry.title.trim().is_empty() {
+        parts.push(entry.day());
+    }
+    if !entry.tags.is_empty() {
+        let tags: Vec<String> = entry.tags.iter().map(|tag| format!("#{}", tag)).collect();
+        parts.push(tags.join(" "));
+    }
+    parts.retain(|part| !part.is_empty());
+    parts.join(" · ")
+}
+
+fn to_markdown(entry: &ExportedEntry) -> String {
+    let mut md = format!("# {}\n\n", entry.heading());
+    let byline = byline(entry);
+    if !byline.is_empty() {
+        md.push_str(&format!("*{}*\n\n", byline));
+    }
+    md.push_str(&html_to_markdown(&entry.body));
+    md.trim_end().to_string()
+}
+
+fn to_plaintext(entry: &ExportedEntry) -> String {
+    let mut lines = vec![entry.heading(), byline(entry)];
+    lines.retain(|line| !line.is_empty());
+    let mut text = lines.join("\n");
+    for paragraph in paragraphs(&entry.body) {
+        text.push_str("\n\n");
+        text.push_str(&paragraph);
+    }
+    text
+}
+
+fn to_html(entry: &ExportedEntry) -> String {
+    let mut html = format!("<h1>{}</h1>\n", escape(&entry.heading()));
+    let byline = byline(entry);
+    if !byline.is_empty() {
+        html.push_str(&format!("<p><em>{}</em></p>\n", escape(&byline)));
+    }
+    html.push_str(&entry.body);
+    html
+}
+
+fn copied_entry(conn: &rusqlite::Connection, id: i32) -> Result<ExportedEntry, String> {
+    if is_locked(conn, id).map_err(|e| e.to_string())? {
+        return Err(format!("Entry {} is locked; unlock it before copying", id));
+    }
+    let entry = conn
+        .query_row(
+            "SELECT uuid, title, created_at, body FROM journal_entries WHERE id = ?1",
+            [id],
+            |row| {
+                Ok(ExportedEntry {
+                    uuid: row.get(0)?,
+                    title: row.get(1)?,
+                    created_at: row.get(2)?,
+                    tags: Vec::new(),
+                    mood: None,
+                    body: row.get(3)?,
+                })
+            },
+        )
+        .map_err(|_| format!("Entry {} not found", id))?;
+    let tags = tags_for_entry(conn, id).map_err(|e| e.to_string())?;
+    Ok(ExportedEntry { tags, ..entry })
+}
+
+/// Empties the clipboard after the configured delay if it still holds `text`.
+fn schedule_clear(app: &AppHandle, text: String) {
+    let secs = Settings::load().clipboard.clear_after_secs;
+    if secs == 0 || text.is_empty() {
+        return;
+    }
+    let generation = GENERATION.fetch_add(1, Ordering::SeqCst) + 1;
+    let app = app.clone();
+    thread::spawn(move || {
+        thread::sleep(Duration::from_secs(secs as u64));
+        if GENERATION.load(Ordering::SeqCst) != generation {
+            return;
+        }
+        // Whatever the user copied from elsewhere since is theirs to keep
+        if app.clipboard().read_text().ok().as_deref() != Some(text.as_str()) {
+            return;
+        }
+        match app.clipboard().clear() {
+            Ok(()) => debug!("Cleared copied journal text from the clipboard"),
+            Err(e) => warn!("Could not clear the clipboard: {}", e),
+        }
+    });
+}
+
+/// Copies an entry's title and text to the clipboard. Locked entries are refused.
+#[command]
+pub fn copy_entry(app: AppHandle, id: i32) -> Result<(), String> {
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    if is_locked(&db.conn, id).map_err(|e| e.to_string())? {
+        return Err(format!("Entry {} is locked; unlock it before copying", id));
+    }
+    let (title, body): (String, String) = db
+        .conn
+        .query_row("SELECT title, body FROM journal_entries WHERE id = ?1", [id], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })
+        .map_err(|e| e.to_string())?;
+    let text = entry_text(&title, &body);
+    app.clipboard().write_text(text.clone()).map_err(|e| e.to_string())?;
+    schedule_clear(&app, text);
+    Ok(())
+}
+
+/// Copies entry `id` with its date and tags, as `format`. Locked entries are
+/// refused.
+#[command]
+pub fn copy_entry_to_clipboard(app: AppHandle, id: i32, format: CopyFormat) -> Result<(), String> {
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    let entry = copied_entry(&db.conn, id)?;
+    // What the clipboard reads back as text, so it can be cleared later
+    let text = match format {
+        CopyFormat::Markdown => {
+            let text = to_markdown(&entry);
+            app.clipboard().write_text(text.clone()).map_err(|e| e.to_string())?;
+            text
+        }
+        CopyFormat::Plaintext => {
+            let text = to_plaintext(&entry);
+            app.clipboard().write_text(text.clone()).map_err(|e| e.to_string())?;
+            text
+        }
+        CopyFormat::Html => {
+            let text = to_plaintext(&entry);
+            app.clipboard()
+                .write_html(to_html(&entry), Some(text.clone()))
+                .map_err(|e| e.to_string())?;
+            text
+        }
+    };
+    schedule_clear(&app, text);
+    Ok(())
+}
+
+/// Called by the window after text was copied from it, so it gets cleared too.
+#[command]
+pub fn clear_clipboard_later(app: AppHandle, text: String) {
+    schedule_clear(&app, text);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use journal_core::insert_entry;
+
+    #[test]
+    fn test_entry_text() {
+        assert_eq!(entry_text("Monday", "<p>Rain all <strong>day</strong></p>"), "Monday\n\nRain all **day**");
+        assert_eq!(entry_text("  ", "<p>Untitled</p>"), "Untitled");
+        assert_eq!(entry_text("Monday", ""), "Monday");
+    }
+
+    #[test]
+    fn test_copy_formats() {
+        let db = DatabaseManager::open_in_memory().unwrap();
+        let id = insert_entry(
+            &db.conn,
+            "Tram <28>",
+            "<p>Up to the <strong>castle</strong></p><ul><li>tarts</li></ul>",
+            "2024-03-04T10:00:00+00:00",
+        )
+        .unwrap();
+        crate::tags::replace_tags(&db.conn, id, &["lisbon".to_string()]).unwrap();
+        let entry = copied_entry(&db.conn, id).unwrap();
+        let day = entry.day();
+        assert_eq!(
+            to_markdown(&entry),
+            format!("# Tram <28>\n\n*{} · #lisbon*\n\nUp to the **castle**\n\n- tarts", day)
+        );
+        assert_eq!(to_plaintext(&entry), format!("Tram <28>\n{} · #lisbon\n\nUp to the castle\n\n• tarts", day));
+        assert!(to_html(&entry).starts_with(&format!("<h1>Tram &lt;28&gt;</h1>\n<p><em>{} · #lisbon</em></p>\n<p>", day)));
+
+        let untitled = ExportedEntry {
+            title: String::new(),
+            tags: Vec::new(),
+            ..entry
+        };
+        assert_eq!(to_markdown(&untitled), format!("# {}\n\nUp to the **castle**\n\n- tarts", day));
+    }
+}
@@ -0,0 +1,428 @@
+use chrono::{Local, NaiveDateTime, TimeZone};
+use log::{debug, error, info, warn};
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+use tauri::command;
+
+use journal_core::audit::AuditAction;
+use journal_core::keychain::KeychainManager;
+
+use crate::backup::{self, backup_file_name, backups_dir, backups_to_prune, is_overdue, parse_backup_name};
+use crate::blob_store::{BlobStore, Remote};
+use crate::crypto::{self, Sealed};
+use crate::jobs::{Job, JobKind, CANCELLED};
+use crate::security;
+use crate::settings::{CloudBackupSettings, Settings};
+use crate::DatabaseManager;
+
+// Off-site backups in any S3-compatible store (AWS, MinIO, Backblaze B2 and
+// the like) or on a WebDAV server such as Nextcloud. A snapshot is taken the
+// way a local backup is, sealed with a passphrase before it leaves the
+// machine and uploaded as `journal-<timestamp>-<reason>.db.sealed`, so the
+// server only ever holds ciphertext. The scheduler uploads one when the last
+// is older than the chosen frequency and prunes old ones the way local
+// backups are pruned. A sealed snapshot waits in `Backups/` until its upload
+// succeeds, so one that fails is sent again, unchanged, on the next run.
+// The snapshot inside is still encrypted with the journal's key, so restoring
+// one needs that key as well as the passphrase.
+
+pub(crate) const PASSPHRASE_ACCOUNT: &str = "journal_cloud_backup_passphrase";
+pub(crate) const CREDENTIAL_ACCOUNT: &str = "journal_cloud_backup_credential";
+const SEALED_SUFFIX: &str = ".sealed";
+const SCHEDULED_REASON: &str = "scheduled";
+/// Used when the store doesn't care, as MinIO doesn't.
+const DEFAULT_REGION: &str = "us-east-1";
+/// Holds a snapshot in `Backups/` while it's sealed or after it's opened.
+const TRANSFER_FILE_NAME: &str = "cloud-transfer.tmp";
+/// Folder in `Backups/` for sealed snapshots not yet uploaded.
+const PENDING_DIR_NAME: &str = "cloud-pending";
+const MIN_PASSPHRASE_CHARS: usize = 8;
+/// Lets the local scheduler take its backup first.
+const SCHEDULER_START_DELAY: Duration = Duration::from_secs(10 * 60);
+const SCHEDULER_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CloudCredentials {
+    pub access_key_id: String,
+    pub secret_access_key: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CloudBackupInfo {
+    /// Name of the snapshot where it's stored.
+    id: String,
+    created_at: String,
+    /// Why the snapshot was taken, e.g. `scheduled`.
+    reason: String,
+}
+
+/// Splits `journal-20240105-093000-scheduled.db.sealed` into its local timestamp and reason.
+fn parse_sealed_name(name: &str) -> Option<(NaiveDateTime, &str)> {
+    parse_backup_name(name.strip_suffix(SEALED_SUFFIX)?)
+}
+
+/// Every snapshot in `store`, newest first.
+fn snapshots_in(store: &dyn BlobStore) -> Result<Vec<(String, NaiveDateTime, String)>, String> {
+    let mut snapshots: Vec<_> = store
+        .list()?
+        .into_iter()
+        .filter_map(|name| {
+            let (taken_at, reason) = parse_sealed_name(&name)?;
+            let reason = reason.to_string();
+            Some((name, taken_at, reason))
+        })
+        .collect();
+    snapshots.sort_by_key(|snapshot| Reverse(snapshot.1));
+    Ok(snapshots)
+}
+
+fn scheduled_snapshots(store: &dyn BlobStore) -> Result<Vec<(String, NaiveDateTime)>, String> {
+    Ok(snapshots_in(store)?
+        .into_iter()
+        .filter(|(_, _, reason)| reason == SCHEDULED_REASON)
+        .map(|(name, taken_at, _)| (name, taken_at))
+        .collect())
+}
+
+/// Removes the scheduled snapshots the retention policy no longer keeps.
+fn prune(store: &dyn BlobStore, settings: &CloudBackupSettings) -> Result<(), String> {
+    for name in backups_to_prune(scheduled_snapshots(store)?, settings.keep_daily, settings.keep_weekly) {
+        debug!("Pruning old cloud backup {}", name);
+        if let Err(e) = store.delete(&name) {
+            warn!("Failed to prune cloud backup {}: {}", name, e);
+        }
+    }
+    Ok(())
+}
+
+fn transfer_file() -> Result<PathBuf, String> {
+    let dir = backups_dir().map_err(|e| e.to_string())?;
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create backups directory: {}", e))?;
+    Ok(dir.join(TRANSFER_FILE_NAME))
+}
+
+pub(crate) fn pending_dir() -> Result<PathBuf, String> {
+    Ok(backups_dir().map_err(|e| e.to_string())?.join(PENDING_DIR_NAME))
+}
+
+/// Uploads the sealed snapshot at `path` to `store` under its file name and
+/// removes it once it's there.
+fn upload_pending(store: &dyn BlobStore, path: &Path) -> Result<String, String> {
+    let name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+    let sealed = fs::read(path).map_err(|e| format!("Failed to read {}: {}", name, e))?;
+    store.put(&name, &sealed)?;
+    fs::remove_file(path).map_err(|e| format!("Failed to remove {}: {}", path.display(), e))?;
+    info!("Uploaded cloud backup {}", name);
+    Ok(name)
+}
+
+/// The sealed snapshots waiting in `dir` to be uploaded, oldest first.
+pub(crate) fn pending_in(dir: &Path) -> Result<Vec<PathBuf>, String> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut pending = Vec::new();
+    for dir_entry in fs::read_dir(dir).map_err(|e| e.to_string())? {
+        let path = dir_entry.map_err(|e| e.to_string())?.path();
+        let name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+        if let Some((taken_at, _)) = parse_sealed_name(&name) {
+            pending.push((taken_at, path));
+        }
+    }
+    pending.sort();
+    Ok(pending.into_iter().map(|(_, path)| path).collect())
+}
+
+/// Sends the snapshots in `dir` whose uploads failed before.
+fn resume_uploads(store: &dyn BlobStore, dir: &Path) -> Result<(), String> {
+    for path in pending_in(dir)? {
+        debug!("Resuming upload of {:?}", path);
+        upload_pending(store, &path)?;
+    }
+    Ok(())
+}
+
+/// Snapshots the journal in `db`, seals it with `passphrase` and uploads it
+/// to `store` as part of `job`. Returns the name it was uploaded as.
+fn upload_snapshot(
+    db: &DatabaseManager,
+    store: &dyn BlobStore,
+    passphrase: &str,
+    reason: &str,
+    job: &Job,
+) -> Result<String, String> {
+    job.step(0, 3, None)?;
+    let tmp = transfer_file()?;
+    db.backup_to(&tmp).map_err(|e| e.to_string())?;
+    let snapshot = fs::read(&tmp).map_err(|e| format!("Failed to read snapshot: {}", e));
+    let _ = fs::remove_file(&tmp);
+    job.step(1, 3, None)?;
+    let sealed = crypto::seal(passphrase, &snapshot?)?.to_bytes();
+    job.step(2, 3, None)?;
+    let dir = pending_dir()?;
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    let path = dir.join(format!("{}{}", backup_file_name(reason), SEALED_SUFFIX));
+    fs::write(&path, sealed).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    upload_pending(store, &path)
+}
+
+/// Downloads snapshot `name` from `store`, opens it with `passphrase` and
+/// restores the journal from it as part of `job`.
+fn restore_snapshot(store: &dyn BlobStore, passphrase: &str, name: &str, job: &Job) -> Result<(), String> {
+    job.step(0, 3, None)?;
+    let sealed = Sealed::from_bytes(&store.get(name)?)?;
+    job.step(1, 3, None)?;
+    let snapshot = crypto::open(passphrase, &sealed)?;
+    job.step(2, 3, None)?;
+    let tmp = transfer_file()?;
+    let result = fs::write(&tmp, snapshot)
+        .map_err(|e| format!("Failed to write snapshot: {}", e))
+        .and_then(|_| backup::restore_from(&tmp));
+    let _ = fs::remove_file(&tmp);
+    result
+}
+
+fn stored_secret(account: &str) -> Result<Option<String>, String> {
+    KeychainManager::get_secret(account).map_err(|e| e.to_string())
+}
+
+/// The configured store and the passphrase its snapshots are sealed with.
+fn open_configured(settings: &CloudBackupSettings) -> Result<(Box<dyn BlobStore>, String), String> {
+    if !settings.enabled {
+        return Err("Cloud backup is not set up".to_string());
+    }
+    let remote = settings.remote.as_ref().ok_or("No cloud backup location is configured")?;
+    let passphrase = stored_secret(PASSPHRASE_ACCOUNT)?
+        .ok_or_else(|| "Cloud backup has no passphrase; set it up again".to_string())?;
+    Ok((remote.open(stored_secret(CREDENTIAL_ACCOUNT)?)?, passphrase))
+}
+
+fn backup_info(name: String, taken_at: NaiveDateTime, reason: String) -> CloudBackupInfo {
+    let created_at = match Local.from_local_datetime(&taken_at).earliest() {
+        Some(local) => local.to_rfc3339(),
+        None => taken_at.and_utc().to_rfc3339(),
+    };
+    CloudBackupInfo {
+        id: name,
+        created_at,
+        reason,
+    }
+}
+
+fn list_in(store: &dyn BlobStore) -> Result<Vec<CloudBackupInfo>, String> {
+    Ok(snapshots_in(store)?
+        .into_iter()
+        .map(|(name, taken_at, reason)| backup_info(name, taken_at, reason))
+        .collect())
+}
+
+/// Uploads a snapshot if one is due, then applies the retention policy.
+fn run_scheduled_upload() -> Result<(), String> {
+    let settings = Settings::load().cloud_backup;
+    if !settings.enabled {
+        return Ok(());
+    }
+    let (store, passphrase) = open_configured(&settings)?;
+    resume_uploads(store.as_ref(), &pending_dir()?)?;
+    let last = scheduled_snapshots(store.as_ref())?.into_iter().map(|(_, taken_at)| taken_at).max();
+    if !is_overdue(settings.frequency, last, Local::now().naive_local()) {
+        return Ok(());
+    }
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    Job::run(JobKind::Backup, "Backing up to the cloud", |job| {
+        upload_snapshot(&db, store.as_ref(), &passphrase, SCHEDULED_REASON, job)
+    })?;
+    prune(store.as_ref(), &settings)
+}
+
+/// Starts the background thread that uploads scheduled snapshots. Like the
+/// local scheduler, it waits for the database key to be unlocked.
+pub fn start_scheduler() {
+    thread::spawn(|| {
+        thread::sleep(SCHEDULER_START_DELAY);
+        loop {
+            if KeychainManager::has_cached_key() {
+                match run_scheduled_upload() {
+                    Ok(()) => {}
+                    Err(e) if e == CANCELLED => debug!("Cloud backup cancelled"),
+                    Err(e) => error!("Cloud backup failed: {}", e),
+                }
+            }
+            thread::sleep(SCHEDULER_INTERVAL);
+        }
+    });
+}
+
+/// Sets up backups to `remote` and returns the snapshots already there, so a
+/// new machine can restore one straight away. If there are any, `passphrase`
+/// must open them. The first upload is left to the scheduler.
+fn configure(remote: Remote, credential: String, passphrase: String) -> Result<Vec<CloudBackupInfo>, String> {
+    if passphrase.chars().count() < MIN_PASSPHRASE_CHARS {
+        return Err(format!("Passphrase must be at least {} characters", MIN_PASSPHRASE_CHARS));
+    }
+    let store = remote.open(Some(credential.clone()))?;
+    // Listing also checks the server can be reached with these credentials
+    let existing = snapshots_in(store.as_ref())?;
+    if let Some((name, _, _)) = existing.first() {
+        crypto::open(&passphrase, &Sealed::from_bytes(&store.get(name)?)?)
+            .map_err(|_| "Passphrase doesn't match the one the backups there were made with".to_string())?;
+    }
+    info!("Enabling cloud backup to {}", remote.id());
+    KeychainManager::store_secret(PASSPHRASE_ACCOUNT, &passphrase).map_err(|e| e.to_string())?;
+    KeychainManager::store_secret(CREDENTIAL_ACCOUNT, &credential).map_err(|e| e.to_string())?;
+    let mut settings = Settings::load();
+    settings.cloud_backup.enabled = true;
+    settings.cloud_backup.remote = Some(remote);
+    settings.save().map_err(|e| e.to_string())?;
+    Ok(existing
+        .into_iter()
+        .map(|(name, taken_at, reason)| backup_info(name, taken_at, reason))
+        .collect())
+}
+
+// These talk to the server, so they run off the main thread.
+
+/// Turns on encrypted backups to an S3-compatible bucket and returns the
+/// backups already in it. `region` defaults to `us-east-1` and `prefix` to
+/// the top of the bucket.
+#[command]
+pub async fn configure_cloud_backup(
+    endpoint: String,
+    bucket: String,
+    credentials: CloudCredentials,
+    passphrase: String,
+    region: Option<String>,
+    prefix: Option<String>,
+) -> Result<Vec<CloudBackupInfo>, String> {
+    let remote = Remote::S3 {
+        endpoint,
+        region: region.filter(|region| !region.is_empty()).unwrap_or_else(|| DEFAULT_REGION.to_string()),
+        bucket,
+        prefix: prefix.unwrap_or_default(),
+        access_key_id: credentials.access_key_id,
+    };
+    tauri::async_runtime::spawn_blocking(move || configure(remote, credentials.secret_access_key, passphrase))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+/// Turns on encrypted backups to folder `url` on a WebDAV server, such as
+/// `https://cloud.example.com/remote.php/dav/files/<user>/Journal` on
+/// Nextcloud, and returns the backups already in it.
+#[command]
+pub async fn configure_webdav_backup(
+    url: String,
+    username: String,
+    password: String,
+    passphrase: String,
+) -> Result<Vec<CloudBackupInfo>, String> {
+    let remote = Remote::WebDav { url, username };
+    tauri::async_runtime::spawn_blocking(move || configure(remote, password, passphrase))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+/// Stops cloud backups. Snapshots already uploaded stay where they are, and
+/// ones still waiting to go up are dropped.
+#[command]
+pub fn disable_cloud_backup() -> Result<(), String> {
+    let mut settings = Settings::load();
+    settings.cloud_backup.enabled = false;
+    settings.save().map_err(|e| e.to_string())?;
+    let pending = pending_dir()?;
+    if pending.exists() {
+        fs::remove_dir_all(&pending).map_err(|e| format!("Failed to remove {}: {}", pending.display(), e))?;
+    }
+    KeychainManager::delete_secret(PASSPHRASE_ACCOUNT).map_err(|e| e.to_string())?;
+    KeychainManager::delete_secret(CREDENTIAL_ACCOUNT).map_err(|e| e.to_string())
+}
+
+/// Backups in the configured bucket or folder, newest first.
+#[command]
+pub async fn list_cloud_backups() -> Result<Vec<CloudBackupInfo>, String> {
+    tauri::async_runtime::spawn_blocking(|| {
+        let (store, _) = open_configured(&Settings::load().cloud_backup)?;
+        list_in(store.as_ref())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Replaces the live database with cloud backup `id`, or the newest
+/// one, snapshotting the current database locally first.
+#[command]
+pub async fn restore_from_cloud(id: Option<String>) -> Result<(), String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let (store, passphrase) = open_configured(&Settings::load().cloud_backup)?;
+        let name = match id {
+            Some(id) if parse_sealed_name(&id).is_none() || id.contains(['/', '\\']) => {
+                return Err(format!("Invalid cloud backup id '{}'", id));
+            }
+            Some(id) => id,
+            None => snapshots_in(store.as_ref())?
+                .into_iter()
+                .next()
+                .map(|(name, _, _)| name)
+                .ok_or("There are no cloud backups to restore")?,
+        };
+        Job::run(JobKind::Backup, format!("Restoring {}", name), |job| {
+            restore_snapshot(store.as_ref(), &passphrase, &name, job)
+        })?;
+        security::audit(AuditAction::Import, &format!("restored cloud backup {}", name));
+        Ok(())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_sealed_name() {
+        let (taken_at, reason) = parse_sealed_name("journal-20240105-093000-scheduled.db.sealed").unwrap();
+        assert_eq!(taken_at.to_string(), "2024-01-05 09:30:00");
+        assert_eq!(reason, "scheduled");
+        assert!(parse_sealed_name("journal-20240105-093000-scheduled.db").is_none());
+        assert!(parse_sealed_name("notes.sealed").is_none());
+    }
+
+    #[test]
+    fn test_prune() {
+        let dir = std::env::temp_dir().join(format!("journal-cloud-backup-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let names = [
+            "journal-20240105-093000-scheduled.db.sealed",
+            "journal-20240105-193000-scheduled.db.sealed",
+            "journal-20240104-093000-scheduled.db.sealed",
+            "journal-20240101-093000-manual.db.sealed",
+            "notes.txt",
+        ];
+        for name in names {
+            fs::write(dir.join(name), "sealed").unwrap();
+        }
+        let store = Remote::Folder { path: dir.to_string_lossy().to_string() }.open(None).unwrap();
+        let settings = CloudBackupSettings {
+            keep_daily: 1,
+            keep_weekly: 0,
+            ..Default::default()
+        };
+        prune(store.as_ref(), &settings).unwrap();
+        let ids: Vec<String> = list_in(store.as_ref()).unwrap().into_iter().map(|info| info.id).collect();
+        assert_eq!(ids, [names[1], names[3]]);
+        assert!(dir.join("notes.txt").exists());
+
+        let pending = dir.join(PENDING_DIR_NAME);
+        fs::create_dir_all(&pending).unwrap();
+        fs::write(pending.join(names[2]), "sealed").unwrap();
+        resume_uploads(store.as_ref(), &pending).unwrap();
+        assert!(dir.join(names[2]).exists());
+        assert!(!pending.join(names[2]).exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
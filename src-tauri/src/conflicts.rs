@@ -0,0 +1,411 @@
+use log::{debug, info};
+use rusqlite::OptionalExtension;
+use serde::{Deserialize, Serialize};
+use tauri::command;
+
+use journal_core::links::update_links;
+use journal_core::people::update_people;
+use journal_core::sentiment::body_sentiment;
+use journal_core::text::body_word_count;
+use journal_core::timestamp_now;
+
+use crate::diff::{diff3, merge, DiffChunk};
+use crate::git_sync;
+use crate::markdown_mirror;
+use crate::recent_menu;
+use crate::settings::{LimitSettings, Settings};
+use crate::sync::EntryRecord;
+use crate::validation::clean_entry;
+use crate::DatabaseManager;
+
+// Sync settles every entry on one version by Lamport order, which is right
+// for convergence but means a concurrent edit on another device can quietly
+// replace (or be replaced by) the local one. When both sides changed an entry
+// since the last version they shared, the losing side is kept here so the
+// user can review a three-way diff and pick, or merge by hand.
+//
+// `sync_bases` holds that last shared version per entry: whatever was last
+// sent to or merged from another device. Both sides having moved on from it
+// is what makes two versions a conflict rather than an update.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictSide {
+    Local,
+    Remote,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ConflictChoice {
+    Local,
+    Remote,
+    /// Text the user merged by hand, e.g. starting from `Conflict::merged`.
+    Merged { title: String, body: String },
+}
+
+#[derive(Debug, Serialize)]
+pub struct Conflict {
+    id: i32,
+    entry_id: i32,
+    local_title: String,
+    remote_title: String,
+    /// Device that made the remote version.
+    remote_origin: String,
+    /// The version sync kept; the other one only survives in this conflict.
+    kept: ConflictSide,
+    detected_at: String,
+    diff: Vec<DiffChunk>,
+    /// The body with every non-overlapping change applied, when there are no
+    /// overlapping ones.
+    merged: Option<String>,
+}
+
+/// Records `record` as the version this device and another now share.
+pub fn record_shared(conn: &rusqlite::Connection, record: &EntryRecord) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT OR REPLACE INTO sync_bases (uuid, lamport, origin, title, body) VALUES (?1, ?2, ?3, ?4, ?5)",
+        rusqlite::params![record.uuid, record.lamport, record.origin, record.title, record.body],
+    )?;
+    Ok(())
+}
+
+/// `record_shared` for everything just sent to another device, in one transaction.
+pub fn record_all_shared(conn: &rusqlite::Connection, records: &[EntryRecord]) -> Result<(), String> {
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    for record in records {
+        record_shared(&tx, record).map_err(|e| e.to_string())?;
+    }
+    tx.commit().map_err(|e| e.to_string())
+}
+
+/// Checks an incoming version of entry `id` against the local one before sync
+/// picks a winner, recording a conflict if both changed since their shared
+/// base. `remote_wins` says which one sync is about to keep.
+pub fn detect(
+    conn: &rusqlite::Connection,
+    id: i32,
+    record: &EntryRecord,
+    device_id: &str,
+    remote_wins: bool,
+) -> rusqlite::Result<bool> {
+    let (title, body, lamport, origin): (String, String, i64, String) = conn.query_row(
+        "SELECT title, body, lamport, COALESCE(origin, ?2) FROM journal_entries WHERE id = ?1",
+        rusqlite::params![id, device_id],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+    )?;
+    if title == record.title && body == record.body {
+        return Ok(false);
+    }
+    let base: Option<(i64, String, String, String)> = conn
+        .query_row(
+            "SELECT lamport, COALESCE(origin, ?2), title, body FROM sync_bases WHERE uuid = ?1",
+            rusqlite::params![record.uuid, device_id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+        )
+        .optional()?;
+    // Without a base the entry has never been shared, so it can't have been
+    // edited anywhere else
+    let Some((base_lamport, base_origin, base_title, base_body)) = base else {
+        return Ok(false);
+    };
+    let base_version = (base_lamport, base_origin.as_str());
+    // Local unchanged since the base is a plain update; a remote version no
+    // newer than the base is one this device has already moved past
+    if (lamport, origin.as_str()) == base_version || (record.lamport, record.origin.as_str()) <= base_version {
+        return Ok(false);
+    }
+    info!("Conflicting edits to entry {} from device {}", id, record.origin);
+    conn.execute(
+        "INSERT OR REPLACE INTO conflicts
+            (entry_id, base_title, base_body, local_title, local_body, remote_title, remote_body,
+             remote_origin, kept, detected_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+        rusqlite::params![
+            id,
+            base_title,
+            base_body,
+            title,
+            body,
+            record.title,
+            record.body,
+            record.origin,
+            if remote_wins { "remote" } else { "local" },
+            timestamp_now(),
+        ],
+    )?;
+    Ok(true)
+}
+
+#[command]
+pub fn list_conflicts() -> Result<Vec<Conflict>, String> {
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    let mut stmt = db
+        .conn
+        .prepare(
+            "SELECT id, entry_id, base_body, local_title, local_body, remote_title, remote_body,
+                    remote_origin, kept, detected_at
+             FROM conflicts ORDER BY detected_at DESC",
+        )
+        .map_err(|e| e.to_string())?;
+    let conflicts = stmt
+        .query_map([], |row| {
+            let (base, local, remote): (String, String, String) = (row.get(2)?, row.get(4)?, row.get(6)?);
+            let diff = diff3(&base, &local, &remote);
+            Ok(Conflict {
+                id: row.get(0)?,
+                entry_id: row.get(1)?,
+                local_title: row.get(3)?,
+                remote_title: row.get(5)?,
+                remote_origin: row.get(7)?,
+                kept: match row.get::<_, String>(8)?.as_str() {
+                    "remote" => ConflictSide::Remote,
+                    _ => ConflictSide::Local,
+                },
+                detected_at: row.get(9)?,
+                merged: merge(&diff),
+                diff,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(conflicts)
+}
+
+/// Saves the text `choice` picks for conflict `id` as a new local edit and
+/// drops the conflict. Hand-merged text is refused if it's over `limits`.
+fn resolve(
+    conn: &rusqlite::Connection,
+    id: i32,
+    choice: ConflictChoice,
+    limits: &LimitSettings,
+) -> Result<(), String> {
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    let (entry_id, local_title, local_body, remote_title, remote_body): (i32, String, String, String, String) = tx
+        .query_row(
+            "SELECT entry_id, local_title, local_body, remote_title, remote_body FROM conflicts WHERE id = ?1",
+            rusqlite::params![id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?)),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Conflict {} not found", id))?;
+    let (title, body) = match choice {
+        ConflictChoice::Local => (local_title, local_body),
+        ConflictChoice::Remote => (remote_title, remote_body),
+        // Checked like any save; the stored versions already were
+        ConflictChoice::Merged { title, body } => clean_entry(&title, &body, limits)?,
+    };
+    let (current_title, current_body, locked): (String, String, bool) = tx
+        .query_row(
+            "SELECT title, body, locked FROM journal_entries WHERE id = ?1",
+            rusqlite::params![entry_id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .map_err(|e| e.to_string())?;
+    if locked {
+        return Err(format!("Entry {} is locked; unlock it before resolving", entry_id));
+    }
+    debug!("Resolving conflict {} on entry {}", id, entry_id);
+    if title != current_title || body != current_body {
+        tx.execute(
+            "UPDATE journal_entries SET title = ?1, body = ?2, word_count = ?3, sentiment = ?4 WHERE id = ?5",
+            rusqlite::params![title, body, body_word_count(&body), body_sentiment(&body), entry_id],
+        )
+        .map_err(|e| e.to_string())?;
+        update_links(&tx, entry_id, &body).map_err(|e| e.to_string())?;
+        update_people(&tx, entry_id, &body).map_err(|e| e.to_string())?;
+    }
+    tx.execute("DELETE FROM conflicts WHERE id = ?1", rusqlite::params![id])
+        .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())
+}
+
+/// Settles a conflict. The chosen text is saved as a new local edit, so it
+/// supersedes both versions on every device at the next sync.
+#[command]
+pub fn resolve_conflict(id: i32, choice: ConflictChoice) -> Result<(), String> {
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    resolve(&db.conn, id, choice, &Settings::load().limits)?;
+    git_sync::commit_on_save();
+    markdown_mirror::refresh();
+    recent_menu::refresh();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use journal_core::insert_entry;
+    use journal_core::validation::TOO_LARGE;
+
+    fn record(uuid: &str, lamport: i64, origin: &str, body: &str) -> EntryRecord {
+        EntryRecord {
+            uuid: uuid.to_string(),
+            lamport,
+            origin: origin.to_string(),
+            title: "Monday".to_string(),
+            body: body.to_string(),
+            created_at: "2024-03-05T12:00:00+00:00".to_string(),
+            updated_at: "2024-03-05T12:00:00+00:00".to_string(),
+            mood_score: None,
+            mood_emoji: None,
+            tags: Vec::new(),
+            local_date: None,
+        }
+    }
+
+    /// A local entry and the record of it as last shared with another device.
+    fn shared_entry(conn: &rusqlite::Connection) -> (i32, EntryRecord) {
+        let id = insert_entry(conn, "Monday", "<p>Base</p>", "2024-03-05T12:00:00+00:00").unwrap();
+        let (uuid, lamport): (String, i64) = conn
+            .query_row("SELECT uuid, lamport FROM journal_entries WHERE id = ?1", [id], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .unwrap();
+        let base = record(&uuid, lamport, "local", "<p>Base</p>");
+        record_shared(conn, &base).unwrap();
+        (id, base)
+    }
+
+    fn edit(conn: &rusqlite::Connection, id: i32, body: &str) {
+        conn.execute("UPDATE journal_entries SET body = ?1 WHERE id = ?2", rusqlite::params![body, id])
+            .unwrap();
+    }
+
+    fn body(conn: &rusqlite::Connection, id: i32) -> String {
+        conn.query_row("SELECT body FROM journal_entries WHERE id = ?1", [id], |row| row.get(0))
+            .unwrap()
+    }
+
+    fn conflict_ids(conn: &rusqlite::Connection) -> Vec<i32> {
+        let mut stmt = conn.prepare("SELECT id FROM conflicts ORDER BY id").unwrap();
+        let ids = stmt.query_map([], |row| row.get(0)).unwrap();
+        ids.collect::<rusqlite::Result<_>>().unwrap()
+    }
+
+    /// Entry `id` edited locally and remotely since its base, with the conflict recorded.
+    fn conflicted_entry(conn: &rusqlite::Connection) -> (i32, i32) {
+        let (id, base) = shared_entry(conn);
+        edit(conn, id, "<p>Local</p>");
+        let remote = record(&base.uuid, base.lamport + 10, "remote", "<p>Remote</p>");
+        assert!(detect(conn, id, &remote, "local", true).unwrap());
+        (id, conflict_ids(conn)[0])
+    }
+
+    #[test]
+    fn test_detect_without_base() {
+        let db = DatabaseManager::open_in_memory().unwrap();
+        let id = insert_entry(&db.conn, "Monday", "<p>Local</p>", "2024-03-05T12:00:00+00:00").unwrap();
+        let uuid: String = db
+            .conn
+            .query_row("SELECT uuid FROM journal_entries WHERE id = ?1", [id], |row| row.get(0))
+            .unwrap();
+        let remote = record(&uuid, 10, "remote", "<p>Remote</p>");
+        assert!(!detect(&db.conn, id, &remote, "local", true).unwrap());
+        assert!(conflict_ids(&db.conn).is_empty());
+    }
+
+    #[test]
+    fn test_detect_local_unchanged() {
+        let db = DatabaseManager::open_in_memory().unwrap();
+        let (id, base) = shared_entry(&db.conn);
+        let remote = record(&base.uuid, base.lamport + 10, "remote", "<p>Remote</p>");
+        assert!(!detect(&db.conn, id, &remote, "local", true).unwrap());
+        assert!(conflict_ids(&db.conn).is_empty());
+    }
+
+    #[test]
+    fn test_detect_remote_not_newer_than_base() {
+        let db = DatabaseManager::open_in_memory().unwrap();
+        let (id, base) = shared_entry(&db.conn);
+        edit(&db.conn, id, "<p>Local</p>");
+        for lamport in [base.lamport, base.lamport - 1] {
+            let remote = record(&base.uuid, lamport, "local", "<p>Older</p>");
+            assert!(!detect(&db.conn, id, &remote, "local", false).unwrap());
+        }
+        assert!(conflict_ids(&db.conn).is_empty());
+    }
+
+    #[test]
+    fn test_detect_conflict() {
+        let db = DatabaseManager::open_in_memory().unwrap();
+        let (id, _) = conflicted_entry(&db.conn);
+        let (base, local, remote, kept): (String, String, String, String) = db
+            .conn
+            .query_row(
+                "SELECT base_body, local_body, remote_body, kept FROM conflicts WHERE entry_id = ?1",
+                [id],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+            )
+            .unwrap();
+        assert_eq!(
+            (base.as_str(), local.as_str(), remote.as_str(), kept.as_str()),
+            ("<p>Base</p>", "<p>Local</p>", "<p>Remote</p>", "remote")
+        );
+    }
+
+    #[test]
+    fn test_resolve_each_choice() {
+        let limits = LimitSettings::default();
+        let choices = [
+            (ConflictChoice::Local, "<p>Local</p>"),
+            (ConflictChoice::Remote, "<p>Remote</p>"),
+            (
+                ConflictChoice::Merged { title: "Monday".to_string(), body: "<p>Both\u{7}</p>".to_string() },
+                "<p>Both</p>",
+            ),
+        ];
+        for (choice, expected) in choices {
+            let db = DatabaseManager::open_in_memory().unwrap();
+            let (id, conflict) = conflicted_entry(&db.conn);
+            resolve(&db.conn, conflict, choice, &limits).unwrap();
+            assert_eq!(body(&db.conn, id), expected);
+            assert!(conflict_ids(&db.conn).is_empty());
+        }
+    }
+
+    #[test]
+    fn test_resolve_refuses_oversized_merge() {
+        let db = DatabaseManager::open_in_memory().unwrap();
+        let (id, conflict) = conflicted_entry(&db.conn);
+        let limits = LimitSettings { max_body_mb: 1, ..Default::default() };
+        let choice = ConflictChoice::Merged { title: "Monday".to_string(), body: "a".repeat(1024 * 1024 + 1) };
+        assert!(resolve(&db.conn, conflict, choice, &limits).unwrap_err().starts_with(TOO_LARGE));
+        assert_eq!(body(&db.conn, id), "<p>Local</p>");
+        assert_eq!(conflict_ids(&db.conn), vec![conflict]);
+    }
+
+    #[test]
+    fn test_resolve_refuses_locked_entry() {
+        let db = DatabaseManager::open_in_memory().unwrap();
+        let (id, conflict) = conflicted_entry(&db.conn);
+        db.conn.execute("UPDATE journal_entries SET locked = 1 WHERE id = ?1", [id]).unwrap();
+        assert!(resolve(&db.conn, conflict, ConflictChoice::Remote, &LimitSettings::default()).is_err());
+        assert_eq!(body(&db.conn, id), "<p>Local</p>");
+        assert_eq!(conflict_ids(&db.conn), vec![conflict]);
+    }
+
+    #[test]
+    fn test_record_all_shared() {
+        let db = DatabaseManager::open_in_memory().unwrap();
+        let records = [record("u1", 3, "a", "<p>One</p>"), record("u2", 4, "b", "<p>Two</p>")];
+        record_all_shared(&db.conn, &records).unwrap();
+        // A later share replaces the base
+        record_all_shared(&db.conn, &[record("u1", 5, "b", "<p>One again</p>")]).unwrap();
+        let mut stmt = db.conn.prepare("SELECT uuid, lamport, origin, body FROM sync_bases ORDER BY uuid").unwrap();
+        let bases: Vec<(String, i64, String, String)> = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        assert_eq!(
+            bases,
+            vec![
+                ("u1".to_string(), 5, "b".to_string(), "<p>One again</p>".to_string()),
+                ("u2".to_string(), 4, "b".to_string(), "<p>Two</p>".to_string()),
+            ]
+        );
+    }
+}
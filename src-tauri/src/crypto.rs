@@ -0,0 +1,90 @@
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Nonce};
+use argon2::Argon2;
+
+// Passphrase-based encryption shared by locked entries and sync bundles:
+// an Argon2id key derived from the passphrase and a random salt, then AES-256-GCM.
+
+/// What `open` fails with when the passphrase is wrong.
+pub const INCORRECT_PASSPHRASE: &str = "Incorrect passphrase";
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+
+pub struct Sealed {
+    pub salt: Vec<u8>,
+    pub nonce: Vec<u8>,
+    pub ciphertext: Vec<u8>,
+}
+
+impl Sealed {
+    /// Single-blob form: `salt || nonce || ciphertext`.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        [self.salt.as_slice(), self.nonce.as_slice(), self.ciphertext.as_slice()].concat()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        if bytes.len() < SALT_LEN + NONCE_LEN {
+            return Err("Encrypted data is truncated".to_string());
+        }
+        let (salt, rest) = bytes.split_at(SALT_LEN);
+        let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+        Ok(Sealed {
+            salt: salt.to_vec(),
+            nonce: nonce.to_vec(),
+            ciphertext: ciphertext.to_vec(),
+        })
+    }
+}
+
+/// Stretches a passphrase into a 256-bit key.
+pub fn derive_key(passphrase: &str, salt: &[u8]) -> Result<[u8; 32], String> {
+    let mut key = [0u8; 32];
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| format!("Failed to derive key from passphrase: {}", e))?;
+    Ok(key)
+}
+
+pub fn seal(passphrase: &str, plaintext: &[u8]) -> Result<Sealed, String> {
+    let mut salt = vec![0u8; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    let key = derive_key(passphrase, &salt)?;
+    let cipher = Aes256Gcm::new_from_slice(&key).map_err(|e| e.to_string())?;
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, plaintext)
+        .map_err(|_| "Failed to encrypt data".to_string())?;
+    Ok(Sealed { salt, nonce: nonce.to_vec(), ciphertext })
+}
+
+pub fn open(passphrase: &str, sealed: &Sealed) -> Result<Vec<u8>, String> {
+    if sealed.nonce.len() != NONCE_LEN {
+        return Err("Encrypted data is corrupt".to_string());
+    }
+    let key = derive_key(passphrase, &sealed.salt)?;
+    let cipher = Aes256Gcm::new_from_slice(&key).map_err(|e| e.to_string())?;
+    // AES-GCM authentication fails for a wrong key, which is how we detect a bad passphrase
+    cipher
+        .decrypt(Nonce::from_slice(&sealed.nonce), sealed.ciphertext.as_ref())
+        .map_err(|_| INCORRECT_PASSPHRASE.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seal_round_trip_through_bytes() {
+        let sealed = seal("correct horse", b"secret").unwrap();
+        let restored = Sealed::from_bytes(&sealed.to_bytes()).unwrap();
+        assert_eq!(open("correct horse", &restored).unwrap(), b"secret");
+    }
+
+    #[test]
+    fn test_wrong_passphrase_rejected() {
+        let sealed = seal("correct horse", b"secret").unwrap();
+        assert_eq!(open("battery staple", &sealed).unwrap_err(), "Incorrect passphrase");
+        assert!(Sealed::from_bytes(&[0u8; 4]).is_err());
+    }
+}
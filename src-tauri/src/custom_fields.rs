@@ -0,0 +1,255 @@
+use log::debug;
+use rusqlite::OptionalExtension;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tauri::command;
+
+use journal_core::timestamp_now;
+
+use crate::DatabaseManager;
+
+// Fields the user defines to track alongside their writing (sleep hours,
+// medication taken, a workout). Each field has a type that every value,
+// including its default, is checked against. Values are stored as JSON text.
+
+const MAX_FIELD_NAME_CHARS: usize = 50;
+const MAX_TEXT_VALUE_CHARS: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FieldType {
+    Number,
+    Boolean,
+    Text,
+}
+
+impl FieldType {
+    fn as_str(self) -> &'static str {
+        match self {
+            FieldType::Number => "number",
+            FieldType::Boolean => "boolean",
+            FieldType::Text => "text",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "number" => Some(FieldType::Number),
+            "boolean" => Some(FieldType::Boolean),
+            "text" => Some(FieldType::Text),
+            _ => None,
+        }
+    }
+
+    fn check(self, value: &Value) -> Result<(), String> {
+        let valid = match (self, value) {
+            (FieldType::Number, Value::Number(_)) | (FieldType::Boolean, Value::Bool(_)) => true,
+            (FieldType::Text, Value::String(text)) => text.chars().count() <= MAX_TEXT_VALUE_CHARS,
+            _ => false,
+        };
+        if valid {
+            Ok(())
+        } else {
+            Err(format!("{} is not a valid {} value", value, self.as_str()))
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct CustomField {
+    id: i32,
+    name: String,
+    field_type: FieldType,
+    default: Option<Value>,
+}
+
+/// A field's value on one entry: what was set, or the field's default.
+#[derive(Debug, Serialize)]
+pub struct CustomFieldValue {
+    field_id: i32,
+    name: String,
+    field_type: FieldType,
+    value: Option<Value>,
+    /// Whether `value` was set on this entry rather than taken from the default.
+    is_set: bool,
+}
+
+fn parse_json(json: Option<String>) -> Option<Value> {
+    json.and_then(|json| serde_json::from_str(&json).ok())
+}
+
+fn field_type(row: &rusqlite::Row, index: usize) -> rusqlite::Result<FieldType> {
+    let value: String = row.get(index)?;
+    FieldType::parse(&value).ok_or_else(|| {
+        rusqlite::Error::FromSqlConversionFailure(
+            index,
+            rusqlite::types::Type::Text,
+            format!("Unknown field type '{}'", value).into(),
+        )
+    })
+}
+
+fn define(conn: &rusqlite::Connection, name: &str, field_type: FieldType, default: Option<Value>) -> Result<i32, String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("Field name cannot be empty".to_string());
+    }
+    if name.chars().count() > MAX_FIELD_NAME_CHARS {
+        return Err(format!("Field names must be at most {} characters", MAX_FIELD_NAME_CHARS));
+    }
+    let default = default.filter(|value| !value.is_null());
+    if let Some(default) = &default {
+        field_type.check(default)?;
+    }
+    conn.execute(
+        "INSERT INTO custom_fields (name, field_type, default_value, created_at) VALUES (?1, ?2, ?3, ?4)",
+        rusqlite::params![name, field_type.as_str(), default.map(|d| d.to_string()), timestamp_now()],
+    )
+    .map_err(|e| match e {
+        rusqlite::Error::SqliteFailure(error, _) if error.code == rusqlite::ErrorCode::ConstraintViolation => {
+            format!("A field named '{}' already exists", name)
+        }
+        e => e.to_string(),
+    })?;
+    Ok(conn.last_insert_rowid() as i32)
+}
+
+fn set_value(conn: &rusqlite::Connection, entry_id: i32, field_id: i32, value: Value) -> Result<(), String> {
+    let field_type = conn
+        .query_row(
+            "SELECT field_type FROM custom_fields WHERE id = ?1",
+            rusqlite::params![field_id],
+            |row| field_type(row, 0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Field {} not found", field_id))?;
+    if value.is_null() {
+        conn.execute(
+            "DELETE FROM custom_field_values WHERE entry_id = ?1 AND field_id = ?2",
+            rusqlite::params![entry_id, field_id],
+        )
+        .map_err(|e| e.to_string())?;
+        return Ok(());
+    }
+    field_type.check(&value)?;
+    conn.execute(
+        "INSERT INTO custom_field_values (entry_id, field_id, value) VALUES (?1, ?2, ?3)
+         ON CONFLICT (entry_id, field_id) DO UPDATE SET value = excluded.value",
+        rusqlite::params![entry_id, field_id, value.to_string()],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+fn entry_values(conn: &rusqlite::Connection, entry_id: i32) -> rusqlite::Result<Vec<CustomFieldValue>> {
+    let mut stmt = conn.prepare(
+        "SELECT f.id, f.name, f.field_type, v.value, f.default_value FROM custom_fields f
+         LEFT JOIN custom_field_values v ON v.field_id = f.id AND v.entry_id = ?1
+         ORDER BY f.name COLLATE NOCASE",
+    )?;
+    let values = stmt
+        .query_map(rusqlite::params![entry_id], |row| {
+            let value = parse_json(row.get(3)?);
+            Ok(CustomFieldValue {
+                field_id: row.get(0)?,
+                name: row.get(1)?,
+                field_type: field_type(row, 2)?,
+                is_set: value.is_some(),
+                value: value.or(parse_json(row.get(4)?)),
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(values)
+}
+
+#[command]
+pub fn get_custom_fields() -> Result<Vec<CustomField>, String> {
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    let mut stmt = db
+        .conn
+        .prepare("SELECT id, name, field_type, default_value FROM custom_fields ORDER BY name COLLATE NOCASE")
+        .map_err(|e| e.to_string())?;
+    let fields = stmt
+        .query_map([], |row| {
+            Ok(CustomField {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                field_type: field_type(row, 2)?,
+                default: parse_json(row.get(3)?),
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(fields)
+}
+
+#[command]
+pub fn define_custom_field(name: String, field_type: FieldType, default: Option<Value>) -> Result<i32, String> {
+    debug!("Defining custom field '{}'", name);
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    define(&db.conn, &name, field_type, default)
+}
+
+/// Deletes a field along with its value on every entry.
+#[command]
+pub fn delete_custom_field(id: i32) -> Result<(), String> {
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    db.conn
+        .execute("DELETE FROM custom_fields WHERE id = ?1", rusqlite::params![id])
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Sets a field's value on an entry; `value: null` clears it back to the default.
+#[command]
+pub fn set_custom_field_value(entry_id: i32, field_id: i32, value: Value) -> Result<(), String> {
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    set_value(&db.conn, entry_id, field_id, value)
+}
+
+/// Every defined field with its value on this entry.
+#[command]
+pub fn get_entry_custom_fields(entry_id: i32) -> Result<Vec<CustomFieldValue>, String> {
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    entry_values(&db.conn, entry_id).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use journal_core::insert_entry;
+    use serde_json::json;
+
+    #[test]
+    fn test_field_type_check() {
+        assert!(FieldType::Number.check(&json!(7.5)).is_ok());
+        assert!(FieldType::Number.check(&json!("7.5")).is_err());
+        assert!(FieldType::Boolean.check(&json!(true)).is_ok());
+        assert!(FieldType::Text.check(&json!("ran 5k")).is_ok());
+        assert!(FieldType::Text.check(&json!("x".repeat(MAX_TEXT_VALUE_CHARS + 1))).is_err());
+        for field_type in [FieldType::Number, FieldType::Boolean, FieldType::Text] {
+            assert_eq!(FieldType::parse(field_type.as_str()), Some(field_type));
+        }
+    }
+
+    #[test]
+    fn test_values_fall_back_to_default() {
+        let db = DatabaseManager::open_in_memory().unwrap();
+        let entry_id = insert_entry(&db.conn, "Tuesday", "", &timestamp_now()).unwrap();
+        let sleep = define(&db.conn, "Sleep hours", FieldType::Number, Some(json!(8))).unwrap();
+        let meds = define(&db.conn, "Took meds", FieldType::Boolean, None).unwrap();
+        assert!(define(&db.conn, "sleep HOURS", FieldType::Text, None).is_err());
+        assert!(define(&db.conn, "Mood note", FieldType::Number, Some(json!("good"))).is_err());
+
+        set_value(&db.conn, entry_id, meds, json!(true)).unwrap();
+        assert!(set_value(&db.conn, entry_id, sleep, json!("lots")).is_err());
+        let values = entry_values(&db.conn, entry_id).unwrap();
+        assert_eq!((values[0].name.as_str(), &values[0].value, values[0].is_set), ("Sleep hours", &Some(json!(8)), false));
+        assert_eq!((values[1].name.as_str(), &values[1].value, values[1].is_set), ("Took meds", &Some(json!(true)), true));
+
+        set_value(&db.conn, entry_id, meds, Value::Null).unwrap();
+        assert_eq!(entry_values(&db.conn, entry_id).unwrap()[1].value, None);
+    }
+}
@@ -0,0 +1,304 @@
+use log::{debug, warn};
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter, Url};
+use tauri_plugin_deep_link::DeepLinkExt;
+use tauri_plugin_opener::OpenerExt;
+
+use journal_core::keychain::KeychainManager;
+use journal_core::text::text_to_html;
+
+use crate::api_server::append_to_today;
+use crate::stats::get_writing_streak;
+use crate::tags::{normalize_tags, replace_tags};
+use crate::tray::show_main;
+use crate::{create_entry, CreateEntryRequest, DatabaseManager};
+
+// `journal://` links, so Shortcuts, launchers and browser extensions can
+// start or open entries:
+//
+//   journal://new?text=...&title=...   creates an entry and opens it
+//   journal://entry/{id}               opens an entry
+//
+// and x-callback-url actions, which run without bringing the window up and
+// report back by opening the caller's `x-success` or `x-error` URL:
+//
+//   journal://x-callback-url/append?text=...
+//   journal://x-callback-url/create?title=...&body=...&tags=a,b
+//   journal://x-callback-url/streak
+//
+// macOS hands links to the running app; on Windows and Linux they start a
+// second instance, which the single-instance plugin forwards here. Links
+// that arrive while the journal is locked wait until it's unlocked.
+
+pub const SCHEME: &str = "journal";
+/// Schemes a caller can't ask to be called back on.
+const REFUSED_CALLBACK_SCHEMES: &[&str] = &[SCHEME, "file", "javascript", "data"];
+
+static PENDING: Mutex<Vec<Url>> = Mutex::new(Vec::new());
+
+#[derive(Debug, PartialEq)]
+pub enum Action {
+    Append { text: String },
+    Create { title: String, body: String, tags: Vec<String> },
+    Streak,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum DeepLink {
+    New { title: String, text: String },
+    Entry(i32),
+    Callback {
+        action: Action,
+        success: Option<Url>,
+        error: Option<Url>,
+    },
+}
+
+fn callback_url(value: Option<String>) -> Result<Option<Url>, String> {
+    let Some(value) = value.filter(|v| !v.is_empty()) else {
+        return Ok(None);
+    };
+    let url = Url::parse(&value).map_err(|e| format!("Invalid callback '{}': {}", value, e))?;
+    if REFUSED_CALLBACK_SCHEMES.contains(&url.scheme()) {
+        return Err(format!("Refusing to call back {}:// URLs", url.scheme()));
+    }
+    Ok(Some(url))
+}
+
+impl DeepLink {
+    pub fn parse(url: &Url) -> Result<Self, String> {
+        if url.scheme() != SCHEME {
+            return Err(format!("Not a {}:// link: {}", SCHEME, url));
+        }
+        let optional = |name: &str| {
+            url.query_pairs()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.into_owned())
+        };
+        let param = |name: &str| optional(name).unwrap_or_default();
+        let segments: Vec<&str> = url
+            .path_segments()
+            .map(|segments| segments.filter(|s| !s.is_empty()).collect())
+            .unwrap_or_default();
+        match (url.host_str(), segments.as_slice()) {
+            (Some("new"), []) => Ok(DeepLink::New {
+                title: param("title"),
+                text: param("text"),
+            }),
+            (Some("entry"), [id]) => id
+                .parse()
+                .map(DeepLink::Entry)
+                .map_err(|_| format!("Invalid entry id '{}'", id)),
+            (Some("x-callback-url"), [name]) => {
+                let action = match *name {
+                    "append" => Action::Append { text: param("text") },
+                    "create" => Action::Create {
+                        title: param("title"),
+                        body: param("body"),
+                        tags: normalize_tags(param("tags").split(',').map(str::to_string).collect())?,
+                    },
+                    "streak" => Action::Streak,
+                    _ => return Err(format!("Unsupported action '{}'", name)),
+                };
+                Ok(DeepLink::Callback {
+                    action,
+                    success: callback_url(optional("x-success"))?,
+                    error: callback_url(optional("x-error"))?,
+                })
+            }
+            _ => Err(format!("Unsupported link {}", url)),
+        }
+    }
+}
+
+fn open(app: &AppHandle, link: DeepLink) -> Result<(), String> {
+    let id = match link {
+        DeepLink::New { title, text } => create_entry(CreateEntryRequest {
+            title,
+            body: text_to_html(&text),
+            created_at: None,
+        })?,
+        DeepLink::Entry(id) => id,
+        DeepLink::Callback { action, success, error } => {
+            let (callback, params) = match run(app, action) {
+                Ok(params) => (success, params),
+                Err(e) => (error, vec![("errorMessage", e)]),
+            };
+            if let Some(callback) = callback {
+                app.opener()
+                    .open_url(with_params(callback, params).as_str(), None::<&str>)
+                    .map_err(|e| e.to_string())?;
+            }
+            return Ok(());
+        }
+    };
+    app.emit("open-entry", id).map_err(|e| e.to_string())
+}
+
+/// Runs an x-callback-url action, returning the parameters to call back with.
+fn run(app: &AppHandle, action: Action) -> Result<Vec<(&'static str, String)>, String> {
+    match action {
+        Action::Append { text } => {
+            let id = append_to_today(&text)?;
+            let _ = app.emit("entries-changed", ());
+            Ok(vec![("id", id.to_string())])
+        }
+        Action::Create { title, body, tags } => {
+            let id = create_entry(CreateEntryRequest {
+                title,
+                body: text_to_html(&body),
+                created_at: None,
+            })?;
+            if !tags.is_empty() {
+                let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+                replace_tags(&db.conn, id, &tags).map_err(|e| e.to_string())?;
+            }
+            let _ = app.emit("entries-changed", ());
+            Ok(vec![("id", id.to_string())])
+        }
+        Action::Streak => {
+            let streak = get_writing_streak()?;
+            Ok(vec![
+                ("currentDays", streak.current_days.to_string()),
+                ("longestDays", streak.longest_days.to_string()),
+                ("wroteToday", streak.wrote_today.to_string()),
+            ])
+        }
+    }
+}
+
+fn with_params(mut url: Url, params: Vec<(&'static str, String)>) -> Url {
+    url.query_pairs_mut().extend_pairs(params);
+    url
+}
+
+fn handle_url(app: &AppHandle, url: Url) {
+    debug!("Opening link {}", url);
+    let link = match DeepLink::parse(&url) {
+        Ok(link) => link,
+        Err(e) => {
+            warn!("Could not open {}: {}", url, e);
+            return;
+        }
+    };
+    if !KeychainManager::has_cached_key() {
+        // The window is where the journal gets unlocked
+        show_main(app);
+        if let Ok(mut pending) = PENDING.lock() {
+            pending.push(url);
+        }
+        return;
+    }
+    if !matches!(link, DeepLink::Callback { .. }) {
+        show_main(app);
+    }
+    if let Err(e) = open(app, link) {
+        warn!("Could not open {}: {}", url, e);
+    }
+}
+
+/// Opens links that arrived while the journal was locked. Called once the
+/// keychain has been authorized.
+pub fn open_pending(app: &AppHandle) {
+    let pending = PENDING.lock().map(|mut pending| std::mem::take(&mut *pending)).unwrap_or_default();
+    for url in pending {
+        handle_url(app, url);
+    }
+}
+
+/// Starts handling links, including the one the app was launched with.
+pub fn register(app: &AppHandle) {
+    let handle = app.clone();
+    app.deep_link().on_open_url(move |event| {
+        for url in event.urls() {
+            handle_url(&handle, url);
+        }
+    });
+    // Installers register the scheme on release; Linux and Windows dev builds
+    // have to do it at runtime
+    #[cfg(any(target_os = "linux", all(debug_assertions, windows)))]
+    if let Err(e) = app.deep_link().register_all() {
+        warn!("Could not register {}:// links: {}", SCHEME, e);
+    }
+    if let Ok(Some(urls)) = app.deep_link().get_current() {
+        for url in urls {
+            handle_url(app, url);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(url: &str) -> Result<DeepLink, String> {
+        DeepLink::parse(&Url::parse(url).unwrap())
+    }
+
+    #[test]
+    fn test_parse_links() {
+        assert_eq!(
+            parse("journal://new?text=Hello%20there&title=From+Shortcuts"),
+            Ok(DeepLink::New {
+                title: "From Shortcuts".to_string(),
+                text: "Hello there".to_string()
+            })
+        );
+        assert_eq!(
+            parse("journal://new"),
+            Ok(DeepLink::New {
+                title: String::new(),
+                text: String::new()
+            })
+        );
+        assert_eq!(parse("journal://entry/42"), Ok(DeepLink::Entry(42)));
+        assert_eq!(parse("journal://entry/42/"), Ok(DeepLink::Entry(42)));
+    }
+
+    #[test]
+    fn test_parse_callbacks() {
+        assert_eq!(
+            parse(
+                "journal://x-callback-url/create?title=Run&body=5k&tags=%23health,%20running,Health\
+                 &x-success=shortcuts://x-callback-url/done"
+            ),
+            Ok(DeepLink::Callback {
+                action: Action::Create {
+                    title: "Run".to_string(),
+                    body: "5k".to_string(),
+                    tags: vec!["health".to_string(), "running".to_string()],
+                },
+                success: Some(Url::parse("shortcuts://x-callback-url/done").unwrap()),
+                error: None,
+            })
+        );
+        assert_eq!(
+            parse("journal://x-callback-url/streak"),
+            Ok(DeepLink::Callback {
+                action: Action::Streak,
+                success: None,
+                error: None
+            })
+        );
+        assert!(parse("journal://x-callback-url/append?text=hi&x-error=file:///tmp/x").is_err());
+        assert!(parse("journal://x-callback-url/append?text=hi&x-success=journal://entry/1").is_err());
+        assert!(parse("journal://x-callback-url/delete").is_err());
+    }
+
+    #[test]
+    fn test_callback_params() {
+        let url = with_params(
+            Url::parse("shortcuts://x-callback-url/done?keep=1").unwrap(),
+            vec![("errorMessage", "Nothing to append".to_string())],
+        );
+        assert_eq!(url.as_str(), "shortcuts://x-callback-url/done?keep=1&errorMessage=Nothing+to+append");
+    }
+
+    #[test]
+    fn test_parse_rejects_other_links() {
+        assert!(parse("journal://entry/abc").is_err());
+        assert!(parse("journal://entry").is_err());
+        assert!(parse("journal://delete/1").is_err());
+        assert!(parse("https://new?text=x").is_err());
+    }
+}
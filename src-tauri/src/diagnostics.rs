@@ -0,0 +1,101 @@
+use serde::Serialize;
+use tauri::command;
+
+use journal_core::keychain::{KeychainError, KeychainManager};
+use journal_core::migration_history::{self, MigrationRecord};
+use journal_core::profiles::{active_profile, database_path};
+use journal_core::{DatabaseManager, SCHEMA_VERSION};
+
+use crate::backup::last_backup_at;
+
+// What the diagnostics panel shows when the journal won't open. Opening can
+// fail at the keychain, at the database file or part-way through migrating
+// it, and each comes back as the same kind of error string, so this looks at
+// every stage on its own. It only reads: nothing is migrated, no key is
+// created and no keychain item is written.
+
+#[derive(Debug, Serialize)]
+pub struct HealthCheck {
+    pub ok: bool,
+    /// What went wrong, in words for the user; `None` when `ok`.
+    pub message: Option<String>,
+}
+
+impl HealthCheck {
+    fn from_result<T>(result: &Result<T, String>) -> Self {
+        HealthCheck {
+            ok: result.is_ok(),
+            message: result.as_ref().err().cloned(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct HealthReport {
+    /// Whether the database key can be read, from the keychain or a
+    /// passphrase entered this session.
+    pub keychain: HealthCheck,
+    /// Whether the database opens with that key.
+    pub database: HealthCheck,
+    pub database_path: Option<String>,
+    /// `None` when the database couldn't be opened or doesn't exist yet.
+    pub schema_version: Option<i64>,
+    /// The schema version this build brings databases up to.
+    pub latest_schema_version: i64,
+    /// Migrations that will run the next time the journal is opened.
+    pub pending_migrations: Option<i64>,
+    pub last_backup_at: Option<String>,
+}
+
+/// The database key, looked up as opening the journal would but never
+/// created; `None` for an unencrypted journal.
+fn read_key() -> Result<Option<String>, String> {
+    if active_profile().unencrypted {
+        return Ok(None);
+    }
+    // A passphrase is never kept, so its key is only there once entered
+    if !KeychainManager::has_cached_key() && KeychainManager::uses_passphrase() {
+        return Err(KeychainError::PassphraseRequired.to_user_message());
+    }
+    KeychainManager::new()
+        .and_then(|keychain| keychain.get_key())
+        .map(Some)
+        .map_err(|e| e.to_user_message())
+}
+
+/// Checks each step of opening the journal, for a diagnostics panel.
+#[command]
+pub fn get_health() -> HealthReport {
+    let key = read_key();
+    let path = database_path().map_err(|e| e.to_string());
+    let version = match (&key, &path) {
+        (Ok(key), Ok(path)) if path.exists() => {
+            let cipher = active_profile().cipher.unwrap_or_default();
+            DatabaseManager::stored_schema_version(path, key.as_deref(), &cipher)
+                .map(Some)
+                .map_err(|e| e.to_string())
+        }
+        // Created on first open
+        (Ok(_), Ok(_)) => Ok(None),
+        (Err(_), _) => Err("Can't be opened without its key".to_string()),
+        (_, Err(e)) => Err(e.clone()),
+    };
+    let schema_version = version.as_ref().ok().copied().flatten();
+    HealthReport {
+        keychain: HealthCheck::from_result(&key),
+        database: HealthCheck::from_result(&version),
+        database_path: path.as_ref().ok().map(|path| path.to_string_lossy().into_owned()),
+        schema_version,
+        latest_schema_version: SCHEMA_VERSION,
+        pending_migrations: schema_version.map(|version| (SCHEMA_VERSION - version).max(0)),
+        last_backup_at: last_backup_at().ok().flatten(),
+    }
+}
+
+/// What the app did by itself to get the journal open, such as moving a
+/// legacy key file into the keychain, newest first.
+#[command]
+pub fn get_migration_history() -> Result<Vec<MigrationRecord>, String> {
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    migration_history::migration_history(&db.conn).map_err(|e| e.to_string())
+}
@@ -0,0 +1,194 @@
+use serde::Serialize;
+
+// Three-way diff of entry bodies for conflict review. Bodies are the editor's
+// HTML, which often has no newlines at all, so they're compared in blocks:
+// a segment ends after a newline or after a block-level closing tag. Segments
+// keep their terminators, so concatenating them gives back the exact body.
+
+const BLOCK_ENDS: &[&str] = &[
+    "\n", "</p>", "</div>", "</li>", "</ul>", "</ol>", "</blockquote>", "</pre>", "</h1>", "</h2>", "</h3>",
+    "</h4>", "</h5>", "</h6>", "<br>", "<br/>", "<br />",
+];
+
+/// One run of the body. Everything but `Conflict` merges without asking.
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum DiffChunk {
+    Unchanged { text: String },
+    /// Changed on this device only.
+    Local { base: String, text: String },
+    /// Changed on the other device only.
+    Remote { base: String, text: String },
+    /// Changed the same way on both.
+    Both { base: String, text: String },
+    Conflict { base: String, local: String, remote: String },
+}
+
+pub fn segments(body: &str) -> Vec<&str> {
+    let mut segments = Vec::new();
+    let mut start = 0;
+    let mut i = 0;
+    while i < body.len() {
+        match BLOCK_ENDS.iter().find(|end| body[i..].starts_with(*end)) {
+            Some(end) => {
+                i += end.len();
+                segments.push(&body[start..i]);
+                start = i;
+            }
+            None => i += body[i..].chars().next().map_or(1, char::len_utf8),
+        }
+    }
+    if start < body.len() {
+        segments.push(&body[start..]);
+    }
+    segments
+}
+
+/// For each segment of `a`, the index of the segment of `b` it's paired with
+/// along a longest common subsequence.
+fn lcs_matches(a: &[&str], b: &[&str]) -> Vec<Option<usize>> {
+    // lengths[i][j]: LCS length of a[i..] and b[j..]
+    let mut lengths = vec![vec![0u32; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lengths[i][j] = if a[i] == b[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+    let mut matches = vec![None; a.len()];
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        if a[i] == b[j] {
+            matches[i] = Some(j);
+            i += 1;
+            j += 1;
+        } else if lengths[i + 1][j] >= lengths[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    matches
+}
+
+fn classify(base: &[&str], local: &[&str], remote: &[&str]) -> DiffChunk {
+    let (base, local, remote) = (base.concat(), local.concat(), remote.concat());
+    if local == base {
+        DiffChunk::Remote { base, text: remote }
+    } else if remote == base {
+        DiffChunk::Local { base, text: local }
+    } else if local == remote {
+        DiffChunk::Both { base, text: local }
+    } else {
+        DiffChunk::Conflict { base, local, remote }
+    }
+}
+
+/// diff3: runs where all three agree are unchanged; the runs between them
+/// are classified by which sides departed from the base.
+pub fn diff3(base: &str, local: &str, remote: &str) -> Vec<DiffChunk> {
+    let (base, local, remote) = (segments(base), segments(local), segments(remote));
+    let to_local = lcs_matches(&base, &local);
+    let to_remote = lcs_matches(&base, &remote);
+    let mut chunks = Vec::new();
+    let (mut b, mut l, mut r) = (0, 0, 0);
+    loop {
+        let mut stable = 0;
+        while b + stable < base.len()
+            && to_local[b + stable] == Some(l + stable)
+            && to_remote[b + stable] == Some(r + stable)
+        {
+            stable += 1;
+        }
+        if stable > 0 {
+            chunks.push(DiffChunk::Unchanged { text: base[b..b + stable].concat() });
+            (b, l, r) = (b + stable, l + stable, r + stable);
+            continue;
+        }
+        let next = (b..base.len()).find_map(|i| match (to_local[i], to_remote[i]) {
+            (Some(li), Some(ri)) if li >= l && ri >= r => Some((i, li, ri)),
+            _ => None,
+        });
+        let (nb, nl, nr) = next.unwrap_or((base.len(), local.len(), remote.len()));
+        if (nb, nl, nr) == (b, l, r) {
+            return chunks;
+        }
+        chunks.push(classify(&base[b..nb], &local[l..nl], &remote[r..nr]));
+        (b, l, r) = (nb, nl, nr);
+    }
+}
+
+/// The merged body, if no chunk needs a decision.
+pub fn merge(chunks: &[DiffChunk]) -> Option<String> {
+    chunks
+        .iter()
+        .map(|chunk| match chunk {
+            DiffChunk::Unchanged { text }
+            | DiffChunk::Local { text, .. }
+            | DiffChunk::Remote { text, .. }
+            | DiffChunk::Both { text, .. } => Some(text.as_str()),
+            DiffChunk::Conflict { .. } => None,
+        })
+        .collect::<Option<Vec<_>>>()
+        .map(|parts| parts.concat())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_segments_keep_terminators() {
+        let body = "<p>One</p><p>Two<br>three</p>\ntail";
+        assert_eq!(segments(body), vec!["<p>One</p>", "<p>Two<br>", "three</p>", "\n", "tail"]);
+        assert_eq!(segments(body).concat(), body);
+        assert_eq!(segments("<p>café</p>"), vec!["<p>café</p>"]);
+    }
+
+    #[test]
+    fn test_diff3_merges_separate_edits() {
+        let base = "<p>a</p><p>b</p><p>c</p>";
+        let local = "<p>A</p><p>b</p><p>c</p>";
+        let remote = "<p>a</p><p>b</p><p>C</p>";
+        let chunks = diff3(base, local, remote);
+        assert_eq!(
+            chunks,
+            vec![
+                DiffChunk::Local { base: "<p>a</p>".to_string(), text: "<p>A</p>".to_string() },
+                DiffChunk::Unchanged { text: "<p>b</p>".to_string() },
+                DiffChunk::Remote { base: "<p>c</p>".to_string(), text: "<p>C</p>".to_string() },
+            ]
+        );
+        assert_eq!(merge(&chunks).as_deref(), Some("<p>A</p><p>b</p><p>C</p>"));
+    }
+
+    #[test]
+    fn test_diff3_reports_overlapping_edits() {
+        let chunks = diff3("<p>a</p><p>b</p>", "<p>a</p><p>mine</p>", "<p>a</p><p>theirs</p>");
+        assert_eq!(
+            chunks[1],
+            DiffChunk::Conflict {
+                base: "<p>b</p>".to_string(),
+                local: "<p>mine</p>".to_string(),
+                remote: "<p>theirs</p>".to_string(),
+            }
+        );
+        assert_eq!(merge(&chunks), None);
+    }
+
+    #[test]
+    fn test_diff3_handles_appends_and_identical_changes() {
+        let chunks = diff3("<p>a</p>", "<p>a</p><p>new</p>", "<p>a</p><p>new</p>");
+        assert_eq!(
+            chunks,
+            vec![
+                DiffChunk::Unchanged { text: "<p>a</p>".to_string() },
+                DiffChunk::Both { base: String::new(), text: "<p>new</p>".to_string() },
+            ]
+        );
+        assert!(diff3("", "", "").is_empty());
+    }
+}
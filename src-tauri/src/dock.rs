@@ -0,0 +1,116 @@
+use std::sync::OnceLock;
+use tauri::AppHandle;
+
+use crate::tray;
+
+// The Dock icon's context menu on macOS: New Entry and Open Today's Entry,
+// which do what the same items in the tray menu do. Tauri has no Dock menu of
+// its own, so AppKit is asked for one through `applicationDockMenu:` on the
+// app delegate. Elsewhere there's no Dock and this does nothing.
+
+static APP: OnceLock<AppHandle> = OnceLock::new();
+
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+enum DockAction {
+    NewEntry,
+    OpenToday,
+}
+
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+fn clicked(action: DockAction) {
+    let Some(app) = APP.get() else {
+        return;
+    };
+    match action {
+        DockAction::NewEntry => tray::new_entry(app),
+        DockAction::OpenToday => tray::open_today(app),
+    }
+}
+
+/// Adds the Dock menu. Called once from setup.
+pub fn create(app: &AppHandle) {
+    let _ = APP.set(app.clone());
+    #[cfg(target_os = "macos")]
+    macos::create();
+}
+
+#[cfg(target_os = "macos")]
+mod macos {
+    use log::warn;
+    use std::cell::OnceCell;
+    use std::ptr;
+
+    use objc2::rc::Retained;
+    use objc2::runtime::{AnyObject, Imp, NSObject, Sel};
+    use objc2::{define_class, msg_send, sel, MainThreadMarker, MainThreadOnly};
+    use objc2_app_kit::{NSMenu, NSMenuItem};
+    use objc2_foundation::{NSObjectProtocol, NSString};
+
+    use super::DockAction;
+    use crate::app_delegate;
+
+    define_class!(
+        #[unsafe(super(NSObject))]
+        #[thread_kind = MainThreadOnly]
+        #[name = "JournalDockMenuTarget"]
+        struct DockMenuTarget;
+
+        unsafe impl NSObjectProtocol for DockMenuTarget {}
+
+        impl DockMenuTarget {
+            #[unsafe(method(newEntry:))]
+            fn new_entry(&self, _sender: Option<&AnyObject>) {
+                super::clicked(DockAction::NewEntry);
+            }
+
+            #[unsafe(method(openToday:))]
+            fn open_today(&self, _sender: Option<&AnyObject>) {
+                super::clicked(DockAction::OpenToday);
+            }
+        }
+    );
+
+    thread_local! {
+        /// AppKit objects stay on the main thread, along with the menu's target.
+        static DOCK_MENU: OnceCell<(Retained<NSMenu>, Retained<DockMenuTarget>)> = const { OnceCell::new() };
+    }
+
+    fn menu_item(mtm: MainThreadMarker, title: &str, action: Sel, target: &DockMenuTarget) -> Retained<NSMenuItem> {
+        let item = unsafe {
+            NSMenuItem::initWithTitle_action_keyEquivalent(
+                NSMenuItem::alloc(mtm),
+                &NSString::from_str(title),
+                Some(action),
+                &NSString::new(),
+            )
+        };
+        unsafe { item.setTarget(Some(target)) };
+        item
+    }
+
+    /// `applicationDockMenu:` on the app delegate.
+    extern "C-unwind" fn dock_menu(_delegate: &AnyObject, _cmd: Sel, _app: &AnyObject) -> *mut NSMenu {
+        DOCK_MENU.with(|menu| menu.get().map_or(ptr::null_mut(), |(menu, _)| Retained::as_ptr(menu).cast_mut()))
+    }
+
+    pub fn create() {
+        let Some(mtm) = MainThreadMarker::new() else {
+            warn!("The Dock menu must be created on the main thread");
+            return;
+        };
+        let target: Retained<DockMenuTarget> = unsafe { msg_send![DockMenuTarget::alloc(mtm), init] };
+        let menu = NSMenu::new(mtm);
+        menu.addItem(&menu_item(mtm, "New Entry", sel!(newEntry:), &target));
+        menu.addItem(&menu_item(mtm, "Open Today's Entry", sel!(openToday:), &target));
+        DOCK_MENU.with(|cell| {
+            let _ = cell.set((menu, target));
+        });
+        let imp: Imp = unsafe {
+            std::mem::transmute::<extern "C-unwind" fn(&AnyObject, Sel, &AnyObject) -> *mut NSMenu, Imp>(dock_menu)
+        };
+        if !unsafe { app_delegate::add_method(mtm, sel!(applicationDockMenu:), imp, c"@@:@") } {
+            warn!("Could not give the application a Dock menu");
+        }
+    }
+}
@@ -0,0 +1,719 @@
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::OsRng;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use log::{debug, error, info, warn};
+use rustls::pki_types::ServerName;
+use rustls::{ClientConfig, ClientConnection, RootCertStore, StreamOwned};
+use serde::Serialize;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use tauri::{command, AppHandle, Emitter};
+
+use journal_core::insert_entry;
+use journal_core::keychain::KeychainManager;
+use journal_core::text::{strip_html, text_to_html};
+
+use crate::attachments::{add_audio_attachment, add_image};
+use crate::file_drop::SkippedFile;
+use crate::settings::{EmailIngestSettings, LimitSettings, Settings};
+use crate::validation::{clean_body, clean_entry};
+use crate::{git_sync, DatabaseManager};
+
+// Notes emailed to a mailbox kept for the purpose, for writing on the go
+// from any mail app. When it's turned on, the mailbox is checked over IMAP
+// every few minutes. Each unread message whose subject holds the secret
+// token becomes an entry, filed at the time it was sent and titled with the
+// rest of the subject. Its text becomes the body, with the signature cut
+// off. Attached images are shown below the text, and voice memos are
+// attached. Other files are left out and counted. Messages are marked read
+// once they've been looked at, so none is imported twice. Other mail in the
+// mailbox is left alone.
+
+pub(crate) const PASSWORD_ACCOUNT: &str = "journal_email_password";
+pub(crate) const TOKEN_ACCOUNT: &str = "journal_email_subject_token";
+const TOKEN_BYTES: usize = 6;
+const IO_TIMEOUT: Duration = Duration::from_secs(30);
+/// Larger messages are refused rather than read into memory.
+const MAX_MESSAGE_BYTES: usize = 50 * 1024 * 1024;
+const POLLER_START_DELAY: Duration = Duration::from_secs(2 * 60);
+const POLLER_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+#[derive(Debug, Default, Serialize)]
+pub struct EmailIngestReport {
+    /// Entries created, one per message.
+    pub created: Vec<i32>,
+    pub images: usize,
+    pub voice_memos: usize,
+    /// Attached files other than images and voice memos, which were left out.
+    pub skipped_attachments: usize,
+    /// Messages that couldn't be imported, by subject.
+    pub failed: Vec<SkippedFile>,
+}
+
+#[derive(Debug)]
+struct MailAttachment {
+    mime: String,
+    data: Vec<u8>,
+}
+
+#[derive(Debug, Default)]
+struct MailNote {
+    subject: String,
+    sent_at: Option<DateTime<Utc>>,
+    text: String,
+    attachments: Vec<MailAttachment>,
+}
+
+/// A connection to an IMAP server, past its greeting.
+struct Imap<S: Read + Write> {
+    stream: BufReader<S>,
+    next_tag: u32,
+}
+
+/// The length of the literal a response line ends with, as in `BODY[] {310}`.
+fn literal_len(line: &[u8]) -> Option<usize> {
+    let line = line.strip_suffix(b"\r\n").or_else(|| line.strip_suffix(b"\n"))?;
+    let line = line.strip_suffix(b"}")?;
+    let start = line.iter().rposition(|&b| b == b'{')?;
+    std::str::from_utf8(&line[start + 1..]).ok()?.parse().ok()
+}
+
+/// `value` as an IMAP quoted string.
+fn quote(value: &str) -> Result<String, String> {
+    if value.contains(['\r', '\n']) {
+        return Err("Mail settings can't contain line breaks".to_string());
+    }
+    Ok(format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\"")))
+}
+
+impl<S: Read + Write> Imap<S> {
+    fn new(stream: S) -> Result<Self, String> {
+        let mut imap = Imap {
+            stream: BufReader::new(stream),
+            next_tag: 1,
+        };
+        let greeting = imap.read_line()?;
+        if !greeting.starts_with(b"* OK") {
+            return Err(format!("Unexpected greeting from mail server: {}", String::from_utf8_lossy(&greeting).trim()));
+        }
+        Ok(imap)
+    }
+
+    fn read_line(&mut self) -> Result<Vec<u8>, String> {
+        let mut line = Vec::new();
+        self.stream
+            .read_until(b'\n', &mut line)
+            .map_err(|e| format!("Failed to read from mail server: {}", e))?;
+        if line.is_empty() {
+            return Err("The mail server closed the connection".to_string());
+        }
+        Ok(line)
+    }
+
+    /// Sends `command` and returns everything the server sent back before
+    /// saying it's done, literals included. A refusal is an error.
+    fn command(&mut self, command: &str) -> Result<Vec<u8>, String> {
+        let tag = format!("a{}", self.next_tag);
+        self.next_tag += 1;
+        let stream = self.stream.get_mut();
+        stream
+            .write_all(format!("{} {}\r\n", tag, command).as_bytes())
+            .and_then(|_| stream.flush())
+            .map_err(|e| format!("Failed to write to mail server: {}", e))?;
+        let mut data = Vec::new();
+        loop {
+            let mut line = self.read_line()?;
+            while let Some(len) = literal_len(&line) {
+                if len > MAX_MESSAGE_BYTES {
+                    return Err(format!("A message is over {} MB", MAX_MESSAGE_BYTES / (1024 * 1024)));
+                }
+                data.extend_from_slice(&line);
+                let mut literal = vec![0; len];
+                self.stream
+                    .read_exact(&mut literal)
+                    .map_err(|e| format!("Failed to read from mail server: {}", e))?;
+                data.extend_from_slice(&literal);
+                line = self.read_line()?;
+            }
+            if let Some(status) = line.strip_prefix(format!("{} ", tag).as_bytes()) {
+                let status = String::from_utf8_lossy(status);
+                if status.starts_with("OK") {
+                    return Ok(data);
+                }
+                // Never echo the command, which may hold the password
+                let verb = command.split_whitespace().next().unwrap_or_default();
+                return Err(format!("Mail server refused {}: {}", verb, status.trim()));
+            }
+            data.extend_from_slice(&line);
+        }
+    }
+}
+
+/// UIDs listed by a `UID SEARCH` response.
+fn parse_search(data: &[u8]) -> Vec<u32> {
+    let text = String::from_utf8_lossy(data);
+    text.lines()
+        .filter_map(|line| line.strip_prefix("* SEARCH"))
+        .flat_map(|uids| uids.split_whitespace().filter_map(|uid| uid.parse().ok()))
+        .collect()
+}
+
+/// The first literal in a response, which for a `FETCH` of one message is the message.
+fn first_literal(data: &[u8]) -> Option<&[u8]> {
+    let mut start = 0;
+    for line in data.split_inclusive(|&b| b == b'\n') {
+        start += line.len();
+        if let Some(len) = literal_len(line) {
+            return data.get(start..start + len);
+        }
+    }
+    None
+}
+
+/// Logs in, opens `settings.mailbox` and returns the unread messages whose
+/// subject holds `token`, by UID, as raw RFC 822 text.
+fn fetch_new<S: Read + Write>(
+    imap: &mut Imap<S>,
+    settings: &EmailIngestSettings,
+    password: &str,
+    token: &str,
+) -> Result<Vec<(u32, Vec<u8>)>, String> {
+    imap.command(&format!("LOGIN {} {}", quote(&settings.username)?, quote(password)?))?;
+    imap.command(&format!("SELECT {}", quote(&settings.mailbox)?))?;
+    let uids = parse_search(&imap.command(&format!("UID SEARCH UNSEEN SUBJECT {}", quote(token)?))?);
+    let mut messages = Vec::new();
+    for uid in uids {
+        // PEEK leaves the message unread until it's been imported
+        let data = imap.command(&format!("UID FETCH {} BODY.PEEK[]", uid))?;
+        match first_literal(&data) {
+            Some(message) => messages.push((uid, message.to_vec())),
+            None => warn!("Mail server sent no message for UID {}", uid),
+        }
+    }
+    Ok(messages)
+}
+
+fn connect(settings: &EmailIngestSettings) -> Result<Imap<StreamOwned<ClientConnection, TcpStream>>, String> {
+    let tcp = TcpStream::connect((settings.host.as_str(), settings.port))
+        .map_err(|e| format!("Failed to reach {}: {}", settings.host, e))?;
+    tcp.set_read_timeout(Some(IO_TIMEOUT)).map_err(|e| e.to_string())?;
+    tcp.set_write_timeout(Some(IO_TIMEOUT)).map_err(|e| e.to_string())?;
+    let roots = RootCertStore::from_iter(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+    let config = ClientConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+        .with_safe_default_protocol_versions()
+        .map_err(|e| e.to_string())?
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    let server_name = ServerName::try_from(settings.host.clone())
+        .map_err(|_| format!("'{}' is not a valid server name", settings.host))?;
+    let connection = ClientConnection::new(Arc::new(config), server_name).map_err(|e| e.to_string())?;
+    Imap::new(StreamOwned::new(connection, tcp))
+}
+
+/// Splits a message or part into its header block and body.
+fn split_message(raw: &[u8]) -> (&[u8], &[u8]) {
+    for (i, window) in raw.windows(2).enumerate() {
+        if window == b"\n\n" {
+            return (&raw[..i + 1], &raw[i + 2..]);
+        }
+        if window == b"\n\r" && raw.get(i + 2) == Some(&b'\n') {
+            return (&raw[..i + 1], &raw[i + 3..]);
+        }
+    }
+    (raw, &[])
+}
+
+/// Headers by lowercase name, with folded lines joined.
+fn parse_headers(raw: &[u8]) -> Vec<(String, String)> {
+    let mut headers: Vec<(String, String)> = Vec::new();
+    for line in String::from_utf8_lossy(raw).lines() {
+        if line.starts_with([' ', '\t']) {
+            if let Some((_, value)) = headers.last_mut() {
+                value.push(' ');
+                value.push_str(line.trim());
+            }
+        } else if let Some((name, value)) = line.split_once(':') {
+            headers.push((name.trim().to_ascii_lowercase(), value.trim().to_string()));
+        }
+    }
+    headers
+}
+
+fn header<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    headers.iter().find(|(header, _)| header == name).map(|(_, value)| value.as_str())
+}
+
+/// Parameter `name` of a header value such as `text/plain; charset="utf-8"`.
+fn header_param(value: &str, name: &str) -> Option<String> {
+    value.split(';').skip(1).find_map(|param| {
+        let (key, value) = param.split_once('=')?;
+        key.trim().eq_ignore_ascii_case(name).then(|| value.trim().trim_matches('"').to_string())
+    })
+}
+
+/// Text in `charset`. Latin-1 and its Windows cousin are common in mail;
+/// anything else is read as UTF-8.
+fn decode_charset(bytes: &[u8], charset: &str) -> String {
+    match charset.to_ascii_lowercase().as_str() {
+        "iso-8859-1" | "latin1" | "windows-1252" => bytes.iter().map(|&b| b as char).collect(),
+        _ => String::from_utf8_lossy(bytes).into_owned(),
+    }
+}
+
+fn hex_byte(hex: &[u8]) -> Option<u8> {
+    u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok()
+}
+
+fn decode_quoted_printable(data: &[u8], in_header: bool) -> Vec<u8> {
+    let mut decoded = Vec::with_capacity(data.len());
+    let mut i = 0;
+    while i < data.len() {
+        match data[i] {
+            b'=' if data[i + 1..].starts_with(b"\r\n") => i += 3,
+            b'=' if data[i + 1..].starts_with(b"\n") => i += 2,
+            b'=' => match data.get(i + 1..i + 3).and_then(hex_byte) {
+                Some(byte) => {
+                    decoded.push(byte);
+                    i += 3;
+                }
+                None => {
+                    decoded.push(b'=');
+                    i += 1;
+                }
+            },
+            b'_' if in_header => {
+                decoded.push(b' ');
+                i += 1;
+            }
+            byte => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    decoded
+}
+
+fn decode_base64(data: &[u8]) -> Vec<u8> {
+    let compact: Vec<u8> = data.iter().copied().filter(|b| !b.is_ascii_whitespace()).collect();
+    base64::engine::general_purpose::STANDARD.decode(compact).unwrap_or_default()
+}
+
+/// A header value with its RFC 2047 encoded words, such as `=?UTF-8?Q?Caf=C3=A9?=`, decoded.
+fn decode_words(value: &str) -> String {
+    let mut decoded = String::new();
+    let mut rest = value;
+    let mut after_word = false;
+    while let Some(start) = rest.find("=?") {
+        let word = rest[start + 2..].splitn(3, '?').collect::<Vec<_>>();
+        let [charset, encoding, tail] = word[..] else { break };
+        let Some(end) = tail.find("?=") else { break };
+        let between = &rest[..start];
+        // Whitespace between two encoded words isn't part of the text
+        if !(after_word && between.trim().is_empty()) {
+            decoded.push_str(between);
+        }
+        let text = &tail[..end];
+        let bytes = match encoding {
+            "B" | "b" => decode_base64(text.as_bytes()),
+            _ => decode_quoted_printable(text.as_bytes(), true),
+        };
+        decoded.push_str(&decode_charset(&bytes, charset));
+        rest = &tail[end + 2..];
+        after_word = true;
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+/// The lines of a multipart body between each `--boundary`.
+fn multipart_parts<'a>(body: &'a [u8], boundary: &str) -> Vec<&'a [u8]> {
+    let delimiter = format!("--{}", boundary);
+    let last = format!("{}--", delimiter);
+    let mut parts = Vec::new();
+    let mut start = None;
+    let mut pos = 0;
+    for line in body.split_inclusive(|&b| b == b'\n') {
+        let trimmed = line.trim_ascii_end();
+        if trimmed == delimiter.as_bytes() || trimmed == last.as_bytes() {
+            if let Some(start) = start {
+                parts.push(&body[start..pos]);
+            }
+            if trimmed == last.as_bytes() {
+                return parts;
+            }
+            start = Some(pos + line.len());
+        }
+        pos += line.len();
+    }
+    parts
+}
+
+/// Reads the part with `headers` and `body` into `note`. Plain text parts make
+/// up the note's text, and the first HTML part stands in for them if there
+/// are none; anything else is an attachment.
+fn read_part(headers: &[(String, String)], body: &[u8], note: &mut MailNote, html: &mut Option<String>) {
+    let content_type = header(headers, "content-type").unwrap_or("text/plain");
+    let mime = content_type.split(';').next().unwrap_or_default().trim().to_ascii_lowercase();
+    if mime.starts_with("multipart/") {
+        if let Some(boundary) = header_param(content_type, "boundary") {
+            for part in multipart_parts(body, &boundary) {
+                let (part_headers, part_body) = split_message(part);
+                read_part(&parse_headers(part_headers), part_body, note, html);
+            }
+        }
+        return;
+    }
+    let data = match header(headers, "content-transfer-encoding").map(str::to_ascii_lowercase).as_deref() {
+        Some("base64") => decode_base64(body),
+        Some("quoted-printable") => decode_quoted_printable(body, false),
+        _ => body.to_vec(),
+    };
+    let attached = header(headers, "content-disposition").is_some_and(|disposition| {
+        disposition.to_ascii_lowercase().starts_with("attachment") || header_param(disposition, "filename").is_some()
+    });
+    let charset = header_param(content_type, "charset").unwrap_or_default();
+    match mime.as_str() {
+        "text/plain" if !attached => {
+            // Some mail apps split the text around an inline image
+            if !note.text.is_empty() {
+                note.text.push('\n');
+            }
+            note.text.push_str(&decode_charset(&data, &charset));
+        }
+        "text/html" if !attached => {
+            if html.is_none() {
+                *html = Some(decode_charset(&data, &charset));
+            }
+        }
+        _ => note.attachments.push(MailAttachment { mime, data }),
+    }
+}
+
+/// The text of an HTML part, keeping its line breaks.
+fn html_text(html: &str) -> String {
+    let mut marked = html.to_string();
+    for tag in ["<br", "<BR", "</p", "</P", "</div", "</DIV", "</li", "</LI"] {
+        marked = marked.replace(tag, &format!("\n{}", tag));
+    }
+    strip_html(&marked).lines().map(str::trim).collect::<Vec<_>>().join("\n")
+}
+
+/// Text without the signature, which starts at the conventional `-- ` line.
+fn without_signature(text: &str) -> &str {
+    let text = text.trim();
+    match text.find("\n-- \n").or_else(|| text.find("\r\n-- \r\n")) {
+        Some(end) => text[..end].trim_end(),
+        None => text,
+    }
+}
+
+fn parse_message(raw: &[u8]) -> MailNote {
+    let (headers, body) = split_message(raw);
+    let headers = parse_headers(headers);
+    let mut note = MailNote {
+        subject: decode_words(header(&headers, "subject").unwrap_or_default()),
+        sent_at: header(&headers, "date")
+            .and_then(|date| DateTime::parse_from_rfc2822(date).ok())
+            .map(|date| date.with_timezone(&Utc)),
+        ..Default::default()
+    };
+    let mut html = None;
+    read_part(&headers, body, &mut note, &mut html);
+    if note.text.trim().is_empty() {
+        note.text = html.as_deref().map(html_text).unwrap_or_default();
+    }
+    note.text = without_signature(&note.text).replace("\r\n", "\n");
+    note
+}
+
+/// The entry title for `subject`: the subject without the token.
+fn title_for(subject: &str, token: &str) -> String {
+    subject.replace(token, " ").split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Creates the entry for `note` with its images and voice memos, and adds what
+/// happened to `report`. A note over `limits` is refused.
+fn import_note(
+    conn: &rusqlite::Connection,
+    note: &MailNote,
+    token: &str,
+    limits: &LimitSettings,
+    report: &mut EmailIngestReport,
+) -> Result<(), String> {
+    let created_at = note.sent_at.unwrap_or_else(Utc::now).to_rfc3339();
+    let (title, mut body) = clean_entry(&title_for(&note.subject, token), &text_to_html(&note.text), limits)?;
+    let id = insert_entry(conn, &title, &body, &created_at).map_err(|e| e.to_string())?;
+    report.created.push(id);
+
+    // Attachments need the entry to belong to, so they're put in once it
+    // exists. One that's too big is left out rather than losing the note.
+    let mut images = 0;
+    for attachment in &note.attachments {
+        let added = if attachment.mime.starts_with("image/") {
+            add_image(id, &attachment.mime, &attachment.data).map(|image| {
+                body.push_str(&format!("<p><img src=\"{}\"></p>", image.token));
+                images += 1;
+            })
+        } else if attachment.mime.starts_with("audio/") {
+            add_audio_attachment(id, attachment.data.clone(), attachment.mime.clone()).map(|_| report.voice_memos += 1)
+        } else {
+            Err(format!("{} files aren't kept", attachment.mime))
+        };
+        if let Err(e) = added {
+            debug!("Left an attachment of '{}' out: {}", note.subject, e);
+            report.skipped_attachments += 1;
+        }
+    }
+    if images > 0 {
+        // Images have no words or links, so the columns derived from the body stay right
+        let body = clean_body(&body, limits)?;
+        conn.execute("UPDATE journal_entries SET body = ?1 WHERE id = ?2", rusqlite::params![body, id])
+            .map_err(|e| e.to_string())?;
+        report.images += images;
+    }
+    Ok(())
+}
+
+fn stored_secret(account: &str) -> Result<String, String> {
+    KeychainManager::get_secret(account)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "Email import isn't set up; set it up again".to_string())
+}
+
+/// Imports the new notes in the configured mailbox and marks them read.
+fn check_mailbox(settings: &EmailIngestSettings) -> Result<EmailIngestReport, String> {
+    let password = stored_secret(PASSWORD_ACCOUNT)?;
+    let token = stored_secret(TOKEN_ACCOUNT)?;
+    let mut imap = connect(settings)?;
+    let messages = fetch_new(&mut imap, settings, &password, &token)?;
+    let mut report = EmailIngestReport::default();
+    if messages.is_empty() {
+        let _ = imap.command("LOGOUT");
+        return Ok(report);
+    }
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    let limits = Settings::load().limits;
+    for (uid, raw) in messages {
+        let note = parse_message(&raw);
+        // The server's search is loose about case and encoding
+        if !note.subject.contains(&token) {
+            continue;
+        }
+        if let Err(reason) = import_note(&db.conn, &note, &token, &limits, &mut report) {
+            warn!("Failed to import emailed note '{}': {}", note.subject, reason);
+            report.failed.push(SkippedFile {
+                name: note.subject.clone(),
+                reason,
+            });
+        }
+        // Read either way, so a message that fails isn't tried every few minutes
+        imap.command(&format!("UID STORE {} +FLAGS.SILENT (\\Seen)", uid))?;
+    }
+    let _ = imap.command("LOGOUT");
+    debug!("Imported {} emailed notes", report.created.len());
+    if !report.created.is_empty() {
+        git_sync::commit_on_save();
+    }
+    Ok(report)
+}
+
+/// Checks the mailbox and tells the window when entries arrived.
+fn check_and_notify(app: &AppHandle, settings: &EmailIngestSettings) -> Result<EmailIngestReport, String> {
+    let report = check_mailbox(settings)?;
+    if !report.created.is_empty() {
+        let _ = app.emit("entries-changed", ());
+    }
+    Ok(report)
+}
+
+/// Starts the background thread that checks the mailbox. Like the sync
+/// reconciler, it waits for the journal to be unlocked.
+pub fn start_poller(app: AppHandle) {
+    thread::spawn(move || {
+        thread::sleep(POLLER_START_DELAY);
+        loop {
+            let settings = Settings::load().email;
+            if settings.enabled && KeychainManager::has_cached_key() {
+                if let Err(e) = check_and_notify(&app, &settings) {
+                    error!("Checking mail failed: {}", e);
+                }
+            }
+            thread::sleep(POLLER_INTERVAL);
+        }
+    });
+}
+
+/// Turns on importing notes emailed to `mailbox` on IMAP server `host`, after
+/// checking the login works. Returns the token the subject of each note must
+/// hold, creating one the first time.
+#[command]
+pub async fn configure_email_ingest(
+    host: String,
+    port: Option<u16>,
+    username: String,
+    password: String,
+    mailbox: Option<String>,
+) -> Result<String, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let mut settings = Settings::load();
+        let defaults = EmailIngestSettings::default();
+        let email = EmailIngestSettings {
+            enabled: true,
+            host: host.trim().to_string(),
+            port: port.unwrap_or(defaults.port),
+            username,
+            mailbox: mailbox.filter(|mailbox| !mailbox.is_empty()).unwrap_or(defaults.mailbox),
+        };
+        let mut imap = connect(&email)?;
+        imap.command(&format!("LOGIN {} {}", quote(&email.username)?, quote(&password)?))?;
+        imap.command(&format!("EXAMINE {}", quote(&email.mailbox)?))?;
+        let _ = imap.command("LOGOUT");
+        let token = match KeychainManager::get_secret(TOKEN_ACCOUNT).map_err(|e| e.to_string())? {
+            Some(token) => token,
+            None => {
+                let mut bytes = [0u8; TOKEN_BYTES];
+                OsRng.fill_bytes(&mut bytes);
+                let token = format!("journal-{}", hex::encode(bytes));
+                KeychainManager::store_secret(TOKEN_ACCOUNT, &token).map_err(|e| e.to_string())?;
+                token
+            }
+        };
+        KeychainManager::store_secret(PASSWORD_ACCOUNT, &password).map_err(|e| e.to_string())?;
+        info!("Checking {} on {} for emailed notes", email.mailbox, email.host);
+        settings.email = email;
+        settings.save().map_err(|e| e.to_string())?;
+        Ok(token)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Stops checking the mailbox and forgets its password and token.
+#[command]
+pub fn disable_email_ingest() -> Result<(), String> {
+    let mut settings = Settings::load();
+    settings.email.enabled = false;
+    settings.save().map_err(|e| e.to_string())?;
+    KeychainManager::delete_secret(PASSWORD_ACCOUNT).map_err(|e| e.to_string())?;
+    KeychainManager::delete_secret(TOKEN_ACCOUNT).map_err(|e| e.to_string())
+}
+
+/// Checks the mailbox for emailed notes right away.
+#[command]
+pub async fn check_email_now(app: AppHandle) -> Result<EmailIngestReport, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let settings = Settings::load().email;
+        if !settings.enabled {
+            return Err("Email import is not set up".to_string());
+        }
+        check_and_notify(&app, &settings)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    /// A mail server that sends back `replies` whatever it's told.
+    struct Script {
+        replies: Cursor<Vec<u8>>,
+        sent: Vec<u8>,
+    }
+
+    impl Read for Script {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.replies.read(buf)
+        }
+    }
+
+    impl Write for Script {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.sent.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    const MESSAGE: &str = "Subject: =?UTF-8?Q?Caf=C3=A9?= journal-0a1b2c3d4e5f\r\n\
+        Date: Tue, 5 Mar 2024 12:00:00 +0000\r\n\
+        Content-Type: multipart/mixed; boundary=\"b1\"\r\n\
+        \r\n\
+        --b1\r\n\
+        Content-Type: multipart/alternative; boundary=b2\r\n\
+        \r\n\
+        --b2\r\n\
+        Content-Type: text/plain; charset=utf-8\r\n\
+        Content-Transfer-Encoding: quoted-printable\r\n\
+        \r\n\
+        Trams and tiles =E2=80=94 all day.\r\n\
+        Long line that w=\r\n\
+        raps\r\n\
+        -- \r\n\
+        Sent from my phone\r\n\
+        --b2\r\n\
+        Content-Type: text/html\r\n\
+        \r\n\
+        <p>Trams and tiles</p>\r\n\
+        --b2--\r\n\
+        --b1\r\n\
+        Content-Type: image/png; name=\"tile.png\"\r\n\
+        Content-Disposition: attachment; filename=\"tile.png\"\r\n\
+        Content-Transfer-Encoding: base64\r\n\
+        \r\n\
+        iVBORw0K\r\n\
+        --b1--\r\n";
+
+    #[test]
+    fn test_parse_message() {
+        let note = parse_message(MESSAGE.as_bytes());
+        assert_eq!(note.subject, "Café journal-0a1b2c3d4e5f");
+        assert_eq!(title_for(&note.subject, "journal-0a1b2c3d4e5f"), "Café");
+        assert_eq!(note.sent_at.unwrap().to_rfc3339(), "2024-03-05T12:00:00+00:00");
+        assert_eq!(note.text, "Trams and tiles — all day.\nLong line that wraps");
+        assert_eq!(note.attachments.len(), 1);
+        assert_eq!(note.attachments[0].mime, "image/png");
+        assert_eq!(note.attachments[0].data, b"\x89PNG\r\n");
+
+        let html_only = parse_message(b"Subject: Hi\r\nContent-Type: text/html\r\n\r\n<p>One</p><p>Two<br>Three</p>");
+        assert_eq!(html_only.text, "One\nTwo\nThree");
+        assert_eq!(decode_words("=?utf-8?B?w6k=?= =?utf-8?Q?t=C3=A9?= done"), "été done");
+    }
+
+    #[test]
+    fn test_fetch_new() {
+        let replies = format!(
+            "* OK IMAP ready\r\na1 OK Logged in\r\n* 3 EXISTS\r\na2 OK [READ-WRITE] Selected\r\n\
+             * SEARCH 7\r\na3 OK Search done\r\n* 1 FETCH (UID 7 BODY[] {{{}}}\r\n{})\r\na4 OK Fetched\r\n",
+            MESSAGE.len(),
+            MESSAGE
+        );
+        let script = Script {
+            replies: Cursor::new(replies.into_bytes()),
+            sent: Vec::new(),
+        };
+        let mut imap = Imap::new(script).unwrap();
+        let settings = EmailIngestSettings {
+            username: "ana@example.com".to_string(),
+            ..Default::default()
+        };
+        let messages = fetch_new(&mut imap, &settings, "pa\"ss", "journal-0a1b2c3d4e5f").unwrap();
+        assert_eq!(messages, [(7, MESSAGE.as_bytes().to_vec())]);
+        let sent = String::from_utf8(imap.stream.into_inner().sent).unwrap();
+        assert_eq!(
+            sent,
+            "a1 LOGIN \"ana@example.com\" \"pa\\\"ss\"\r\na2 SELECT \"INBOX\"\r\n\
+             a3 UID SEARCH UNSEEN SUBJECT \"journal-0a1b2c3d4e5f\"\r\na4 UID FETCH 7 BODY.PEEK[]\r\n"
+        );
+    }
+}
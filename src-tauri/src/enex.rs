@@ -0,0 +1,416 @@
+use base64::Engine;
+use chrono::{NaiveDateTime, Utc};
+use log::debug;
+use md5::{Digest, Md5};
+use roxmltree::{Document, Node, ParsingOptions};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs;
+use tauri::command;
+
+use journal_core::{insert_entry, parse_entry_datetime};
+
+use crate::attachments::add_image;
+use crate::file_drop::SkippedFile;
+use crate::importers::{preview_entries, ImportPreview, ImportedEntry};
+use crate::jobs::{Job, JobKind, CANCELLED};
+use crate::settings::{LimitSettings, Settings};
+use crate::tags::{imported_tags, replace_tags};
+use crate::validation::{clean_body, clean_entry};
+use crate::{git_sync, markdown_mirror, recent_menu, DatabaseManager};
+
+// Notes exported from Evernote as an `.enex` file. Each note becomes an entry
+// filed at its creation date, with its tags. The note itself is ENML, an XHTML
+// dialect, which is turned into the markup the editor writes; images embedded
+// in it become image attachments shown where they were. Other attached files
+// are left out and counted in the report. A dry run reads the notes and
+// reports what importing them would do. The notes are imported one by one as a
+// job, so cancelling it keeps the ones imported so far.
+
+/// ENEX and ENML both start with a DOCTYPE, which roxmltree refuses by default.
+const PARSING_OPTIONS: ParsingOptions = ParsingOptions {
+    allow_dtd: true,
+    nodes_limit: u32::MAX,
+};
+
+#[derive(Debug, Default, Serialize)]
+pub struct EnexImportReport {
+    /// Entries created, one per note.
+    pub created: Vec<i32>,
+    pub images: usize,
+    /// Attached files other than images, which were left out.
+    pub skipped_resources: usize,
+    /// Notes that couldn't be imported, by title.
+    pub failed: Vec<SkippedFile>,
+    /// What a dry run found; `None` when the notes were imported.
+    pub preview: Option<ImportPreview>,
+}
+
+#[derive(Debug)]
+struct Resource {
+    /// Hex MD5 of the data, which is how ENML's `<en-media>` refers to it.
+    hash: String,
+    mime: String,
+    data: Vec<u8>,
+}
+
+#[derive(Debug)]
+struct EnexNote {
+    title: String,
+    created_at: Option<String>,
+    tags: Vec<String>,
+    /// The ENML document.
+    content: String,
+    resources: Vec<Resource>,
+}
+
+fn child_text<'a>(node: Node<'a, '_>, name: &str) -> Option<&'a str> {
+    node.children().find(|child| child.has_tag_name(name)).and_then(|child| child.text())
+}
+
+/// Evernote's `20240305T120000Z` as RFC3339.
+fn parse_enex_date(value: &str) -> Option<String> {
+    let datetime = NaiveDateTime::parse_from_str(value.trim(), "%Y%m%dT%H%M%SZ").ok()?;
+    Some(datetime.and_utc().to_rfc3339())
+}
+
+fn parse_resource(node: Node) -> Option<Resource> {
+    let encoded: String = child_text(node, "data")?.split_whitespace().collect();
+    let data = base64::engine::general_purpose::STANDARD.decode(encoded).ok()?;
+    Some(Resource {
+        hash: hex::encode(Md5::digest(&data)),
+        mime: child_text(node, "mime")?.trim().to_string(),
+        data,
+    })
+}
+
+fn parse_enex(xml: &str) -> Result<Vec<EnexNote>, String> {
+    let document =
+        Document::parse_with_options(xml, PARSING_OPTIONS).map_err(|e| format!("Not an ENEX file: {}", e))?;
+    if !document.root_element().has_tag_name("en-export") {
+        return Err("Not an ENEX file".to_string());
+    }
+    Ok(document
+        .root_element()
+        .children()
+        .filter(|node| node.has_tag_name("note"))
+        .map(|note| EnexNote {
+            title: child_text(note, "title").unwrap_or_default().trim().to_string(),
+            created_at: child_text(note, "created").and_then(parse_enex_date),
+            tags: note
+                .children()
+                .filter(|node| node.has_tag_name("tag"))
+                .filter_map(|node| node.text())
+                .map(str::to_string)
+                .collect(),
+            content: child_text(note, "content").unwrap_or_default().to_string(),
+            resources: note
+                .children()
+                .filter(|node| node.has_tag_name("resource"))
+                .filter_map(parse_resource)
+                .collect(),
+        })
+        .collect())
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+fn is_block(node: Node) -> bool {
+    matches!(
+        node.tag_name().name(),
+        "div" | "p" | "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "ul" | "ol" | "blockquote" | "pre" | "hr" | "table"
+    )
+}
+
+/// Appends the editor's markup for the children of `node`. `media` gives the
+/// `<img>` for an `<en-media>` hash, or `None` to drop it.
+fn push_children(html: &mut String, node: Node, media: &dyn Fn(&str) -> Option<String>) {
+    for child in node.children() {
+        push_node(html, child, media);
+    }
+}
+
+fn push_wrapped(html: &mut String, tag: &str, node: Node, media: &dyn Fn(&str) -> Option<String>) {
+    html.push_str(&format!("<{}>", tag));
+    push_children(html, node, media);
+    html.push_str(&format!("</{}>", tag));
+}
+
+fn push_node(html: &mut String, node: Node, media: &dyn Fn(&str) -> Option<String>) {
+    if node.is_text() {
+        html.push_str(&escape(node.text().unwrap_or_default()));
+        return;
+    }
+    if !node.is_element() {
+        return;
+    }
+    match node.tag_name().name() {
+        // Evernote writes a paragraph as a div, and nests them freely
+        "div" | "p" if node.children().any(is_block) => push_children(html, node, media),
+        "div" | "p" | "td" | "th" => push_wrapped(html, "p", node, media),
+        "h1" => push_wrapped(html, "h1", node, media),
+        "h2" | "h3" | "h4" | "h5" | "h6" => push_wrapped(html, "h2", node, media),
+        "b" | "strong" => push_wrapped(html, "strong", node, media),
+        "i" | "em" => push_wrapped(html, "em", node, media),
+        "s" | "strike" | "del" => push_wrapped(html, "s", node, media),
+        "code" => push_wrapped(html, "code", node, media),
+        "ul" | "ol" | "li" | "blockquote" | "pre" => push_wrapped(html, node.tag_name().name(), node, media),
+        "br" => html.push_str("<br>"),
+        "hr" => html.push_str("<hr>"),
+        "a" => match node.attribute("href") {
+            Some(href) => {
+                html.push_str(&format!("<a href=\"{}\">", escape(href).replace('"', "&quot;")));
+                push_children(html, node, media);
+                html.push_str("</a>");
+            }
+            None => push_children(html, node, media),
+        },
+        "en-todo" => html.push_str(if node.attribute("checked") == Some("true") { "☑ " } else { "☐ " }),
+        "en-media" => {
+            if let Some(img) = node.attribute("hash").and_then(media) {
+                html.push_str(&img);
+            }
+        }
+        "en-crypt" => html.push_str("[Encrypted text left out]"),
+        _ => push_children(html, node, media),
+    }
+}
+
+/// A note's ENML as the editor's HTML.
+fn enml_to_html(enml: &str, media: &dyn Fn(&str) -> Option<String>) -> Result<String, String> {
+    if enml.trim().is_empty() {
+        return Ok(String::new());
+    }
+    let document =
+        Document::parse_with_options(enml, PARSING_OPTIONS).map_err(|e| format!("Unreadable note: {}", e))?;
+    let mut html = String::new();
+    // Loose text and inline markup at the top level are gathered into paragraphs
+    let mut in_paragraph = false;
+    for child in document.root_element().children() {
+        if child.is_element() && is_block(child) {
+            if in_paragraph {
+                html.push_str("</p>");
+                in_paragraph = false;
+            }
+            push_node(&mut html, child, media);
+        } else if in_paragraph || !child.is_text() || !child.text().unwrap_or_default().trim().is_empty() {
+            if !in_paragraph {
+                html.push_str("<p>");
+                in_paragraph = true;
+            }
+            push_node(&mut html, child, media);
+        }
+    }
+    if in_paragraph {
+        html.push_str("</p>");
+    }
+    Ok(html)
+}
+
+/// Creates the entry for `note` and its image attachments, and returns its id
+/// and how many images it has. A note over `limits` is refused.
+fn import_note(conn: &rusqlite::Connection, note: &EnexNote, limits: &LimitSettings) -> Result<(i32, usize), String> {
+    let created_at = note.created_at.clone().unwrap_or_else(|| Utc::now().to_rfc3339());
+    let (title, body) = clean_entry(&note.title, &enml_to_html(&note.content, &|_| None)?, limits)?;
+    let id = insert_entry(conn, &title, &body, &created_at).map_err(|e| e.to_string())?;
+    replace_tags(conn, id, &imported_tags(&note.tags)).map_err(|e| e.to_string())?;
+
+    // Images need the entry to belong to, so they're put in once it exists
+    let mut images = HashMap::new();
+    for resource in note.resources.iter().filter(|resource| resource.mime.starts_with("image/")) {
+        if !images.contains_key(&resource.hash) {
+            let attachment = add_image(id, &resource.mime, &resource.data)?;
+            images.insert(resource.hash.clone(), attachment.token);
+        }
+    }
+    if !images.is_empty() {
+        let img = |hash: &str| images.get(hash).map(|token| format!("<img src=\"{}\">", token));
+        // Images have no words or links, so the columns derived from the body stay right
+        let body = clean_body(&enml_to_html(&note.content, &img)?, limits)?;
+        conn.execute("UPDATE journal_entries SET body = ?1 WHERE id = ?2", rusqlite::params![body, id])
+            .map_err(|e| e.to_string())?;
+    }
+    Ok((id, images.len()))
+}
+
+/// The entry `note` would become, without its images.
+fn previewed_entry(note: &EnexNote) -> Result<ImportedEntry, String> {
+    Ok(ImportedEntry {
+        title: note.title.clone(),
+        body: enml_to_html(&note.content, &|_| None)?,
+        created_at: note
+            .created_at
+            .as_deref()
+            .and_then(|created_at| parse_entry_datetime(created_at).ok())
+            .unwrap_or_else(Utc::now),
+        tags: note.tags.clone(),
+        notebook: None,
+    })
+}
+
+/// What importing `notes` would do: the preview, and the notes that would fail.
+fn preview_notes(conn: &rusqlite::Connection, notes: &[EnexNote]) -> Result<EnexImportReport, String> {
+    let mut report = EnexImportReport::default();
+    let mut entries = Vec::new();
+    for note in notes {
+        match previewed_entry(note) {
+            Ok(entry) => entries.push(entry),
+            Err(reason) => report.failed.push(SkippedFile {
+                name: note.title.clone(),
+                reason,
+            }),
+        }
+        let resources = note.resources.iter();
+        report.images += resources.clone().filter(|resource| resource.mime.starts_with("image/")).count();
+        report.skipped_resources += resources.filter(|resource| !resource.mime.starts_with("image/")).count();
+    }
+    report.preview = Some(preview_entries(conn, &entries)?);
+    Ok(report)
+}
+
+/// Imports every note in the Evernote export at `path` as an entry. With
+/// `dry_run`, only reports what importing them would do; otherwise the import
+/// runs as a job.
+#[command]
+pub fn import_enex(path: String, dry_run: Option<bool>) -> Result<EnexImportReport, String> {
+    let xml = fs::read_to_string(&path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let notes = parse_enex(&xml)?;
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    if dry_run.unwrap_or(false) {
+        return preview_notes(&db.conn, &notes);
+    }
+    let job = Job::start(JobKind::Import, format!("Importing {}", path));
+    let limits = Settings::load().limits;
+    let mut report = EnexImportReport::default();
+    for (i, note) in notes.iter().enumerate() {
+        if job.step(i, notes.len(), Some(&note.title)).is_err() {
+            break;
+        }
+        let other_files = note.resources.iter().filter(|resource| !resource.mime.starts_with("image/"));
+        report.skipped_resources += other_files.count();
+        match import_note(&db.conn, note, &limits) {
+            Ok((id, images)) => {
+                report.created.push(id);
+                report.images += images;
+            }
+            Err(reason) => report.failed.push(SkippedFile {
+                name: note.title.clone(),
+                reason,
+            }),
+        }
+    }
+    debug!("Imported {} of {} Evernote notes from {}", report.created.len(), notes.len(), path);
+    if !report.created.is_empty() {
+        git_sync::commit_on_save();
+        markdown_mirror::refresh();
+        recent_menu::refresh();
+    }
+    let result = if job.is_cancelled() { Err(CANCELLED.to_string()) } else { Ok(report) };
+    job.finish(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use journal_core::validation::TOO_LARGE;
+
+    const PIXEL: &[u8] = b"GIF89a\x01\x00\x01\x00\x00\x00\x00;";
+
+    fn enex() -> String {
+        let data = base64::engine::general_purpose::STANDARD.encode(PIXEL);
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE en-export SYSTEM "http://xml.evernote.com/pub/evernote-export3.dtd">
+<en-export export-date="20240310T120000Z" application="Evernote" version="10">
+  <note>
+    <title>Lisbon</title>
+    <created>20240305T120000Z</created>
+    <tag>travel</tag>
+    <tag>city break</tag>
+    <content><![CDATA[<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE en-note SYSTEM "http://xml.evernote.com/pub/enml2.dtd">
+<en-note><div>Trams &amp; <b>tiles</b></div><div><en-todo checked="true"/>pack</div><div><br/></div>
+<div><en-media type="image/gif" hash="{hash}"/></div>Loose <i>text</i></en-note>]]></content>
+    <resource>
+      <data encoding="base64">
+{data}
+      </data>
+      <mime>image/gif</mime>
+    </resource>
+    <resource>
+      <data encoding="base64">JVBERi0=</data>
+      <mime>application/pdf</mime>
+    </resource>
+  </note>
+  <note>
+    <title>Untimed</title>
+    <content><![CDATA[<en-note><div><div>Nested</div></div></en-note>]]></content>
+  </note>
+</en-export>"#,
+            hash = hex::encode(Md5::digest(PIXEL)),
+            data = data,
+        )
+    }
+
+    #[test]
+    fn test_parse_enex() {
+        let notes = parse_enex(&enex()).unwrap();
+        assert_eq!(notes.len(), 2);
+        let lisbon = &notes[0];
+        assert_eq!(lisbon.title, "Lisbon");
+        assert_eq!(lisbon.created_at.as_deref(), Some("2024-03-05T12:00:00+00:00"));
+        assert_eq!(lisbon.tags, ["travel", "city break"]);
+        assert_eq!(imported_tags(&lisbon.tags), ["travel", "city-break"]);
+        assert_eq!(lisbon.resources.len(), 2);
+        assert_eq!(lisbon.resources[0].data, PIXEL);
+        assert_eq!(notes[1].created_at, None);
+        assert!(parse_enex("<html></html>").is_err());
+    }
+
+    #[test]
+    fn test_enml_to_html() {
+        let notes = parse_enex(&enex()).unwrap();
+        let hash = hex::encode(Md5::digest(PIXEL));
+        let img = |h: &str| (h == hash).then(|| "<img src=\"attachment:7\">".to_string());
+        assert_eq!(
+            enml_to_html(&notes[0].content, &img).unwrap(),
+            "<p>Trams &amp; <strong>tiles</strong></p><p>☑ pack</p><p><br></p>\
+             <p><img src=\"attachment:7\"></p><p>Loose <em>text</em></p>"
+        );
+        assert!(enml_to_html(&notes[0].content, &|_| None).unwrap().contains("<p></p>"));
+        assert_eq!(enml_to_html(&notes[1].content, &|_| None).unwrap(), "<p>Nested</p>");
+    }
+
+    #[test]
+    fn test_preview_notes() {
+        let db = DatabaseManager::open_in_memory().unwrap();
+        insert_entry(&db.conn, "Lisbon", "<p>Earlier</p>", "2024-03-05T12:00:00+00:00").unwrap();
+        let report = preview_notes(&db.conn, &parse_enex(&enex()).unwrap()).unwrap();
+        assert!(report.created.is_empty());
+        assert_eq!((report.images, report.skipped_resources), (1, 1));
+        let preview = report.preview.unwrap();
+        assert_eq!(preview.entries, 2);
+        assert_eq!(preview.duplicates.len(), 1);
+        assert_eq!(preview.duplicates[0].title, "Lisbon");
+        assert_eq!(preview.tags, ["travel", "city-break"]);
+    }
+
+    #[test]
+    fn test_import_note_limits() {
+        let db = DatabaseManager::open_in_memory().unwrap();
+        let limits = LimitSettings { max_body_mb: 1, ..Default::default() };
+        let mut note = parse_enex(&enex()).unwrap().remove(1);
+        note.title = "Unti\u{7}med".to_string();
+        let (id, _) = import_note(&db.conn, &note, &limits).unwrap();
+        let title: String =
+            db.conn.query_row("SELECT title FROM journal_entries WHERE id = ?1", [id], |row| row.get(0)).unwrap();
+        assert_eq!(title, "Untimed");
+
+        note.content = format!("<en-note><div>{}</div></en-note>", "word ".repeat(250_000));
+        assert!(import_note(&db.conn, &note, &limits).unwrap_err().starts_with(TOO_LARGE));
+        let count: i64 = db.conn.query_row("SELECT COUNT(*) FROM journal_entries", [], |row| row.get(0)).unwrap();
+        assert_eq!(count, 1);
+    }
+}
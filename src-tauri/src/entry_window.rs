@@ -0,0 +1,56 @@
+use tauri::{command, AppHandle, Manager, WebviewUrl, WebviewWindowBuilder};
+
+use journal_core::keychain::KeychainManager;
+
+use crate::{privacy, DatabaseManager};
+
+// Entries opened in windows of their own, so an old entry can stay on screen
+// while a new one is written in the main window. Each is the same frontend
+// loaded at `#entry/{id}`, which shows only that entry's editor. Commands open
+// their own connection, and SQLite's busy timeout serializes the saves.
+
+const LABEL_PREFIX: &str = "entry-";
+const WINDOW_SIZE: (f64, f64) = (720.0, 820.0);
+
+fn window_label(id: i32) -> String {
+    format!("{}{}", LABEL_PREFIX, id)
+}
+
+/// Opens entry `id` in its own window, or brings its window forward if it's
+/// already open. Async because creating a window from a synchronous command
+/// deadlocks on Windows.
+#[command]
+pub async fn open_entry_window(app: AppHandle, id: i32) -> Result<(), String> {
+    if !KeychainManager::has_cached_key() {
+        return Err("Unlock the journal before opening entries".to_string());
+    }
+    if let Some(window) = app.get_webview_window(&window_label(id)) {
+        window.unminimize().map_err(|e| e.to_string())?;
+        window.show().map_err(|e| e.to_string())?;
+        return window.set_focus().map_err(|e| e.to_string());
+    }
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    let title: String = db
+        .conn
+        .query_row(
+            "SELECT title FROM journal_entries WHERE id = ?1",
+            rusqlite::params![id],
+            |row| row.get(0),
+        )
+        .map_err(|_| format!("Entry {} not found", id))?;
+    let url = WebviewUrl::App(format!("index.html#entry/{}", id).into());
+    WebviewWindowBuilder::new(&app, window_label(id), url)
+        .title(if title.trim().is_empty() { "Untitled" } else { title.trim() })
+        .inner_size(WINDOW_SIZE.0, WINDOW_SIZE.1)
+        .content_protected(privacy::content_protected())
+        .build()
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Closes entry `id`'s window, if it has one, once the entry is gone.
+pub fn close(app: &AppHandle, id: i32) {
+    if let Some(window) = app.get_webview_window(&window_label(id)) {
+        let _ = window.close();
+    }
+}
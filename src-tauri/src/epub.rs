@@ -0,0 +1,241 @@
+use chrono::{Datelike, NaiveDate, Utc};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::Write;
+use tauri::command;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use journal_core::audit::AuditAction;
+
+use crate::export::{exported_entries, html_to_markdown, ExportedEntry};
+use crate::file_drop::markdown_to_html;
+use crate::security;
+use crate::stats::local_date;
+use crate::DatabaseManager;
+
+// A year of the journal as an EPUB 3 book, for reading on an e-reader or
+// sending to a print-on-demand service: a chapter per month, each entry a
+// section in it, and a table of contents listing both. Bodies go through
+// Markdown and back so what lands in the book is well-formed XHTML, which the
+// editor's HTML isn't always. Locked entries are left out, as in the other
+// exports.
+
+const CONTAINER: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles>
+    <rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/>
+  </rootfiles>
+</container>
+"#;
+const STYLESHEET: &str = "body { font-family: serif; line-height: 1.5; }
+h1 { text-align: center; margin: 2em 0; }
+h2 { margin-top: 2em; }
+p.date { font-style: italic; color: #555; }
+";
+
+/// The entries of one month, oldest first.
+struct Chapter<'a> {
+    month: NaiveDate,
+    entries: Vec<&'a ExportedEntry>,
+}
+
+impl Chapter<'_> {
+    fn file(&self) -> String {
+        format!("{}.xhtml", self.month.format("%m"))
+    }
+
+    fn title(&self) -> String {
+        self.month.format("%B").to_string()
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn xhtml(title: &str, body: &str) -> String {
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>
+<!DOCTYPE html>
+<html xmlns=\"http://www.w3.org/1999/xhtml\" xmlns:epub=\"http://www.idpf.org/2007/ops\">
+<head>
+<title>{}</title>
+<link rel=\"stylesheet\" type=\"text/css\" href=\"style.css\"/>
+</head>
+<body>
+{}
+</body>
+</html>
+",
+        escape(title),
+        body
+    )
+}
+
+fn chapter_xhtml(chapter: &Chapter) -> String {
+    let mut body = format!("<h1>{}</h1>\n", escape(&chapter.title()));
+    for (i, entry) in chapter.entries.iter().enumerate() {
+        body.push_str(&format!(
+            "<section id=\"entry-{}\">\n<h2>{}</h2>\n<p class=\"date\">{}</p>\n{}</section>\n",
+            i + 1,
+            escape(&entry.heading()),
+            escape(&entry.day()),
+            markdown_to_html(&html_to_markdown(&entry.body))
+        ));
+    }
+    xhtml(&chapter.title(), &body)
+}
+
+/// The EPUB 3 table of contents: chapters, each listing its entries.
+fn nav_xhtml(title: &str, chapters: &[Chapter]) -> String {
+    let mut body = String::from("<nav epub:type=\"toc\" id=\"toc\">\n<h1>Contents</h1>\n<ol>\n");
+    for chapter in chapters {
+        body.push_str(&format!("<li><a href=\"{}\">{}</a>\n<ol>\n", chapter.file(), escape(&chapter.title())));
+        for (i, entry) in chapter.entries.iter().enumerate() {
+            body.push_str(&format!(
+                "<li><a href=\"{}#entry-{}\">{}</a></li>\n",
+                chapter.file(),
+                i + 1,
+                escape(&entry.heading())
+            ));
+        }
+        body.push_str("</ol>\n</li>\n");
+    }
+    body.push_str("</ol>\n</nav>");
+    xhtml(title, &body)
+}
+
+fn package_opf(title: &str, identifier: &str, chapters: &[Chapter]) -> String {
+    let mut manifest = String::from(
+        "<item id=\"nav\" href=\"nav.xhtml\" media-type=\"application/xhtml+xml\" properties=\"nav\"/>
+    <item id=\"style\" href=\"style.css\" media-type=\"text/css\"/>\n",
+    );
+    let mut spine = String::new();
+    for (i, chapter) in chapters.iter().enumerate() {
+        manifest.push_str(&format!(
+            "    <item id=\"chapter-{}\" href=\"{}\" media-type=\"application/xhtml+xml\"/>\n",
+            i + 1,
+            chapter.file()
+        ));
+        spine.push_str(&format!("    <itemref idref=\"chapter-{}\"/>\n", i + 1));
+    }
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="book-id">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+    <dc:identifier id="book-id">urn:uuid:{identifier}</dc:identifier>
+    <dc:title>{title}</dc:title>
+    <dc:language>en</dc:language>
+    <meta property="dcterms:modified">{modified}</meta>
+  </metadata>
+  <manifest>
+    {manifest}  </manifest>
+  <spine>
+{spine}  </spine>
+</package>
+"#,
+        title = escape(title),
+        modified = Utc::now().format("%Y-%m-%dT%H:%M:%SZ"),
+    )
+}
+
+/// The readable entries written in `year`, local time, by month.
+fn chapters(entries: &[ExportedEntry], year: i32) -> Vec<Chapter<'_>> {
+    let mut months: BTreeMap<u32, Vec<&ExportedEntry>> = BTreeMap::new();
+    for entry in entries {
+        if let Some(date) = local_date(&entry.created_at).filter(|date| date.year() == year) {
+            months.entry(date.month()).or_default().push(entry);
+        }
+    }
+    months
+        .into_iter()
+        .filter_map(|(month, entries)| Some(Chapter { month: NaiveDate::from_ymd_opt(year, month, 1)?, entries }))
+        .collect()
+}
+
+/// Writes the entries of `year` to `path` as an EPUB and returns how many
+/// went in.
+pub fn export_year_epub(conn: &rusqlite::Connection, year: i32, path: &str) -> Result<usize, String> {
+    let entries = exported_entries(conn).map_err(|e| e.to_string())?;
+    let chapters = chapters(&entries, year);
+    if chapters.is_empty() {
+        return Err(format!("There are no entries from {} to export", year));
+    }
+    let title = format!("Journal {}", year);
+    let identifier = uuid::Uuid::new_v4().to_string();
+
+    let file = File::create(path).map_err(|e| format!("Failed to write {}: {}", path, e))?;
+    let mut zip = ZipWriter::new(file);
+    // Readers find the book by `mimetype` being the first file, uncompressed
+    let stored = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+    let deflated = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    let mut files = vec![
+        ("META-INF/container.xml".to_string(), CONTAINER.to_string()),
+        ("OEBPS/content.opf".to_string(), package_opf(&title, &identifier, &chapters)),
+        ("OEBPS/nav.xhtml".to_string(), nav_xhtml(&title, &chapters)),
+        ("OEBPS/style.css".to_string(), STYLESHEET.to_string()),
+    ];
+    files.extend(chapters.iter().map(|chapter| (format!("OEBPS/{}", chapter.file()), chapter_xhtml(chapter))));
+    zip.start_file("mimetype", stored).map_err(|e| e.to_string())?;
+    zip.write_all(b"application/epub+zip").map_err(|e| e.to_string())?;
+    for (name, contents) in files {
+        zip.start_file(name, deflated).map_err(|e| e.to_string())?;
+        zip.write_all(contents.as_bytes()).map_err(|e| e.to_string())?;
+    }
+    zip.finish().map_err(|e| e.to_string())?;
+    Ok(chapters.iter().map(|chapter| chapter.entries.len()).sum())
+}
+
+/// Writes the entries of `year` to `path` as an EPUB book with a chapter per
+/// month, and returns how many went in.
+#[command]
+pub fn export_epub(year: i32, path: String) -> Result<usize, String> {
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    let count = export_year_epub(&db.conn, year, &path)?;
+    security::audit(AuditAction::Export, &format!("{} entries from {} as EPUB to {}", count, year, path));
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use journal_core::insert_entry;
+    use std::io::Read;
+
+    #[test]
+    fn test_export_year_epub() {
+        let db = DatabaseManager::open_in_memory().unwrap();
+        insert_entry(&db.conn, "Lisbon", "<p>Trams &amp; tiles<br>and rain</p>", "2024-03-05T12:00:00+00:00")
+            .unwrap();
+        insert_entry(&db.conn, "", "<p>Home</p>", "2024-03-09T12:00:00+00:00").unwrap();
+        insert_entry(&db.conn, "Spring", "<p>Blossom</p>", "2024-04-02T12:00:00+00:00").unwrap();
+        insert_entry(&db.conn, "New year", "<p>Later</p>", "2025-01-01T12:00:00+00:00").unwrap();
+
+        let path = std::env::temp_dir().join(format!("journal-{}.epub", std::process::id()));
+        let path = path.to_str().unwrap();
+        assert_eq!(export_year_epub(&db.conn, 2024, path).unwrap(), 3);
+        assert!(export_year_epub(&db.conn, 2020, path).is_err());
+
+        let mut archive = zip::ZipArchive::new(File::open(path).unwrap()).unwrap();
+        assert_eq!(archive.by_index(0).unwrap().name(), "mimetype");
+        let mut read = |name: &str| {
+            let mut contents = String::new();
+            archive.by_name(name).unwrap().read_to_string(&mut contents).unwrap();
+            contents
+        };
+        assert_eq!(read("mimetype"), "application/epub+zip");
+        let march = read("OEBPS/03.xhtml");
+        assert!(march.contains("<h1>March</h1>"));
+        assert!(march.contains("<h2>Lisbon</h2>"));
+        assert!(march.contains("Trams &amp; tiles<br />"));
+        let nav = read("OEBPS/nav.xhtml");
+        assert!(nav.contains("<a href=\"03.xhtml#entry-2\">"));
+        assert!(nav.contains("<a href=\"04.xhtml\">April</a>"));
+        assert!(!read("OEBPS/content.opf").contains("01.xhtml"));
+        std::fs::remove_file(path).unwrap();
+    }
+}
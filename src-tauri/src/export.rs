@@ -0,0 +1,621 @@
+use printpdf::{BuiltinFont, IndirectFontRef, Mm, PdfDocument, PdfDocumentReference, PdfLayerReference};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use tauri::command;
+
+use journal_core::audit::AuditAction;
+use journal_core::text::strip_html;
+use journal_core::timestamp_now;
+
+use crate::jobs::{Job, JobKind};
+use crate::mood::Mood;
+use crate::security;
+use crate::stats::{local_date, BLOCK_ENDS};
+use crate::tags::tags_for_entry;
+use crate::DatabaseManager;
+
+// The whole journal in formats other apps can read: a single Markdown
+// document, JSON for scripts, or a PDF to print. Locked entries are left out,
+// since their text can't be read without unlocking each one. A CSV of entry
+// metadata, for spreadsheets, lists them too but never with their text. JSON
+// exports say which version of the format they're in, so the app can import
+// them back and refuse ones from a newer version it might misread.
+
+/// A4, in millimetres.
+const PAGE_SIZE: (f32, f32) = (210.0, 297.0);
+const PAGE_MARGIN: f32 = 20.0;
+const MM_PER_POINT: f32 = 0.3528;
+/// Helvetica's glyphs average about half as wide as the font is tall.
+const CHAR_WIDTH_RATIO: f32 = 0.5;
+const LINE_SPACING: f32 = 1.4;
+const TITLE_SIZE: f32 = 16.0;
+const DATE_SIZE: f32 = 9.0;
+const BODY_SIZE: f32 = 11.0;
+
+/// What a JSON export says it is, so an import can tell it from other JSON.
+pub(crate) const JSON_EXPORT_FORMAT: &str = "journal";
+/// Raised whenever JSON exports change in a way older versions can't read.
+/// Exports from before there was a header are a bare list of entries.
+pub(crate) const JSON_EXPORT_VERSION: u32 = 1;
+/// How the error starts when a file was made by a newer version of the app.
+pub(crate) const NEWER_VERSION: &str = "newer_version";
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Markdown,
+    Json,
+    Pdf,
+}
+
+impl ExportFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Markdown => "md",
+            ExportFormat::Json => "json",
+            ExportFormat::Pdf => "pdf",
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ExportFormat::Markdown => "Markdown",
+            ExportFormat::Json => "JSON",
+            ExportFormat::Pdf => "PDF",
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct ExportedEntry {
+    pub(crate) uuid: Option<String>,
+    pub(crate) title: String,
+    pub(crate) created_at: String,
+    pub(crate) tags: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) mood: Option<Mood>,
+    /// The editor's HTML.
+    pub(crate) body: String,
+}
+
+impl ExportedEntry {
+    pub(crate) fn day(&self) -> String {
+        local_date(&self.created_at)
+            .map(|day| day.format("%A, %B %-d, %Y").to_string())
+            .unwrap_or_default()
+    }
+
+    /// The title, or the day for an untitled entry.
+    pub(crate) fn heading(&self) -> String {
+        match self.title.trim() {
+            "" => self.day(),
+            title => title.to_string(),
+        }
+    }
+}
+
+/// A JSON export: the entries, under a header saying what wrote them.
+#[derive(Serialize)]
+struct JsonExport<'a> {
+    format: &'static str,
+    format_version: u32,
+    /// The version of the app that made it.
+    app_version: &'static str,
+    exported_at: String,
+    entries: &'a [ExportedEntry],
+}
+
+/// The error for a `what` made by a newer version of the app than this one,
+/// which can't be sure of reading it.
+pub(crate) fn newer_version_error(what: &str, made_by: Option<&str>, action: &str) -> String {
+    let made_by = made_by.map(|version| format!(" ({})", version)).unwrap_or_default();
+    format!(
+        "{}: This {} was made by a newer version of Journal{}. Update the app to {} it.",
+        NEWER_VERSION, what, made_by, action
+    )
+}
+
+pub(crate) fn exported_entries(conn: &rusqlite::Connection) -> rusqlite::Result<Vec<ExportedEntry>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, uuid, title, created_at, body, mood_score, mood_emoji FROM journal_entries
+         WHERE locked = 0 ORDER BY created_ms, id",
+    )?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, i32>(0)?,
+                ExportedEntry {
+                    uuid: row.get(1)?,
+                    title: row.get(2)?,
+                    created_at: row.get(3)?,
+                    tags: Vec::new(),
+                    mood: Mood::from_columns(row.get(5)?, row.get(6)?),
+                    body: row.get(4)?,
+                },
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    rows.into_iter()
+        .map(|(id, entry)| Ok(ExportedEntry { tags: tags_for_entry(conn, id)?, ..entry }))
+        .collect()
+}
+
+/// Value of attribute `name` inside a tag such as `a href="..."`.
+fn attribute(tag: &str, name: &str) -> Option<String> {
+    let start = tag.find(&format!(" {}=\"", name))? + name.len() + 3;
+    let len = tag[start..].find('"')?;
+    Some(strip_html(&tag[start..start + len]))
+}
+
+/// Ends the current line and starts the next inside any quotes, after a
+/// blank line if `blank`.
+fn start_line(md: &mut String, quote_depth: usize, blank: bool) {
+    md.truncate(md.trim_end_matches(' ').len());
+    if !md.is_empty() {
+        if !md.ends_with('\n') {
+            md.push('\n');
+        }
+        let last_line = md[..md.len() - 1].rsplit('\n').next().unwrap_or_default();
+        if blank && !last_line.chars().all(|c| c == '>') {
+            // Only quotes that go on past the blank line continue through it
+            let last_depth = last_line.chars().take_while(|&c| c == '>' || c == ' ').filter(|&c| c == '>').count();
+            md.push_str(&">".repeat(quote_depth.min(last_depth)));
+            md.push('\n');
+        }
+    }
+    md.push_str(&"> ".repeat(quote_depth));
+}
+
+/// The editor's HTML as Markdown. Covers the markup the editor writes;
+/// other tags are dropped and their text kept.
+pub(crate) fn html_to_markdown(html: &str) -> String {
+    let mut md = String::new();
+    let mut quote_depth = 0;
+    // `None` for a bulleted list, or the next number of a numbered one
+    let mut lists: Vec<Option<u32>> = Vec::new();
+    let mut links: Vec<String> = Vec::new();
+    let mut list_opened = false;
+    let mut item_started = false;
+    let mut in_pre = false;
+    let mut rest = html;
+    while !rest.is_empty() {
+        let start = rest.find('<').unwrap_or(rest.len());
+        let text = strip_html(&rest[..start]);
+        if in_pre {
+            md.push_str(&text);
+        } else {
+            for c in text.chars() {
+                if !c.is_whitespace() {
+                    md.push(c);
+                    item_started = false;
+                } else if !md.is_empty() && !md.ends_with([' ', '\n']) {
+                    md.push(' ');
+                }
+            }
+        }
+        let Some(len) = rest[start..].find('>') else {
+            break;
+        };
+        let tag = &rest[start + 1..start + len];
+        rest = &rest[start + len + 1..];
+        let closing = tag.starts_with('/');
+        let name = tag
+            .trim_start_matches('/')
+            .split(|c: char| c.is_whitespace() || c == '/')
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        match (name.as_str(), closing) {
+            ("p" | "div", false) if item_started => item_started = false,
+            ("p" | "div", false) => {
+                start_line(&mut md, quote_depth, lists.is_empty());
+                md.push_str(&"  ".repeat(lists.len()));
+            }
+            ("h1" | "h2" | "h3" | "h4" | "h5" | "h6", false) => {
+                start_line(&mut md, quote_depth, true);
+                md.push_str(&"#".repeat(name[1..].parse().unwrap_or(1)));
+                md.push(' ');
+            }
+            ("br", _) => {
+                md.push('\\');
+                start_line(&mut md, quote_depth, false);
+            }
+            ("hr", _) => {
+                start_line(&mut md, quote_depth, true);
+                md.push_str("---");
+            }
+            ("blockquote", false) => quote_depth += 1,
+            ("blockquote", true) => quote_depth = quote_depth.saturating_sub(1),
+            ("ul", false) | ("ol", false) => {
+                let start = attribute(tag, "start").and_then(|n| n.parse().ok()).unwrap_or(1);
+                lists.push((name == "ol").then_some(start));
+                list_opened = true;
+            }
+            ("ul", true) | ("ol", true) => {
+                lists.pop();
+            }
+            ("li", false) => {
+                start_line(&mut md, quote_depth, list_opened && lists.len() == 1);
+                list_opened = false;
+                md.push_str(&"  ".repeat(lists.len().saturating_sub(1)));
+                match lists.last_mut() {
+                    Some(Some(number)) => {
+                        md.push_str(&format!("{}. ", number));
+                        *number += 1;
+                    }
+                    _ => md.push_str("- "),
+                }
+                match attribute(tag, "data-checked").as_deref() {
+                    Some("true") => md.push_str("[x] "),
+                    Some(_) => md.push_str("[ ] "),
+                    None => {}
+                }
+                item_started = true;
+            }
+            ("strong" | "b", _) => md.push_str("**"),
+            ("em" | "i", _) => md.push('*'),
+            ("s" | "del" | "strike", _) => md.push_str("~~"),
+            ("code", _) if !in_pre => md.push('`'),
+            ("pre", false) => {
+                start_line(&mut md, quote_depth, true);
+                md.push_str("```\n");
+                in_pre = true;
+            }
+            ("pre", true) => {
+                in_pre = false;
+                start_line(&mut md, quote_depth, false);
+                md.push_str("```");
+            }
+            ("a", false) => {
+                md.push('[');
+                links.push(attribute(tag, "href").unwrap_or_default());
+            }
+            ("a", true) => md.push_str(&format!("]({})", links.pop().unwrap_or_default())),
+            ("img", _) => md.push_str(&format!(
+                "![{}]({})",
+                attribute(tag, "alt").unwrap_or_default(),
+                attribute(tag, "src").unwrap_or_default()
+            )),
+            _ => {}
+        }
+    }
+    md.trim_end().to_string()
+}
+
+fn to_markdown(entries: &[ExportedEntry]) -> String {
+    let mut md = String::from("# Journal\n");
+    for entry in entries {
+        md.push_str(&format!("\n## {}\n\n*{}*", entry.heading(), entry.day()));
+        if !entry.tags.is_empty() {
+            let tags: Vec<String> = entry.tags.iter().map(|tag| format!("#{}", tag)).collect();
+            md.push_str(&format!(" · {}", tags.join(" ")));
+        }
+        md.push_str("\n\n");
+        md.push_str(&html_to_markdown(&entry.body));
+        md.push('\n');
+    }
+    md
+}
+
+/// Paragraphs of an HTML body as plain text, list items bulleted.
+pub(crate) fn paragraphs(html: &str) -> Vec<String> {
+    let mut blocks = html.replace("<li>", "<li>• ");
+    for end in BLOCK_ENDS {
+        blocks = blocks.replace(end, "\n");
+    }
+    strip_html(&blocks)
+        .lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|line| !line.is_empty())
+        .collect()
+}
+
+/// Greedy word wrap to lines of at most `width` characters. Longer words
+/// are broken.
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        let mut word: Vec<char> = word.chars().collect();
+        while word.len() > width {
+            if !line.is_empty() {
+                lines.push(std::mem::take(&mut line));
+            }
+            lines.push(word.drain(..width).collect());
+        }
+        let word: String = word.into_iter().collect();
+        if !line.is_empty() && line.chars().count() + 1 + word.chars().count() > width {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(&word);
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+/// Lays text out top to bottom, starting a new page when one fills up. The
+/// built-in Helvetica has no glyph widths to measure with, so lines are
+/// wrapped by an estimated character count.
+struct PdfWriter {
+    doc: PdfDocumentReference,
+    layer: PdfLayerReference,
+    regular: IndirectFontRef,
+    bold: IndirectFontRef,
+    y: f32,
+}
+
+impl PdfWriter {
+    fn new(title: &str) -> Result<Self, String> {
+        let (doc, page, layer) = PdfDocument::new(title, Mm(PAGE_SIZE.0), Mm(PAGE_SIZE.1), "Text");
+        let regular = doc.add_builtin_font(BuiltinFont::Helvetica).map_err(|e| e.to_string())?;
+        let bold = doc.add_builtin_font(BuiltinFont::HelveticaBold).map_err(|e| e.to_string())?;
+        let layer = doc.get_page(page).get_layer(layer);
+        Ok(PdfWriter { doc, layer, regular, bold, y: PAGE_SIZE.1 - PAGE_MARGIN })
+    }
+
+    fn new_page(&mut self) {
+        let (page, layer) = self.doc.add_page(Mm(PAGE_SIZE.0), Mm(PAGE_SIZE.1), "Text");
+        self.layer = self.doc.get_page(page).get_layer(layer);
+        self.y = PAGE_SIZE.1 - PAGE_MARGIN;
+    }
+
+    fn paragraph(&mut self, text: &str, size: f32, bold: bool) {
+        let width = ((PAGE_SIZE.0 - 2.0 * PAGE_MARGIN) / (size * MM_PER_POINT * CHAR_WIDTH_RATIO)) as usize;
+        let height = size * MM_PER_POINT * LINE_SPACING;
+        for line in wrap(text, width) {
+            if self.y - height < PAGE_MARGIN {
+                self.new_page();
+            }
+            self.y -= height;
+            let font = if bold { &self.bold } else { &self.regular };
+            self.layer.use_text(line, size, Mm(PAGE_MARGIN), Mm(self.y), font);
+        }
+    }
+
+    fn space(&mut self, mm: f32) {
+        self.y -= mm;
+    }
+
+    fn finish(self) -> Result<Vec<u8>, String> {
+        self.doc.save_to_bytes().map_err(|e| e.to_string())
+    }
+}
+
+fn to_pdf(entries: &[ExportedEntry], job: &Job) -> Result<Vec<u8>, String> {
+    let mut pdf = PdfWriter::new("Journal")?;
+    for (i, entry) in entries.iter().enumerate() {
+        job.step(i, entries.len(), Some(&entry.heading()))?;
+        // Each entry starts on a fresh page
+        if i > 0 {
+            pdf.new_page();
+        }
+        pdf.paragraph(&entry.heading(), TITLE_SIZE, true);
+        pdf.paragraph(&entry.day(), DATE_SIZE, false);
+        pdf.space(BODY_SIZE * MM_PER_POINT);
+        for paragraph in paragraphs(&entry.body) {
+            pdf.paragraph(&paragraph, BODY_SIZE, false);
+            pdf.space(BODY_SIZE * MM_PER_POINT * 0.5);
+        }
+    }
+    pdf.finish()
+}
+
+/// Writes every readable entry to `path` as `format`, oldest first, and
+/// returns how many were written. A PDF reports its progress through `job`.
+pub fn export_to(
+    conn: &rusqlite::Connection,
+    format: ExportFormat,
+    path: &str,
+    job: &Job,
+) -> Result<usize, String> {
+    let entries = exported_entries(conn).map_err(|e| e.to_string())?;
+    let contents = match format {
+        ExportFormat::Markdown => to_markdown(&entries).into_bytes(),
+        ExportFormat::Json => serde_json::to_vec_pretty(&JsonExport {
+            format: JSON_EXPORT_FORMAT,
+            format_version: JSON_EXPORT_VERSION,
+            app_version: env!("CARGO_PKG_VERSION"),
+            exported_at: timestamp_now(),
+            entries: &entries,
+        })
+        .map_err(|e| e.to_string())?,
+        ExportFormat::Pdf => to_pdf(&entries, job)?,
+    };
+    fs::write(path, contents).map_err(|e| format!("Failed to write {}: {}", path, e))?;
+    Ok(entries.len())
+}
+
+/// Runs in the background as a job.
+#[command]
+pub async fn export_entries(format: ExportFormat, path: String) -> Result<usize, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+        let label = format!("Exporting {} to {}", format.name(), path);
+        let count = Job::run(JobKind::Export, label, |job| export_to(&db.conn, format, &path, job))?;
+        security::audit(AuditAction::Export, &format!("{} entries as {} to {}", count, format.name(), path));
+        Ok(count)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// `text` as a CSV field, quoted when it has to be.
+fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+/// A row per entry, oldest first: id, local date, title, word count, tags and
+/// mood, and with `include_bodies` the text as Markdown.
+fn to_csv(conn: &rusqlite::Connection, include_bodies: bool) -> rusqlite::Result<(String, usize)> {
+    let mut stmt = conn.prepare(
+        "SELECT id, created_at, title, word_count, mood_score, mood_emoji, locked, body FROM journal_entries
+         ORDER BY created_ms, id",
+    )?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, i32>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, i64>(3)?,
+                row.get::<_, Option<i64>>(4)?,
+                row.get::<_, Option<String>>(5)?,
+                row.get::<_, bool>(6)?,
+                row.get::<_, String>(7)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut csv = String::from("id,date,title,word_count,tags,mood,mood_emoji");
+    if include_bodies {
+        csv.push_str(",body");
+    }
+    csv.push_str("\r\n");
+    for (id, created_at, title, word_count, mood_score, mood_emoji, locked, body) in &rows {
+        let date = local_date(created_at).map(|date| date.to_string()).unwrap_or_default();
+        let mut fields = vec![
+            id.to_string(),
+            date,
+            csv_field(title),
+            word_count.to_string(),
+            csv_field(&tags_for_entry(conn, *id)?.join(" ")),
+            mood_score.map(|score| score.to_string()).unwrap_or_default(),
+            csv_field(mood_emoji.as_deref().unwrap_or_default()),
+        ];
+        if include_bodies {
+            fields.push(if *locked { String::new() } else { csv_field(&html_to_markdown(body)) });
+        }
+        csv.push_str(&fields.join(","));
+        csv.push_str("\r\n");
+    }
+    Ok((csv, rows.len()))
+}
+
+/// Writes the CSV of entry metadata to `path` and returns how many entries it lists.
+pub fn export_csv_to(conn: &rusqlite::Connection, path: &str, include_bodies: bool) -> Result<usize, String> {
+    let (csv, count) = to_csv(conn, include_bodies).map_err(|e| e.to_string())?;
+    fs::write(path, csv).map_err(|e| format!("Failed to write {}: {}", path, e))?;
+    Ok(count)
+}
+
+/// Writes id, date, title, word count, tags and mood of every entry to `path`
+/// as CSV. Bodies are left out unless `include_bodies` is set.
+#[command]
+pub fn export_csv(path: String, include_bodies: Option<bool>) -> Result<usize, String> {
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    let include_bodies = include_bodies.unwrap_or(false);
+    let count = export_csv_to(&db.conn, &path, include_bodies)?;
+    let what = if include_bodies { "entries with text" } else { "entries' metadata" };
+    security::audit(AuditAction::Export, &format!("{} {} as CSV to {}", count, what, path));
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use journal_core::insert_entry;
+
+    #[test]
+    fn test_html_to_markdown() {
+        let html = "<h2>Trip</h2><p>We <strong>walked</strong> &amp; <em>talked</em>.<br>Then home.</p>\
+                    <ul><li><p>bread</p></li><li>eggs<ul><li>brown</li></ul></li></ul><ol><li>one</li><li>two</li></ol>\
+                    <blockquote><p>Be kind</p><p>Always</p></blockquote><p><a href=\"https://example.org\">site</a></p>";
+        assert_eq!(
+            html_to_markdown(html),
+            "## Trip\n\nWe **walked** & *talked*.\\\nThen home.\n\n- bread\n- eggs\n  - brown\n\n1. one\n2. two\n\n\
+             > Be kind\n>\n> Always\n\n[site](https://example.org)"
+        );
+        assert_eq!(
+            html_to_markdown("<ul data-type=\"taskList\"><li data-checked=\"true\"><p>call</p></li></ul>"),
+            "- [x] call"
+        );
+    }
+
+    #[test]
+    fn test_wrap() {
+        assert_eq!(wrap("the quick brown fox", 9), vec!["the quick", "brown fox"]);
+        assert_eq!(wrap("abcdefghij k", 4), vec!["abcd", "efgh", "ij k"]);
+        assert!(wrap("   ", 10).is_empty());
+    }
+
+    #[test]
+    fn test_paragraphs() {
+        assert_eq!(
+            paragraphs("<p>First  line</p><ul><li>milk</li><li>eggs</li></ul><p></p>"),
+            vec!["First line", "• milk", "• eggs"]
+        );
+    }
+
+    #[test]
+    fn test_export_formats() {
+        let db = DatabaseManager::open_in_memory().unwrap();
+        let id = insert_entry(&db.conn, "Lisbon", "<p>Trams and <em>tiles</em></p>", "2024-03-05T12:00:00+00:00")
+            .unwrap();
+        insert_entry(&db.conn, "Secret", "", "2024-03-06T12:00:00+00:00").unwrap();
+        db.conn
+            .execute("UPDATE journal_entries SET locked = 1 WHERE title = 'Secret'", [])
+            .unwrap();
+        db.conn
+            .execute("INSERT INTO entry_tags (entry_id, tag) VALUES (?1, 'travel')", rusqlite::params![id])
+            .unwrap();
+
+        let entries = exported_entries(&db.conn).unwrap();
+        assert_eq!(entries.len(), 1);
+        let md = to_markdown(&entries);
+        assert!(md.starts_with("# Journal\n\n## Lisbon\n\n*"));
+        assert!(md.contains(" · #travel\n\nTrams and *tiles*\n"));
+
+        let dir = std::env::temp_dir().join(format!("journal-export-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let job = Job::start(JobKind::Export, "Test");
+        let json = dir.join("journal.json");
+        assert_eq!(export_to(&db.conn, ExportFormat::Json, json.to_str().unwrap(), &job).unwrap(), 1);
+        let value: serde_json::Value = serde_json::from_slice(&fs::read(&json).unwrap()).unwrap();
+        assert_eq!((value["format"].as_str(), value["format_version"].as_u64()), (Some("journal"), Some(1)));
+        assert_eq!(value["entries"][0]["tags"][0], "travel");
+        let pdf = dir.join("journal.pdf");
+        export_to(&db.conn, ExportFormat::Pdf, pdf.to_str().unwrap(), &job).unwrap();
+        assert!(fs::read(&pdf).unwrap().starts_with(b"%PDF"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_csv() {
+        let db = DatabaseManager::open_in_memory().unwrap();
+        let id = insert_entry(&db.conn, "Lisbon, day 1", "<p>Trams</p>", "2024-03-05T12:00:00+00:00").unwrap();
+        insert_entry(&db.conn, "Secret", "<p>hidden</p>", "2024-03-06T12:00:00+00:00").unwrap();
+        db.conn
+            .execute("UPDATE journal_entries SET locked = 1 WHERE title = 'Secret'", [])
+            .unwrap();
+        db.conn
+            .execute("UPDATE journal_entries SET mood_score = 4 WHERE id = ?1", rusqlite::params![id])
+            .unwrap();
+        for tag in ["travel", "city"] {
+            db.conn
+                .execute("INSERT INTO entry_tags (entry_id, tag) VALUES (?1, ?2)", rusqlite::params![id, tag])
+                .unwrap();
+        }
+
+        let (csv, count) = to_csv(&db.conn, false).unwrap();
+        assert_eq!(count, 2);
+        let lines: Vec<&str> = csv.split("\r\n").collect();
+        assert_eq!(lines[0], "id,date,title,word_count,tags,mood,mood_emoji");
+        assert!(lines[1].starts_with(&format!("{},2024-03-05,\"Lisbon, day 1\",1,", id)));
+        assert!(lines[1].ends_with(",city travel,4,"));
+        assert!(!csv.contains("hidden"));
+
+        let (csv, _) = to_csv(&db.conn, true).unwrap();
+        assert!(csv.starts_with("id,date,title,word_count,tags,mood,mood_emoji,body\r\n"));
+        assert!(csv.contains(",Trams\r\n"));
+        assert!(!csv.contains("hidden"));
+        assert_eq!(csv_field("say \"hi\"\nthen"), "\"say \"\"hi\"\"\nthen\"");
+    }
+}
@@ -0,0 +1,220 @@
+use chrono::Utc;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::fs;
+use tauri::command;
+
+use journal_core::audit::AuditAction;
+use journal_core::parse_entry_datetime;
+use journal_core::text::strip_html;
+
+use crate::security;
+use crate::tags::tags_for_entry;
+use crate::{DatabaseManager, DateRange};
+
+// A private Atom feed of the newest entries, written to a local file for a
+// feed reader or a static site generator to pick up. Each entry carries its
+// whole body as HTML, or with `summary_only` just the start of its text, so
+// the feed can be shared without the rest. The feed's id comes from the path
+// it's written to, so exporting to the same file again updates the same feed
+// in a reader. Locked entries are left out, as in the other exports.
+
+const DEFAULT_TITLE: &str = "Journal";
+const DEFAULT_LIMIT: usize = 50;
+/// How much of an entry's text a summary-only feed shows.
+const SUMMARY_CHARS: usize = 280;
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct FeedOptions {
+    /// Title of the feed, also given as its author; "Journal" if empty.
+    pub title: String,
+    /// Only the start of each entry's text instead of its body.
+    pub summary_only: bool,
+    /// How many of the newest entries in range are included; 50 if unset.
+    pub limit: Option<usize>,
+}
+
+struct FeedEntry {
+    id: i32,
+    uuid: String,
+    title: String,
+    created_at: String,
+    updated_at: Option<String>,
+    body: String,
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// The start of the text of `body`, cut at a word.
+fn summary(body: &str) -> String {
+    let text = strip_html(body).split_whitespace().collect::<Vec<_>>().join(" ");
+    if text.chars().count() <= SUMMARY_CHARS {
+        return text;
+    }
+    let cut: String = text.chars().take(SUMMARY_CHARS).collect();
+    let cut = cut.rfind(' ').map_or(cut.as_str(), |end| &cut[..end]);
+    format!("{}…", cut.trim_end_matches(|c: char| c.is_ascii_punctuation()))
+}
+
+/// The newest `limit` unlocked entries within `range`, newest first.
+fn feed_entries(
+    conn: &rusqlite::Connection,
+    (start, end): (Option<i64>, Option<i64>),
+    limit: usize,
+) -> rusqlite::Result<Vec<FeedEntry>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, uuid, title, created_at, updated_at, body FROM journal_entries
+         WHERE locked = 0
+           AND (?1 IS NULL OR created_ms >= ?1)
+           AND (?2 IS NULL OR created_ms <= ?2)
+         ORDER BY created_ms DESC, id DESC
+         LIMIT ?3",
+    )?;
+    let entries = stmt
+        .query_map(rusqlite::params![start, end, limit as i64], |row| {
+            Ok(FeedEntry {
+                id: row.get(0)?,
+                uuid: row.get(1)?,
+                title: row.get(2)?,
+                created_at: row.get(3)?,
+                updated_at: row.get(4)?,
+                body: row.get(5)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(entries)
+}
+
+/// The feed of the entries within `range` as Atom XML, identified by `id`,
+/// and how many entries it has.
+fn render_feed(
+    conn: &rusqlite::Connection,
+    id: &str,
+    range: &DateRange,
+    options: &FeedOptions,
+) -> Result<(String, usize), String> {
+    let entries =
+        feed_entries(conn, range.millis()?, options.limit.unwrap_or(DEFAULT_LIMIT)).map_err(|e| e.to_string())?;
+    let title = match options.title.trim() {
+        "" => DEFAULT_TITLE,
+        title => title,
+    };
+
+    let mut xml = String::new();
+    let mut latest = None;
+    for entry in &entries {
+        let published = parse_entry_datetime(&entry.created_at).unwrap_or_else(|_| Utc::now());
+        let updated = entry.updated_at.as_deref().and_then(|date| parse_entry_datetime(date).ok());
+        let updated = updated.unwrap_or(published).max(published);
+        latest = latest.max(Some(updated));
+        let entry_title = if entry.title.trim().is_empty() {
+            published.format("%B %-d, %Y").to_string()
+        } else {
+            entry.title.clone()
+        };
+        xml.push_str("  <entry>\n");
+        xml.push_str(&format!("    <title>{}</title>\n", escape(&entry_title)));
+        xml.push_str(&format!("    <id>urn:uuid:{}</id>\n", escape(&entry.uuid)));
+        xml.push_str(&format!("    <published>{}</published>\n", published.to_rfc3339()));
+        xml.push_str(&format!("    <updated>{}</updated>\n", updated.to_rfc3339()));
+        for tag in tags_for_entry(conn, entry.id).map_err(|e| e.to_string())? {
+            xml.push_str(&format!("    <category term=\"{}\"/>\n", escape(&tag)));
+        }
+        if options.summary_only {
+            xml.push_str(&format!("    <summary>{}</summary>\n", escape(&summary(&entry.body))));
+        } else {
+            xml.push_str(&format!("    <content type=\"html\">{}</content>\n", escape(&entry.body)));
+        }
+        xml.push_str("  </entry>\n");
+    }
+
+    let header = format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+         <feed xmlns=\"http://www.w3.org/2005/Atom\">\n\
+         \x20 <title>{title}</title>\n\
+         \x20 <id>{id}</id>\n\
+         \x20 <updated>{updated}</updated>\n\
+         \x20 <author><name>{title}</name></author>\n\
+         \x20 <generator>Journal</generator>\n",
+        title = escape(title),
+        id = escape(id),
+        updated = latest.unwrap_or_else(Utc::now).to_rfc3339(),
+    );
+    Ok((format!("{}{}</feed>\n", header, xml), entries.len()))
+}
+
+/// Writes an Atom feed of the newest entries within `range` to file `path`
+/// and returns how many entries it has.
+#[command]
+pub fn export_feed(path: String, range: DateRange, options: Option<FeedOptions>) -> Result<usize, String> {
+    let options = options.unwrap_or_default();
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    let id = format!("urn:sha256:{}", hex::encode(Sha256::digest(path.as_bytes())));
+    let (xml, count) = render_feed(&db.conn, &id, &range, &options)?;
+    fs::write(&path, xml).map_err(|e| e.to_string())?;
+    security::audit(AuditAction::Export, &format!("{} entries as an Atom feed to {}", count, path));
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use journal_core::insert_entry;
+
+    #[test]
+    fn test_summary() {
+        assert_eq!(summary("<p>Trams and <em>tiles</em></p>"), "Trams and tiles");
+        let long = format!("<p>{}</p>", "tiles, ".repeat(60));
+        let cut = summary(&long);
+        assert!(cut.ends_with("tiles…"));
+        assert!(cut.chars().count() <= SUMMARY_CHARS + 1);
+    }
+
+    #[test]
+    fn test_render_feed() {
+        let db = DatabaseManager::open_in_memory().unwrap();
+        let lisbon =
+            insert_entry(&db.conn, "Lisbon & Porto", "<p>Trams and <em>tiles</em></p>", "2024-03-05T12:00:00+00:00")
+                .unwrap();
+        insert_entry(&db.conn, "", "<p>Back home</p>", "2024-04-02T12:00:00+00:00").unwrap();
+        let secret = insert_entry(&db.conn, "Secret", "", "2024-04-03T12:00:00+00:00").unwrap();
+        insert_entry(&db.conn, "Old", "<p>Long ago</p>", "2023-01-01T12:00:00+00:00").unwrap();
+        db.conn.execute("UPDATE journal_entries SET locked = 1 WHERE id = ?1", [secret]).unwrap();
+        db.conn
+            .execute("INSERT INTO entry_tags (entry_id, tag) VALUES (?1, 'travel')", rusqlite::params![lisbon])
+            .unwrap();
+
+        let range = DateRange {
+            start: Some("2024-01-01T00:00:00+00:00".to_string()),
+            end: None,
+        };
+        let (xml, count) = render_feed(&db.conn, "urn:test", &range, &FeedOptions::default()).unwrap();
+        assert_eq!(count, 2);
+        assert!(xml.starts_with("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<feed xmlns="));
+        assert!(xml.contains("  <title>Journal</title>\n  <id>urn:test</id>\n"));
+        let position = |text: &str| xml.find(text).unwrap();
+        assert!(position("<title>April 2, 2024</title>") < position("<title>Lisbon &amp; Porto</title>"));
+        assert!(xml.contains("<category term=\"travel\"/>"));
+        assert!(xml.contains("<content type=\"html\">&lt;p&gt;Trams and &lt;em&gt;tiles&lt;/em&gt;&lt;/p&gt;"));
+        assert!(xml.contains("<published>2024-03-05T12:00:00+00:00</published>"));
+        assert!(!xml.contains("Secret") && !xml.contains("Long ago"));
+        assert!(xml.ends_with("  </entry>\n</feed>\n"));
+
+        let options = FeedOptions {
+            title: "Travels".to_string(),
+            summary_only: true,
+            limit: Some(1),
+        };
+        let (xml, count) = render_feed(&db.conn, "urn:test", &DateRange::default(), &options).unwrap();
+        assert_eq!(count, 1);
+        assert!(xml.contains("<author><name>Travels</name></author>"));
+        assert!(xml.contains("<summary>Back home</summary>"));
+        assert!(!xml.contains("<content"));
+    }
+}
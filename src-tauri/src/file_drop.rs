@@ -0,0 +1,329 @@
+use chrono::{Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use log::{debug, warn};
+use pulldown_cmark::{html, CowStr, Event, Options, Parser};
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::thread;
+use tauri::{command, AppHandle, Emitter};
+
+use journal_core::keychain::KeychainManager;
+use journal_core::parse_entry_date;
+use journal_core::text::text_to_html;
+
+use crate::attachments::{add_image, AttachmentRef, MAX_IMAGE_BYTES};
+use crate::{create_entry, CreateEntryRequest};
+
+// Files dropped onto the window. Text and Markdown files become new entries,
+// dated from their front matter or a date in the file name; images are
+// attached to the entry that's open. The frontend hears what happened through
+// a `files-dropped` event, since the drop itself never reaches the webview.
+
+const MAX_TEXT_BYTES: usize = 5 * 1024 * 1024;
+const FRONT_MATTER_DELIMITER: &str = "---";
+/// Front-matter keys read as the entry's date, in order of preference.
+const DATE_KEYS: &[&str] = &["date", "created", "created_at"];
+
+/// The entry open in the main window, where dropped images go.
+static CURRENT_ENTRY: Mutex<Option<i32>> = Mutex::new(None);
+
+#[derive(Debug, Default, Serialize)]
+pub struct DropReport {
+    /// Entries created from text and Markdown files.
+    created: Vec<i32>,
+    /// Images attached to the open entry.
+    attached: Vec<AttachmentRef>,
+    skipped: Vec<SkippedFile>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SkippedFile {
+    pub(crate) name: String,
+    pub(crate) reason: String,
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) struct DroppedEntry {
+    pub(crate) title: String,
+    pub(crate) body: String,
+    pub(crate) created_at: Option<String>,
+}
+
+pub(crate) enum FileKind {
+    Text,
+    Markdown,
+    Image(&'static str),
+}
+
+pub(crate) fn file_kind(path: &Path) -> Option<FileKind> {
+    let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+    match extension.as_str() {
+        "txt" => Some(FileKind::Text),
+        "md" | "markdown" => Some(FileKind::Markdown),
+        "png" => Some(FileKind::Image("image/png")),
+        "jpg" | "jpeg" => Some(FileKind::Image("image/jpeg")),
+        "gif" => Some(FileKind::Image("image/gif")),
+        "webp" => Some(FileKind::Image("image/webp")),
+        _ => None,
+    }
+}
+
+fn local_rfc3339(datetime: NaiveDateTime) -> Option<String> {
+    Local
+        .from_local_datetime(&datetime)
+        .earliest()
+        .map(|d| d.with_timezone(&Utc).to_rfc3339())
+}
+
+/// A day without a time is filed at local noon, so it stays on that day
+/// wherever the journal is read.
+fn noon(day: NaiveDate) -> Option<String> {
+    day.and_hms_opt(12, 0, 0).and_then(local_rfc3339)
+}
+
+/// An RFC3339 timestamp, a local `YYYY-MM-DD HH:MM[:SS]`, or a bare day.
+fn parse_date(value: &str) -> Option<String> {
+    let value = value.trim();
+    if let Ok(date) = parse_entry_date(value) {
+        return Some(date);
+    }
+    ["%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+        .and_then(local_rfc3339)
+        .or_else(|| NaiveDate::parse_from_str(value, "%Y-%m-%d").ok().and_then(noon))
+}
+
+/// Words of a file name, with underscores read as spaces.
+fn name_to_title(name: &str) -> String {
+    name.split(|c: char| c.is_whitespace() || c == '_')
+        .map(|word| word.trim_matches(|c| c == '-' || c == '.'))
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// A date in a file name such as `2024-03-05 Lisbon.md` or
+/// `journal_2024_03_05.txt`, and the rest of the name.
+fn date_in_name(stem: &str) -> Option<(NaiveDate, String)> {
+    stem.char_indices().find_map(|(i, _)| {
+        let candidate = stem.get(i..i + 10)?;
+        let date = NaiveDate::parse_from_str(&candidate.replace(['_', '.'], "-"), "%Y-%m-%d").ok()?;
+        Some((date, format!("{} {}", &stem[..i], &stem[i + 10..])))
+    })
+}
+
+/// Splits simple `key: value` front matter off a Markdown file, with keys
+/// lowercased and values unquoted. Without front matter, the text is returned whole.
+fn split_front_matter(text: &str) -> (Vec<(String, String)>, &str) {
+    let Some(rest) = text
+        .strip_prefix(FRONT_MATTER_DELIMITER)
+        .and_then(|rest| rest.strip_prefix('\n').or_else(|| rest.strip_prefix("\r\n")))
+    else {
+        return (Vec::new(), text);
+    };
+    let Some(end) = rest.find(&format!("\n{}", FRONT_MATTER_DELIMITER)) else {
+        return (Vec::new(), text);
+    };
+    let fields = rest[..end]
+        .lines()
+        .filter_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            let value = value.trim().trim_matches(|c| c == '"' || c == '\'');
+            Some((key.trim().to_ascii_lowercase(), value.to_string()))
+        })
+        .collect();
+    let after = &rest[end + 1 + FRONT_MATTER_DELIMITER.len()..];
+    (fields, after.split_once('\n').map_or("", |(_, body)| body))
+}
+
+/// Markdown rendered to the editor's HTML. Raw HTML in the file is kept as
+/// text rather than passed through.
+pub(crate) fn markdown_to_html(markdown: &str) -> String {
+    let options = Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TASKLISTS | Options::ENABLE_TABLES;
+    let events = Parser::new_ext(markdown, options).map(|event| match event {
+        Event::Html(raw) | Event::InlineHtml(raw) => Event::Text(CowStr::from(raw.into_string())),
+        event => event,
+    });
+    let mut body = String::new();
+    html::push_html(&mut body, events);
+    body
+}
+
+/// Turns a dropped file's contents into an entry. The title comes from front
+/// matter, a leading `# heading` or the file name; the date from front matter
+/// or the file name, and otherwise the entry is filed as written now.
+pub(crate) fn parse_dropped(file_name: &str, contents: &str, markdown: bool) -> DroppedEntry {
+    let contents = contents.strip_prefix('\u{feff}').unwrap_or(contents);
+    let (fields, mut text) = if markdown { split_front_matter(contents) } else { (Vec::new(), contents) };
+    let field = |keys: &[&str]| {
+        keys.iter()
+            .find_map(|key| fields.iter().find(|(k, _)| k == key))
+            .map(|(_, value)| value.as_str())
+            .filter(|value| !value.is_empty())
+    };
+
+    let mut title = field(&["title"]).map(str::to_string);
+    if markdown && title.is_none() {
+        let trimmed = text.trim_start();
+        if let Some(heading) = trimmed.lines().next().and_then(|line| line.strip_prefix("# ")) {
+            title = Some(heading.trim().to_string());
+            text = trimmed.split_once('\n').map_or("", |(_, rest)| rest);
+        }
+    }
+    let stem = Path::new(file_name).file_stem().and_then(|s| s.to_str()).unwrap_or_default();
+    let (name_date, name) = match date_in_name(stem) {
+        Some((date, rest)) => (Some(date), rest),
+        None => (None, stem.to_string()),
+    };
+    let title = title.unwrap_or_else(|| match (name_to_title(&name), name_date) {
+        // Named like the CLI names entries
+        (name, Some(date)) if name.is_empty() => date.format("%B %-d, %Y").to_string(),
+        (name, _) => name,
+    });
+
+    DroppedEntry {
+        title,
+        body: if markdown { markdown_to_html(text) } else { text_to_html(text.trim()) },
+        created_at: field(DATE_KEYS).and_then(parse_date).or_else(|| name_date.and_then(noon)),
+    }
+}
+
+fn read_file(path: &Path, limit: usize) -> Result<Vec<u8>, String> {
+    let size = fs::metadata(path).map_err(|e| e.to_string())?.len();
+    if size > limit as u64 {
+        return Err(format!("Larger than {} MB", limit / (1024 * 1024)));
+    }
+    fs::read(path).map_err(|e| e.to_string())
+}
+
+/// A text or Markdown file's contents, refused if too large or not UTF-8.
+pub(crate) fn read_text(path: &Path) -> Result<String, String> {
+    String::from_utf8(read_file(path, MAX_TEXT_BYTES)?).map_err(|_| "Not UTF-8 text".to_string())
+}
+
+fn import_text(path: &Path, markdown: bool) -> Result<i32, String> {
+    let contents = read_text(path)?;
+    let name = path.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+    let entry = parse_dropped(name, &contents, markdown);
+    create_entry(CreateEntryRequest {
+        title: entry.title,
+        body: entry.body,
+        created_at: entry.created_at,
+    })
+}
+
+fn import(paths: &[PathBuf]) -> DropReport {
+    let mut report = DropReport::default();
+    let unlocked = KeychainManager::has_cached_key();
+    let current = CURRENT_ENTRY.lock().ok().and_then(|current| *current);
+    for path in paths {
+        let outcome = match file_kind(path) {
+            _ if !unlocked => Err("Unlock the journal first".to_string()),
+            Some(FileKind::Text) => import_text(path, false).map(|id| report.created.push(id)),
+            Some(FileKind::Markdown) => import_text(path, true).map(|id| report.created.push(id)),
+            Some(FileKind::Image(mime)) => match current {
+                Some(entry_id) => read_file(path, MAX_IMAGE_BYTES)
+                    .and_then(|data| add_image(entry_id, mime, &data))
+                    .map(|attachment| report.attached.push(attachment)),
+                None => Err("Open an entry to attach images to".to_string()),
+            },
+            None => Err("Only text, Markdown and image files can be dropped".to_string()),
+        };
+        if let Err(reason) = outcome {
+            let name = path.file_name().map_or_else(|| path.display().to_string(), |n| n.to_string_lossy().into_owned());
+            report.skipped.push(SkippedFile { name, reason });
+        }
+    }
+    report
+}
+
+/// Imports dropped files off the event loop, then reports to the frontend.
+pub fn handle(app: &AppHandle, paths: Vec<PathBuf>) {
+    let app = app.clone();
+    thread::spawn(move || {
+        let report = import(&paths);
+        debug!(
+            "Dropped files: {} entries created, {} images attached, {} skipped",
+            report.created.len(),
+            report.attached.len(),
+            report.skipped.len()
+        );
+        if let Err(e) = app.emit("files-dropped", &report) {
+            warn!("Could not report dropped files: {}", e);
+        }
+    });
+}
+
+/// The entry open in the main window, if any.
+pub(crate) fn current_entry() -> Option<i32> {
+    CURRENT_ENTRY.lock().ok().and_then(|current| *current)
+}
+
+/// Tells the backend which entry is open, so dropped images know where to go.
+#[command]
+pub fn set_current_entry(id: Option<i32>) {
+    if let Ok(mut current) = CURRENT_ENTRY.lock() {
+        *current = id;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_date_in_name() {
+        let day = NaiveDate::from_ymd_opt(2024, 3, 5).unwrap();
+        let (date, rest) = date_in_name("2024-03-05 Lisbon trip").unwrap();
+        assert_eq!((date, name_to_title(&rest).as_str()), (day, "Lisbon trip"));
+        let (date, rest) = date_in_name("journal_2024_03_05").unwrap();
+        assert_eq!((date, name_to_title(&rest).as_str()), (day, "journal"));
+        assert!(date_in_name("notes 2024-13-05").is_none());
+        assert!(date_in_name("shopping list").is_none());
+    }
+
+    #[test]
+    fn test_parse_markdown_with_front_matter() {
+        let markdown = "---\ntitle: \"Back home\"\ndate: 2024-03-05T08:30:00Z\ntags: travel\n---\n\n\
+                        Unpacked **everything**.\n\n<script>alert(1)</script>\n";
+        let entry = parse_dropped("2023-01-01 ignored.md", markdown, true);
+        assert_eq!(entry.title, "Back home");
+        assert_eq!(entry.created_at.as_deref(), Some("2024-03-05T08:30:00+00:00"));
+        assert!(entry.body.starts_with("<p>Unpacked <strong>everything</strong>.</p>"));
+        assert!(!entry.body.contains("<script>") && entry.body.contains("&lt;script&gt;"));
+    }
+
+    #[test]
+    fn test_parse_markdown_title_and_date_from_name() {
+        let entry = parse_dropped("2024-03-05.md", "# Rainy Tuesday\n\nStayed in.", true);
+        assert_eq!(entry.title, "Rainy Tuesday");
+        assert_eq!(entry.body.trim(), "<p>Stayed in.</p>");
+        assert_eq!(entry.created_at, noon(NaiveDate::from_ymd_opt(2024, 3, 5).unwrap()));
+
+        let untitled = parse_dropped("2024-03-05.txt", "Stayed in.", false);
+        assert_eq!(untitled.title, "March 5, 2024");
+    }
+
+    #[test]
+    fn test_parse_text() {
+        let entry = parse_dropped("grocery_run.txt", "Eggs < milk\nBread\n", false);
+        assert_eq!(
+            entry,
+            DroppedEntry {
+                title: "grocery run".to_string(),
+                body: "<p>Eggs &lt; milk</p><p>Bread</p>".to_string(),
+                created_at: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_date() {
+        assert_eq!(parse_date("2024-03-05T08:30:00+01:00").as_deref(), Some("2024-03-05T07:30:00+00:00"));
+        assert!(parse_date("2024-03-05 08:30").is_some());
+        assert_eq!(parse_date("2024-03-05"), noon(NaiveDate::from_ymd_opt(2024, 3, 5).unwrap()));
+        assert_eq!(parse_date("last tuesday"), None);
+    }
+}
@@ -0,0 +1,386 @@
+use chrono::Local;
+use std::path::{Path, PathBuf};
+use std::thread;
+use tauri::menu::{Submenu, SubmenuBuilder};
+use tauri::{AppHandle, Emitter, Wry};
+use tauri_plugin_dialog::{DialogExt, FilePath, MessageDialogButtons, MessageDialogKind};
+
+use journal_core::audit::AuditAction;
+use journal_core::keychain::KeychainManager;
+
+use crate::enex::import_enex;
+use crate::export::{export_csv_to, export_to, ExportFormat};
+use crate::jobs::{Job, JobKind, CANCELLED};
+use crate::folder_import::import_folder;
+use crate::importers::{import_journal, ImportInput, ImportPreview, ImportSource};
+use crate::obsidian::export_vault;
+use crate::static_site::{export_site, HtmlExportOptions};
+use crate::{file_drop, import_database, security, DatabaseManager};
+
+// File ▸ Export and File ▸ Import. Each item asks for a path or folder with a
+// native file dialog, runs the same code as the matching command and reports
+// back in a native message box, so none of it waits on the webview. The long
+// ones run as jobs like the commands do, and one cancelled from the window
+// ends quietly. Imports of entries from elsewhere do a dry run first and
+// ask before writing anything.
+
+const EXPORT_FORMATS: &[(&str, ExportFormat)] = &[
+    ("export_markdown", ExportFormat::Markdown),
+    ("export_json", ExportFormat::Json),
+    ("export_pdf", ExportFormat::Pdf),
+];
+const EXPORT_CSV: &str = "export_csv";
+const EXPORT_SITE: &str = "export_site";
+const EXPORT_OBSIDIAN: &str = "export_obsidian";
+const EXPORT_ARCHIVE: &str = "export_archive";
+const IMPORT_FILES: &str = "import_files";
+const IMPORT_FOLDER: &str = "import_folder";
+const IMPORT_ENEX: &str = "import_enex";
+const IMPORT_SOURCES: &[(&str, ImportSource, &str)] = &[
+    ("import_journal", ImportSource::Journal, "Journal JSON Export…"),
+    ("import_journey", ImportSource::Journey, "Journey Export…"),
+    ("import_diaro", ImportSource::Diaro, "Diaro Backup…"),
+    ("import_jrnl", ImportSource::Jrnl, "jrnl Journal…"),
+    ("import_apple_notes", ImportSource::AppleNotes, "Apple Notes Export Folder…"),
+    ("import_notes_app", ImportSource::NotesApp, "Apple Notes…"),
+];
+const IMPORT_ARCHIVE: &str = "import_archive";
+/// Duplicates named in the question asked before an import; the rest are counted.
+const MAX_LISTED_DUPLICATES: usize = 5;
+/// The encrypted database file, as the Settings import also expects it.
+const ARCHIVE_EXTENSION: &str = "db";
+
+pub fn export_submenu(app: &AppHandle) -> tauri::Result<Submenu<Wry>> {
+    let mut submenu = SubmenuBuilder::new(app, "Export");
+    for (id, format) in EXPORT_FORMATS {
+        submenu = submenu.text(*id, format!("{}…", format.name()));
+    }
+    submenu
+        .text(EXPORT_CSV, "CSV (Metadata)…")
+        .text(EXPORT_SITE, "Website…")
+        .text(EXPORT_OBSIDIAN, "Obsidian Vault…")
+        .separator()
+        .text(EXPORT_ARCHIVE, "Encrypted Archive…")
+        .build()
+}
+
+pub fn import_submenu(app: &AppHandle) -> tauri::Result<Submenu<Wry>> {
+    let mut submenu = SubmenuBuilder::new(app, "Import")
+        .text(IMPORT_FILES, "Markdown or Text Files…")
+        .text(IMPORT_FOLDER, "Folder of Notes…")
+        .text(IMPORT_ENEX, "Evernote Export…");
+    for (id, source, label) in IMPORT_SOURCES {
+        if source.is_available() {
+            submenu = submenu.text(*id, *label);
+        }
+    }
+    submenu.text(IMPORT_ARCHIVE, "Encrypted Archive…").build()
+}
+
+fn report(app: &AppHandle, title: &str, result: Result<String, String>) {
+    let (message, kind) = match result {
+        Ok(message) => (message, MessageDialogKind::Info),
+        // The user stopped it, so there's nothing to tell them
+        Err(e) if e == CANCELLED => return,
+        Err(e) => (format!("{} failed: {}", title, e), MessageDialogKind::Error),
+    };
+    app.dialog().message(message).title(title).kind(kind).show(|_| {});
+}
+
+/// Asks where to save, then writes the file with `write`.
+fn save_as<F>(app: &AppHandle, filter: &str, extension: &str, write: F)
+where
+    F: FnOnce(&DatabaseManager, &Path) -> Result<String, String> + Send + 'static,
+{
+    let handle = app.clone();
+    app.dialog()
+        .file()
+        .add_filter(filter, &[extension])
+        .set_file_name(format!("journal-{}.{}", Local::now().format("%Y-%m-%d"), extension))
+        .save_file(move |path| {
+            let Some(path) = path else {
+                return;
+            };
+            let result = path.into_path().map_err(|e| e.to_string()).and_then(|path| {
+                let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+                write(&db, &path)
+            });
+            report(&handle, "Export", result);
+        });
+}
+
+/// Asks for a folder, then writes into it with `write`.
+fn save_to_folder<F>(app: &AppHandle, write: F)
+where
+    F: FnOnce(&DatabaseManager, &Path) -> Result<String, String> + Send + 'static,
+{
+    let handle = app.clone();
+    app.dialog().file().pick_folder(move |dir| {
+        let Some(dir) = dir else {
+            return;
+        };
+        let result = dir.into_path().map_err(|e| e.to_string()).and_then(|dir| {
+            let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+            write(&db, &dir)
+        });
+        report(&handle, "Export", result);
+    });
+}
+
+fn import_archive(app: &AppHandle) {
+    let handle = app.clone();
+    app.dialog()
+        .file()
+        .add_filter("Encrypted Archive", &[ARCHIVE_EXTENSION])
+        .pick_file(move |path| {
+            let Some(path) = path else {
+                return;
+            };
+            let result = path.into_path().map_err(|e| e.to_string()).and_then(|path| {
+                import_database(path.to_string_lossy().into_owned())?;
+                let _ = handle.emit("entries-changed", ());
+                Ok("Imported the journal. The previous one was backed up first.".to_string())
+            });
+            report(&handle, "Import", result);
+        });
+}
+
+/// What a dry run found, as the question asked before importing.
+fn preview_question(preview: &ImportPreview, from: &str) -> String {
+    let mut question = format!("Import {} entries from {}?", preview.entries, from);
+    if let (Some(first), Some(last)) = (&preview.first_day, &preview.last_day) {
+        question.push_str(&format!("\n\nThey were written from {} to {}.", first, last));
+    }
+    if !preview.notebooks.is_empty() {
+        question.push_str(&format!(" They go in the notebooks {}.", preview.notebooks.join(", ")));
+    }
+    if !preview.duplicates.is_empty() {
+        question.push_str(&format!(
+            "\n\n{} look like entries the journal already has and would be added again:",
+            preview.duplicates.len()
+        ));
+        for duplicate in preview.duplicates.iter().take(MAX_LISTED_DUPLICATES) {
+            question.push_str(&format!("\n{} ({})", duplicate.title, duplicate.day));
+        }
+        if preview.duplicates.len() > MAX_LISTED_DUPLICATES {
+            question.push_str(&format!("\nand {} more", preview.duplicates.len() - MAX_LISTED_DUPLICATES));
+        }
+    }
+    question
+}
+
+/// Shows what a dry run of importing from `from` found and runs `import` once
+/// the user agrees.
+fn confirm_import<F>(app: &AppHandle, from: &str, preview: Result<ImportPreview, String>, import: F)
+where
+    F: FnOnce() -> Result<String, String> + Send + 'static,
+{
+    let preview = match preview {
+        Ok(preview) if preview.entries == 0 => {
+            return report(app, "Import", Ok(format!("There's nothing to import from {}.", from)))
+        }
+        Ok(preview) => preview,
+        Err(e) => return report(app, "Import", Err(e)),
+    };
+    let handle = app.clone();
+    app.dialog()
+        .message(preview_question(&preview, from))
+        .title("Import")
+        .buttons(MessageDialogButtons::OkCancelCustom("Import".to_string(), "Cancel".to_string()))
+        .show(move |confirmed| {
+            if confirmed {
+                report(&handle, "Import", import());
+            }
+        });
+}
+
+fn import_notes_folder(app: &AppHandle) {
+    let handle = app.clone();
+    app.dialog().file().pick_folder(move |dir| {
+        let Some(dir) = dir else {
+            return;
+        };
+        let dir = match dir.into_path() {
+            Ok(dir) => dir.to_string_lossy().into_owned(),
+            Err(e) => return report(&handle, "Import", Err(e.to_string())),
+        };
+        let preview = import_folder(dir.clone(), Some(true)).map(|report| report.preview.unwrap_or_default());
+        let emitter = handle.clone();
+        confirm_import(&handle, "the folder", preview, move || {
+            let report = import_folder(dir, None)?;
+            let _ = emitter.emit("entries-changed", ());
+            let mut message = format!("Imported {} entries.", report.imported.len());
+            if !report.failed.is_empty() {
+                message.push_str(&format!("\n\n{} files couldn't be imported:", report.failed.len()));
+                for file in &report.failed {
+                    message.push_str(&format!("\n{}: {}", file.name, file.reason));
+                }
+            }
+            Ok(message)
+        });
+    });
+}
+
+fn import_evernote(app: &AppHandle) {
+    let handle = app.clone();
+    app.dialog()
+        .file()
+        .add_filter("Evernote Export", &["enex"])
+        .pick_file(move |path| {
+            let Some(path) = path else {
+                return;
+            };
+            let path = match path.into_path() {
+                Ok(path) => path.to_string_lossy().into_owned(),
+                Err(e) => return report(&handle, "Import", Err(e.to_string())),
+            };
+            let preview = import_enex(path.clone(), Some(true)).map(|report| report.preview.unwrap_or_default());
+            let emitter = handle.clone();
+            confirm_import(&handle, "Evernote", preview, move || {
+                let report = import_enex(path, None)?;
+                let _ = emitter.emit("entries-changed", ());
+                let mut message = format!("Imported {} notes with {} images.", report.created.len(), report.images);
+                if report.skipped_resources > 0 {
+                    let skipped = report.skipped_resources;
+                    message.push_str(&format!(" {} attached files that aren't images were left out.", skipped));
+                }
+                for note in &report.failed {
+                    message.push_str(&format!("\n{}: {}", note.name, note.reason));
+                }
+                Ok(message)
+            });
+        });
+}
+
+/// Asks for another app's export unless the entries are read from the app
+/// itself, then imports them.
+fn import_other_journal(app: &AppHandle, source: ImportSource) {
+    let importer = source.importer();
+    let handle = app.clone();
+    let chosen = move |path: Option<FilePath>| {
+        let Some(path) = path else {
+            return;
+        };
+        match path.into_path() {
+            Ok(path) => import_from(&handle, source, Some(path.to_string_lossy().into_owned())),
+            Err(e) => report(&handle, "Import", Err(e.to_string())),
+        }
+    };
+    match importer.input() {
+        ImportInput::File(extensions) => app.dialog().file().add_filter(importer.name(), extensions).pick_file(chosen),
+        ImportInput::Folder => app.dialog().file().pick_folder(chosen),
+        ImportInput::App => {
+            // Reading another app can take a while, so not on the menu's thread
+            let handle = app.clone();
+            thread::spawn(move || import_from(&handle, source, None));
+        }
+    }
+}
+
+fn import_from(app: &AppHandle, source: ImportSource, path: Option<String>) {
+    let name = source.importer().name();
+    let preview = import_journal(source, path.clone(), true).map(|report| report.preview.unwrap_or_default());
+    let handle = app.clone();
+    confirm_import(app, name, preview, move || {
+        let imported = import_journal(source, path, false)?;
+        let _ = handle.emit("entries-changed", ());
+        Ok(format!("Imported {} entries from {}.", imported.created.len(), name))
+    });
+}
+
+fn import_files(app: &AppHandle) {
+    let handle = app.clone();
+    app.dialog()
+        .file()
+        .add_filter("Markdown or Text", &["md", "markdown", "txt"])
+        .pick_files(move |paths| {
+            let paths: Vec<PathBuf> = paths
+                .unwrap_or_default()
+                .into_iter()
+                .filter_map(|path| path.into_path().ok())
+                .collect();
+            // Imported like dropped files, so the frontend reports them the same way
+            if !paths.is_empty() {
+                file_drop::handle(&handle, paths);
+            }
+        });
+}
+
+/// Runs the Export or Import item `id`. Returns false for other menu items.
+pub fn handle(app: &AppHandle, id: &str) -> bool {
+    let known = id == EXPORT_CSV
+        || id == EXPORT_SITE
+        || id == EXPORT_OBSIDIAN
+        || id == EXPORT_ARCHIVE
+        || id == IMPORT_FILES
+        || id == IMPORT_FOLDER
+        || id == IMPORT_ENEX
+        || id == IMPORT_ARCHIVE
+        || IMPORT_SOURCES.iter().any(|(item, _, _)| *item == id)
+        || EXPORT_FORMATS.iter().any(|(item, _)| *item == id);
+    if known && !KeychainManager::has_cached_key() {
+        app.dialog()
+            .message("Unlock the journal before exporting or importing.")
+            .kind(MessageDialogKind::Warning)
+            .show(|_| {});
+        return true;
+    }
+    if let Some((_, format)) = EXPORT_FORMATS.iter().find(|(item, _)| *item == id) {
+        let format = *format;
+        save_as(app, format.name(), format.extension(), move |db, path| {
+            let label = format!("Exporting {} to {}", format.name(), path.display());
+            let count = Job::run(JobKind::Export, label, |job| {
+                export_to(&db.conn, format, &path.to_string_lossy(), job)
+            })?;
+            security::audit(
+                AuditAction::Export,
+                &format!("{} entries as {} to {}", count, format.name(), path.display()),
+            );
+            Ok(format!("Exported {} entries to {}", count, path.display()))
+        });
+    } else if id == EXPORT_CSV {
+        save_as(app, "CSV", "csv", |db, path| {
+            let count = export_csv_to(&db.conn, &path.to_string_lossy(), false)?;
+            security::audit(AuditAction::Export, &format!("{} entries' metadata as CSV to {}", count, path.display()));
+            Ok(format!("Exported {} entries to {}", count, path.display()))
+        });
+    } else if id == EXPORT_SITE {
+        save_to_folder(app, |db, dir| {
+            let label = format!("Exporting a website to {}", dir.display());
+            let options = HtmlExportOptions::default();
+            let count = Job::run(JobKind::Export, label, |job| export_site(&db.conn, dir, &options, job))?;
+            security::audit(AuditAction::Export, &format!("{} entries as HTML to {}", count, dir.display()));
+            Ok(format!("Exported {} entries as a website to {}", count, dir.display()))
+        });
+    } else if id == EXPORT_OBSIDIAN {
+        save_to_folder(app, |db, dir| {
+            let count = export_vault(&db.conn, dir)?;
+            security::audit(
+                AuditAction::Export,
+                &format!("{} entries as an Obsidian vault to {}", count, dir.display()),
+            );
+            Ok(format!("Exported {} entries as an Obsidian vault to {}", count, dir.display()))
+        });
+    } else if id == EXPORT_ARCHIVE {
+        save_as(app, "Encrypted Archive", ARCHIVE_EXTENSION, |db, path| {
+            let label = format!("Exporting the encrypted journal to {}", path.display());
+            Job::run(JobKind::Export, label, |job| {
+                let mut progress = |copied: u64, total: u64| job.step(copied as usize, total as usize, None).is_ok();
+                db.backup_with_progress(path, &mut progress).map_err(|e| e.to_string())
+            })?;
+            security::audit(AuditAction::Export, &format!("encrypted archive to {}", path.display()));
+            Ok(format!("Exported the encrypted journal to {}", path.display()))
+        });
+    } else if id == IMPORT_FILES {
+        import_files(app);
+    } else if id == IMPORT_FOLDER {
+        import_notes_folder(app);
+    } else if id == IMPORT_ENEX {
+        import_evernote(app);
+    } else if id == IMPORT_ARCHIVE {
+        import_archive(app);
+    } else if let Some((_, source, _)) = IMPORT_SOURCES.iter().find(|(item, _, _)| *item == id) {
+        import_other_journal(app, *source);
+    }
+    known
+}
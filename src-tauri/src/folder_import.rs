@@ -0,0 +1,184 @@
+use chrono::{DateTime, Utc};
+use log::debug;
+use serde::Serialize;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use tauri::command;
+
+use journal_core::parse_entry_datetime;
+
+use crate::file_drop::{file_kind, parse_dropped, read_text, FileKind, SkippedFile};
+use crate::importers::{import_entries, preview_entries, ImportPreview, ImportedEntry};
+use crate::jobs::{Job, JobKind};
+use crate::{git_sync, markdown_mirror, recent_menu, DatabaseManager};
+
+// A folder of notes from another app or an old journal, brought in at once:
+// every text and Markdown file in it and its subfolders becomes an entry, read
+// the way a dropped file is. A file without a date in its front matter or name
+// is filed at the time it was last modified. The entries are created in one
+// transaction, so an import that fails or is cancelled partway leaves nothing
+// behind; a file that can't be read is only listed in the report with the
+// reason. A dry run reads the files and reports what importing them would do.
+
+#[derive(Debug, Default, Serialize)]
+pub struct FolderImportReport {
+    pub imported: Vec<ImportedFile>,
+    pub failed: Vec<SkippedFile>,
+    /// What a dry run found; `None` when the files were imported.
+    pub preview: Option<ImportPreview>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImportedFile {
+    /// The file's path inside the folder.
+    pub name: String,
+    pub id: i32,
+}
+
+/// Text and Markdown files under `dir`. Hidden files and folders, such as an
+/// editor's settings, are passed over, and symlinks aren't followed.
+fn text_files(dir: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        let path = entry.path();
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            text_files(&path, files)?;
+        } else if file_type.is_file() && matches!(file_kind(&path), Some(FileKind::Text | FileKind::Markdown)) {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn modified_at(path: &Path) -> Option<DateTime<Utc>> {
+    let modified = fs::metadata(path).ok()?.modified().ok()?;
+    Some(DateTime::from(modified))
+}
+
+/// The entry in the file at `path`.
+fn read_entry(path: &Path) -> Result<ImportedEntry, String> {
+    let contents = read_text(path)?;
+    let file_name = path.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+    let entry = parse_dropped(file_name, &contents, matches!(file_kind(path), Some(FileKind::Markdown)));
+    let created_at = entry
+        .created_at
+        .and_then(|created_at| parse_entry_datetime(&created_at).ok())
+        .or_else(|| modified_at(path))
+        .unwrap_or_else(Utc::now);
+    Ok(ImportedEntry {
+        title: entry.title,
+        body: entry.body,
+        created_at,
+        tags: Vec::new(),
+        notebook: None,
+    })
+}
+
+/// Creates an entry from every text and Markdown file under `dir`, in order
+/// of path, as part of `job`. Without a job it's a dry run: nothing is written
+/// and the report says what would be.
+pub fn import_folder_into(
+    conn: &rusqlite::Connection,
+    dir: &Path,
+    job: Option<&Job>,
+) -> Result<FolderImportReport, String> {
+    let mut files = Vec::new();
+    text_files(dir, &mut files).map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?;
+    files.sort();
+
+    let mut report = FolderImportReport::default();
+    let mut names = Vec::new();
+    let mut entries = Vec::new();
+    for path in &files {
+        let name = path.strip_prefix(dir).unwrap_or(path).display().to_string();
+        match read_entry(path) {
+            Ok(entry) => {
+                names.push(name);
+                entries.push(entry);
+            }
+            Err(reason) => report.failed.push(SkippedFile { name, reason }),
+        }
+    }
+    let Some(job) = job else {
+        report.preview = Some(preview_entries(conn, &entries)?);
+        return Ok(report);
+    };
+    let ids = import_entries(conn, &entries, job)?;
+    report.imported = names.into_iter().zip(ids).map(|(name, id)| ImportedFile { name, id }).collect();
+    debug!("Imported {} files from {}, {} failed", report.imported.len(), dir.display(), report.failed.len());
+    Ok(report)
+}
+
+/// Imports every `.txt` and `.md` file in folder `dir` and its subfolders as
+/// an entry, and reports which files were imported and why any weren't. With
+/// `dry_run`, only reports what importing them would do; otherwise the import
+/// runs as a job.
+#[command]
+pub fn import_folder(dir: String, dry_run: Option<bool>) -> Result<FolderImportReport, String> {
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    let report = if dry_run.unwrap_or(false) {
+        import_folder_into(&db.conn, Path::new(&dir), None)?
+    } else {
+        let label = format!("Importing {}", dir);
+        Job::run(JobKind::Import, label, |job| import_folder_into(&db.conn, Path::new(&dir), Some(job)))?
+    };
+    if !report.imported.is_empty() {
+        git_sync::commit_on_save();
+        markdown_mirror::refresh();
+        recent_menu::refresh();
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_import_folder() {
+        let dir = std::env::temp_dir().join(format!("journal-folder-import-{}", std::process::id()));
+        fs::create_dir_all(dir.join("2024")).unwrap();
+        fs::create_dir_all(dir.join(".obsidian")).unwrap();
+        fs::write(dir.join("2024/2024-03-05 Lisbon.md"), "Trams and **tiles**").unwrap();
+        fs::write(dir.join("ideas.txt"), "---\nnot front matter in a text file").unwrap();
+        fs::write(dir.join("notes.md"), "---\ndate: 2023-12-31T22:00:00Z\n---\n# Year end\n\nQuiet").unwrap();
+        fs::write(dir.join("binary.txt"), [0xff, 0xfe, 0x00]).unwrap();
+        fs::write(dir.join(".obsidian/workspace.md"), "settings").unwrap();
+        fs::write(dir.join("photo.png"), [0u8; 4]).unwrap();
+
+        let db = DatabaseManager::open_in_memory().unwrap();
+        let preview = import_folder_into(&db.conn, &dir, None).unwrap();
+        assert!(preview.imported.is_empty());
+        assert_eq!(preview.failed.len(), 1);
+        assert_eq!(preview.preview.unwrap().entries, 3);
+        let report = import_folder_into(&db.conn, &dir, Some(&Job::start(JobKind::Import, "Test"))).unwrap();
+        let names: Vec<&str> = report.imported.iter().map(|file| file.name.as_str()).collect();
+        assert_eq!(names, [Path::new("2024/2024-03-05 Lisbon.md").to_str().unwrap(), "ideas.txt", "notes.md"]);
+        assert_eq!(report.failed.len(), 1);
+        let failed = &report.failed[0];
+        assert_eq!((failed.name.as_str(), failed.reason.as_str()), ("binary.txt", "Not UTF-8 text"));
+
+        let entry = |id: i32| -> (String, String) {
+            db.conn
+                .query_row("SELECT title, created_at FROM journal_entries WHERE id = ?1", [id], |row| {
+                    Ok((row.get(0)?, row.get(1)?))
+                })
+                .unwrap()
+        };
+        let (title, created_at) = entry(report.imported[0].id);
+        assert_eq!(title, "Lisbon");
+        assert!(created_at.starts_with("2024-03-0"));
+        assert_eq!(entry(report.imported[2].id), ("Year end".to_string(), "2023-12-31T22:00:00+00:00".to_string()));
+        let (title, created_at) = entry(report.imported[1].id);
+        assert_eq!(title, "ideas");
+        assert_eq!(created_at, modified_at(&dir.join("ideas.txt")).unwrap().to_rfc3339());
+        let again = import_folder_into(&db.conn, &dir, None).unwrap().preview.unwrap();
+        assert_eq!(again.duplicates.len(), 3);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,474 @@
+use log::{debug, info, warn};
+use rusqlite::OptionalExtension;
+use serde_json::{Map, Value};
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use std::sync::Mutex;
+use std::thread;
+use tauri::command;
+
+use crate::conflicts::record_all_shared;
+use crate::settings::{GitSettings, LimitSettings, Settings};
+use crate::sync::{ensure_device_id, full_state, merge_state, Changeset, EntryRecord, SyncReport, FORMAT_VERSION};
+use crate::DatabaseManager;
+
+// Mirrors the journal into a Git repository as one Markdown file per entry,
+// committed whenever an entry is saved. With a remote configured the
+// repository doubles as a sync channel: other devices' commits are merged
+// into the database with the same Lamport rule the other sync modes use, and
+// the merge commit records exactly what the database ends up holding, so Git
+// itself never has to resolve a textual conflict.
+//
+// The files are a mirror, not a second source of truth: edits made to them
+// outside the app don't bump the entry's clock and lose to the app's copy.
+// Locked entries are never written; whatever was mirrored before the lock is
+// left as it was. Authentication with the remote is whatever the user's own
+// Git setup provides (SSH keys, credential helpers).
+
+const ENTRIES_DIR: &str = "entries";
+const REMOTE_NAME: &str = "origin";
+const FRONT_MATTER_DELIMITER: &str = "---";
+/// Front-matter keys in the order they're written; the body follows.
+const FRONT_MATTER_KEYS: &[&str] = &[
+    "uuid",
+    "title",
+    "created_at",
+    "local_date",
+    "updated_at",
+    "tags",
+    "mood_score",
+    "mood_emoji",
+    "lamport",
+    "origin",
+];
+
+/// Serializes every Git run so saves arriving together don't race for the index.
+static GIT_LOCK: Mutex<()> = Mutex::new(());
+
+fn git(repo: &Path, args: &[&str]) -> Result<String, String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .args(args)
+        // Fail instead of waiting on a password prompt nobody can see
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .output()
+        .map_err(|e| format!("Failed to run git: {}", e))?;
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    } else {
+        Err(format!(
+            "git {} failed: {}",
+            args.first().unwrap_or(&""),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))
+    }
+}
+
+/// For commands that answer yes or no through their exit status.
+fn git_check(repo: &Path, args: &[&str]) -> Result<bool, String> {
+    Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .args(args)
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .output()
+        .map(|output| output.status.success())
+        .map_err(|e| format!("Failed to run git: {}", e))
+}
+
+fn entry_path(uuid: &str) -> String {
+    format!("{}/{}.md", ENTRIES_DIR, uuid)
+}
+
+/// Markdown with the sync metadata as front matter. Values are written as
+/// JSON, which YAML readers accept and which round-trips without a YAML parser.
+fn to_markdown(record: &EntryRecord) -> Result<String, String> {
+    let Value::Object(fields) = serde_json::to_value(record).map_err(|e| e.to_string())? else {
+        return Err("Entry did not serialize to an object".to_string());
+    };
+    let mut markdown = format!("{}\n", FRONT_MATTER_DELIMITER);
+    for key in FRONT_MATTER_KEYS {
+        match fields.get(*key) {
+            None | Some(Value::Null) => {}
+            Some(value) => markdown.push_str(&format!("{}: {}\n", key, value)),
+        }
+    }
+    markdown.push_str(&format!("{}\n\n{}", FRONT_MATTER_DELIMITER, record.body));
+    Ok(markdown)
+}
+
+fn from_markdown(markdown: &str) -> Result<EntryRecord, String> {
+    let invalid = || "Not a journal entry file".to_string();
+    let rest = markdown
+        .strip_prefix(FRONT_MATTER_DELIMITER)
+        .and_then(|rest| rest.strip_prefix('\n'))
+        .ok_or_else(invalid)?;
+    let end = rest.find(&format!("\n{}\n", FRONT_MATTER_DELIMITER)).ok_or_else(invalid)?;
+    let mut fields = Map::new();
+    for line in rest[..end].lines() {
+        let (key, value) = line.split_once(": ").ok_or_else(invalid)?;
+        let value = serde_json::from_str(value).map_err(|e| format!("Invalid value for {}: {}", key, e))?;
+        fields.insert(key.to_string(), value);
+    }
+    let body = &rest[end + FRONT_MATTER_DELIMITER.len() + 2..];
+    fields.insert("body".to_string(), Value::String(body.strip_prefix('\n').unwrap_or(body).to_string()));
+    serde_json::from_value(Value::Object(fields)).map_err(|e| format!("Invalid entry file: {}", e))
+}
+
+/// Writes the database's entries into the working tree, touching only files
+/// whose contents changed, and removes files for entries that no longer exist.
+fn mirror(conn: &rusqlite::Connection, repo: &Path, device_id: &str) -> Result<(), String> {
+    let state = full_state(conn, device_id).map_err(|e| e.to_string())?;
+    let locked = conn
+        .prepare("SELECT uuid FROM journal_entries WHERE locked = 1 AND uuid IS NOT NULL")
+        .map_err(|e| e.to_string())?
+        .query_map([], |row| row.get::<_, String>(0))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<HashSet<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    let dir = repo.join(ENTRIES_DIR);
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {:?}: {}", dir, e))?;
+    let mut mirrored = HashSet::new();
+    for record in &state.entries {
+        let path = repo.join(entry_path(&record.uuid));
+        let markdown = to_markdown(record)?;
+        if fs::read_to_string(&path).ok().as_deref() != Some(markdown.as_str()) {
+            fs::write(&path, markdown).map_err(|e| format!("Failed to write {:?}: {}", path, e))?;
+        }
+        mirrored.insert(record.uuid.as_str());
+    }
+    for file in fs::read_dir(&dir).map_err(|e| format!("Failed to read {:?}: {}", dir, e))? {
+        let path = file.map_err(|e| e.to_string())?.path();
+        if path.extension().and_then(|ext| ext.to_str()) != Some("md") {
+            continue;
+        }
+        let Some(uuid) = path.file_stem().and_then(|stem| stem.to_str()) else { continue };
+        if !mirrored.contains(uuid) && !locked.contains(uuid) {
+            fs::remove_file(&path).map_err(|e| format!("Failed to remove {:?}: {}", path, e))?;
+        }
+    }
+    Ok(())
+}
+
+/// Stages the mirrored entries and commits them, returning how many files
+/// the commit touched. Inside a merge the commit is made even if empty.
+fn commit(repo: &Path, merging: bool) -> Result<usize, String> {
+    git(repo, &["add", "--all", "--", ENTRIES_DIR])?;
+    let changed = git(repo, &["diff", "--cached", "--name-only", "--", ENTRIES_DIR])?.lines().count();
+    if changed == 0 && !merging {
+        return Ok(0);
+    }
+    let message = if merging {
+        "Merge journal entries from other devices".to_string()
+    } else if changed == 1 {
+        "Update 1 entry".to_string()
+    } else {
+        format!("Update {} entries", changed)
+    };
+    git(repo, &["commit", "--quiet", "--no-verify", "-m", &message])?;
+    Ok(changed)
+}
+
+fn commit_changes(repo: &Path, device_id: &str) -> Result<usize, String> {
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    mirror(&db.conn, repo, device_id)?;
+    commit(repo, false)
+}
+
+/// `(status, path)` for each entry file changed between two revisions.
+fn changed_entries(repo: &Path, base: Option<&str>, theirs: &str) -> Result<Vec<(char, String)>, String> {
+    let listing = match base {
+        Some(base) => git(repo, &["diff", "--name-status", "--no-renames", base, theirs, "--", ENTRIES_DIR])?,
+        // Unrelated histories: everything on their side is new to us
+        None => git(repo, &["ls-tree", "-r", "--name-only", theirs, "--", ENTRIES_DIR])?
+            .lines()
+            .map(|path| format!("A\t{}\n", path))
+            .collect(),
+    };
+    Ok(parse_name_status(&listing))
+}
+
+fn parse_name_status(listing: &str) -> Vec<(char, String)> {
+    listing
+        .lines()
+        .filter_map(|line| {
+            let (status, path) = line.split_once('\t')?;
+            let status = status.chars().next()?;
+            path.ends_with(".md").then(|| (status, path.to_string()))
+        })
+        .collect()
+}
+
+fn read_entry(repo: &Path, revision: &str, path: &str) -> Result<EntryRecord, String> {
+    from_markdown(&git(repo, &["show", &format!("{}:{}", revision, path)])?)
+        .map_err(|e| format!("{} at {}: {}", path, revision, e))
+}
+
+/// Deletes the local copy of an entry another device deleted, unless it was
+/// changed here since the version they deleted; an edit outranks a delete.
+fn apply_deletion(conn: &rusqlite::Connection, deleted: &EntryRecord, device_id: &str) -> Result<bool, String> {
+    let local: Option<(i32, i64, String, bool)> = conn
+        .query_row(
+            "SELECT id, lamport, COALESCE(origin, ?2), locked FROM journal_entries WHERE uuid = ?1",
+            rusqlite::params![deleted.uuid, device_id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+    match local {
+        Some((id, lamport, origin, false)) if lamport == deleted.lamport && origin == deleted.origin => {
+            conn.execute("DELETE FROM journal_entries WHERE id = ?1", rusqlite::params![id])
+                .map_err(|e| e.to_string())?;
+            Ok(true)
+        }
+        _ => Ok(false),
+    }
+}
+
+/// Fetches the remote branch and merges it: entries are merged into the
+/// database first, then recorded as a merge commit whose tree is the
+/// database's mirror.
+fn pull(
+    conn: &rusqlite::Connection,
+    repo: &Path,
+    device_id: &str,
+    branch: &str,
+    limits: &LimitSettings,
+) -> Result<SyncReport, String> {
+    let mut report = SyncReport::default();
+    if git(repo, &["ls-remote", "--heads", REMOTE_NAME, branch])?.trim().is_empty() {
+        debug!("Remote has no branch {} yet", branch);
+        return Ok(report);
+    }
+    git(repo, &["fetch", "--quiet", REMOTE_NAME, branch])?;
+    let theirs = git(repo, &["rev-parse", "FETCH_HEAD"])?.trim().to_string();
+    if git_check(repo, &["merge-base", "--is-ancestor", &theirs, "HEAD"])? {
+        return Ok(report);
+    }
+    let base = git(repo, &["merge-base", "HEAD", &theirs]).ok().map(|base| base.trim().to_string());
+
+    let mut entries = Vec::new();
+    let mut deletions = Vec::new();
+    for (status, path) in changed_entries(repo, base.as_deref(), &theirs)? {
+        let parsed = match (status, base.as_deref()) {
+            ('D', Some(base)) => read_entry(repo, base, &path).map(|record| deletions.push(record)),
+            ('D', None) => Ok(()),
+            _ => read_entry(repo, &theirs, &path).map(|record| entries.push(record)),
+        };
+        if let Err(e) = parsed {
+            warn!("Skipping {}", e);
+            report.skipped += 1;
+        }
+    }
+    report.add(merge_state(
+        conn,
+        device_id,
+        &Changeset {
+            format_version: FORMAT_VERSION,
+            device_id: theirs.clone(),
+            seq: 0,
+            entries,
+            tombstones: Vec::new(),
+        },
+        limits,
+    )?);
+    for deleted in &deletions {
+        if apply_deletion(conn, deleted, device_id)? {
+            report.deleted += 1;
+        } else {
+            report.skipped += 1;
+        }
+    }
+
+    // `ours` keeps our tree; the mirror then replaces it with the merged state
+    git(
+        repo,
+        &["merge", "--quiet", "--no-ff", "--no-commit", "--allow-unrelated-histories", "-s", "ours", &theirs],
+    )?;
+    mirror(conn, repo, device_id)?;
+    commit(repo, true)?;
+    Ok(report)
+}
+
+/// Commits local changes, merges the remote's and pushes the result.
+fn sync_with_remote(settings: &GitSettings, device_id: &str, limits: &LimitSettings) -> Result<SyncReport, String> {
+    let repo = settings.repo()?;
+    let _guard = GIT_LOCK.lock().map_err(|e| e.to_string())?;
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    mirror(&db.conn, repo, device_id)?;
+    commit(repo, false)?;
+    if settings.remote_url.is_none() {
+        return Ok(SyncReport::default());
+    }
+    let mut report = pull(&db.conn, repo, device_id, &settings.branch, limits)?;
+    let remote_ref = format!("{}/{}", REMOTE_NAME, settings.branch);
+    report.pushed = if git_check(repo, &["rev-parse", "--verify", "--quiet", &remote_ref])? {
+        git(repo, &["diff", "--name-only", &remote_ref, "HEAD", "--", ENTRIES_DIR])?.lines().count()
+    } else {
+        git(repo, &["ls-files", "--", ENTRIES_DIR])?.lines().count()
+    };
+    git(repo, &["push", "--quiet", REMOTE_NAME, &format!("HEAD:refs/heads/{}", settings.branch)])?;
+    let state = full_state(&db.conn, device_id).map_err(|e| e.to_string())?;
+    record_all_shared(&db.conn, &state.entries)?;
+    debug!("Git sync finished: {:?}", report);
+    Ok(report)
+}
+
+/// Runs a sync with the configured repository, for the background reconciler.
+pub fn sync_configured(settings: &Settings) -> Result<SyncReport, String> {
+    sync_with_remote(&settings.git, &settings.sync.device_id, &settings.limits)
+}
+
+/// Commits the saved entry in the background, if Git sync is on. The mirror
+/// covers the whole journal, so changes made by commands that don't call this
+/// go into the next commit.
+pub fn commit_on_save() {
+    let settings = Settings::load();
+    if !settings.git.enabled {
+        return;
+    }
+    thread::spawn(move || {
+        let result = settings.git.repo().and_then(|repo| {
+            let _guard = GIT_LOCK.lock().map_err(|e| e.to_string())?;
+            commit_changes(repo, &settings.sync.device_id)
+        });
+        if let Err(e) = result {
+            warn!("Failed to commit to the journal repository: {}", e);
+        }
+    });
+}
+
+/// Creates the repository if needed and points it at `remote_url`.
+fn prepare_repo(repo: &Path, remote_url: Option<&str>, branch: &str) -> Result<(), String> {
+    fs::create_dir_all(repo).map_err(|e| format!("Failed to create {:?}: {}", repo, e))?;
+    if !repo.join(".git").exists() {
+        info!("Creating journal repository at {:?}", repo);
+        git(repo, &["init", "--quiet"])?;
+        git(repo, &["symbolic-ref", "HEAD", &format!("refs/heads/{}", branch)])?;
+    }
+    // Commits need an identity; don't override one the user already set up
+    if !git_check(repo, &["config", "user.name"])? {
+        git(repo, &["config", "user.name", "Journal"])?;
+    }
+    if !git_check(repo, &["config", "user.email"])? {
+        git(repo, &["config", "user.email", "journal@localhost"])?;
+    }
+    let has_remote = git_check(repo, &["remote", "get-url", REMOTE_NAME])?;
+    match (remote_url, has_remote) {
+        (Some(url), true) => git(repo, &["remote", "set-url", REMOTE_NAME, url]).map(|_| ()),
+        (Some(url), false) => git(repo, &["remote", "add", REMOTE_NAME, url]).map(|_| ()),
+        (None, _) => Ok(()),
+    }?;
+    // Merges need a commit to merge into, even for an empty journal
+    if !git_check(repo, &["rev-parse", "--verify", "--quiet", "HEAD"])? {
+        git(repo, &["commit", "--quiet", "--allow-empty", "-m", "Start journal"])?;
+    }
+    Ok(())
+}
+
+fn configure(path: String, remote_url: Option<String>, branch: Option<String>) -> Result<SyncReport, String> {
+    git(Path::new("."), &["--version"]).map_err(|_| "Git is not installed".to_string())?;
+    let mut settings = Settings::load();
+    ensure_device_id(&mut settings.sync);
+    let remote_url = remote_url.map(|url| url.trim().to_string()).filter(|url| !url.is_empty());
+    let branch = branch
+        .map(|branch| branch.trim().to_string())
+        .filter(|branch| !branch.is_empty())
+        .unwrap_or_else(|| GitSettings::default().branch);
+    prepare_repo(Path::new(&path), remote_url.as_deref(), &branch)?;
+    settings.git = GitSettings {
+        enabled: true,
+        repo_path: Some(path),
+        remote_url,
+        branch,
+    };
+    settings.save().map_err(|e| e.to_string())?;
+    sync_configured(&settings)
+}
+
+/// Starts mirroring entries into the Git repository at `path`, creating it
+/// if needed, and syncing through `remote_url` when one is given.
+#[command]
+pub async fn configure_git_sync(
+    path: String,
+    remote_url: Option<String>,
+    branch: Option<String>,
+) -> Result<SyncReport, String> {
+    tauri::async_runtime::spawn_blocking(move || configure(path, remote_url, branch))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+/// Stops committing entries. The repository and its history are left alone.
+#[command]
+pub fn disable_git_sync() -> Result<(), String> {
+    let mut settings = Settings::load();
+    settings.git.enabled = false;
+    settings.save().map_err(|e| e.to_string())
+}
+
+/// Commits, then pulls and pushes if a remote is configured.
+#[command]
+pub async fn git_sync_now() -> Result<SyncReport, String> {
+    tauri::async_runtime::spawn_blocking(|| {
+        let settings = Settings::load();
+        if !settings.git.enabled {
+            return Err("Git sync is not enabled".to_string());
+        }
+        sync_configured(&settings)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record() -> EntryRecord {
+        EntryRecord {
+            uuid: "0b7e".to_string(),
+            lamport: 12,
+            origin: "device-a".to_string(),
+            title: "A \"quoted\": title".to_string(),
+            body: "---\nFirst line\n\nSecond line".to_string(),
+            created_at: "2024-05-01T10:00:00+00:00".to_string(),
+            updated_at: "2024-05-01T10:05:00.000Z".to_string(),
+            mood_score: None,
+            mood_emoji: None,
+            tags: vec!["work".to_string(), "travel".to_string()],
+            local_date: Some("2024-05-01".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_markdown_round_trip() {
+        let markdown = to_markdown(&record()).unwrap();
+        assert!(markdown.starts_with("---\nuuid: \"0b7e\"\ntitle: \"A \\\"quoted\\\": title\"\n"));
+        assert!(!markdown.contains("mood_score"));
+        assert_eq!(from_markdown(&markdown).unwrap(), record());
+
+        let mut with_mood = record();
+        with_mood.mood_score = Some(4);
+        with_mood.mood_emoji = Some("🙂".to_string());
+        assert_eq!(from_markdown(&to_markdown(&with_mood).unwrap()).unwrap(), with_mood);
+    }
+
+    #[test]
+    fn test_from_markdown_rejects_other_files() {
+        assert!(from_markdown("# Notes\n\nNothing to see").is_err());
+        assert!(from_markdown("---\ntitle: unquoted\n---\n\nbody").is_err());
+    }
+
+    #[test]
+    fn test_parse_name_status_keeps_entry_files() {
+        let listing = "M\tentries/a.md\nD\tentries/b.md\nA\tentries/notes.txt\n";
+        assert_eq!(
+            parse_name_status(listing),
+            vec![('M', "entries/a.md".to_string()), ('D', "entries/b.md".to_string())]
+        );
+    }
+}
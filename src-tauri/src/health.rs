@@ -0,0 +1,339 @@
+use chrono::{NaiveDate, Utc};
+use log::debug;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use tauri::command;
+
+use crate::DatabaseManager;
+
+// Steps and sleep from a health app or wearable, imported from the CSV files
+// they export and kept as one figure a day, so they can be set against the
+// journal: whether mood and how much was written go with how active or rested
+// the day was. An export may list a day once or as many samples, a row per
+// walk or per stretch of sleep; the samples of a day are added up. Importing
+// a day again replaces its figure, so overlapping exports don't count twice.
+
+/// Fewer days than this say nothing about how two things go together.
+const MIN_CORRELATION_DAYS: usize = 7;
+const DAY_FORMATS: &[&str] = &["%Y-%m-%d", "%Y/%m/%d", "%m/%d/%Y", "%d.%m.%Y"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MetricKind {
+    Steps,
+    /// Hours asleep.
+    Sleep,
+}
+
+impl MetricKind {
+    const ALL: [MetricKind; 2] = [MetricKind::Steps, MetricKind::Sleep];
+
+    fn as_str(self) -> &'static str {
+        match self {
+            MetricKind::Steps => "steps",
+            MetricKind::Sleep => "sleep",
+        }
+    }
+
+    /// Words in the heading of the column with this kind's figures, best first.
+    fn column_words(self) -> &'static [&'static str] {
+        match self {
+            MetricKind::Steps => &["step"],
+            MetricKind::Sleep => &["asleep", "sleep", "duration", "hours", "minutes"],
+        }
+    }
+}
+
+/// What a metric is compared with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Measure {
+    /// The day's average mood score.
+    Mood,
+    /// Words written that day.
+    WordCount,
+}
+
+#[derive(Debug, Serialize)]
+pub struct HealthImportReport {
+    pub days: usize,
+    /// Rows without a readable date or figure.
+    pub skipped_rows: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Correlation {
+    pub metric: MetricKind,
+    pub measure: Measure,
+    /// Pearson's r, from -1 to 1; `None` when there are too few days or
+    /// either side never changes.
+    pub coefficient: Option<f64>,
+    /// Days with both a figure and an entry to compare it with.
+    pub days: usize,
+}
+
+/// The records of CSV `text`, with quoted fields unquoted. Blank lines are
+/// left out.
+fn csv_records(text: &str) -> Vec<Vec<String>> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = text.trim_start_matches('\u{feff}').chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => record.push(std::mem::take(&mut field)),
+            '\n' if !quoted => {
+                record.push(std::mem::take(&mut field));
+                records.push(std::mem::take(&mut record));
+            }
+            '\r' if !quoted => {}
+            c => field.push(c),
+        }
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+    records.retain(|record| record.iter().any(|field| !field.trim().is_empty()));
+    records
+}
+
+/// The day a date or timestamp in an export falls on, as written.
+fn parse_day(text: &str) -> Option<NaiveDate> {
+    let date = text.trim().split(['T', ' ']).next()?;
+    DAY_FORMATS.iter().find_map(|format| NaiveDate::parse_from_str(date, format).ok())
+}
+
+/// A figure as written, in the units it's stored in. Sleep can be given as
+/// `7:30`, or in minutes when the column says so.
+fn parse_value(text: &str, kind: MetricKind, minutes: bool) -> Option<f64> {
+    let text = text.trim().replace(',', "");
+    let value = if kind == MetricKind::Sleep && text.contains(':') {
+        let mut value = 0.0;
+        for (part, unit) in text.split(':').zip([1.0, 60.0, 3600.0]) {
+            value += part.parse::<f64>().ok()? / unit;
+        }
+        value
+    } else if minutes {
+        text.parse::<f64>().ok()? / 60.0
+    } else {
+        text.parse::<f64>().ok()?
+    };
+    (value.is_finite() && value >= 0.0).then_some(value)
+}
+
+/// The `kind` figure of each day in CSV `text`, and how many rows couldn't
+/// be read. The columns are found by their headings.
+fn daily_figures(text: &str, kind: MetricKind) -> Result<(BTreeMap<NaiveDate, f64>, usize), String> {
+    let records = csv_records(text);
+    let Some((header, rows)) = records.split_first() else {
+        return Err("The file is empty".to_string());
+    };
+    let headings: Vec<String> = header.iter().map(|heading| heading.trim().to_lowercase()).collect();
+    let date_column = headings
+        .iter()
+        .position(|heading| ["date", "day", "start"].iter().any(|word| heading.contains(word)))
+        .unwrap_or(0);
+    let column = |word: &str| (0..headings.len()).find(|&i| i != date_column && headings[i].contains(word));
+    let value_column = kind
+        .column_words()
+        .iter()
+        .find_map(|word| column(word))
+        .or_else(|| column("value"))
+        .ok_or_else(|| format!("No {} column in the file", kind.as_str()))?;
+    let minutes = headings[value_column].contains("min");
+
+    let mut figures = BTreeMap::new();
+    let mut skipped = 0;
+    for row in rows {
+        let day = row.get(date_column).and_then(|field| parse_day(field));
+        let value = row.get(value_column).and_then(|field| parse_value(field, kind, minutes));
+        match (day, value) {
+            (Some(day), Some(value)) => *figures.entry(day).or_insert(0.0) += value,
+            _ => skipped += 1,
+        }
+    }
+    Ok((figures, skipped))
+}
+
+fn store_figures(
+    conn: &rusqlite::Connection,
+    kind: MetricKind,
+    figures: &BTreeMap<NaiveDate, f64>,
+) -> rusqlite::Result<()> {
+    let tx = conn.unchecked_transaction()?;
+    let imported_at = Utc::now().to_rfc3339();
+    {
+        let mut insert = tx.prepare(
+            "INSERT OR REPLACE INTO daily_metrics (day, kind, value, imported_at) VALUES (?1, ?2, ?3, ?4)",
+        )?;
+        for (day, value) in figures {
+            insert.execute(rusqlite::params![day.to_string(), kind.as_str(), value, imported_at])?;
+        }
+    }
+    tx.commit()
+}
+
+/// Pearson's correlation coefficient of `pairs`.
+fn pearson(pairs: &[(f64, f64)]) -> Option<f64> {
+    if pairs.len() < MIN_CORRELATION_DAYS {
+        return None;
+    }
+    let n = pairs.len() as f64;
+    let mean_x = pairs.iter().map(|(x, _)| x).sum::<f64>() / n;
+    let mean_y = pairs.iter().map(|(_, y)| y).sum::<f64>() / n;
+    let (mut covariance, mut variance_x, mut variance_y) = (0.0, 0.0, 0.0);
+    for (x, y) in pairs {
+        covariance += (x - mean_x) * (y - mean_y);
+        variance_x += (x - mean_x).powi(2);
+        variance_y += (y - mean_y).powi(2);
+    }
+    if variance_x == 0.0 || variance_y == 0.0 {
+        return None;
+    }
+    Some(covariance / (variance_x * variance_y).sqrt())
+}
+
+fn correlations(conn: &rusqlite::Connection) -> rusqlite::Result<Vec<Correlation>> {
+    // Words written and mood scores (sum and count) of each local day.
+    let mut days: HashMap<NaiveDate, (f64, f64, u32)> = HashMap::new();
+    let mut stmt =
+        conn.prepare("SELECT local_date, word_count, mood_score FROM journal_entries WHERE local_date IS NOT NULL")?;
+    let rows = stmt.query_map([], |row| {
+        Ok((row.get::<_, String>(0)?, row.get::<_, Option<i64>>(1)?, row.get::<_, Option<i64>>(2)?))
+    })?;
+    for row in rows {
+        let (day, words, mood) = row?;
+        let Ok(day) = day.parse() else {
+            continue;
+        };
+        let totals = days.entry(day).or_insert((0.0, 0.0, 0));
+        totals.0 += words.unwrap_or(0) as f64;
+        if let Some(mood) = mood {
+            totals.1 += mood as f64;
+            totals.2 += 1;
+        }
+    }
+
+    let mut metrics: HashMap<&str, Vec<(NaiveDate, f64)>> = HashMap::new();
+    let mut stmt = conn.prepare("SELECT day, kind, value FROM daily_metrics ORDER BY day")?;
+    let rows = stmt.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get(2)?)))?;
+    let rows = rows.collect::<Result<Vec<(String, String, f64)>, _>>()?;
+    for (day, kind, value) in &rows {
+        if let Ok(day) = day.parse() {
+            metrics.entry(kind.as_str()).or_default().push((day, *value));
+        }
+    }
+
+    let mut correlations = Vec::new();
+    for metric in MetricKind::ALL {
+        let figures = metrics.get(metric.as_str()).map(Vec::as_slice).unwrap_or_default();
+        for measure in [Measure::Mood, Measure::WordCount] {
+            let pairs: Vec<(f64, f64)> = figures
+                .iter()
+                .filter_map(|(day, value)| {
+                    let &(words, mood_total, moods) = days.get(day)?;
+                    match measure {
+                        Measure::Mood if moods > 0 => Some((*value, mood_total / moods as f64)),
+                        Measure::Mood => None,
+                        Measure::WordCount => Some((*value, words)),
+                    }
+                })
+                .collect();
+            correlations.push(Correlation {
+                metric,
+                measure,
+                coefficient: pearson(&pairs),
+                days: pairs.len(),
+            });
+        }
+    }
+    Ok(correlations)
+}
+
+/// Imports the daily `kind` figures in CSV file `path`, replacing any
+/// imported before for the same days.
+#[command]
+pub fn import_health_csv(path: String, kind: MetricKind) -> Result<HealthImportReport, String> {
+    let text = fs::read_to_string(&path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let (figures, skipped_rows) = daily_figures(&text, kind)?;
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    store_figures(&db.conn, kind, &figures).map_err(|e| e.to_string())?;
+    debug!("Imported {} days of {} figures, skipped {} rows", figures.len(), kind.as_str(), skipped_rows);
+    Ok(HealthImportReport {
+        days: figures.len(),
+        skipped_rows,
+    })
+}
+
+/// How steps and sleep go with mood and words written, over the days that
+/// have both.
+#[command]
+pub fn get_correlations() -> Result<Vec<Correlation>, String> {
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    correlations(&db.conn).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use journal_core::insert_entry;
+
+    fn day(text: &str) -> NaiveDate {
+        text.parse().unwrap()
+    }
+
+    #[test]
+    fn test_daily_figures() {
+        let steps = "\u{feff}Start Date,End Date,\"Steps (count)\"\r\n\
+                     2024-03-05 08:00:00 -0800,2024-03-05 09:00:00 -0800,\"1,200\"\r\n\
+                     2024-03-05 18:00:00 -0800,2024-03-05 19:00:00 -0800,800\r\n\
+                     2024-03-06T10:00:00Z,2024-03-06T11:00:00Z,4000\r\n\
+                     yesterday,,100\r\n\r\n";
+        let (figures, skipped) = daily_figures(steps, MetricKind::Steps).unwrap();
+        assert_eq!(figures.into_iter().collect::<Vec<_>>(), [(day("2024-03-05"), 2000.0), (day("2024-03-06"), 4000.0)]);
+        assert_eq!(skipped, 1);
+
+        let sleep = "Start Time,End Time,Minutes Awake,Minutes Asleep\n03/05/2024 23:10,03/06/2024 07:00,20,450\n";
+        let (figures, _) = daily_figures(sleep, MetricKind::Sleep).unwrap();
+        assert_eq!(figures[&day("2024-03-05")], 7.5);
+        let (figures, _) = daily_figures("Date,Sleep\n2024-03-05,7:45\n", MetricKind::Sleep).unwrap();
+        assert_eq!(figures[&day("2024-03-05")], 7.75);
+        let error = daily_figures("Date,Mood\n2024-03-05,4\n", MetricKind::Steps).unwrap_err();
+        assert_eq!(error, "No steps column in the file");
+        assert!(daily_figures("", MetricKind::Steps).is_err());
+    }
+
+    #[test]
+    fn test_correlations() {
+        let db = DatabaseManager::open_in_memory().unwrap();
+        let mut figures = BTreeMap::new();
+        for i in 1..=8 {
+            let created_at = format!("2024-03-{:02}T12:00:00+00:00", i);
+            let id = insert_entry(&db.conn, "Day", &"word ".repeat(i * 10), &created_at).unwrap();
+            let mood = if i % 2 == 0 { 4 } else { 2 };
+            db.conn.execute("UPDATE journal_entries SET mood_score = ?1 WHERE id = ?2", [mood, id]).unwrap();
+            figures.insert(day(&format!("2024-03-{:02}", i)), 1000.0 * i as f64);
+        }
+        figures.insert(day("2024-04-01"), 500.0);
+        store_figures(&db.conn, MetricKind::Steps, &figures).unwrap();
+        store_figures(&db.conn, MetricKind::Steps, &figures).unwrap();
+
+        let correlations = correlations(&db.conn).unwrap();
+        let find = |metric, measure| correlations.iter().find(|c| c.metric == metric && c.measure == measure).unwrap();
+        let words = find(MetricKind::Steps, Measure::WordCount);
+        assert_eq!(words.days, 8);
+        assert!((words.coefficient.unwrap() - 1.0).abs() < 1e-9);
+        let mood = find(MetricKind::Steps, Measure::Mood).coefficient.unwrap();
+        assert!(mood > 0.0 && mood < 1.0);
+        let sleep = find(MetricKind::Sleep, Measure::Mood);
+        assert_eq!((sleep.days, sleep.coefficient), (0, None));
+    }
+}
@@ -0,0 +1,781 @@
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use log::debug;
+use roxmltree::{Document, Node};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use tauri::command;
+use zip::ZipArchive;
+
+use journal_core::{in_transaction, insert_entry, ErrorResponse};
+use journal_core::text::{strip_html, text_to_html};
+
+use crate::apple_notes::{AppleNotes, NotesApp};
+use crate::export::{newer_version_error, JSON_EXPORT_FORMAT, JSON_EXPORT_VERSION};
+use crate::file_drop::markdown_to_html;
+use crate::jobs::{Job, JobKind};
+use crate::notebooks::notebook_named;
+use crate::settings::Settings;
+use crate::stats::local_date;
+use crate::tags::{imported_tags, replace_tags};
+use crate::validation::clean_entry;
+use crate::{git_sync, markdown_mirror, recent_menu, DatabaseManager};
+
+// Journals kept in other apps: Journey's JSON export (a `.zip` of one file per
+// entry, or a single `.json`), a Diaro backup and a `jrnl` plain-text journal,
+// as well as this app's own JSON export.
+// Each reader only turns its format into entries; creating them is shared, and
+// a dry run stops before it so the user can see how many entries an export
+// holds, which of them the journal seems to have already and when they were
+// written before anything is written. The entries are created in one
+// transaction, so an import that fails or is cancelled partway leaves nothing
+// behind. Apple Notes, read from an export folder or from Notes itself, is in
+// `apple_notes`.
+
+/// Times `jrnl` has written between the brackets, newest default first.
+const JRNL_TIME_FORMATS: &[&str] = &[
+    "%Y-%m-%d %I:%M:%S %p",
+    "%Y-%m-%d %I:%M %p",
+    "%Y-%m-%d %H:%M:%S",
+    "%Y-%m-%d %H:%M",
+];
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportSource {
+    /// A JSON export from this app.
+    Journal,
+    Journey,
+    Diaro,
+    Jrnl,
+    /// A folder of `.html` or `.txt` notes exported from Apple Notes.
+    AppleNotes,
+    /// Notes itself, read through AppleScript on macOS.
+    NotesApp,
+}
+
+impl ImportSource {
+    pub fn importer(self) -> Box<dyn Importer> {
+        match self {
+            ImportSource::Journal => Box::new(JournalExport),
+            ImportSource::Journey => Box::new(Journey),
+            ImportSource::Diaro => Box::new(Diaro),
+            ImportSource::Jrnl => Box::new(Jrnl),
+            ImportSource::AppleNotes => Box::new(AppleNotes),
+            ImportSource::NotesApp => Box::new(NotesApp),
+        }
+    }
+
+    /// Whether the source can be read on this platform.
+    pub fn is_available(self) -> bool {
+        !matches!(self, ImportSource::NotesApp) || cfg!(target_os = "macos")
+    }
+}
+
+/// What an importer reads from.
+pub enum ImportInput {
+    /// A file with one of these extensions.
+    File(&'static [&'static str]),
+    Folder,
+    /// Nothing; the entries are read straight from the other app.
+    App,
+}
+
+pub trait Importer {
+    /// The app, as the user knows it.
+    fn name(&self) -> &'static str;
+    fn input(&self) -> ImportInput;
+    /// Every entry in the export at `path`, oldest first. `path` is empty
+    /// for importers that read from the app.
+    fn read(&self, path: &Path) -> Result<Vec<ImportedEntry>, String>;
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ImportedEntry {
+    /// Empty if the app had none; the entry is then named for its day.
+    pub title: String,
+    /// The editor's HTML.
+    pub body: String,
+    pub created_at: DateTime<Utc>,
+    pub tags: Vec<String>,
+    /// The notebook it goes in, created if the journal has none by that name.
+    pub notebook: Option<String>,
+}
+
+impl ImportedEntry {
+    /// The title it's saved with.
+    fn saved_title(&self) -> String {
+        match self.title.trim() {
+            // Named like the CLI names entries
+            "" => self.created_at.with_timezone(&Local).format("%B %-d, %Y").to_string(),
+            title => title.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImportReport {
+    /// Entries in the export.
+    pub found: usize,
+    /// Entries created; none on a dry run.
+    pub created: Vec<i32>,
+    /// What a dry run found; `None` when the entries were imported.
+    pub preview: Option<ImportPreview>,
+}
+
+/// What an import would do, from a dry run.
+#[derive(Debug, Default, Serialize)]
+pub struct ImportPreview {
+    pub entries: usize,
+    /// Entries that look like ones the journal already has.
+    pub duplicates: Vec<Duplicate>,
+    /// The first and last days the entries were written, `YYYY-MM-DD` in local time.
+    pub first_day: Option<String>,
+    pub last_day: Option<String>,
+    /// Notebooks the entries go in, which are created if the journal has none by that name.
+    pub notebooks: Vec<String>,
+    /// Tags as they'll be saved.
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Duplicate {
+    /// The imported entry's title.
+    pub title: String,
+    pub day: String,
+    /// The entry the journal already has.
+    pub existing_id: i32,
+}
+
+pub(crate) fn read_file(path: &Path) -> Result<String, String> {
+    fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))
+}
+
+fn is_zip(path: &Path) -> bool {
+    path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("zip"))
+}
+
+/// The names and contents of the files in the zip at `path` whose names end
+/// in `suffix`, in archive order. Other files, such as photos, are passed over.
+fn zipped_texts(path: &Path, suffix: &str) -> Result<Vec<(String, String)>, String> {
+    let file = File::open(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let mut archive = ZipArchive::new(file).map_err(|e| e.to_string())?;
+    let mut texts = Vec::new();
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i).map_err(|e| e.to_string())?;
+        if !file.is_file() || !file.name().to_ascii_lowercase().ends_with(suffix) {
+            continue;
+        }
+        let name = file.name().to_string();
+        let mut contents = String::new();
+        file.read_to_string(&mut contents).map_err(|e| format!("Failed to read {}: {}", name, e))?;
+        texts.push((name, contents));
+    }
+    Ok(texts)
+}
+
+fn from_millis(millis: i64) -> Result<DateTime<Utc>, String> {
+    DateTime::from_timestamp_millis(millis).ok_or_else(|| format!("Invalid date {}", millis))
+}
+
+/// This app's own JSON export.
+struct JournalExport;
+
+/// What the header of a JSON export says, all optional so anything else
+/// that's JSON can be recognised as not an export.
+#[derive(Deserialize)]
+struct JournalExportHeader {
+    format: Option<String>,
+    format_version: Option<u32>,
+    app_version: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct JournalExportEntry {
+    #[serde(default)]
+    title: String,
+    created_at: String,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    body: String,
+}
+
+fn parse_journal_export(json: &str) -> Result<Vec<ImportedEntry>, String> {
+    let mut value: serde_json::Value =
+        serde_json::from_str(json).map_err(|e| format!("The file isn't valid JSON: {}", e))?;
+    if !value.is_array() {
+        let header = JournalExportHeader::deserialize(&value).map_err(|e| e.to_string())?;
+        if header.format.as_deref() != Some(JSON_EXPORT_FORMAT) {
+            return Err("This JSON file isn't an export from Journal".to_string());
+        }
+        if header.format_version.unwrap_or(0) > JSON_EXPORT_VERSION {
+            return Err(newer_version_error("export", header.app_version.as_deref(), "import"));
+        }
+        value = value["entries"].take();
+    }
+    let entries: Vec<JournalExportEntry> =
+        serde_json::from_value(value).map_err(|e| format!("The export's entries can't be read: {}", e))?;
+    entries
+        .into_iter()
+        .map(|entry| {
+            let created_at = DateTime::parse_from_rfc3339(&entry.created_at)
+                .map_err(|_| format!("Invalid date {}", entry.created_at))?;
+            Ok(ImportedEntry {
+                title: entry.title,
+                body: entry.body,
+                created_at: created_at.with_timezone(&Utc),
+                tags: entry.tags,
+                notebook: None,
+            })
+        })
+        .collect()
+}
+
+impl Importer for JournalExport {
+    fn name(&self) -> &'static str {
+        "Journal"
+    }
+
+    fn input(&self) -> ImportInput {
+        ImportInput::File(&["json"])
+    }
+
+    fn read(&self, path: &Path) -> Result<Vec<ImportedEntry>, String> {
+        parse_journal_export(&read_file(path)?)
+    }
+}
+
+/// Journey, which writes each entry as a JSON object.
+struct Journey;
+
+#[derive(Deserialize)]
+struct JourneyEntry {
+    #[serde(default)]
+    text: String,
+    /// Milliseconds since the epoch.
+    date_journal: i64,
+    #[serde(default)]
+    tags: Vec<String>,
+    /// `html` for entries from its rich text editor; older ones are Markdown.
+    #[serde(default, rename = "type")]
+    kind: String,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum JourneyFile {
+    One(JourneyEntry),
+    Many(Vec<JourneyEntry>),
+}
+
+fn parse_journey(json: &str) -> Result<Vec<ImportedEntry>, String> {
+    let entries = match serde_json::from_str(json).map_err(|e| e.to_string())? {
+        JourneyFile::One(entry) => vec![entry],
+        JourneyFile::Many(entries) => entries,
+    };
+    entries
+        .into_iter()
+        .map(|entry| {
+            Ok(ImportedEntry {
+                title: String::new(),
+                body: if entry.kind.eq_ignore_ascii_case("html") { entry.text } else { markdown_to_html(&entry.text) },
+                created_at: from_millis(entry.date_journal)?,
+                tags: entry.tags,
+                notebook: None,
+            })
+        })
+        .collect()
+}
+
+impl Importer for Journey {
+    fn name(&self) -> &'static str {
+        "Journey"
+    }
+
+    fn input(&self) -> ImportInput {
+        ImportInput::File(&["zip", "json"])
+    }
+
+    fn read(&self, path: &Path) -> Result<Vec<ImportedEntry>, String> {
+        let mut entries = if is_zip(path) {
+            let mut entries = Vec::new();
+            for (name, json) in zipped_texts(path, ".json")? {
+                entries.extend(parse_journey(&json).map_err(|e| format!("{}: {}", name, e))?);
+            }
+            entries
+        } else {
+            parse_journey(&read_file(path)?)?
+        };
+        entries.sort_by_key(|entry| entry.created_at);
+        Ok(entries)
+    }
+}
+
+/// Diaro, whose backup is an XML dump of its tables, on its own or zipped
+/// with the photos.
+struct Diaro;
+
+fn child_text<'a>(node: Node<'a, '_>, name: &str) -> &'a str {
+    node.children()
+        .find(|child| child.has_tag_name(name))
+        .and_then(|child| child.text())
+        .unwrap_or_default()
+}
+
+/// The rows of the table called `name`.
+fn diaro_rows<'a, 'input>(doc: &'a Document<'input>, name: &'a str) -> impl Iterator<Item = Node<'a, 'input>> {
+    doc.root_element()
+        .children()
+        .filter(move |table| table.has_tag_name("table") && table.attribute("name") == Some(name))
+        .flat_map(|table| table.children().filter(|row| row.has_tag_name("r")))
+}
+
+fn parse_diaro(xml: &str) -> Result<Vec<ImportedEntry>, String> {
+    let doc = Document::parse(xml).map_err(|e| e.to_string())?;
+    let tag_titles: HashMap<&str, &str> = diaro_rows(&doc, "diaro_tags")
+        .map(|row| (child_text(row, "uid"), child_text(row, "title")))
+        .collect();
+    diaro_rows(&doc, "diaro_entries")
+        .map(|row| {
+            let date = child_text(row, "date");
+            let millis = date.trim().parse().map_err(|_| format!("Invalid date {}", date))?;
+            // Tags are listed by id, as `,id1,id2,`
+            let tags = child_text(row, "tags")
+                .split(',')
+                .filter_map(|uid| tag_titles.get(uid.trim()).map(|title| title.to_string()))
+                .collect();
+            Ok(ImportedEntry {
+                title: child_text(row, "title").trim().to_string(),
+                body: text_to_html(child_text(row, "text").trim()),
+                created_at: from_millis(millis)?,
+                tags,
+                notebook: None,
+            })
+        })
+        .collect()
+}
+
+impl Importer for Diaro {
+    fn name(&self) -> &'static str {
+        "Diaro"
+    }
+
+    fn input(&self) -> ImportInput {
+        ImportInput::File(&["zip", "xml"])
+    }
+
+    fn read(&self, path: &Path) -> Result<Vec<ImportedEntry>, String> {
+        let xml = if is_zip(path) {
+            let (_, xml) = zipped_texts(path, ".xml")?
+                .into_iter()
+                .next()
+                .ok_or_else(|| "The backup has no DiaroBackup.xml".to_string())?;
+            xml
+        } else {
+            read_file(path)?
+        };
+        let mut entries = parse_diaro(&xml)?;
+        entries.sort_by_key(|entry| entry.created_at);
+        Ok(entries)
+    }
+}
+
+/// jrnl's plain-text journal: each entry starts with `[date] Title.` on a
+/// line of its own, and `@words` are its tags.
+struct Jrnl;
+
+/// The time of a `[2024-03-05 09:12] …` line, read as local time, and the rest of the line.
+fn jrnl_heading(line: &str) -> Option<(DateTime<Utc>, &str)> {
+    let (date, rest) = line.strip_prefix('[')?.split_once(']')?;
+    let datetime = JRNL_TIME_FORMATS
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(date.trim(), format).ok())?;
+    let local = Local.from_local_datetime(&datetime).earliest()?;
+    Some((local.with_timezone(&Utc), rest.trim()))
+}
+
+/// Splits the first line of an entry after its first sentence, as jrnl does.
+/// A line without one is all title.
+fn split_title(line: &str) -> (&str, &str) {
+    let end = line.char_indices().find_map(|(i, c)| {
+        let next = line[i + c.len_utf8()..].chars().next();
+        (matches!(c, '.' | '?' | '!') && next.is_some_and(char::is_whitespace)).then_some(i + c.len_utf8())
+    });
+    match end {
+        Some(end) => (&line[..end], line[end..].trim()),
+        None => (line, ""),
+    }
+}
+
+/// The `@tags` in `text`, without the `@`.
+fn jrnl_tags(text: &str) -> Vec<String> {
+    text.split_whitespace()
+        .filter_map(|word| word.strip_prefix('@'))
+        .map(|tag| tag.trim_end_matches(|c: char| !c.is_alphanumeric() && c != '_' && c != '-'))
+        .filter(|tag| !tag.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+fn parse_jrnl(text: &str) -> Vec<ImportedEntry> {
+    let mut entries = Vec::new();
+    let mut current: Option<(DateTime<Utc>, &str, Vec<&str>)> = None;
+    let finish = |entries: &mut Vec<ImportedEntry>, (created_at, heading, lines): (DateTime<Utc>, &str, Vec<&str>)| {
+        // A starred entry ends its heading with ` *`
+        let heading = heading.strip_suffix(" *").unwrap_or(heading);
+        let (title, first_line) = split_title(heading);
+        let body = std::iter::once(first_line).chain(lines).collect::<Vec<_>>().join("\n");
+        entries.push(ImportedEntry {
+            title: title.to_string(),
+            tags: jrnl_tags(&format!("{}\n{}", title, body)),
+            body: text_to_html(body.trim()),
+            created_at,
+            notebook: None,
+        });
+    };
+    for line in text.lines() {
+        match jrnl_heading(line) {
+            Some((created_at, heading)) => {
+                if let Some(entry) = current.take() {
+                    finish(&mut entries, entry);
+                }
+                current = Some((created_at, heading, Vec::new()));
+            }
+            None => {
+                if let Some((_, _, lines)) = current.as_mut() {
+                    lines.push(line);
+                }
+            }
+        }
+    }
+    if let Some(entry) = current {
+        finish(&mut entries, entry);
+    }
+    entries
+}
+
+impl Importer for Jrnl {
+    fn name(&self) -> &'static str {
+        "jrnl"
+    }
+
+    fn input(&self) -> ImportInput {
+        ImportInput::File(&["txt"])
+    }
+
+    fn read(&self, path: &Path) -> Result<Vec<ImportedEntry>, String> {
+        let text = read_file(path)?;
+        let entries = parse_jrnl(&text);
+        if entries.is_empty() && !text.trim().is_empty() {
+            return Err("No jrnl entries found; they start with a line like [2024-03-05 09:12]".to_string());
+        }
+        Ok(entries)
+    }
+}
+
+/// Text of `html` with runs of whitespace as single spaces, to compare bodies by.
+fn plain_text(html: &str) -> String {
+    strip_html(html).split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// What importing `entries` would do, without writing anything. An entry
+/// counts as a duplicate of one written the same day with the same title,
+/// ignoring case, or the same text.
+pub fn preview_entries(conn: &rusqlite::Connection, entries: &[ImportedEntry]) -> Result<ImportPreview, String> {
+    let mut stmt = conn
+        .prepare("SELECT id, title, body, created_at FROM journal_entries")
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((row.get::<_, i32>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?, row.get::<_, String>(3)?))
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    let mut by_day: HashMap<NaiveDate, Vec<(i32, String, String)>> = HashMap::new();
+    for (id, title, body, created_at) in rows {
+        if let Some(day) = local_date(&created_at) {
+            by_day.entry(day).or_default().push((id, title.trim().to_lowercase(), plain_text(&body)));
+        }
+    }
+
+    let mut preview = ImportPreview {
+        entries: entries.len(),
+        ..ImportPreview::default()
+    };
+    let mut tags = Vec::new();
+    for entry in entries {
+        let day = entry.created_at.with_timezone(&Local).date_naive();
+        let title = entry.saved_title();
+        let (lower_title, text) = (title.to_lowercase(), plain_text(&entry.body));
+        let existing = by_day.get(&day).into_iter().flatten().find(|(_, existing_title, existing_text)| {
+            *existing_title == lower_title || (!text.is_empty() && *existing_text == text)
+        });
+        if let Some((existing_id, _, _)) = existing {
+            preview.duplicates.push(Duplicate {
+                title,
+                day: day.format("%Y-%m-%d").to_string(),
+                existing_id: *existing_id,
+            });
+        }
+        tags.extend(entry.tags.iter().cloned());
+        if let Some(name) = entry.notebook.as_deref().map(str::trim).filter(|name| !name.is_empty()) {
+            if !preview.notebooks.iter().any(|notebook| notebook == name) {
+                preview.notebooks.push(name.to_string());
+            }
+        }
+    }
+    let days = || entries.iter().map(|entry| entry.created_at.with_timezone(&Local).date_naive());
+    preview.first_day = days().min().map(|day| day.format("%Y-%m-%d").to_string());
+    preview.last_day = days().max().map(|day| day.format("%Y-%m-%d").to_string());
+    preview.tags = imported_tags(&tags);
+    Ok(preview)
+}
+
+/// Creates `entries` in one transaction and returns their ids. Cancelling
+/// `job` rolls back the entries created so far.
+pub fn import_entries(conn: &rusqlite::Connection, entries: &[ImportedEntry], job: &Job) -> Result<Vec<i32>, String> {
+    let limits = Settings::load().limits;
+    in_transaction(conn, |tx| -> Result<_, ErrorResponse> {
+        let mut created = Vec::new();
+        let mut notebooks: HashMap<&str, i32> = HashMap::new();
+        for (i, entry) in entries.iter().enumerate() {
+            job.step(i, entries.len(), Some(&entry.saved_title()))?;
+            let created_at = entry.created_at.to_rfc3339();
+            let (title, body) = clean_entry(&entry.saved_title(), &entry.body, &limits)
+                .map_err(|e| format!("{} ('{}')", e, entry.saved_title()))?;
+            let id = insert_entry(tx, &title, &body, &created_at)?;
+            replace_tags(tx, id, &imported_tags(&entry.tags))?;
+            if let Some(name) = entry.notebook.as_deref().map(str::trim).filter(|name| !name.is_empty()) {
+                let notebook_id = match notebooks.get(name) {
+                    Some(notebook_id) => *notebook_id,
+                    None => {
+                        let notebook_id = notebook_named(tx, name)?;
+                        notebooks.insert(name, notebook_id);
+                        notebook_id
+                    }
+                };
+                tx.execute(
+                    "UPDATE journal_entries SET notebook_id = ?1 WHERE id = ?2",
+                    rusqlite::params![notebook_id, id],
+                )?;
+            }
+            created.push(id);
+        }
+        Ok(created)
+    })
+    .map_err(|e| e.message)
+}
+
+/// Reads the `source` export at `path` and creates an entry for each entry in
+/// it. With `dry_run`, nothing is written and the report says what would be.
+/// `path` isn't needed for sources read from the app. An import runs as a job.
+#[command]
+pub fn import_journal(source: ImportSource, path: Option<String>, dry_run: bool) -> Result<ImportReport, String> {
+    let importer = source.importer();
+    let path = match (importer.input(), path) {
+        (ImportInput::App, _) => PathBuf::new(),
+        (_, Some(path)) => PathBuf::from(path),
+        (_, None) => return Err(format!("Choose the {} export to import", importer.name())),
+    };
+    let entries = importer.read(&path)?;
+    let mut report = ImportReport {
+        found: entries.len(),
+        created: Vec::new(),
+        preview: None,
+    };
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    if dry_run {
+        report.preview = Some(preview_entries(&db.conn, &entries)?);
+        return Ok(report);
+    }
+    if entries.is_empty() {
+        return Ok(report);
+    }
+    let label = format!("Importing from {}", importer.name());
+    report.created = Job::run(JobKind::Import, label, |job| import_entries(&db.conn, &entries, job))?;
+    debug!("Imported {} {} entries from {}", report.created.len(), importer.name(), path.display());
+    git_sync::commit_on_save();
+    markdown_mirror::refresh();
+    recent_menu::refresh();
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::NEWER_VERSION;
+    use std::io::Write;
+    use zip::write::SimpleFileOptions;
+    use zip::ZipWriter;
+
+    #[test]
+    fn test_parse_journey() {
+        let entries = parse_journey(
+            r#"[{"text": "Trams and **tiles**", "date_journal": 1709640000000, "tags": ["travel", "city break"]},
+                {"text": "<p>Home</p>", "date_journal": 1712059200000, "type": "html"}]"#,
+        )
+        .unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].body, "<p>Trams and <strong>tiles</strong></p>\n");
+        assert_eq!(entries[0].created_at.to_rfc3339(), "2024-03-05T12:00:00+00:00");
+        assert_eq!(entries[0].tags, ["travel", "city break"]);
+        assert_eq!(entries[1].body, "<p>Home</p>");
+        assert_eq!(parse_journey(r#"{"text": "One", "date_journal": 0}"#).unwrap().len(), 1);
+        assert!(parse_journey(r#"{"text": "Undated"}"#).is_err());
+    }
+
+    #[test]
+    fn test_parse_journal_export() {
+        let entry = r#"{"uuid": null, "title": "Lisbon", "created_at": "2024-03-05T12:00:00+00:00",
+                        "tags": ["travel"], "body": "<p>Trams</p>"}"#;
+        let current =
+            format!(r#"{{"format": "journal", "format_version": 1, "app_version": "1.0.0", "entries": [{}]}}"#, entry);
+        let entries = parse_journal_export(&current).unwrap();
+        assert_eq!(entries[0].title, "Lisbon");
+        assert_eq!(entries[0].tags, ["travel"]);
+        assert_eq!(entries[0].created_at.to_rfc3339(), "2024-03-05T12:00:00+00:00");
+        // Exports from before the header
+        assert_eq!(parse_journal_export(&format!("[{}]", entry)).unwrap().len(), 1);
+
+        let newer = r#"{"format": "journal", "format_version": 2, "app_version": "9.0.0", "entries": []}"#;
+        let error = parse_journal_export(newer).unwrap_err();
+        assert!(error.starts_with(NEWER_VERSION) && error.contains("(9.0.0)"));
+        assert!(parse_journal_export(r#"{"text": "Journey"}"#).unwrap_err().contains("isn't an export"));
+    }
+
+    #[test]
+    fn test_read_journey_zip() {
+        let path = std::env::temp_dir().join(format!("journal-journey-{}.zip", std::process::id()));
+        let mut zip = ZipWriter::new(File::create(&path).unwrap());
+        for (name, contents) in [
+            ("b.json", r#"{"text": "Later", "date_journal": 1712059200000}"#),
+            ("b-photo.jpg", "not json"),
+            ("a.json", r#"{"text": "Earlier", "date_journal": 1709640000000}"#),
+        ] {
+            zip.start_file(name, SimpleFileOptions::default()).unwrap();
+            zip.write_all(contents.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap();
+
+        let entries = Journey.read(&path).unwrap();
+        let bodies: Vec<&str> = entries.iter().map(|entry| entry.body.as_str()).collect();
+        assert_eq!(bodies, ["<p>Earlier</p>\n", "<p>Later</p>\n"]);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_parse_diaro() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<data version="2">
+<table name="diaro_tags">
+<r><uid>t1</uid><title>travel</title></r>
+<r><uid>t2</uid><title>food</title></r>
+</table>
+<table name="diaro_entries">
+<r><uid>e1</uid><date>1709640000000</date><title>Lisbon</title><text>Trams &amp; tiles
+
+Pastéis</text><tags>,t1,t2,</tags></r>
+<r><uid>e2</uid><date>1712059200000</date><title></title><text>Home</text><tags></tags></r>
+</table>
+</data>"#;
+        let entries = parse_diaro(xml).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].title, "Lisbon");
+        assert_eq!(entries[0].body, text_to_html("Trams & tiles\n\nPastéis"));
+        assert_eq!(entries[0].tags, ["travel", "food"]);
+        assert_eq!(entries[1].title, "");
+        assert!(entries[1].tags.is_empty());
+        assert!(parse_diaro("<data><table name=\"diaro_entries\"><r><date>soon</date></r></table></data>").is_err());
+    }
+
+    #[test]
+    fn test_split_title() {
+        assert_eq!(split_title("Lisbon. Trams and tiles."), ("Lisbon.", "Trams and tiles."));
+        assert_eq!(split_title("Version 1.2 is out"), ("Version 1.2 is out", ""));
+        assert_eq!(split_title("What now? Rain"), ("What now?", "Rain"));
+    }
+
+    #[test]
+    fn test_parse_jrnl() {
+        let text = "[2024-03-05 09:12] Lisbon. Trams with @anna.\nTiles everywhere @travel\n\n\
+                    [2024-03-06 07:30:00 PM] Home at last *\n[not a date] still home\n";
+        let entries = parse_jrnl(text);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].title, "Lisbon.");
+        assert_eq!(entries[0].body, text_to_html("Trams with @anna.\nTiles everywhere @travel"));
+        assert_eq!(entries[0].tags, ["anna", "travel"]);
+        let morning = Local.with_ymd_and_hms(2024, 3, 5, 9, 12, 0).unwrap();
+        assert_eq!(entries[0].created_at, morning.with_timezone(&Utc));
+        assert_eq!(entries[1].title, "Home at last");
+        assert_eq!(entries[1].body, text_to_html("[not a date] still home"));
+        let evening = Local.with_ymd_and_hms(2024, 3, 6, 19, 30, 0).unwrap();
+        assert_eq!(entries[1].created_at, evening.with_timezone(&Utc));
+        assert!(parse_jrnl("no entries here").is_empty());
+    }
+
+    #[test]
+    fn test_import_entries() {
+        let db = DatabaseManager::open_in_memory().unwrap();
+        let created_at = Local.with_ymd_and_hms(2024, 3, 5, 12, 0, 0).unwrap().with_timezone(&Utc);
+        let entries = [ImportedEntry {
+            title: String::new(),
+            body: "<p>Trams</p>".to_string(),
+            created_at,
+            tags: vec!["city break".to_string(), "#travel".to_string()],
+            notebook: Some("Travel".to_string()),
+        }];
+        let ids = import_entries(&db.conn, &entries, &Job::start(JobKind::Import, "Test")).unwrap();
+        assert_eq!(ids.len(), 1);
+        let title: String = db
+            .conn
+            .query_row("SELECT title FROM journal_entries WHERE id = ?1", [ids[0]], |row| row.get(0))
+            .unwrap();
+        assert_eq!(title, "March 5, 2024");
+        assert_eq!(crate::tags::tags_for_entry(&db.conn, ids[0]).unwrap(), ["city-break", "travel"]);
+        let notebook: String = db
+            .conn
+            .query_row(
+                "SELECT n.name FROM journal_entries e JOIN notebooks n ON n.id = e.notebook_id WHERE e.id = ?1",
+                [ids[0]],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(notebook, "Travel");
+    }
+
+    #[test]
+    fn test_preview_entries() {
+        let db = DatabaseManager::open_in_memory().unwrap();
+        let noon = |day: u32| Local.with_ymd_and_hms(2024, 3, day, 12, 0, 0).unwrap().with_timezone(&Utc);
+        insert_entry(&db.conn, "Lisbon", "<p>Trams</p>", &noon(5).to_rfc3339()).unwrap();
+        insert_entry(&db.conn, "Evening", "<p>Fado  and wine</p>", &noon(6).to_rfc3339()).unwrap();
+        let entry = |title: &str, body: &str, day: u32, notebook: Option<&str>| ImportedEntry {
+            title: title.to_string(),
+            body: body.to_string(),
+            created_at: noon(day),
+            tags: vec!["Travel".to_string()],
+            notebook: notebook.map(str::to_string),
+        };
+        let entries = [
+            entry("lisbon", "<p>Something else</p>", 5, Some("Trips")),
+            entry("", "<p>Fado and wine</p>", 6, Some("Trips")),
+            entry("Lisbon", "<p>Trams</p>", 9, None),
+            entry("Porto", "<p>Port</p>", 2, Some("Travel")),
+        ];
+        let preview = preview_entries(&db.conn, &entries).unwrap();
+        assert_eq!(preview.entries, 4);
+        let duplicates: Vec<(&str, &str)> =
+            preview.duplicates.iter().map(|d| (d.title.as_str(), d.day.as_str())).collect();
+        assert_eq!(duplicates, [("lisbon", "2024-03-05"), ("March 6, 2024", "2024-03-06")]);
+        assert_eq!(preview.first_day.as_deref(), Some("2024-03-02"));
+        assert_eq!(preview.last_day.as_deref(), Some("2024-03-09"));
+        assert_eq!(preview.notebooks, ["Trips", "Travel"]);
+        assert_eq!(preview.tags, ["Travel"]);
+        let count: i64 = db.conn.query_row("SELECT COUNT(*) FROM journal_entries", [], |row| row.get(0)).unwrap();
+        assert_eq!(count, 2);
+    }
+}
@@ -0,0 +1,345 @@
+use chrono::Utc;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use tauri::{command, AppHandle, Emitter};
+
+use journal_core::profiles::profile_dir;
+
+// The operations that take a while: imports, exports, reading photos,
+// transcribing memos, reindexing, cloud backups and database maintenance.
+// Each run is a job with a number. While it runs, a `job-progress` event
+// tells the window how far along it is and which entry it's on, so several
+// can be shown at once and any of them cancelled with `cancel_job`. A
+// cancelled job stops at its next step. The list is kept in `jobs.json` next
+// to the database, so a reloaded window can ask for it with `list_jobs`, and
+// a job cut off when the app quit shows up as interrupted.
+
+const JOBS_FILE_NAME: &str = "jobs.json";
+/// How many finished jobs are remembered.
+const KEPT_JOBS: usize = 50;
+
+pub const CANCELLED: &str = "Cancelled";
+
+/// Set once the app is running; until then jobs are neither shown nor saved.
+static APP: OnceLock<AppHandle> = OnceLock::new();
+static NEXT_JOB: AtomicU64 = AtomicU64::new(1);
+/// Every job this run and the ones kept from before, oldest first.
+static JOBS: Mutex<Vec<JobInfo>> = Mutex::new(Vec::new());
+/// The cancel flag of every job running now, by id.
+static RUNNING: Mutex<BTreeMap<u64, Arc<AtomicBool>>> = Mutex::new(BTreeMap::new());
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobKind {
+    Import,
+    Export,
+    Ocr,
+    Transcription,
+    Reindex,
+    Backup,
+    Maintenance,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobState {
+    Running,
+    Done,
+    Failed,
+    Cancelled,
+    /// Still running when the app last quit.
+    Interrupted,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobInfo {
+    pub id: u64,
+    pub kind: JobKind,
+    /// What the job is doing, as shown to the user.
+    pub label: String,
+    pub state: JobState,
+    pub percent: u8,
+    /// The entry being worked on; `None` when the job doesn't go entry by entry.
+    pub current: Option<String>,
+    /// Why a failed job failed.
+    pub error: Option<String>,
+    pub started_at: String,
+    pub finished_at: Option<String>,
+}
+
+/// The event exports sent before there were other jobs, still sent for them.
+#[derive(Debug, Clone, Serialize)]
+struct ExportProgress {
+    job_id: u64,
+    percent: u8,
+    current: Option<String>,
+}
+
+/// Writes the list to the profile's folder.
+fn save(jobs: &[JobInfo]) {
+    let result = profile_dir()
+        .map_err(|e| e.to_string())
+        .and_then(|dir| {
+            let json = serde_json::to_vec_pretty(jobs).map_err(|e| e.to_string())?;
+            fs::write(dir.join(JOBS_FILE_NAME), json).map_err(|e| e.to_string())
+        });
+    if let Err(e) = result {
+        warn!("Failed to save the job list: {}", e);
+    }
+}
+
+/// Applies `change` to job `id` in the list and tells the window. The list
+/// is saved when `change` returns true, so not on every small step.
+fn update(id: u64, change: impl FnOnce(&mut JobInfo) -> bool) {
+    let Ok(mut jobs) = JOBS.lock() else {
+        return;
+    };
+    let Some(info) = jobs.iter_mut().find(|info| info.id == id) else {
+        return;
+    };
+    let changed = change(info);
+    let info = info.clone();
+    let Some(app) = APP.get() else {
+        return;
+    };
+    if changed {
+        save(&jobs);
+    }
+    drop(jobs);
+    if info.kind == JobKind::Export {
+        let progress = ExportProgress {
+            job_id: info.id,
+            percent: info.percent,
+            current: info.current.clone(),
+        };
+        let _ = app.emit("export-progress", progress);
+    }
+    let _ = app.emit("job-progress", info);
+}
+
+/// `jobs` as read back from disk: the ones that were running didn't finish.
+fn restored(mut jobs: Vec<JobInfo>) -> Vec<JobInfo> {
+    for info in &mut jobs {
+        if info.state == JobState::Running {
+            info.state = JobState::Interrupted;
+            info.current = None;
+        }
+    }
+    jobs
+}
+
+/// One run of a long operation, from start to finish.
+pub struct Job {
+    id: u64,
+    cancelled: Arc<AtomicBool>,
+    finished: bool,
+}
+
+impl Job {
+    /// Starts a job of `kind` and adds it to the list.
+    pub fn start(kind: JobKind, label: impl Into<String>) -> Self {
+        let id = NEXT_JOB.fetch_add(1, Ordering::SeqCst);
+        let cancelled = Arc::new(AtomicBool::new(false));
+        if let Ok(mut running) = RUNNING.lock() {
+            running.insert(id, cancelled.clone());
+        }
+        if let Ok(mut jobs) = JOBS.lock() {
+            jobs.push(JobInfo {
+                id,
+                kind,
+                label: label.into(),
+                state: JobState::Running,
+                percent: 0,
+                current: None,
+                error: None,
+                started_at: Utc::now().to_rfc3339(),
+                finished_at: None,
+            });
+            let finished = jobs.iter().filter(|info| info.state != JobState::Running).count();
+            let mut excess = finished.saturating_sub(KEPT_JOBS);
+            jobs.retain(|info| {
+                let forgotten = excess > 0 && info.state != JobState::Running;
+                if forgotten {
+                    excess -= 1;
+                }
+                !forgotten
+            });
+        }
+        update(id, |_| true);
+        Job {
+            id,
+            cancelled,
+            finished: false,
+        }
+    }
+
+    /// Runs `work` as a job of `kind`, which ends as `work` does.
+    pub fn run<T>(
+        kind: JobKind,
+        label: impl Into<String>,
+        work: impl FnOnce(&Job) -> Result<T, String>,
+    ) -> Result<T, String> {
+        let job = Job::start(kind, label);
+        let result = work(&job);
+        job.finish(result)
+    }
+
+    /// Reports that `done` of `total` steps are finished and `current` is
+    /// next, or fails with `CANCELLED` if the job was cancelled.
+    pub fn step(&self, done: usize, total: usize, current: Option<&str>) -> Result<(), String> {
+        if self.is_cancelled() {
+            return Err(CANCELLED.to_string());
+        }
+        let percent = (done * 100).checked_div(total).unwrap_or(100).min(100) as u8;
+        update(self.id, |info| {
+            let changed = info.percent != percent;
+            info.percent = percent;
+            info.current = current.map(str::to_string);
+            changed
+        });
+        Ok(())
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Ends the job as `result` says and passes it on. An error after the job
+    /// was cancelled becomes `CANCELLED`.
+    pub fn finish<T>(mut self, result: Result<T, String>) -> Result<T, String> {
+        let result = match result {
+            Err(_) if self.is_cancelled() => Err(CANCELLED.to_string()),
+            result => result,
+        };
+        let (state, error) = match &result {
+            Ok(_) => (JobState::Done, None),
+            Err(e) if e == CANCELLED => (JobState::Cancelled, None),
+            Err(e) => (JobState::Failed, Some(e.clone())),
+        };
+        self.end(state, error);
+        result
+    }
+
+    fn end(&mut self, state: JobState, error: Option<String>) {
+        self.finished = true;
+        if let Ok(mut running) = RUNNING.lock() {
+            running.remove(&self.id);
+        }
+        update(self.id, |info| {
+            info.state = state;
+            if state == JobState::Done {
+                info.percent = 100;
+            }
+            info.current = None;
+            info.error = error;
+            info.finished_at = Some(Utc::now().to_rfc3339());
+            true
+        });
+    }
+}
+
+impl Drop for Job {
+    fn drop(&mut self) {
+        if !self.finished {
+            self.end(JobState::Failed, Some("Stopped unexpectedly".to_string()));
+        }
+    }
+}
+
+/// Shows jobs in the window of `app` and reads back the list kept from
+/// before. Called once from setup.
+pub fn start(app: &AppHandle) {
+    let kept = profile_dir()
+        .ok()
+        .and_then(|dir| fs::read(dir.join(JOBS_FILE_NAME)).ok())
+        .and_then(|json| serde_json::from_slice::<Vec<JobInfo>>(&json).ok())
+        .map(restored)
+        .unwrap_or_default();
+    if let Some(last) = kept.iter().map(|info| info.id).max() {
+        NEXT_JOB.fetch_max(last + 1, Ordering::SeqCst);
+    }
+    if let Ok(mut jobs) = JOBS.lock() {
+        jobs.splice(0..0, kept);
+        save(&jobs);
+    }
+    let _ = APP.set(app.clone());
+}
+
+/// Every job running now and the last ones to finish, newest first.
+#[command]
+pub fn list_jobs() -> Vec<JobInfo> {
+    JOBS.lock().map(|jobs| jobs.iter().rev().cloned().collect()).unwrap_or_default()
+}
+
+/// Stops job `id` at its next step. Returns false if it isn't running.
+#[command]
+pub fn cancel_job(id: u64) -> bool {
+    let running = RUNNING.lock().map(|running| running.get(&id).cloned()).ok().flatten();
+    match running {
+        Some(cancelled) => {
+            cancelled.store(true, Ordering::SeqCst);
+            true
+        }
+        None => false,
+    }
+}
+
+/// `cancel_job`, by the name exports used before there were other jobs.
+#[command]
+pub fn cancel_export(job_id: u64) -> bool {
+    cancel_job(job_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info(id: u64) -> JobInfo {
+        list_jobs().into_iter().find(|info| info.id == id).unwrap()
+    }
+
+    #[test]
+    fn test_cancel_job() {
+        let job = Job::start(JobKind::Export, "PDF");
+        let id = job.id;
+        assert!(job.step(1, 2, Some("Lisbon")).is_ok());
+        assert_eq!((info(id).percent, info(id).current.as_deref()), (50, Some("Lisbon")));
+        assert!(cancel_job(id));
+        assert_eq!(job.step(2, 2, None), Err(CANCELLED.to_string()));
+        let result: Result<(), String> = job.finish(Err("Failed to write".to_string()));
+        assert_eq!(result, Err(CANCELLED.to_string()));
+        assert_eq!(info(id).state, JobState::Cancelled);
+        assert!(!cancel_job(id));
+    }
+
+    #[test]
+    fn test_run() {
+        let id = Job::run(JobKind::Reindex, "Spotlight", |job| Ok(job.id)).unwrap();
+        assert_eq!((info(id).state, info(id).percent), (JobState::Done, 100));
+        let job = Job::start(JobKind::Ocr, "Photos");
+        let id = job.id;
+        assert!(job.finish::<()>(Err("Tesseract is missing".to_string())).is_err());
+        assert_eq!((info(id).state, info(id).error.as_deref()), (JobState::Failed, Some("Tesseract is missing")));
+        let job = Job::start(JobKind::Import, "Folder");
+        let id = job.id;
+        drop(job);
+        assert_eq!(info(id).state, JobState::Failed);
+    }
+
+    #[test]
+    fn test_restored() {
+        let json = r#"[{"id": 3, "kind": "import", "label": "Folder", "state": "running", "percent": 40,
+                        "current": "Lisbon", "error": null, "started_at": "2024-03-05T12:00:00Z", "finished_at": null},
+                       {"id": 4, "kind": "export", "label": "PDF", "state": "done", "percent": 100,
+                        "current": null, "error": null, "started_at": "2024-03-05T12:00:00Z",
+                        "finished_at": "2024-03-05T12:01:00Z"}]"#;
+        let jobs = restored(serde_json::from_str(json).unwrap());
+        assert_eq!(jobs[0].state, JobState::Interrupted);
+        assert_eq!(jobs[0].current, None);
+        assert_eq!(jobs[1].state, JobState::Done);
+    }
+}
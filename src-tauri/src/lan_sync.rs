@@ -0,0 +1,666 @@
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::{Aead, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Nonce};
+use hkdf::Hkdf;
+use hmac::{Hmac, Mac};
+use log::{debug, error, info, warn};
+use mdns_sd::{ServiceDaemon, ServiceEvent, ServiceInfo};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::io::{Read, Write};
+use std::net::{IpAddr, SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use tauri::{command, AppHandle, Emitter};
+
+use journal_core::keychain::KeychainManager;
+
+use crate::conflicts::record_all_shared;
+use crate::crypto;
+use crate::settings::{LimitSettings, Settings, SyncSettings};
+use crate::sync::{ensure_device_id, full_state, merge_state, Changeset, SyncReport};
+use crate::DatabaseManager;
+
+// Direct device-to-device sync on the local network, with no account or
+// store in between. Devices advertise themselves over mDNS; a sync is one
+// TCP connection on which both sides prove they know the shared LAN
+// passphrase (HMAC challenge-response over fresh nonces from each side),
+// derive per-direction session keys from it, and swap their full state as
+// AES-GCM frames. Merging is the same Lamport merge the store-based sync uses.
+//
+// The key is derived from the passphrase with the listening device's own
+// random salt, which it tells the connecting device. The connecting device
+// proves it has the key first, and the listener says nothing that depends on
+// the key until that proof checks out, so a stranger on the network gets
+// nothing to guess the passphrase against.
+
+const SERVICE_TYPE: &str = "_journalsync._tcp.local.";
+const PROTOCOL_VERSION: u32 = 2;
+pub(crate) const PASSPHRASE_ACCOUNT: &str = "journal_lan_sync_passphrase";
+const MIN_PASSPHRASE_CHARS: usize = 8;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 32;
+/// Largest handshake message; anyone on the network can send one.
+const MAX_HANDSHAKE_FRAME_BYTES: usize = 4 * 1024;
+/// Largest message once the peer has proved it knows the passphrase.
+const MAX_FRAME_BYTES: usize = 256 * 1024 * 1024;
+const DISCOVERY_TIMEOUT: Duration = Duration::from_secs(3);
+/// How long a connecting device has to finish proving itself.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+const IO_TIMEOUT: Duration = Duration::from_secs(30);
+const ACCEPT_POLL_INTERVAL: Duration = Duration::from_millis(250);
+/// Connections still proving themselves at once; more are turned away.
+const MAX_HANDSHAKES: usize = 4;
+
+static SERVER: Mutex<Option<LanServer>> = Mutex::new(None);
+static HANDSHAKES: AtomicUsize = AtomicUsize::new(0);
+
+struct LanServer {
+    daemon: ServiceDaemon,
+    fullname: String,
+    stop: Arc<AtomicBool>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LanPeer {
+    device_id: String,
+    name: String,
+    address: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ClientHello {
+    version: u32,
+    device_id: String,
+    nonce: String,
+}
+
+/// Carries nothing derived from the key: the client hasn't proved itself yet.
+#[derive(Serialize, Deserialize)]
+struct ServerHello {
+    device_id: String,
+    nonce: String,
+    /// Hex salt the server's key is derived with.
+    salt: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Proof {
+    proof: String,
+}
+
+/// The listening side's key, derived once when it starts.
+struct ServerKey {
+    salt: Vec<u8>,
+    key: [u8; 32],
+}
+
+/// One of the `MAX_HANDSHAKES` places for a connection that hasn't proved
+/// itself yet, given back when dropped.
+struct HandshakeSlot;
+
+impl HandshakeSlot {
+    fn take() -> Option<Self> {
+        HANDSHAKES
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| (n < MAX_HANDSHAKES).then_some(n + 1))
+            .ok()
+            .map(|_| HandshakeSlot)
+    }
+}
+
+impl Drop for HandshakeSlot {
+    fn drop(&mut self) {
+        HANDSHAKES.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// The nonces and device ids both sides agreed on during the handshake.
+struct Transcript {
+    client_nonce: Vec<u8>,
+    server_nonce: Vec<u8>,
+    client_id: String,
+    server_id: String,
+}
+
+impl Transcript {
+    fn mac(&self, key: &[u8; 32], role: &str) -> Hmac<Sha256> {
+        let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(key).expect("HMAC accepts any key length");
+        for part in [
+            role.as_bytes(),
+            &self.client_nonce,
+            &self.server_nonce,
+            self.client_id.as_bytes(),
+            self.server_id.as_bytes(),
+        ] {
+            // Length-prefixed so no two transcripts serialize the same way
+            mac.update(&(part.len() as u32).to_be_bytes());
+            mac.update(part);
+        }
+        mac
+    }
+
+    fn proof(&self, key: &[u8; 32], role: &str) -> String {
+        hex::encode(self.mac(key, role).finalize().into_bytes())
+    }
+
+    fn verify(&self, key: &[u8; 32], role: &str, proof: &str) -> Result<(), String> {
+        let proof = hex::decode(proof).map_err(|_| "Malformed handshake".to_string())?;
+        self.mac(key, role)
+            .verify_slice(&proof)
+            .map_err(|_| "The other device uses a different LAN sync passphrase".to_string())
+    }
+
+    fn session_cipher(&self, key: &[u8; 32], direction: &str) -> Result<Aes256Gcm, String> {
+        let salt = [self.client_nonce.as_slice(), self.server_nonce.as_slice()].concat();
+        let mut session_key = [0u8; 32];
+        Hkdf::<Sha256>::new(Some(&salt), key)
+            .expand(direction.as_bytes(), &mut session_key)
+            .map_err(|e| e.to_string())?;
+        Aes256Gcm::new_from_slice(&session_key).map_err(|e| e.to_string())
+    }
+}
+
+fn random_nonce() -> Vec<u8> {
+    let mut nonce = vec![0u8; NONCE_LEN];
+    OsRng.fill_bytes(&mut nonce);
+    nonce
+}
+
+/// `value` from a handshake, which must decode to `len` bytes.
+fn decode_hex(value: &str, len: usize) -> Result<Vec<u8>, String> {
+    hex::decode(value)
+        .ok()
+        .filter(|bytes| bytes.len() == len)
+        .ok_or_else(|| "Malformed handshake".to_string())
+}
+
+fn write_frame(stream: &mut TcpStream, payload: &[u8]) -> Result<(), String> {
+    stream
+        .write_all(&(payload.len() as u32).to_be_bytes())
+        .and_then(|_| stream.write_all(payload))
+        .map_err(|e| format!("Connection to peer failed: {}", e))
+}
+
+/// Reads a frame of at most `max_bytes`. The buffer grows as the frame
+/// arrives rather than to the length the peer claims up front.
+fn read_frame(stream: &mut TcpStream, max_bytes: usize) -> Result<Vec<u8>, String> {
+    let mut len = [0u8; 4];
+    stream
+        .read_exact(&mut len)
+        .map_err(|e| format!("Connection to peer failed: {}", e))?;
+    let len = u32::from_be_bytes(len) as usize;
+    if len > max_bytes {
+        return Err("Peer sent an oversized message".to_string());
+    }
+    let mut payload = Vec::new();
+    Read::take(&mut *stream, len as u64)
+        .read_to_end(&mut payload)
+        .map_err(|e| format!("Connection to peer failed: {}", e))?;
+    if payload.len() < len {
+        return Err("Connection to peer closed mid-message".to_string());
+    }
+    Ok(payload)
+}
+
+fn write_json<T: Serialize>(stream: &mut TcpStream, message: &T) -> Result<(), String> {
+    write_frame(stream, &serde_json::to_vec(message).map_err(|e| e.to_string())?)
+}
+
+/// Reads a handshake message.
+fn read_json<T: DeserializeOwned>(stream: &mut TcpStream) -> Result<T, String> {
+    serde_json::from_slice(&read_frame(stream, MAX_HANDSHAKE_FRAME_BYTES)?)
+        .map_err(|_| "Malformed message from peer".to_string())
+}
+
+/// An authenticated connection. Each direction has its own key and a message
+/// counter as nonce, so frames can't be replayed, reordered or reflected.
+struct SecureChannel {
+    stream: TcpStream,
+    sender: Aes256Gcm,
+    receiver: Aes256Gcm,
+    sent: u64,
+    received: u64,
+}
+
+fn counter_nonce(counter: u64) -> [u8; 12] {
+    let mut nonce = [0u8; 12];
+    nonce[4..].copy_from_slice(&counter.to_be_bytes());
+    nonce
+}
+
+impl SecureChannel {
+    fn send<T: Serialize>(&mut self, message: &T) -> Result<(), String> {
+        let plaintext = serde_json::to_vec(message).map_err(|e| e.to_string())?;
+        let ciphertext = self
+            .sender
+            .encrypt(Nonce::from_slice(&counter_nonce(self.sent)), plaintext.as_ref())
+            .map_err(|_| "Failed to encrypt message".to_string())?;
+        self.sent += 1;
+        write_frame(&mut self.stream, &ciphertext)
+    }
+
+    fn receive<T: DeserializeOwned>(&mut self) -> Result<T, String> {
+        let ciphertext = read_frame(&mut self.stream, MAX_FRAME_BYTES)?;
+        let plaintext = self
+            .receiver
+            .decrypt(Nonce::from_slice(&counter_nonce(self.received)), ciphertext.as_ref())
+            .map_err(|_| "Message from peer failed authentication".to_string())?;
+        self.received += 1;
+        serde_json::from_slice(&plaintext).map_err(|_| "Malformed message from peer".to_string())
+    }
+}
+
+fn lan_passphrase() -> Result<String, String> {
+    KeychainManager::get_secret(PASSPHRASE_ACCOUNT)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "LAN sync has no passphrase; set it up again".to_string())
+}
+
+/// Gives `settings` a salt for the LAN sync key if it has none yet, and
+/// returns whether it did.
+fn ensure_lan_salt(settings: &mut SyncSettings) -> bool {
+    if decode_hex(&settings.lan_salt, SALT_LEN).is_ok() {
+        return false;
+    }
+    let mut salt = [0u8; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    settings.lan_salt = hex::encode(salt);
+    true
+}
+
+fn server_key(passphrase: &str, salt: &str) -> Result<ServerKey, String> {
+    let salt = decode_hex(salt, SALT_LEN)?;
+    let key = crypto::derive_key(passphrase, &salt)?;
+    Ok(ServerKey { salt, key })
+}
+
+fn connect(address: SocketAddr, device_id: &str, passphrase: &str) -> Result<SecureChannel, String> {
+    let mut stream = TcpStream::connect_timeout(&address, IO_TIMEOUT)
+        .map_err(|e| format!("Could not reach {}: {}", address, e))?;
+    stream.set_read_timeout(Some(IO_TIMEOUT)).map_err(|e| e.to_string())?;
+    let client_nonce = random_nonce();
+    write_json(
+        &mut stream,
+        &ClientHello {
+            version: PROTOCOL_VERSION,
+            device_id: device_id.to_string(),
+            nonce: hex::encode(&client_nonce),
+        },
+    )?;
+    let hello: ServerHello = read_json(&mut stream)?;
+    let key = crypto::derive_key(passphrase, &decode_hex(&hello.salt, SALT_LEN)?)?;
+    let transcript = Transcript {
+        client_nonce,
+        server_nonce: decode_hex(&hello.nonce, NONCE_LEN)?,
+        client_id: device_id.to_string(),
+        server_id: hello.device_id,
+    };
+    write_json(&mut stream, &Proof { proof: transcript.proof(&key, "client") })?;
+    // A server with another passphrase hangs up instead of answering
+    let proof: Proof = read_json(&mut stream)
+        .map_err(|_| "The other device uses a different LAN sync passphrase".to_string())?;
+    transcript.verify(&key, "server", &proof.proof)?;
+    Ok(SecureChannel {
+        stream,
+        sender: transcript.session_cipher(&key, "client-to-server")?,
+        receiver: transcript.session_cipher(&key, "server-to-client")?,
+        sent: 0,
+        received: 0,
+    })
+}
+
+fn accept(mut stream: TcpStream, device_id: &str, key: &ServerKey) -> Result<SecureChannel, String> {
+    stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT)).map_err(|e| e.to_string())?;
+    let hello: ClientHello = read_json(&mut stream)?;
+    if hello.version != PROTOCOL_VERSION {
+        return Err(format!("Peer speaks LAN sync version {}", hello.version));
+    }
+    let transcript = Transcript {
+        client_nonce: decode_hex(&hello.nonce, NONCE_LEN)?,
+        server_nonce: random_nonce(),
+        client_id: hello.device_id,
+        server_id: device_id.to_string(),
+    };
+    write_json(
+        &mut stream,
+        &ServerHello {
+            device_id: device_id.to_string(),
+            nonce: hex::encode(&transcript.server_nonce),
+            salt: hex::encode(&key.salt),
+        },
+    )?;
+    let proof: Proof = read_json(&mut stream)?;
+    transcript.verify(&key.key, "client", &proof.proof)?;
+    write_json(&mut stream, &Proof { proof: transcript.proof(&key.key, "server") })?;
+    stream.set_read_timeout(Some(IO_TIMEOUT)).map_err(|e| e.to_string())?;
+    Ok(SecureChannel {
+        stream,
+        sender: transcript.session_cipher(&key.key, "server-to-client")?,
+        receiver: transcript.session_cipher(&key.key, "client-to-server")?,
+        sent: 0,
+        received: 0,
+    })
+}
+
+/// Serves one peer: merge what it sends, then answer with everything we know,
+/// which by then includes its changes too. `slot` is held until the peer has
+/// proved itself.
+fn serve(
+    stream: TcpStream,
+    slot: HandshakeSlot,
+    device_id: &str,
+    key: &ServerKey,
+    app: &AppHandle,
+) -> Result<(), String> {
+    // Without the database key there's nothing to sync with; refusing here
+    // also keeps a peer from triggering a keychain prompt
+    if !KeychainManager::has_cached_key() {
+        return Err("Journal is locked".to_string());
+    }
+    let mut channel = accept(stream, device_id, key)?;
+    drop(slot);
+    let incoming: Changeset = channel.receive()?;
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    let report = merge_state(&db.conn, device_id, &incoming, &Settings::load().limits)?;
+    let state = full_state(&db.conn, device_id).map_err(|e| e.to_string())?;
+    channel.send(&state)?;
+    record_all_shared(&db.conn, &state.entries)?;
+    info!("LAN sync from device {}: {:?}", incoming.device_id, report);
+    if report.changed() {
+        let _ = app.emit("entries-synced", report);
+    }
+    Ok(())
+}
+
+fn run_listener(listener: TcpListener, device_id: String, key: ServerKey, stop: Arc<AtomicBool>, app: AppHandle) {
+    let key = Arc::new(key);
+    while !stop.load(Ordering::Relaxed) {
+        match listener.accept() {
+            Ok((stream, peer)) => {
+                let Some(slot) = HandshakeSlot::take() else {
+                    debug!("Turned away LAN sync connection from {}: too many handshakes", peer);
+                    continue;
+                };
+                debug!("LAN sync connection from {}", peer);
+                let device_id = device_id.clone();
+                let key = key.clone();
+                let app = app.clone();
+                thread::spawn(move || {
+                    let _ = stream.set_nonblocking(false);
+                    if let Err(e) = serve(stream, slot, &device_id, &key, &app) {
+                        warn!("LAN sync with {} failed: {}", peer, e);
+                    }
+                });
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => thread::sleep(ACCEPT_POLL_INTERVAL),
+            Err(e) => {
+                error!("LAN sync listener stopped: {}", e);
+                return;
+            }
+        }
+    }
+}
+
+/// Starts listening and advertising this device, if LAN sync is enabled.
+pub fn start_server(app: AppHandle) -> Result<(), String> {
+    let mut all_settings = Settings::load();
+    let mut server = SERVER.lock().map_err(|e| e.to_string())?;
+    if !all_settings.sync.lan_enabled || server.is_some() {
+        return Ok(());
+    }
+    // Set up before salts were per device
+    if ensure_lan_salt(&mut all_settings.sync) {
+        all_settings.save().map_err(|e| e.to_string())?;
+    }
+    let settings = all_settings.sync;
+    let key = server_key(&lan_passphrase()?, &settings.lan_salt)?;
+    let listener = TcpListener::bind("0.0.0.0:0").map_err(|e| format!("Failed to open LAN sync port: {}", e))?;
+    listener.set_nonblocking(true).map_err(|e| e.to_string())?;
+    let port = listener.local_addr().map_err(|e| e.to_string())?.port();
+
+    let daemon = ServiceDaemon::new().map_err(|e| format!("Failed to start mDNS: {}", e))?;
+    let host_name = format!("journal-{}.local.", &settings.device_id[..8.min(settings.device_id.len())]);
+    let properties = [
+        ("device_id", settings.device_id.as_str()),
+        ("name", settings.device_name.as_str()),
+    ];
+    let service = ServiceInfo::new(SERVICE_TYPE, &settings.device_id, &host_name, "", port, &properties[..])
+        .map_err(|e| e.to_string())?
+        .enable_addr_auto();
+    let fullname = service.get_fullname().to_string();
+    daemon.register(service).map_err(|e| format!("Failed to advertise on the network: {}", e))?;
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let listener_stop = stop.clone();
+    let device_id = settings.device_id.clone();
+    thread::spawn(move || run_listener(listener, device_id, key, listener_stop, app));
+    info!("LAN sync listening on port {}", port);
+    *server = Some(LanServer { daemon, fullname, stop });
+    Ok(())
+}
+
+fn stop_server() {
+    if let Some(server) = SERVER.lock().ok().and_then(|mut server| server.take()) {
+        server.stop.store(true, Ordering::Relaxed);
+        let _ = server.daemon.unregister(&server.fullname);
+        let _ = server.daemon.shutdown();
+        info!("LAN sync stopped");
+    }
+}
+
+/// Browses for other devices for a few seconds.
+fn browse(device_id: &str) -> Result<Vec<(LanPeer, Vec<SocketAddr>)>, String> {
+    let daemon = ServiceDaemon::new().map_err(|e| format!("Failed to start mDNS: {}", e))?;
+    let events = daemon.browse(SERVICE_TYPE).map_err(|e| e.to_string())?;
+    let deadline = Instant::now() + DISCOVERY_TIMEOUT;
+    let mut peers: Vec<(LanPeer, Vec<SocketAddr>)> = Vec::new();
+    while let Some(remaining) = deadline.checked_duration_since(Instant::now()) {
+        let Ok(event) = events.recv_timeout(remaining) else { break };
+        if let ServiceEvent::ServiceResolved(info) = event {
+            let Some(peer_id) = info.get_property_val_str("device_id") else { continue };
+            if peer_id == device_id || peers.iter().any(|(peer, _)| peer.device_id == peer_id) {
+                continue;
+            }
+            let mut addresses: Vec<SocketAddr> = info
+                .get_addresses()
+                .iter()
+                .map(|ip| SocketAddr::new(*ip, info.get_port()))
+                .collect();
+            // IPv4 first: link-local IPv6 addresses often need a scope id we don't have
+            addresses.sort_by_key(|address| matches!(address.ip(), IpAddr::V6(_)));
+            let Some(first) = addresses.first() else { continue };
+            let name = info.get_property_val_str("name").unwrap_or_default();
+            peers.push((
+                LanPeer {
+                    device_id: peer_id.to_string(),
+                    name: if name.is_empty() { peer_id.to_string() } else { name.to_string() },
+                    address: first.to_string(),
+                },
+                addresses,
+            ));
+        }
+    }
+    let _ = daemon.shutdown();
+    Ok(peers)
+}
+
+fn sync_with(device_id: &str, peer_id: &str, limits: &LimitSettings) -> Result<SyncReport, String> {
+    let passphrase = lan_passphrase()?;
+    let (_, addresses) = browse(device_id)?
+        .into_iter()
+        .find(|(peer, _)| peer.device_id == peer_id)
+        .ok_or_else(|| "That device is no longer on the network".to_string())?;
+    let mut last_error = String::new();
+    for address in addresses {
+        match connect(address, device_id, &passphrase) {
+            Ok(mut channel) => {
+                let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+                let state = full_state(&db.conn, device_id).map_err(|e| e.to_string())?;
+                channel.send(&state)?;
+                record_all_shared(&db.conn, &state.entries)?;
+                let reply: Changeset = channel.receive()?;
+                return merge_state(&db.conn, device_id, &reply, limits);
+            }
+            Err(e) => last_error = e,
+        }
+    }
+    Err(last_error)
+}
+
+/// Turns LAN sync on or off. Enabling needs the passphrase shared by the
+/// devices that should be able to sync with each other.
+#[command]
+pub fn set_lan_sync(app: AppHandle, enabled: bool, passphrase: Option<String>) -> Result<(), String> {
+    let mut settings = Settings::load();
+    if enabled {
+        let passphrase = passphrase.ok_or_else(|| "A passphrase is required".to_string())?;
+        if passphrase.chars().count() < MIN_PASSPHRASE_CHARS {
+            return Err(format!("Passphrase must be at least {} characters", MIN_PASSPHRASE_CHARS));
+        }
+        KeychainManager::store_secret(PASSPHRASE_ACCOUNT, &passphrase).map_err(|e| e.to_string())?;
+        ensure_device_id(&mut settings.sync);
+        // A new passphrase gets a new salt
+        settings.sync.lan_salt.clear();
+        ensure_lan_salt(&mut settings.sync);
+    } else {
+        KeychainManager::delete_secret(PASSPHRASE_ACCOUNT).map_err(|e| e.to_string())?;
+    }
+    settings.sync.lan_enabled = enabled;
+    settings.save().map_err(|e| e.to_string())?;
+    // Restart so a new passphrase or device name takes effect
+    stop_server();
+    start_server(app)
+}
+
+/// Devices currently advertising LAN sync, excluding this one.
+#[command]
+pub async fn discover_lan_peers() -> Result<Vec<LanPeer>, String> {
+    tauri::async_runtime::spawn_blocking(|| {
+        let settings = Settings::load().sync;
+        Ok(browse(&settings.device_id)?.into_iter().map(|(peer, _)| peer).collect())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Syncs directly with a device found by `discover_lan_peers`.
+#[command]
+pub async fn sync_with_peer(device_id: String) -> Result<SyncReport, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let settings = Settings::load();
+        if !settings.sync.lan_enabled {
+            return Err("LAN sync is not enabled".to_string());
+        }
+        sync_with(&settings.sync.device_id, &device_id, &settings.limits)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transcript() -> Transcript {
+        Transcript {
+            client_nonce: vec![1; NONCE_LEN],
+            server_nonce: vec![2; NONCE_LEN],
+            client_id: "laptop".to_string(),
+            server_id: "desktop".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_proofs_are_bound_to_key_and_role() {
+        let key = [7u8; 32];
+        let transcript = transcript();
+        let proof = transcript.proof(&key, "server");
+        assert!(transcript.verify(&key, "server", &proof).is_ok());
+        // A server proof can't be reflected back as the client's
+        assert!(transcript.verify(&key, "client", &proof).is_err());
+        assert!(transcript.verify(&[8u8; 32], "server", &proof).is_err());
+    }
+
+    fn server_key_for(passphrase: &str) -> ServerKey {
+        server_key(passphrase, &hex::encode([3u8; SALT_LEN])).unwrap()
+    }
+
+    #[test]
+    fn test_channel_round_trip() {
+        let key = server_key_for("correct horse");
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut channel = accept(stream, "desktop", &key).unwrap();
+            let message: String = channel.receive().unwrap();
+            channel.send(&format!("{} back", message)).unwrap();
+        });
+        let mut channel = connect(address, "laptop", "correct horse").unwrap();
+        channel.send(&"hello".to_string()).unwrap();
+        assert_eq!(channel.receive::<String>().unwrap(), "hello back");
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_wrong_passphrase_is_rejected() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            accept(stream, "desktop", &server_key_for("correct horse")).is_err()
+        });
+        assert!(connect(address, "laptop", "battery staple").is_err());
+        assert!(server.join().unwrap());
+    }
+
+    #[test]
+    fn test_server_proves_nothing_to_strangers() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            accept(stream, "desktop", &server_key_for("correct horse")).err()
+        });
+        let mut stream = TcpStream::connect(address).unwrap();
+        let hello = ClientHello {
+            version: PROTOCOL_VERSION,
+            device_id: "stranger".to_string(),
+            nonce: hex::encode([1u8; NONCE_LEN]),
+        };
+        write_json(&mut stream, &hello).unwrap();
+        let reply: serde_json::Value = read_json(&mut stream).unwrap();
+        let mut fields: Vec<&String> = reply.as_object().unwrap().keys().collect();
+        fields.sort();
+        assert_eq!(fields, ["device_id", "nonce", "salt"]);
+        write_json(&mut stream, &Proof { proof: hex::encode([0u8; 32]) }).unwrap();
+        assert!(server.join().unwrap().is_some());
+        // Hung up without a proof of its own
+        assert!(read_frame(&mut stream, MAX_HANDSHAKE_FRAME_BYTES).is_err());
+    }
+
+    #[test]
+    fn test_handshake_slots() {
+        let slots: Vec<HandshakeSlot> = (0..MAX_HANDSHAKES).map_while(|_| HandshakeSlot::take()).collect();
+        assert_eq!(slots.len(), MAX_HANDSHAKES);
+        assert!(HandshakeSlot::take().is_none());
+        drop(slots);
+        assert!(HandshakeSlot::take().is_some());
+    }
+
+    #[test]
+    fn test_large_frames_need_authentication() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            accept(stream, "desktop", &server_key_for("correct horse")).err()
+        });
+        let mut stream = TcpStream::connect(address).unwrap();
+        stream.write_all(&(MAX_HANDSHAKE_FRAME_BYTES as u32 + 1).to_be_bytes()).unwrap();
+        assert_eq!(server.join().unwrap().as_deref(), Some("Peer sent an oversized message"));
+    }
+}
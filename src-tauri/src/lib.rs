@@ -1,14 +1,983 @@
-// Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
+use rusqlite::OptionalExtension;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use crate::api_server::{reset_api_token, set_api_server};
+use crate::archive::{archive_entry, unarchive_entry};
+use crate::attachments::{
+    add_audio_attachment, gc_attachments, get_attachment_text, get_audio_attachment,
+    get_entry_attachments, paste_image_from_clipboard,
+};
+use crate::backup::{list_backups, restore_backup};
+use crate::bulk::{bulk_delete, bulk_move_to_notebook, bulk_set_tags};
+use crate::clipboard::{clear_clipboard_later, copy_entry, copy_entry_to_clipboard};
+use crate::cloud_backup::{
+    configure_cloud_backup, configure_webdav_backup, disable_cloud_backup, list_cloud_backups, restore_from_cloud,
+};
+use crate::conflicts::{list_conflicts, resolve_conflict};
+use crate::custom_fields::{
+    define_custom_field, delete_custom_field, get_custom_fields, get_entry_custom_fields,
+    set_custom_field_value,
+};
+use crate::diagnostics::{get_health, get_migration_history};
+use crate::email_ingest::{check_email_now, configure_email_ingest, disable_email_ingest};
+use crate::enex::import_enex;
+use crate::entry_window::open_entry_window;
+use crate::epub::export_epub;
+use crate::export::{export_csv, export_entries};
+use crate::feed::export_feed;
+use crate::file_drop::set_current_entry;
+use crate::folder_import::import_folder;
+use crate::git_sync::{configure_git_sync, disable_git_sync, git_sync_now};
+use crate::health::{get_correlations, import_health_csv};
+use crate::importers::import_journal;
+use crate::jobs::{cancel_export, cancel_job, list_jobs};
+use crate::lan_sync::{discover_lan_peers, set_lan_sync, sync_with_peer};
+use crate::links::{get_backlinks, get_outgoing_links};
+use crate::location::{get_entries_near, set_entry_location, Location};
+use crate::maintenance::{get_storage_report, optimize_database};
+use crate::markdown_mirror::set_markdown_mirror;
+use crate::metadata::{get_entry_metadata, set_entry_metadata};
+use crate::locks::{is_locked, lock_entry, remove_entry_lock, save_locked_entry, unlock_entry};
+use crate::logging::export_logs;
+use crate::mood::{Mood, get_mood_trends, set_mood};
+use crate::notebooks::{create_notebook, delete_notebook, get_notebooks};
+use crate::obsidian::export_obsidian;
+use crate::people::{get_entries_mentioning, get_people};
+use crate::photo_gaps::{get_photo_gaps, journal_photo_day, set_photo_folder};
+use crate::print::{print_entry, print_window};
+use crate::privacy::{get_privacy_screen, set_privacy_options, set_privacy_screen};
+use crate::profiles::{create_profile, get_data_directory, list_profiles, set_data_directory, switch_profile};
+use crate::prompts::{add_prompt, delete_prompt, get_daily_prompt, get_random_prompt};
+use crate::related::get_related_entries;
+use crate::sealing::unseal_entry;
+use crate::search::search_entries;
+use crate::security::{get_audit_log, get_security_status, set_cipher_params, set_encryption_enabled};
+use crate::settings::{get_settings, save_settings, Settings};
+use crate::shortcuts::{get_shortcuts, set_shortcut, ShortcutAction};
+use crate::spotlight::{rebuild_spotlight_index, set_spotlight_indexing};
+use crate::static_site::export_html;
+use crate::stats::{
+    get_longest_entries, get_on_this_day, get_sentiment_trend, get_word_frequencies, get_writing_stats,
+    get_writing_streak, get_writing_style,
+};
+use crate::summaries::{get_summaries, summarize_entry, summarize_range};
+use crate::sync::{configure_sync, disable_sync, sync_now};
+use crate::tags::{get_all_tags, get_entry_tags, set_entry_tags, suggest_tags, tags_for_entry};
+use crate::templates::{
+    create_entry_from_template, create_template, delete_template, find_or_create_today, get_templates,
+    set_default_template, update_template,
+};
+use crate::transcription::transcribe_attachment;
+use crate::tray::quick_capture;
+use crate::unlock_attempts::Secret;
+use crate::weather::Weather;
+use crate::wipe::secure_wipe;
+use crate::year_review::{export_year_review, generate_year_review};
+use tauri_plugin_updater;
+use log::{debug, info, warn};
+use chrono::{Local, Utc};
+use tauri::menu::{AboutMetadata, MenuBuilder, MenuItemBuilder, SubmenuBuilder};
+use tauri_plugin_clipboard_manager;
+use tauri_plugin_opener;
+use tauri_plugin_process;
+use tauri_plugin_dialog;
+use tauri::{AppHandle, DragDropEvent, Emitter, Manager, WindowEvent};
+use journal_core::audit::AuditAction;
+use journal_core::keychain::{KeychainError, KeychainManager};
+use journal_core::links::update_links;
+use journal_core::people::update_people;
+use journal_core::sentiment::body_sentiment;
+use journal_core::text::{body_word_count, snippet};
+use journal_core::{
+    created_ms_for, insert_entry, local_date_for, parse_entry_date, parse_entry_datetime, DatabaseManager,
+    ErrorResponse, JournalEntry,
+};
+
+mod api_server;
+#[cfg(target_os = "macos")]
+mod app_delegate;
+mod apple_notes;
+mod archive;
+mod attachments;
+mod backup;
+mod blob_store;
+mod bulk;
+mod clipboard;
+mod cloud_backup;
+mod conflicts;
+mod crypto;
+mod custom_fields;
+mod deep_link;
+mod diagnostics;
+mod diff;
+mod dock;
+mod email_ingest;
+mod enex;
+mod entry_window;
+mod epub;
+mod export;
+mod feed;
+mod file_drop;
+mod file_menu;
+mod folder_import;
+mod git_sync;
+mod health;
+mod importers;
+mod jobs;
+mod lan_sync;
+mod links;
+mod location;
+mod locks;
+mod logging;
+mod maintenance;
+mod markdown_mirror;
+mod metadata;
+mod mood;
+mod notebooks;
+mod obsidian;
+mod ocr;
+mod people;
+mod photo_gaps;
+mod print;
+mod privacy;
+mod profiles;
+mod prompts;
+mod recent_menu;
+mod related;
+mod sealing;
+mod search;
+mod security;
+mod services;
+mod settings;
+mod shortcuts;
+mod spotlight;
+mod static_site;
+mod stats;
+mod summaries;
+mod sync;
+mod tags;
+mod templates;
+mod transcription;
+mod tray;
+mod unlock_attempts;
+mod validation;
+mod weather;
+mod wipe;
+mod year_review;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct FullJournalEntry {
+    id: i32,
+    title: String,
+    body: String,
+    created_at: String,
+    mood: Option<Mood>,
+    tags: Vec<String>,
+    locked: bool,
+    /// Past the sealing age and not unsealed, so it can't be changed.
+    sealed: bool,
+    archived: bool,
+    weather: Option<Weather>,
+    location: Option<Location>,
+    /// Goes up with every change to the title or body; passed back to
+    /// `save_entry` to catch edits made elsewhere in the meantime.
+    version: i64,
+}
+
+/// Optional RFC3339 bounds used by commands that aggregate over time.
+#[derive(Debug, Default, Deserialize)]
+struct DateRange {
+    start: Option<String>,
+    end: Option<String>,
+}
+
+impl DateRange {
+    /// The bounds as epoch milliseconds, to compare with `created_ms`.
+    fn millis(&self) -> Result<(Option<i64>, Option<i64>), String> {
+        let millis = |bound: &Option<String>| {
+            bound.as_deref().map(|bound| parse_entry_datetime(bound).map(|d| d.timestamp_millis())).transpose()
+        };
+        Ok((millis(&self.start)?, millis(&self.end)?))
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct AdjacentEntries {
+    previous: Option<i32>,
+    next: Option<i32>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CreateEntryRequest {
+    title: String,
+    body: String,
+    /// Lets users file an entry under an earlier day; defaults to now.
+    created_at: Option<String>,
+}
+
+/// The timeline, newest first. Archived entries are left out unless
+/// `include_archived` is set.
 #[tauri::command]
-fn greet(name: &str) -> String {
-    format!("Hello, {}! You've been greeted from Rust!", name)
+fn get_entries(include_archived: Option<bool>) -> Result<Vec<JournalEntry>, String> {
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    let mut stmt = db.conn
+        .prepare(&format!(
+            "SELECT {} FROM journal_entries WHERE archived = 0 OR ?1 ORDER BY created_ms DESC, id DESC",
+            JournalEntry::COLUMNS
+        ))
+        .map_err(|e| e.to_string())?;
+    let entries = stmt
+        .query_map(rusqlite::params![include_archived.unwrap_or(false)], JournalEntry::from_row)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(entries)
+}
+
+/// Characters of body text shown under each title in the list.
+const SNIPPET_CHARS: usize = 140;
+/// How much of each body is read to make a snippet from. Markup takes up
+/// some of it, so this is well over `SNIPPET_CHARS`.
+const SNIPPET_SOURCE_CHARS: i64 = 2000;
+
+#[derive(Debug, Serialize)]
+struct EntryPreview {
+    #[serde(flatten)]
+    entry: JournalEntry,
+    /// The start of the body as plain text; empty for locked entries.
+    snippet: String,
+}
+
+/// `get_entries` with the start of each body, so the list can show it
+/// without loading every entry.
+#[tauri::command]
+fn get_entries_preview(include_archived: Option<bool>) -> Result<Vec<EntryPreview>, String> {
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    let mut stmt = db.conn
+        .prepare(&format!(
+            "SELECT {}, substr(body, 1, ?2) FROM journal_entries
+             WHERE archived = 0 OR ?1 ORDER BY created_ms DESC, id DESC",
+            JournalEntry::COLUMNS
+        ))
+        .map_err(|e| e.to_string())?;
+    let entries = stmt
+        .query_map(rusqlite::params![include_archived.unwrap_or(false), SNIPPET_SOURCE_CHARS], |row| {
+            Ok(EntryPreview {
+                entry: JournalEntry::from_row(row)?,
+                snippet: snippet(&row.get::<_, String>(7)?, SNIPPET_CHARS),
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(entries)
+}
+
+/// Entry summaries written between `start` and `end` (inclusive), oldest first,
+/// for calendar and timeline views. Timestamps are compared as instants via
+/// `created_ms`, so differing UTC offsets can't skew the result.
+#[tauri::command]
+fn get_entries_between(start: String, end: String) -> Result<Vec<JournalEntry>, String> {
+    let start = parse_entry_datetime(&start)?;
+    let end = parse_entry_datetime(&end)?;
+    if start > end {
+        return Err("Start of range must not be after its end".to_string());
+    }
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    let mut stmt = db.conn
+        .prepare(&format!(
+            "SELECT {} FROM journal_entries
+             WHERE created_ms BETWEEN ?1 AND ?2
+             ORDER BY created_ms ASC, id ASC",
+            JournalEntry::COLUMNS
+        ))
+        .map_err(|e| e.to_string())?;
+    let entries = stmt
+        .query_map(
+            rusqlite::params![start.timestamp_millis(), end.timestamp_millis()],
+            JournalEntry::from_row,
+        )
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(entries)
+}
+
+/// IDs of the entries written immediately before and after `id`, for
+/// previous/next navigation in the reading view. Ties on `created_ms` are
+/// broken by id so every entry is reachable.
+#[tauri::command]
+fn get_adjacent_entries(id: i32) -> Result<AdjacentEntries, String> {
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    let created_ms: Option<i64> = db.conn
+        .query_row(
+            "SELECT created_ms FROM journal_entries WHERE id = ?1",
+            rusqlite::params![id],
+            |row| row.get(0),
+        )
+        .map_err(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => format!("Entry {} not found", id),
+            e => e.to_string(),
+        })?;
+    let neighbour = |sql: &str| -> Result<Option<i32>, String> {
+        db.conn
+            .query_row(sql, rusqlite::params![created_ms, id], |row| row.get(0))
+            .optional()
+            .map_err(|e| e.to_string())
+    };
+    Ok(AdjacentEntries {
+        previous: neighbour(
+            "SELECT id FROM journal_entries
+             WHERE (created_ms, id) < (?1, ?2)
+             ORDER BY created_ms DESC, id DESC LIMIT 1",
+        )?,
+        next: neighbour(
+            "SELECT id FROM journal_entries
+             WHERE (created_ms, id) > (?1, ?2)
+             ORDER BY created_ms ASC, id ASC LIMIT 1",
+        )?,
+    })
+}
+
+/// Loads a full entry. For locked entries `body` is left empty; the text is
+/// only available through `unlock_entry`.
+fn load_entry(conn: &rusqlite::Connection, id: i32) -> Result<FullJournalEntry, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, title, body, created_at, mood_score, mood_emoji, locked, archived, weather, latitude, longitude, place,
+                    version
+             FROM journal_entries WHERE id = ?1",
+        )
+        .map_err(|e| e.to_string())?;
+    let mut entry = stmt
+        .query_row(rusqlite::params![id], |row| {
+            Ok(FullJournalEntry {
+                id: row.get(0)?,
+                title: row.get(1)?,
+                body: row.get(2)?,
+                created_at: row.get(3)?,
+                mood: Mood::from_columns(row.get(4)?, row.get(5)?),
+                tags: Vec::new(),
+                locked: row.get(6)?,
+                sealed: false,
+                archived: row.get(7)?,
+                weather: weather::from_column(row.get(8)?),
+                location: Location::from_columns(row.get(9)?, row.get(10)?, row.get(11)?),
+                version: row.get(12)?,
+            })
+        })
+        .map_err(|e| e.to_string())?;
+    entry.tags = tags_for_entry(conn, id).map_err(|e| e.to_string())?;
+    entry.sealed = sealing::is_sealed(conn, id)?;
+    Ok(entry)
+}
+
+#[tauri::command]
+fn get_entry(id: i32) -> Result<FullJournalEntry, String> {
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    load_entry(&db.conn, id)
+}
+
+#[tauri::command]
+fn create_entry(request: CreateEntryRequest) -> Result<i32, String> {
+    let created_at = match request.created_at.as_deref() {
+        Some(date) => parse_entry_date(date)?,
+        None => Utc::now().to_rfc3339(),
+    };
+    let (title, body) = validation::clean_entry(&request.title, &request.body, &Settings::load().limits)?;
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    let id = insert_entry(&db.conn, &title, &body, &created_at).map_err(|e| e.to_string())?;
+    git_sync::commit_on_save();
+    markdown_mirror::refresh();
+    recent_menu::refresh();
+    // Today's weather says nothing about an entry filed under an earlier day
+    if request.created_at.is_none() {
+        weather::capture_for_new_entry(id);
+    }
+    Ok(id)
+}
+
+/// Today's entry, by local date, so the today view always has somewhere to
+/// type. It's created from the default template if it doesn't exist yet.
+#[tauri::command]
+fn get_or_create_today() -> Result<FullJournalEntry, String> {
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    let (id, created) = find_or_create_today(&db.conn, Local::now())?;
+    if created {
+        git_sync::commit_on_save();
+        markdown_mirror::refresh();
+        recent_menu::refresh();
+        weather::capture_for_new_entry(id);
+    }
+    load_entry(&db.conn, id)
+}
+
+/// How the error from `save_entry` starts when the entry changed after it was
+/// loaded, so the editor can tell it apart and offer to reload.
+const SAVE_CONFLICT: &str = "save_conflict";
+
+/// Saves an entry's title and body and returns its new version. `version` is
+/// the one the editor loaded; if the text has changed since, in another
+/// window, over sync or through the local API, the save is refused rather
+/// than overwrite the newer text.
+#[tauri::command]
+fn save_entry(id: i32, title: String, body: String, version: Option<i64>) -> Result<i64, String> {
+    let (title, body) = validation::clean_entry(&title, &body, &Settings::load().limits)?;
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    if is_locked(&db.conn, id).map_err(|e| e.to_string())? {
+        return Err(format!("Entry {} is locked; unlock it before saving", id));
+    }
+    sealing::ensure_unsealed(&db.conn, id)?;
+    let version = save_entry_text(&db.conn, id, &title, &body, version)?;
+    git_sync::commit_on_save();
+    markdown_mirror::refresh();
+    recent_menu::refresh();
+    Ok(version)
+}
+
+/// Writes an already cleaned title and body to entry `id` if it's still at
+/// `version` (any version when `None`), returning the new one.
+fn save_entry_text(
+    conn: &rusqlite::Connection,
+    id: i32,
+    title: &str,
+    body: &str,
+    version: Option<i64>,
+) -> Result<i64, String> {
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    let current: i64 = tx
+        .query_row("SELECT version FROM journal_entries WHERE id = ?1", rusqlite::params![id], |row| row.get(0))
+        .map_err(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => format!("Entry {} not found", id),
+            e => e.to_string(),
+        })?;
+    if version.is_some_and(|version| version != current) {
+        return Err(format!("{}: entry {} was changed elsewhere since it was opened", SAVE_CONFLICT, id));
+    }
+    tx.execute(
+        "UPDATE journal_entries SET title = ?1, body = ?2, word_count = ?3, sentiment = ?4 WHERE id = ?5",
+        rusqlite::params![title, body, body_word_count(body), body_sentiment(body), id],
+    )
+    .map_err(|e| e.to_string())?;
+    update_links(&tx, id, body).map_err(|e| e.to_string())?;
+    update_people(&tx, id, body).map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(current + 1)
+}
+
+#[tauri::command]
+fn set_entry_date(id: i32, date: String) -> Result<(), String> {
+    let created_at = parse_entry_date(&date)?;
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    sealing::ensure_unsealed(&db.conn, id)?;
+    let updated = db.conn.execute(
+        "UPDATE journal_entries SET created_at = ?1, local_date = ?2, created_ms = ?3 WHERE id = ?4",
+        rusqlite::params![created_at, local_date_for(&created_at), created_ms_for(&created_at), id],
+    )
+    .map_err(|e| e.to_string())?;
+    if updated == 0 {
+        return Err(format!("Entry {} not found", id));
+    }
+    git_sync::commit_on_save();
+    markdown_mirror::refresh();
+    recent_menu::refresh();
+    Ok(())
+}
+
+#[tauri::command]
+fn delete_all_entries() -> Result<(), String> {
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    backup::snapshot(&db, "pre-delete-all").map_err(|e| e.to_string())?;
+    let deleted = db.conn.execute("DELETE FROM journal_entries", [])
+        .map_err(|e| e.to_string())?;
+    security::audit(AuditAction::DeleteAll, &format!("{} entries", deleted));
+    git_sync::commit_on_save();
+    markdown_mirror::refresh();
+    recent_menu::refresh();
+    Ok(())
+}
+
+#[tauri::command]
+fn delete_entry(app: AppHandle, id: i32) -> Result<(), String> {
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    sealing::ensure_unsealed(&db.conn, id)?;
+    db.conn.execute("DELETE FROM journal_entries WHERE id = ?1", rusqlite::params![id])
+        .map_err(|e| e.to_string())?;
+    entry_window::close(&app, id);
+    git_sync::commit_on_save();
+    markdown_mirror::refresh();
+    recent_menu::refresh();
+    Ok(())
+}
+
+#[tauri::command]
+fn export_database(path: String) -> Result<(), String> {
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    db.export_database(&PathBuf::from(&path)).map_err(|e| e.to_string())?;
+    security::audit(AuditAction::Export, &format!("database to {}", path));
+    Ok(())
+}
+
+#[tauri::command]
+fn import_database(path: String) -> Result<(), String> {
+    backup::validate_backup(Path::new(&path))?;
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    backup::snapshot(&db, "pre-import").map_err(|e| e.to_string())?;
+    db.import_database(&PathBuf::from(&path)).map_err(|e| e.to_string())?;
+    drop(db);
+    security::audit(AuditAction::Import, &format!("database from {}", path));
+    recent_menu::refresh();
+    Ok(())
+}
+
+/// Tells the unlock screen whether to ask for a passphrase or recovery key
+/// next, or, when the keychain refused, to offer a retry or a passphrase.
+fn unlock_error(error: KeychainError) -> ErrorResponse {
+    let error_type = if error.needs_passphrase() {
+        "passphrase_required"
+    } else if error.needs_recovery_key() {
+        "recovery_key_required"
+    } else if matches!(error, KeychainError::KeychainAccessDenied | KeychainError::AuthenticationFailed) {
+        "keychain_denied"
+    } else {
+        "keychain_error"
+    };
+    ErrorResponse {
+        message: error.to_user_message(),
+        error_type: error_type.to_string(),
+    }
+}
+
+/// Sent to every window after each attempt to unlock the journal, so the
+/// ones that didn't ask can leave or change their unlock screen too.
+#[derive(Clone, Serialize)]
+struct KeychainStatus {
+    unlocked: bool,
+    /// The `error_type` of a failed attempt, e.g. `keychain_denied`.
+    error_type: Option<String>,
+    message: Option<String>,
+}
+
+fn report_unlock(app: &AppHandle, result: Result<(), ErrorResponse>) -> Result<(), ErrorResponse> {
+    let status = match &result {
+        Ok(()) => KeychainStatus {
+            unlocked: true,
+            error_type: None,
+            message: None,
+        },
+        Err(e) => KeychainStatus {
+            unlocked: false,
+            error_type: Some(e.error_type.clone()),
+            message: Some(e.message.clone()),
+        },
+    };
+    let _ = app.emit("keychain-status", status);
+    if result.is_ok() {
+        deep_link::open_pending(app);
+        recent_menu::refresh();
+    }
+    result
+}
+
+fn authorize(app: &AppHandle) -> Result<(), ErrorResponse> {
+    let result = KeychainManager::new().and_then(|manager| manager.authorize_keychain());
+    report_unlock(app, result.map_err(unlock_error))
+}
+
+#[tauri::command]
+fn authorize_keychain_command(app: AppHandle) -> Result<(), ErrorResponse> {
+    authorize(&app)
+}
+
+/// Asks the keychain for the key again after it refused, e.g. once the user
+/// has allowed the app in Keychain Access or unlocked their login keychain.
+#[tauri::command]
+fn retry_keychain_access(app: AppHandle) -> Result<(), ErrorResponse> {
+    info!("Retrying keychain access");
+    authorize(&app)
+}
+
+/// Unlocks the journal with a secret the user typed, counting wrong ones
+/// towards the unlock throttle.
+fn unlock_with(app: &AppHandle, unlock: impl FnOnce() -> Result<(), KeychainError>) -> Result<(), ErrorResponse> {
+    unlock_attempts::check(Secret::Journal).map_err(|message| ErrorResponse {
+        message,
+        error_type: "unlock_throttled".to_string(),
+    })?;
+    let result = unlock();
+    let correct = match &result {
+        Ok(()) => Some(true),
+        Err(KeychainError::WrongPassphrase | KeychainError::InvalidRecoveryKey | KeychainError::WrongRecoveryKey) => {
+            Some(false)
+        }
+        Err(_) => None,
+    };
+    unlock_attempts::record(app, Secret::Journal, correct);
+    report_unlock(app, result.map_err(unlock_error))
+}
+
+/// Unlocks with a passphrase where there's no system keychain to hold the key.
+#[tauri::command]
+fn unlock_with_passphrase(app: AppHandle, passphrase: String) -> Result<(), ErrorResponse> {
+    unlock_with(&app, || KeychainManager::unlock_with_passphrase(&passphrase))
+}
+
+/// Keys the journal by a passphrase from now on, for when the keychain keeps
+/// refusing. A journal that already has a database needs its recovery key
+/// too, unless it was unlocked earlier this session.
+#[tauri::command]
+fn switch_to_passphrase(app: AppHandle, passphrase: String, recovery_key: Option<String>) -> Result<(), ErrorResponse> {
+    unlock_with(&app, || KeychainManager::switch_to_passphrase(&passphrase, recovery_key.as_deref()))
+}
+
+/// Opens a journal whose key isn't in this computer's keychain, e.g. one
+/// copied from another machine, with the recovery key shown there.
+#[tauri::command]
+fn restore_with_recovery_key(app: AppHandle, recovery_key: String) -> Result<(), ErrorResponse> {
+    unlock_with(&app, || KeychainManager::restore_with_recovery_key(&recovery_key))
+}
+
+#[tauri::command]
+fn generate_recovery_key() -> Result<String, String> {
+    KeychainManager::recovery_key().map_err(|e| e.to_user_message())
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
+    logging::init();
+    debug!("Starting application");
+
     tauri::Builder::default()
+        // Must come first, so a second launch (e.g. from a journal:// link) is
+        // handed to this instance before anything else starts
+        .plugin(tauri_plugin_single_instance::init(|app, _argv, _cwd| tray::show_main(app)))
+        .plugin(tauri_plugin_deep_link::init())
+        .plugin(tauri_plugin_clipboard_manager::init())
         .plugin(tauri_plugin_opener::init())
-        .invoke_handler(tauri::generate_handler![greet])
+        .plugin(tauri_plugin_process::init())
+        .setup(|app| {
+            // Build the application menu --------------------------
+            let settings = MenuItemBuilder::new("Settings…")
+                .id("settings")
+                .accelerator("Cmd+,")
+                .build(app)?;
+            let check_updates = MenuItemBuilder::new("Check for updates…")
+                .id("check_updates")
+                .build(app)?;
+            let app_submenu = SubmenuBuilder::new(app, &app.package_info().name)
+                .about(Some(AboutMetadata::default()))
+                .separator()
+                .item(&settings)
+                .item(&check_updates)
+                .separator()
+                .quit()
+                .build()?;
+
+            // File ▸ New Entry and Quick Capture, with the user's shortcuts
+            let new_entry = shortcuts::menu_item(app.handle(), ShortcutAction::NewEntry)?;
+            let quick_capture = shortcuts::menu_item(app.handle(), ShortcutAction::QuickCapture)?;
+            // File ▸ Recent Entries, filled in from the database
+            let recent_entries = recent_menu::create(app.handle())?;
+            // File ▸ Export and File ▸ Import
+            let export_menu = file_menu::export_submenu(app.handle())?;
+            let import_menu = file_menu::import_submenu(app.handle())?;
+            // File ▸ Print, for the entry that's open
+            let print_item = MenuItemBuilder::new("Print…")
+                .id("print")
+                .accelerator("CmdOrCtrl+P")
+                .build(app)?;
+            let file_menu = SubmenuBuilder::new(app, "File")
+                .item(&new_entry)
+                .item(&quick_capture)
+                .item(&recent_entries)
+                .separator()
+                .item(&export_menu)
+                .item(&import_menu)
+                .separator()
+                .item(&print_item)
+                .build()?;
+
+            // Edit menu from the platform's own items, so undo, clipboard and
+            // selection go through the native responder chain and the webview
+            // handles them like any text field
+            let edit_menu = SubmenuBuilder::new(app, "Edit")
+                .undo()
+                .redo()
+                .separator()
+                .cut()
+                .copy()
+                .paste()
+                .separator()
+                .select_all()
+                .build()?;
+
+            // Window ▸ Blur raises or lowers the privacy screen
+            let blur_item = shortcuts::menu_item(app.handle(), ShortcutAction::Blur)?;
+            let lock_item = shortcuts::menu_item(app.handle(), ShortcutAction::Lock)?;
+            let window_menu = SubmenuBuilder::new(app, "Window")
+                .item(&blur_item)
+                .item(&lock_item)
+                .build()?;
+
+            let menu = MenuBuilder::new(app)
+                .items(&[&app_submenu, &file_menu, &edit_menu, &window_menu])
+                .build()?;
+            app.set_menu(menu)?;
+            tray::create(app.handle())?;
+            dock::create(app.handle());
+            privacy::apply(app.handle());
+            deep_link::register(app.handle());
+            services::register(app.handle());
+            spotlight::start(app.handle());
+            jobs::start(app.handle());
+            markdown_mirror::start(app.handle());
+
+            backup::start_scheduler();
+            cloud_backup::start_scheduler();
+            related::start_indexer();
+            ocr::start_worker();
+            transcription::start_worker();
+            sync::start_reconciler(app.handle().clone());
+            email_ingest::start_poller(app.handle().clone());
+            if let Err(e) = lan_sync::start_server(app.handle().clone()) {
+                warn!("LAN sync unavailable: {}", e);
+            }
+            if let Err(e) = api_server::start_server(app.handle().clone()) {
+                warn!("API server unavailable: {}", e);
+            }
+            Ok(())
+        })
+        .on_menu_event(|window, menu_event| match menu_event.id().0.as_str() {
+            "settings" => window.emit("open-settings", {}).unwrap(),
+            "check_updates" => window.emit("check-for-updates", {}).unwrap(),
+            "new_entry" => window.emit("new-entry", {}).unwrap(),
+            "blur" => privacy::toggle(window),
+            "quick_capture" => tray::open_quick_capture(window),
+            "lock" => tray::lock(window),
+            "print" => print::print_current(window),
+            id if file_menu::handle(window, id) => {}
+            id => {
+                if let Some(entry_id) = recent_menu::clicked_entry(id) {
+                    tray::show_main(window);
+                    window.emit("open-entry", entry_id).unwrap();
+                }
+            }
+        })
+        .on_window_event(|window, event| match event {
+            WindowEvent::DragDrop(DragDropEvent::Drop { paths, .. }) => {
+                file_drop::handle(window.app_handle(), paths.clone());
+            }
+            WindowEvent::Focused(false) => privacy::focus_lost(window.app_handle()),
+            _ => {}
+        })
+        .plugin(tauri_plugin_updater::Builder::new().build())
+        .plugin(tauri_plugin_dialog::init())
+        .register_uri_scheme_protocol(attachments::SCHEME, attachments::handle_protocol)
+        .invoke_handler(tauri::generate_handler![
+            get_entries,
+            get_entries_preview,
+            get_entries_between,
+            get_adjacent_entries,
+            get_entry,
+            open_entry_window,
+            print_entry,
+            print_window,
+            create_entry,
+            set_current_entry,
+            import_folder,
+            import_enex,
+            import_journal,
+            list_jobs,
+            cancel_job,
+            cancel_export,
+            get_or_create_today,
+            quick_capture,
+            save_entry,
+            set_entry_date,
+            delete_all_entries,
+            delete_entry,
+            archive_entry,
+            unarchive_entry,
+            export_database,
+            export_entries,
+            export_csv,
+            export_html,
+            export_obsidian,
+            export_feed,
+            set_markdown_mirror,
+            export_epub,
+            import_database,
+            authorize_keychain_command,
+            unlock_with_passphrase,
+            retry_keychain_access,
+            switch_to_passphrase,
+            restore_with_recovery_key,
+            generate_recovery_key,
+            secure_wipe,
+            get_security_status,
+            get_audit_log,
+            set_encryption_enabled,
+            set_cipher_params,
+            unseal_entry,
+            set_mood,
+            get_mood_trends,
+            import_health_csv,
+            get_correlations,
+            get_writing_stats,
+            get_longest_entries,
+            get_sentiment_trend,
+            get_word_frequencies,
+            get_writing_style,
+            get_writing_streak,
+            get_on_this_day,
+            generate_year_review,
+            export_year_review,
+            get_templates,
+            create_template,
+            update_template,
+            delete_template,
+            create_entry_from_template,
+            set_default_template,
+            get_random_prompt,
+            get_daily_prompt,
+            add_prompt,
+            delete_prompt,
+            get_backlinks,
+            get_outgoing_links,
+            get_people,
+            get_entries_mentioning,
+            set_photo_folder,
+            get_photo_gaps,
+            journal_photo_day,
+            set_entry_tags,
+            get_entry_tags,
+            get_all_tags,
+            suggest_tags,
+            search_entries,
+            get_notebooks,
+            create_notebook,
+            delete_notebook,
+            bulk_delete,
+            bulk_set_tags,
+            bulk_move_to_notebook,
+            lock_entry,
+            unlock_entry,
+            save_locked_entry,
+            remove_entry_lock,
+            list_backups,
+            restore_backup,
+            configure_cloud_backup,
+            configure_webdav_backup,
+            disable_cloud_backup,
+            list_cloud_backups,
+            restore_from_cloud,
+            get_settings,
+            save_settings,
+            list_profiles,
+            create_profile,
+            switch_profile,
+            get_data_directory,
+            set_data_directory,
+            get_privacy_screen,
+            set_privacy_screen,
+            set_privacy_options,
+            get_shortcuts,
+            set_shortcut,
+            rebuild_spotlight_index,
+            set_spotlight_indexing,
+            configure_sync,
+            disable_sync,
+            sync_now,
+            configure_email_ingest,
+            disable_email_ingest,
+            check_email_now,
+            set_lan_sync,
+            discover_lan_peers,
+            sync_with_peer,
+            configure_git_sync,
+            disable_git_sync,
+            git_sync_now,
+            list_conflicts,
+            resolve_conflict,
+            set_api_server,
+            reset_api_token,
+            add_audio_attachment,
+            get_audio_attachment,
+            get_entry_attachments,
+            get_attachment_text,
+            transcribe_attachment,
+            paste_image_from_clipboard,
+            copy_entry,
+            copy_entry_to_clipboard,
+            clear_clipboard_later,
+            gc_attachments,
+            optimize_database,
+            get_storage_report,
+            export_logs,
+            get_health,
+            get_migration_history,
+            set_entry_location,
+            get_entries_near,
+            set_entry_metadata,
+            get_entry_metadata,
+            define_custom_field,
+            get_custom_fields,
+            delete_custom_field,
+            set_custom_field_value,
+            get_entry_custom_fields,
+            get_related_entries,
+            summarize_entry,
+            summarize_range,
+            get_summaries,
+        ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(conn: &rusqlite::Connection) -> (i32, i64) {
+        let id = insert_entry(conn, "Monday", "<p>First</p>", "2024-03-05T12:00:00+00:00").unwrap();
+        let version = conn
+            .query_row("SELECT version FROM journal_entries WHERE id = ?1", [id], |row| row.get(0))
+            .unwrap();
+        (id, version)
+    }
+
+    fn body(conn: &rusqlite::Connection, id: i32) -> String {
+        conn.query_row("SELECT body FROM journal_entries WHERE id = ?1", [id], |row| row.get(0))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_save_at_loaded_version() {
+        let db = DatabaseManager::open_in_memory().unwrap();
+        let (id, version) = entry(&db.conn);
+        let saved = save_entry_text(&db.conn, id, "Monday", "<p>Second</p>", Some(version)).unwrap();
+        assert_eq!(saved, version + 1);
+        assert_eq!(body(&db.conn, id), "<p>Second</p>");
+        // The returned version is the one the next save has to match
+        assert_eq!(save_entry_text(&db.conn, id, "Monday", "<p>Third</p>", Some(saved)).unwrap(), saved + 1);
+    }
+
+    #[test]
+    fn test_save_at_stale_version_is_refused() {
+        let db = DatabaseManager::open_in_memory().unwrap();
+        let (id, version) = entry(&db.conn);
+        save_entry_text(&db.conn, id, "Monday", "<p>Elsewhere</p>", Some(version)).unwrap();
+        let error = save_entry_text(&db.conn, id, "Monday", "<p>Here</p>", Some(version)).unwrap_err();
+        assert!(error.starts_with(SAVE_CONFLICT));
+        assert_eq!(body(&db.conn, id), "<p>Elsewhere</p>");
+    }
+
+    #[test]
+    fn test_save_without_version() {
+        let db = DatabaseManager::open_in_memory().unwrap();
+        let (id, version) = entry(&db.conn);
+        save_entry_text(&db.conn, id, "Monday", "<p>Elsewhere</p>", Some(version)).unwrap();
+        assert_eq!(save_entry_text(&db.conn, id, "Monday", "<p>Here</p>", None).unwrap(), version + 2);
+        assert_eq!(body(&db.conn, id), "<p>Here</p>");
+    }
+}
@@ -0,0 +1,61 @@
+use serde::Serialize;
+use tauri::command;
+
+use crate::{DatabaseManager, JournalEntry};
+
+#[derive(Debug, Serialize)]
+pub struct OutgoingLink {
+    title: String,
+    /// The linked entry, or `None` when no entry with that title exists yet.
+    entry_id: Option<i32>,
+}
+
+/// Entries whose body links to this entry's title.
+#[command]
+pub fn get_backlinks(entry_id: i32) -> Result<Vec<JournalEntry>, String> {
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    let mut stmt = db.conn
+        .prepare(&format!(
+            "SELECT {} FROM journal_entries
+             WHERE id != ?1 AND id IN (
+                SELECT l.source_id FROM entry_links l
+                JOIN journal_entries t ON trim(t.title) = l.target_title COLLATE NOCASE
+                WHERE t.id = ?1
+             )
+             ORDER BY created_ms DESC, id DESC",
+            JournalEntry::COLUMNS
+        ))
+        .map_err(|e| e.to_string())?;
+    let entries = stmt
+        .query_map(rusqlite::params![entry_id], JournalEntry::from_row)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(entries)
+}
+
+/// Links written in this entry, resolved to entry ids where a matching title exists.
+#[command]
+pub fn get_outgoing_links(entry_id: i32) -> Result<Vec<OutgoingLink>, String> {
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    let mut stmt = db.conn
+        .prepare(
+            "SELECT l.target_title, MIN(t.id) FROM entry_links l
+             LEFT JOIN journal_entries t ON trim(t.title) = l.target_title COLLATE NOCASE
+             WHERE l.source_id = ?1
+             GROUP BY l.target_title
+             ORDER BY MIN(l.rowid)",
+        )
+        .map_err(|e| e.to_string())?;
+    let links = stmt
+        .query_map(rusqlite::params![entry_id], |row| {
+            Ok(OutgoingLink {
+                title: row.get(0)?,
+                entry_id: row.get(1)?,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(links)
+}
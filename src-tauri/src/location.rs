@@ -0,0 +1,177 @@
+use log::debug;
+use serde::{Deserialize, Serialize};
+use tauri::command;
+
+use crate::{DatabaseManager, JournalEntry};
+
+const MAX_PLACE_CHARS: usize = 200;
+/// Mean Earth radius used for great-circle distances.
+const EARTH_RADIUS_KM: f64 = 6371.0;
+/// Kilometres per degree of latitude, close enough everywhere for a prefilter.
+const KM_PER_DEGREE: f64 = 111.2;
+
+/// Where an entry was written.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Location {
+    pub latitude: f64,
+    pub longitude: f64,
+    /// Human-readable name, e.g. "Lisbon, Portugal".
+    pub place: Option<String>,
+}
+
+impl Location {
+    /// Rebuilds a location from its stored columns; `None` when unset.
+    pub fn from_columns(latitude: Option<f64>, longitude: Option<f64>, place: Option<String>) -> Option<Self> {
+        Some(Location { latitude: latitude?, longitude: longitude?, place })
+    }
+
+    fn normalized(self) -> Result<Self, String> {
+        if !(-90.0..=90.0).contains(&self.latitude) || !(-180.0..=180.0).contains(&self.longitude) {
+            return Err(format!("Invalid coordinates {}, {}", self.latitude, self.longitude));
+        }
+        let place = match self.place {
+            Some(place) => {
+                let trimmed = place.trim();
+                if trimmed.chars().count() > MAX_PLACE_CHARS {
+                    return Err(format!("Place names must be at most {} characters", MAX_PLACE_CHARS));
+                }
+                (!trimmed.is_empty()).then(|| trimmed.to_string())
+            }
+            None => None,
+        };
+        Ok(Location { place, ..self })
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct NearbyEntry {
+    #[serde(flatten)]
+    entry: JournalEntry,
+    location: Location,
+    distance_km: f64,
+}
+
+/// Great-circle distance between two points, by the haversine formula.
+fn distance_km(from: (f64, f64), to: (f64, f64)) -> f64 {
+    let (lat1, lat2) = (from.0.to_radians(), to.0.to_radians());
+    let d_lat = lat2 - lat1;
+    let d_lon = (to.1 - from.1).to_radians();
+    let a = (d_lat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (d_lon / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_KM * a.sqrt().asin()
+}
+
+fn entries_near(
+    conn: &rusqlite::Connection,
+    latitude: f64,
+    longitude: f64,
+    radius_km: f64,
+) -> rusqlite::Result<Vec<NearbyEntry>> {
+    // SQLite has no trigonometry, so narrow down by latitude band here and
+    // measure the real distance below
+    let band = radius_km / KM_PER_DEGREE;
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {}, latitude, longitude, place FROM journal_entries
+         WHERE latitude BETWEEN ?1 AND ?2 AND longitude IS NOT NULL",
+        JournalEntry::COLUMNS
+    ))?;
+    let mut entries = stmt
+        .query_map(rusqlite::params![latitude - band, latitude + band], |row| {
+            let location = Location {
+                latitude: row.get(7)?,
+                longitude: row.get(8)?,
+                place: row.get(9)?,
+            };
+            Ok(NearbyEntry {
+                entry: JournalEntry::from_row(row)?,
+                distance_km: distance_km((latitude, longitude), (location.latitude, location.longitude)),
+                location,
+            })
+        })?
+        .filter(|entry| entry.as_ref().map_or(true, |entry| entry.distance_km <= radius_km))
+        .collect::<Result<Vec<_>, _>>()?;
+    entries.sort_by(|a, b| a.distance_km.total_cmp(&b.distance_km));
+    Ok(entries)
+}
+
+/// Sets or clears (`location: null`) where an entry was written.
+#[command]
+pub fn set_entry_location(entry_id: i32, location: Option<Location>) -> Result<(), String> {
+    let location = location.map(Location::normalized).transpose()?;
+    debug!("Setting location for entry {}", entry_id);
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    let updated = db
+        .conn
+        .execute(
+            "UPDATE journal_entries SET latitude = ?1, longitude = ?2, place = ?3 WHERE id = ?4",
+            rusqlite::params![
+                location.as_ref().map(|l| l.latitude),
+                location.as_ref().map(|l| l.longitude),
+                location.and_then(|l| l.place),
+                entry_id
+            ],
+        )
+        .map_err(|e| e.to_string())?;
+    if updated == 0 {
+        return Err(format!("Entry {} not found", entry_id));
+    }
+    Ok(())
+}
+
+/// Entries written within `radius_km` of a point, nearest first, for the map.
+#[command]
+pub fn get_entries_near(latitude: f64, longitude: f64, radius_km: f64) -> Result<Vec<NearbyEntry>, String> {
+    if !(radius_km.is_finite() && radius_km >= 0.0) {
+        return Err("Radius must be a positive distance".to_string());
+    }
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    entries_near(&db.conn, latitude, longitude, radius_km).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use journal_core::{insert_entry, timestamp_now};
+
+    const LONDON: (f64, f64) = (51.5074, -0.1278);
+    const PARIS: (f64, f64) = (48.8566, 2.3522);
+
+    #[test]
+    fn test_distance_km() {
+        assert!((distance_km(LONDON, PARIS) - 343.5).abs() < 1.0);
+        assert_eq!(distance_km(PARIS, PARIS), 0.0);
+    }
+
+    #[test]
+    fn test_normalized_location() {
+        let location = Location { latitude: 10.0, longitude: 20.0, place: Some("  ".to_string()) };
+        assert_eq!(location.normalized().unwrap().place, None);
+        assert!(Location { latitude: 91.0, longitude: 0.0, place: None }.normalized().is_err());
+        assert!(Location { latitude: 0.0, longitude: f64::NAN, place: None }.normalized().is_err());
+    }
+
+    #[test]
+    fn test_entries_near() {
+        let db = DatabaseManager::open_in_memory().unwrap();
+        for (title, (latitude, longitude)) in [("London", LONDON), ("Paris", PARIS)] {
+            let id = insert_entry(&db.conn, title, "", &timestamp_now()).unwrap();
+            db.conn
+                .execute(
+                    "UPDATE journal_entries SET latitude = ?1, longitude = ?2 WHERE id = ?3",
+                    rusqlite::params![latitude, longitude, id],
+                )
+                .unwrap();
+        }
+        insert_entry(&db.conn, "Nowhere", "", &timestamp_now()).unwrap();
+
+        let near_london = entries_near(&db.conn, LONDON.0, LONDON.1, 50.0).unwrap();
+        assert_eq!(near_london.len(), 1);
+        assert_eq!(near_london[0].entry.title, "London");
+
+        let titles: Vec<_> = entries_near(&db.conn, PARIS.0, PARIS.1, 500.0)
+            .unwrap()
+            .into_iter()
+            .map(|nearby| nearby.entry.title)
+            .collect();
+        assert_eq!(titles, vec!["Paris", "London"]);
+    }
+}
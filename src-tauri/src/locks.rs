@@ -0,0 +1,176 @@
+use log::debug;
+use rusqlite::OptionalExtension;
+use tauri::{command, AppHandle};
+
+use journal_core::links::update_links;
+use journal_core::people::update_people;
+use journal_core::text::body_word_count;
+
+use crate::crypto::{self, Sealed, INCORRECT_PASSPHRASE};
+use crate::markdown_mirror;
+use crate::sealing;
+use crate::settings::Settings;
+use crate::unlock_attempts::{self, Secret};
+use crate::validation::clean_entry;
+use crate::{load_entry, DatabaseManager, FullJournalEntry};
+
+// Locked entries keep their title visible in the list, but the body is
+// encrypted a second time (on top of SQLCipher) with a key derived from a
+// passphrase only the user knows. The plaintext body is never written back
+// to `body` until the lock is removed.
+
+const MIN_PASSPHRASE_CHARS: usize = 6;
+
+fn encrypt_body(passphrase: &str, body: &str) -> Result<Sealed, String> {
+    crypto::seal(passphrase, body.as_bytes())
+}
+
+fn decrypt_body(passphrase: &str, locked: &Sealed) -> Result<String, String> {
+    let plaintext = crypto::open(passphrase, locked)?;
+    String::from_utf8(plaintext).map_err(|e| e.to_string())
+}
+
+/// Decrypts entry `id`'s body with a passphrase the user typed, counting
+/// wrong ones towards the unlock throttle.
+fn open_locked(app: &AppHandle, conn: &rusqlite::Connection, id: i32, passphrase: &str) -> Result<String, String> {
+    unlock_attempts::check(Secret::Entry)?;
+    let result = decrypt_body(passphrase, &load_locked_body(conn, id)?);
+    let correct = match &result {
+        Ok(_) => Some(true),
+        Err(e) if e == INCORRECT_PASSPHRASE => Some(false),
+        Err(_) => None,
+    };
+    unlock_attempts::record(app, Secret::Entry, correct);
+    result
+}
+
+pub fn is_locked(conn: &rusqlite::Connection, id: i32) -> rusqlite::Result<bool> {
+    conn.query_row(
+        "SELECT locked FROM journal_entries WHERE id = ?1",
+        rusqlite::params![id],
+        |row| row.get(0),
+    )
+    .optional()
+    .map(|locked| locked.unwrap_or(false))
+}
+
+fn load_locked_body(conn: &rusqlite::Connection, id: i32) -> Result<Sealed, String> {
+    conn.query_row(
+        "SELECT lock_salt, lock_nonce, locked_body FROM journal_entries WHERE id = ?1 AND locked = 1",
+        rusqlite::params![id],
+        |row| {
+            Ok(Sealed {
+                salt: row.get(0)?,
+                nonce: row.get(1)?,
+                ciphertext: row.get(2)?,
+            })
+        },
+    )
+    .optional()
+    .map_err(|e| e.to_string())?
+    .ok_or_else(|| format!("Entry {} is not locked", id))
+}
+
+fn store_locked_body(conn: &rusqlite::Connection, id: i32, locked: &Sealed) -> rusqlite::Result<()> {
+    conn.execute(
+        "UPDATE journal_entries
+         SET locked = 1, body = '', lock_salt = ?1, lock_nonce = ?2, locked_body = ?3
+         WHERE id = ?4",
+        rusqlite::params![locked.salt, locked.nonce, locked.ciphertext, id],
+    )?;
+    // Links and people are derived from the body, so they'd leak what the lock hides
+    update_links(conn, id, "")?;
+    update_people(conn, id, "")
+}
+
+/// Encrypts an entry's body with `passphrase`.
+#[command]
+pub fn lock_entry(id: i32, passphrase: String) -> Result<(), String> {
+    if passphrase.chars().count() < MIN_PASSPHRASE_CHARS {
+        return Err(format!("Passphrase must be at least {} characters", MIN_PASSPHRASE_CHARS));
+    }
+    debug!("Locking entry {}", id);
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    let body: String = db.conn
+        .query_row(
+            "SELECT body FROM journal_entries WHERE id = ?1 AND locked = 0",
+            rusqlite::params![id],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Entry {} not found or already locked", id))?;
+    let locked = encrypt_body(&passphrase, &body)?;
+    let tx = db.conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    store_locked_body(&tx, id, &locked).map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())?;
+    // Its plain-text copy shouldn't outlive the lock
+    markdown_mirror::refresh();
+    Ok(())
+}
+
+/// Returns a locked entry with its body decrypted. Nothing is persisted, so the
+/// entry stays locked for the next `get_entry`.
+#[command]
+pub fn unlock_entry(app: AppHandle, id: i32, passphrase: String) -> Result<FullJournalEntry, String> {
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    let body = open_locked(&app, &db.conn, id, &passphrase)?;
+    let mut entry = load_entry(&db.conn, id)?;
+    entry.body = body;
+    Ok(entry)
+}
+
+/// Saves edits to a locked entry, re-encrypting the new body with the same passphrase.
+#[command]
+pub fn save_locked_entry(app: AppHandle, id: i32, title: String, body: String, passphrase: String) -> Result<(), String> {
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    open_locked(&app, &db.conn, id, &passphrase)?;
+    sealing::ensure_unsealed(&db.conn, id)?;
+    let (title, body) = clean_entry(&title, &body, &Settings::load().limits)?;
+    let locked = encrypt_body(&passphrase, &body)?;
+    let tx = db.conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    tx.execute(
+        "UPDATE journal_entries SET title = ?1, word_count = ?2 WHERE id = ?3",
+        rusqlite::params![title, body_word_count(&body), id],
+    )
+    .map_err(|e| e.to_string())?;
+    store_locked_body(&tx, id, &locked).map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())
+}
+
+/// Permanently decrypts a locked entry back into a normal one.
+#[command]
+pub fn remove_entry_lock(app: AppHandle, id: i32, passphrase: String) -> Result<(), String> {
+    debug!("Removing lock from entry {}", id);
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    let body = open_locked(&app, &db.conn, id, &passphrase)?;
+    let tx = db.conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    tx.execute(
+        "UPDATE journal_entries
+         SET locked = 0, body = ?1, lock_salt = NULL, lock_nonce = NULL, locked_body = NULL
+         WHERE id = ?2",
+        rusqlite::params![body, id],
+    )
+    .map_err(|e| e.to_string())?;
+    update_links(&tx, id, &body).map_err(|e| e.to_string())?;
+    update_people(&tx, id, &body).map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encrypt_round_trip() {
+        let locked = encrypt_body("correct horse", "<p>secret</p>").unwrap();
+        assert_ne!(locked.ciphertext, b"<p>secret</p>".to_vec());
+        assert_eq!(decrypt_body("correct horse", &locked).unwrap(), "<p>secret</p>");
+    }
+
+    #[test]
+    fn test_wrong_passphrase_rejected() {
+        let locked = encrypt_body("correct horse", "secret").unwrap();
+        assert_eq!(decrypt_body("battery staple", &locked).unwrap_err(), INCORRECT_PASSPHRASE);
+    }
+}
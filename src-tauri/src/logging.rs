@@ -0,0 +1,266 @@
+use chrono::{SecondsFormat, Utc};
+use log::{info, LevelFilter, Log, Metadata, Record};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tauri::command;
+
+use journal_core::app_support_dir;
+
+use crate::settings::Settings;
+
+// A bundled app's stderr goes nowhere anyone can see, so log lines are also
+// written to `Logs/journal.log` under app support, shared by every profile.
+// The file is rotated once it reaches `MAX_LOG_BYTES`, keeping a few older
+// ones. The app never logs entry text on purpose, but errors can quote what
+// they failed on, so `export_logs` redacts quoted text, email addresses,
+// tokens and the home folder before the logs leave the machine.
+
+const LOG_DIR_NAME: &str = "Logs";
+const LOG_FILE_NAME: &str = "journal.log";
+const MAX_LOG_BYTES: u64 = 1024 * 1024;
+/// Rotated files kept besides the current one, `journal.log.1` the newest.
+const KEPT_LOGS: usize = 4;
+/// Unbroken runs of letters and digits at least this long are taken for keys or tokens.
+const TOKEN_CHARS: usize = 32;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogLevel {
+    Error,
+    Warn,
+    #[default]
+    Info,
+    Debug,
+    Trace,
+}
+
+impl LogLevel {
+    fn filter(self) -> LevelFilter {
+        match self {
+            LogLevel::Error => LevelFilter::Error,
+            LogLevel::Warn => LevelFilter::Warn,
+            LogLevel::Info => LevelFilter::Info,
+            LogLevel::Debug => LevelFilter::Debug,
+            LogLevel::Trace => LevelFilter::Trace,
+        }
+    }
+}
+
+struct LogFile {
+    dir: PathBuf,
+    file: File,
+    size: u64,
+}
+
+impl LogFile {
+    fn open(dir: &Path) -> std::io::Result<Self> {
+        fs::create_dir_all(dir)?;
+        let file = OpenOptions::new().create(true).append(true).open(dir.join(LOG_FILE_NAME))?;
+        let size = file.metadata()?.len();
+        Ok(LogFile {
+            dir: dir.to_path_buf(),
+            file,
+            size,
+        })
+    }
+
+    /// Shifts every file along one, dropping the oldest, and starts a new one.
+    fn rotate(&mut self) -> std::io::Result<()> {
+        let numbered = |n: usize| self.dir.join(format!("{}.{}", LOG_FILE_NAME, n));
+        let _ = fs::remove_file(numbered(KEPT_LOGS));
+        for n in (1..KEPT_LOGS).rev() {
+            let _ = fs::rename(numbered(n), numbered(n + 1));
+        }
+        fs::rename(self.dir.join(LOG_FILE_NAME), numbered(1))?;
+        *self = LogFile::open(&self.dir)?;
+        Ok(())
+    }
+
+    fn write_line(&mut self, line: &str) -> std::io::Result<()> {
+        if self.size + line.len() as u64 > MAX_LOG_BYTES && self.size > 0 {
+            self.rotate()?;
+        }
+        self.file.write_all(line.as_bytes())?;
+        self.size += line.len() as u64;
+        Ok(())
+    }
+}
+
+/// Logs to the file at the level in Settings, and to stderr as `RUST_LOG` says
+/// within that.
+struct Logger {
+    stderr: env_logger::Logger,
+    file: Mutex<Option<LogFile>>,
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        self.stderr.log(record);
+        let line = format!(
+            "{} {:<5} {}: {}\n",
+            Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+            record.level(),
+            record.target(),
+            record.args()
+        );
+        if let Ok(mut file) = self.file.lock() {
+            if let Some(log_file) = file.as_mut() {
+                // Nowhere left to report a failure to write the log
+                if log_file.write_line(&line).is_err() {
+                    *file = None;
+                }
+            }
+        }
+    }
+
+    fn flush(&self) {
+        if let Ok(mut file) = self.file.lock() {
+            if let Some(log_file) = file.as_mut() {
+                let _ = log_file.file.flush();
+            }
+        }
+    }
+}
+
+fn log_dir() -> Option<PathBuf> {
+    app_support_dir().ok().map(|dir| dir.join(LOG_DIR_NAME))
+}
+
+/// Installs the logger. Called once, before anything is logged.
+pub fn init() {
+    let file = log_dir().and_then(|dir| LogFile::open(&dir).ok());
+    let logger = Logger {
+        stderr: env_logger::Builder::from_default_env().build(),
+        file: Mutex::new(file),
+    };
+    if log::set_boxed_logger(Box::new(logger)).is_ok() {
+        apply(Settings::load().logging.level);
+    }
+}
+
+/// Logs at `level` from now on.
+pub fn apply(level: LogLevel) {
+    log::set_max_level(level.filter());
+}
+
+/// `line` with what could be the user's own text or secrets taken out.
+fn redact(line: &str, home: Option<&str>) -> String {
+    let line = match home.filter(|home| !home.is_empty()) {
+        Some(home) => line.replace(home, "~"),
+        None => line.to_string(),
+    };
+    let mut redacted = String::with_capacity(line.len());
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        redacted.push(c);
+        // Quoted text: titles, search queries, dates typed in, file names.
+        // An apostrophe inside a word isn't a quote.
+        let close = match c {
+            '"' => '"',
+            '\'' if !redacted.chars().rev().nth(1).is_some_and(char::is_alphanumeric) => '\'',
+            '‘' => '’',
+            '“' => '”',
+            _ => continue,
+        };
+        if let Some(len) = chars.clone().position(|next| next == close) {
+            chars.nth(len);
+            redacted.push_str("[redacted]");
+            redacted.push(close);
+        }
+    }
+    redacted
+        .split(' ')
+        .map(|word| {
+            let token = word.trim_matches(|c: char| !c.is_alphanumeric());
+            if token.contains('@') && token.contains('.') {
+                word.replace(token, "[email]")
+            } else if token.len() >= TOKEN_CHARS && token.chars().all(|c| c.is_ascii_alphanumeric()) {
+                word.replace(token, "[token]")
+            } else {
+                word.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// The log files in `dir`, oldest first.
+fn log_files(dir: &Path) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> =
+        (1..=KEPT_LOGS).rev().map(|n| dir.join(format!("{}.{}", LOG_FILE_NAME, n))).collect();
+    files.push(dir.join(LOG_FILE_NAME));
+    files.into_iter().filter(|path| path.exists()).collect()
+}
+
+fn export_from(dir: &Path, path: &Path, home: Option<&str>) -> Result<usize, String> {
+    let mut lines = Vec::new();
+    for file in log_files(dir) {
+        let contents = fs::read(&file).map_err(|e| format!("Failed to read {}: {}", file.display(), e))?;
+        lines.extend(String::from_utf8_lossy(&contents).lines().map(|line| redact(line, home)));
+    }
+    let mut contents = lines.join("\n");
+    contents.push('\n');
+    fs::write(path, contents).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    Ok(lines.len())
+}
+
+/// Writes the logs, oldest first and redacted, to `path` for attaching to a
+/// bug report. Returns how many lines were written.
+#[command]
+pub fn export_logs(path: String) -> Result<usize, String> {
+    log::logger().flush();
+    let dir = log_dir().ok_or_else(|| "Could not find the logs folder".to_string())?;
+    let home = std::env::var("HOME").ok();
+    let count = export_from(&dir, Path::new(&path), home.as_deref())?;
+    info!("Exported {} log lines", count);
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact() {
+        let home = Some("/Users/sam");
+        assert_eq!(
+            redact("Invalid entry date 'March 1st' in /Users/sam/Journal", home),
+            "Invalid entry date '[redacted]' in ~/Journal"
+        );
+        assert_eq!(redact("Couldn't open “Trip notes”", home), "Couldn't open “[redacted]”");
+        assert_eq!(
+            redact("Mail from sam@example.com, key 0123456789abcdef0123456789abcdef.", home),
+            "Mail from [email], key [token]."
+        );
+        assert_eq!(redact("Applying schema migration 38", home), "Applying schema migration 38");
+    }
+
+    #[test]
+    fn test_rotation_and_export() {
+        let dir = std::env::temp_dir().join(format!("journal-logs-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let mut log = LogFile::open(&dir).unwrap();
+        let line = format!("{}\n", "x".repeat(1023));
+        let lines_per_file = MAX_LOG_BYTES as usize / line.len();
+        for _ in 0..lines_per_file * (KEPT_LOGS + 2) {
+            log.write_line(&line).unwrap();
+        }
+        log.write_line("last 'secret'\n").unwrap();
+        assert_eq!(log_files(&dir).len(), KEPT_LOGS + 1);
+        assert!(!dir.join(format!("{}.{}", LOG_FILE_NAME, KEPT_LOGS + 1)).exists());
+
+        let export = dir.join("export.log");
+        let count = export_from(&dir, &export, None).unwrap();
+        // The oldest full file was dropped when the last line started a new one
+        assert_eq!(count, lines_per_file * KEPT_LOGS + 1);
+        assert!(fs::read_to_string(&export).unwrap().ends_with("last '[redacted]'\n"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,226 @@
+use log::info;
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::command;
+
+use journal_core::profiles::database_path;
+
+use crate::backup::{backups_dir, scheduled_dir};
+use crate::jobs::{Job, JobKind};
+use crate::settings::Settings;
+use crate::DatabaseManager;
+
+// Keeps the database file in shape and shows what the app is storing.
+// SQLite holds on to the pages of deleted rows for reuse instead of handing
+// them back to the disk, so deleting years of entries or attachments never
+// makes the file smaller. Optimizing rebuilds it at its real size and
+// refreshes the statistics the query planner uses.
+
+/// Each step of an optimization, with what the job shows while it runs.
+const STEPS: &[(&str, &str)] = &[
+    ("Compacting", "VACUUM"),
+    ("Updating statistics", "ANALYZE"),
+    ("Optimizing queries", "PRAGMA optimize"),
+];
+
+#[derive(Debug, Serialize)]
+pub struct OptimizeReport {
+    pub size_before: u64,
+    pub size_after: u64,
+    pub reclaimed_bytes: u64,
+}
+
+/// How big the database is, in whole pages.
+fn database_size(conn: &rusqlite::Connection) -> rusqlite::Result<u64> {
+    conn.query_row("SELECT page_count * page_size FROM pragma_page_count(), pragma_page_size()", [], |row| {
+        row.get(0)
+    })
+}
+
+fn optimize(conn: &rusqlite::Connection, job: &Job) -> Result<OptimizeReport, String> {
+    let size_before = database_size(conn).map_err(|e| e.to_string())?;
+    for (i, (label, sql)) in STEPS.iter().enumerate() {
+        job.step(i, STEPS.len(), Some(label))?;
+        conn.execute_batch(sql).map_err(|e| format!("{} failed: {}", label, e))?;
+    }
+    let size_after = database_size(conn).map_err(|e| e.to_string())?;
+    Ok(OptimizeReport {
+        size_before,
+        size_after,
+        reclaimed_bytes: size_before.saturating_sub(size_after),
+    })
+}
+
+/// Compacts the database and refreshes its statistics, returning how much
+/// space was given back. Runs in the background as a job; `VACUUM` itself
+/// can't be cancelled part-way, only between steps.
+#[command]
+pub async fn optimize_database() -> Result<OptimizeReport, String> {
+    tauri::async_runtime::spawn_blocking(|| {
+        let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+        let report = Job::run(JobKind::Maintenance, "Optimizing the database", |job| optimize(&db.conn, job))?;
+        info!("Optimized the database, reclaiming {} bytes", report.reclaimed_bytes);
+        Ok(report)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[derive(Debug, Serialize)]
+pub struct TableSize {
+    pub name: String,
+    pub bytes: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FolderSize {
+    pub path: String,
+    pub bytes: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct StorageReport {
+    pub database_path: String,
+    /// The database file with its write-ahead log, if there is one.
+    pub database_bytes: u64,
+    /// Space each table and its indexes take up in the file, largest first.
+    pub tables: Vec<TableSize>,
+    pub attachment_count: i64,
+    /// Attachment bytes as stored, counting a file attached twice once.
+    pub attachment_bytes: u64,
+    /// Snapshots taken before risky operations, and scheduled backups if
+    /// they go to another folder.
+    pub backups: Vec<FolderSize>,
+    /// Free space on the disk the database is on; `None` where it can't be told.
+    pub free_bytes: Option<u64>,
+}
+
+/// Space in the database taken by each table, its indexes counted with it.
+fn table_sizes(conn: &rusqlite::Connection) -> rusqlite::Result<Vec<TableSize>> {
+    let mut stmt = conn.prepare(
+        "SELECT COALESCE(m.tbl_name, s.name), SUM(s.pgsize) AS bytes
+         FROM dbstat s LEFT JOIN sqlite_master m ON m.name = s.name
+         GROUP BY 1 ORDER BY bytes DESC, 1",
+    )?;
+    let tables = stmt
+        .query_map([], |row| {
+            Ok(TableSize {
+                name: row.get(0)?,
+                bytes: row.get(1)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(tables)
+}
+
+fn file_size(path: &Path) -> u64 {
+    fs::metadata(path).map(|meta| meta.len()).unwrap_or(0)
+}
+
+/// Bytes in the files under `dir`, none if it doesn't exist.
+fn folder_size(dir: &Path) -> u64 {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return 0;
+    };
+    entries
+        .flatten()
+        .map(|entry| match entry.file_type() {
+            Ok(kind) if kind.is_dir() => folder_size(&entry.path()),
+            Ok(_) => entry.metadata().map(|meta| meta.len()).unwrap_or(0),
+            Err(_) => 0,
+        })
+        .sum()
+}
+
+#[cfg(unix)]
+fn free_space(path: &Path) -> Option<u64> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut stats = std::mem::MaybeUninit::<libc::statvfs>::uninit();
+    // SAFETY: `path` is a valid C string and `stats` is only read once statvfs has filled it in
+    if unsafe { libc::statvfs(path.as_ptr(), stats.as_mut_ptr()) } != 0 {
+        return None;
+    }
+    let stats = unsafe { stats.assume_init() };
+    Some(stats.f_bavail as u64 * stats.f_frsize as u64)
+}
+
+#[cfg(not(unix))]
+fn free_space(_path: &Path) -> Option<u64> {
+    None
+}
+
+/// What the journal takes up on disk and where, for a storage view in Settings.
+#[command]
+pub fn get_storage_report() -> Result<StorageReport, String> {
+    let path = database_path().map_err(|e| e.to_string())?;
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    let tables = table_sizes(&db.conn).map_err(|e| e.to_string())?;
+    let (attachment_count, attachment_bytes): (i64, i64) = db
+        .conn
+        .query_row(
+            "SELECT (SELECT COUNT(*) FROM attachments), (SELECT COALESCE(SUM(size), 0) FROM attachment_blobs)",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .map_err(|e| e.to_string())?;
+
+    let mut backup_dirs: Vec<PathBuf> = vec![backups_dir().map_err(|e| e.to_string())?];
+    let scheduled = scheduled_dir(&Settings::load().backup).map_err(|e| e.to_string())?;
+    if !backup_dirs.contains(&scheduled) {
+        backup_dirs.push(scheduled);
+    }
+    let mut wal = path.clone().into_os_string();
+    wal.push("-wal");
+    Ok(StorageReport {
+        database_path: path.to_string_lossy().into_owned(),
+        database_bytes: file_size(&path) + file_size(Path::new(&wal)),
+        tables,
+        attachment_count,
+        attachment_bytes: attachment_bytes as u64,
+        backups: backup_dirs
+            .iter()
+            .map(|dir| FolderSize {
+                path: dir.to_string_lossy().into_owned(),
+                bytes: folder_size(dir),
+            })
+            .collect(),
+        free_bytes: path.parent().and_then(free_space),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use journal_core::{insert_entry, timestamp_now};
+
+    #[test]
+    fn test_optimize_reclaims_deleted_space() {
+        let db = DatabaseManager::open_in_memory().unwrap();
+        let body = format!("<p>{}</p>", "word ".repeat(20_000));
+        for _ in 0..20 {
+            insert_entry(&db.conn, "Long", &body, &timestamp_now()).unwrap();
+        }
+        db.conn.execute("DELETE FROM journal_entries", []).unwrap();
+
+        let report = optimize(&db.conn, &Job::start(JobKind::Maintenance, "Test")).unwrap();
+        assert!(report.reclaimed_bytes > 0);
+        assert_eq!(report.size_after, database_size(&db.conn).unwrap());
+    }
+
+    #[test]
+    fn test_table_sizes() {
+        let db = DatabaseManager::open_in_memory().unwrap();
+        let body = format!("<p>{}</p>", "word ".repeat(20_000));
+        insert_entry(&db.conn, "Long", &body, &timestamp_now()).unwrap();
+        let tables = table_sizes(&db.conn).unwrap();
+        assert_eq!(tables[0].name, "journal_entries");
+        // Indexes are counted with their table, not on their own
+        assert!(!tables.iter().any(|table| table.name == "idx_journal_entries_local_date"));
+        // Pages freed by migrations belong to no table
+        assert!(tables.iter().map(|table| table.bytes).sum::<u64>() <= database_size(&db.conn).unwrap());
+    }
+}
@@ -0,0 +1,159 @@
+use log::{debug, warn};
+use std::collections::BTreeSet;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use tauri::{command, AppHandle, Listener};
+
+use journal_core::audit::AuditAction;
+use journal_core::keychain::KeychainManager;
+
+use crate::obsidian::vault_notes;
+use crate::security;
+use crate::settings::{MirrorSettings, Settings};
+use crate::DatabaseManager;
+
+// An always-current plain-text copy of the journal, for backup tools like
+// Time Machine or Syncthing to pick up: every readable entry is kept as a
+// Markdown file in a folder the user chooses, written the way the Obsidian
+// export writes them and brought up to date whenever entries are saved. Only
+// files whose contents changed are rewritten, so backup tools see real
+// changes only. The folder may hold other files; a list of the ones the
+// mirror wrote is kept in it, and only those are ever removed. Locked
+// entries are left out, so locking an entry removes its file.
+
+/// The files the mirror wrote, one name per line.
+const MANIFEST_FILE_NAME: &str = ".journal-mirror";
+/// Events after which the mirror may be out of date.
+const REFRESH_EVENTS: &[&str] = &["entries-changed", "entries-synced", "files-dropped"];
+
+/// Serializes mirror runs so two can't write the folder at once.
+static MIRROR_LOCK: Mutex<()> = Mutex::new(());
+/// Set while a run is waiting to start, so saves arriving together share it.
+static PENDING: AtomicBool = AtomicBool::new(false);
+
+/// Brings the Markdown files in `dir` up to date and returns how many entries
+/// it holds.
+pub fn mirror_into(conn: &rusqlite::Connection, dir: &Path) -> Result<usize, String> {
+    let notes = vault_notes(conn)?;
+    fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    let manifest = dir.join(MANIFEST_FILE_NAME);
+    let written: BTreeSet<String> = fs::read_to_string(&manifest)
+        .unwrap_or_default()
+        .lines()
+        // Only ever a file in the folder itself, whatever the list says
+        .filter(|file| !file.contains(['/', '\\']) && file.ends_with(".md"))
+        .map(str::to_string)
+        .collect();
+    let mut current = BTreeSet::new();
+    for (name, md) in &notes {
+        let file = format!("{}.md", name);
+        let path = dir.join(&file);
+        if fs::read_to_string(&path).ok().as_deref() != Some(md.as_str()) {
+            fs::write(&path, md).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        }
+        current.insert(file);
+    }
+    for file in written.difference(&current) {
+        let path = dir.join(file);
+        match fs::remove_file(&path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                return Err(format!("Failed to remove {}: {}", path.display(), e));
+            }
+            _ => {}
+        }
+    }
+    let listing: String = current.iter().map(|file| format!("{}\n", file)).collect();
+    fs::write(&manifest, listing).map_err(|e| format!("Failed to write {}: {}", manifest.display(), e))?;
+    Ok(notes.len())
+}
+
+fn mirror_configured(settings: &MirrorSettings) -> Result<usize, String> {
+    let dir = settings.directory.as_deref().ok_or("No mirror folder is configured")?;
+    let _guard = MIRROR_LOCK.lock().map_err(|e| e.to_string())?;
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    mirror_into(&db.conn, Path::new(dir))
+}
+
+/// Updates the mirror in the background, if it's on and the journal is
+/// unlocked. Like the Git mirror, it covers the whole journal, so changes
+/// made by commands that don't call this are picked up by the next run.
+pub fn refresh() {
+    let settings = Settings::load().mirror;
+    if !settings.enabled || !KeychainManager::has_cached_key() || PENDING.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    thread::spawn(move || {
+        PENDING.store(false, Ordering::SeqCst);
+        match mirror_configured(&settings) {
+            Ok(count) => debug!("Mirrored {} entries as Markdown", count),
+            Err(e) => warn!("Failed to update the Markdown mirror: {}", e),
+        }
+    });
+}
+
+/// Keeps the mirror current as entries change elsewhere. Called once from setup.
+pub fn start(app: &AppHandle) {
+    for event in REFRESH_EVENTS {
+        app.listen_any(*event, |_| refresh());
+    }
+    refresh();
+}
+
+/// Starts mirroring every entry as Markdown into folder `directory`, writing
+/// it now and returning how many entries it holds, or stops when `directory`
+/// is `None`. Stopping leaves the files where they are.
+#[command]
+pub async fn set_markdown_mirror(directory: Option<String>) -> Result<usize, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let mut settings = Settings::load();
+        settings.mirror = MirrorSettings {
+            enabled: directory.is_some(),
+            directory: directory.or(settings.mirror.directory),
+        };
+        settings.save().map_err(|e| e.to_string())?;
+        if !settings.mirror.enabled {
+            return Ok(0);
+        }
+        let count = mirror_configured(&settings.mirror)?;
+        if let Some(dir) = &settings.mirror.directory {
+            security::audit(AuditAction::Export, &format!("{} entries as a Markdown mirror to {}", count, dir));
+        }
+        Ok(count)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use journal_core::insert_entry;
+
+    #[test]
+    fn test_mirror_into() {
+        let db = DatabaseManager::open_in_memory().unwrap();
+        let id = insert_entry(&db.conn, "Lisbon", "<p>Trams</p>", "2024-03-05T12:00:00+00:00").unwrap();
+        insert_entry(&db.conn, "Home", "<p>Back</p>", "2024-04-02T12:00:00+00:00").unwrap();
+        let dir = std::env::temp_dir().join(format!("journal-mirror-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("notes.md"), "Not the journal's").unwrap();
+
+        assert_eq!(mirror_into(&db.conn, &dir).unwrap(), 2);
+        assert!(fs::read_to_string(dir.join("2024-03-05.md")).unwrap().ends_with("# Lisbon\n\nTrams\n"));
+        assert!(dir.join("2024-04-02.md").exists());
+
+        db.conn
+            .execute("UPDATE journal_entries SET locked = 1 WHERE id = ?1", rusqlite::params![id])
+            .unwrap();
+        assert_eq!(mirror_into(&db.conn, &dir).unwrap(), 1);
+        assert!(!dir.join("2024-03-05.md").exists());
+        assert!(dir.join("2024-04-02.md").exists());
+        assert_eq!(fs::read_to_string(dir.join("notes.md")).unwrap(), "Not the journal's");
+        assert_eq!(fs::read_to_string(dir.join(MANIFEST_FILE_NAME)).unwrap(), "2024-04-02.md\n");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,126 @@
+use log::debug;
+use rusqlite::OptionalExtension;
+use serde_json::{Map, Value};
+use tauri::command;
+
+use crate::DatabaseManager;
+
+// Free-form per-entry fields, kept as one JSON object in
+// `journal_entries.metadata` so new kinds of data can be stored without a
+// schema change. Keys and sizes are checked here, since SQLite will store
+// whatever it's given.
+
+const MAX_KEY_CHARS: usize = 64;
+const MAX_METADATA_BYTES: usize = 64 * 1024;
+
+fn validate_key(key: &str) -> Result<(), String> {
+    let valid = !key.is_empty()
+        && key.chars().count() <= MAX_KEY_CHARS
+        && key
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '_' | '-' | '.'));
+    if valid {
+        Ok(())
+    } else {
+        Err(format!(
+            "Invalid metadata key '{}': use up to {} lowercase letters, digits, '_', '-' or '.'",
+            key, MAX_KEY_CHARS
+        ))
+    }
+}
+
+/// Sets `key` to `value` in `metadata`, or removes it when `value` is null.
+fn apply(metadata: &mut Map<String, Value>, key: &str, value: Value) -> Result<(), String> {
+    validate_key(key)?;
+    if value.is_null() {
+        metadata.remove(key);
+    } else {
+        metadata.insert(key.to_string(), value);
+    }
+    Ok(())
+}
+
+fn parse(json: &str) -> Result<Map<String, Value>, String> {
+    serde_json::from_str(json).map_err(|e| format!("Stored metadata is not a JSON object: {}", e))
+}
+
+/// The metadata object of entry `id`, or `None` if there's no such entry.
+pub fn load(conn: &rusqlite::Connection, id: i32) -> Result<Option<Map<String, Value>>, String> {
+    let json: Option<String> = conn
+        .query_row(
+            "SELECT metadata FROM journal_entries WHERE id = ?1",
+            rusqlite::params![id],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+    json.as_deref().map(parse).transpose()
+}
+
+/// Sets one field of an entry's metadata; a null `value` removes it.
+pub fn set(conn: &rusqlite::Connection, id: i32, key: &str, value: Value) -> Result<(), String> {
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    let mut metadata = load(&tx, id)?.ok_or_else(|| format!("Entry {} not found", id))?;
+    apply(&mut metadata, key, value)?;
+    let json = serde_json::to_string(&metadata).map_err(|e| e.to_string())?;
+    if json.len() > MAX_METADATA_BYTES {
+        return Err(format!("Entry metadata is limited to {} KB", MAX_METADATA_BYTES / 1024));
+    }
+    tx.execute(
+        "UPDATE journal_entries SET metadata = ?1 WHERE id = ?2",
+        rusqlite::params![json, id],
+    )
+    .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())
+}
+
+#[command]
+pub fn set_entry_metadata(id: i32, key: String, value: Value) -> Result<(), String> {
+    debug!("Setting metadata '{}' on entry {}", key, id);
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    set(&db.conn, id, &key, value)
+}
+
+#[command]
+pub fn get_entry_metadata(id: i32) -> Result<Map<String, Value>, String> {
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    load(&db.conn, id)?.ok_or_else(|| format!("Entry {} not found", id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use journal_core::{insert_entry, timestamp_now};
+    use serde_json::json;
+
+    #[test]
+    fn test_validate_key() {
+        assert!(validate_key("sleep_hours").is_ok());
+        assert!(validate_key("app.raycast-id").is_ok());
+        assert!(validate_key("").is_err());
+        assert!(validate_key("Sleep Hours").is_err());
+        assert!(validate_key(&"k".repeat(MAX_KEY_CHARS + 1)).is_err());
+    }
+
+    #[test]
+    fn test_apply_sets_and_removes() {
+        let mut metadata = Map::new();
+        apply(&mut metadata, "steps", json!(8000)).unwrap();
+        apply(&mut metadata, "meds", json!(["a", "b"])).unwrap();
+        apply(&mut metadata, "steps", Value::Null).unwrap();
+        assert_eq!(Value::Object(metadata), json!({"meds": ["a", "b"]}));
+    }
+
+    #[test]
+    fn test_set_entry_metadata() {
+        let db = DatabaseManager::open_in_memory().unwrap();
+        let id = insert_entry(&db.conn, "Run", "", &timestamp_now()).unwrap();
+        assert_eq!(load(&db.conn, id).unwrap(), Some(Map::new()));
+
+        set(&db.conn, id, "distance_km", json!(5.2)).unwrap();
+        assert_eq!(load(&db.conn, id).unwrap().unwrap()["distance_km"], json!(5.2));
+        assert!(set(&db.conn, id, "notes", json!("x".repeat(MAX_METADATA_BYTES))).is_err());
+        assert!(set(&db.conn, id + 1, "distance_km", json!(1)).is_err());
+        assert_eq!(load(&db.conn, id + 1).unwrap(), None);
+    }
+}
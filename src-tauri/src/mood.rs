@@ -0,0 +1,164 @@
+use log::debug;
+use serde::{Deserialize, Serialize};
+use tauri::command;
+
+use crate::{DatabaseManager, DateRange};
+
+const MAX_EMOJI_CHARS: usize = 16;
+
+/// Fixed five-point scale so moods can be averaged and charted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MoodLevel {
+    Awful,
+    Bad,
+    Okay,
+    Good,
+    Great,
+}
+
+impl MoodLevel {
+    pub fn score(self) -> i64 {
+        match self {
+            MoodLevel::Awful => 1,
+            MoodLevel::Bad => 2,
+            MoodLevel::Okay => 3,
+            MoodLevel::Good => 4,
+            MoodLevel::Great => 5,
+        }
+    }
+
+    pub fn from_score(score: i64) -> Option<Self> {
+        match score {
+            1 => Some(MoodLevel::Awful),
+            2 => Some(MoodLevel::Bad),
+            3 => Some(MoodLevel::Okay),
+            4 => Some(MoodLevel::Good),
+            5 => Some(MoodLevel::Great),
+            _ => None,
+        }
+    }
+}
+
+/// A mood attached to an entry: a point on the scale, a free-form emoji, or both.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Mood {
+    pub level: Option<MoodLevel>,
+    pub emoji: Option<String>,
+}
+
+impl Mood {
+    /// Rebuilds a mood from its stored columns; `None` when neither is set.
+    pub fn from_columns(score: Option<i64>, emoji: Option<String>) -> Option<Self> {
+        let level = score.and_then(MoodLevel::from_score);
+        if level.is_none() && emoji.is_none() {
+            return None;
+        }
+        Some(Mood { level, emoji })
+    }
+
+    /// Trims the emoji and rejects anything that is clearly not a short marker.
+    fn normalized(self) -> Result<Self, String> {
+        let emoji = match self.emoji {
+            Some(emoji) => {
+                let trimmed = emoji.trim();
+                if trimmed.chars().count() > MAX_EMOJI_CHARS {
+                    return Err(format!("Mood emoji must be at most {} characters", MAX_EMOJI_CHARS));
+                }
+                (!trimmed.is_empty()).then(|| trimmed.to_string())
+            }
+            None => None,
+        };
+        Ok(Mood { level: self.level, emoji })
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct MoodTrendPoint {
+    /// Calendar day (`YYYY-MM-DD`) the entries were written on.
+    date: String,
+    average_score: f64,
+    entry_count: i64,
+}
+
+/// Sets or clears (`mood: null`) the mood of an entry.
+#[command]
+pub fn set_mood(entry_id: i32, mood: Option<Mood>) -> Result<(), String> {
+    let mood = mood.map(Mood::normalized).transpose()?;
+    let score = mood.as_ref().and_then(|m| m.level).map(MoodLevel::score);
+    let emoji = mood.and_then(|m| m.emoji);
+    debug!("Setting mood for entry {}", entry_id);
+
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    let updated = db.conn
+        .execute(
+            "UPDATE journal_entries SET mood_score = ?1, mood_emoji = ?2 WHERE id = ?3",
+            rusqlite::params![score, emoji, entry_id],
+        )
+        .map_err(|e| e.to_string())?;
+    if updated == 0 {
+        return Err(format!("Entry {} not found", entry_id));
+    }
+    Ok(())
+}
+
+/// Average mood score per day within `range`, oldest first, for charting.
+#[command]
+pub fn get_mood_trends(range: DateRange) -> Result<Vec<MoodTrendPoint>, String> {
+    let (start, end) = range.millis()?;
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    let mut stmt = db.conn
+        .prepare(
+            "SELECT local_date AS day, AVG(mood_score), COUNT(*)
+             FROM journal_entries
+             WHERE mood_score IS NOT NULL AND local_date IS NOT NULL
+               AND (?1 IS NULL OR created_ms >= ?1)
+               AND (?2 IS NULL OR created_ms <= ?2)
+             GROUP BY day
+             ORDER BY day ASC",
+        )
+        .map_err(|e| e.to_string())?;
+    let points = stmt
+        .query_map(rusqlite::params![start, end], |row| {
+            Ok(MoodTrendPoint {
+                date: row.get(0)?,
+                average_score: row.get(1)?,
+                entry_count: row.get(2)?,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(points)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_score_round_trip() {
+        for score in 1..=5 {
+            assert_eq!(MoodLevel::from_score(score).unwrap().score(), score);
+        }
+        assert!(MoodLevel::from_score(0).is_none());
+        assert!(MoodLevel::from_score(6).is_none());
+    }
+
+    #[test]
+    fn test_from_columns() {
+        assert!(Mood::from_columns(None, None).is_none());
+        let mood = Mood::from_columns(Some(4), Some("🙂".to_string())).unwrap();
+        assert_eq!(mood.level, Some(MoodLevel::Good));
+        assert_eq!(mood.emoji.as_deref(), Some("🙂"));
+    }
+
+    #[test]
+    fn test_normalized_emoji() {
+        let mood = Mood { level: None, emoji: Some("  ".to_string()) }.normalized().unwrap();
+        assert!(mood.emoji.is_none());
+
+        let too_long = Mood { level: None, emoji: Some("x".repeat(MAX_EMOJI_CHARS + 1)) };
+        assert!(too_long.normalized().is_err());
+    }
+}
@@ -0,0 +1,72 @@
+use chrono::Utc;
+use serde::Serialize;
+use tauri::command;
+
+use crate::DatabaseManager;
+
+#[derive(Debug, Serialize)]
+pub struct Notebook {
+    id: i32,
+    name: String,
+    entry_count: i64,
+}
+
+#[command]
+pub fn get_notebooks() -> Result<Vec<Notebook>, String> {
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    let mut stmt = db.conn
+        .prepare(
+            "SELECT n.id, n.name, COUNT(e.id) FROM notebooks n
+             LEFT JOIN journal_entries e ON e.notebook_id = n.id
+             GROUP BY n.id
+             ORDER BY n.name COLLATE NOCASE",
+        )
+        .map_err(|e| e.to_string())?;
+    let notebooks = stmt
+        .query_map([], |row| {
+            Ok(Notebook {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                entry_count: row.get(2)?,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(notebooks)
+}
+
+#[command]
+pub fn create_notebook(name: String) -> Result<i32, String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("Notebook name cannot be empty".to_string());
+    }
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    db.conn
+        .execute(
+            "INSERT INTO notebooks (name, created_at) VALUES (?1, ?2)",
+            rusqlite::params![name, Utc::now().to_rfc3339()],
+        )
+        .map_err(|e| e.to_string())?;
+    Ok(db.conn.last_insert_rowid() as i32)
+}
+
+/// The id of the notebook called `name`, created if there's none.
+pub(crate) fn notebook_named(conn: &rusqlite::Connection, name: &str) -> rusqlite::Result<i32> {
+    conn.execute(
+        "INSERT OR IGNORE INTO notebooks (name, created_at) VALUES (?1, ?2)",
+        rusqlite::params![name, Utc::now().to_rfc3339()],
+    )?;
+    conn.query_row("SELECT id FROM notebooks WHERE name = ?1", rusqlite::params![name], |row| row.get(0))
+}
+
+/// Deletes a notebook; its entries are kept and simply leave the notebook.
+#[command]
+pub fn delete_notebook(id: i32) -> Result<(), String> {
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    db.conn
+        .execute("DELETE FROM notebooks WHERE id = ?1", rusqlite::params![id])
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
@@ -0,0 +1,225 @@
+use chrono::NaiveDate;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+use tauri::command;
+
+use journal_core::audit::AuditAction;
+
+use crate::export::{exported_entries, html_to_markdown, ExportedEntry};
+use crate::security;
+use crate::stats::local_date;
+use crate::DatabaseManager;
+
+// The journal as an Obsidian vault: a Markdown file per entry, named like
+// Obsidian's daily notes (`2024-03-05.md`), with the date, tags and mood in
+// YAML front-matter where its properties view and tag pane find them. Entry
+// titles become aliases, and since files are named by day, each `[[title]]`
+// link is pointed at the file it means, keeping the title as its text. Locked
+// entries are left out, as in the other exports.
+
+/// Characters Obsidian doesn't allow in note names.
+const RESERVED: &[char] = &['\\', '/', ':', '*', '?', '"', '<', '>', '|', '#', '^', '[', ']'];
+
+/// An entry and the name of its note, without `.md`.
+struct Note<'a> {
+    entry: &'a ExportedEntry,
+    date: Option<NaiveDate>,
+    name: String,
+}
+
+/// `text` as a double-quoted YAML string.
+fn yaml_string(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// `title` with characters Obsidian refuses in note names dropped; `Untitled` if nothing is left.
+fn note_title(title: &str) -> String {
+    let name: String = title.chars().filter(|c| !RESERVED.contains(c)).collect();
+    match name.trim().trim_start_matches('.') {
+        "" => "Untitled".to_string(),
+        name => name.to_string(),
+    }
+}
+
+/// The note's name: the day for the first entry of a day, then `2024-03-05 2`
+/// and so on. Undated entries are named by title.
+fn note_name(date: Option<NaiveDate>, title: &str, taken: &mut HashSet<String>) -> String {
+    let base = match date {
+        Some(date) => date.format("%Y-%m-%d").to_string(),
+        None => note_title(title),
+    };
+    let mut name = base.clone();
+    let mut n = 2;
+    // Obsidian matches note names case-insensitively
+    while !taken.insert(name.to_lowercase()) {
+        name = format!("{} {}", base, n);
+        n += 1;
+    }
+    name
+}
+
+fn front_matter(note: &Note) -> String {
+    let entry = note.entry;
+    let mut yaml = String::from("---\n");
+    if let Some(date) = note.date {
+        yaml.push_str(&format!("date: {}\n", date.format("%Y-%m-%d")));
+    }
+    yaml.push_str(&format!("created: {}\n", yaml_string(&entry.created_at)));
+    if !entry.title.trim().is_empty() {
+        yaml.push_str(&format!("aliases:\n  - {}\n", yaml_string(entry.title.trim())));
+    }
+    if !entry.tags.is_empty() {
+        yaml.push_str("tags:\n");
+        for tag in &entry.tags {
+            yaml.push_str(&format!("  - {}\n", yaml_string(tag)));
+        }
+    }
+    if let Some(mood) = &entry.mood {
+        if let Some(level) = mood.level {
+            yaml.push_str(&format!("mood: {}\n", level.score()));
+        }
+        if let Some(emoji) = &mood.emoji {
+            yaml.push_str(&format!("mood_emoji: {}\n", yaml_string(emoji)));
+        }
+    }
+    yaml.push_str("---\n");
+    yaml
+}
+
+/// Points each `[[title]]` in `md` at the note of the entry with that title,
+/// as `[[2024-03-05|title]]`. Links that already name their note, or name no
+/// exported entry, are left as written.
+fn retarget_links(md: &str, names: &HashMap<String, String>) -> String {
+    let mut out = String::new();
+    let mut rest = md;
+    while let Some(start) = rest.find("[[") {
+        let after = &rest[start + 2..];
+        let Some(end) = after.find("]]") else { break };
+        out.push_str(&rest[..start]);
+        let link = &after[..end];
+        let (target, text) = link.split_once('|').unwrap_or((link, link));
+        match names.get(&target.trim().to_ascii_lowercase()) {
+            Some(name) if name != target.trim() => out.push_str(&format!("[[{}|{}]]", name, text.trim())),
+            _ => out.push_str(&rest[start..start + end + 4]),
+        }
+        rest = &after[end + 2..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// A note for every readable entry, as its name and Markdown.
+pub(crate) fn vault_notes(conn: &rusqlite::Connection) -> Result<Vec<(String, String)>, String> {
+    let entries = exported_entries(conn).map_err(|e| e.to_string())?;
+    let mut taken = HashSet::new();
+    let notes: Vec<Note> = entries
+        .iter()
+        .map(|entry| {
+            let date = local_date(&entry.created_at);
+            let name = note_name(date, &entry.title, &mut taken);
+            Note { entry, date, name }
+        })
+        .collect();
+    let mut names_by_title: HashMap<String, String> = HashMap::new();
+    for note in &notes {
+        let title = note.entry.title.trim();
+        if !title.is_empty() {
+            names_by_title.entry(title.to_ascii_lowercase()).or_insert_with(|| note.name.clone());
+        }
+    }
+
+    Ok(notes
+        .into_iter()
+        .map(|note| {
+            let mut md = front_matter(&note);
+            let title = note.entry.title.trim();
+            if !title.is_empty() {
+                md.push_str(&format!("\n# {}\n", title));
+            }
+            let body = retarget_links(&html_to_markdown(&note.entry.body), &names_by_title);
+            if !body.is_empty() {
+                md.push_str(&format!("\n{}\n", body));
+            }
+            (note.name, md)
+        })
+        .collect())
+}
+
+/// Writes a note for every readable entry into `dir` and returns how many
+/// were written.
+pub fn export_vault(conn: &rusqlite::Connection, dir: &Path) -> Result<usize, String> {
+    let notes = vault_notes(conn)?;
+    fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    for (name, md) in &notes {
+        let path = dir.join(format!("{}.md", name));
+        fs::write(&path, md).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    }
+    Ok(notes.len())
+}
+
+/// Writes the journal as an Obsidian vault into folder `dir`, created if
+/// needed, and returns how many entries were written.
+#[command]
+pub fn export_obsidian(dir: String) -> Result<usize, String> {
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    let count = export_vault(&db.conn, Path::new(&dir))?;
+    security::audit(AuditAction::Export, &format!("{} entries as an Obsidian vault to {}", count, dir));
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use journal_core::insert_entry;
+
+    #[test]
+    fn test_note_name() {
+        let day = NaiveDate::from_ymd_opt(2024, 3, 5);
+        let mut taken = HashSet::new();
+        assert_eq!(note_name(day, "Lisbon", &mut taken), "2024-03-05");
+        assert_eq!(note_name(day, "Evening", &mut taken), "2024-03-05 2");
+        assert_eq!(note_name(None, "What? A/B: test", &mut taken), "What AB test");
+        assert_eq!(note_name(None, "  ", &mut taken), "Untitled");
+        assert_eq!(note_name(None, "untitled", &mut taken), "untitled 2");
+    }
+
+    #[test]
+    fn test_retarget_links() {
+        let names = HashMap::from([("lisbon".to_string(), "2024-03-05".to_string())]);
+        assert_eq!(
+            retarget_links("Back from [[Lisbon]], not [[Porto]]", &names),
+            "Back from [[2024-03-05|Lisbon]], not [[Porto]]"
+        );
+        assert_eq!(retarget_links("[[Lisbon|the trip]]", &names), "[[2024-03-05|the trip]]");
+        assert_eq!(retarget_links("[[2024-03-05]]", &names), "[[2024-03-05]]");
+    }
+
+    #[test]
+    fn test_export_vault() {
+        let db = DatabaseManager::open_in_memory().unwrap();
+        let id = insert_entry(&db.conn, "Lisbon", "<p>Trams and <em>tiles</em></p>", "2024-03-05T12:00:00+00:00")
+            .unwrap();
+        insert_entry(&db.conn, "Home", "<p>Back from [[lisbon]]</p>", "2024-04-02T12:00:00+00:00").unwrap();
+        db.conn
+            .execute(
+                "UPDATE journal_entries SET mood_score = 4, mood_emoji = '😊' WHERE id = ?1",
+                rusqlite::params![id],
+            )
+            .unwrap();
+        db.conn
+            .execute("INSERT INTO entry_tags (entry_id, tag) VALUES (?1, 'travel')", rusqlite::params![id])
+            .unwrap();
+
+        let dir = std::env::temp_dir().join(format!("journal-vault-{}", std::process::id()));
+        assert_eq!(export_vault(&db.conn, &dir).unwrap(), 2);
+        assert_eq!(
+            fs::read_to_string(dir.join("2024-03-05.md")).unwrap(),
+            "---\ndate: 2024-03-05\ncreated: \"2024-03-05T12:00:00+00:00\"\naliases:\n  - \"Lisbon\"\n\
+             tags:\n  - \"travel\"\nmood: 4\nmood_emoji: \"😊\"\n---\n\n# Lisbon\n\nTrams and *tiles*\n"
+        );
+        let home = fs::read_to_string(dir.join("2024-04-02.md")).unwrap();
+        assert!(home.ends_with("Back from [[2024-03-05|lisbon]]\n"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
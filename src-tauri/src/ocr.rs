@@ -0,0 +1,154 @@
+use log::{debug, error, warn};
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::Duration;
+
+use journal_core::keychain::KeychainManager;
+
+use crate::attachments::{read_blob, store_attachment_text, unread_blobs};
+use crate::jobs::{Job, JobKind, CANCELLED};
+use crate::settings::{OcrSettings, Settings};
+use crate::DatabaseManager;
+
+// Makes photos searchable: a background worker runs Tesseract over image
+// attachments that haven't been read yet and keeps the words it finds in
+// `attachment_text`, which search matches alongside entry text. Tesseract
+// runs on this machine, so images never leave it.
+
+const WORKER_START_DELAY: Duration = Duration::from_secs(90);
+const WORKER_INTERVAL: Duration = Duration::from_secs(5 * 60);
+/// Images read per pass, so a large import doesn't hold the database for long.
+const BATCH_SIZE: usize = 20;
+const OCR_SOURCE: &str = "ocr";
+
+/// Runs Tesseract over an image. `Err` means Tesseract itself couldn't be
+/// run; an image it can't read gives `Ok(None)`.
+fn recognize(settings: &OcrSettings, image: &[u8]) -> Result<Option<String>, String> {
+    let mut child = Command::new(&settings.tesseract_path)
+        .args(["stdin", "stdout", "-l", &settings.languages])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to run {}: {}", settings.tesseract_path, e))?;
+    let mut stdin = child.stdin.take().ok_or("Tesseract's input was not available")?;
+    // Written from another thread so a full output pipe can't stall the write
+    let output = thread::scope(|scope| {
+        scope.spawn(move || stdin.write_all(image));
+        child.wait_with_output()
+    })
+    .map_err(|e| e.to_string())?;
+    if !output.status.success() {
+        warn!("Tesseract could not read an image: {}", String::from_utf8_lossy(&output.stderr).trim());
+        return Ok(None);
+    }
+    let text = String::from_utf8_lossy(&output.stdout);
+    Ok(Some(text.split_whitespace().collect::<Vec<_>>().join(" ")))
+}
+
+/// Reads the next batch of unread images with `recognize`, as a job when
+/// there are any, and returns how many there were.
+fn extract_pending(
+    conn: &rusqlite::Connection,
+    recognize: impl Fn(&[u8]) -> Result<Option<String>, String>,
+) -> Result<usize, String> {
+    let pending = unread_blobs(conn, "image", BATCH_SIZE).map_err(|e| e.to_string())?;
+    if pending.is_empty() {
+        return Ok(0);
+    }
+    Job::run(JobKind::Ocr, "Reading text in photos", |job| {
+        for (i, (hash, size)) in pending.iter().enumerate() {
+            job.step(i, pending.len(), None)?;
+            let image = read_blob(conn, hash, 0, *size as usize).map_err(|e| e.to_string())?;
+            // Unreadable images get empty text, so they aren't tried again every pass
+            let text = recognize(&image)?.unwrap_or_default();
+            store_attachment_text(conn, hash, OCR_SOURCE, &text).map_err(|e| e.to_string())?;
+        }
+        Ok(pending.len())
+    })
+}
+
+fn run_pass(settings: &OcrSettings) -> Result<usize, String> {
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    extract_pending(&db.conn, |image| recognize(settings, image))
+}
+
+/// Starts the background thread that reads new image attachments when OCR is
+/// turned on. Like the indexer, it waits for the journal to be unlocked.
+pub fn start_worker() {
+    thread::spawn(|| {
+        thread::sleep(WORKER_START_DELAY);
+        loop {
+            let settings = Settings::load().ocr;
+            if settings.enabled && KeychainManager::has_cached_key() {
+                match run_pass(&settings) {
+                    Ok(0) => {}
+                    Ok(count) => debug!("Read text from {} images", count),
+                    Err(e) if e == CANCELLED => debug!("Reading text from images was cancelled"),
+                    Err(e) => error!("Reading text from images failed: {}", e),
+                }
+            }
+            thread::sleep(WORKER_INTERVAL);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use journal_core::{insert_entry, timestamp_now};
+
+    fn add_attachment(conn: &rusqlite::Connection, entry_id: i32, kind: &str, data: &[u8]) {
+        let hash = journal_core::attachments::content_hash(data);
+        conn.execute(
+            "INSERT OR IGNORE INTO attachment_blobs (hash, size, data) VALUES (?1, ?2, ?3)",
+            rusqlite::params![hash, data.len() as i64, data],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO attachments (entry_id, kind, mime, size, hash, created_at) VALUES (?1, ?2, 'x', ?3, ?4, '')",
+            rusqlite::params![entry_id, kind, data.len() as i64, hash],
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_extract_pending_reads_each_image_once() {
+        let db = DatabaseManager::open_in_memory().unwrap();
+        let entry_id = insert_entry(&db.conn, "Receipts", "", &timestamp_now()).unwrap();
+        add_attachment(&db.conn, entry_id, "image", b"receipt");
+        add_attachment(&db.conn, entry_id, "image", b"receipt");
+        add_attachment(&db.conn, entry_id, "image", b"blurry");
+        add_attachment(&db.conn, entry_id, "audio", b"memo");
+
+        let read = |image: &[u8]| match image {
+            b"receipt" => Ok(Some("CAFE LUNA total 12.50".to_string())),
+            _ => Ok(None),
+        };
+        assert_eq!(extract_pending(&db.conn, read).unwrap(), 2);
+        assert_eq!(extract_pending(&db.conn, read).unwrap(), 0);
+        let texts: Vec<String> = db
+            .conn
+            .prepare("SELECT text FROM attachment_text ORDER BY text")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(texts, vec!["", "CAFE LUNA total 12.50"]);
+    }
+
+    #[test]
+    fn test_extract_pending_stops_when_tesseract_is_missing() {
+        let db = DatabaseManager::open_in_memory().unwrap();
+        let entry_id = insert_entry(&db.conn, "Photo", "", &timestamp_now()).unwrap();
+        add_attachment(&db.conn, entry_id, "image", b"photo");
+        let settings = OcrSettings {
+            tesseract_path: "/nonexistent/tesseract".to_string(),
+            ..OcrSettings::default()
+        };
+        assert!(extract_pending(&db.conn, |image| recognize(&settings, image)).is_err());
+        assert_eq!(unread_blobs(&db.conn, "image", BATCH_SIZE).unwrap().len(), 1);
+    }
+}
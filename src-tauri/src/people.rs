@@ -0,0 +1,85 @@
+use serde::Serialize;
+use tauri::command;
+
+use crate::{DatabaseManager, JournalEntry};
+
+#[derive(Debug, Serialize)]
+pub struct Person {
+    name: String,
+    entry_count: i64,
+    last_mentioned: String,
+}
+
+fn people(conn: &rusqlite::Connection) -> rusqlite::Result<Vec<Person>> {
+    let mut stmt = conn.prepare(
+        "SELECT p.name, COUNT(*), MAX(e.created_at) FROM people p
+         JOIN entry_people ep ON ep.person_id = p.id
+         JOIN journal_entries e ON e.id = ep.entry_id
+         GROUP BY p.id
+         ORDER BY COUNT(*) DESC, p.name COLLATE NOCASE",
+    )?;
+    let people = stmt
+        .query_map([], |row| {
+            Ok(Person {
+                name: row.get(0)?,
+                entry_count: row.get(1)?,
+                last_mentioned: row.get(2)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(people)
+}
+
+fn entries_mentioning(conn: &rusqlite::Connection, person: &str) -> rusqlite::Result<Vec<JournalEntry>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM journal_entries
+         WHERE id IN (
+            SELECT ep.entry_id FROM entry_people ep
+            JOIN people p ON p.id = ep.person_id
+            WHERE p.name = ?1
+         )
+         ORDER BY created_ms DESC, id DESC",
+        JournalEntry::COLUMNS
+    ))?;
+    let entries = stmt
+        .query_map(rusqlite::params![person.trim().trim_start_matches('@')], JournalEntry::from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(entries)
+}
+
+/// Everyone mentioned in the journal, most mentioned first.
+#[command]
+pub fn get_people() -> Result<Vec<Person>, String> {
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    people(&db.conn).map_err(|e| e.to_string())
+}
+
+/// Entries that mention `person` by name or @-mention, newest first.
+#[command]
+pub fn get_entries_mentioning(person: String) -> Result<Vec<JournalEntry>, String> {
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    entries_mentioning(&db.conn, &person).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use journal_core::{insert_entry, timestamp_now};
+
+    #[test]
+    fn test_people_and_their_entries() {
+        let db = DatabaseManager::open_in_memory().unwrap();
+        insert_entry(&db.conn, "Hike", "<p>Up the hill with Noor and @tomas</p>", "2024-05-01T09:00:00Z").unwrap();
+        insert_entry(&db.conn, "Dinner", "<p>Cooked for Noor</p>", "2024-05-03T19:00:00Z").unwrap();
+        insert_entry(&db.conn, "Alone", "<p>quiet day</p>", &timestamp_now()).unwrap();
+
+        let people = people(&db.conn).unwrap();
+        let names: Vec<_> = people.iter().map(|p| (p.name.as_str(), p.entry_count)).collect();
+        assert_eq!(names, vec![("Noor", 2), ("tomas", 1)]);
+        assert_eq!(people[0].last_mentioned, "2024-05-03T19:00:00Z");
+
+        let titles: Vec<_> = entries_mentioning(&db.conn, "noor").unwrap().into_iter().map(|e| e.title).collect();
+        assert_eq!(titles, vec!["Dinner", "Hike"]);
+        assert_eq!(entries_mentioning(&db.conn, "@Tomas").unwrap().len(), 1);
+    }
+}
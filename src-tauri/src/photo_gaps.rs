@@ -0,0 +1,325 @@
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeZone};
+use log::debug;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use tauri::command;
+
+use journal_core::insert_entry;
+
+use crate::attachments::add_image;
+use crate::file_drop::{file_kind, FileKind};
+use crate::settings::{PhotoSettings, Settings};
+use crate::validation::clean_body;
+use crate::{git_sync, markdown_mirror, recent_menu, DatabaseManager};
+
+// Days worth writing about, found in a photos folder the user chooses: a day
+// with a handful of photos and no entry was probably one to remember. Nothing
+// is looked at until a folder is chosen, and photos are only read, never
+// moved or changed. A photo's day is when its camera says it was taken, from
+// the EXIF data of a JPEG, or else when the file was last modified. From a
+// suggested day, the photos the user picks become a new entry's images.
+
+/// Enough of a JPEG to hold its EXIF data, which comes first.
+const EXIF_BYTES: u64 = 128 * 1024;
+const EXIF_IFD_TAG: u16 = 0x8769;
+const DATE_TIME_TAG: u16 = 0x0132;
+const DATE_TIME_ORIGINAL_TAG: u16 = 0x9003;
+
+#[derive(Debug, Serialize)]
+pub struct PhotoGap {
+    /// The local day, as `YYYY-MM-DD`.
+    pub date: String,
+    /// The day's photos, in the order they were taken.
+    pub photos: Vec<PhotoInfo>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PhotoInfo {
+    pub path: String,
+    pub taken_at: String,
+}
+
+struct Photo {
+    path: PathBuf,
+    taken_at: DateTime<Local>,
+}
+
+/// When the camera says the photo in TIFF data `tiff` was taken, as its
+/// `DateTimeOriginal`, or its `DateTime` if it has no original.
+fn tiff_taken_at(tiff: &[u8]) -> Option<NaiveDateTime> {
+    let little_endian = match tiff.get(..2)? {
+        b"II" => true,
+        b"MM" => false,
+        _ => return None,
+    };
+    let u16_at = |at: usize| {
+        let bytes = [*tiff.get(at)?, *tiff.get(at + 1)?];
+        Some(if little_endian { u16::from_le_bytes(bytes) } else { u16::from_be_bytes(bytes) })
+    };
+    let u32_at = |at: usize| {
+        let bytes: [u8; 4] = tiff.get(at..at + 4)?.try_into().ok()?;
+        let value = if little_endian { u32::from_le_bytes(bytes) } else { u32::from_be_bytes(bytes) };
+        Some(value as usize)
+    };
+    // Where the value of `tag` is in the directory at `ifd`
+    let value_of = |ifd: usize, tag: u16| {
+        let count = u16_at(ifd)? as usize;
+        (0..count).map(|i| ifd + 2 + i * 12).find(|&field| u16_at(field) == Some(tag)).map(|field| field + 8)
+    };
+    // Dates are 20 bytes of text, too long to be stored in place
+    let date_at = |value: usize| {
+        let offset = u32_at(value)?;
+        let text = std::str::from_utf8(tiff.get(offset..offset + 19)?).ok()?;
+        NaiveDateTime::parse_from_str(text, "%Y:%m:%d %H:%M:%S").ok()
+    };
+    let ifd0 = u32_at(4)?;
+    value_of(ifd0, EXIF_IFD_TAG)
+        .and_then(u32_at)
+        .and_then(|exif| value_of(exif, DATE_TIME_ORIGINAL_TAG))
+        .and_then(date_at)
+        .or_else(|| value_of(ifd0, DATE_TIME_TAG).and_then(date_at))
+}
+
+/// When the JPEG starting with `data` was taken, from its EXIF data.
+fn jpeg_taken_at(data: &[u8]) -> Option<NaiveDateTime> {
+    if !data.starts_with(&[0xff, 0xd8]) {
+        return None;
+    }
+    let mut at = 2;
+    // Segments up to the image itself, each a marker and a length
+    while data.get(at) == Some(&0xff) {
+        let marker = *data.get(at + 1)?;
+        if marker == 0xda {
+            return None;
+        }
+        let length = u16::from_be_bytes([*data.get(at + 2)?, *data.get(at + 3)?]) as usize;
+        let segment = data.get(at + 4..at + 2 + length)?;
+        if marker == 0xe1 && segment.starts_with(b"Exif\0\0") {
+            return tiff_taken_at(&segment[6..]);
+        }
+        at += 2 + length;
+    }
+    None
+}
+
+/// When the photo at `path` was taken, as far as can be told.
+fn taken_at(path: &Path) -> io::Result<DateTime<Local>> {
+    if matches!(file_kind(path), Some(FileKind::Image("image/jpeg"))) {
+        let mut data = Vec::new();
+        File::open(path)?.take(EXIF_BYTES).read_to_end(&mut data)?;
+        if let Some(taken_at) = jpeg_taken_at(&data).and_then(|taken| Local.from_local_datetime(&taken).earliest()) {
+            return Ok(taken_at);
+        }
+    }
+    Ok(DateTime::from(fs::metadata(path)?.modified()?))
+}
+
+/// The photos under `dir`. Hidden files and folders, such as a photo app's
+/// library data, are passed over, and symlinks aren't followed.
+fn photos_in(dir: &Path, photos: &mut Vec<Photo>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        let path = entry.path();
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            photos_in(&path, photos)?;
+        } else if file_type.is_file() && matches!(file_kind(&path), Some(FileKind::Image(_))) {
+            match taken_at(&path) {
+                Ok(taken_at) => photos.push(Photo { path, taken_at }),
+                Err(e) => debug!("Passed over {}: {}", path.display(), e),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Days with at least `min_photos` of `photos` and no entry, newest first.
+fn photo_gaps(conn: &rusqlite::Connection, photos: Vec<Photo>, min_photos: usize) -> rusqlite::Result<Vec<PhotoGap>> {
+    let written: HashSet<NaiveDate> = conn
+        .prepare("SELECT DISTINCT local_date FROM journal_entries WHERE local_date IS NOT NULL")?
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>, _>>()?
+        .iter()
+        .filter_map(|day| day.parse().ok())
+        .collect();
+    let mut days: BTreeMap<NaiveDate, Vec<Photo>> = BTreeMap::new();
+    for photo in photos {
+        days.entry(photo.taken_at.date_naive()).or_default().push(photo);
+    }
+    let gaps = days
+        .into_iter()
+        .rev()
+        .filter(|(day, photos)| photos.len() >= min_photos.max(1) && !written.contains(day))
+        .map(|(day, mut photos)| {
+            photos.sort_by_key(|photo| photo.taken_at);
+            PhotoGap {
+                date: day.to_string(),
+                photos: photos
+                    .into_iter()
+                    .map(|photo| PhotoInfo {
+                        path: photo.path.display().to_string(),
+                        taken_at: photo.taken_at.to_rfc3339(),
+                    })
+                    .collect(),
+            }
+        })
+        .collect();
+    Ok(gaps)
+}
+
+/// The chosen photos folder, if the user has opted in.
+fn configured_folder(settings: &PhotoSettings) -> Option<PathBuf> {
+    settings.directory.as_deref().filter(|_| settings.enabled).map(PathBuf::from)
+}
+
+/// Creates an entry on `date` with the images at `paths`, filed at the time
+/// the first of them was taken. A photo that can't be added is left out.
+fn journal_photos(conn: &rusqlite::Connection, date: NaiveDate, paths: &[PathBuf]) -> Result<i32, String> {
+    let first_taken = paths.iter().filter_map(|path| taken_at(path).ok()).filter(|t| t.date_naive() == date).min();
+    let created_at = first_taken
+        .or_else(|| date.and_hms_opt(12, 0, 0).and_then(|noon| Local.from_local_datetime(&noon).earliest()))
+        .ok_or_else(|| format!("Invalid date {}", date))?;
+    let title = date.format("%B %-d, %Y").to_string();
+    let id = insert_entry(conn, &title, "", &created_at.to_rfc3339()).map_err(|e| e.to_string())?;
+
+    let mut body = String::new();
+    for path in paths {
+        let added = match file_kind(path) {
+            Some(FileKind::Image(mime)) => {
+                fs::read(path).map_err(|e| e.to_string()).and_then(|data| add_image(id, mime, &data))
+            }
+            _ => Err("Not a photo".to_string()),
+        };
+        match added {
+            Ok(image) => body.push_str(&format!("<p><img src=\"{}\"></p>", image.token)),
+            Err(e) => debug!("Left {} out: {}", path.display(), e),
+        }
+    }
+    // Images have no words or links, so the columns derived from the body stay right
+    let body = clean_body(&body, &Settings::load().limits)?;
+    conn.execute("UPDATE journal_entries SET body = ?1 WHERE id = ?2", rusqlite::params![body, id])
+        .map_err(|e| e.to_string())?;
+    Ok(id)
+}
+
+/// Chooses the folder photos are looked for in, or with `None` stops
+/// looking. The folder itself is left alone.
+#[command]
+pub fn set_photo_folder(directory: Option<String>) -> Result<(), String> {
+    if let Some(dir) = &directory {
+        if !Path::new(dir).is_dir() {
+            return Err(format!("{} is not a folder", dir));
+        }
+    }
+    let mut settings = Settings::load();
+    settings.photos.enabled = directory.is_some();
+    settings.photos.directory = directory.or(settings.photos.directory);
+    settings.save().map_err(|e| e.to_string())
+}
+
+/// Days in the photos folder with several photos but no entry, newest first.
+/// Empty until a folder is chosen.
+#[command]
+pub async fn get_photo_gaps() -> Result<Vec<PhotoGap>, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let settings = Settings::load().photos;
+        let Some(dir) = configured_folder(&settings) else {
+            return Ok(Vec::new());
+        };
+        let mut photos = Vec::new();
+        photos_in(&dir, &mut photos).map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?;
+        let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+        photo_gaps(&db.conn, photos, settings.min_photos).map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Creates an entry on `date` (`YYYY-MM-DD`) with the photos at `paths`,
+/// which must be in the photos folder, and returns its id.
+#[command]
+pub async fn journal_photo_day(date: String, paths: Vec<String>) -> Result<i32, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let date: NaiveDate = date.parse().map_err(|_| format!("Invalid date {}", date))?;
+        let dir = configured_folder(&Settings::load().photos).ok_or("Choose a photos folder first")?;
+        let dir = dir.canonicalize().map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?;
+        let paths = paths
+            .iter()
+            .map(|path| match Path::new(path).canonicalize() {
+                Ok(path) if path.starts_with(&dir) => Ok(path),
+                _ => Err(format!("{} is not in the photos folder", path)),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+        let id = journal_photos(&db.conn, date, &paths)?;
+        git_sync::commit_on_save();
+        markdown_mirror::refresh();
+        recent_menu::refresh();
+        Ok(id)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A JPEG with only an EXIF segment, taken at `taken_at`.
+    fn jpeg(taken_at: &str) -> Vec<u8> {
+        // Big-endian TIFF: IFD0 with a pointer to the EXIF IFD, which has
+        // DateTimeOriginal pointing at the text after it.
+        let mut tiff = b"MM\0\x2a\0\0\0\x08".to_vec();
+        tiff.extend([0, 1, 0x87, 0x69, 0, 4, 0, 0, 0, 1, 0, 0, 0, 26, 0, 0, 0, 0]);
+        tiff.extend([0, 1, 0x90, 0x03, 0, 2, 0, 0, 0, 20, 0, 0, 0, 44, 0, 0, 0, 0]);
+        tiff.extend(taken_at.as_bytes());
+        tiff.push(0);
+        let mut data = vec![0xff, 0xd8, 0xff, 0xe0, 0, 4, 0, 0, 0xff, 0xe1];
+        data.extend(((tiff.len() + 8) as u16).to_be_bytes());
+        data.extend(b"Exif\0\0");
+        data.extend(tiff);
+        data.extend([0xff, 0xda]);
+        data
+    }
+
+    #[test]
+    fn test_jpeg_taken_at() {
+        let taken_at = NaiveDateTime::parse_from_str("2024-03-05 14:22:10", "%Y-%m-%d %H:%M:%S").unwrap();
+        assert_eq!(jpeg_taken_at(&jpeg("2024:03:05 14:22:10")), Some(taken_at));
+        assert_eq!(jpeg_taken_at(&jpeg("0000:00:00 00:00:00")), None);
+        assert_eq!(jpeg_taken_at(&[0xff, 0xd8, 0xff, 0xda]), None);
+        assert_eq!(jpeg_taken_at(b"\x89PNG"), None);
+    }
+
+    #[test]
+    fn test_photo_gaps() {
+        let dir = std::env::temp_dir().join(format!("journal-photo-gaps-{}", std::process::id()));
+        fs::create_dir_all(dir.join("2024/.thumbnails")).unwrap();
+        for (i, day) in ["05", "05", "05", "06", "06", "06", "07"].iter().enumerate() {
+            let taken_at = format!("2024:03:{} 1{}:00:00", day, i);
+            fs::write(dir.join(format!("2024/IMG_{}.jpg", i)), jpeg(&taken_at)).unwrap();
+        }
+        fs::write(dir.join("2024/.thumbnails/IMG_0.jpg"), jpeg("2024:03:07 10:00:00")).unwrap();
+        fs::write(dir.join("2024/notes.txt"), "not a photo").unwrap();
+
+        let db = DatabaseManager::open_in_memory().unwrap();
+        let local = |text: &str| Local.from_local_datetime(&text.parse().unwrap()).unwrap().to_rfc3339();
+        insert_entry(&db.conn, "Lisbon", "<p>Trams</p>", &local("2024-03-06T20:00:00")).unwrap();
+        let mut photos = Vec::new();
+        photos_in(&dir, &mut photos).unwrap();
+        assert_eq!(photos.len(), 7);
+        let gaps = photo_gaps(&db.conn, photos, 3).unwrap();
+        assert_eq!(gaps.len(), 1);
+        assert_eq!(gaps[0].date, "2024-03-05");
+        let paths: Vec<&str> = gaps[0].photos.iter().map(|photo| photo.path.as_str()).collect();
+        assert!(paths[0].ends_with("IMG_0.jpg") && paths[2].ends_with("IMG_2.jpg"));
+        assert_eq!(gaps[0].photos[1].taken_at, local("2024-03-05T11:00:00"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,100 @@
+use log::warn;
+use serde::Serialize;
+use tauri::{command, AppHandle, Emitter, WebviewWindow};
+use tauri_plugin_dialog::{DialogExt, MessageDialogKind};
+
+use journal_core::keychain::KeychainManager;
+
+use crate::stats::local_date;
+use crate::{file_drop, tray, DatabaseManager};
+
+// Printing a single entry. The main window keeps a print-only view that
+// holds nothing but the entry's title, date and formatted body, so the OS
+// print dialog gets a clean page rather than the sidebar and editor chrome.
+// The backend fills that view through a `print-entry` event, and the window
+// opens the dialog with `print_window` once it has rendered.
+
+const EVENT: &str = "print-entry";
+
+#[derive(Debug, Serialize)]
+pub struct PrintableEntry {
+    id: i32,
+    title: String,
+    /// The day the entry was written for, spelled out.
+    date: String,
+    /// The entry's HTML, as the editor saved it.
+    body: String,
+}
+
+fn printable(conn: &rusqlite::Connection, id: i32) -> Result<PrintableEntry, String> {
+    let (title, body, created_at, locked): (String, String, String, bool) = conn
+        .query_row(
+            "SELECT title, body, created_at, locked FROM journal_entries WHERE id = ?1",
+            rusqlite::params![id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+        )
+        .map_err(|_| format!("Entry {} not found", id))?;
+    if locked {
+        return Err("Locked entries can't be printed".to_string());
+    }
+    let title = title.trim();
+    Ok(PrintableEntry {
+        id,
+        title: if title.is_empty() { "Untitled" } else { title }.to_string(),
+        date: local_date(&created_at).map_or_else(String::new, |day| day.format("%A, %B %-d, %Y").to_string()),
+        body,
+    })
+}
+
+/// Lays entry `id` out in the main window's print view, which then asks for
+/// the print dialog.
+#[command]
+pub fn print_entry(app: AppHandle, id: i32) -> Result<(), String> {
+    if !KeychainManager::has_cached_key() {
+        return Err("Unlock the journal before printing".to_string());
+    }
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    let entry = printable(&db.conn, id)?;
+    tray::show_main(&app);
+    app.emit_to("main", EVENT, entry).map_err(|e| e.to_string())
+}
+
+/// Opens the OS print dialog for the calling window.
+#[command]
+pub fn print_window(window: WebviewWindow) -> Result<(), String> {
+    window.print().map_err(|e| e.to_string())
+}
+
+/// File ▸ Print: prints the entry open in the main window.
+pub fn print_current(app: &AppHandle) {
+    let result = match file_drop::current_entry() {
+        Some(id) => print_entry(app.clone(), id),
+        None => Err("Open an entry to print it".to_string()),
+    };
+    if let Err(e) = result {
+        warn!("Could not print: {}", e);
+        app.dialog().message(e).title("Print").kind(MessageDialogKind::Warning).show(|_| {});
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use journal_core::insert_entry;
+
+    #[test]
+    fn test_printable() {
+        let db = DatabaseManager::open_in_memory().unwrap();
+        let id = insert_entry(&db.conn, " ", "<p>Hello</p>", "2024-03-05T12:00:00+00:00").unwrap();
+        let entry = printable(&db.conn, id).unwrap();
+        assert_eq!(entry.title, "Untitled");
+        assert!(entry.date.contains("March"));
+        assert_eq!(entry.body, "<p>Hello</p>");
+
+        db.conn
+            .execute("UPDATE journal_entries SET locked = 1 WHERE id = ?1", rusqlite::params![id])
+            .unwrap();
+        assert!(printable(&db.conn, id).is_err());
+        assert!(printable(&db.conn, id + 1).is_err());
+    }
+}
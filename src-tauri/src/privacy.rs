@@ -0,0 +1,87 @@
+use log::warn;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+use tauri::{command, AppHandle, Emitter, Manager};
+
+use crate::settings::Settings;
+
+// The privacy screen, which covers every journal window at once. It's kept
+// here rather than in each webview so the windows agree on it, and so it can
+// come up on its own: from Window ▸ Blur, after a minute without input, or
+// when the app loses focus. While it's up the windows are also excluded from
+// screenshots, recordings and screen sharing, which can be made permanent.
+
+const EVENT: &str = "privacy-screen";
+/// Time for focus to land when it moves between the app's own windows.
+const FOCUS_SETTLE: Duration = Duration::from_millis(150);
+
+static ENGAGED: AtomicBool = AtomicBool::new(false);
+
+/// Whether new windows should be left out of screen captures.
+pub fn content_protected() -> bool {
+    ENGAGED.load(Ordering::SeqCst) || Settings::load().privacy.hide_from_screen_capture
+}
+
+/// Brings every window's content protection in line with the current state.
+pub fn apply(app: &AppHandle) {
+    let protected = content_protected();
+    for window in app.webview_windows().values() {
+        if let Err(e) = window.set_content_protected(protected) {
+            warn!("Failed to update screen capture protection: {}", e);
+        }
+    }
+}
+
+/// Raises or lowers the privacy screen and tells the windows.
+pub fn set(app: &AppHandle, engaged: bool) {
+    if ENGAGED.swap(engaged, Ordering::SeqCst) == engaged {
+        return;
+    }
+    apply(app);
+    let _ = app.emit(EVENT, engaged);
+}
+
+pub fn toggle(app: &AppHandle) {
+    set(app, !ENGAGED.load(Ordering::SeqCst));
+}
+
+/// Raises the screen when focus leaves the app, if the user asked for that.
+/// Focus moving to another of the app's windows doesn't count.
+pub fn focus_lost(app: &AppHandle) {
+    if !Settings::load().privacy.engage_on_focus_loss {
+        return;
+    }
+    let app = app.clone();
+    thread::spawn(move || {
+        thread::sleep(FOCUS_SETTLE);
+        let focused = app.webview_windows().values().any(|window| window.is_focused().unwrap_or(false));
+        if !focused {
+            set(&app, true);
+        }
+    });
+}
+
+#[command]
+pub fn get_privacy_screen() -> bool {
+    ENGAGED.load(Ordering::SeqCst)
+}
+
+#[command]
+pub fn set_privacy_screen(app: AppHandle, engaged: bool) {
+    set(&app, engaged);
+}
+
+#[command]
+pub fn set_privacy_options(
+    app: AppHandle,
+    engage_on_focus_loss: bool,
+    hide_from_screen_capture: bool,
+) -> Result<(), String> {
+    let mut settings = Settings::load();
+    settings.privacy.engage_on_focus_loss = engage_on_focus_loss;
+    settings.privacy.hide_from_screen_capture = hide_from_screen_capture;
+    settings.save().map_err(|e| e.to_string())?;
+    apply(&app);
+    Ok(())
+}
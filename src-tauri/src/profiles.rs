@@ -0,0 +1,55 @@
+use log::info;
+use std::path::Path;
+use tauri::{command, AppHandle};
+
+use journal_core::keychain::KeychainManager;
+use journal_core::profiles::{self, ProfileSummary};
+
+use crate::DatabaseManager;
+
+// Commands for journal profiles, which are kept in journal-core. Everything
+// running in the background (sync, the API server, scheduled backups) was
+// started for the open profile, so switching relaunches the app, which then
+// asks to unlock the other profile. Moving a profile's database doesn't need
+// a relaunch, since every connection looks the location up when it opens.
+
+#[command]
+pub fn list_profiles() -> Result<Vec<ProfileSummary>, String> {
+    profiles::list_profiles().map_err(|e| e.to_string())
+}
+
+#[command]
+pub fn create_profile(name: String) -> Result<(), String> {
+    profiles::create_profile(&name).map(|_| ()).map_err(|e| e.to_string())
+}
+
+#[command]
+pub fn switch_profile(app: AppHandle, name: String) -> Result<(), String> {
+    if profiles::active_profile().name.eq_ignore_ascii_case(name.trim()) {
+        return Ok(());
+    }
+    let profile = profiles::switch_profile(&name).map_err(|e| e.to_string())?;
+    info!("Switching to profile {}", profile.name);
+    app.restart()
+}
+
+/// The folder the open profile's database is in.
+#[command]
+pub fn get_data_directory() -> Result<String, String> {
+    profiles::database_dir()
+        .map(|dir| dir.to_string_lossy().into_owned())
+        .map_err(|e| e.to_string())
+}
+
+/// Moves the open profile's database into `path`, which must be an existing
+/// folder with no journal in it yet. The copy is checked before the journal
+/// switches to it. Returns the database's new path.
+#[command]
+pub fn set_data_directory(path: String) -> Result<String, String> {
+    if !KeychainManager::has_cached_key() {
+        return Err("Unlock the journal before moving it".to_string());
+    }
+    let moved_to = DatabaseManager::relocate(Path::new(&path)).map_err(|e| e.to_string())?;
+    info!("Journal database is now at {:?}", moved_to);
+    Ok(moved_to.to_string_lossy().into_owned())
+}
@@ -0,0 +1,140 @@
+use chrono::{Datelike, Local, NaiveDate, Utc};
+use serde::Serialize;
+use tauri::command;
+use uuid::Uuid;
+
+use crate::DatabaseManager;
+
+const MAX_PROMPT_CHARS: usize = 500;
+
+const BUILT_IN_PROMPTS: &[&str] = &[
+    "What made you smile today?",
+    "What is something you're looking forward to this week?",
+    "Describe a small moment from today you'd like to remember.",
+    "What's been on your mind lately that you haven't said out loud?",
+    "Who made a difference in your day, and how?",
+    "What is one thing you learned recently?",
+    "What drained your energy today, and what restored it?",
+    "Write about a place where you feel completely at ease.",
+    "What would you tell yourself from a year ago?",
+    "What are three things you're grateful for right now?",
+    "What is a decision you're currently weighing?",
+    "Describe how your body feels right now.",
+    "What did you avoid today, and why?",
+    "What is something you'd like to let go of?",
+    "When did you last feel proud of yourself?",
+    "What does a good day look like for you at the moment?",
+    "What conversation has stayed with you recently?",
+    "What are you curious about these days?",
+    "Write about something that surprised you this week.",
+    "What boundary do you want to set or keep?",
+    "What is a habit you'd like to build, and what's the first step?",
+    "Describe your ideal morning in detail.",
+    "What's a worry you can put down for tonight?",
+    "What would make tomorrow a little better than today?",
+    "Write a letter to someone you miss.",
+    "What did you notice on your last walk outside?",
+    "Which of your values felt most present today?",
+    "What's something kind you did for someone else recently?",
+    "What are you holding on to that no longer fits?",
+    "How have you changed over the past month?",
+];
+
+#[derive(Debug, Serialize)]
+pub struct Prompt {
+    /// `None` for built-in prompts, the row id for user-added ones.
+    id: Option<i32>,
+    text: String,
+}
+
+/// Built-in prompts followed by the user's own, in a stable order.
+fn all_prompts(conn: &rusqlite::Connection) -> rusqlite::Result<Vec<Prompt>> {
+    let mut prompts: Vec<Prompt> = BUILT_IN_PROMPTS
+        .iter()
+        .map(|text| Prompt { id: None, text: text.to_string() })
+        .collect();
+    let mut stmt = conn.prepare("SELECT id, text FROM user_prompts ORDER BY id")?;
+    let user_prompts = stmt.query_map([], |row| {
+        Ok(Prompt { id: Some(row.get(0)?), text: row.get(1)? })
+    })?;
+    for prompt in user_prompts {
+        prompts.push(prompt?);
+    }
+    Ok(prompts)
+}
+
+/// Picks the same index for every request on a given day, so the daily prompt
+/// doesn't change each time the app is reopened. `count` must be non-zero;
+/// the built-in library guarantees that.
+fn day_index(count: usize, day: NaiveDate) -> usize {
+    day.num_days_from_ce().unsigned_abs() as usize % count
+}
+
+fn daily_prompt(conn: &rusqlite::Connection) -> rusqlite::Result<Prompt> {
+    let mut prompts = all_prompts(conn)?;
+    let index = day_index(prompts.len(), Local::now().date_naive());
+    Ok(prompts.swap_remove(index))
+}
+
+/// Today's prompt text, used by the `{{prompt}}` template variable.
+pub fn daily_prompt_text(conn: &rusqlite::Connection) -> rusqlite::Result<String> {
+    daily_prompt(conn).map(|prompt| prompt.text)
+}
+
+#[command]
+pub fn get_random_prompt() -> Result<Prompt, String> {
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    let mut prompts = all_prompts(&db.conn).map_err(|e| e.to_string())?;
+    // A v4 UUID is as good a source of randomness as we need here.
+    let index = (Uuid::new_v4().as_u128() % prompts.len() as u128) as usize;
+    Ok(prompts.swap_remove(index))
+}
+
+#[command]
+pub fn get_daily_prompt() -> Result<Prompt, String> {
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    daily_prompt(&db.conn).map_err(|e| e.to_string())
+}
+
+#[command]
+pub fn add_prompt(text: String) -> Result<i32, String> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Err("Prompt cannot be empty".to_string());
+    }
+    if text.chars().count() > MAX_PROMPT_CHARS {
+        return Err(format!("Prompt must be at most {} characters", MAX_PROMPT_CHARS));
+    }
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    db.conn
+        .execute(
+            "INSERT INTO user_prompts (text, created_at) VALUES (?1, ?2)",
+            rusqlite::params![text, Utc::now().to_rfc3339()],
+        )
+        .map_err(|e| e.to_string())?;
+    Ok(db.conn.last_insert_rowid() as i32)
+}
+
+#[command]
+pub fn delete_prompt(id: i32) -> Result<(), String> {
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    db.conn
+        .execute("DELETE FROM user_prompts WHERE id = ?1", rusqlite::params![id])
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_day_index_is_stable_and_rotates() {
+        let day = NaiveDate::from_ymd_opt(2024, 3, 5).unwrap();
+        let next = day.succ_opt().unwrap();
+        let count = BUILT_IN_PROMPTS.len();
+        assert_eq!(day_index(count, day), day_index(count, day));
+        assert_ne!(day_index(count, day), day_index(count, next));
+        assert!(day_index(count, day) < count);
+    }
+}
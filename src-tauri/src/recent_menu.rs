@@ -0,0 +1,139 @@
+use log::warn;
+use std::sync::{Mutex, OnceLock};
+use std::thread;
+use tauri::menu::{MenuItemBuilder, Submenu, SubmenuBuilder};
+use tauri::{AppHandle, Listener, Wry};
+
+use journal_core::keychain::KeychainManager;
+
+use crate::stats::entry_day;
+use crate::{DatabaseManager, JournalEntry};
+
+// File ▸ Recent Entries: the latest entries, rebuilt from the database
+// whenever they may have changed. Items are identified as
+// `recent_entry:{id}`, and choosing one opens that entry. While the journal
+// is locked the submenu is emptied, so titles don't show on a locked journal.
+
+const RECENT_COUNT: u32 = 10;
+const ITEM_PREFIX: &str = "recent_entry:";
+/// Events after which the list may be out of date.
+const REFRESH_EVENTS: &[&str] = &["entries-changed", "entries-synced", "files-dropped", "journal-locked"];
+
+static MENU: OnceLock<Submenu<Wry>> = OnceLock::new();
+/// Keeps concurrent rebuilds from interleaving their items.
+static REBUILD_LOCK: Mutex<()> = Mutex::new(());
+
+fn recent_entries(conn: &rusqlite::Connection) -> rusqlite::Result<Vec<JournalEntry>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM journal_entries WHERE archived = 0
+         ORDER BY created_ms DESC, id DESC LIMIT ?1",
+        JournalEntry::COLUMNS
+    ))?;
+    let entries = stmt
+        .query_map(rusqlite::params![RECENT_COUNT], JournalEntry::from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(entries)
+}
+
+/// The title, or the day it was written for an untitled entry.
+fn label(entry: &JournalEntry) -> String {
+    let title = entry.title.trim();
+    if !title.is_empty() {
+        return title.to_string();
+    }
+    entry_day(entry).map_or_else(|| "Untitled".to_string(), |day| day.format("%B %-d, %Y").to_string())
+}
+
+/// The entry a menu item opens, if it's one of the recent entries.
+pub fn clicked_entry(item_id: &str) -> Option<i32> {
+    item_id.strip_prefix(ITEM_PREFIX)?.parse().ok()
+}
+
+fn rebuild(menu: &Submenu<Wry>) -> Result<(), String> {
+    let entries = if KeychainManager::has_cached_key() {
+        let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+        recent_entries(&db.conn).map_err(|e| e.to_string())?
+    } else {
+        Vec::new()
+    };
+    let _guard = REBUILD_LOCK.lock().map_err(|e| e.to_string())?;
+    for item in menu.items().map_err(|e| e.to_string())? {
+        menu.remove(item.as_ref()).map_err(|e| e.to_string())?;
+    }
+    let app = menu.app_handle();
+    if entries.is_empty() {
+        let placeholder = MenuItemBuilder::new("No Recent Entries").enabled(false).build(app);
+        return placeholder.and_then(|item| menu.append(&item)).map_err(|e| e.to_string());
+    }
+    for entry in &entries {
+        let item = MenuItemBuilder::with_id(format!("{}{}", ITEM_PREFIX, entry.id), label(entry))
+            .build(app)
+            .map_err(|e| e.to_string())?;
+        menu.append(&item).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// Rebuilds the submenu in the background, once it exists. Called after
+/// commands that add, retitle, redate or remove entries.
+pub fn refresh() {
+    let Some(menu) = MENU.get().cloned() else {
+        return;
+    };
+    thread::spawn(move || {
+        if let Err(e) = rebuild(&menu) {
+            warn!("Failed to update recent entries menu: {}", e);
+        }
+    });
+}
+
+/// Builds the submenu and keeps it current from then on.
+pub fn create(app: &AppHandle) -> tauri::Result<Submenu<Wry>> {
+    let menu = SubmenuBuilder::new(app, "Recent Entries").build()?;
+    let _ = MENU.set(menu.clone());
+    for event in REFRESH_EVENTS {
+        app.listen_any(*event, |_| refresh());
+    }
+    refresh();
+    Ok(menu)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use journal_core::insert_entry;
+
+    #[test]
+    fn test_recent_entries() {
+        let db = DatabaseManager::open_in_memory().unwrap();
+        let ids: Vec<i32> = (1..=12)
+            .map(|day| {
+                let created_at = format!("2024-03-{:02}T12:00:00+00:00", day);
+                insert_entry(&db.conn, &format!("Day {}", day), "", &created_at).unwrap()
+            })
+            .collect();
+        db.conn
+            .execute("UPDATE journal_entries SET archived = 1 WHERE id = ?1", rusqlite::params![ids[11]])
+            .unwrap();
+        let recent = recent_entries(&db.conn).unwrap();
+        assert_eq!(recent.len(), 10);
+        assert_eq!((recent[0].id, recent[9].id), (ids[10], ids[1]));
+    }
+
+    #[test]
+    fn test_labels_and_ids() {
+        let entry = JournalEntry {
+            id: 7,
+            title: "  ".to_string(),
+            created_at: "2024-03-05T12:00:00+00:00".to_string(),
+            word_count: 0,
+            notebook_id: None,
+            archived: false,
+            local_date: None,
+        };
+        assert!(label(&entry).starts_with("March "));
+        assert_eq!(clicked_entry("recent_entry:7"), Some(7));
+        assert_eq!(clicked_entry("recent_entry:x"), None);
+        assert_eq!(clicked_entry("new_entry"), None);
+    }
+}
@@ -0,0 +1,225 @@
+use log::{debug, error};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::thread;
+use std::time::Duration;
+use tauri::command;
+
+use journal_core::keychain::KeychainManager;
+use journal_core::text::{is_stop_word, strip_html};
+
+use crate::{DatabaseManager, JournalEntry};
+
+// "You wrote about this before": entries are compared by TF-IDF over the words
+// they use. Each entry's term counts are kept in `entry_terms`, refreshed by a
+// background indexer for entries changed since they were last counted. Term
+// weights depend on the whole journal, so similarity is worked out when asked.
+
+const INDEXER_START_DELAY: Duration = Duration::from_secs(60);
+const INDEXER_INTERVAL: Duration = Duration::from_secs(10 * 60);
+const MIN_TERM_CHARS: usize = 3;
+const MAX_RELATED: usize = 50;
+
+#[derive(Debug, Serialize)]
+pub struct RelatedEntry {
+    #[serde(flatten)]
+    entry: JournalEntry,
+    /// Cosine similarity to the entry asked about, from 0 to 1.
+    score: f64,
+}
+
+/// Counts the meaningful words of an entry, lowercased.
+fn term_counts(title: &str, body: &str) -> HashMap<String, i64> {
+    let text = format!("{} {}", title, strip_html(body)).to_lowercase();
+    let mut counts = HashMap::new();
+    for word in text.split(|c: char| !c.is_alphanumeric()) {
+        if word.chars().count() < MIN_TERM_CHARS
+            || word.chars().all(|c| c.is_ascii_digit())
+            || is_stop_word(word)
+        {
+            continue;
+        }
+        *counts.entry(word.to_string()).or_insert(0) += 1;
+    }
+    counts
+}
+
+/// Recounts the terms of every entry changed since it was last indexed.
+fn index_stale(conn: &rusqlite::Connection) -> rusqlite::Result<usize> {
+    let tx = conn.unchecked_transaction()?;
+    let stale = {
+        let mut stmt = tx.prepare(
+            "SELECT e.id, e.title, e.body, e.updated_at FROM journal_entries e
+             LEFT JOIN entry_terms_indexed i ON i.entry_id = e.id
+             WHERE i.entry_id IS NULL OR i.updated_at IS NOT e.updated_at",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            let updated_at: Option<String> = row.get(3)?;
+            Ok((row.get::<_, i32>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?, updated_at))
+        })?;
+        rows.collect::<Result<Vec<_>, _>>()?
+    };
+    for (id, title, body, updated_at) in &stale {
+        tx.execute("DELETE FROM entry_terms WHERE entry_id = ?1", rusqlite::params![id])?;
+        let mut insert = tx.prepare_cached("INSERT INTO entry_terms (entry_id, term, count) VALUES (?1, ?2, ?3)")?;
+        for (term, count) in term_counts(title, body) {
+            insert.execute(rusqlite::params![id, term, count])?;
+        }
+        tx.execute(
+            "INSERT OR REPLACE INTO entry_terms_indexed (entry_id, updated_at) VALUES (?1, ?2)",
+            rusqlite::params![id, updated_at],
+        )?;
+    }
+    tx.commit()?;
+    Ok(stale.len())
+}
+
+/// Log-scaled term frequency times inverse document frequency.
+fn weight(count: i64, df: i64, documents: i64) -> f64 {
+    (1.0 + (count as f64).ln()) * ((documents as f64 + 1.0) / (df as f64 + 1.0)).ln()
+}
+
+/// Cosine similarity of `target` to each candidate, highest first.
+fn rank(
+    target: &HashMap<String, i64>,
+    candidates: &HashMap<i32, HashMap<String, i64>>,
+    df: &HashMap<String, i64>,
+    documents: i64,
+) -> Vec<(i32, f64)> {
+    let vector = |terms: &HashMap<String, i64>| -> HashMap<String, f64> {
+        terms
+            .iter()
+            .map(|(term, &count)| (term.clone(), weight(count, df.get(term).copied().unwrap_or(1), documents)))
+            .collect()
+    };
+    let norm = |v: &HashMap<String, f64>| v.values().map(|w| w * w).sum::<f64>().sqrt();
+    let target = vector(target);
+    let target_norm = norm(&target);
+    let mut scores: Vec<(i32, f64)> = candidates
+        .iter()
+        .filter_map(|(&id, terms)| {
+            let other = vector(terms);
+            let dot: f64 = target.iter().filter_map(|(term, w)| other.get(term).map(|o| w * o)).sum();
+            let norms = target_norm * norm(&other);
+            (dot > 0.0 && norms > 0.0).then(|| (id, dot / norms))
+        })
+        .collect();
+    scores.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+    scores
+}
+
+fn related_entries(conn: &rusqlite::Connection, id: i32, k: usize) -> rusqlite::Result<Vec<RelatedEntry>> {
+    let mut target = HashMap::new();
+    let mut candidates: HashMap<i32, HashMap<String, i64>> = HashMap::new();
+    let mut stmt = conn.prepare(
+        "SELECT entry_id, term, count FROM entry_terms WHERE entry_id = ?1 OR entry_id IN (
+             SELECT DISTINCT o.entry_id FROM entry_terms o
+             JOIN entry_terms t ON t.term = o.term AND t.entry_id = ?1
+         )",
+    )?;
+    let rows = stmt.query_map(rusqlite::params![id], |row| {
+        Ok((row.get::<_, i32>(0)?, row.get::<_, String>(1)?, row.get::<_, i64>(2)?))
+    })?;
+    for row in rows {
+        let (entry_id, term, count) = row?;
+        let terms = if entry_id == id { &mut target } else { candidates.entry(entry_id).or_default() };
+        terms.insert(term, count);
+    }
+    if target.is_empty() {
+        return Ok(Vec::new());
+    }
+    let documents: i64 = conn.query_row("SELECT COUNT(*) FROM entry_terms_indexed", [], |row| row.get(0))?;
+    let mut df = HashMap::new();
+    let mut stmt = conn.prepare(
+        "SELECT term, COUNT(*) FROM entry_terms
+         WHERE term IN (SELECT term FROM entry_terms WHERE entry_id IN (
+             SELECT DISTINCT o.entry_id FROM entry_terms o
+             JOIN entry_terms t ON t.term = o.term AND t.entry_id = ?1
+         ))
+         GROUP BY term",
+    )?;
+    for row in stmt.query_map(rusqlite::params![id], |row| Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?)))? {
+        let (term, count) = row?;
+        df.insert(term, count);
+    }
+
+    let mut entry_stmt = conn.prepare(&format!("SELECT {} FROM journal_entries WHERE id = ?1", JournalEntry::COLUMNS))?;
+    rank(&target, &candidates, &df, documents)
+        .into_iter()
+        .take(k)
+        .map(|(entry_id, score)| {
+            Ok(RelatedEntry {
+                entry: entry_stmt.query_row(rusqlite::params![entry_id], JournalEntry::from_row)?,
+                score,
+            })
+        })
+        .collect()
+}
+
+/// Starts the background thread that keeps term counts current, so related
+/// entries are ready when an entry is opened. Like the backup scheduler, it
+/// waits for the journal to be unlocked rather than prompting for the key.
+pub fn start_indexer() {
+    thread::spawn(|| {
+        thread::sleep(INDEXER_START_DELAY);
+        loop {
+            if KeychainManager::has_cached_key() {
+                let result = DatabaseManager::new()
+                    .map_err(|e| e.to_string())
+                    .and_then(|db| index_stale(&db.conn).map_err(|e| e.to_string()));
+                match result {
+                    Ok(0) => {}
+                    Ok(count) => debug!("Indexed terms of {} entries", count),
+                    Err(e) => error!("Indexing entry terms failed: {}", e),
+                }
+            }
+            thread::sleep(INDEXER_INTERVAL);
+        }
+    });
+}
+
+/// Up to `k` past entries most similar to entry `id`, for the reading view.
+#[command]
+pub fn get_related_entries(id: i32, k: usize) -> Result<Vec<RelatedEntry>, String> {
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    // Catches up on anything saved since the indexer last ran
+    index_stale(&db.conn).map_err(|e| e.to_string())?;
+    related_entries(&db.conn, id, k.min(MAX_RELATED)).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use journal_core::{insert_entry, timestamp_now};
+
+    #[test]
+    fn test_term_counts() {
+        let counts = term_counts("Lisbon trip", "<p>The trams in Lisbon, and 2024's pastéis!</p>");
+        assert_eq!(counts.get("lisbon"), Some(&2));
+        assert_eq!(counts.get("pastéis"), Some(&1));
+        assert!(!counts.contains_key("the") && !counts.contains_key("2024") && !counts.contains_key("in"));
+    }
+
+    #[test]
+    fn test_related_entries_ranks_by_shared_words() {
+        let db = DatabaseManager::open_in_memory().unwrap();
+        let ids: Vec<i32> = [
+            ("Lisbon", "<p>Trams, tiles and custard tarts in Lisbon</p>"),
+            ("Back in Lisbon", "<p>Rode the trams again, more custard tarts</p>"),
+            ("Work", "<p>Long meeting about budgets, then tiles for the bathroom</p>"),
+            ("Garden", "<p>Planted tomatoes</p>"),
+        ]
+        .iter()
+        .map(|(title, body)| insert_entry(&db.conn, title, body, &timestamp_now()).unwrap())
+        .collect();
+        let trip = ids[0];
+        assert_eq!(index_stale(&db.conn).unwrap(), 4);
+        assert_eq!(index_stale(&db.conn).unwrap(), 0);
+
+        let related = related_entries(&db.conn, trip, 5).unwrap();
+        let titles: Vec<_> = related.iter().map(|r| r.entry.title.as_str()).collect();
+        assert_eq!(titles, vec!["Back in Lisbon", "Work"]);
+        assert!(related[0].score > related[1].score && related[0].score <= 1.0);
+        assert_eq!(related_entries(&db.conn, trip, 1).unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,89 @@
+use chrono::{DateTime, Duration, Utc};
+use log::info;
+use rusqlite::OptionalExtension;
+use std::collections::BTreeSet;
+use std::sync::Mutex;
+use tauri::command;
+
+use journal_core::audit::{self, AuditAction};
+
+use crate::settings::Settings;
+use crate::DatabaseManager;
+
+// Write-once mode: with `sealing.seal_after_days` set, an entry that old can
+// no longer be edited or deleted, so the journal stands as a record of what
+// was written at the time. An entry can still be unsealed on purpose; that's
+// noted in the audit log and lasts until the journal is locked or the app
+// quits.
+
+static UNSEALED: Mutex<BTreeSet<i32>> = Mutex::new(BTreeSet::new());
+
+/// Whether an entry created at `created_at` is past sealing at `now`.
+fn past_seal(created_at: &str, seal_after_days: u32, now: DateTime<Utc>) -> bool {
+    if seal_after_days == 0 {
+        return false;
+    }
+    DateTime::parse_from_rfc3339(created_at)
+        .is_ok_and(|created_at| now - created_at.with_timezone(&Utc) >= Duration::days(seal_after_days as i64))
+}
+
+fn is_unsealed(id: i32) -> bool {
+    UNSEALED.lock().is_ok_and(|unsealed| unsealed.contains(&id))
+}
+
+/// Whether entry `id` is sealed right now.
+pub fn is_sealed(conn: &rusqlite::Connection, id: i32) -> Result<bool, String> {
+    let seal_after_days = Settings::load().sealing.seal_after_days;
+    if seal_after_days == 0 || is_unsealed(id) {
+        return Ok(false);
+    }
+    let created_at: Option<String> = conn
+        .query_row("SELECT created_at FROM journal_entries WHERE id = ?1", [id], |row| row.get(0))
+        .optional()
+        .map_err(|e| e.to_string())?;
+    Ok(created_at.is_some_and(|created_at| past_seal(&created_at, seal_after_days, Utc::now())))
+}
+
+/// Refuses to go on if entry `id` is sealed.
+pub fn ensure_unsealed(conn: &rusqlite::Connection, id: i32) -> Result<(), String> {
+    if is_sealed(conn, id)? {
+        return Err(format!("Entry {} is sealed; unseal it before changing or deleting it", id));
+    }
+    Ok(())
+}
+
+/// Seals every entry unsealed this session again, e.g. when the journal locks.
+pub(crate) fn reseal_all() {
+    if let Ok(mut unsealed) = UNSEALED.lock() {
+        unsealed.clear();
+    }
+}
+
+/// Lets sealed entry `id` be edited or deleted until the journal is locked or
+/// the app quits. The window asks for confirmation first.
+#[command]
+pub fn unseal_entry(id: i32) -> Result<(), String> {
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    if !is_sealed(&db.conn, id)? {
+        return Ok(());
+    }
+    UNSEALED.lock().map_err(|e| e.to_string())?.insert(id);
+    info!("Unsealed entry {}", id);
+    audit::record(&db.conn, AuditAction::Unseal, &format!("entry {}", id)).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_past_seal() {
+        let now = DateTime::parse_from_rfc3339("2024-03-10T12:00:00Z").unwrap().with_timezone(&Utc);
+        assert!(past_seal("2024-03-03T12:00:00Z", 7, now));
+        assert!(!past_seal("2024-03-03T12:00:01Z", 7, now));
+        assert!(past_seal("2024-03-09T06:00:00-05:00", 1, now));
+        assert!(!past_seal("2024-03-09T09:00:00-05:00", 1, now));
+        assert!(!past_seal("2020-01-01T00:00:00Z", 0, now));
+        assert!(!past_seal("not a date", 7, now));
+    }
+}
@@ -0,0 +1,188 @@
+use chrono::NaiveDate;
+use tauri::command;
+
+use crate::{DatabaseManager, JournalEntry};
+
+/// A parsed search box query, e.g.
+/// `tag:work before:2024-01-01 after:2023-06-01 "exact phrase" coffee`.
+#[derive(Debug, Default, PartialEq)]
+pub struct SearchQuery {
+    /// Bare words and quoted phrases; each must appear in the title, the body,
+    /// or text found in one of the entry's attachments.
+    pub text: Vec<String>,
+    pub tags: Vec<String>,
+    /// Only entries written before this day (exclusive).
+    pub before: Option<NaiveDate>,
+    /// Only entries written on or after this day.
+    pub after: Option<NaiveDate>,
+    /// Whether archived entries can match; they're left out by default.
+    pub include_archived: bool,
+}
+
+impl SearchQuery {
+    pub fn parse(input: &str) -> Result<Self, String> {
+        let mut query = SearchQuery::default();
+        let mut chars = input.chars().peekable();
+        while let Some(&c) = chars.peek() {
+            if c.is_whitespace() {
+                chars.next();
+                continue;
+            }
+            if c == '"' {
+                chars.next();
+                // An unclosed quote runs to the end of the input
+                let phrase: String = chars.by_ref().take_while(|&c| c != '"').collect();
+                let phrase = phrase.trim();
+                if !phrase.is_empty() {
+                    query.text.push(phrase.to_string());
+                }
+                continue;
+            }
+            let token: String = std::iter::from_fn(|| chars.next_if(|c| !c.is_whitespace())).collect();
+            query.push_token(&token)?;
+        }
+        Ok(query)
+    }
+
+    fn push_token(&mut self, token: &str) -> Result<(), String> {
+        match token.split_once(':') {
+            Some(("tag", tag)) if !tag.is_empty() => {
+                self.tags.push(tag.trim_start_matches('#').to_string())
+            }
+            Some(("before", date)) if !date.is_empty() => self.before = Some(parse_filter_date(date)?),
+            Some(("after", date)) if !date.is_empty() => self.after = Some(parse_filter_date(date)?),
+            _ => self.text.push(token.to_string()),
+        }
+        Ok(())
+    }
+
+    /// Builds the `WHERE` clause (without the keyword) and its positional parameters.
+    pub fn to_sql(&self) -> (String, Vec<String>) {
+        let mut clauses = vec!["1 = 1".to_string()];
+        let mut params = Vec::new();
+        if !self.include_archived {
+            clauses.push("archived = 0".to_string());
+        }
+        for text in &self.text {
+            params.push(format!("%{}%", escape_like(text)));
+            let n = params.len();
+            clauses.push(format!(
+                "(title LIKE ?{n} ESCAPE '\\' OR body LIKE ?{n} ESCAPE '\\' OR id IN (
+                    SELECT a.entry_id FROM attachments a JOIN attachment_text t ON t.hash = a.hash
+                    WHERE t.text LIKE ?{n} ESCAPE '\\'
+                ))"
+            ));
+        }
+        for tag in &self.tags {
+            params.push(tag.clone());
+            clauses.push(format!(
+                "id IN (SELECT entry_id FROM entry_tags WHERE tag = ?{})",
+                params.len()
+            ));
+        }
+        if let Some(before) = self.before {
+            params.push(before.format("%Y-%m-%d").to_string());
+            clauses.push(format!("local_date < ?{}", params.len()));
+        }
+        if let Some(after) = self.after {
+            params.push(after.format("%Y-%m-%d").to_string());
+            clauses.push(format!("local_date >= ?{}", params.len()));
+        }
+        (clauses.join(" AND "), params)
+    }
+}
+
+fn parse_filter_date(date: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .map_err(|_| format!("Invalid date '{}' in search, expected YYYY-MM-DD", date))
+}
+
+fn escape_like(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+}
+
+/// Searches entries with the query language described on `SearchQuery`.
+/// Archived entries are only searched when `include_archived` is set.
+#[command]
+pub fn search_entries(query: String, include_archived: Option<bool>) -> Result<Vec<JournalEntry>, String> {
+    let mut query = SearchQuery::parse(&query)?;
+    query.include_archived = include_archived.unwrap_or(false);
+    let (clause, params) = query.to_sql();
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    let mut stmt = db.conn
+        .prepare(&format!(
+            "SELECT {} FROM journal_entries WHERE {} ORDER BY created_ms DESC, id DESC",
+            JournalEntry::COLUMNS,
+            clause
+        ))
+        .map_err(|e| e.to_string())?;
+    let entries = stmt
+        .query_map(rusqlite::params_from_iter(params.iter()), JournalEntry::from_row)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_full_query() {
+        let query = SearchQuery::parse(r#"tag:work before:2024-01-01 after:2023-06-01 "exact phrase" coffee"#).unwrap();
+        assert_eq!(query.text, vec!["exact phrase", "coffee"]);
+        assert_eq!(query.tags, vec!["work"]);
+        assert_eq!(query.before, NaiveDate::from_ymd_opt(2024, 1, 1));
+        assert_eq!(query.after, NaiveDate::from_ymd_opt(2023, 6, 1));
+    }
+
+    #[test]
+    fn test_parse_edge_cases() {
+        let query = SearchQuery::parse(r#"note:taking tag: "unclosed phrase"#).unwrap();
+        assert_eq!(query.text, vec!["note:taking", "tag:", "unclosed phrase"]);
+        assert!(SearchQuery::parse("before:yesterday").is_err());
+        assert_eq!(SearchQuery::parse("   ").unwrap(), SearchQuery::default());
+    }
+
+    #[test]
+    fn test_to_sql_numbers_params() {
+        let query = SearchQuery::parse("50% tag:home").unwrap();
+        let (clause, params) = query.to_sql();
+        assert_eq!(params, vec!["%50\\%%", "home"]);
+        assert!(clause.contains("LIKE ?1"));
+        assert!(clause.contains("tag = ?2"));
+        assert!(clause.contains("archived = 0"));
+    }
+
+    #[test]
+    fn test_text_matches_attachment_text() {
+        let db = DatabaseManager::open_in_memory().unwrap();
+        let id = journal_core::insert_entry(&db.conn, "Lunch", "<p>photo below</p>", "2024-05-01T12:00:00Z").unwrap();
+        db.conn
+            .execute_batch(&format!(
+                "INSERT INTO attachment_blobs (hash, size, data) VALUES ('h', 1, x'00');
+                 INSERT INTO attachments (entry_id, kind, mime, size, hash, created_at)
+                 VALUES ({}, 'image', 'image/png', 1, 'h', '');
+                 INSERT INTO attachment_text (hash, source, text, extracted_at) VALUES ('h', 'ocr', 'CAFE LUNA', '');",
+                id
+            ))
+            .unwrap();
+        let (clause, params) = SearchQuery::parse("luna").unwrap().to_sql();
+        let found: Vec<i32> = db
+            .conn
+            .prepare(&format!("SELECT id FROM journal_entries WHERE {}", clause))
+            .unwrap()
+            .query_map(rusqlite::params_from_iter(params.iter()), |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(found, vec![id]);
+    }
+
+    #[test]
+    fn test_to_sql_can_include_archived() {
+        let query = SearchQuery { include_archived: true, ..SearchQuery::parse("coffee").unwrap() };
+        assert!(!query.to_sql().0.contains("archived"));
+    }
+}
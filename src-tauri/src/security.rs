@@ -0,0 +1,119 @@
+use log::warn;
+use serde::Serialize;
+use tauri::command;
+
+use journal_core::audit::{self, AuditAction, AuditEvent};
+use journal_core::cipher::CipherParams;
+use journal_core::keychain::KeychainManager;
+use journal_core::profiles::active_profile;
+use journal_core::ErrorResponse;
+
+use crate::{backup, DatabaseManager};
+
+// A summary of how the journal is protected, for the settings screen and for
+// anyone checking that their data is encrypted the way they expect, and the
+// audit log of what has happened to it as a whole.
+
+/// Where the database key is kept between launches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyStorage {
+    /// The system keychain: Keychain on macOS, Credential Manager on
+    /// Windows, the Secret Service on Linux.
+    Keychain,
+    /// Nowhere; it's derived from a passphrase entered each launch.
+    Passphrase,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SecurityStatus {
+    /// False when the database has been turned into plain SQLite.
+    pub encrypted: bool,
+    /// The crypto library SQLCipher uses, e.g. `openssl` or `commoncrypto`.
+    pub cipher_provider: Option<String>,
+    pub cipher_version: Option<String>,
+    /// Page size, KDF work factor and HMAC the database is encrypted with.
+    pub cipher_params: Option<CipherParams>,
+    pub key_storage: KeyStorage,
+    /// When the database was last re-encrypted under a new key; `None` if
+    /// never.
+    pub last_key_rotation: Option<String>,
+    /// Key files left on disk in plain text, which anyone who can read the
+    /// folder could open the journal with.
+    pub plaintext_key_files: Vec<String>,
+}
+
+#[command]
+pub fn get_security_status() -> Result<SecurityStatus, ErrorResponse> {
+    let db = DatabaseManager::new()?;
+    let cipher = db.cipher()?;
+    let cipher_params = cipher.as_ref().map(|_| active_profile().cipher.unwrap_or_default());
+    let key_storage = if KeychainManager::uses_passphrase() {
+        KeyStorage::Passphrase
+    } else {
+        KeyStorage::Keychain
+    };
+    let plaintext_key_files = KeychainManager::plaintext_key_files()
+        .map_err(|e| e.to_string())?
+        .iter()
+        .map(|path| path.display().to_string())
+        .collect();
+    Ok(SecurityStatus {
+        encrypted: cipher.is_some(),
+        cipher_provider: cipher.as_ref().map(|(provider, _)| provider.clone()),
+        cipher_version: cipher.map(|(_, version)| version),
+        cipher_params,
+        key_storage,
+        last_key_rotation: db.last_key_rotation()?,
+        plaintext_key_files,
+    })
+}
+
+/// Adds an event to the open journal's audit log. What it describes has
+/// already happened, so failing to log it is only reported. The journal is
+/// opened afresh, since after an import it's a different database.
+pub(crate) fn audit(action: AuditAction, detail: &str) {
+    let result = DatabaseManager::new()
+        .map_err(|e| e.to_string())
+        .and_then(|db| audit::record(&db.conn, action, detail).map_err(|e| e.to_string()));
+    if let Err(e) = result {
+        warn!("Could not write to the audit log: {}", e);
+    }
+}
+
+/// The audit log, newest first.
+#[command]
+pub fn get_audit_log() -> Result<Vec<AuditEvent>, String> {
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    audit::audit_log(&db.conn).map_err(|e| e.to_string())
+}
+
+/// Turns the open profile's database into plain SQLite, or encrypts it again.
+/// Plain SQLite can be read with any SQLite tool, and by anyone who can read
+/// the file, so it's only meant for a journal on an encrypted volume. A backup
+/// is taken first.
+#[command]
+pub fn set_encryption_enabled(enabled: bool) -> Result<(), ErrorResponse> {
+    let db = DatabaseManager::new()?;
+    if db.is_encrypted() == enabled {
+        return Ok(());
+    }
+    backup::snapshot(&db, if enabled { "pre-encrypt" } else { "pre-decrypt" })?;
+    drop(db);
+    if !enabled {
+        warn!("Storing the journal unencrypted");
+    }
+    DatabaseManager::set_encrypted(enabled)
+}
+
+/// Re-encrypts the open profile's database with new SQLCipher parameters,
+/// after taking a backup. Backups taken before can still be restored only
+/// after changing back.
+#[command]
+pub fn set_cipher_params(params: CipherParams) -> Result<(), ErrorResponse> {
+    params.validate()?;
+    let db = DatabaseManager::new()?;
+    backup::snapshot(&db, "pre-cipher-change")?;
+    drop(db);
+    DatabaseManager::set_cipher_params(params)
+}
@@ -0,0 +1,107 @@
+use std::sync::OnceLock;
+use tauri::{AppHandle, Emitter};
+
+use journal_core::keychain::KeychainManager;
+
+use crate::api_server::append_to_today;
+
+// "Add to Journal" in the macOS Services menu, declared under NSServices in
+// Info.plist. Text selected in any app is handed to the provider object
+// registered below, which appends it to today's entry followed by a note of
+// the app it came from. Other platforms have no Services menu, so there the
+// provider is never registered.
+
+static APP: OnceLock<AppHandle> = OnceLock::new();
+
+/// `text` with a note of where it was sent from.
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+fn clipping(text: &str, source: Option<&str>) -> String {
+    let source = source.map(str::trim).filter(|source| !source.is_empty());
+    format!("{}\n\n— Added from {}", text.trim(), source.unwrap_or("another app"))
+}
+
+/// Appends text sent from `source` to today's entry.
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+fn add_to_journal(text: &str, source: Option<&str>) -> Result<i32, String> {
+    if text.trim().is_empty() {
+        return Err("There's no text to add".to_string());
+    }
+    if !KeychainManager::has_cached_key() {
+        return Err("Unlock the journal before adding to it".to_string());
+    }
+    let id = append_to_today(&clipping(text, source))?;
+    if let Some(app) = APP.get() {
+        let _ = app.emit("entries-changed", ());
+    }
+    Ok(id)
+}
+
+/// Starts answering the Services menu. Called once from setup.
+pub fn register(app: &AppHandle) {
+    let _ = APP.set(app.clone());
+    #[cfg(target_os = "macos")]
+    macos::register();
+}
+
+#[cfg(target_os = "macos")]
+mod macos {
+    use log::warn;
+    use objc2::rc::Retained;
+    use objc2::runtime::NSObject;
+    use objc2::{define_class, msg_send, MainThreadMarker, MainThreadOnly};
+    use objc2_app_kit::{NSApplication, NSPasteboard, NSPasteboardTypeString, NSWorkspace};
+    use objc2_foundation::{NSObjectProtocol, NSString};
+
+    define_class!(
+        #[unsafe(super(NSObject))]
+        #[thread_kind = MainThreadOnly]
+        #[name = "JournalServiceProvider"]
+        struct ServiceProvider;
+
+        unsafe impl NSObjectProtocol for ServiceProvider {}
+
+        impl ServiceProvider {
+            /// The `addToJournal` message named in Info.plist.
+            #[unsafe(method(addToJournal:userData:error:))]
+            fn add_to_journal(&self, pasteboard: &NSPasteboard, _user_data: Option<&NSString>, error: *mut *mut NSString) {
+                let text = unsafe { pasteboard.stringForType(NSPasteboardTypeString) }.map(|text| text.to_string());
+                // Services don't bring the journal forward, so the app the
+                // text was selected in is still the frontmost one
+                let source = NSWorkspace::sharedWorkspace()
+                    .frontmostApplication()
+                    .and_then(|app| app.localizedName())
+                    .map(|name| name.to_string());
+                let result = super::add_to_journal(text.as_deref().unwrap_or_default(), source.as_deref());
+                if let Err(e) = result {
+                    warn!("Could not add to the journal from a service: {}", e);
+                    if !error.is_null() {
+                        unsafe { *error = Retained::autorelease_ptr(NSString::from_str(&e)) };
+                    }
+                }
+            }
+        }
+    );
+
+    pub fn register() {
+        let Some(mtm) = MainThreadMarker::new() else {
+            warn!("Services must be registered from the main thread");
+            return;
+        };
+        let provider: Retained<ServiceProvider> = unsafe { msg_send![ServiceProvider::alloc(mtm), init] };
+        NSApplication::sharedApplication(mtm).setServicesProvider(Some(&provider));
+        // The application holds its services provider weakly
+        std::mem::forget(provider);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clipping_notes_the_source() {
+        assert_eq!(clipping(" Quote \n", Some("Safari")), "Quote\n\n— Added from Safari");
+        assert_eq!(clipping("Quote", Some(" ")), "Quote\n\n— Added from another app");
+        assert_eq!(clipping("Quote", None), "Quote\n\n— Added from another app");
+    }
+}
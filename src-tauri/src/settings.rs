@@ -0,0 +1,463 @@
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::command;
+
+use journal_core::profiles::profile_dir;
+use journal_core::validation::DEFAULT_MAX_BODY_MB;
+use journal_core::ErrorResponse;
+
+use crate::blob_store::Remote;
+use crate::logging::LogLevel;
+use crate::summaries::SummaryProvider;
+use crate::weather::WeatherProvider;
+
+const SETTINGS_FILE_NAME: &str = "settings.json";
+
+// Settings live in a plain JSON file next to the database rather than inside
+// it, because some of them are needed before the database can be opened. Each
+// profile has its own, so a private journal isn't synced like the main one.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BackupFrequency {
+    Daily,
+    Weekly,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BackupSettings {
+    pub enabled: bool,
+    pub frequency: BackupFrequency,
+    /// Folder scheduled backups are written to; the app's `Backups/` folder when unset.
+    pub directory: Option<String>,
+    /// How many days to keep the newest daily snapshot for; at least the
+    /// newest snapshot is always kept.
+    pub keep_daily: u32,
+    /// How many weeks to keep the newest weekly snapshot for.
+    pub keep_weekly: u32,
+}
+
+impl Default for BackupSettings {
+    fn default() -> Self {
+        BackupSettings {
+            enabled: false,
+            frequency: BackupFrequency::Daily,
+            directory: None,
+            keep_daily: 7,
+            keep_weekly: 4,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CloudBackupSettings {
+    pub enabled: bool,
+    /// Where encrypted snapshots are uploaded; its secret lives in the keychain.
+    pub remote: Option<Remote>,
+    pub frequency: BackupFrequency,
+    /// How many days to keep the newest daily snapshot for, in the cloud.
+    pub keep_daily: u32,
+    /// How many weeks to keep the newest weekly snapshot for, in the cloud.
+    pub keep_weekly: u32,
+}
+
+impl Default for CloudBackupSettings {
+    fn default() -> Self {
+        CloudBackupSettings {
+            enabled: false,
+            remote: None,
+            frequency: BackupFrequency::Daily,
+            keep_daily: 7,
+            keep_weekly: 4,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SyncSettings {
+    pub enabled: bool,
+    /// Where changesets are exchanged: a synced folder, WebDAV, or S3.
+    pub remote: Option<Remote>,
+    /// Names this device's changesets in the remote; assigned on first setup.
+    pub device_id: String,
+    /// Whether this device can be found and synced with on the local network.
+    pub lan_enabled: bool,
+    /// Shown to other devices on the local network.
+    pub device_name: String,
+    /// Hex salt this device derives its LAN sync key with, made when LAN sync
+    /// is turned on. Peers are told it, so it isn't secret.
+    pub lan_salt: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GitSettings {
+    pub enabled: bool,
+    /// Repository entries are mirrored into as Markdown files.
+    pub repo_path: Option<String>,
+    /// Pulled from and pushed to when set; credentials come from the user's Git setup.
+    pub remote_url: Option<String>,
+    pub branch: String,
+}
+
+impl Default for GitSettings {
+    fn default() -> Self {
+        GitSettings {
+            enabled: false,
+            repo_path: None,
+            remote_url: None,
+            branch: "main".to_string(),
+        }
+    }
+}
+
+impl GitSettings {
+    pub fn repo(&self) -> Result<&Path, String> {
+        self.repo_path
+            .as_deref()
+            .map(Path::new)
+            .ok_or_else(|| "No journal repository is configured".to_string())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ApiSettings {
+    /// Whether the localhost API for scripts and launchers is running.
+    pub enabled: bool,
+    pub port: u16,
+}
+
+impl Default for ApiSettings {
+    fn default() -> Self {
+        ApiSettings {
+            enabled: false,
+            port: 7455,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WeatherSettings {
+    /// Off unless the user opts in, since looking up the weather sends the
+    /// location below to the provider.
+    pub enabled: bool,
+    pub provider: WeatherProvider,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SummarySettings {
+    pub enabled: bool,
+    pub provider: SummaryProvider,
+    /// Base URL of the model server; only localhost addresses are accepted.
+    pub endpoint: String,
+    /// Model to ask, for servers like Ollama that host several.
+    pub model: String,
+}
+
+impl Default for SummarySettings {
+    fn default() -> Self {
+        SummarySettings {
+            enabled: false,
+            provider: SummaryProvider::Ollama,
+            endpoint: "http://localhost:11434".to_string(),
+            model: "llama3.2".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OcrSettings {
+    /// Whether words in image attachments are recognized so search can find them.
+    pub enabled: bool,
+    /// The Tesseract executable, by name on the `PATH` or as a full path.
+    pub tesseract_path: String,
+    /// Tesseract language codes, joined with `+` (e.g. `eng+deu`).
+    pub languages: String,
+}
+
+impl Default for OcrSettings {
+    fn default() -> Self {
+        OcrSettings {
+            enabled: false,
+            tesseract_path: "tesseract".to_string(),
+            languages: "eng".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TranscriptionSettings {
+    /// Whether voice memos are transcribed in the background.
+    pub enabled: bool,
+    /// The whisper.cpp command line program, by name on the `PATH` or as a full path.
+    pub whisper_path: String,
+    /// A ggml model file downloaded for whisper.cpp, e.g. `ggml-base.en.bin`.
+    pub model_path: Option<String>,
+    /// Converts memos to the WAV whisper.cpp reads.
+    pub ffmpeg_path: String,
+    /// Spoken language code, or `auto` to detect it.
+    pub language: String,
+}
+
+impl Default for TranscriptionSettings {
+    fn default() -> Self {
+        TranscriptionSettings {
+            enabled: false,
+            whisper_path: "whisper-cli".to_string(),
+            model_path: None,
+            ffmpeg_path: "ffmpeg".to_string(),
+            language: "auto".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PrivacySettings {
+    /// Whether the privacy screen comes up when the app loses focus.
+    pub engage_on_focus_loss: bool,
+    /// Whether windows show up blank in screenshots, recordings and screen sharing.
+    pub hide_from_screen_capture: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EmailIngestSettings {
+    /// Whether the mailbox is checked for notes; its password lives in the keychain.
+    pub enabled: bool,
+    /// IMAP server, reached over TLS.
+    pub host: String,
+    pub port: u16,
+    pub username: String,
+    pub mailbox: String,
+}
+
+impl Default for EmailIngestSettings {
+    fn default() -> Self {
+        EmailIngestSettings {
+            enabled: false,
+            host: String::new(),
+            port: 993,
+            username: String::new(),
+            mailbox: "INBOX".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PhotoSettings {
+    /// Whether `directory` is looked through for days worth writing about.
+    pub enabled: bool,
+    pub directory: Option<String>,
+    /// How many photos make a day without an entry worth suggesting.
+    pub min_photos: usize,
+}
+
+impl Default for PhotoSettings {
+    fn default() -> Self {
+        PhotoSettings {
+            enabled: false,
+            directory: None,
+            min_photos: 5,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MirrorSettings {
+    /// Whether every entry is kept as a Markdown file in `directory`.
+    pub enabled: bool,
+    pub directory: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SpotlightSettings {
+    /// Whether entry titles and dates are added to Spotlight on macOS.
+    pub enabled: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UnlockSettings {
+    /// Wrong guesses in a row at the journal's passphrase or recovery key, or
+    /// at a locked entry's passphrase, after which the journal locks itself
+    /// and forgets its key; 0 to never do so.
+    pub lock_after_failures: u32,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SealingSettings {
+    /// Days after which an entry can no longer be edited or deleted unless
+    /// it's unsealed first; 0 to never seal entries.
+    pub seal_after_days: u32,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ClipboardSettings {
+    /// Seconds after which journal text copied from the app is cleared from
+    /// the clipboard; 0 to leave it there.
+    pub clear_after_secs: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LimitSettings {
+    /// Largest entry body that's saved, in MB; 0 for no limit.
+    pub max_body_mb: u32,
+    /// Largest file that can be attached to an entry, in MB; 0 for no limit.
+    pub max_attachment_mb: u32,
+}
+
+impl Default for LimitSettings {
+    fn default() -> Self {
+        LimitSettings {
+            max_body_mb: DEFAULT_MAX_BODY_MB,
+            max_attachment_mb: 100,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LoggingSettings {
+    /// The least severe messages written to the log file.
+    pub level: LogLevel,
+}
+
+/// Menu accelerators, in the menu's own syntax (e.g. `CmdOrCtrl+Shift+L`).
+/// Empty when the action has no shortcut.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ShortcutSettings {
+    pub new_entry: String,
+    pub blur: String,
+    pub quick_capture: String,
+    pub lock: String,
+}
+
+impl Default for ShortcutSettings {
+    fn default() -> Self {
+        ShortcutSettings {
+            new_entry: "CmdOrCtrl+N".to_string(),
+            blur: "Ctrl+B".to_string(),
+            quick_capture: String::new(),
+            lock: String::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub backup: BackupSettings,
+    pub cloud_backup: CloudBackupSettings,
+    pub sync: SyncSettings,
+    pub git: GitSettings,
+    pub api: ApiSettings,
+    pub weather: WeatherSettings,
+    pub summaries: SummarySettings,
+    pub ocr: OcrSettings,
+    pub transcription: TranscriptionSettings,
+    pub privacy: PrivacySettings,
+    pub shortcuts: ShortcutSettings,
+    pub spotlight: SpotlightSettings,
+    pub mirror: MirrorSettings,
+    pub email: EmailIngestSettings,
+    pub photos: PhotoSettings,
+    pub unlock: UnlockSettings,
+    pub clipboard: ClipboardSettings,
+    pub sealing: SealingSettings,
+    pub limits: LimitSettings,
+    pub logging: LoggingSettings,
+}
+
+pub(crate) fn settings_path() -> Result<PathBuf, ErrorResponse> {
+    Ok(profile_dir()?.join(SETTINGS_FILE_NAME))
+}
+
+impl Settings {
+    /// Reads the settings file, falling back to defaults if it is missing or unreadable.
+    pub fn load() -> Settings {
+        let path = match settings_path() {
+            Ok(path) => path,
+            Err(e) => {
+                warn!("Could not locate settings file: {}", e);
+                return Settings::default();
+            }
+        };
+        match fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|e| {
+                warn!("Ignoring invalid settings file {:?}: {}", path, e);
+                Settings::default()
+            }),
+            Err(_) => Settings::default(),
+        }
+    }
+
+    pub fn save(&self) -> Result<(), ErrorResponse> {
+        let path = settings_path()?;
+        debug!("Saving settings to {:?}", path);
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|e| ErrorResponse {
+                message: format!("Failed to create settings directory: {}", e),
+                error_type: "file_error".to_string(),
+            })?;
+        }
+        let contents = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        fs::write(&path, contents).map_err(|e| ErrorResponse {
+            message: format!("Failed to write settings: {}", e),
+            error_type: "file_error".to_string(),
+        })
+    }
+}
+
+#[command]
+pub fn get_settings() -> Settings {
+    Settings::load()
+}
+
+#[command]
+pub fn save_settings(settings: Settings) -> Result<(), String> {
+    settings.save().map_err(|e| e.to_string())?;
+    crate::logging::apply(settings.logging.level);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_partial_settings_use_defaults() {
+        let settings: Settings = serde_json::from_str(r#"{"backup": {"enabled": true}}"#).unwrap();
+        assert!(settings.backup.enabled);
+        assert_eq!(settings.backup.frequency, BackupFrequency::Daily);
+        assert_eq!(settings.backup.keep_daily, 7);
+        assert!(!settings.sync.enabled);
+        assert_eq!(settings.git.branch, "main");
+        assert!(!settings.weather.enabled);
+        assert_eq!(settings.summaries.endpoint, "http://localhost:11434");
+        assert_eq!(settings.ocr.languages, "eng");
+        assert!(settings.transcription.model_path.is_none());
+        assert!(!settings.privacy.engage_on_focus_loss);
+        assert_eq!(settings.shortcuts.new_entry, "CmdOrCtrl+N");
+        assert_eq!(settings.limits.max_body_mb, 10);
+    }
+}
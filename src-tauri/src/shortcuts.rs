@@ -0,0 +1,197 @@
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use tauri::menu::{MenuItem, MenuItemBuilder};
+use tauri::{command, AppHandle, Wry};
+
+use crate::settings::{Settings, ShortcutSettings};
+
+// Keyboard shortcuts the user can remap. Each is the accelerator of an item
+// in the app menu, so changing one updates that item in place and the menu
+// shows the new keys straight away. An empty accelerator means no shortcut.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ShortcutAction {
+    NewEntry,
+    Blur,
+    QuickCapture,
+    Lock,
+}
+
+const ACTIONS: [ShortcutAction; 4] = [
+    ShortcutAction::NewEntry,
+    ShortcutAction::Blur,
+    ShortcutAction::QuickCapture,
+    ShortcutAction::Lock,
+];
+
+const MODIFIERS: &[&str] = &[
+    "cmdorctrl", "commandorcontrol", "cmd", "command", "super", "ctrl", "control", "alt", "option", "shift",
+];
+const NAMED_KEYS: &[&str] = &[
+    "space", "enter", "return", "tab", "backspace", "delete", "escape", "esc", "up", "down", "left", "right",
+    "home", "end", "pageup", "pagedown", "plus", "minus", "comma", "period", "slash", "backslash",
+];
+
+/// The menu items whose accelerators follow the settings.
+static ITEMS: Mutex<Vec<(ShortcutAction, MenuItem<Wry>)>> = Mutex::new(Vec::new());
+
+impl ShortcutAction {
+    /// The app menu item this shortcut triggers.
+    pub fn menu_id(self) -> &'static str {
+        match self {
+            ShortcutAction::NewEntry => "new_entry",
+            ShortcutAction::Blur => "blur",
+            ShortcutAction::QuickCapture => "quick_capture",
+            ShortcutAction::Lock => "lock",
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            ShortcutAction::NewEntry => "New Entry",
+            ShortcutAction::Blur => "Blur",
+            ShortcutAction::QuickCapture => "Quick Capture…",
+            ShortcutAction::Lock => "Lock Journal",
+        }
+    }
+}
+
+impl ShortcutSettings {
+    fn get(&self, action: ShortcutAction) -> &str {
+        match action {
+            ShortcutAction::NewEntry => &self.new_entry,
+            ShortcutAction::Blur => &self.blur,
+            ShortcutAction::QuickCapture => &self.quick_capture,
+            ShortcutAction::Lock => &self.lock,
+        }
+    }
+
+    fn set(&mut self, action: ShortcutAction, accelerator: String) {
+        match action {
+            ShortcutAction::NewEntry => self.new_entry = accelerator,
+            ShortcutAction::Blur => self.blur = accelerator,
+            ShortcutAction::QuickCapture => self.quick_capture = accelerator,
+            ShortcutAction::Lock => self.lock = accelerator,
+        }
+    }
+}
+
+fn is_function_key(key: &str) -> bool {
+    key.strip_prefix('f')
+        .and_then(|n| n.parse::<u8>().ok())
+        .is_some_and(|n| (1..=24).contains(&n))
+}
+
+fn is_key(key: &str) -> bool {
+    let single = key.len() == 1 && key.chars().all(|c| c.is_ascii_alphanumeric());
+    single || is_function_key(key) || NAMED_KEYS.contains(&key)
+}
+
+/// Lowercases `accelerator`, with its modifiers spelled one way and in one
+/// order, so two spellings of the same keys compare equal. Errors unless it's
+/// modifiers followed by a single key; only function keys may go without one.
+fn normalize(accelerator: &str) -> Result<String, String> {
+    let invalid = || format!("\"{}\" is not a valid shortcut", accelerator);
+    let lowered = accelerator.to_lowercase();
+    let mut parts: Vec<&str> = lowered.split('+').map(str::trim).collect();
+    let key = parts.pop().filter(|key| is_key(key)).ok_or_else(invalid)?;
+    let mut modifiers = parts
+        .into_iter()
+        .map(|modifier| match modifier {
+            "commandorcontrol" => Some("cmdorctrl"),
+            "command" | "super" => Some("cmd"),
+            "control" => Some("ctrl"),
+            "option" => Some("alt"),
+            _ => MODIFIERS.contains(&modifier).then_some(modifier),
+        })
+        .collect::<Option<Vec<_>>>()
+        .ok_or_else(invalid)?;
+    if modifiers.is_empty() && !is_function_key(key) {
+        return Err(invalid());
+    }
+    modifiers.sort_unstable();
+    modifiers.dedup();
+    modifiers.push(key);
+    Ok(modifiers.join("+"))
+}
+
+/// Checks `accelerator` for `action`, refusing one already used by another action.
+fn validate(shortcuts: &ShortcutSettings, action: ShortcutAction, accelerator: &str) -> Result<(), String> {
+    if accelerator.is_empty() {
+        return Ok(());
+    }
+    let wanted = normalize(accelerator)?;
+    for other in ACTIONS.into_iter().filter(|other| *other != action) {
+        let existing = shortcuts.get(other);
+        if !existing.is_empty() && normalize(existing).ok().as_deref() == Some(wanted.as_str()) {
+            return Err(format!("{} is already the shortcut for {}", accelerator, other.label().trim_end_matches('…')));
+        }
+    }
+    Ok(())
+}
+
+/// Builds the app menu item for `action` with the user's shortcut, and keeps
+/// it so later changes reach it.
+pub fn menu_item(app: &AppHandle, action: ShortcutAction) -> tauri::Result<MenuItem<Wry>> {
+    let accelerator = Settings::load().shortcuts.get(action).to_string();
+    let mut builder = MenuItemBuilder::with_id(action.menu_id(), action.label());
+    if !accelerator.is_empty() {
+        builder = builder.accelerator(accelerator);
+    }
+    let item = builder.build(app)?;
+    if let Ok(mut items) = ITEMS.lock() {
+        items.push((action, item.clone()));
+    }
+    Ok(item)
+}
+
+#[command]
+pub fn get_shortcuts() -> ShortcutSettings {
+    Settings::load().shortcuts
+}
+
+/// Remaps `action` to `accelerator`, or removes its shortcut when it's empty.
+#[command]
+pub fn set_shortcut(action: ShortcutAction, accelerator: String) -> Result<(), String> {
+    let accelerator = accelerator.trim().to_string();
+    let mut settings = Settings::load();
+    validate(&settings.shortcuts, action, &accelerator)?;
+    settings.shortcuts.set(action, accelerator.clone());
+    settings.save().map_err(|e| e.to_string())?;
+    let items = ITEMS.lock().map_err(|e| e.to_string())?;
+    for (_, item) in items.iter().filter(|(item_action, _)| *item_action == action) {
+        let accelerator = Some(accelerator.as_str()).filter(|a| !a.is_empty());
+        if let Err(e) = item.set_accelerator(accelerator) {
+            warn!("Failed to update the {} shortcut: {}", action.menu_id(), e);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(normalize("Shift+CmdOrCtrl+N").unwrap(), "cmdorctrl+shift+n");
+        assert_eq!(normalize("Command+Option+b").unwrap(), "alt+cmd+b");
+        assert_eq!(normalize("F5").unwrap(), "f5");
+        assert!(normalize("N").is_err());
+        assert!(normalize("Ctrl+").is_err());
+        assert!(normalize("Ctrl+F25").is_err());
+        assert!(normalize("Hyper+N").is_err());
+        assert!(normalize("Ctrl+NN").is_err());
+    }
+
+    #[test]
+    fn test_validate_refuses_shortcuts_in_use() {
+        let shortcuts = ShortcutSettings::default();
+        assert!(validate(&shortcuts, ShortcutAction::Lock, "CommandOrControl+N").is_err());
+        assert!(validate(&shortcuts, ShortcutAction::NewEntry, "CmdOrCtrl+N").is_ok());
+        assert!(validate(&shortcuts, ShortcutAction::Lock, "CmdOrCtrl+Shift+L").is_ok());
+        assert!(validate(&shortcuts, ShortcutAction::Blur, "").is_ok());
+    }
+}
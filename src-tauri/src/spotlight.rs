@@ -0,0 +1,338 @@
+use log::{debug, warn};
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::thread;
+use tauri::{command, AppHandle, Listener};
+
+use journal_core::keychain::KeychainManager;
+use journal_core::parse_entry_datetime;
+
+use crate::jobs::{Job, JobKind};
+use crate::settings::Settings;
+use crate::DatabaseManager;
+
+// Opt-in Spotlight search on macOS. Each entry's title and date (never its
+// body) are handed to Core Spotlight, so the entry can be found from macOS
+// search and opened from there. Locked and archived entries are left out.
+// After the first full pass only entries whose title or date changed are
+// sent again, and turning indexing off removes everything that was sent.
+
+/// Spotlight item ids are `entry:{id}`.
+const ITEM_PREFIX: &str = "entry:";
+/// Events after which the index may be out of date.
+const REFRESH_EVENTS: &[&str] = &["entries-changed", "entries-synced", "files-dropped"];
+
+static APP: OnceLock<AppHandle> = OnceLock::new();
+/// What Spotlight was last given, by entry id. `None` until the first full
+/// pass, or after indexing is turned off.
+static INDEXED: Mutex<Option<HashMap<i32, SpotlightItem>>> = Mutex::new(None);
+
+#[derive(Debug, Clone, PartialEq)]
+struct SpotlightItem {
+    id: i32,
+    title: String,
+    /// Seconds since the Unix epoch.
+    created_at: i64,
+}
+
+impl SpotlightItem {
+    fn identifier(&self) -> String {
+        format!("{}{}", ITEM_PREFIX, self.id)
+    }
+}
+
+/// The entry a Spotlight result stands for.
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+fn entry_id(identifier: &str) -> Option<i32> {
+    identifier.strip_prefix(ITEM_PREFIX)?.parse().ok()
+}
+
+fn indexable_entries(conn: &rusqlite::Connection) -> rusqlite::Result<Vec<SpotlightItem>> {
+    let mut stmt = conn.prepare("SELECT id, title, created_at FROM journal_entries WHERE locked = 0 AND archived = 0")?;
+    let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?)))?;
+    let mut items = Vec::new();
+    for row in rows {
+        let (id, title, created_at) = row?;
+        let Ok(created_at) = parse_entry_datetime(&created_at) else {
+            continue;
+        };
+        let title = title.trim();
+        items.push(SpotlightItem {
+            id,
+            title: if title.is_empty() { "Untitled" } else { title }.to_string(),
+            created_at: created_at.timestamp(),
+        });
+    }
+    Ok(items)
+}
+
+/// Items that are new or changed since `indexed`, and ids no longer there.
+fn changes(indexed: &HashMap<i32, SpotlightItem>, current: &[SpotlightItem]) -> (Vec<SpotlightItem>, Vec<i32>) {
+    let changed = current
+        .iter()
+        .filter(|item| indexed.get(&item.id) != Some(item))
+        .cloned()
+        .collect();
+    let mut removed: Vec<i32> = indexed
+        .keys()
+        .filter(|id| !current.iter().any(|item| item.id == **id))
+        .copied()
+        .collect();
+    removed.sort_unstable();
+    (changed, removed)
+}
+
+/// Brings Spotlight up to date, starting over when `full`. Returns how many
+/// entries were sent.
+fn sync(full: bool) -> Result<usize, String> {
+    if !Settings::load().spotlight.enabled || !KeychainManager::has_cached_key() {
+        return Ok(0);
+    }
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    let current = indexable_entries(&db.conn).map_err(|e| e.to_string())?;
+    let mut indexed = INDEXED.lock().map_err(|e| e.to_string())?;
+    let sent = match indexed.as_ref().filter(|_| !full) {
+        Some(previous) => {
+            let (changed, removed) = changes(previous, &current);
+            let removed: Vec<String> = removed.iter().map(|id| format!("{}{}", ITEM_PREFIX, id)).collect();
+            platform::remove(&removed)?;
+            platform::index(&changed)?;
+            changed.len()
+        }
+        None => {
+            platform::remove_all()?;
+            platform::index(&current)?;
+            current.len()
+        }
+    };
+    debug!("Sent {} entries to Spotlight", sent);
+    *indexed = Some(current.into_iter().map(|item| (item.id, item)).collect());
+    Ok(sent)
+}
+
+/// Updates Spotlight in the background, if indexing is on.
+pub fn refresh() {
+    if !Settings::load().spotlight.enabled {
+        return;
+    }
+    thread::spawn(|| {
+        if let Err(e) = sync(false) {
+            warn!("Failed to update Spotlight: {}", e);
+        }
+    });
+}
+
+/// Keeps Spotlight current and opens entries chosen from it. Called once from setup.
+pub fn start(app: &AppHandle) {
+    let _ = APP.set(app.clone());
+    for event in REFRESH_EVENTS {
+        app.listen_any(*event, |_| refresh());
+    }
+    #[cfg(target_os = "macos")]
+    platform::open_results();
+    refresh();
+}
+
+/// Indexes every entry again from scratch, as a job.
+#[command]
+pub fn rebuild_spotlight_index() -> Result<usize, String> {
+    if !Settings::load().spotlight.enabled {
+        return Err("Spotlight indexing is turned off".to_string());
+    }
+    Job::run(JobKind::Reindex, "Rebuilding the Spotlight index", |_| sync(true))
+}
+
+/// Turns Spotlight indexing on, indexing every entry, or off, removing them all.
+#[command]
+pub fn set_spotlight_indexing(enabled: bool) -> Result<usize, String> {
+    if enabled && !cfg!(target_os = "macos") {
+        return Err("Spotlight is only available on macOS".to_string());
+    }
+    let mut settings = Settings::load();
+    settings.spotlight.enabled = enabled;
+    settings.save().map_err(|e| e.to_string())?;
+    if enabled {
+        return sync(true);
+    }
+    let mut indexed = INDEXED.lock().map_err(|e| e.to_string())?;
+    platform::remove_all()?;
+    *indexed = None;
+    Ok(0)
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use log::warn;
+    use objc2::rc::Retained;
+    use objc2::runtime::{AnyObject, Bool, Imp, Sel};
+    use objc2::{sel, AllocAnyThread, MainThreadMarker};
+    use objc2_core_spotlight::{
+        CSSearchableIndex, CSSearchableItem, CSSearchableItemActionType, CSSearchableItemActivityIdentifier,
+        CSSearchableItemAttributeSet,
+    };
+    use objc2_foundation::{NSArray, NSDate, NSString, NSUserActivity};
+    use tauri::Emitter;
+
+    use super::{entry_id, SpotlightItem, APP};
+    use crate::{app_delegate, tray};
+
+    /// Groups the journal's items, so they can be removed together.
+    const DOMAIN: &str = "entries";
+
+    fn searchable_item(item: &SpotlightItem) -> Retained<CSSearchableItem> {
+        unsafe {
+            let attributes = CSSearchableItemAttributeSet::initWithItemContentType(
+                CSSearchableItemAttributeSet::alloc(),
+                &NSString::from_str("public.content"),
+            );
+            attributes.setTitle(Some(&NSString::from_str(&item.title)));
+            attributes.setContentCreationDate(Some(&NSDate::dateWithTimeIntervalSince1970(item.created_at as f64)));
+            CSSearchableItem::initWithUniqueIdentifier_domainIdentifier_attributeSet(
+                CSSearchableItem::alloc(),
+                Some(&NSString::from_str(&item.identifier())),
+                Some(&NSString::from_str(DOMAIN)),
+                &attributes,
+            )
+        }
+    }
+
+    pub fn index(items: &[SpotlightItem]) -> Result<(), String> {
+        if items.is_empty() {
+            return Ok(());
+        }
+        let items: Vec<Retained<CSSearchableItem>> = items.iter().map(searchable_item).collect();
+        unsafe {
+            CSSearchableIndex::defaultSearchableIndex()
+                .indexSearchableItems_completionHandler(&NSArray::from_retained_slice(&items), None);
+        }
+        Ok(())
+    }
+
+    pub fn remove(identifiers: &[String]) -> Result<(), String> {
+        if identifiers.is_empty() {
+            return Ok(());
+        }
+        let identifiers: Vec<Retained<NSString>> = identifiers.iter().map(|id| NSString::from_str(id)).collect();
+        unsafe {
+            CSSearchableIndex::defaultSearchableIndex().deleteSearchableItemsWithIdentifiers_completionHandler(
+                &NSArray::from_retained_slice(&identifiers),
+                None,
+            );
+        }
+        Ok(())
+    }
+
+    pub fn remove_all() -> Result<(), String> {
+        unsafe {
+            CSSearchableIndex::defaultSearchableIndex().deleteSearchableItemsWithDomainIdentifiers_completionHandler(
+                &NSArray::from_retained_slice(&[NSString::from_str(DOMAIN)]),
+                None,
+            );
+        }
+        Ok(())
+    }
+
+    /// `application:continueUserActivity:restorationHandler:` on the app
+    /// delegate, which is how a chosen Spotlight result reaches the app.
+    extern "C-unwind" fn continue_activity(
+        _delegate: &AnyObject,
+        _cmd: Sel,
+        _app: &AnyObject,
+        activity: &NSUserActivity,
+        _restoration_handler: *mut AnyObject,
+    ) -> Bool {
+        let is_search_result = unsafe { activity.activityType().isEqualToString(CSSearchableItemActionType) };
+        let identifier = unsafe { activity.userInfo() }
+            .and_then(|info| unsafe { info.objectForKey(CSSearchableItemActivityIdentifier) })
+            .and_then(|value| value.downcast::<NSString>().ok())
+            .map(|value| value.to_string());
+        let (Some(app), Some(id)) = (APP.get(), identifier.as_deref().and_then(entry_id)) else {
+            return Bool::NO;
+        };
+        if !is_search_result {
+            return Bool::NO;
+        }
+        tray::show_main(app);
+        let _ = app.emit("open-entry", id);
+        Bool::YES
+    }
+
+    pub fn open_results() {
+        let Some(mtm) = MainThreadMarker::new() else {
+            warn!("Spotlight results must be set up on the main thread");
+            return;
+        };
+        type ContinueActivity = extern "C-unwind" fn(&AnyObject, Sel, &AnyObject, &NSUserActivity, *mut AnyObject) -> Bool;
+        let imp: Imp = unsafe { std::mem::transmute::<ContinueActivity, Imp>(continue_activity) };
+        let sel = sel!(application:continueUserActivity:restorationHandler:);
+        if !unsafe { app_delegate::add_method(mtm, sel, imp, c"B@:@@@?") } {
+            warn!("Could not open entries chosen in Spotlight");
+        }
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+mod platform {
+    use super::SpotlightItem;
+
+    pub fn index(_items: &[SpotlightItem]) -> Result<(), String> {
+        Ok(())
+    }
+
+    pub fn remove(_identifiers: &[String]) -> Result<(), String> {
+        Ok(())
+    }
+
+    pub fn remove_all() -> Result<(), String> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use journal_core::insert_entry;
+
+    fn item(id: i32, title: &str) -> SpotlightItem {
+        SpotlightItem {
+            id,
+            title: title.to_string(),
+            created_at: 1_700_000_000,
+        }
+    }
+
+    #[test]
+    fn test_indexable_entries_skip_locked_and_archived() {
+        let db = DatabaseManager::open_in_memory().unwrap();
+        let kept = insert_entry(&db.conn, " ", "<p>Private</p>", "2024-03-05T12:00:00+00:00").unwrap();
+        let locked = insert_entry(&db.conn, "Locked", "", "2024-03-06T12:00:00+00:00").unwrap();
+        let archived = insert_entry(&db.conn, "Archived", "", "2024-03-07T12:00:00+00:00").unwrap();
+        db.conn
+            .execute("UPDATE journal_entries SET locked = 1 WHERE id = ?1", rusqlite::params![locked])
+            .unwrap();
+        db.conn
+            .execute("UPDATE journal_entries SET archived = 1 WHERE id = ?1", rusqlite::params![archived])
+            .unwrap();
+        let items = indexable_entries(&db.conn).unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!((items[0].id, items[0].title.as_str()), (kept, "Untitled"));
+        assert_eq!(items[0].created_at, 1_709_640_000);
+    }
+
+    #[test]
+    fn test_changes() {
+        let indexed: HashMap<i32, SpotlightItem> =
+            [item(1, "Same"), item(2, "Old title"), item(3, "Gone")].into_iter().map(|i| (i.id, i)).collect();
+        let current = [item(1, "Same"), item(2, "New title"), item(4, "Added")];
+        let (changed, removed) = changes(&indexed, &current);
+        assert_eq!(changed, vec![item(2, "New title"), item(4, "Added")]);
+        assert_eq!(removed, vec![3]);
+    }
+
+    #[test]
+    fn test_identifiers() {
+        assert_eq!(item(7, "A").identifier(), "entry:7");
+        assert_eq!(entry_id("entry:7"), Some(7));
+        assert_eq!(entry_id("entry:"), None);
+    }
+}
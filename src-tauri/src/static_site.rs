@@ -0,0 +1,363 @@
+use chrono::{Datelike, NaiveDate};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+use tauri::command;
+
+use journal_core::audit::AuditAction;
+
+use crate::export::{exported_entries, ExportedEntry};
+use crate::jobs::{Job, JobKind};
+use crate::security;
+use crate::stats::local_date;
+use crate::DatabaseManager;
+
+// The journal as a folder of plain HTML pages, to archive it or hand it to
+// someone: an index by year and month, a page per entry and a page per tag.
+// There's no script and nothing is fetched, so the folder can be opened from
+// disk or put on any static host as it is. Locked entries are left out, as in
+// the other exports.
+
+const STYLESHEET: &str = "body { max-width: 42rem; margin: 2rem auto; padding: 0 1rem; \
+font-family: Georgia, serif; line-height: 1.6; color: #222; }
+header { font-family: sans-serif; font-size: 0.9rem; margin-bottom: 2rem; }
+a { color: #2a5db0; }
+ul.entries { list-style: none; padding: 0; }
+ul.entries time, p.meta { color: #666; font-family: sans-serif; font-size: 0.85rem; }
+nav.adjacent { display: flex; justify-content: space-between; margin-top: 3rem; font-family: sans-serif; }
+blockquote { border-left: 3px solid #ccc; margin-left: 0; padding-left: 1rem; color: #555; }
+";
+const MAX_SLUG_CHARS: usize = 60;
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct HtmlExportOptions {
+    /// Heading of every page; "Journal" if empty.
+    pub title: String,
+    /// Only entries with at least one of these tags; every entry if empty.
+    pub tags: Vec<String>,
+}
+
+/// An entry and where its page goes, relative to `entries/`.
+struct EntryPage<'a> {
+    entry: &'a ExportedEntry,
+    date: Option<NaiveDate>,
+    file: String,
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// `text` lowercased with runs of anything but letters and digits turned into
+/// single hyphens, for file names; `fallback` if nothing is left.
+fn slug(text: &str, fallback: &str) -> String {
+    let mut slug = String::new();
+    for c in text.chars() {
+        if c.is_alphanumeric() {
+            slug.extend(c.to_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    let slug: String = slug.trim_end_matches('-').chars().take(MAX_SLUG_CHARS).collect();
+    match slug.trim_end_matches('-') {
+        "" => fallback.to_string(),
+        slug => slug.to_string(),
+    }
+}
+
+/// `name.html`, or `name-2.html` and so on if that's taken.
+fn unique_file(name: &str, taken: &mut HashSet<String>) -> String {
+    let mut file = format!("{}.html", name);
+    let mut n = 2;
+    while !taken.insert(file.clone()) {
+        file = format!("{}-{}.html", name, n);
+        n += 1;
+    }
+    file
+}
+
+/// A complete page; `root` leads from the page back to the site's top folder.
+fn html_page(site_title: &str, title: &str, root: &str, content: &str) -> String {
+    format!(
+        "<!DOCTYPE html>
+<html>
+<head>
+<meta charset=\"utf-8\">
+<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">
+<title>{title} · {site}</title>
+<link rel=\"stylesheet\" href=\"{root}style.css\">
+</head>
+<body>
+<header><a href=\"{root}index.html\">{site}</a> · <a href=\"{root}tags/index.html\">Tags</a></header>
+<main>
+{content}
+</main>
+</body>
+</html>
+",
+        title = escape(title),
+        site = escape(site_title),
+    )
+}
+
+/// `pages` as a list of links; `root` leads to the site's top folder.
+fn entry_list(pages: &[&EntryPage], root: &str) -> String {
+    let mut html = String::from("<ul class=\"entries\">\n");
+    for page in pages {
+        html.push_str(&format!(
+            "<li><a href=\"{}entries/{}\">{}</a> <time>{}</time></li>\n",
+            root,
+            page.file,
+            escape(&page.entry.heading()),
+            escape(&page.entry.day())
+        ));
+    }
+    html.push_str("</ul>\n");
+    html
+}
+
+/// Turns each `[[title]]` in `body` into a link to the page of the entry with
+/// that title, compared ASCII case-insensitively as the editor does. Links to
+/// entries that weren't exported are left as written.
+fn link_titles(body: &str, files: &HashMap<String, String>) -> String {
+    let mut html = String::new();
+    let mut rest = body;
+    while let Some(start) = rest.find("[[") {
+        let after = &rest[start + 2..];
+        let Some(end) = after.find("]]") else { break };
+        html.push_str(&rest[..start]);
+        let title = &after[..end];
+        match files.get(&title.trim().to_ascii_lowercase()) {
+            Some(file) => html.push_str(&format!("<a href=\"{}\">[[{}]]</a>", file, title)),
+            None => html.push_str(&rest[start..start + end + 4]),
+        }
+        rest = &after[end + 2..];
+    }
+    html.push_str(rest);
+    html
+}
+
+fn write(path: &Path, contents: &str) -> Result<(), String> {
+    fs::write(path, contents).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+/// Writes the site for every readable entry matching `options` into `dir`
+/// and returns how many entries it has. The entry pages are all made, with
+/// their progress reported through `job`, before anything is written.
+pub fn export_site(
+    conn: &rusqlite::Connection,
+    dir: &Path,
+    options: &HtmlExportOptions,
+    job: &Job,
+) -> Result<usize, String> {
+    let mut entries = exported_entries(conn).map_err(|e| e.to_string())?;
+    if !options.tags.is_empty() {
+        entries.retain(|entry| {
+            let wanted = |tag: &String| {
+                options.tags.iter().any(|wanted| wanted.trim_start_matches('#').eq_ignore_ascii_case(tag))
+            };
+            entry.tags.iter().any(wanted)
+        });
+    }
+    let site_title = match options.title.trim() {
+        "" => "Journal",
+        title => title,
+    };
+
+    let mut taken = HashSet::new();
+    let pages: Vec<EntryPage> = entries
+        .iter()
+        .map(|entry| {
+            let date = local_date(&entry.created_at);
+            let day = date
+                .map(|date| date.format("%Y-%m-%d").to_string())
+                .unwrap_or_else(|| "undated".to_string());
+            let file = unique_file(&format!("{}-{}", day, slug(&entry.title, "entry")), &mut taken);
+            EntryPage { entry, date, file }
+        })
+        .collect();
+
+    let mut files_by_title: HashMap<String, String> = HashMap::new();
+    for page in &pages {
+        let title = page.entry.title.trim();
+        if !title.is_empty() {
+            files_by_title.entry(title.to_ascii_lowercase()).or_insert_with(|| page.file.clone());
+        }
+    }
+    let mut pages_by_tag: BTreeMap<String, Vec<&EntryPage>> = BTreeMap::new();
+    for page in &pages {
+        for tag in &page.entry.tags {
+            pages_by_tag.entry(tag.to_lowercase()).or_default().push(page);
+        }
+    }
+
+    // tags/index.html lists the tags
+    let mut taken = HashSet::from(["index.html".to_string()]);
+    let tag_files: HashMap<&str, String> = pages_by_tag
+        .keys()
+        .map(|tag| (tag.as_str(), unique_file(&slug(tag, "tag"), &mut taken)))
+        .collect();
+    let tag_links = |tags: &[String]| -> String {
+        tags.iter()
+            .filter_map(|tag| {
+                let file = tag_files.get(tag.to_lowercase().as_str())?;
+                Some(format!("<a href=\"../tags/{}\">#{}</a>", file, escape(tag)))
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+
+    let mut entry_pages = Vec::with_capacity(pages.len());
+    for (i, page) in pages.iter().enumerate() {
+        let entry = page.entry;
+        job.step(i, pages.len(), Some(&entry.heading()))?;
+        let mut content = format!(
+            "<h1>{}</h1>\n<p class=\"meta\"><time>{}</time>",
+            escape(&entry.heading()),
+            escape(&entry.day())
+        );
+        if !entry.tags.is_empty() {
+            content.push_str(&format!(" · {}", tag_links(&entry.tags)));
+        }
+        content.push_str("</p>\n<article>\n");
+        content.push_str(&link_titles(&entry.body, &files_by_title));
+        content.push_str("\n</article>\n<nav class=\"adjacent\">");
+        let adjacent = |page: Option<&EntryPage>, label: &str| match page {
+            Some(page) => format!("<a href=\"{}\">{} {}</a>", page.file, label, escape(&page.entry.heading())),
+            None => "<span></span>".to_string(),
+        };
+        content.push_str(&adjacent(i.checked_sub(1).map(|i| &pages[i]), "←"));
+        content.push_str(&adjacent(pages.get(i + 1), "→"));
+        content.push_str("</nav>");
+        entry_pages.push((&page.file, html_page(site_title, &entry.heading(), "../", &content)));
+    }
+
+    fs::create_dir_all(dir.join("entries")).map_err(|e| e.to_string())?;
+    fs::create_dir_all(dir.join("tags")).map_err(|e| e.to_string())?;
+    write(&dir.join("style.css"), STYLESHEET)?;
+    for (file, html) in &entry_pages {
+        write(&dir.join("entries").join(file), html)?;
+    }
+
+    let mut by_month: BTreeMap<(i32, u32), Vec<&EntryPage>> = BTreeMap::new();
+    let mut undated = Vec::new();
+    for page in &pages {
+        match page.date {
+            Some(date) => by_month.entry((date.year(), date.month())).or_default().push(page),
+            None => undated.push(page),
+        }
+    }
+    let mut index = format!("<h1>{}</h1>\n", escape(site_title));
+    let mut year = None;
+    for ((y, _), month_pages) in &by_month {
+        if year != Some(*y) {
+            index.push_str(&format!("<h2>{}</h2>\n", y));
+            year = Some(*y);
+        }
+        let month = month_pages[0].date.map(|date| date.format("%B").to_string()).unwrap_or_default();
+        index.push_str(&format!("<h3>{}</h3>\n", month));
+        index.push_str(&entry_list(month_pages, ""));
+    }
+    if !undated.is_empty() {
+        index.push_str("<h2>Undated</h2>\n");
+        index.push_str(&entry_list(&undated, ""));
+    }
+    write(&dir.join("index.html"), &html_page(site_title, "Entries", "", &index))?;
+
+    let mut tags_index = String::from("<h1>Tags</h1>\n<ul>\n");
+    for (tag, tag_pages) in &pages_by_tag {
+        let file = &tag_files[tag.as_str()];
+        tags_index.push_str(&format!(
+            "<li><a href=\"{}\">#{}</a> ({})</li>\n",
+            file,
+            escape(tag),
+            tag_pages.len()
+        ));
+        let content = format!("<h1>#{}</h1>\n{}", escape(tag), entry_list(tag_pages, "../"));
+        write(&dir.join("tags").join(file), &html_page(site_title, &format!("#{}", tag), "../", &content))?;
+    }
+    tags_index.push_str("</ul>\n");
+    write(&dir.join("tags").join("index.html"), &html_page(site_title, "Tags", "../", &tags_index))?;
+
+    Ok(pages.len())
+}
+
+/// Writes the journal as a static website into folder `dir`, created if
+/// needed, and returns how many entries it has. Runs in the background as a job.
+#[command]
+pub async fn export_html(dir: String, options: HtmlExportOptions) -> Result<usize, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+        let label = format!("Exporting a website to {}", dir);
+        let count = Job::run(JobKind::Export, label, |job| export_site(&db.conn, Path::new(&dir), &options, job))?;
+        security::audit(AuditAction::Export, &format!("{} entries as HTML to {}", count, dir));
+        Ok(count)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use journal_core::insert_entry;
+
+    #[test]
+    fn test_slug() {
+        assert_eq!(slug("Lisbon, day 2!", "entry"), "lisbon-day-2");
+        assert_eq!(slug("  Café  ", "entry"), "café");
+        assert_eq!(slug("???", "entry"), "entry");
+    }
+
+    #[test]
+    fn test_link_titles() {
+        let files = HashMap::from([("lisbon".to_string(), "2024-03-05-lisbon.html".to_string())]);
+        assert_eq!(
+            link_titles("<p>See [[Lisbon]] and [[Porto]]</p>", &files),
+            "<p>See <a href=\"2024-03-05-lisbon.html\">[[Lisbon]]</a> and [[Porto]]</p>"
+        );
+        assert_eq!(link_titles("<p>[[open</p>", &files), "<p>[[open</p>");
+    }
+
+    #[test]
+    fn test_export_site() {
+        let db = DatabaseManager::open_in_memory().unwrap();
+        let id = insert_entry(&db.conn, "Lisbon", "<p>Trams & tiles</p>", "2024-03-05T12:00:00+00:00").unwrap();
+        insert_entry(&db.conn, "Home", "<p>Back from [[lisbon]]</p>", "2024-04-02T12:00:00+00:00").unwrap();
+        insert_entry(&db.conn, "Secret", "", "2024-04-03T12:00:00+00:00").unwrap();
+        db.conn
+            .execute("UPDATE journal_entries SET locked = 1 WHERE title = 'Secret'", [])
+            .unwrap();
+        db.conn
+            .execute("INSERT INTO entry_tags (entry_id, tag) VALUES (?1, 'travel')", rusqlite::params![id])
+            .unwrap();
+
+        let dir = std::env::temp_dir().join(format!("journal-site-{}", std::process::id()));
+        let job = Job::start(JobKind::Export, "Test");
+        assert_eq!(export_site(&db.conn, &dir, &HtmlExportOptions::default(), &job).unwrap(), 2);
+        let index = fs::read_to_string(dir.join("index.html")).unwrap();
+        assert!(index.contains("<h2>2024</h2>\n<h3>March</h3>"));
+        assert!(index.contains("<h3>April</h3>"));
+        assert!(index.contains("href=\"entries/2024-03-05-lisbon.html\""));
+        assert!(!index.contains("Secret"));
+        let home = fs::read_to_string(dir.join("entries/2024-04-02-home.html")).unwrap();
+        assert!(home.contains("<a href=\"2024-03-05-lisbon.html\">[[lisbon]]</a>"));
+        let tag = fs::read_to_string(dir.join("tags/travel.html")).unwrap();
+        assert!(tag.contains("href=\"../entries/2024-03-05-lisbon.html\""));
+        fs::remove_dir_all(&dir).unwrap();
+
+        let options = HtmlExportOptions {
+            title: "Trips".to_string(),
+            tags: vec!["#Travel".to_string()],
+        };
+        assert_eq!(export_site(&db.conn, &dir, &options, &job).unwrap(), 1);
+        assert!(fs::read_to_string(dir.join("index.html")).unwrap().contains("<h1>Trips</h1>"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
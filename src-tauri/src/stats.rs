@@ -0,0 +1,400 @@
+use chrono::{Local, NaiveDate};
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use tauri::command;
+
+use journal_core::parse_entry_datetime;
+use journal_core::text::{count_words, is_stop_word, strip_html};
+
+use crate::{DatabaseManager, DateRange, JournalEntry};
+
+const DEFAULT_LONGEST_LIMIT: u32 = 10;
+const MAX_TOP_WORDS: usize = 500;
+/// Closing markup that ends a sentence even without punctuation, like a list item.
+pub(crate) const BLOCK_ENDS: &[&str] = &[
+    "</p>", "</li>", "</h1>", "</h2>", "</h3>", "</h4>", "</h5>", "</h6>", "</blockquote>", "<br>", "<br/>",
+    "<br />",
+];
+
+#[derive(Debug, Serialize)]
+pub struct WritingStats {
+    total_entries: i64,
+    total_words: i64,
+    average_words: f64,
+    longest_entry_words: i64,
+}
+
+/// Totals computed from the stored `word_count` column, so no bodies are loaded.
+#[command]
+pub fn get_writing_stats() -> Result<WritingStats, String> {
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    db.conn
+        .query_row(
+            "SELECT COUNT(*), COALESCE(SUM(word_count), 0), COALESCE(AVG(word_count), 0.0),
+                    COALESCE(MAX(word_count), 0)
+             FROM journal_entries",
+            [],
+            |row| {
+                Ok(WritingStats {
+                    total_entries: row.get(0)?,
+                    total_words: row.get(1)?,
+                    average_words: row.get(2)?,
+                    longest_entry_words: row.get(3)?,
+                })
+            },
+        )
+        .map_err(|e| e.to_string())
+}
+
+#[derive(Debug, Serialize)]
+pub struct SentimentTrendPoint {
+    /// Calendar day (`YYYY-MM-DD`) the entries were written on.
+    date: String,
+    /// Average sentiment of the day's entries, from -1 (negative) to 1 (positive).
+    average_sentiment: f64,
+    entry_count: i64,
+}
+
+fn sentiment_trend(
+    conn: &rusqlite::Connection,
+    (start, end): (Option<i64>, Option<i64>),
+) -> rusqlite::Result<Vec<SentimentTrendPoint>> {
+    let mut stmt = conn.prepare(
+        "SELECT local_date AS day, AVG(sentiment), COUNT(*)
+         FROM journal_entries
+         WHERE sentiment IS NOT NULL AND local_date IS NOT NULL
+           AND (?1 IS NULL OR created_ms >= ?1)
+           AND (?2 IS NULL OR created_ms <= ?2)
+         GROUP BY day
+         ORDER BY day ASC",
+    )?;
+    let points = stmt
+        .query_map(rusqlite::params![start, end], |row| {
+            Ok(SentimentTrendPoint {
+                date: row.get(0)?,
+                average_sentiment: row.get(1)?,
+                entry_count: row.get(2)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(points)
+}
+
+/// Average sentiment per day within `range`, oldest first, scored when each
+/// entry was saved.
+#[command]
+pub fn get_sentiment_trend(range: DateRange) -> Result<Vec<SentimentTrendPoint>, String> {
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    sentiment_trend(&db.conn, range.millis()?).map_err(|e| e.to_string())
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct Streak {
+    /// First and last local days (`YYYY-MM-DD`) of the run.
+    pub(crate) start: String,
+    pub(crate) end: String,
+    pub(crate) days: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WritingStreak {
+    /// Consecutive days written up to today, or up to yesterday while today
+    /// can still keep the streak going.
+    pub(crate) current_days: i64,
+    pub(crate) longest_days: i64,
+    pub(crate) wrote_today: bool,
+}
+
+/// The local calendar day of a stored `created_at`.
+pub(crate) fn local_date(created_at: &str) -> Option<NaiveDate> {
+    parse_entry_datetime(created_at).ok().map(|d| d.with_timezone(&Local).date_naive())
+}
+
+/// The day `entry` was written on where it was written.
+pub(crate) fn entry_day(entry: &JournalEntry) -> Option<NaiveDate> {
+    match &entry.local_date {
+        Some(day) => day.parse().ok(),
+        None => local_date(&entry.created_at),
+    }
+}
+
+/// The longest run of consecutive days among `days`, earliest if tied.
+pub(crate) fn longest_streak(days: &BTreeSet<NaiveDate>) -> Option<Streak> {
+    let mut best: Option<(NaiveDate, NaiveDate)> = None;
+    let mut current: Option<(NaiveDate, NaiveDate)> = None;
+    let length = |(start, end): (NaiveDate, NaiveDate)| (end - start).num_days();
+    for &day in days {
+        let run = match current {
+            Some((start, end)) if end.succ_opt() == Some(day) => (start, day),
+            _ => (day, day),
+        };
+        current = Some(run);
+        if best.is_none_or(|b| length(run) > length(b)) {
+            best = Some(run);
+        }
+    }
+    best.map(|(start, end)| Streak {
+        start: start.to_string(),
+        end: end.to_string(),
+        days: (end - start).num_days() + 1,
+    })
+}
+
+fn streak_as_of(days: &BTreeSet<NaiveDate>, today: NaiveDate) -> WritingStreak {
+    let wrote_today = days.contains(&today);
+    let mut current_days = 0;
+    let mut day = if wrote_today { Some(today) } else { today.pred_opt() };
+    while let Some(d) = day.filter(|d| days.contains(d)) {
+        current_days += 1;
+        day = d.pred_opt();
+    }
+    WritingStreak {
+        current_days,
+        longest_days: longest_streak(days).map_or(0, |streak| streak.days),
+        wrote_today,
+    }
+}
+
+pub(crate) fn writing_streak(conn: &rusqlite::Connection, today: NaiveDate) -> rusqlite::Result<WritingStreak> {
+    let mut stmt = conn.prepare("SELECT DISTINCT local_date FROM journal_entries WHERE local_date IS NOT NULL")?;
+    let days = stmt
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>, _>>()?
+        .iter()
+        .filter_map(|day| day.parse().ok())
+        .collect();
+    Ok(streak_as_of(&days, today))
+}
+
+/// How many days in a row the user has written, counted in local time.
+#[command]
+pub fn get_writing_streak() -> Result<WritingStreak, String> {
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    writing_streak(&db.conn, Local::now().date_naive()).map_err(|e| e.to_string())
+}
+
+/// Entries written on `today`'s month and day in earlier years, newest first.
+fn on_this_day(conn: &rusqlite::Connection, today: NaiveDate) -> rusqlite::Result<Vec<JournalEntry>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM journal_entries
+         WHERE archived = 0 AND substr(local_date, 6) = ?1 AND local_date < ?2
+         ORDER BY local_date DESC, created_ms ASC",
+        JournalEntry::COLUMNS
+    ))?;
+    let entries = stmt
+        .query_map(rusqlite::params![today.format("%m-%d").to_string(), today.to_string()], JournalEntry::from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(entries)
+}
+
+/// Entries written on this day in earlier years, by the day they were written
+/// on where they were written.
+#[command]
+pub fn get_on_this_day() -> Result<Vec<JournalEntry>, String> {
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    on_this_day(&db.conn, Local::now().date_naive()).map_err(|e| e.to_string())
+}
+
+/// Entry summaries ordered by word count, longest first.
+#[command]
+pub fn get_longest_entries(limit: Option<u32>) -> Result<Vec<JournalEntry>, String> {
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    let mut stmt = db.conn
+        .prepare(&format!(
+            "SELECT {} FROM journal_entries ORDER BY word_count DESC, created_ms DESC LIMIT ?1",
+            JournalEntry::COLUMNS
+        ))
+        .map_err(|e| e.to_string())?;
+    let entries = stmt
+        .query_map(
+            rusqlite::params![limit.unwrap_or(DEFAULT_LONGEST_LIMIT)],
+            JournalEntry::from_row,
+        )
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(entries)
+}
+
+#[derive(Debug, Serialize)]
+pub struct WordCount {
+    word: String,
+    count: i64,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct WritingStyle {
+    sentence_count: i64,
+    average_sentence_words: f64,
+    longest_sentence_words: i64,
+    average_word_chars: f64,
+    /// Distinct words over all words, from 0 to 1; higher means a more varied vocabulary.
+    vocabulary_richness: f64,
+}
+
+/// Bodies of the unlocked entries within `range`. They stay in Rust; only
+/// the counts made from them reach the frontend.
+fn bodies_in(conn: &rusqlite::Connection, (start, end): (Option<i64>, Option<i64>)) -> rusqlite::Result<Vec<String>> {
+    let mut stmt = conn.prepare(
+        "SELECT body FROM journal_entries
+         WHERE locked = 0
+           AND (?1 IS NULL OR created_ms >= ?1)
+           AND (?2 IS NULL OR created_ms <= ?2)",
+    )?;
+    let bodies = stmt
+        .query_map(rusqlite::params![start, end], |row| row.get(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(bodies)
+}
+
+/// Lowercased words of plain text, keeping inner apostrophes ("don't").
+fn words(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !(c.is_alphanumeric() || c == '\'' || c == '’'))
+        .map(|word| word.trim_matches(|c| c == '\'' || c == '’').replace('’', "'").to_lowercase())
+        .filter(|word| !word.is_empty())
+}
+
+/// Word counts of each sentence of an HTML body. Sentences end at `.`, `!`
+/// or `?`, and at the end of a paragraph, heading or list item.
+fn sentence_lengths(body: &str) -> Vec<usize> {
+    let mut blocks = body.to_string();
+    for end in BLOCK_ENDS {
+        blocks = blocks.replace(end, "\n");
+    }
+    strip_html(&blocks)
+        .split(['.', '!', '?', '\n'])
+        .map(count_words)
+        .filter(|&words| words > 0)
+        .collect()
+}
+
+/// The `top_n` words used most in `bodies`, leaving out common words and numbers.
+fn word_frequencies(bodies: &[String], top_n: usize) -> Vec<WordCount> {
+    let mut counts: HashMap<String, i64> = HashMap::new();
+    for body in bodies {
+        for word in words(&strip_html(body)) {
+            if !is_stop_word(&word) && !word.chars().all(|c| c.is_ascii_digit()) {
+                *counts.entry(word).or_insert(0) += 1;
+            }
+        }
+    }
+    let mut ranked: Vec<WordCount> = counts.into_iter().map(|(word, count)| WordCount { word, count }).collect();
+    ranked.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.word.cmp(&b.word)));
+    ranked.truncate(top_n);
+    ranked
+}
+
+fn writing_style(bodies: &[String]) -> WritingStyle {
+    let sentences: Vec<usize> = bodies.iter().flat_map(|body| sentence_lengths(body)).collect();
+    let all_words: Vec<String> = bodies.iter().flat_map(|body| words(&strip_html(body)).collect::<Vec<_>>()).collect();
+    if sentences.is_empty() || all_words.is_empty() {
+        return WritingStyle::default();
+    }
+    let distinct = all_words.iter().collect::<HashSet<_>>().len();
+    let chars: usize = all_words.iter().map(|word| word.chars().count()).sum();
+    WritingStyle {
+        sentence_count: sentences.len() as i64,
+        average_sentence_words: sentences.iter().sum::<usize>() as f64 / sentences.len() as f64,
+        longest_sentence_words: sentences.iter().copied().max().unwrap_or(0) as i64,
+        average_word_chars: chars as f64 / all_words.len() as f64,
+        vocabulary_richness: distinct as f64 / all_words.len() as f64,
+    }
+}
+
+/// The words used most within `range`, for a "words you used most" view.
+#[command]
+pub fn get_word_frequencies(range: DateRange, top_n: usize) -> Result<Vec<WordCount>, String> {
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    let bodies = bodies_in(&db.conn, range.millis()?).map_err(|e| e.to_string())?;
+    Ok(word_frequencies(&bodies, top_n.min(MAX_TOP_WORDS)))
+}
+
+/// Sentence and word length metrics within `range`.
+#[command]
+pub fn get_writing_style(range: DateRange) -> Result<WritingStyle, String> {
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    let bodies = bodies_in(&db.conn, range.millis()?).map_err(|e| e.to_string())?;
+    Ok(writing_style(&bodies))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn test_longest_streak() {
+        let days: BTreeSet<_> = ["2024-01-01", "2024-01-02", "2024-01-05", "2024-01-06", "2024-01-07", "2024-02-01"]
+            .iter()
+            .map(|d| day(d))
+            .collect();
+        let streak = longest_streak(&days).unwrap();
+        assert_eq!((streak.start.as_str(), streak.end.as_str(), streak.days), ("2024-01-05", "2024-01-07", 3));
+        assert_eq!(longest_streak(&BTreeSet::new()), None);
+    }
+
+    #[test]
+    fn test_current_streak() {
+        let days: BTreeSet<_> = ["2024-03-01", "2024-03-03", "2024-03-04"].iter().map(|d| day(d)).collect();
+        let today = streak_as_of(&days, day("2024-03-04"));
+        assert_eq!((today.current_days, today.longest_days, today.wrote_today), (2, 2, true));
+        // Not written yet today, but the streak isn't broken until tomorrow
+        let next_day = streak_as_of(&days, day("2024-03-05"));
+        assert_eq!((next_day.current_days, next_day.wrote_today), (2, false));
+        assert_eq!(streak_as_of(&days, day("2024-03-06")).current_days, 0);
+    }
+
+    #[test]
+    fn test_local_dates() {
+        let db = DatabaseManager::open_in_memory().unwrap();
+        let insert = |title: &str, created_at: &str, local_date: &str| {
+            let id = journal_core::insert_entry(&db.conn, title, "", created_at).unwrap();
+            db.conn
+                .execute("UPDATE journal_entries SET local_date = ?1 WHERE id = ?2", rusqlite::params![local_date, id])
+                .unwrap();
+        };
+        // Written on the evening of March 4th in California, March 5th in UTC
+        insert("Evening", "2023-03-05T02:00:00+00:00", "2023-03-04");
+        insert("Lisbon", "2022-03-05T12:00:00+00:00", "2022-03-05");
+        insert("Today", "2024-03-05T12:00:00+00:00", "2024-03-05");
+        insert("Yesterday", "2024-03-04T12:00:00+00:00", "2024-03-04");
+
+        let titles = |today| -> Vec<String> {
+            on_this_day(&db.conn, day(today)).unwrap().into_iter().map(|entry| entry.title).collect()
+        };
+        assert_eq!(titles("2024-03-05"), ["Lisbon"]);
+        assert_eq!(titles("2024-03-04"), ["Evening"]);
+        assert_eq!(writing_streak(&db.conn, day("2024-03-05")).unwrap().current_days, 2);
+    }
+
+    #[test]
+    fn test_sentence_lengths() {
+        let body = "<p>Woke early. Ran <strong>five</strong> miles!</p><ul><li>eggs</li><li>more coffee</li></ul>";
+        assert_eq!(sentence_lengths(body), vec![2, 3, 1, 2]);
+        assert!(sentence_lengths("<p></p>").is_empty());
+    }
+
+    #[test]
+    fn test_word_frequencies() {
+        let bodies = vec![
+            "<p>Coffee with Ana. Coffee again, then the 5 o’clock train</p>".to_string(),
+            "<p>Train was late; more coffee</p>".to_string(),
+        ];
+        let top: Vec<_> = word_frequencies(&bodies, 2).into_iter().map(|w| (w.word, w.count)).collect();
+        assert_eq!(top, vec![("coffee".to_string(), 3), ("train".to_string(), 2)]);
+        assert!(word_frequencies(&bodies, 50).iter().any(|w| w.word == "o'clock"));
+    }
+
+    #[test]
+    fn test_writing_style() {
+        let style = writing_style(&["<p>One two three. Four five!</p><p>Six</p>".to_string()]);
+        assert_eq!(style.sentence_count, 3);
+        assert_eq!(style.average_sentence_words, 2.0);
+        assert_eq!(style.longest_sentence_words, 3);
+        assert_eq!(style.vocabulary_richness, 1.0);
+        assert_eq!(writing_style(&[]).sentence_count, 0);
+    }
+}
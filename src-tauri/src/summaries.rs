@@ -0,0 +1,312 @@
+use log::debug;
+use reqwest::blocking::Client;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::time::Duration;
+use tauri::command;
+
+use journal_core::text::strip_html;
+use journal_core::{parse_entry_datetime, timestamp_now};
+
+use crate::locks::is_locked;
+use crate::settings::{Settings, SummarySettings};
+use crate::DatabaseManager;
+
+// Optional summaries written by a language model the user runs on their own
+// machine (Ollama or llama.cpp's server). Journal text must never leave the
+// device, so the configured endpoint has to be a loopback address; anything
+// else is refused before a request is made.
+
+/// Local models can be slow, especially on the first request while loading.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+/// Keeps range prompts inside the context window of small local models.
+const MAX_PROMPT_CHARS: usize = 24_000;
+
+const ENTRY_INSTRUCTIONS: &str =
+    "Summarize this journal entry in two or three sentences, in the second person, keeping its tone.";
+const RANGE_INSTRUCTIONS: &str =
+    "Summarize these journal entries in a short paragraph: the main events, recurring themes and how the writer felt.";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SummaryProvider {
+    #[default]
+    Ollama,
+    LlamaCpp,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Summary {
+    id: i32,
+    /// Set for single-entry summaries.
+    entry_id: Option<i32>,
+    /// Set for summaries of a date range.
+    range_start: Option<String>,
+    range_end: Option<String>,
+    model: String,
+    summary: String,
+    created_at: String,
+}
+
+impl Summary {
+    const COLUMNS: &'static str = "id, entry_id, range_start, range_end, model, summary, created_at";
+
+    fn from_row(row: &rusqlite::Row) -> rusqlite::Result<Self> {
+        Ok(Summary {
+            id: row.get(0)?,
+            entry_id: row.get(1)?,
+            range_start: row.get(2)?,
+            range_end: row.get(3)?,
+            model: row.get(4)?,
+            summary: row.get(5)?,
+            created_at: row.get(6)?,
+        })
+    }
+}
+
+#[derive(Deserialize)]
+struct OllamaResponse {
+    response: String,
+}
+
+#[derive(Deserialize)]
+struct LlamaCppResponse {
+    content: String,
+}
+
+/// The configured endpoint, if it's on this machine.
+fn local_endpoint(endpoint: &str) -> Result<Url, String> {
+    let url = Url::parse(endpoint.trim()).map_err(|e| format!("Invalid model endpoint '{}': {}", endpoint, e))?;
+    let local = matches!(url.host_str(), Some("localhost" | "127.0.0.1" | "[::1]"));
+    if !local || !matches!(url.scheme(), "http" | "https") {
+        return Err(format!(
+            "The model endpoint must be on this machine (localhost), not '{}'",
+            endpoint
+        ));
+    }
+    Ok(url)
+}
+
+fn generate(settings: &SummarySettings, prompt: &str) -> Result<String, String> {
+    if !settings.enabled {
+        return Err("Summaries are not enabled".to_string());
+    }
+    let base = local_endpoint(&settings.endpoint)?;
+    let (path, request) = match settings.provider {
+        SummaryProvider::Ollama => (
+            "api/generate",
+            json!({ "model": settings.model, "prompt": prompt, "stream": false }),
+        ),
+        SummaryProvider::LlamaCpp => ("completion", json!({ "prompt": prompt, "n_predict": 512 })),
+    };
+    let url = base.join(path).map_err(|e| e.to_string())?;
+    let client = Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+        .map_err(|e| e.to_string())?;
+    let body = client
+        .post(url)
+        .body(request.to_string())
+        .header("Content-Type", "application/json")
+        .send()
+        .and_then(|response| response.error_for_status())
+        .and_then(|response| response.text())
+        .map_err(|e| format!("The local model didn't respond: {}", e))?;
+    parse_response(settings.provider, &body)
+}
+
+fn parse_response(provider: SummaryProvider, body: &str) -> Result<String, String> {
+    let text = match provider {
+        SummaryProvider::Ollama => serde_json::from_str::<OllamaResponse>(body).map(|r| r.response),
+        SummaryProvider::LlamaCpp => serde_json::from_str::<LlamaCppResponse>(body).map(|r| r.content),
+    }
+    .map_err(|e| format!("Unexpected response from the local model: {}", e))?;
+    let text = text.trim();
+    if text.is_empty() {
+        return Err("The local model returned an empty summary".to_string());
+    }
+    Ok(text.to_string())
+}
+
+/// Instructions followed by each entry as a dated section, cut off at
+/// `MAX_PROMPT_CHARS` so a long range still fits.
+fn build_prompt(instructions: &str, entries: &[(String, String, String)]) -> String {
+    let mut prompt = format!("{}\n", instructions);
+    for (title, body, created_at) in entries {
+        let date = created_at.get(..10).unwrap_or(created_at);
+        let section = format!("\n## {} ({})\n{}\n", title, date, strip_html(body).trim());
+        if prompt.chars().count() + section.chars().count() > MAX_PROMPT_CHARS {
+            break;
+        }
+        prompt.push_str(&section);
+    }
+    prompt
+}
+
+fn store(
+    conn: &rusqlite::Connection,
+    entry_id: Option<i32>,
+    range: Option<(&str, &str)>,
+    model: &str,
+    summary: &str,
+) -> rusqlite::Result<Summary> {
+    conn.execute(
+        "INSERT INTO summaries (entry_id, range_start, range_end, model, summary, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        rusqlite::params![entry_id, range.map(|r| r.0), range.map(|r| r.1), model, summary, timestamp_now()],
+    )?;
+    conn.query_row(
+        &format!("SELECT {} FROM summaries WHERE id = ?1", Summary::COLUMNS),
+        rusqlite::params![conn.last_insert_rowid()],
+        Summary::from_row,
+    )
+}
+
+fn model_name(settings: &SummarySettings) -> String {
+    match settings.provider {
+        SummaryProvider::Ollama => settings.model.clone(),
+        SummaryProvider::LlamaCpp => "llama.cpp".to_string(),
+    }
+}
+
+fn summarize_entry_blocking(id: i32) -> Result<Summary, String> {
+    let settings = Settings::load().summaries;
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    if is_locked(&db.conn, id).map_err(|e| e.to_string())? {
+        return Err(format!("Entry {} is locked and can't be summarized", id));
+    }
+    let entry: (String, String, String) = db
+        .conn
+        .query_row(
+            "SELECT title, body, created_at FROM journal_entries WHERE id = ?1",
+            rusqlite::params![id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .map_err(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => format!("Entry {} not found", id),
+            e => e.to_string(),
+        })?;
+    debug!("Summarizing entry {}", id);
+    let summary = generate(&settings, &build_prompt(ENTRY_INSTRUCTIONS, &[entry]))?;
+    store(&db.conn, Some(id), None, &model_name(&settings), &summary).map_err(|e| e.to_string())
+}
+
+fn summarize_range_blocking(start: String, end: String) -> Result<Summary, String> {
+    let (start, end) = (parse_entry_datetime(&start)?, parse_entry_datetime(&end)?);
+    let millis = (start.timestamp_millis(), end.timestamp_millis());
+    let (start, end) = (start.to_rfc3339(), end.to_rfc3339());
+    let settings = Settings::load().summaries;
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    let mut stmt = db
+        .conn
+        .prepare(
+            "SELECT title, body, created_at FROM journal_entries
+             WHERE locked = 0 AND created_ms BETWEEN ?1 AND ?2
+             ORDER BY created_ms ASC, id ASC",
+        )
+        .map_err(|e| e.to_string())?;
+    let entries = stmt
+        .query_map(rusqlite::params![millis.0, millis.1], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    if entries.is_empty() {
+        return Err("There are no entries to summarize in that range".to_string());
+    }
+    debug!("Summarizing {} entries", entries.len());
+    let summary = generate(&settings, &build_prompt(RANGE_INSTRUCTIONS, &entries))?;
+    store(&db.conn, None, Some((&start, &end)), &model_name(&settings), &summary).map_err(|e| e.to_string())
+}
+
+/// Summarizes one entry with the local model and stores the result.
+#[command]
+pub async fn summarize_entry(id: i32) -> Result<Summary, String> {
+    tauri::async_runtime::spawn_blocking(move || summarize_entry_blocking(id))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+/// Summarizes the unlocked entries written between `start` and `end`.
+#[command]
+pub async fn summarize_range(start: String, end: String) -> Result<Summary, String> {
+    tauri::async_runtime::spawn_blocking(move || summarize_range_blocking(start, end))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+/// Stored summaries, newest first; only those of `entry_id` when given.
+#[command]
+pub fn get_summaries(entry_id: Option<i32>) -> Result<Vec<Summary>, String> {
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    let mut stmt = db
+        .conn
+        .prepare(&format!(
+            "SELECT {} FROM summaries WHERE ?1 IS NULL OR entry_id = ?1 ORDER BY id DESC",
+            Summary::COLUMNS
+        ))
+        .map_err(|e| e.to_string())?;
+    let summaries = stmt
+        .query_map(rusqlite::params![entry_id], Summary::from_row)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(summaries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use journal_core::insert_entry;
+
+    #[test]
+    fn test_only_local_endpoints_allowed() {
+        assert!(local_endpoint("http://localhost:11434").is_ok());
+        assert!(local_endpoint("http://127.0.0.1:8080/").is_ok());
+        assert!(local_endpoint("http://[::1]:11434").is_ok());
+        assert!(local_endpoint("https://api.example.com").is_err());
+        assert!(local_endpoint("http://localhost.example.com").is_err());
+        assert!(local_endpoint("file:///etc/passwd").is_err());
+        assert!(local_endpoint("not a url").is_err());
+    }
+
+    #[test]
+    fn test_generate_requires_opt_in() {
+        let settings = SummarySettings::default();
+        assert_eq!(generate(&settings, "hi"), Err("Summaries are not enabled".to_string()));
+        let remote = SummarySettings { enabled: true, endpoint: "https://llm.example.com".to_string(), ..settings };
+        assert!(generate(&remote, "hi").is_err());
+    }
+
+    #[test]
+    fn test_parse_response() {
+        let ollama = r#"{"model": "llama3.2", "response": " A calm day. ", "done": true}"#;
+        assert_eq!(parse_response(SummaryProvider::Ollama, ollama).unwrap(), "A calm day.");
+        let llama_cpp = r#"{"content": "Busy week.", "stop": true}"#;
+        assert_eq!(parse_response(SummaryProvider::LlamaCpp, llama_cpp).unwrap(), "Busy week.");
+        assert!(parse_response(SummaryProvider::Ollama, r#"{"response": "  "}"#).is_err());
+        assert!(parse_response(SummaryProvider::LlamaCpp, ollama).is_err());
+    }
+
+    #[test]
+    fn test_build_prompt_stops_at_limit() {
+        let short = ("Monday".to_string(), "<p>Rain</p>".to_string(), "2024-05-06T08:00:00+00:00".to_string());
+        let long = ("Tuesday".to_string(), "x".repeat(MAX_PROMPT_CHARS), "2024-05-07T08:00:00+00:00".to_string());
+        let prompt = build_prompt("Summarize.", &[short, long]);
+        assert!(prompt.contains("## Monday (2024-05-06)\nRain"));
+        assert!(!prompt.contains("Tuesday"));
+    }
+
+    #[test]
+    fn test_store_summary() {
+        let db = DatabaseManager::open_in_memory().unwrap();
+        let id = insert_entry(&db.conn, "Monday", "<p>Rain</p>", &timestamp_now()).unwrap();
+        let summary = store(&db.conn, Some(id), None, "llama3.2", "A rainy Monday.").unwrap();
+        assert_eq!((summary.entry_id, summary.range_start), (Some(id), None));
+        db.conn
+            .execute("DELETE FROM journal_entries WHERE id = ?1", rusqlite::params![id])
+            .unwrap();
+        let remaining: i64 = db.conn.query_row("SELECT COUNT(*) FROM summaries", [], |row| row.get(0)).unwrap();
+        assert_eq!(remaining, 0);
+    }
+}
@@ -0,0 +1,886 @@
+use log::{debug, error, info, warn};
+use rusqlite::OptionalExtension;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::thread;
+use std::time::Duration;
+use tauri::{command, AppHandle, Emitter};
+use uuid::Uuid;
+
+use journal_core::keychain::KeychainManager;
+use journal_core::links::update_links;
+use journal_core::people::update_people;
+use journal_core::sentiment::body_sentiment;
+use journal_core::text::body_word_count;
+use journal_core::{created_ms_for, local_date_for};
+
+use crate::blob_store::{BlobStore, Remote};
+use crate::conflicts;
+use crate::crypto::{self, Sealed};
+use crate::git_sync;
+use crate::settings::{LimitSettings, Settings, SyncSettings};
+use crate::tags::{normalize_tags, replace_tags, tags_for_entry};
+use crate::validation::clean_entry;
+use crate::DatabaseManager;
+
+// End-to-end encrypted sync between devices.
+//
+// Each device appends numbered changesets to a shared blob store as
+// `<device_id>.<seq>.changeset` and never rewrites them, so no backend ever
+// has to merge a file. A changeset holds the entries and tombstones this
+// device changed since its last push, sealed with a passphrase the user's
+// devices share; the store only ever sees ciphertext.
+//
+// Every change carries a Lamport timestamp (the `lamport` column, kept by the
+// schema triggers) and the id of the device that made it. The higher
+// (lamport, device) pair wins, so all devices settle on the same version
+// whatever order they see changesets in, without trusting wall clocks.
+//
+// Locked entries never leave this device and are never overwritten by a
+// remote copy: their plaintext is exactly what the lock is meant to hide.
+
+pub const FORMAT_VERSION: u32 = 1;
+const CHANGESET_EXTENSION: &str = ".changeset";
+pub(crate) const PASSPHRASE_ACCOUNT: &str = "journal_sync_passphrase";
+pub(crate) const CREDENTIAL_ACCOUNT: &str = "journal_sync_credential";
+const MIN_PASSPHRASE_CHARS: usize = 8;
+const RECONCILER_START_DELAY: Duration = Duration::from_secs(60);
+const RECONCILER_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EntryRecord {
+    pub uuid: String,
+    pub lamport: i64,
+    pub origin: String,
+    pub title: String,
+    pub body: String,
+    pub created_at: String,
+    pub updated_at: String,
+    pub mood_score: Option<i64>,
+    pub mood_emoji: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// The day it was written on where it was written; missing from devices
+    /// that predate it, which leaves the receiving device to work it out.
+    #[serde(default)]
+    pub local_date: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Tombstone {
+    pub uuid: String,
+    pub lamport: i64,
+    pub origin: String,
+    pub deleted_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Changeset {
+    pub format_version: u32,
+    pub device_id: String,
+    pub seq: u64,
+    pub entries: Vec<EntryRecord>,
+    pub tombstones: Vec<Tombstone>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize)]
+pub struct SyncReport {
+    pub inserted: usize,
+    pub updated: usize,
+    pub deleted: usize,
+    pub skipped: usize,
+    /// Local changes uploaded in this run.
+    pub pushed: usize,
+    /// Entries edited on both sides, now waiting in `list_conflicts`.
+    pub conflicts: usize,
+    /// Remote entries refused by the limits in Settings, and not stored.
+    pub rejected: usize,
+}
+
+impl SyncReport {
+    pub fn add(&mut self, other: SyncReport) {
+        self.inserted += other.inserted;
+        self.updated += other.updated;
+        self.deleted += other.deleted;
+        self.skipped += other.skipped;
+        self.pushed += other.pushed;
+        self.conflicts += other.conflicts;
+        self.rejected += other.rejected;
+    }
+
+    /// Whether merging changed anything the open window should reload.
+    pub fn changed(&self) -> bool {
+        self.inserted + self.updated + self.deleted > 0
+    }
+}
+
+fn changeset_name(device_id: &str, seq: u64) -> String {
+    format!("{}.{:012}{}", device_id, seq, CHANGESET_EXTENSION)
+}
+
+/// Splits `<device_id>.<seq>.changeset`; anything else in the store is ignored.
+fn parse_changeset_name(name: &str) -> Option<(&str, u64)> {
+    let (device_id, seq) = name.strip_suffix(CHANGESET_EXTENSION)?.rsplit_once('.')?;
+    if device_id.is_empty() || seq.is_empty() || !seq.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    Some((device_id, seq.parse().ok()?))
+}
+
+/// Changeset sequence numbers per device, in order.
+fn changesets_by_device(names: &[String]) -> BTreeMap<String, Vec<u64>> {
+    let mut devices: BTreeMap<String, Vec<u64>> = BTreeMap::new();
+    for (device_id, seq) in names.iter().filter_map(|name| parse_changeset_name(name)) {
+        devices.entry(device_id.to_string()).or_default().push(seq);
+    }
+    for seqs in devices.values_mut() {
+        seqs.sort_unstable();
+        seqs.dedup();
+    }
+    devices
+}
+
+/// Lamport order with the device id as tie-break: true if `a` supersedes `b`.
+fn supersedes(a: (i64, &str), b: (i64, &str)) -> bool {
+    a > b
+}
+
+fn seal_changeset(passphrase: &str, changeset: &Changeset) -> Result<Vec<u8>, String> {
+    let json = serde_json::to_vec(changeset).map_err(|e| e.to_string())?;
+    Ok(crypto::seal(passphrase, &json)?.to_bytes())
+}
+
+fn open_changeset(passphrase: &str, bytes: &[u8]) -> Result<Changeset, String> {
+    let plaintext = crypto::open(passphrase, &Sealed::from_bytes(bytes)?)?;
+    serde_json::from_slice(&plaintext).map_err(|e| format!("Invalid sync data: {}", e))
+}
+
+/// Changes with a clock value above `since`, plus the clock they were read at.
+/// With `local_only`, just the ones made on this device.
+fn collect_changes(
+    conn: &rusqlite::Connection,
+    device_id: &str,
+    since: i64,
+    local_only: bool,
+) -> rusqlite::Result<(Vec<EntryRecord>, Vec<Tombstone>, i64)> {
+    let clock: i64 = conn.query_row("SELECT value FROM sync_clock WHERE id = 1", [], |row| row.get(0))?;
+    let rows = conn
+        .prepare(
+            "SELECT id, uuid, lamport, COALESCE(origin, ?3), title, body, created_at, updated_at, mood_score,
+                    mood_emoji, local_date
+             FROM journal_entries
+             WHERE (?2 = 0 OR origin IS NULL) AND locked = 0 AND uuid IS NOT NULL AND lamport > ?1
+             ORDER BY lamport",
+        )?
+        .query_map(rusqlite::params![since, local_only, device_id], |row| {
+            Ok((
+                row.get::<_, i32>(0)?,
+                EntryRecord {
+                    uuid: row.get(1)?,
+                    lamport: row.get(2)?,
+                    origin: row.get(3)?,
+                    title: row.get(4)?,
+                    body: row.get(5)?,
+                    created_at: row.get(6)?,
+                    updated_at: row.get::<_, Option<String>>(7)?.unwrap_or_default(),
+                    mood_score: row.get(8)?,
+                    mood_emoji: row.get(9)?,
+                    tags: Vec::new(),
+                    local_date: row.get(10)?,
+                },
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut entries = Vec::with_capacity(rows.len());
+    for (id, mut record) in rows {
+        record.tags = tags_for_entry(conn, id)?;
+        entries.push(record);
+    }
+    let tombstones = conn
+        .prepare(
+            "SELECT uuid, lamport, COALESCE(origin, ?3), deleted_at FROM deleted_entries
+             WHERE (?2 = 0 OR origin IS NULL) AND lamport > ?1
+             ORDER BY lamport",
+        )?
+        .query_map(rusqlite::params![since, local_only, device_id], |row| {
+            Ok(Tombstone {
+                uuid: row.get(0)?,
+                lamport: row.get(1)?,
+                origin: row.get(2)?,
+                deleted_at: row.get(3)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok((entries, tombstones, clock))
+}
+
+/// Everything this device knows, whichever device made it, as one changeset
+/// for a peer that syncs directly rather than through a store.
+pub fn full_state(conn: &rusqlite::Connection, device_id: &str) -> rusqlite::Result<Changeset> {
+    let (entries, tombstones, _) = collect_changes(conn, device_id, 0, false)?;
+    Ok(Changeset {
+        format_version: FORMAT_VERSION,
+        device_id: device_id.to_string(),
+        seq: 0,
+        entries,
+        tombstones,
+    })
+}
+
+/// The local version of an entry or tombstone; `origin` is `NULL` for changes
+/// made on this device.
+fn local_version(
+    conn: &rusqlite::Connection,
+    sql: &str,
+    uuid: &str,
+    device_id: &str,
+) -> rusqlite::Result<Option<(i64, String)>> {
+    conn.query_row(sql, rusqlite::params![uuid], |row| {
+        Ok((row.get(0)?, row.get::<_, Option<String>>(1)?.unwrap_or_else(|| device_id.to_string())))
+    })
+    .optional()
+}
+
+/// Applies a remote entry, cleaned like a local save and refused if it's over
+/// `limits`. Tags are written before the row so the explicit lamport set last
+/// isn't replaced by the tag triggers' local stamp.
+fn apply_entry(
+    conn: &rusqlite::Connection,
+    record: &EntryRecord,
+    device_id: &str,
+    limits: &LimitSettings,
+) -> Result<SyncReport, String> {
+    let mut report = SyncReport::default();
+    let (title, body) = match clean_entry(&record.title, &record.body, limits) {
+        Ok(cleaned) => cleaned,
+        Err(e) => {
+            warn!("Refusing synced entry {}: {}", record.uuid, e);
+            report.rejected += 1;
+            return Ok(report);
+        }
+    };
+    let record = &EntryRecord { title, body, ..record.clone() };
+    let remote = (record.lamport, record.origin.as_str());
+    let tombstone = local_version(
+        conn,
+        "SELECT lamport, origin FROM deleted_entries WHERE uuid = ?1",
+        &record.uuid,
+        device_id,
+    )
+    .map_err(|e| e.to_string())?;
+    if let Some((lamport, origin)) = &tombstone {
+        if !supersedes(remote, (*lamport, origin)) {
+            report.skipped += 1;
+            return Ok(report);
+        }
+    }
+    let local: Option<(i32, i64, Option<String>, bool)> = conn
+        .query_row(
+            "SELECT id, lamport, origin, locked FROM journal_entries WHERE uuid = ?1",
+            rusqlite::params![record.uuid],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+    let tags = normalize_tags(record.tags.clone())?;
+
+    let id = match local {
+        None => {
+            // Inserted at lamport 0 so the update below visibly changes the
+            // version and the touch trigger leaves it alone
+            conn.execute(
+                "INSERT INTO journal_entries (title, body, created_at, word_count, uuid, lamport, origin)
+                 VALUES (?1, ?2, ?3, ?4, ?5, 0, ?6)",
+                rusqlite::params![
+                    record.title,
+                    record.body,
+                    record.created_at,
+                    body_word_count(&record.body),
+                    record.uuid,
+                    record.origin,
+                ],
+            )
+            .map_err(|e| e.to_string())?;
+            // The edit outranks the delete it raced with, so the tombstone goes
+            conn.execute("DELETE FROM deleted_entries WHERE uuid = ?1", rusqlite::params![record.uuid])
+                .map_err(|e| e.to_string())?;
+            report.inserted += 1;
+            conn.last_insert_rowid() as i32
+        }
+        Some((_, _, _, true)) => {
+            report.skipped += 1;
+            return Ok(report);
+        }
+        Some((id, lamport, origin, false)) => {
+            let origin = origin.unwrap_or_else(|| device_id.to_string());
+            let remote_wins = supersedes(remote, (lamport, &origin));
+            if conflicts::detect(conn, id, record, device_id, remote_wins).map_err(|e| e.to_string())? {
+                report.conflicts += 1;
+            }
+            if !remote_wins {
+                report.skipped += 1;
+                return Ok(report);
+            }
+            report.updated += 1;
+            id
+        }
+    };
+
+    replace_tags(conn, id, &tags).map_err(|e| e.to_string())?;
+    let local_date = record.local_date.clone().or_else(|| local_date_for(&record.created_at));
+    conn.execute(
+        "UPDATE journal_entries
+         SET title = ?1, body = ?2, created_at = ?3, word_count = ?4, sentiment = ?5,
+             mood_score = ?6, mood_emoji = ?7, updated_at = ?8, lamport = ?9, origin = ?10, local_date = ?11,
+             created_ms = ?12
+         WHERE id = ?13",
+        rusqlite::params![
+            record.title,
+            record.body,
+            record.created_at,
+            body_word_count(&record.body),
+            body_sentiment(&record.body),
+            record.mood_score,
+            record.mood_emoji,
+            record.updated_at,
+            record.lamport,
+            record.origin,
+            local_date,
+            created_ms_for(&record.created_at),
+            id,
+        ],
+    )
+    .map_err(|e| e.to_string())?;
+    update_links(conn, id, &record.body).map_err(|e| e.to_string())?;
+    update_people(conn, id, &record.body).map_err(|e| e.to_string())?;
+    conflicts::record_shared(conn, record).map_err(|e| e.to_string())?;
+    Ok(report)
+}
+
+fn apply_tombstone(conn: &rusqlite::Connection, tombstone: &Tombstone, device_id: &str) -> Result<SyncReport, String> {
+    let mut report = SyncReport::default();
+    let remote = (tombstone.lamport, tombstone.origin.as_str());
+    let local: Option<(i32, i64, Option<String>, bool)> = conn
+        .query_row(
+            "SELECT id, lamport, origin, locked FROM journal_entries WHERE uuid = ?1",
+            rusqlite::params![tombstone.uuid],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+    match local {
+        Some((id, lamport, origin, locked)) => {
+            let origin = origin.unwrap_or_else(|| device_id.to_string());
+            if locked || !supersedes(remote, (lamport, &origin)) {
+                report.skipped += 1;
+                return Ok(report);
+            }
+            conn.execute("DELETE FROM journal_entries WHERE id = ?1", rusqlite::params![id])
+                .map_err(|e| e.to_string())?;
+            report.deleted += 1;
+        }
+        None => {
+            let existing = local_version(
+                conn,
+                "SELECT lamport, origin FROM deleted_entries WHERE uuid = ?1",
+                &tombstone.uuid,
+                device_id,
+            )
+            .map_err(|e| e.to_string())?;
+            if let Some((lamport, origin)) = &existing {
+                if !supersedes(remote, (*lamport, origin)) {
+                    report.skipped += 1;
+                    return Ok(report);
+                }
+            }
+        }
+    }
+    // Replaces the local stamp the delete trigger just wrote, so the tombstone
+    // isn't pushed back out as if this device had made it
+    conn.execute(
+        "INSERT OR REPLACE INTO deleted_entries (uuid, deleted_at, lamport, origin) VALUES (?1, ?2, ?3, ?4)",
+        rusqlite::params![tombstone.uuid, tombstone.deleted_at, tombstone.lamport, tombstone.origin],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(report)
+}
+
+fn apply_changeset(
+    tx: &rusqlite::Connection,
+    device_id: &str,
+    changeset: &Changeset,
+    limits: &LimitSettings,
+) -> Result<SyncReport, String> {
+    if changeset.format_version > FORMAT_VERSION {
+        return Err(format!(
+            "Sync data from device {} needs a newer version of the app",
+            changeset.device_id
+        ));
+    }
+    debug!(
+        "Merging changeset {} from device {}: {} entries, {} tombstones",
+        changeset.seq,
+        changeset.device_id,
+        changeset.entries.len(),
+        changeset.tombstones.len()
+    );
+    let mut report = SyncReport::default();
+    for record in &changeset.entries {
+        report.add(apply_entry(tx, record, device_id, limits)?);
+    }
+    for tombstone in &changeset.tombstones {
+        report.add(apply_tombstone(tx, tombstone, device_id)?);
+    }
+    // Lamport rule: the local clock moves past everything it has seen
+    let max_lamport = changeset
+        .entries
+        .iter()
+        .map(|record| record.lamport)
+        .chain(changeset.tombstones.iter().map(|tombstone| tombstone.lamport))
+        .max()
+        .unwrap_or(0);
+    tx.execute(
+        "UPDATE sync_clock SET value = MAX(value, ?1) WHERE id = 1",
+        rusqlite::params![max_lamport],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(report)
+}
+
+/// Merges a peer's changeset in one transaction.
+pub fn merge_state(
+    conn: &rusqlite::Connection,
+    device_id: &str,
+    changeset: &Changeset,
+    limits: &LimitSettings,
+) -> Result<SyncReport, String> {
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    let report = apply_changeset(&tx, device_id, changeset, limits)?;
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(report)
+}
+
+/// Merges a changeset from the store in one transaction, recording it as
+/// applied so it isn't downloaded again.
+fn merge_changeset(
+    conn: &rusqlite::Connection,
+    remote_id: &str,
+    device_id: &str,
+    changeset: &Changeset,
+    limits: &LimitSettings,
+) -> Result<SyncReport, String> {
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    let report = apply_changeset(&tx, device_id, changeset, limits)?;
+    tx.execute(
+        "INSERT OR REPLACE INTO sync_cursors (remote, device_id, last_seq) VALUES (?1, ?2, ?3)",
+        rusqlite::params![remote_id, changeset.device_id, changeset.seq as i64],
+    )
+    .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(report)
+}
+
+fn cursor(conn: &rusqlite::Connection, remote_id: &str, device_id: &str) -> rusqlite::Result<u64> {
+    conn.query_row(
+        "SELECT last_seq FROM sync_cursors WHERE remote = ?1 AND device_id = ?2",
+        rusqlite::params![remote_id, device_id],
+        |row| row.get::<_, i64>(0),
+    )
+    .optional()
+    .map(|seq| seq.unwrap_or(0).max(0) as u64)
+}
+
+/// Pulls every unseen changeset from the other devices, then pushes this
+/// device's own changes as the next changeset in its sequence.
+pub fn sync_with(
+    conn: &rusqlite::Connection,
+    store: &dyn BlobStore,
+    remote_id: &str,
+    device_id: &str,
+    passphrase: &str,
+    limits: &LimitSettings,
+) -> Result<SyncReport, String> {
+    let devices = changesets_by_device(&store.list()?);
+    let mut report = SyncReport::default();
+    for (peer, seqs) in devices.iter().filter(|(peer, _)| peer.as_str() != device_id) {
+        let seen = cursor(conn, remote_id, peer).map_err(|e| e.to_string())?;
+        for (expected, &seq) in (seen + 1..).zip(seqs.iter().filter(|&&seq| seq > seen)) {
+            // A gap means a changeset is still uploading; pick up from here next time
+            if seq != expected {
+                break;
+            }
+            let changeset = open_changeset(passphrase, &store.get(&changeset_name(peer, seq))?)?;
+            report.add(merge_changeset(conn, remote_id, device_id, &changeset, limits)?);
+        }
+    }
+
+    let pushed: i64 = conn
+        .query_row(
+            "SELECT lamport FROM sync_pushes WHERE remote = ?1",
+            rusqlite::params![remote_id],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?
+        .unwrap_or(0);
+    let (entries, tombstones, clock) = collect_changes(conn, device_id, pushed, true).map_err(|e| e.to_string())?;
+    if !entries.is_empty() || !tombstones.is_empty() {
+        // Numbering from what's already in the store means a push that failed
+        // after uploading is simply repeated under the next number
+        let seq = devices.get(device_id).and_then(|seqs| seqs.last()).copied().unwrap_or(0) + 1;
+        report.pushed = entries.len() + tombstones.len();
+        let changeset = Changeset {
+            format_version: FORMAT_VERSION,
+            device_id: device_id.to_string(),
+            seq,
+            entries,
+            tombstones,
+        };
+        store.put(&changeset_name(device_id, seq), &seal_changeset(passphrase, &changeset)?)?;
+        conflicts::record_all_shared(conn, &changeset.entries)?;
+    }
+    conn.execute(
+        "INSERT OR REPLACE INTO sync_pushes (remote, lamport) VALUES (?1, ?2)",
+        rusqlite::params![remote_id, clock],
+    )
+    .map_err(|e| e.to_string())?;
+    debug!("Sync with {} finished: {:?}", remote_id, report);
+    Ok(report)
+}
+
+/// Gives this device its sync identity the first time any kind of sync is set up.
+pub fn ensure_device_id(settings: &mut SyncSettings) {
+    if settings.device_id.is_empty() {
+        settings.device_id = Uuid::new_v4().to_string();
+    }
+}
+
+fn stored_secret(account: &str) -> Result<Option<String>, String> {
+    KeychainManager::get_secret(account).map_err(|e| e.to_string())
+}
+
+fn sync_configured(settings: &Settings) -> Result<SyncReport, String> {
+    let remote = settings
+        .sync
+        .remote
+        .as_ref()
+        .ok_or_else(|| "No sync location is configured".to_string())?;
+    let passphrase = stored_secret(PASSPHRASE_ACCOUNT)?
+        .ok_or_else(|| "Sync has no passphrase; set it up again".to_string())?;
+    let store = remote.open(stored_secret(CREDENTIAL_ACCOUNT)?)?;
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    sync_with(
+        &db.conn,
+        store.as_ref(),
+        &remote.id(),
+        &settings.sync.device_id,
+        &passphrase,
+        &settings.limits,
+    )
+}
+
+/// Sets up sync against `remote` and runs a first sync. If other devices
+/// already sync there, the passphrase must open their changesets, so a typo
+/// can't split the journal in two.
+fn configure(remote: Remote, passphrase: String, credential: Option<String>) -> Result<SyncReport, String> {
+    if passphrase.chars().count() < MIN_PASSPHRASE_CHARS {
+        return Err(format!("Passphrase must be at least {} characters", MIN_PASSPHRASE_CHARS));
+    }
+    let store = remote.open(credential.clone())?;
+    let mut settings = Settings::load();
+    ensure_device_id(&mut settings.sync);
+    let names = store.list()?;
+    let existing = names
+        .iter()
+        .find(|name| parse_changeset_name(name).is_some_and(|(device, _)| device != settings.sync.device_id));
+    if let Some(name) = existing {
+        open_changeset(&passphrase, &store.get(name)?)
+            .map_err(|_| "Passphrase doesn't match the one used by your other devices".to_string())?;
+    }
+    info!("Enabling sync with {}", remote.id());
+    KeychainManager::store_secret(PASSPHRASE_ACCOUNT, &passphrase).map_err(|e| e.to_string())?;
+    match (&credential, remote.needs_credential()) {
+        (Some(credential), true) => KeychainManager::store_secret(CREDENTIAL_ACCOUNT, credential),
+        _ => KeychainManager::delete_secret(CREDENTIAL_ACCOUNT),
+    }
+    .map_err(|e| e.to_string())?;
+    settings.sync.enabled = true;
+    settings.sync.remote = Some(remote);
+    settings.save().map_err(|e| e.to_string())?;
+    sync_configured(&settings)
+}
+
+// Network syncs can take a while, so these run off the main thread.
+
+/// Turns on sync with a folder, WebDAV server or S3 bucket. `credential` is
+/// the WebDAV password or S3 secret key.
+#[command]
+pub async fn configure_sync(
+    remote: Remote,
+    passphrase: String,
+    credential: Option<String>,
+) -> Result<SyncReport, String> {
+    tauri::async_runtime::spawn_blocking(move || configure(remote, passphrase, credential))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+/// Stops syncing. Changesets already uploaded stay for the other devices.
+#[command]
+pub fn disable_sync() -> Result<(), String> {
+    let mut settings = Settings::load();
+    settings.sync.enabled = false;
+    settings.save().map_err(|e| e.to_string())?;
+    KeychainManager::delete_secret(PASSPHRASE_ACCOUNT).map_err(|e| e.to_string())?;
+    KeychainManager::delete_secret(CREDENTIAL_ACCOUNT).map_err(|e| e.to_string())
+}
+
+/// Syncs with the configured remote right away.
+#[command]
+pub async fn sync_now() -> Result<SyncReport, String> {
+    tauri::async_runtime::spawn_blocking(|| {
+        let settings = Settings::load();
+        if !settings.sync.enabled {
+            return Err("Sync is not enabled".to_string());
+        }
+        sync_configured(&settings)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Starts the background reconciler, which also pulls and pushes the Git
+/// mirror when it has a remote. Like the backup scheduler it waits for the
+/// database key to be unlocked, and it emits `entries-synced` when a merge
+/// changed anything so the open window can reload.
+pub fn start_reconciler(app: AppHandle) {
+    thread::spawn(move || {
+        thread::sleep(RECONCILER_START_DELAY);
+        loop {
+            let settings = Settings::load();
+            if settings.sync.enabled && KeychainManager::has_cached_key() {
+                match sync_configured(&settings) {
+                    Ok(report) if report.changed() => {
+                        let _ = app.emit("entries-synced", report);
+                    }
+                    Ok(_) => {}
+                    Err(e) => error!("Sync failed: {}", e),
+                }
+            } else if settings.sync.enabled {
+                warn!("Skipping sync until the journal is unlocked");
+            }
+            if settings.git.enabled && settings.git.remote_url.is_some() && KeychainManager::has_cached_key() {
+                match git_sync::sync_configured(&settings) {
+                    Ok(report) if report.changed() => {
+                        let _ = app.emit("entries-synced", report);
+                    }
+                    Ok(_) => {}
+                    Err(e) => error!("Git sync failed: {}", e),
+                }
+            }
+            thread::sleep(RECONCILER_INTERVAL);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_changeset_name() {
+        let name = changeset_name("3f1c-device", 7);
+        assert_eq!(name, "3f1c-device.000000000007.changeset");
+        assert_eq!(parse_changeset_name(&name), Some(("3f1c-device", 7)));
+        assert_eq!(parse_changeset_name("device.000000000007 2.changeset"), None);
+        assert_eq!(parse_changeset_name("device.changeset"), None);
+        assert_eq!(parse_changeset_name("notes.txt"), None);
+    }
+
+    #[test]
+    fn test_changesets_by_device_sorts_and_dedups() {
+        let names: Vec<String> = ["b.000000000002.changeset", "a.000000000001.changeset", "b.000000000001.changeset", "b.000000000002.changeset"]
+            .iter()
+            .map(|name| name.to_string())
+            .collect();
+        let devices = changesets_by_device(&names);
+        assert_eq!(devices["a"], vec![1]);
+        assert_eq!(devices["b"], vec![1, 2]);
+    }
+
+    #[test]
+    fn test_supersedes_breaks_ties_by_device() {
+        assert!(supersedes((5, "a"), (4, "z")));
+        assert!(supersedes((5, "b"), (5, "a")));
+        assert!(!supersedes((5, "a"), (5, "a")));
+    }
+
+    #[test]
+    fn test_changeset_round_trip() {
+        let changeset = Changeset {
+            format_version: FORMAT_VERSION,
+            device_id: "a".to_string(),
+            seq: 1,
+            entries: Vec::new(),
+            tombstones: vec![Tombstone {
+                uuid: "u".to_string(),
+                lamport: 3,
+                origin: "a".to_string(),
+                deleted_at: "2024-05-01T10:00:00.000Z".to_string(),
+            }],
+        };
+        let sealed = seal_changeset("correct horse battery", &changeset).unwrap();
+        let opened = open_changeset("correct horse battery", &sealed).unwrap();
+        assert_eq!(opened.tombstones, changeset.tombstones);
+        assert!(open_changeset("wrong passphrase", &sealed).is_err());
+    }
+
+    fn record(uuid: &str, lamport: i64, origin: &str, body: &str) -> EntryRecord {
+        EntryRecord {
+            uuid: uuid.to_string(),
+            lamport,
+            origin: origin.to_string(),
+            title: "Monday".to_string(),
+            body: body.to_string(),
+            created_at: "2024-03-05T12:00:00+00:00".to_string(),
+            updated_at: "2024-03-05T12:00:00+00:00".to_string(),
+            mood_score: None,
+            mood_emoji: None,
+            tags: Vec::new(),
+            local_date: None,
+        }
+    }
+
+    fn changeset(entries: Vec<EntryRecord>, tombstones: Vec<Tombstone>) -> Changeset {
+        Changeset {
+            format_version: FORMAT_VERSION,
+            device_id: "remote".to_string(),
+            seq: 1,
+            entries,
+            tombstones,
+        }
+    }
+
+    fn stored(conn: &rusqlite::Connection, uuid: &str) -> Option<(String, String)> {
+        conn.query_row(
+            "SELECT title, body FROM journal_entries WHERE uuid = ?1",
+            rusqlite::params![uuid],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()
+        .unwrap()
+    }
+
+    #[test]
+    fn test_merge_cleans_and_limits_remote_entries() {
+        let db = DatabaseManager::open_in_memory().unwrap();
+        let limits = LimitSettings { max_body_mb: 1, ..Default::default() };
+        let mut garbled = record("u1", 5, "remote", "<p>Hi\u{8}</p>");
+        garbled.title = "Mon\u{0}day".to_string();
+        let huge = record("u2", 5, "remote", &"a".repeat(1024 * 1024 + 1));
+        let report = merge_state(&db.conn, "local", &changeset(vec![garbled, huge], Vec::new()), &limits).unwrap();
+        assert_eq!((report.inserted, report.rejected), (1, 1));
+        assert_eq!(stored(&db.conn, "u1"), Some(("Monday".to_string(), "<p>Hi</p>".to_string())));
+        assert_eq!(stored(&db.conn, "u2"), None);
+
+        // An oversized edit doesn't replace what's there either
+        let report = merge_state(
+            &db.conn,
+            "local",
+            &changeset(vec![record("u1", 9, "remote", &"a".repeat(1024 * 1024 + 1))], Vec::new()),
+            &limits,
+        )
+        .unwrap();
+        assert_eq!((report.updated, report.rejected), (0, 1));
+        assert_eq!(stored(&db.conn, "u1").unwrap().1, "<p>Hi</p>");
+    }
+
+    fn merge(conn: &rusqlite::Connection, entries: Vec<EntryRecord>, tombstones: Vec<Tombstone>) -> SyncReport {
+        merge_state(conn, "local", &changeset(entries, tombstones), &LimitSettings::default()).unwrap()
+    }
+
+    fn tombstone(uuid: &str, lamport: i64, origin: &str) -> Tombstone {
+        Tombstone {
+            uuid: uuid.to_string(),
+            lamport,
+            origin: origin.to_string(),
+            deleted_at: "2024-03-06T12:00:00+00:00".to_string(),
+        }
+    }
+
+    fn tombstone_version(conn: &rusqlite::Connection, uuid: &str) -> Option<(i64, Option<String>)> {
+        conn.query_row(
+            "SELECT lamport, origin FROM deleted_entries WHERE uuid = ?1",
+            rusqlite::params![uuid],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()
+        .unwrap()
+    }
+
+    #[test]
+    fn test_merge_keeps_higher_lamport() {
+        let db = DatabaseManager::open_in_memory().unwrap();
+        assert_eq!(merge(&db.conn, vec![record("u", 5, "b", "<p>B5</p>")], Vec::new()).inserted, 1);
+        let older = merge(&db.conn, vec![record("u", 4, "z", "<p>Z4</p>")], Vec::new());
+        assert_eq!((older.updated, older.skipped), (0, 1));
+        // Ties go to the higher device id
+        let tie_lost = merge(&db.conn, vec![record("u", 5, "a", "<p>A5</p>")], Vec::new());
+        assert_eq!((tie_lost.updated, tie_lost.skipped), (0, 1));
+        assert_eq!(stored(&db.conn, "u").unwrap().1, "<p>B5</p>");
+        let tie_won = merge(&db.conn, vec![record("u", 5, "c", "<p>C5</p>")], Vec::new());
+        assert_eq!((tie_won.updated, tie_won.conflicts), (1, 0));
+        assert_eq!(stored(&db.conn, "u").unwrap().1, "<p>C5</p>");
+        assert_eq!(merge(&db.conn, vec![record("u", 6, "a", "<p>A6</p>")], Vec::new()).updated, 1);
+        assert_eq!(stored(&db.conn, "u").unwrap().1, "<p>A6</p>");
+    }
+
+    #[test]
+    fn test_merge_tombstones_against_edits() {
+        let db = DatabaseManager::open_in_memory().unwrap();
+        merge(&db.conn, vec![record("u", 5, "b", "<p>B5</p>")], Vec::new());
+        // An older delete loses to the edit
+        assert_eq!(merge(&db.conn, Vec::new(), vec![tombstone("u", 4, "z")]).skipped, 1);
+        assert!(stored(&db.conn, "u").is_some());
+        // A newer one removes it, stamped with the remote version
+        assert_eq!(merge(&db.conn, Vec::new(), vec![tombstone("u", 6, "a")]).deleted, 1);
+        assert!(stored(&db.conn, "u").is_none());
+        assert_eq!(tombstone_version(&db.conn, "u"), Some((6, Some("a".to_string()))));
+        // An edit older than the delete stays deleted; a newer one brings it back
+        assert_eq!(merge(&db.conn, vec![record("u", 5, "c", "<p>C5</p>")], Vec::new()).skipped, 1);
+        assert!(stored(&db.conn, "u").is_none());
+        assert_eq!(merge(&db.conn, vec![record("u", 7, "b", "<p>B7</p>")], Vec::new()).inserted, 1);
+        assert_eq!(stored(&db.conn, "u").unwrap().1, "<p>B7</p>");
+        assert_eq!(tombstone_version(&db.conn, "u"), None);
+    }
+
+    #[test]
+    fn test_merging_twice_changes_nothing() {
+        let db = DatabaseManager::open_in_memory().unwrap();
+        let entries = vec![record("u1", 5, "b", "<p>One</p>"), record("u2", 6, "b", "<p>Two</p>")];
+        let tombstones = vec![tombstone("u3", 7, "b")];
+        let first = merge(&db.conn, entries.clone(), tombstones.clone());
+        assert_eq!(first.inserted, 2);
+        let state = full_state(&db.conn, "local").unwrap();
+        let second = merge(&db.conn, entries, tombstones);
+        assert_eq!(second, SyncReport { skipped: 3, ..Default::default() });
+        let again = full_state(&db.conn, "local").unwrap();
+        assert_eq!((again.entries, again.tombstones), (state.entries, state.tombstones));
+    }
+
+    #[test]
+    fn test_merged_entries_keep_their_origin() {
+        let db = DatabaseManager::open_in_memory().unwrap();
+        merge(&db.conn, vec![record("u", 5, "remote", "<p>Hi</p>")], Vec::new());
+        let (lamport, origin): (i64, Option<String>) = db
+            .conn
+            .query_row("SELECT lamport, origin FROM journal_entries WHERE uuid = 'u'", [], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .unwrap();
+        assert_eq!((lamport, origin.as_deref()), (5, Some("remote")));
+        // Nothing to push back out as a change made here
+        let (entries, tombstones, _) = collect_changes(&db.conn, "local", 0, true).unwrap();
+        assert!(entries.is_empty() && tombstones.is_empty());
+    }
+}
@@ -0,0 +1,229 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use tauri::command;
+
+use journal_core::text::{is_stop_word, strip_html};
+
+use crate::DatabaseManager;
+
+const MAX_TAG_CHARS: usize = 50;
+const MAX_SUGGESTIONS: usize = 8;
+/// Longer runs of words are sentences that happen to lack stop words, not keywords.
+const MAX_PHRASE_WORDS: usize = 3;
+/// Added to candidates already used as tags elsewhere, so the journal's
+/// vocabulary is suggested before new near-duplicates.
+const EXISTING_TAG_BONUS: f64 = 2.0;
+
+#[derive(Debug, Serialize)]
+pub struct TagCount {
+    tag: String,
+    entry_count: i64,
+}
+
+/// Trims tags, drops a leading `#`, and removes case-insensitive duplicates.
+/// Tags can't contain whitespace so they stay usable in `tag:` search filters.
+pub fn normalize_tags(tags: Vec<String>) -> Result<Vec<String>, String> {
+    let mut normalized: Vec<String> = Vec::new();
+    for tag in tags {
+        let tag = tag.trim().trim_start_matches('#');
+        if tag.is_empty() {
+            continue;
+        }
+        if tag.chars().any(char::is_whitespace) {
+            return Err(format!("Tag '{}' cannot contain spaces", tag));
+        }
+        if tag.chars().count() > MAX_TAG_CHARS {
+            return Err(format!("Tag '{}' must be at most {} characters", tag, MAX_TAG_CHARS));
+        }
+        if !normalized.iter().any(|t| t.eq_ignore_ascii_case(tag)) {
+            normalized.push(tag.to_string());
+        }
+    }
+    Ok(normalized)
+}
+
+/// Tags brought in from another app, as the journal allows them: spaces
+/// become hyphens, and any still refused are dropped.
+pub(crate) fn imported_tags(tags: &[String]) -> Vec<String> {
+    let tags = tags.iter().map(|tag| tag.split_whitespace().collect::<Vec<_>>().join("-"));
+    normalize_tags(tags.filter(|tag| normalize_tags(vec![tag.clone()]).is_ok()).collect()).unwrap_or_default()
+}
+
+/// Replaces all tags on an entry. Expects tags already passed through `normalize_tags`.
+pub fn replace_tags(conn: &rusqlite::Connection, entry_id: i32, tags: &[String]) -> rusqlite::Result<()> {
+    conn.execute("DELETE FROM entry_tags WHERE entry_id = ?1", rusqlite::params![entry_id])?;
+    let mut insert = conn.prepare("INSERT INTO entry_tags (entry_id, tag) VALUES (?1, ?2)")?;
+    for tag in tags {
+        insert.execute(rusqlite::params![entry_id, tag])?;
+    }
+    Ok(())
+}
+
+pub fn tags_for_entry(conn: &rusqlite::Connection, entry_id: i32) -> rusqlite::Result<Vec<String>> {
+    let mut stmt = conn.prepare("SELECT tag FROM entry_tags WHERE entry_id = ?1 ORDER BY tag")?;
+    let tags = stmt
+        .query_map(rusqlite::params![entry_id], |row| row.get(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(tags)
+}
+
+/// Keyword phrases of `text` by RAKE (rapid automatic keyword extraction):
+/// runs of words between stop words and punctuation are candidates, each
+/// word scores its degree over its frequency, and a phrase the sum of its
+/// words. Highest first.
+fn rake(text: &str) -> Vec<(Vec<String>, f64)> {
+    let mut phrases: Vec<Vec<String>> = Vec::new();
+    let text = text.to_lowercase();
+    let fragments = text.split(|c: char| !(c.is_alphanumeric() || c.is_whitespace() || c == '\'' || c == '-'));
+    for fragment in fragments {
+        let mut phrase = Vec::new();
+        for word in fragment.split_whitespace().map(|word| word.trim_matches(|c| c == '\'' || c == '-')) {
+            let keyword = word.chars().count() > 1 && !is_stop_word(word) && !word.chars().all(|c| c.is_ascii_digit());
+            if keyword {
+                phrase.push(word.to_string());
+            } else if !phrase.is_empty() {
+                phrases.push(std::mem::take(&mut phrase));
+            }
+        }
+        if !phrase.is_empty() {
+            phrases.push(phrase);
+        }
+    }
+    phrases.retain(|phrase| phrase.len() <= MAX_PHRASE_WORDS);
+
+    let mut frequency: HashMap<&str, f64> = HashMap::new();
+    let mut degree: HashMap<&str, f64> = HashMap::new();
+    for phrase in &phrases {
+        for word in phrase {
+            *frequency.entry(word).or_default() += 1.0;
+            *degree.entry(word).or_default() += phrase.len() as f64;
+        }
+    }
+    let mut scored: Vec<(Vec<String>, f64)> = Vec::new();
+    for phrase in &phrases {
+        if scored.iter().any(|(seen, _)| seen == phrase) {
+            continue;
+        }
+        let score = phrase.iter().map(|word| degree[word.as_str()] / frequency[word.as_str()]).sum();
+        scored.push((phrase.clone(), score));
+    }
+    scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+    scored
+}
+
+/// Turns keyword phrases into tags the entry doesn't have yet, preferring
+/// (and spelling like) tags already in use.
+fn tag_suggestions(keywords: Vec<(Vec<String>, f64)>, entry_tags: &[String], known_tags: &[String]) -> Vec<String> {
+    let mut suggestions: Vec<(String, f64)> = Vec::new();
+    for (phrase, score) in keywords {
+        let tag = phrase.join("-");
+        if tag.chars().count() > MAX_TAG_CHARS
+            || entry_tags.iter().any(|t| t.eq_ignore_ascii_case(&tag))
+            || suggestions.iter().any(|(t, _)| t.eq_ignore_ascii_case(&tag))
+        {
+            continue;
+        }
+        match known_tags.iter().find(|t| t.eq_ignore_ascii_case(&tag)) {
+            Some(known) => suggestions.push((known.clone(), score + EXISTING_TAG_BONUS)),
+            None => suggestions.push((tag, score)),
+        }
+    }
+    suggestions.sort_by(|a, b| b.1.total_cmp(&a.1));
+    suggestions.into_iter().take(MAX_SUGGESTIONS).map(|(tag, _)| tag).collect()
+}
+
+/// Candidate tags drawn from an entry's title and body, for the UI to offer
+/// as one-click additions.
+#[command]
+pub fn suggest_tags(entry_id: i32) -> Result<Vec<String>, String> {
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    let (title, body, locked): (String, String, bool) = db.conn
+        .query_row(
+            "SELECT title, body, locked FROM journal_entries WHERE id = ?1",
+            rusqlite::params![entry_id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .map_err(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => format!("Entry {} not found", entry_id),
+            e => e.to_string(),
+        })?;
+    // A locked entry's body isn't readable here, and its title alone is too little to go on
+    if locked {
+        return Ok(Vec::new());
+    }
+    let known_tags = db.conn
+        .prepare("SELECT DISTINCT tag FROM entry_tags")
+        .and_then(|mut stmt| stmt.query_map([], |row| row.get(0))?.collect::<Result<Vec<String>, _>>())
+        .map_err(|e| e.to_string())?;
+    let entry_tags = tags_for_entry(&db.conn, entry_id).map_err(|e| e.to_string())?;
+    let keywords = rake(&format!("{}.\n{}", title, strip_html(&body)));
+    Ok(tag_suggestions(keywords, &entry_tags, &known_tags))
+}
+
+#[command]
+pub fn set_entry_tags(id: i32, tags: Vec<String>) -> Result<(), String> {
+    let tags = normalize_tags(tags)?;
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    db.in_transaction(|tx| replace_tags(tx, id, &tags)).map_err(|e| e.to_string())
+}
+
+#[command]
+pub fn get_entry_tags(id: i32) -> Result<Vec<String>, String> {
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    tags_for_entry(&db.conn, id).map_err(|e| e.to_string())
+}
+
+/// Every tag in use with the number of entries carrying it, most used first.
+#[command]
+pub fn get_all_tags() -> Result<Vec<TagCount>, String> {
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    let mut stmt = db.conn
+        .prepare("SELECT tag, COUNT(*) AS n FROM entry_tags GROUP BY tag ORDER BY n DESC, tag")
+        .map_err(|e| e.to_string())?;
+    let tags = stmt
+        .query_map([], |row| {
+            Ok(TagCount {
+                tag: row.get(0)?,
+                entry_count: row.get(1)?,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(tags)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_tags() {
+        let tags = vec![" #Work ".to_string(), "work".to_string(), "".to_string(), "travel".to_string()];
+        assert_eq!(normalize_tags(tags).unwrap(), vec!["Work", "travel"]);
+        assert!(normalize_tags(vec!["two words".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_rake_prefers_phrases() {
+        let keywords =
+            rake("Spent the morning at the farmers market. The farmers market had ripe peaches, and I bought bread.");
+        let phrases: Vec<String> = keywords.iter().map(|(phrase, _)| phrase.join(" ")).collect();
+        assert_eq!(phrases[0], "farmers market");
+        assert!(phrases.contains(&"ripe peaches".to_string()));
+        assert!(!phrases.iter().any(|p| p.split(' ').any(is_stop_word)));
+    }
+
+    #[test]
+    fn test_tag_suggestions() {
+        let keywords = rake("Long run by the river. Legs sore after the run, then coffee with Ana.");
+        let entry_tags = vec!["coffee".to_string()];
+        let known_tags = vec!["Running".to_string(), "River".to_string()];
+        let suggestions = tag_suggestions(keywords, &entry_tags, &known_tags);
+        // Spelled like the tag already in use
+        assert!(suggestions.contains(&"River".to_string()));
+        assert!(suggestions.contains(&"long-run".to_string()));
+        assert!(!suggestions.iter().any(|t| t.eq_ignore_ascii_case("coffee")));
+        assert!(suggestions.iter().all(|t| normalize_tags(vec![t.clone()]).is_ok()));
+    }
+}
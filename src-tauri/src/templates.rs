@@ -0,0 +1,263 @@
+use chrono::{DateTime, Local, Utc};
+use log::debug;
+use rusqlite::OptionalExtension;
+use serde::{Deserialize, Serialize};
+use tauri::command;
+
+use crate::prompts::daily_prompt_text;
+use crate::settings::Settings;
+use crate::validation::clean_entry;
+use crate::{insert_entry, DatabaseManager};
+
+#[derive(Debug, Serialize)]
+pub struct Template {
+    id: i32,
+    name: String,
+    title: String,
+    body: String,
+    created_at: String,
+    /// Whether today's entry is created from this template.
+    is_default: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TemplateRequest {
+    name: String,
+    title: String,
+    body: String,
+}
+
+impl TemplateRequest {
+    fn validated(self) -> Result<Self, String> {
+        let name = self.name.trim().to_string();
+        if name.is_empty() {
+            return Err("Template name cannot be empty".to_string());
+        }
+        Ok(TemplateRequest { name, ..self })
+    }
+}
+
+/// Values substituted for `{{variable}}` placeholders when a template is expanded.
+pub struct TemplateContext {
+    date: String,
+    weekday: String,
+    prompt: String,
+}
+
+impl TemplateContext {
+    pub fn for_time(now: DateTime<Local>, prompt: String) -> Self {
+        TemplateContext {
+            date: now.format("%B %-d, %Y").to_string(),
+            weekday: now.format("%A").to_string(),
+            prompt,
+        }
+    }
+}
+
+/// Replaces the known `{{variable}}` placeholders; unknown ones are left as written
+/// so a typo stays visible instead of silently disappearing.
+pub fn expand(text: &str, context: &TemplateContext) -> String {
+    text.replace("{{date}}", &context.date)
+        .replace("{{weekday}}", &context.weekday)
+        .replace("{{prompt}}", &context.prompt)
+}
+
+#[command]
+pub fn get_templates() -> Result<Vec<Template>, String> {
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    let mut stmt = db.conn
+        .prepare("SELECT id, name, title, body, created_at, is_default FROM templates ORDER BY name COLLATE NOCASE")
+        .map_err(|e| e.to_string())?;
+    let templates = stmt
+        .query_map([], |row| {
+            Ok(Template {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                title: row.get(2)?,
+                body: row.get(3)?,
+                created_at: row.get(4)?,
+                is_default: row.get(5)?,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(templates)
+}
+
+#[command]
+pub fn create_template(request: TemplateRequest) -> Result<i32, String> {
+    let request = request.validated()?;
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    db.conn
+        .execute(
+            "INSERT INTO templates (name, title, body, created_at) VALUES (?1, ?2, ?3, ?4)",
+            rusqlite::params![request.name, request.title, request.body, Utc::now().to_rfc3339()],
+        )
+        .map_err(|e| e.to_string())?;
+    Ok(db.conn.last_insert_rowid() as i32)
+}
+
+#[command]
+pub fn update_template(id: i32, request: TemplateRequest) -> Result<(), String> {
+    let request = request.validated()?;
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    let updated = db.conn
+        .execute(
+            "UPDATE templates SET name = ?1, title = ?2, body = ?3 WHERE id = ?4",
+            rusqlite::params![request.name, request.title, request.body, id],
+        )
+        .map_err(|e| e.to_string())?;
+    if updated == 0 {
+        return Err(format!("Template {} not found", id));
+    }
+    Ok(())
+}
+
+#[command]
+pub fn delete_template(id: i32) -> Result<(), String> {
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    db.conn
+        .execute("DELETE FROM templates WHERE id = ?1", rusqlite::params![id])
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Makes `id` the template today's entry is created from, or clears the
+/// default when `id` is null.
+#[command]
+pub fn set_default_template(id: Option<i32>) -> Result<(), String> {
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    let tx = db.conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    tx.execute("UPDATE templates SET is_default = 0 WHERE is_default = 1", [])
+        .map_err(|e| e.to_string())?;
+    if let Some(id) = id {
+        let updated = tx
+            .execute("UPDATE templates SET is_default = 1 WHERE id = ?1", rusqlite::params![id])
+            .map_err(|e| e.to_string())?;
+        if updated == 0 {
+            return Err(format!("Template {} not found", id));
+        }
+    }
+    tx.commit().map_err(|e| e.to_string())
+}
+
+/// The first unlocked entry written on `now`'s local day, or a new one made
+/// from the default template (a dated, empty entry if there's none). Returns
+/// the id and whether the entry was just created.
+pub fn find_or_create_today(conn: &rusqlite::Connection, now: DateTime<Local>) -> Result<(i32, bool), String> {
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    let existing: Option<i32> = tx
+        .query_row(
+            "SELECT id FROM journal_entries
+             WHERE locked = 0 AND local_date = ?1
+             ORDER BY created_ms ASC, id ASC LIMIT 1",
+            rusqlite::params![now.date_naive().to_string()],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+    if let Some(id) = existing {
+        return Ok((id, false));
+    }
+    let template: Option<(String, String)> = tx
+        .query_row("SELECT title, body FROM templates WHERE is_default = 1", [], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })
+        .optional()
+        .map_err(|e| e.to_string())?;
+    let (title, body) = match template {
+        Some((title, body)) => {
+            let prompt = daily_prompt_text(&tx).map_err(|e| e.to_string())?;
+            let context = TemplateContext::for_time(now, prompt);
+            (expand(&title, &context), expand(&body, &context))
+        }
+        None => (now.format("%B %-d, %Y").to_string(), String::new()),
+    };
+    let (title, body) = clean_entry(&title, &body, &Settings::load().limits)?;
+    debug!("Creating today's entry");
+    let id = insert_entry(&tx, &title, &body, &now.with_timezone(&Utc).to_rfc3339()).map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok((id, true))
+}
+
+/// Creates a new entry from a template, expanding its variables for the current day.
+#[command]
+pub fn create_entry_from_template(template_id: i32) -> Result<i32, String> {
+    debug!("Creating entry from template {}", template_id);
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    let (title, body): (String, String) = db.conn
+        .query_row(
+            "SELECT title, body FROM templates WHERE id = ?1",
+            rusqlite::params![template_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .map_err(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => format!("Template {} not found", template_id),
+            e => e.to_string(),
+        })?;
+
+    let prompt = daily_prompt_text(&db.conn).map_err(|e| e.to_string())?;
+    let context = TemplateContext::for_time(Local::now(), prompt);
+    let (title, body) = clean_entry(&expand(&title, &context), &expand(&body, &context), &Settings::load().limits)?;
+    insert_entry(&db.conn, &title, &body, &Utc::now().to_rfc3339()).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_expand_variables() {
+        let now = Local.with_ymd_and_hms(2024, 3, 5, 9, 30, 0).unwrap();
+        let context = TemplateContext::for_time(now, "What made you smile?".to_string());
+        assert_eq!(
+            expand("{{weekday}}, {{date}} – {{prompt}} {{unknown}}", &context),
+            "Tuesday, March 5, 2024 – What made you smile? {{unknown}}"
+        );
+    }
+
+    #[test]
+    fn test_find_or_create_today() {
+        let db = DatabaseManager::open_in_memory().unwrap();
+        let now = Local.with_ymd_and_hms(2024, 3, 5, 9, 30, 0).unwrap();
+        let (id, created) = find_or_create_today(&db.conn, now).unwrap();
+        assert!(created);
+        let title: String = db
+            .conn
+            .query_row("SELECT title FROM journal_entries WHERE id = ?1", rusqlite::params![id], |row| row.get(0))
+            .unwrap();
+        assert_eq!(title, "March 5, 2024");
+        assert_eq!(find_or_create_today(&db.conn, now + chrono::Duration::hours(12)).unwrap(), (id, false));
+
+        db.conn
+            .execute(
+                "INSERT INTO templates (name, title, body, created_at, is_default)
+                 VALUES ('Daily', '{{weekday}}', '<p>Sleep:</p>', ?1, 1)",
+                rusqlite::params![Utc::now().to_rfc3339()],
+            )
+            .unwrap();
+        let (next, created) = find_or_create_today(&db.conn, now + chrono::Duration::days(1)).unwrap();
+        assert!(created && next != id);
+        let (title, body): (String, String) = db
+            .conn
+            .query_row(
+                "SELECT title, body FROM journal_entries WHERE id = ?1",
+                rusqlite::params![next],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!((title.as_str(), body.as_str()), ("Wednesday", "<p>Sleep:</p>"));
+    }
+
+    #[test]
+    fn test_blank_template_name_rejected() {
+        let request = TemplateRequest {
+            name: "   ".to_string(),
+            title: String::new(),
+            body: String::new(),
+        };
+        assert!(request.validated().is_err());
+    }
+}
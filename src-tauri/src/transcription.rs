@@ -0,0 +1,208 @@
+use log::{debug, error, warn};
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::Duration;
+use tauri::command;
+use uuid::Uuid;
+
+use journal_core::keychain::KeychainManager;
+
+use crate::attachments::{read_blob, store_attachment_text, unread_blobs};
+use crate::jobs::{Job, JobKind, CANCELLED};
+use crate::settings::{Settings, TranscriptionSettings};
+use crate::DatabaseManager;
+
+// Voice memos to text with whisper.cpp, on this machine and only when turned
+// on. Memos are recorded as WebM or similar while whisper.cpp reads 16 kHz
+// mono WAV, so ffmpeg converts them first. Transcripts are kept in
+// `attachment_text` next to text read from photos, so search finds memos too.
+
+const WORKER_START_DELAY: Duration = Duration::from_secs(120);
+const WORKER_INTERVAL: Duration = Duration::from_secs(10 * 60);
+/// Memos transcribed per pass; each can take a while on a slow machine.
+const BATCH_SIZE: usize = 5;
+const TRANSCRIPT_SOURCE: &str = "transcript";
+
+/// Removes the decoded memo when transcription is done, however it ends.
+struct TempWav(PathBuf);
+
+impl Drop for TempWav {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+/// Converts audio to WAV with ffmpeg, or returns `false` if it can't be decoded.
+fn convert_to_wav(settings: &TranscriptionSettings, audio: &[u8], wav: &TempWav) -> Result<bool, String> {
+    let mut child = Command::new(&settings.ffmpeg_path)
+        .args(["-loglevel", "error", "-i", "pipe:0", "-ar", "16000", "-ac", "1", "-c:a", "pcm_s16le", "-y"])
+        .arg(&wav.0)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to run {}: {}", settings.ffmpeg_path, e))?;
+    let mut stdin = child.stdin.take().ok_or("ffmpeg's input was not available")?;
+    // Written from another thread so a full error pipe can't stall the write
+    let output = thread::scope(|scope| {
+        scope.spawn(move || stdin.write_all(audio));
+        child.wait_with_output()
+    })
+    .map_err(|e| e.to_string())?;
+    if !output.status.success() {
+        warn!("ffmpeg could not decode a memo: {}", String::from_utf8_lossy(&output.stderr).trim());
+    }
+    Ok(output.status.success())
+}
+
+/// Transcribes a memo. `Err` means the tools couldn't be run or aren't set
+/// up; a memo that can't be decoded gives `Ok(None)`.
+fn transcribe(settings: &TranscriptionSettings, audio: &[u8]) -> Result<Option<String>, String> {
+    let model = settings
+        .model_path
+        .as_deref()
+        .ok_or("Choose a whisper.cpp model before transcribing memos")?;
+    let wav = TempWav(std::env::temp_dir().join(format!("journal-memo-{}.wav", Uuid::new_v4())));
+    if !convert_to_wav(settings, audio, &wav)? {
+        return Ok(None);
+    }
+    let output = Command::new(&settings.whisper_path)
+        .args(["-m", model, "-l", &settings.language, "--no-timestamps", "--no-prints", "-f"])
+        .arg(&wav.0)
+        .output()
+        .map_err(|e| format!("Failed to run {}: {}", settings.whisper_path, e))?;
+    if !output.status.success() {
+        return Err(format!("whisper.cpp failed: {}", String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    let text = String::from_utf8_lossy(&output.stdout);
+    Ok(Some(text.split_whitespace().collect::<Vec<_>>().join(" ")))
+}
+
+/// Transcribes the next batch of memos without a transcript, as a job when
+/// there are any, and returns how many there were.
+fn transcribe_pending(
+    conn: &rusqlite::Connection,
+    transcribe: impl Fn(&[u8]) -> Result<Option<String>, String>,
+) -> Result<usize, String> {
+    let pending = unread_blobs(conn, "audio", BATCH_SIZE).map_err(|e| e.to_string())?;
+    if pending.is_empty() {
+        return Ok(0);
+    }
+    Job::run(JobKind::Transcription, "Transcribing voice memos", |job| {
+        for (i, (hash, size)) in pending.iter().enumerate() {
+            job.step(i, pending.len(), None)?;
+            let audio = read_blob(conn, hash, 0, *size as usize).map_err(|e| e.to_string())?;
+            // Undecodable memos get an empty transcript, so they aren't tried again every pass
+            let text = transcribe(&audio)?.unwrap_or_default();
+            store_attachment_text(conn, hash, TRANSCRIPT_SOURCE, &text).map_err(|e| e.to_string())?;
+        }
+        Ok(pending.len())
+    })
+}
+
+fn run_pass(settings: &TranscriptionSettings) -> Result<usize, String> {
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    transcribe_pending(&db.conn, |audio| transcribe(settings, audio))
+}
+
+/// Starts the background thread that transcribes new memos when
+/// transcription is turned on. Like the indexer, it waits for the journal to
+/// be unlocked.
+pub fn start_worker() {
+    thread::spawn(|| {
+        thread::sleep(WORKER_START_DELAY);
+        loop {
+            let settings = Settings::load().transcription;
+            if settings.enabled && KeychainManager::has_cached_key() {
+                match run_pass(&settings) {
+                    Ok(0) => {}
+                    Ok(count) => debug!("Transcribed {} memos", count),
+                    Err(e) if e == CANCELLED => debug!("Transcribing memos was cancelled"),
+                    Err(e) => error!("Transcribing memos failed: {}", e),
+                }
+            }
+            thread::sleep(WORKER_INTERVAL);
+        }
+    });
+}
+
+fn transcribe_attachment_blocking(id: i32) -> Result<String, String> {
+    let settings = Settings::load().transcription;
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    let (hash, size): (String, i64) = db
+        .conn
+        .query_row(
+            "SELECT hash, size FROM attachments WHERE id = ?1 AND kind = 'audio'",
+            rusqlite::params![id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .map_err(|_| format!("Audio attachment {} not found", id))?;
+    let audio = read_blob(&db.conn, &hash, 0, size as usize).map_err(|e| e.to_string())?;
+    let text = transcribe(&settings, &audio)?.ok_or("The recording could not be decoded")?;
+    store_attachment_text(&db.conn, &hash, TRANSCRIPT_SOURCE, &text).map_err(|e| e.to_string())?;
+    Ok(text)
+}
+
+/// Transcribes a memo now, replacing any earlier transcript, and returns the
+/// text so the editor can insert it into the entry. Runs as a job.
+#[command]
+pub async fn transcribe_attachment(id: i32) -> Result<String, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        Job::run(JobKind::Transcription, "Transcribing a voice memo", |_| transcribe_attachment_blocking(id))
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use journal_core::attachments::content_hash;
+    use journal_core::{insert_entry, timestamp_now};
+
+    #[test]
+    fn test_transcribe_pending() {
+        let db = DatabaseManager::open_in_memory().unwrap();
+        let entry_id = insert_entry(&db.conn, "Walk", "", &timestamp_now()).unwrap();
+        for (kind, data) in [("audio", &b"memo"[..]), ("audio", b"noise"), ("image", b"photo")] {
+            db.conn
+                .execute(
+                    "INSERT OR IGNORE INTO attachment_blobs (hash, size, data) VALUES (?1, ?2, ?3)",
+                    rusqlite::params![content_hash(data), data.len() as i64, data],
+                )
+                .unwrap();
+            db.conn
+                .execute(
+                    "INSERT INTO attachments (entry_id, kind, mime, size, hash, created_at)
+                     VALUES (?1, ?2, 'x', ?3, ?4, '')",
+                    rusqlite::params![entry_id, kind, data.len() as i64, content_hash(data)],
+                )
+                .unwrap();
+        }
+
+        let transcribe = |audio: &[u8]| match audio {
+            b"memo" => Ok(Some("Remember to call the vet".to_string())),
+            _ => Ok(None),
+        };
+        assert_eq!(transcribe_pending(&db.conn, transcribe).unwrap(), 2);
+        assert_eq!(transcribe_pending(&db.conn, transcribe).unwrap(), 0);
+        let transcript: String = db
+            .conn
+            .query_row(
+                "SELECT text FROM attachment_text WHERE hash = ?1 AND source = 'transcript'",
+                rusqlite::params![content_hash(b"memo")],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(transcript, "Remember to call the vet");
+    }
+
+    #[test]
+    fn test_transcribe_needs_a_model() {
+        let settings = TranscriptionSettings::default();
+        assert!(transcribe(&settings, b"memo").unwrap_err().contains("model"));
+    }
+}
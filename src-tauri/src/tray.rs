@@ -0,0 +1,157 @@
+use log::warn;
+use tauri::menu::{MenuBuilder, MenuItemBuilder};
+use tauri::tray::{MouseButton, MouseButtonState, TrayIconBuilder, TrayIconEvent};
+use tauri::{
+    command, AppHandle, Emitter, Manager, PhysicalPosition, WebviewUrl, WebviewWindowBuilder, WindowEvent,
+};
+
+use journal_core::keychain::KeychainManager;
+
+use crate::api_server::append_to_today;
+use crate::{privacy, sealing};
+
+// The tray (menu bar on macOS) icon: a left click opens a small capture box
+// for jotting a line into today's entry without bringing the journal forward,
+// and its menu starts or opens entries and locks the journal. The capture box
+// is a second window of the same frontend, which shows only the capture view
+// when loaded at `#quick-capture`.
+
+const TRAY_ID: &str = "journal";
+const QUICK_CAPTURE_LABEL: &str = "quick-capture";
+const QUICK_CAPTURE_SIZE: (f64, f64) = (420.0, 180.0);
+
+pub(crate) fn show_main(app: &AppHandle) {
+    if let Some(window) = app.get_webview_window("main") {
+        let _ = window.unminimize();
+        let _ = window.show();
+        let _ = window.set_focus();
+    }
+}
+
+/// Shows the capture box below `anchor` (the tray icon), or hides it if it's open.
+fn toggle_quick_capture(app: &AppHandle, anchor: Option<PhysicalPosition<f64>>) -> tauri::Result<()> {
+    let window = match app.get_webview_window(QUICK_CAPTURE_LABEL) {
+        Some(window) if window.is_visible()? => return window.hide(),
+        Some(window) => window,
+        None => {
+            let url = WebviewUrl::App("index.html#quick-capture".into());
+            let window = WebviewWindowBuilder::new(app, QUICK_CAPTURE_LABEL, url)
+                .title("Quick Capture")
+                .inner_size(QUICK_CAPTURE_SIZE.0, QUICK_CAPTURE_SIZE.1)
+                .content_protected(privacy::content_protected())
+                .resizable(false)
+                .decorations(false)
+                .always_on_top(true)
+                .skip_taskbar(true)
+                .visible(false)
+                .build()?;
+            let popover = window.clone();
+            // Closes like a popover when the user clicks elsewhere
+            window.on_window_event(move |event| {
+                if let WindowEvent::Focused(false) = event {
+                    let _ = popover.hide();
+                }
+            });
+            window
+        }
+    };
+    if let Some(anchor) = anchor {
+        let scale = window.scale_factor()?;
+        let x = anchor.x - QUICK_CAPTURE_SIZE.0 * scale / 2.0;
+        window.set_position(PhysicalPosition::new(x.max(0.0), anchor.y))?;
+    } else {
+        window.center()?;
+    }
+    window.show()?;
+    window.set_focus()
+}
+
+/// Brings the main window forward and starts a new entry in it.
+pub(crate) fn new_entry(app: &AppHandle) {
+    show_main(app);
+    let _ = app.emit("new-entry", ());
+}
+
+/// Brings the main window forward on today's entry, creating it if needed.
+pub(crate) fn open_today(app: &AppHandle) {
+    show_main(app);
+    let _ = app.emit("open-today", ());
+}
+
+/// Shows or hides the capture box in the middle of the screen.
+pub(crate) fn open_quick_capture(app: &AppHandle) {
+    if let Err(e) = toggle_quick_capture(app, None) {
+        warn!("Could not open quick capture: {}", e);
+    }
+}
+
+/// Forgets the database key and tells the windows, which go back to asking
+/// for keychain access before showing anything.
+pub(crate) fn lock(app: &AppHandle) {
+    KeychainManager::forget_cached_key();
+    sealing::reseal_all();
+    if let Some(window) = app.get_webview_window(QUICK_CAPTURE_LABEL) {
+        let _ = window.hide();
+    }
+    let _ = app.emit("journal-locked", ());
+}
+
+fn on_menu_event(app: &AppHandle, id: &str) {
+    match id {
+        "tray_new_entry" => new_entry(app),
+        "tray_open_today" => open_today(app),
+        "tray_quick_capture" => open_quick_capture(app),
+        "tray_lock" => lock(app),
+        _ => {}
+    }
+}
+
+/// Adds the tray icon. Called once from setup.
+pub fn create(app: &AppHandle) -> tauri::Result<()> {
+    let menu = MenuBuilder::new(app)
+        .item(&MenuItemBuilder::new("New Entry").id("tray_new_entry").build(app)?)
+        .item(&MenuItemBuilder::new("Open Today").id("tray_open_today").build(app)?)
+        .item(&MenuItemBuilder::new("Quick Capture…").id("tray_quick_capture").build(app)?)
+        .separator()
+        .item(&MenuItemBuilder::new("Lock Journal").id("tray_lock").build(app)?)
+        .separator()
+        .quit()
+        .build()?;
+    let mut tray = TrayIconBuilder::with_id(TRAY_ID)
+        .tooltip("Journal")
+        .menu(&menu)
+        .show_menu_on_left_click(false)
+        .on_menu_event(|app, event| on_menu_event(app, event.id().as_ref()))
+        .on_tray_icon_event(|tray, event| {
+            if let TrayIconEvent::Click {
+                button: MouseButton::Left,
+                button_state: MouseButtonState::Up,
+                position,
+                ..
+            } = event
+            {
+                if let Err(e) = toggle_quick_capture(tray.app_handle(), Some(position)) {
+                    warn!("Could not open quick capture: {}", e);
+                }
+            }
+        });
+    if let Some(icon) = app.default_window_icon() {
+        tray = tray.icon(icon.clone());
+    }
+    tray.build(app)?;
+    Ok(())
+}
+
+/// Appends `text` to today's entry from the capture box, then closes it.
+#[command]
+pub fn quick_capture(app: AppHandle, text: String) -> Result<i32, String> {
+    if !KeychainManager::has_cached_key() {
+        return Err("Unlock the journal before capturing".to_string());
+    }
+    let id = append_to_today(&text)?;
+    let _ = app.emit("entries-changed", ());
+    if let Some(window) = app.get_webview_window(QUICK_CAPTURE_LABEL) {
+        let _ = window.hide();
+    }
+    Ok(id)
+}
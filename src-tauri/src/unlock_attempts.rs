@@ -0,0 +1,155 @@
+use log::warn;
+use serde::Serialize;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter};
+
+use crate::settings::Settings;
+use crate::tray;
+
+// Slows down guessing at the secrets the app checks itself: the journal
+// passphrase and recovery key, and locked entries' passphrases. A few
+// mistakes are free; after that each wrong guess doubles the wait before the
+// next one is even tried. Optionally the journal locks itself after a number
+// of wrong guesses in a row, forgetting its key. Every wrong guess is
+// announced with an `unlock-throttled` event, so a window can show the wait.
+
+const EVENT: &str = "unlock-throttled";
+/// Wrong guesses allowed before any waiting.
+const FREE_ATTEMPTS: u32 = 3;
+const FIRST_DELAY: Duration = Duration::from_secs(1);
+const MAX_DELAY: Duration = Duration::from_secs(15 * 60);
+
+static ATTEMPTS: Mutex<[Attempts; 2]> = Mutex::new([Attempts::new(), Attempts::new()]);
+
+/// Which kind of secret was guessed; each is counted on its own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Secret {
+    /// The journal's passphrase or recovery key.
+    Journal,
+    /// A locked entry's passphrase.
+    Entry,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct Throttled {
+    secret: Secret,
+    failures: u32,
+    /// Seconds until the next guess is accepted; 0 when there's no wait.
+    retry_after_secs: u64,
+    /// Whether the journal was locked because of this guess.
+    locked: bool,
+}
+
+#[derive(Debug)]
+struct Attempts {
+    failures: u32,
+    retry_at: Option<Instant>,
+}
+
+impl Attempts {
+    const fn new() -> Self {
+        Attempts {
+            failures: 0,
+            retry_at: None,
+        }
+    }
+
+    /// How long until the next guess is accepted.
+    fn wait(&self, now: Instant) -> Option<Duration> {
+        self.retry_at.filter(|at| *at > now).map(|at| at - now)
+    }
+
+    /// Counts a wrong guess, returning the wait it earns.
+    fn fail(&mut self, now: Instant) -> Option<Duration> {
+        self.failures += 1;
+        let delay = delay_after(self.failures);
+        self.retry_at = delay.map(|delay| now + delay);
+        delay
+    }
+}
+
+/// The wait after `failures` wrong guesses in a row.
+fn delay_after(failures: u32) -> Option<Duration> {
+    let doublings = failures.checked_sub(FREE_ATTEMPTS + 1)?;
+    Some(FIRST_DELAY.saturating_mul(2u32.saturating_pow(doublings)).min(MAX_DELAY))
+}
+
+fn index(secret: Secret) -> usize {
+    match secret {
+        Secret::Journal => 0,
+        Secret::Entry => 1,
+    }
+}
+
+/// Refuses to check a guess at `secret` while it's still being waited out.
+pub fn check(secret: Secret) -> Result<(), String> {
+    let attempts = ATTEMPTS.lock().map_err(|e| e.to_string())?;
+    match attempts[index(secret)].wait(Instant::now()) {
+        Some(wait) => Err(format!(
+            "Too many incorrect attempts. Try again in {} seconds.",
+            wait.as_secs().max(1)
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Records how a guess at `secret` went: `Some(true)` for right,
+/// `Some(false)` for wrong, `None` when it couldn't be checked at all.
+pub fn record(app: &AppHandle, secret: Secret, correct: Option<bool>) {
+    let Ok(mut attempts) = ATTEMPTS.lock() else {
+        return;
+    };
+    let attempts = &mut attempts[index(secret)];
+    match correct {
+        Some(true) => *attempts = Attempts::new(),
+        Some(false) => {
+            let delay = attempts.fail(Instant::now());
+            let lock_after = Settings::load().unlock.lock_after_failures;
+            let locked = lock_after > 0 && attempts.failures >= lock_after;
+            if locked {
+                warn!("Locking the journal after {} incorrect attempts", attempts.failures);
+                tray::lock(app);
+            }
+            let _ = app.emit(
+                EVENT,
+                Throttled {
+                    secret,
+                    failures: attempts.failures,
+                    retry_after_secs: delay.map_or(0, |delay| delay.as_secs()),
+                    locked,
+                },
+            );
+        }
+        None => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_delay_doubles_after_the_free_attempts() {
+        assert_eq!(delay_after(1), None);
+        assert_eq!(delay_after(FREE_ATTEMPTS), None);
+        assert_eq!(delay_after(FREE_ATTEMPTS + 1), Some(Duration::from_secs(1)));
+        assert_eq!(delay_after(FREE_ATTEMPTS + 2), Some(Duration::from_secs(2)));
+        assert_eq!(delay_after(FREE_ATTEMPTS + 4), Some(Duration::from_secs(8)));
+        assert_eq!(delay_after(100), Some(MAX_DELAY));
+    }
+
+    #[test]
+    fn test_wait_runs_out() {
+        let now = Instant::now();
+        let mut attempts = Attempts::new();
+        for _ in 0..FREE_ATTEMPTS {
+            assert_eq!(attempts.fail(now), None);
+        }
+        assert_eq!(attempts.wait(now), None);
+        attempts.fail(now);
+        assert_eq!(attempts.wait(now), Some(Duration::from_secs(1)));
+        assert_eq!(attempts.wait(now + Duration::from_secs(1)), None);
+    }
+}
@@ -0,0 +1,56 @@
+use journal_core::validation;
+
+use crate::settings::LimitSettings;
+
+// The checks in `journal_core::validation`, with the limits from Settings.
+// Every path that writes an entry's text goes through `clean_entry` or
+// `clean_body`: the editor, the local API, imports, emailed notes, templates
+// and photo days. The app starts entries untitled, so only the local API
+// requires a title.
+
+pub(crate) use journal_core::validation::{require_title, strip_control_chars};
+
+/// An entry body as it's saved: control characters stripped, and refused if
+/// it's over `limits`.
+pub(crate) fn clean_body(body: &str, limits: &LimitSettings) -> Result<String, String> {
+    validation::clean_body(body, limits.max_body_mb)
+}
+
+/// An entry's title and body as they're saved. An empty title is kept.
+pub(crate) fn clean_entry(title: &str, body: &str, limits: &LimitSettings) -> Result<(String, String), String> {
+    validation::clean_entry(title, body, limits.max_body_mb)
+}
+
+/// Refuses an attachment over `limits`.
+pub(crate) fn check_attachment(bytes: usize, limits: &LimitSettings) -> Result<(), String> {
+    validation::check_size("Attachments", bytes, limits.max_attachment_mb)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use journal_core::validation::{EMPTY_TITLE, TOO_LARGE};
+
+    #[test]
+    fn test_clean_entry_limits() {
+        let limits = LimitSettings { max_body_mb: 1, max_attachment_mb: 2 };
+        let (title, body) = clean_entry("Mon\u{0}day", "<p>Hi\u{8}</p>", &limits).unwrap();
+        assert_eq!((title.as_str(), body.as_str()), ("Monday", "<p>Hi</p>"));
+        let huge = "a".repeat(1024 * 1024 + 1);
+        assert!(clean_entry("", &huge, &limits).unwrap_err().starts_with(TOO_LARGE));
+        assert!(clean_body(&huge, &limits).unwrap_err().starts_with(TOO_LARGE));
+        assert!(check_attachment(2 * 1024 * 1024, &limits).is_ok());
+        assert!(check_attachment(2 * 1024 * 1024 + 1, &limits).is_err());
+        // 0 turns a limit off
+        let unlimited = LimitSettings { max_body_mb: 0, max_attachment_mb: 0 };
+        assert!(clean_entry("", &huge, &unlimited).is_ok());
+    }
+
+    #[test]
+    fn test_untitled_entries_allowed() {
+        // The editor saves a new entry before it has a title; only the API needs one
+        let (title, _) = clean_entry(" \u{1b}", "<p>Notes</p>", &LimitSettings::default()).unwrap();
+        assert_eq!(title, " ");
+        assert!(require_title(&title).unwrap_err().starts_with(EMPTY_TITLE));
+    }
+}
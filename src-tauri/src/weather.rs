@@ -0,0 +1,195 @@
+use log::{debug, info};
+use reqwest::blocking::Client;
+use serde::{Deserialize, Serialize};
+use std::thread;
+use std::time::Duration;
+
+use crate::settings::{Settings, WeatherSettings};
+use crate::DatabaseManager;
+
+// Optional weather for new entries. When enabled in Settings, the conditions
+// at the configured location are looked up in the background right after an
+// entry is created and stored with it. Being offline, or the provider being
+// down, just means the entry has no weather; nothing waits on it.
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Conditions when an entry was written.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Weather {
+    pub temperature_c: f64,
+    /// Short description, e.g. "Light rain".
+    pub conditions: String,
+    pub provider: WeatherProvider,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WeatherProvider {
+    #[default]
+    OpenMeteo,
+}
+
+impl WeatherProvider {
+    pub fn source(self) -> Result<Box<dyn WeatherSource>, String> {
+        let client = Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .map_err(|e| e.to_string())?;
+        Ok(match self {
+            WeatherProvider::OpenMeteo => Box::new(OpenMeteo { client }),
+        })
+    }
+}
+
+pub trait WeatherSource {
+    fn current(&self, latitude: f64, longitude: f64) -> Result<Weather, String>;
+}
+
+/// open-meteo.com: free, keyless, and doesn't need an account.
+struct OpenMeteo {
+    client: Client,
+}
+
+#[derive(Deserialize)]
+struct OpenMeteoResponse {
+    current: OpenMeteoCurrent,
+}
+
+#[derive(Deserialize)]
+struct OpenMeteoCurrent {
+    temperature_2m: f64,
+    weather_code: u8,
+}
+
+impl WeatherSource for OpenMeteo {
+    fn current(&self, latitude: f64, longitude: f64) -> Result<Weather, String> {
+        let body = self
+            .client
+            .get("https://api.open-meteo.com/v1/forecast")
+            .query(&[
+                ("latitude", latitude.to_string()),
+                ("longitude", longitude.to_string()),
+                ("current", "temperature_2m,weather_code".to_string()),
+            ])
+            .send()
+            .and_then(|response| response.error_for_status())
+            .and_then(|response| response.text())
+            .map_err(|e| e.to_string())?;
+        let response: OpenMeteoResponse = serde_json::from_str(&body).map_err(|e| e.to_string())?;
+        Ok(Weather {
+            temperature_c: response.current.temperature_2m,
+            conditions: wmo_conditions(response.current.weather_code).to_string(),
+            provider: WeatherProvider::OpenMeteo,
+        })
+    }
+}
+
+/// Describes a WMO weather interpretation code, as used by Open-Meteo.
+fn wmo_conditions(code: u8) -> &'static str {
+    match code {
+        0 => "Clear",
+        1 => "Mostly clear",
+        2 => "Partly cloudy",
+        3 => "Overcast",
+        45 | 48 => "Fog",
+        51 | 53 | 55 | 56 | 57 => "Drizzle",
+        61 | 66 | 80 => "Light rain",
+        63 => "Rain",
+        65 | 67 | 81 | 82 => "Heavy rain",
+        71 | 77 | 85 => "Light snow",
+        73 => "Snow",
+        75 | 86 => "Heavy snow",
+        95 => "Thunderstorm",
+        96 | 99 => "Thunderstorm with hail",
+        _ => "Unknown",
+    }
+}
+
+fn lookup(settings: &WeatherSettings) -> Result<Option<Weather>, String> {
+    let (true, Some(latitude), Some(longitude)) = (settings.enabled, settings.latitude, settings.longitude) else {
+        return Ok(None);
+    };
+    settings.provider.source()?.current(latitude, longitude).map(Some)
+}
+
+/// Records the current weather on a just-created entry, in the background.
+pub fn capture_for_new_entry(entry_id: i32) {
+    let settings = Settings::load().weather;
+    if !settings.enabled {
+        return;
+    }
+    thread::spawn(move || {
+        let weather = match lookup(&settings) {
+            Ok(Some(weather)) => weather,
+            Ok(None) => return,
+            Err(e) => {
+                info!("No weather for entry {}: {}", entry_id, e);
+                return;
+            }
+        };
+        let result = serde_json::to_string(&weather).map_err(|e| e.to_string()).and_then(|json| {
+            let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+            db.conn
+                .execute(
+                    "UPDATE journal_entries SET weather = ?1 WHERE id = ?2 AND weather IS NULL",
+                    rusqlite::params![json, entry_id],
+                )
+                .map_err(|e| e.to_string())
+        });
+        match result {
+            Ok(_) => debug!("Recorded weather for entry {}", entry_id),
+            Err(e) => info!("Failed to record weather for entry {}: {}", entry_id, e),
+        }
+    });
+}
+
+/// Reads the stored `weather` column; unreadable values are treated as absent.
+pub fn from_column(json: Option<String>) -> Option<Weather> {
+    json.and_then(|json| serde_json::from_str(&json).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wmo_conditions() {
+        assert_eq!(wmo_conditions(0), "Clear");
+        assert_eq!(wmo_conditions(63), "Rain");
+        assert_eq!(wmo_conditions(99), "Thunderstorm with hail");
+        assert_eq!(wmo_conditions(42), "Unknown");
+    }
+
+    #[test]
+    fn test_parse_open_meteo_response() {
+        let response: OpenMeteoResponse = serde_json::from_str(
+            r#"{"latitude": 51.5, "current": {"time": "2024-05-01T09:00", "interval": 900,
+                "temperature_2m": 14.2, "weather_code": 61}}"#,
+        )
+        .unwrap();
+        assert_eq!(response.current.temperature_2m, 14.2);
+        assert_eq!(wmo_conditions(response.current.weather_code), "Light rain");
+    }
+
+    #[test]
+    fn test_lookup_needs_opt_in_and_location() {
+        let mut settings = WeatherSettings { latitude: Some(51.5), longitude: Some(-0.1), ..Default::default() };
+        assert_eq!(lookup(&settings), Ok(None));
+        settings.enabled = true;
+        settings.longitude = None;
+        assert_eq!(lookup(&settings), Ok(None));
+    }
+
+    #[test]
+    fn test_weather_column_round_trip() {
+        let weather = Weather {
+            temperature_c: -3.5,
+            conditions: "Snow".to_string(),
+            provider: WeatherProvider::OpenMeteo,
+        };
+        assert_eq!(from_column(Some(serde_json::to_string(&weather).unwrap())), Some(weather));
+        assert_eq!(from_column(Some("not json".to_string())), None);
+        assert_eq!(from_column(None), None);
+    }
+}
@@ -0,0 +1,119 @@
+use log::{error, warn};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use tauri::{command, AppHandle};
+
+use journal_core::keychain::KeychainManager;
+use journal_core::profiles::database_path;
+
+use crate::settings::settings_path;
+use crate::{api_server, backup, cloud_backup, email_ingest, lan_sync, spotlight, sync};
+
+// Erasing the open profile's journal in one step, for someone who needs it
+// gone now. The key goes first: once it's out of the keychain and this
+// process, what's left on disk is unreadable even where overwriting doesn't
+// reach every copy (SSDs remap blocks, snapshots keep old ones). The database,
+// its backups and the settings are then overwritten and deleted, and the app
+// relaunches empty. Copies outside the app's control, such as a Git mirror,
+// a sync remote or exports, are left alone.
+
+/// What has to be typed to go ahead, so it can't happen by accident.
+const CONFIRMATION: &str = "ERASE MY JOURNAL";
+/// Keychain accounts holding the profile's other secrets.
+const SECRET_ACCOUNTS: &[&str] = &[
+    api_server::TOKEN_ACCOUNT,
+    sync::PASSPHRASE_ACCOUNT,
+    sync::CREDENTIAL_ACCOUNT,
+    lan_sync::PASSPHRASE_ACCOUNT,
+    cloud_backup::PASSPHRASE_ACCOUNT,
+    cloud_backup::CREDENTIAL_ACCOUNT,
+    email_ingest::PASSWORD_ACCOUNT,
+    email_ingest::TOKEN_ACCOUNT,
+];
+/// Files SQLite may keep next to the database.
+const DATABASE_SUFFIXES: &[&str] = &["", "-journal", "-wal", "-shm"];
+
+/// Overwrites `path` with zeros before deleting it. Missing files are fine.
+fn shred(path: &Path) -> io::Result<()> {
+    let len = match fs::metadata(path) {
+        Ok(metadata) => metadata.len(),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    let mut file = OpenOptions::new().write(true).open(path)?;
+    let zeros = [0u8; 64 * 1024];
+    let mut remaining = len;
+    while remaining > 0 {
+        let chunk = remaining.min(zeros.len() as u64) as usize;
+        file.write_all(&zeros[..chunk])?;
+        remaining -= chunk as u64;
+    }
+    file.sync_all()?;
+    drop(file);
+    fs::remove_file(path)
+}
+
+/// Every file the erase shreds, gathered before anything is removed, since
+/// the settings say where scheduled backups go.
+fn files_to_shred() -> Result<Vec<PathBuf>, String> {
+    let database = database_path().map_err(|e| e.to_string())?;
+    let mut files: Vec<PathBuf> = DATABASE_SUFFIXES
+        .iter()
+        .map(|suffix| PathBuf::from(format!("{}{}", database.display(), suffix)))
+        .collect();
+    files.extend(backup::all_backups()?);
+    files.extend(cloud_backup::pending_in(&cloud_backup::pending_dir()?)?);
+    files.push(settings_path().map_err(|e| e.to_string())?);
+    Ok(files)
+}
+
+/// Erases the open profile's journal, its backups, settings and keys, then
+/// relaunches. `confirmation` must be `CONFIRMATION`. Everything that can be
+/// erased is, even if some step fails; the failures are reported together.
+#[command]
+pub fn secure_wipe(app: AppHandle, confirmation: String) -> Result<(), String> {
+    if confirmation.trim() != CONFIRMATION {
+        return Err(format!("Type {} to erase the journal", CONFIRMATION));
+    }
+    warn!("Erasing the journal");
+    let files = files_to_shred()?;
+    let mut failures = Vec::new();
+    // Spotlight keeps entry titles of its own
+    if let Err(e) = spotlight::set_spotlight_indexing(false) {
+        failures.push(format!("Spotlight: {}", e));
+    }
+    if let Err(e) = KeychainManager::erase_key() {
+        failures.push(format!("database key: {}", e));
+    }
+    for account in SECRET_ACCOUNTS {
+        if let Err(e) = KeychainManager::delete_secret(account) {
+            failures.push(format!("{}: {}", account, e));
+        }
+    }
+    for file in &files {
+        if let Err(e) = shred(file) {
+            failures.push(format!("{}: {}", file.display(), e));
+        }
+    }
+    if !failures.is_empty() {
+        error!("Erase left some things behind: {:?}", failures);
+        return Err(format!("Some things couldn't be erased: {}", failures.join("; ")));
+    }
+    app.restart()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shred_removes_the_file() {
+        let path = std::env::temp_dir().join(format!("journal-wipe-{}.db", std::process::id()));
+        fs::write(&path, vec![7u8; 100_000]).unwrap();
+        shred(&path).unwrap();
+        assert!(!path.exists());
+        // Already gone is fine
+        shred(&path).unwrap();
+    }
+}
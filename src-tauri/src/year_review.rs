@@ -0,0 +1,280 @@
+use chrono::{Datelike, NaiveDate};
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::fs;
+use tauri::command;
+
+use crate::stats::{entry_day, longest_streak, Streak};
+use crate::{DatabaseManager, JournalEntry};
+
+// The annual retrospective: one year of the journal gathered into a single
+// document the frontend lays out, or exports as Markdown. Everything is
+// worked out from entry summaries and stored scores, so no bodies are loaded.
+
+const TOP_COUNT: usize = 10;
+
+#[derive(Debug, Serialize)]
+pub struct Ranked {
+    name: String,
+    entry_count: i64,
+}
+
+/// The brightest entry of a month, by sentiment and then length.
+#[derive(Debug, Serialize)]
+pub struct Highlight {
+    month: u32,
+    entry: JournalEntry,
+    sentiment: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct YearReview {
+    year: i32,
+    total_entries: i64,
+    total_words: i64,
+    average_words: f64,
+    days_written: i64,
+    /// Entries written in each month, January first.
+    entries_per_month: Vec<i64>,
+    top_tags: Vec<Ranked>,
+    top_people: Vec<Ranked>,
+    first_entry: Option<JournalEntry>,
+    last_entry: Option<JournalEntry>,
+    longest_entry: Option<JournalEntry>,
+    longest_streak: Option<Streak>,
+    highlights: Vec<Highlight>,
+}
+
+fn ranked(conn: &rusqlite::Connection, sql: &str, bounds: &(String, String)) -> rusqlite::Result<Vec<Ranked>> {
+    let mut stmt = conn.prepare(sql)?;
+    let ranked = stmt
+        .query_map(rusqlite::params![bounds.0, bounds.1, TOP_COUNT], |row| {
+            Ok(Ranked {
+                name: row.get(0)?,
+                entry_count: row.get(1)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(ranked)
+}
+
+fn year_review(conn: &rusqlite::Connection, year: i32) -> Result<YearReview, String> {
+    let january = |year| NaiveDate::from_ymd_opt(year, 1, 1).ok_or_else(|| format!("Invalid year {}", year));
+    let bounds = (january(year)?.to_string(), january(year + 1)?.to_string());
+    let in_year = "e.local_date >= ?1 AND e.local_date < ?2";
+
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {}, sentiment FROM journal_entries e WHERE {} ORDER BY created_ms ASC, id ASC",
+            JournalEntry::COLUMNS,
+            in_year
+        ))
+        .map_err(|e| e.to_string())?;
+    let entries = stmt
+        .query_map(rusqlite::params![bounds.0, bounds.1], |row| {
+            Ok((JournalEntry::from_row(row)?, row.get::<_, Option<f64>>(7)?))
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+
+    let mut days = BTreeSet::new();
+    let mut entries_per_month = vec![0; 12];
+    let mut best_of_month: Vec<Option<&(JournalEntry, Option<f64>)>> = vec![None; 12];
+    for candidate in &entries {
+        let Some(day) = entry_day(&candidate.0) else {
+            continue;
+        };
+        days.insert(day);
+        let month = day.month0() as usize;
+        entries_per_month[month] += 1;
+        let brightness = |(entry, sentiment): &(JournalEntry, Option<f64>)| {
+            (sentiment.unwrap_or(f64::NEG_INFINITY), entry.word_count)
+        };
+        let better = |best: &(JournalEntry, Option<f64>)| {
+            let (score, words) = brightness(candidate);
+            let (best_score, best_words) = brightness(best);
+            score.total_cmp(&best_score).then(words.cmp(&best_words)).is_gt()
+        };
+        if best_of_month[month].is_none_or(better) {
+            best_of_month[month] = Some(candidate);
+        }
+    }
+
+    let top_tags = ranked(
+        conn,
+        &format!(
+            "SELECT t.tag, COUNT(*) AS n FROM entry_tags t JOIN journal_entries e ON e.id = t.entry_id
+             WHERE {} GROUP BY t.tag ORDER BY n DESC, t.tag LIMIT ?3",
+            in_year
+        ),
+        &bounds,
+    )
+    .map_err(|e| e.to_string())?;
+    let top_people = ranked(
+        conn,
+        &format!(
+            "SELECT p.name, COUNT(*) AS n FROM people p
+             JOIN entry_people ep ON ep.person_id = p.id
+             JOIN journal_entries e ON e.id = ep.entry_id
+             WHERE {} GROUP BY p.id ORDER BY n DESC, p.name COLLATE NOCASE LIMIT ?3",
+            in_year
+        ),
+        &bounds,
+    )
+    .map_err(|e| e.to_string())?;
+
+    let total_entries = entries.len() as i64;
+    let total_words: i64 = entries.iter().map(|(entry, _)| entry.word_count).sum();
+    Ok(YearReview {
+        year,
+        total_entries,
+        total_words,
+        average_words: if total_entries > 0 { total_words as f64 / total_entries as f64 } else { 0.0 },
+        days_written: days.len() as i64,
+        entries_per_month,
+        top_tags,
+        top_people,
+        first_entry: entries.first().map(|(entry, _)| entry.clone()),
+        last_entry: entries.last().map(|(entry, _)| entry.clone()),
+        // The earliest of equally long entries
+        longest_entry: entries
+            .iter()
+            .rev()
+            .max_by_key(|(entry, _)| entry.word_count)
+            .map(|(entry, _)| entry.clone()),
+        longest_streak: longest_streak(&days),
+        highlights: best_of_month
+            .iter()
+            .zip(1..)
+            .filter_map(|(best, month)| {
+                best.map(|(entry, sentiment)| Highlight {
+                    month,
+                    entry: entry.clone(),
+                    sentiment: *sentiment,
+                })
+            })
+            .collect(),
+    })
+}
+
+fn month_name(month: u32) -> &'static str {
+    u8::try_from(month)
+        .ok()
+        .and_then(|m| chrono::Month::try_from(m).ok())
+        .map_or("", |m| m.name())
+}
+
+fn entry_line(entry: &JournalEntry) -> String {
+    let day = entry_day(entry).map(|d| d.to_string()).unwrap_or_default();
+    let title = if entry.title.trim().is_empty() { "Untitled" } else { entry.title.trim() };
+    format!("{} ({})", title, day)
+}
+
+/// The review as a Markdown document.
+fn to_markdown(review: &YearReview) -> String {
+    let mut md = format!("# {} in review\n\n", review.year);
+    md.push_str(&format!(
+        "{} entries and {} words, written on {} days ({:.0} words per entry on average).\n",
+        review.total_entries, review.total_words, review.days_written, review.average_words
+    ));
+    if let Some(streak) = &review.longest_streak {
+        md.push_str(&format!(
+            "\nLongest streak: {} days, {} to {}.\n",
+            streak.days, streak.start, streak.end
+        ));
+    }
+
+    md.push_str("\n## Month by month\n\n| Month | Entries |\n| --- | ---: |\n");
+    for (count, month) in review.entries_per_month.iter().zip(1..) {
+        md.push_str(&format!("| {} | {} |\n", month_name(month), count));
+    }
+    for (heading, ranked) in [("Top tags", &review.top_tags), ("People", &review.top_people)] {
+        if !ranked.is_empty() {
+            md.push_str(&format!("\n## {}\n\n", heading));
+            for item in ranked {
+                md.push_str(&format!("- {} ({})\n", item.name, item.entry_count));
+            }
+        }
+    }
+    if !review.highlights.is_empty() {
+        md.push_str("\n## Highlights\n\n");
+        for highlight in &review.highlights {
+            md.push_str(&format!("- **{}**: {}\n", month_name(highlight.month), entry_line(&highlight.entry)));
+        }
+    }
+    let bookends = [
+        ("First entry", &review.first_entry),
+        ("Last entry", &review.last_entry),
+        ("Longest entry", &review.longest_entry),
+    ];
+    if review.first_entry.is_some() {
+        md.push('\n');
+        for (label, entry) in bookends {
+            if let Some(entry) = entry {
+                md.push_str(&format!("- {}: {}\n", label, entry_line(entry)));
+            }
+        }
+    }
+    md
+}
+
+/// Stats, top tags and people, first and last entries, the longest streak and
+/// a highlight from each month of `year`, counted in local time.
+#[command]
+pub fn generate_year_review(year: i32) -> Result<YearReview, String> {
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    year_review(&db.conn, year)
+}
+
+/// Writes the review of `year` to `path` as Markdown.
+#[command]
+pub fn export_year_review(year: i32, path: String) -> Result<(), String> {
+    let db = DatabaseManager::new().map_err(|e| e.to_string())?;
+    let review = year_review(&db.conn, year)?;
+    fs::write(&path, to_markdown(&review)).map_err(|e| format!("Failed to write {}: {}", path, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use journal_core::{insert_entry, local_day_bounds, parse_entry_datetime};
+
+    fn day(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn test_year_review() {
+        let db = DatabaseManager::open_in_memory().unwrap();
+        let at = |d: &str| {
+            let (start, _) = local_day_bounds(day(d));
+            (parse_entry_datetime(&start).unwrap() + chrono::Duration::hours(12)).to_rfc3339()
+        };
+        insert_entry(&db.conn, "Old", "<p>last year</p>", &at("2023-12-31")).unwrap();
+        let first = insert_entry(&db.conn, "New year", "<p>A quiet start</p>", &at("2024-01-01")).unwrap();
+        insert_entry(&db.conn, "Skating", "<p>A wonderful, happy day with Noor</p>", &at("2024-01-02")).unwrap();
+        let last = insert_entry(&db.conn, "Rain", "<p>Tired and sad all day, long walk, long talk</p>", &at("2024-03-09"))
+            .unwrap();
+        db.conn
+            .execute("INSERT INTO entry_tags (entry_id, tag) VALUES (?1, 'winter')", rusqlite::params![first])
+            .unwrap();
+
+        let review = year_review(&db.conn, 2024).unwrap();
+        assert_eq!((review.total_entries, review.days_written), (3, 3));
+        assert_eq!(review.entries_per_month[..3], [2, 0, 1]);
+        assert_eq!(review.first_entry.as_ref().unwrap().id, first);
+        assert_eq!(review.last_entry.as_ref().unwrap().id, last);
+        assert_eq!(review.longest_entry.as_ref().unwrap().id, last);
+        assert_eq!(review.longest_streak.as_ref().unwrap().days, 2);
+        assert_eq!(review.top_tags[0].name, "winter");
+        assert_eq!(review.top_people[0].name, "Noor");
+        let highlights: Vec<_> = review.highlights.iter().map(|h| (h.month, h.entry.title.as_str())).collect();
+        assert_eq!(highlights, vec![(1, "Skating"), (3, "Rain")]);
+
+        let md = to_markdown(&review);
+        assert!(md.starts_with("# 2024 in review\n"));
+        assert!(md.contains("| March | 1 |") && md.contains("- **January**: Skating (2024-01-02)"));
+        assert!(md.contains("- First entry: New year (2024-01-01)"));
+    }
+}
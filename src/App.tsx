@@ -20,11 +20,37 @@ type Entry = {
   id: number;
   title: string;
   created_at: string;
+  local_date?: string | null;
+  snippet?: string;
+  word_count?: number;
+};
+
+type DropReport = {
+  created: number[];
+  attached: { token: string; url: string }[];
+  skipped: { name: string; reason: string }[];
+};
+
+type PrintableEntry = {
+  id: number;
+  title: string;
+  date: string;
+  body: string;
 };
 
 type Theme = 'system' | 'light' | 'dark';
 
-type KeychainStatus = "unknown" | "authorized" | "error" | "checking";
+type KeychainStatus = "unknown" | "authorized" | "error" | "checking" | "passphrase" | "recovery" | "denied" | "switch";
+
+// What the unlock screen asks for next, by the error_type of a failed unlock
+const nextKeychainStatus = (err: any): KeychainStatus =>
+  err?.error_type === "passphrase_required"
+    ? "passphrase"
+    : err?.error_type === "recovery_key_required"
+      ? "recovery"
+      : err?.error_type === "keychain_denied"
+        ? "denied"
+        : "error";
 
 export default function App() {
   const [selectedId, setSelectedId] = useState<number | null>(null);
@@ -39,6 +65,8 @@ export default function App() {
   const [isCollapsed, setIsCollapsed] = useState(false);
 
   const [showUpToDate, setShowUpToDate] = useState(false);
+  const [dropMessage, setDropMessage] = useState<string | null>(null);
+  const [printEntry, setPrintEntry] = useState<PrintableEntry | null>(null);
 
   // Session‑scoped flag: we remember the authorization only for the lifetime
   // of the current window. Fresh app launches will start as "unknown" again.
@@ -48,9 +76,14 @@ export default function App() {
     initialSessionAuthorized ? "authorized" : "unknown"
   );
   const [keychainError, setKeychainError] = useState<string | null>(null);
+  const [passphrase, setPassphrase] = useState("");
+  const [recoveryKey, setRecoveryKey] = useState("");
+  // When the backend will take the next passphrase or recovery key guess
+  const [unlockRetryAt, setUnlockRetryAt] = useState<number | null>(null);
+  const [, setClockTick] = useState(0);
 
   const refreshEntries = () => {
-    invoke<Entry[]>("get_entries")
+    invoke<Entry[]>("get_entries_preview")
       .then(setEntries)
       .catch((err) => console.error("Failed to fetch entries:", err));
   };
@@ -68,7 +101,7 @@ export default function App() {
       clearTimeout(inactivityTimer.current);
     }
     inactivityTimer.current = setTimeout(() => {
-      setIsBlurred(true);
+      invoke('set_privacy_screen', { engaged: true });
     }, INACTIVITY_DURATION);
   };
 
@@ -78,7 +111,7 @@ export default function App() {
 
   const handleClick = () => {
     if (isBlurred) {
-      setIsBlurred(false);
+      invoke('set_privacy_screen', { engaged: false });
       resetInactivityTimer();
     }
   };
@@ -91,7 +124,7 @@ export default function App() {
 
   const handleImportComplete = async () => {
     // Refresh the entries list after import
-    const entries = await invoke<Entry[]>('get_entries');
+    const entries = await invoke<Entry[]>('get_entries_preview');
     setEntries(entries);
     // If there are entries, select the most recent one
     if (entries.length > 0) {
@@ -174,21 +207,6 @@ export default function App() {
     };
   }, []);
 
-  useEffect(() => {
-    const handleKeyDown = (e: KeyboardEvent) => {
-      if (e.ctrlKey && e.key === 'b') {
-        setIsBlurred(prev => !prev);
-        resetInactivityTimer();
-      }
-      if (e.ctrlKey && e.key === 'n') {
-        handleCreateNewEntry();
-      }
-    };
-
-    window.addEventListener('keydown', handleKeyDown);
-    return () => window.removeEventListener('keydown', handleKeyDown);
-  }, []);
-
 
 useEffect(() => {
   const unlisten = listen('open-settings', () => {
@@ -237,16 +255,122 @@ useEffect( () => {
     handleCreateNewEntry();
   })
 
-  const unlistenBlur = listen('blur', () => {
-    setIsBlurred(prev => !prev);
+  // The privacy screen is raised and lowered by the backend, for every window
+  const unlistenBlur = listen<boolean>('privacy-screen', (event) => {
+    setIsBlurred(event.payload);
+  });
+
+  const unlistenToday = listen('open-today', async () => {
+    try {
+      const today = await invoke<{ id: number }>('get_or_create_today');
+      refreshEntries();
+      setSelectedId(today.id);
+      setShowSettings(false);
+    } catch (err) {
+      console.error('Failed to open today\'s entry:', err);
+    }
+  });
+
+  const unlistenChanged = listen('entries-changed', () => {
+    refreshEntries();
+  });
+
+  const unlistenOpen = listen<number>('open-entry', (event) => {
+    refreshEntries();
+    setSelectedId(event.payload);
+    setShowSettings(false);
+  });
+
+  const unlistenDropped = listen<DropReport>('files-dropped', (event) => {
+    const { created, attached, skipped } = event.payload;
+    refreshEntries();
+    if (created.length > 0) {
+      setSelectedId(created[0]);
+      setShowSettings(false);
+    }
+    if (attached.length > 0 || skipped.length > 0) {
+      const lines = [
+        ...(created.length > 0 ? [`Imported ${created.length} ${created.length === 1 ? 'entry' : 'entries'}.`] : []),
+        ...(attached.length > 0 ? [`Attached ${attached.length} ${attached.length === 1 ? 'image' : 'images'}.`] : []),
+        ...skipped.map(file => `${file.name}: ${file.reason}`),
+      ];
+      setDropMessage(lines.join('\n'));
+    }
+  });
+
+  const unlistenLocked = listen('journal-locked', () => {
+    sessionStorage.removeItem("sessionAuthorized");
+    setKeychainStatus("unknown");
+  });
+
+  // Another window unlocked the journal
+  const unlistenKeychain = listen<{ unlocked: boolean }>('keychain-status', (event) => {
+    if (event.payload.unlocked) {
+      setKeychainStatus("authorized");
+      sessionStorage.setItem("sessionAuthorized", "true");
+    }
   });
 
   return () => {
     unlistenNew.then(f => f());
     unlistenBlur.then(f => f());
+    unlistenToday.then(f => f());
+    unlistenChanged.then(f => f());
+    unlistenOpen.then(f => f());
+    unlistenLocked.then(f => f());
+    unlistenKeychain.then(f => f());
+    unlistenDropped.then(f => f());
   };
 })
 
+useEffect(() => {
+  invoke<boolean>('get_privacy_screen').then(setIsBlurred);
+}, []);
+
+// Wrong passphrase or recovery key guesses earn a growing wait
+useEffect(() => {
+  const unlisten = listen<{ secret: string; retry_after_secs: number }>('unlock-throttled', (event) => {
+    if (event.payload.secret === 'journal' && event.payload.retry_after_secs > 0) {
+      setUnlockRetryAt(Date.now() + event.payload.retry_after_secs * 1000);
+    }
+  });
+  return () => {
+    unlisten.then((f) => f());
+  };
+}, []);
+
+useEffect(() => {
+  if (unlockRetryAt === null) return;
+  const timer = setInterval(() => {
+    if (Date.now() >= unlockRetryAt) setUnlockRetryAt(null);
+    setClockTick((tick) => tick + 1);
+  }, 1000);
+  return () => clearInterval(timer);
+}, [unlockRetryAt]);
+
+const unlockWaitSecs = unlockRetryAt === null ? 0 : Math.max(0, Math.ceil((unlockRetryAt - Date.now()) / 1000));
+
+// The backend fills the print view, which goes to the print dialog once rendered
+useEffect(() => {
+  const unlisten = listen<PrintableEntry>('print-entry', (event) => {
+    setPrintEntry(event.payload);
+  });
+  return () => {
+    unlisten.then((f) => f());
+  };
+}, []);
+
+useEffect(() => {
+  if (!printEntry) return;
+  invoke('print_window').catch((err) => console.error('Failed to print:', err));
+}, [printEntry]);
+
+useEffect(() => {
+  invoke('set_current_entry', { id: selectedId }).catch((err) =>
+    console.error('Failed to set current entry:', err)
+  );
+}, [selectedId]);
+
 const handleInstallUpdate = async () => {
   if (!updateRef.current) return;
   setUpdateInfo(null);
@@ -272,20 +396,46 @@ const handleDismissUpToDate = () => setShowUpToDate(false);
       // Remember for the rest of this session (window). Not persisted across re‑launches.
       sessionStorage.setItem("sessionAuthorized", "true");
     } catch (err: any) {
-      setKeychainStatus("error");
-      setKeychainError(err?.toString() || "Failed to access keychain.");
+      // Without a usable keychain, the key comes from a passphrase or recovery key
+      setKeychainStatus(nextKeychainStatus(err));
+      setKeychainError(err?.message || err?.toString() || "Failed to access keychain.");
     }
   };
 
+  const handleUnlockWith = async (command: string, args: Record<string, string | null> = {}) => {
+    try {
+      await invoke(command, args);
+      setPassphrase("");
+      setRecoveryKey("");
+      setKeychainStatus("authorized");
+      sessionStorage.setItem("sessionAuthorized", "true");
+    } catch (err: any) {
+      if (err?.error_type !== "unlock_throttled") {
+        setKeychainStatus(nextKeychainStatus(err));
+      }
+      setKeychainError(err?.message || err?.toString() || "Failed to unlock the journal.");
+    }
+  };
+
+  const handleUnlockWithPassphrase = () => handleUnlockWith("unlock_with_passphrase", { passphrase });
+
+  const handleRestoreWithRecoveryKey = () =>
+    handleUnlockWith("restore_with_recovery_key", { recoveryKey });
+
+  const handleRetryKeychain = () => handleUnlockWith("retry_keychain_access");
+
+  const handleSwitchToPassphrase = () =>
+    handleUnlockWith("switch_to_passphrase", { passphrase, recoveryKey: recoveryKey || null });
+
   // Load entries once we are authorized
   useEffect(() => {
     if (keychainStatus === "authorized") {
       (async () => {
-        const loadedEntries = await invoke<Entry[]>("get_entries");
+        const loadedEntries = await invoke<Entry[]>("get_entries_preview");
         if (loadedEntries.length === 0) {
           // No entries: create a new one, then reload
           const newId = await createNewEntry();
-          const newEntries = await invoke<Entry[]>("get_entries");
+          const newEntries = await invoke<Entry[]>("get_entries_preview");
           setEntries(newEntries);
           setSelectedId(newId);
         } else {
@@ -302,7 +452,121 @@ const handleDismissUpToDate = () => setShowUpToDate(false);
   return (
     <>
       {/* Keychain authorization modal - only show if not authorized */}
-      {keychainStatus !== "authorized" && (
+      {keychainStatus === "passphrase" && (
+        <Modal
+          visible={true}
+          header="Journal passphrase"
+          body={
+            <div>
+              <p className="mb-2">{keychainError}</p>
+              {unlockWaitSecs > 0 && <p className="mb-2 text-red-600">Try again in {unlockWaitSecs}s.</p>}
+              <input
+                type="password"
+                autoFocus
+                value={passphrase}
+                onChange={(e) => setPassphrase(e.target.value)}
+                onKeyDown={(e) => {
+                  if (e.key === "Enter") handleUnlockWithPassphrase();
+                }}
+                className="w-full px-3 py-2 border rounded text-black"
+                placeholder="Passphrase"
+              />
+            </div>
+          }
+          onClose={() => {}}
+          primaryButton={{ label: "Unlock", onClick: handleUnlockWithPassphrase }}
+        />
+      )}
+      {keychainStatus === "recovery" && (
+        <Modal
+          visible={true}
+          header="Recovery key"
+          body={
+            <div>
+              <p className="mb-2">{keychainError}</p>
+              {unlockWaitSecs > 0 && <p className="mb-2 text-red-600">Try again in {unlockWaitSecs}s.</p>}
+              <input
+                type="text"
+                autoFocus
+                spellCheck={false}
+                value={recoveryKey}
+                onChange={(e) => setRecoveryKey(e.target.value)}
+                onKeyDown={(e) => {
+                  if (e.key === "Enter") handleRestoreWithRecoveryKey();
+                }}
+                className="w-full px-3 py-2 border rounded text-black font-mono"
+                placeholder="XXXX-XXXX-XXXX-…"
+              />
+            </div>
+          }
+          onClose={() => {}}
+          primaryButton={{ label: "Restore", onClick: handleRestoreWithRecoveryKey }}
+        />
+      )}
+      {keychainStatus === "denied" && (
+        <Modal
+          visible={true}
+          header="Keychain access denied"
+          body={
+            <div>
+              <p className="text-red-600 mb-2">{keychainError || "The keychain refused to hand over the journal's key."}</p>
+              <p>
+                Allow Journal in Keychain Access and try again, or keep the journal's key with a passphrase you
+                enter each time it opens instead.
+              </p>
+            </div>
+          }
+          onClose={() => {}}
+          primaryButton={{ label: "Try Again", onClick: handleRetryKeychain }}
+          secondaryButton={{
+            label: "Use a Passphrase",
+            onClick: () => {
+              setKeychainError(null);
+              setKeychainStatus("switch");
+            },
+          }}
+        />
+      )}
+      {keychainStatus === "switch" && (
+        <Modal
+          visible={true}
+          header="Use a passphrase"
+          body={
+            <div>
+              {keychainError && <p className="text-red-600 mb-2">{keychainError}</p>}
+              {unlockWaitSecs > 0 && <p className="mb-2 text-red-600">Try again in {unlockWaitSecs}s.</p>}
+              <p className="mb-2">
+                Choose a passphrase to unlock your journal with. If you already have entries, enter your recovery key
+                too so they can be moved over.
+              </p>
+              <input
+                type="password"
+                autoFocus
+                value={passphrase}
+                onChange={(e) => setPassphrase(e.target.value)}
+                className="w-full px-3 py-2 mb-2 border rounded text-black"
+                placeholder="New passphrase"
+              />
+              <input
+                type="text"
+                spellCheck={false}
+                value={recoveryKey}
+                onChange={(e) => setRecoveryKey(e.target.value)}
+                onKeyDown={(e) => {
+                  if (e.key === "Enter") handleSwitchToPassphrase();
+                }}
+                className="w-full px-3 py-2 border rounded text-black font-mono"
+                placeholder="Recovery key (XXXX-XXXX-XXXX-…)"
+              />
+            </div>
+          }
+          onClose={() => {}}
+          primaryButton={{ label: "Switch", onClick: handleSwitchToPassphrase }}
+          secondaryButton={{ label: "Back", onClick: () => setKeychainStatus("denied") }}
+        />
+      )}
+      {keychainStatus !== "authorized" && keychainStatus !== "passphrase" && keychainStatus !== "recovery" &&
+        keychainStatus !== "denied" && keychainStatus !== "switch" && (
         <Modal
           visible={true}
           header={keychainStatus === "error" ? "Keychain access error" : "Keychain access required"}
@@ -357,9 +621,26 @@ const handleDismissUpToDate = () => setShowUpToDate(false);
           }
         />
       )}
+      {dropMessage && (
+        <Modal
+          visible={true}
+          header="Dropped files"
+          onClose={() => setDropMessage(null)}
+          primaryButton={{ label: 'OK', onClick: () => setDropMessage(null) }}
+          body={<p className="whitespace-pre-line">{dropMessage}</p>}
+        />
+      )}
+      {printEntry && (
+        <article className="print-only">
+          <h1>{printEntry.title}</h1>
+          <time>{printEntry.date}</time>
+          <div dangerouslySetInnerHTML={{ __html: printEntry.body }} />
+        </article>
+      )}
       {/* Only render the main app if authorized */}
       {keychainStatus === "authorized" && (
         <div 
+          className="screen-only"
           onClick={handleClick}
           style={{ 
             display: "flex",
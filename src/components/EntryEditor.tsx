@@ -11,6 +11,7 @@ import { Extension } from '@tiptap/core';
 import { Plugin } from 'prosemirror-state';
 import { openUrl } from '@tauri-apps/plugin-opener';
 import { readText } from '@tauri-apps/plugin-clipboard-manager';
+import { confirm } from '@tauri-apps/plugin-dialog';
 
 const PasteLinkOnSelection = Extension.create({
   name: 'pasteLinkOnSelection',
@@ -47,6 +48,9 @@ export default function EntryEditor({ selectedId, refreshEntries, updateEntryTit
     const [title, setTitle] = useState("");
     const [body, setBody] = useState("");
     const [createdAt, setCreatedAt] = useState<string | null>(null);
+    const [sealed, setSealed] = useState(false);
+    // Version of the text loaded into the editor; a ref so autosave doesn't re-run when it changes
+    const versionRef = useRef<number | null>(null);
 
     const titleRef = useRef<HTMLTextAreaElement>(null);
 
@@ -74,16 +78,24 @@ export default function EntryEditor({ selectedId, refreshEntries, updateEntryTit
       },
     });
 
+    const loadEntry = (id: number) =>
+      invoke<{ title: string; body: string; created_at: string; sealed: boolean; version: number }>("get_entry", { id })
+        .then((entry) => {
+          setTitle(entry.title);
+          setBody(entry.body || "");
+          setCreatedAt(entry.created_at);
+          setSealed(entry.sealed);
+          versionRef.current = entry.version;
+          editor?.commands.setContent(entry.body || '');
+          return entry;
+        });
+
     useEffect(() => {
       if (!editor) return;
 
       if (selectedId !== null) {
-        invoke<{ title: string; body: string; created_at: string }>("get_entry", { id: selectedId })
+        loadEntry(selectedId)
           .then((entry) => {
-            setTitle(entry.title);
-            setBody(entry.body || "");
-            setCreatedAt(entry.created_at);
-            editor.commands.setContent(entry.body || '');
             requestAnimationFrame(() => {
               if (entry.title.trim().length === 0) {
                 titleRef.current?.focus();
@@ -97,12 +109,18 @@ export default function EntryEditor({ selectedId, refreshEntries, updateEntryTit
         setTitle("");
         setBody("");
         setCreatedAt(null);
+        setSealed(false);
+        versionRef.current = null;
         editor.commands.clearContent();
         requestAnimationFrame(() => {
           titleRef.current?.focus();
         });
       }
     }, [selectedId, editor]);
+
+    useEffect(() => {
+      editor?.setEditable(!sealed);
+    }, [editor, sealed]);
   // Ensure title textarea grows to fit initial content
   useEffect(() => {
     if (titleRef.current) {
@@ -113,20 +131,51 @@ export default function EntryEditor({ selectedId, refreshEntries, updateEntryTit
   
     useEffect(() => {
       const timeout = setTimeout(() => {
-        if (selectedId !== null && (title.trim() || body.trim())) {
-          invoke("save_entry", { id: selectedId, title, body })
-            .then(() => {
+        if (selectedId !== null && !sealed && (title.trim() || body.trim())) {
+          invoke<number>("save_entry", { id: selectedId, title, body, version: versionRef.current })
+            .then((version) => {
+              versionRef.current = version;
               console.log("Autosaved");
               if (title.trim()) {
                 refreshEntries();
               }
             })
-            .catch((err) => console.error("Save error:", err));
+            .catch(async (err) => {
+              if (!String(err).startsWith("save_conflict")) {
+                console.error("Save error:", err);
+                return;
+              }
+              const reload = await confirm(
+                'This entry was changed in another window. Load the newer version, or keep your edits and save over it?',
+                { title: 'Entry changed', kind: 'warning', okLabel: 'Load newer', cancelLabel: 'Keep mine' }
+              );
+              if (reload) {
+                loadEntry(selectedId).catch((err) => console.error("Load error:", err));
+              } else {
+                // The next autosave overwrites the other version
+                versionRef.current = null;
+              }
+            });
         }
       }, 1000); // autosave after 1s of pause
   
       return () => clearTimeout(timeout);
-    }, [title, body, selectedId, refreshEntries]);
+    }, [title, body, selectedId, sealed, refreshEntries]);
+
+    const handleUnseal = async () => {
+      if (selectedId === null) return;
+      const confirmed = await confirm(
+        'This entry is sealed so it stays as it was written. Unseal it for editing until the journal is locked?',
+        { title: 'Unseal entry', kind: 'warning' }
+      );
+      if (!confirmed) return;
+      try {
+        await invoke('unseal_entry', { id: selectedId });
+        setSealed(false);
+      } catch (err) {
+        console.error('Unseal error:', err);
+      }
+    };
   
     const handleTitleChange = (e: React.ChangeEvent<HTMLTextAreaElement>) => {
      // Auto-resize textarea height
@@ -139,17 +188,11 @@ export default function EntryEditor({ selectedId, refreshEntries, updateEntryTit
      }
    };
 
+    // Cut, copy, undo and redo come from the native Edit menu and the
+    // editor's own history; only paste needs help turning URLs into links.
     const handleKeyDown = (e: React.KeyboardEvent) => {
       if (!(e.metaKey || e.ctrlKey)) return;
       switch (e.key.toLowerCase()) {
-        case 'x':
-          document.execCommand('cut');
-          e.preventDefault();
-          break;
-        case 'c':
-          document.execCommand('copy');
-          e.preventDefault();
-          break;
         case 'v': {
           e.preventDefault();
           readText().then((text) => {
@@ -163,10 +206,6 @@ export default function EntryEditor({ selectedId, refreshEntries, updateEntryTit
           });
           break;
         }
-        case 'z':
-          document.execCommand(e.shiftKey ? 'redo' : 'undo');
-          e.preventDefault();
-          break;
         default:
           break;
       }
@@ -194,6 +233,14 @@ export default function EntryEditor({ selectedId, refreshEntries, updateEntryTit
                 {new Date(createdAt).toLocaleDateString()}
               </time>
             )}
+          {sealed && (
+            <p className="text-sm text-gray-500 mt-1">
+              Sealed. This entry can no longer be changed.{' '}
+              <button type="button" className="underline" onClick={handleUnseal}>
+                Unseal
+              </button>
+            </p>
+          )}
           <label>
             <textarea
               ref={titleRef}
@@ -201,6 +248,7 @@ export default function EntryEditor({ selectedId, refreshEntries, updateEntryTit
               placeholder="Title"
               value={title}
               onInput={handleTitleChange}
+              readOnly={sealed}
               rows={1}
               style={{ height: 'auto' }}
             />
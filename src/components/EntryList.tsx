@@ -7,8 +7,17 @@ type Entry = {
   id: number;
   title: string;
   created_at: string;
+  // The day it was written on where it was written, as YYYY-MM-DD
+  local_date?: string | null;
+  // The start of the body as plain text, empty for locked entries
+  snippet?: string;
+  word_count?: number;
 };
 
+// Midnight of the day it was written, so the date shown doesn't move with the viewer's time zone
+const writtenOn = (entry: Entry) =>
+  entry.local_date ? new Date(`${entry.local_date}T00:00:00`) : new Date(entry.created_at);
+
 type Props = {
   entries: Entry[];
   onSelect: (id: number | null) => void;
@@ -39,6 +48,24 @@ export default function EntryList({
         console.error('Delete entry error:', err);
       }
     };
+
+    const handleOpenInWindow = async (id: number) => {
+      try {
+        await invoke('open_entry_window', { id });
+        setMenuForId(null);
+      } catch (err) {
+        console.error('Open entry window error:', err);
+      }
+    };
+
+    const handleCopy = async (id: number, format: 'markdown' | 'plaintext' | 'html') => {
+      try {
+        await invoke('copy_entry_to_clipboard', { id, format });
+        setMenuForId(null);
+      } catch (err) {
+        console.error('Copy entry error:', err);
+      }
+    };
   
   return (
     <div 
@@ -83,11 +110,26 @@ export default function EntryList({
               >
                 {entry.title || 'Untitled'}
               </span>
+              {entry.snippet && (
+                <span
+                  className="text-xs text-gray-500"
+                  style={{
+                    display: 'block',
+                    whiteSpace: 'nowrap',
+                    overflow: 'hidden',
+                    textOverflow: 'ellipsis',
+                    fontWeight: 400,
+                  }}
+                >
+                  {entry.snippet}
+                </span>
+              )}
               <time
               dateTime={entry.created_at}
               className="text-xs text-gray-500 block"
               >
-                {new Date(entry.created_at).toLocaleDateString()}
+                {writtenOn(entry).toLocaleDateString()}
+                {entry.word_count ? ` · ${entry.word_count} ${entry.word_count === 1 ? 'word' : 'words'}` : ''}
               </time>
             </button>
               <Tooltip.Root
@@ -110,6 +152,30 @@ export default function EntryList({
                     sideOffset={5} 
                     className="tooltip-content"
                   >
+                    <button
+                      className="open-window-button"
+                      onClick={() => handleOpenInWindow(entry.id)}
+                    >
+                      Open in New Window
+                    </button>
+                    <button
+                      className="open-window-button"
+                      onClick={() => handleCopy(entry.id, 'markdown')}
+                    >
+                      Copy as Markdown
+                    </button>
+                    <button
+                      className="open-window-button"
+                      onClick={() => handleCopy(entry.id, 'plaintext')}
+                    >
+                      Copy as Plain Text
+                    </button>
+                    <button
+                      className="open-window-button"
+                      onClick={() => handleCopy(entry.id, 'html')}
+                    >
+                      Copy as HTML
+                    </button>
                     <button
                       className="delete-entry-button"
                       onClick={() => handleDelete(entry.id)}
@@ -0,0 +1,51 @@
+import { useCallback, useEffect, useState } from "react";
+import { invoke } from "@tauri-apps/api/core";
+import { emit, listen } from "@tauri-apps/api/event";
+import { getCurrentWindow } from "@tauri-apps/api/window";
+import EntryEditor from "./EntryEditor";
+
+// A single entry in a window of its own, opened from the entry list. Saves
+// are announced so the main window's list stays current.
+export default function EntryWindow({ id }: { id: number }) {
+  const [isBlurred, setIsBlurred] = useState(false);
+
+  const refreshEntries = useCallback(() => {
+    emit("entries-changed");
+  }, []);
+
+  const updateEntryTitle = useCallback((_id: number, title: string) => {
+    getCurrentWindow().setTitle(title.trim() || "Untitled");
+  }, []);
+
+  useEffect(() => {
+    invoke<boolean>("get_privacy_screen").then(setIsBlurred);
+    const unlistenBlur = listen<boolean>("privacy-screen", (event) => {
+      setIsBlurred(event.payload);
+    });
+    const unlistenLocked = listen("journal-locked", () => {
+      getCurrentWindow().close();
+    });
+    return () => {
+      unlistenBlur.then((f) => f());
+      unlistenLocked.then((f) => f());
+    };
+  }, []);
+
+  const handleClick = () => {
+    if (isBlurred) invoke("set_privacy_screen", { engaged: false });
+  };
+
+  return (
+    <div
+      onClick={handleClick}
+      className="h-screen bg-white dark:bg-neutral-900 text-black dark:text-white"
+      style={{
+        filter: isBlurred ? "blur(8px)" : "none",
+        transition: "filter 0.3s ease",
+        cursor: isBlurred ? "pointer" : "default",
+      }}
+    >
+      <EntryEditor selectedId={id} refreshEntries={refreshEntries} updateEntryTitle={updateEntryTitle} />
+    </div>
+  );
+}
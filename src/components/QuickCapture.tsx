@@ -0,0 +1,58 @@
+import { useEffect, useRef, useState } from "react";
+import { invoke } from "@tauri-apps/api/core";
+import { getCurrentWindow } from "@tauri-apps/api/window";
+
+// The tray's capture box: a line or two appended to today's entry.
+export default function QuickCapture() {
+  const [text, setText] = useState("");
+  const [error, setError] = useState<string | null>(null);
+  const inputRef = useRef<HTMLTextAreaElement>(null);
+
+  useEffect(() => {
+    const unlisten = getCurrentWindow().onFocusChanged(({ payload: focused }) => {
+      if (focused) inputRef.current?.focus();
+    });
+    return () => {
+      unlisten.then((f) => f());
+    };
+  }, []);
+
+  const save = async () => {
+    if (!text.trim()) return;
+    try {
+      await invoke<number>("quick_capture", { text });
+      setText("");
+      setError(null);
+    } catch (err: any) {
+      setError(err?.toString() || "Could not save.");
+    }
+  };
+
+  const handleKeyDown = (e: React.KeyboardEvent) => {
+    if (e.key === "Enter" && (e.metaKey || e.ctrlKey)) {
+      e.preventDefault();
+      save();
+    }
+    if (e.key === "Escape") {
+      getCurrentWindow().hide();
+    }
+  };
+
+  return (
+    <div className="h-screen flex flex-col p-3 gap-2 bg-white dark:bg-neutral-900 text-black dark:text-white">
+      <textarea
+        ref={inputRef}
+        autoFocus
+        value={text}
+        onChange={(e) => setText(e.target.value)}
+        onKeyDown={handleKeyDown}
+        placeholder="Add to today's entry…"
+        className="flex-1 resize-none bg-transparent outline-none"
+      />
+      <div className="flex items-center justify-between text-xs text-gray-500">
+        <span className="text-red-600">{error}</span>
+        <span>⌘↵ to save · esc to close</span>
+      </div>
+    </div>
+  );
+}
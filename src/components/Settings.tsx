@@ -7,6 +7,38 @@ import { getVersion } from '@tauri-apps/api/app';
 
 type Theme = 'system' | 'light' | 'dark';
 
+type CipherParams = {
+  page_size: number;
+  kdf_iter: number;
+  hmac_algorithm: 'HMAC_SHA1' | 'HMAC_SHA256' | 'HMAC_SHA512';
+};
+
+type SecurityStatus = {
+  encrypted: boolean;
+  cipher_provider: string | null;
+  cipher_version: string | null;
+  cipher_params: CipherParams | null;
+  key_storage: 'keychain' | 'passphrase';
+  last_key_rotation: string | null;
+  plaintext_key_files: string[];
+};
+
+type AuditEvent = {
+  id: number;
+  occurred_at: string;
+  action: string;
+  detail: string;
+};
+
+const AUDIT_ACTIONS: Record<string, string> = {
+  key_migration: 'Key moved',
+  key_rotation: 'Key changed',
+  import: 'Journal replaced',
+  export: 'Exported',
+  delete_all: 'All entries deleted',
+  unseal: 'Entry unsealed',
+};
+
 type Props = {
   currentTheme: Theme;
   onThemeChange: (theme: Theme) => void;
@@ -18,6 +50,14 @@ export default function Settings({ currentTheme, onThemeChange, onImportComplete
   const [importStatus, setImportStatus] = useState<string>('');
   const [deleteStatus, setDeleteStatus] = useState<string>('');
   const [appVersion, setAppVersion] = useState<string | null>(null);
+  const [recoveryKey, setRecoveryKey] = useState<string | null>(null);
+  const [recoveryStatus, setRecoveryStatus] = useState<string>('');
+  const [wipeConfirmation, setWipeConfirmation] = useState<string>('');
+  const [wipeStatus, setWipeStatus] = useState<string>('');
+  const [security, setSecurity] = useState<SecurityStatus | null>(null);
+  const [auditLog, setAuditLog] = useState<AuditEvent[] | null>(null);
+  const [encryptionStatus, setEncryptionStatus] = useState<string>('');
+  const [cipherParams, setCipherParams] = useState<CipherParams | null>(null);
 
   const handleExport = async () => {
     try {
@@ -75,9 +115,79 @@ export default function Settings({ currentTheme, onThemeChange, onImportComplete
     }
   };
 
+  const handleSetEncryption = async (enabled: boolean) => {
+    const confirmed = await confirm(
+      enabled
+        ? 'Encrypt the journal again with its key? A backup is made first.'
+        : 'Store the journal as plain SQLite? Anyone who can read the file, or its backups from now on, can read ' +
+          'every entry. Only do this if the folder it is in is already encrypted. A backup is made first.',
+      {
+        title: enabled ? 'Encrypt journal?' : 'Stop encrypting journal?',
+        okLabel: enabled ? 'Encrypt' : 'Stop encrypting',
+        cancelLabel: 'Cancel'
+      }
+    );
+    if (!confirmed) return;
+    try {
+      setEncryptionStatus(enabled ? 'Encrypting…' : 'Decrypting…');
+      await invoke('set_encryption_enabled', { enabled });
+      const status = await invoke<SecurityStatus>('get_security_status');
+      setSecurity(status);
+      setCipherParams(status.cipher_params);
+      setEncryptionStatus('');
+    } catch (error: any) {
+      setEncryptionStatus(`Failed: ${error?.message ?? error}`);
+    }
+  };
+
+  const handleApplyCipherParams = async () => {
+    if (!cipherParams) return;
+    const confirmed = await confirm(
+      'Re-encrypt the journal with these settings? A backup is made first. Backups made before can only be ' +
+        'restored after switching back.',
+      { title: 'Change encryption settings?', okLabel: 'Re-encrypt', cancelLabel: 'Cancel' }
+    );
+    if (!confirmed) return;
+    try {
+      setEncryptionStatus('Re-encrypting…');
+      await invoke('set_cipher_params', { params: cipherParams });
+      const status = await invoke<SecurityStatus>('get_security_status');
+      setSecurity(status);
+      setCipherParams(status.cipher_params);
+      setEncryptionStatus('');
+    } catch (error: any) {
+      setEncryptionStatus(`Failed: ${error?.message ?? error}`);
+    }
+  };
+
+  const handleShowRecoveryKey = async () => {
+    try {
+      setRecoveryKey(await invoke<string>('generate_recovery_key'));
+      setRecoveryStatus('');
+    } catch (error) {
+      setRecoveryStatus(`Couldn't show the recovery key: ${error}`);
+      setTimeout(() => setRecoveryStatus(''), 3000);
+    }
+  };
+
+  const handleSecureWipe = async () => {
+    try {
+      // The app relaunches once everything is erased
+      await invoke('secure_wipe', { confirmation: wipeConfirmation });
+    } catch (error) {
+      setWipeStatus(`${error}`);
+    }
+  };
+
   
 useEffect(() => {
   getVersion().then(setAppVersion);
+  invoke<SecurityStatus>('get_security_status')
+    .then((status) => {
+      setSecurity(status);
+      setCipherParams(status.cipher_params);
+    })
+    .catch(() => setSecurity(null));
 }, []);
 
   return (
@@ -163,6 +273,214 @@ useEffect(() => {
           </div>
         )}
       </div>
+      {security && (
+        <div style={{ marginBottom: '2rem' }}>
+          <h3 style={{ marginBottom: '1rem' }} className='font-semibold'>Encryption</h3>
+          {security.encrypted ? (
+            <p>SQLCipher {security.cipher_version} ({security.cipher_provider})</p>
+          ) : (
+            <p style={{ color: '#ff4444' }}>Not encrypted: the journal is stored as plain SQLite</p>
+          )}
+          <p>
+            Key kept {security.key_storage === 'passphrase' ? 'nowhere; it comes from your passphrase' : 'in the system keychain'}
+          </p>
+          <p>
+            {security.last_key_rotation
+              ? `Key last changed ${new Date(security.last_key_rotation).toLocaleDateString()}`
+              : 'Key unchanged since the journal was created'}
+          </p>
+          {security.plaintext_key_files.length > 0 && (
+            <p style={{ color: '#ff4444' }}>
+              Unprotected key files found: {security.plaintext_key_files.join(', ')}
+            </p>
+          )}
+          {security.encrypted && cipherParams && (
+            <div style={{ display: 'flex', gap: '1rem', alignItems: 'center', marginTop: '1rem', flexWrap: 'wrap' }}>
+              <label>
+                KDF iterations{' '}
+                <input
+                  type="number"
+                  min={256000}
+                  step={1000}
+                  value={cipherParams.kdf_iter}
+                  onChange={(e) => setCipherParams({ ...cipherParams, kdf_iter: Number(e.target.value) })}
+                  style={{ width: '8rem' }}
+                />
+              </label>
+              <label>
+                Page size{' '}
+                <Select
+                  value={String(cipherParams.page_size)}
+                  onChange={(e) => setCipherParams({ ...cipherParams, page_size: Number(e.target.value) })}
+                  options={['1024', '4096', '8192', '16384', '32768', '65536'].map((size) => ({ value: size, label: size }))}
+                />
+              </label>
+              <label>
+                HMAC{' '}
+                <Select
+                  value={cipherParams.hmac_algorithm}
+                  onChange={(e) =>
+                    setCipherParams({ ...cipherParams, hmac_algorithm: e.target.value as CipherParams['hmac_algorithm'] })
+                  }
+                  options={[
+                    { value: 'HMAC_SHA512', label: 'SHA-512' },
+                    { value: 'HMAC_SHA256', label: 'SHA-256' },
+                    { value: 'HMAC_SHA1', label: 'SHA-1' },
+                  ]}
+                />
+              </label>
+              <button
+                onClick={handleApplyCipherParams}
+                disabled={JSON.stringify(cipherParams) === JSON.stringify(security.cipher_params)}
+                style={{
+                  padding: '0.5rem 1rem',
+                  borderRadius: '4px',
+                  border: '1px solid var(--text-color)',
+                  backgroundColor: 'var(--background-color)',
+                  color: 'var(--text-color)',
+                  cursor: 'pointer'
+                }}
+              >
+                Re-encrypt
+              </button>
+            </div>
+          )}
+          <button
+            onClick={() => handleSetEncryption(!security.encrypted)}
+            style={{
+              marginTop: '1rem',
+              padding: '0.5rem 1rem',
+              borderRadius: '4px',
+              border: '1px solid var(--text-color)',
+              backgroundColor: 'var(--background-color)',
+              color: 'var(--text-color)',
+              cursor: 'pointer'
+            }}
+          >
+            {security.encrypted ? 'Stop encrypting…' : 'Encrypt again'}
+          </button>
+          {encryptionStatus && <div style={{ color: 'var(--text-color)' }}>{encryptionStatus}</div>}
+        </div>
+      )}
+      <div style={{ marginBottom: '2rem' }}>
+        <h3 style={{ marginBottom: '1rem' }} className='font-semibold'>Audit log</h3>
+        <p style={{ marginBottom: '1rem' }}>
+          Every export, import, key change and full deletion is recorded here, and the record can't be edited.
+        </p>
+        {auditLog ? (
+          auditLog.length === 0 ? (
+            <p>Nothing recorded yet.</p>
+          ) : (
+            <ul>
+              {auditLog.map((event) => (
+                <li key={event.id}>
+                  {new Date(event.occurred_at).toLocaleString()} · {AUDIT_ACTIONS[event.action] ?? event.action}
+                  {event.detail && ` · ${event.detail}`}
+                </li>
+              ))}
+            </ul>
+          )
+        ) : (
+          <button
+            onClick={() => invoke<AuditEvent[]>('get_audit_log').then(setAuditLog).catch((err) => console.error('Audit log error:', err))}
+            style={{
+              padding: '0.5rem 1rem',
+              borderRadius: '4px',
+              border: '1px solid var(--text-color)',
+              backgroundColor: 'var(--background-color)',
+              color: 'var(--text-color)',
+              cursor: 'pointer'
+            }}
+          >
+            Show audit log
+          </button>
+        )}
+      </div>
+      <div style={{ marginBottom: '2rem' }}>
+        <h3 style={{ marginBottom: '1rem' }} className='font-semibold'>Recovery key</h3>
+        <p style={{ marginBottom: '1rem' }}>
+          Write the recovery key down somewhere safe. It opens your journal on another computer, or here if its
+          key is ever lost. Anyone with it and a copy of your journal can read it.
+        </p>
+        {recoveryKey ? (
+          <div style={{ display: 'flex', gap: '1rem', alignItems: 'center' }}>
+            <code style={{ fontSize: '1.1rem', userSelect: 'all' }}>{recoveryKey}</code>
+            <button
+              onClick={() => setRecoveryKey(null)}
+              style={{
+                padding: '0.5rem 1rem',
+                borderRadius: '4px',
+                border: '1px solid var(--text-color)',
+                backgroundColor: 'var(--background-color)',
+                color: 'var(--text-color)',
+                cursor: 'pointer'
+              }}
+            >
+              Hide
+            </button>
+          </div>
+        ) : (
+          <button
+            onClick={handleShowRecoveryKey}
+            style={{
+              padding: '0.5rem 1rem',
+              borderRadius: '4px',
+              border: '1px solid var(--text-color)',
+              backgroundColor: 'var(--background-color)',
+              color: 'var(--text-color)',
+              cursor: 'pointer'
+            }}
+          >
+            Show recovery key
+          </button>
+        )}
+        {recoveryStatus && (
+          <div style={{ color: 'var(--text-color)' }}>
+            {recoveryStatus}
+          </div>
+        )}
+      </div>
+      <div style={{ marginBottom: '2rem' }}>
+        <h3 style={{ marginBottom: '1rem', color: '#ff4444' }} className='font-semibold'>Erase journal</h3>
+        <p style={{ marginBottom: '1rem' }}>
+          Immediately and permanently erases this journal, its backups, settings and keys. Type ERASE MY JOURNAL
+          to confirm. Copies elsewhere, such as a Git mirror, a sync folder or exports, aren't touched.
+        </p>
+        <div style={{ display: 'flex', gap: '1rem' }}>
+          <input
+            value={wipeConfirmation}
+            onChange={(e) => setWipeConfirmation(e.target.value)}
+            placeholder='ERASE MY JOURNAL'
+            spellCheck={false}
+            style={{
+              padding: '0.5rem',
+              borderRadius: '4px',
+              border: '1px solid var(--text-color)',
+              backgroundColor: 'var(--background-color)',
+              color: 'var(--text-color)'
+            }}
+          />
+          <button
+            onClick={handleSecureWipe}
+            disabled={wipeConfirmation.trim() !== 'ERASE MY JOURNAL'}
+            style={{
+              padding: '0.5rem 1rem',
+              borderRadius: '4px',
+              border: '1px solid #ff4444',
+              backgroundColor: 'var(--background-color)',
+              color: '#ff4444',
+              cursor: 'pointer'
+            }}
+          >
+            Erase everything
+          </button>
+        </div>
+        {wipeStatus && (
+          <div style={{ color: '#ff4444' }}>
+            {wipeStatus}
+          </div>
+        )}
+      </div>
       <div style={{ marginTop: '2rem', fontSize: '0.9rem', color: 'var(--text-color)' }}>
         Version: {appVersion}
       </div>
@@ -1,13 +1,31 @@
 import React from "react";
 import ReactDOM from "react-dom/client";
 import App from "./App";
+import QuickCapture from "./components/QuickCapture";
+import EntryWindow from "./components/EntryWindow";
 import "./index.css"
 import * as Tooltip from '@radix-ui/react-tooltip';
+import { invoke } from "@tauri-apps/api/core";
+
+// Text copied from any window (Edit ▸ Copy or the shortcut) is handed to the
+// backend, which clears it from the clipboard later if that's turned on.
+document.addEventListener("copy", () => {
+  const text = window.getSelection()?.toString() ?? "";
+  if (text) invoke("clear_clipboard_later", { text }).catch(() => {});
+});
+
+function Root() {
+  const hash = window.location.hash;
+  if (hash === "#quick-capture") return <QuickCapture />;
+  const entry = hash.match(/^#entry\/(\d+)$/);
+  if (entry) return <EntryWindow id={Number(entry[1])} />;
+  return <App />;
+}
 
 ReactDOM.createRoot(document.getElementById("root") as HTMLElement).render(
   <React.StrictMode>
     <Tooltip.Provider>
-      <App />
+      <Root />
     </Tooltip.Provider>
   </React.StrictMode>,
 );